@@ -0,0 +1,145 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use magellanicus::renderer::Renderer;
+use sdl2::event::{Event, EventSender};
+use crate::camera_path::CameraPath;
+
+/// Name of the CSV file that results are appended to if `--benchmark-output` is not given.
+pub const DEFAULT_BENCHMARK_OUTPUT_FILE_NAME: &str = "benchmark.csv";
+
+/// Flies the cameras along a path for a set amount of time, recording how long each frame took.
+pub struct Benchmark {
+    path: CameraPath,
+    duration: Duration,
+    output: PathBuf,
+    scenario: String,
+
+    /// Used to close the window when the benchmark finishes.
+    quit: EventSender,
+
+    started: Option<Instant>,
+    last_frame: Option<Instant>,
+
+    /// Seconds between each drawn frame and the one before it.
+    frame_times: Vec<f32>
+}
+
+impl Benchmark {
+    pub fn new(path: CameraPath, duration: Duration, output: PathBuf, scenario: String, quit: EventSender) -> Benchmark {
+        Benchmark {
+            path,
+            duration,
+            output,
+            scenario,
+            quit,
+            started: None,
+            last_frame: None,
+            frame_times: Vec::new()
+        }
+    }
+
+    /// Move each viewport's camera to where it should be on the path.
+    ///
+    /// Viewports are spread out evenly along the path so they don't all draw the same thing.
+    pub fn move_cameras(&self, renderer: &mut Renderer) {
+        let elapsed = self.started.map(|s| s.elapsed().as_secs_f32()).unwrap_or(0.0);
+        let viewport_count = renderer.get_viewport_count();
+        for v in 0..viewport_count {
+            let offset = self.path.duration() * v as f32 / viewport_count as f32;
+            let (position, rotation) = self.path.sample(elapsed + offset);
+            let mut camera = renderer.get_camera_for_viewport(v);
+            camera.position = position;
+            camera.rotation = rotation;
+            renderer.set_camera_for_viewport(v, camera);
+        }
+    }
+
+    /// Record that a frame was drawn, returning `true` if the benchmark is done.
+    ///
+    /// Timing starts at the first frame, so loading and pipeline warm-up are not counted.
+    pub fn frame_drawn(&mut self) -> bool {
+        let now = Instant::now();
+        match self.last_frame {
+            Some(last_frame) => self.frame_times.push((now - last_frame).as_secs_f32()),
+            None => self.started = Some(now)
+        }
+        self.last_frame = Some(now);
+        self.started.is_some_and(|s| now - s >= self.duration)
+    }
+
+    /// Append the results to the output file and close the window.
+    pub fn finish(self, renderer: &Renderer) -> Result<PathBuf, String> {
+        let results = self.write_results(renderer);
+        let _ = self.quit.push_event(Event::Quit { timestamp: 0 });
+        results
+    }
+
+    fn write_results(&self, renderer: &Renderer) -> Result<PathBuf, String> {
+        if self.frame_times.is_empty() {
+            return Err("No frames were drawn".to_owned())
+        }
+
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_by(|a, b| b.total_cmp(a));
+
+        let total: f32 = sorted.iter().sum();
+        let average = total / sorted.len() as f32;
+        let low_1 = slowest_average(&sorted, 0.01);
+        let low_01 = slowest_average(&sorted, 0.001);
+
+        println!("Benchmark finished after {} frames", sorted.len());
+        println!("  Average: {:.02} FPS ({:.03} ms)", 1.0 / average, average * 1000.0);
+        println!("  1% low: {:.02} FPS ({:.03} ms)", 1.0 / low_1, low_1 * 1000.0);
+        println!("  0.1% low: {:.02} FPS ({:.03} ms)", 1.0 / low_01, low_01 * 1000.0);
+
+        let exists = self.output.is_file();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.output)
+            .map_err(|e| format!("Can't open {}: {e}", self.output.display()))?;
+
+        let parameters = renderer.get_parameters();
+        let mut text = String::new();
+        if !exists {
+            text += "scenario,resolution,viewports,msaa,render_scale,ssao,water_reflections,depth_of_field,motion_blur,taa,post_processing,vsync,seconds,frames,average_fps,average_ms,1%_low_fps,1%_low_ms,0.1%_low_fps,0.1%_low_ms\n";
+        }
+        text += &format!(
+            "{},{}x{},{},{},{},{},{},{},{},{},{},{},{:.03},{},{:.02},{:.03},{:.02},{:.03},{:.02},{:.03}\n",
+            self.scenario,
+            parameters.resolution.width,
+            parameters.resolution.height,
+            parameters.number_of_viewports,
+            parameters.msaa as u32,
+            parameters.render_scale,
+            parameters.ssao,
+            parameters.water_reflections,
+            parameters.depth_of_field,
+            parameters.motion_blur,
+            parameters.taa,
+            parameters.post_processing,
+            parameters.vsync,
+            total,
+            sorted.len(),
+            1.0 / average,
+            average * 1000.0,
+            1.0 / low_1,
+            low_1 * 1000.0,
+            1.0 / low_01,
+            low_01 * 1000.0
+        );
+
+        file.write_all(text.as_bytes()).map_err(|e| format!("Can't write to {}: {e}", self.output.display()))?;
+        Ok(self.output.clone())
+    }
+}
+
+/// Get the average of the slowest `fraction` of `frame_times`, which must be sorted slowest first.
+///
+/// At least one frame is always counted.
+fn slowest_average(frame_times: &[f32], fraction: f32) -> f32 {
+    let count = ((frame_times.len() as f32 * fraction).ceil() as usize).clamp(1, frame_times.len());
+    frame_times[..count].iter().sum::<f32>() / count as f32
+}
@@ -0,0 +1,226 @@
+use std::f32::consts::TAU;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use glam::Vec3;
+use magellanicus::renderer::Renderer;
+use ringhopper::definitions::Scenario;
+
+/// Minimum number of seconds between recorded keyframes, so high frame rates don't make huge files.
+const RECORD_INTERVAL: f32 = 1.0 / 30.0;
+
+/// First line of a saved camera path.
+const CAMERA_PATH_HEADER: &str = "# flycam camera path: time x y z forward_x forward_y forward_z";
+
+/// Where a camera is at a point in time along a [`CameraPath`].
+#[derive(Copy, Clone, Debug)]
+pub struct CameraKeyframe {
+    /// Seconds since the start of the path.
+    pub time: f32,
+    pub position: Vec3,
+    pub rotation: Vec3
+}
+
+/// A looping path for a camera to follow, interpolated linearly between keyframes.
+#[derive(Clone, Debug)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>
+}
+
+impl CameraPath {
+    /// Make a path from keyframes, which must be sorted by time and not be empty.
+    pub fn new(keyframes: Vec<CameraKeyframe>) -> CameraPath {
+        assert!(!keyframes.is_empty(), "camera paths need at least one keyframe");
+        CameraPath { keyframes }
+    }
+
+    /// Make a path that flies through each player starting location in order, taking `duration` for one lap.
+    ///
+    /// If there are not at least two distinct starting locations, the camera turns around in place instead.
+    pub fn through_starting_locations(scenario: &Scenario, duration: Duration) -> CameraPath {
+        let mut points: Vec<Vec3> = Vec::new();
+        for location in scenario.player_starting_locations.items.iter() {
+            let point = Vec3::new(location.position.x as f32, location.position.y as f32, location.position.z as f32 + 0.7);
+            if points.last().map(|p| p.distance(point) > 0.01).unwrap_or(true) {
+                points.push(point);
+            }
+        }
+        if points.len() > 1 && points[0].distance(points[points.len() - 1]) <= 0.01 {
+            points.pop();
+        }
+
+        let seconds = duration.as_secs_f32();
+        if points.len() < 2 {
+            let position = points.first().copied().unwrap_or(Vec3::ZERO);
+            return CameraPath::new((0..=4).map(|i| {
+                let angle = TAU * i as f32 / 4.0;
+                CameraKeyframe {
+                    time: seconds * i as f32 / 4.0,
+                    position,
+                    rotation: Vec3::new(angle.cos(), angle.sin(), 0.0)
+                }
+            }).collect());
+        }
+
+        // Go back to the start, and space keyframes out by distance so the camera moves at a constant speed
+        points.push(points[0]);
+        let length: f32 = points.windows(2).map(|w| w[0].distance(w[1])).sum();
+        let mut distance = 0.0;
+        let keyframes = points.windows(2).map(|w| {
+            let keyframe = CameraKeyframe {
+                time: seconds * distance / length,
+                position: w[0],
+                rotation: (w[1] - w[0]).normalize_or_zero()
+            };
+            distance += w[0].distance(w[1]);
+            keyframe
+        }).chain(std::iter::once(CameraKeyframe {
+            time: seconds,
+            position: points[0],
+            rotation: (points[1] - points[0]).normalize_or_zero()
+        })).collect();
+
+        CameraPath::new(keyframes)
+    }
+
+    /// Get the camera's position and rotation at `time` seconds, looping around after the last keyframe.
+    pub fn sample(&self, time: f32) -> (Vec3, Vec3) {
+        let first = self.keyframes[0];
+        let last = self.keyframes[self.keyframes.len() - 1];
+        let length = last.time - first.time;
+        if length <= 0.0 {
+            return (first.position, first.rotation)
+        }
+
+        let time = first.time + (time - first.time).rem_euclid(length);
+        let next = self.keyframes.partition_point(|k| k.time <= time).clamp(1, self.keyframes.len() - 1);
+        let a = self.keyframes[next - 1];
+        let b = self.keyframes[next];
+        let t = if b.time > a.time { ((time - a.time) / (b.time - a.time)).clamp(0.0, 1.0) } else { 1.0 };
+
+        let rotation = a.rotation.lerp(b.rotation, t).try_normalize().unwrap_or(b.rotation);
+        (a.position.lerp(b.position, t), rotation)
+    }
+
+    /// Get the time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    /// Load a path saved with [`CameraPath::save`].
+    pub fn load(path: &Path) -> Result<CameraPath, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Can't read {}: {e}", path.display()))?;
+
+        let mut keyframes: Vec<CameraKeyframe> = Vec::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let invalid = || format!("Invalid keyframe on line {} of {}", line_index + 1, path.display());
+            let values = line
+                .split_whitespace()
+                .map(|v| v.parse::<f32>().ok().filter(|v| v.is_finite()))
+                .collect::<Option<Vec<f32>>>()
+                .ok_or_else(invalid)?;
+            let [time, x, y, z, forward_x, forward_y, forward_z] = values[..] else {
+                return Err(invalid())
+            };
+            if keyframes.last().is_some_and(|k| k.time > time) {
+                return Err(format!("Keyframes are out of order on line {} of {}", line_index + 1, path.display()))
+            }
+
+            keyframes.push(CameraKeyframe {
+                time,
+                position: Vec3::new(x, y, z),
+                rotation: Vec3::new(forward_x, forward_y, forward_z).try_normalize().ok_or_else(invalid)?
+            });
+        }
+
+        if keyframes.is_empty() {
+            return Err(format!("{} has no keyframes", path.display()))
+        }
+        Ok(CameraPath::new(keyframes))
+    }
+
+    /// Save the path as text, with one keyframe per line.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut text = format!("{CAMERA_PATH_HEADER}\n");
+        for CameraKeyframe { time, position, rotation } in self.keyframes.iter() {
+            text += &format!(
+                "{time} {} {} {} {} {} {}\n",
+                position.x, position.y, position.z, rotation.x, rotation.y, rotation.z
+            );
+        }
+        std::fs::write(path, text).map_err(|e| format!("Can't write {}: {e}", path.display()))
+    }
+}
+
+/// Whether a viewport's camera is being recorded to a path or is following one.
+#[derive(Default)]
+pub enum CameraPathState {
+    #[default]
+    Idle,
+
+    Recording {
+        viewport: usize,
+        output: PathBuf,
+        started: Instant,
+        keyframes: Vec<CameraKeyframe>
+    },
+
+    Playing {
+        viewport: usize,
+        started: Instant,
+        path: CameraPath
+    }
+}
+
+impl CameraPathState {
+    /// Record the camera if recording, or move it along the path if playing.
+    ///
+    /// This should be called once per frame, after the camera has been moved by input.
+    pub fn update(&mut self, renderer: &mut Renderer) {
+        match self {
+            CameraPathState::Idle => (),
+            CameraPathState::Recording { viewport, started, keyframes, .. } => {
+                let time = started.elapsed().as_secs_f32();
+                if keyframes.last().is_some_and(|k| time - k.time < RECORD_INTERVAL) {
+                    return
+                }
+                let camera = renderer.get_camera_for_viewport(*viewport);
+                keyframes.push(CameraKeyframe {
+                    time,
+                    position: camera.position,
+                    rotation: camera.rotation
+                });
+            },
+            CameraPathState::Playing { viewport, started, path } => {
+                let (position, rotation) = path.sample(started.elapsed().as_secs_f32());
+                let mut camera = renderer.get_camera_for_viewport(*viewport);
+                camera.position = position;
+                camera.rotation = rotation;
+                renderer.set_camera_for_viewport(*viewport, camera);
+            }
+        }
+    }
+
+    /// Stop recording or playing, saving the recording if there was one.
+    ///
+    /// Returns what was stopped.
+    pub fn stop(&mut self) -> Result<String, String> {
+        match std::mem::take(self) {
+            CameraPathState::Idle => Err("nothing is being recorded or played".to_owned()),
+            CameraPathState::Recording { output, keyframes, .. } => {
+                if keyframes.is_empty() {
+                    return Err("no frames were recorded".to_owned())
+                }
+                let count = keyframes.len();
+                CameraPath::new(keyframes).save(&output)?;
+                Ok(format!("Saved {count} keyframe(s) to {}", output.display()))
+            },
+            CameraPathState::Playing { .. } => Ok("Stopped playing.".to_owned())
+        }
+    }
+}
+
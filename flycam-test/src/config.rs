@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use sdl2::keyboard::Keycode;
+use toml_edit::{Document, Item};
+
+/// Name of the config file that is loaded from next to the executable if `--config` is not given.
+pub const DEFAULT_CONFIG_FILE_NAME: &str = "flycam.toml";
+
+/// Settings loaded from a TOML file.
+///
+/// Everything is optional, and command line arguments take precedence.
+#[derive(Default)]
+pub struct Config {
+    pub tags: Vec<String>,
+    pub engine: Option<String>,
+    pub resolution: Option<String>,
+    pub vsync: Option<bool>,
+
+    /// Vertical field of view in degrees.
+    pub fov: Option<f32>,
+
+    pub mouse_sensitivity: Option<f32>,
+    pub stick_sensitivity: Option<f32>,
+    pub keybinds: Keybinds
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Can't read {}: {e}", path.display()))?;
+        let document = text.parse::<Document>().map_err(|e| format!("Can't parse {}: {e}", path.display()))?;
+
+        let mut config = Config::default();
+        for (key, item) in document.iter() {
+            let invalid = || format!("Invalid {key} in {}", path.display());
+            match key {
+                "tags" => {
+                    config.tags = item
+                        .as_array()
+                        .and_then(|a| a.iter().map(|t| t.as_str().map(str::to_owned)).collect())
+                        .ok_or_else(invalid)?
+                },
+                "engine" => config.engine = Some(item.as_str().ok_or_else(invalid)?.to_owned()),
+                "resolution" => config.resolution = Some(item.as_str().ok_or_else(invalid)?.to_owned()),
+                "vsync" => config.vsync = Some(item.as_bool().ok_or_else(invalid)?),
+                "fov" => config.fov = Some(as_f32(item).ok_or_else(invalid)?),
+                "mouse_sensitivity" => config.mouse_sensitivity = Some(as_f32(item).ok_or_else(invalid)?),
+                "stick_sensitivity" => config.stick_sensitivity = Some(as_f32(item).ok_or_else(invalid)?),
+                "keybinds" => config.keybinds = Keybinds::load(item).map_err(|e| format!("{e} in {}", path.display()))?,
+                _ => return Err(format!("Unknown setting {key} in {}", path.display()))
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn as_f32(item: &Item) -> Option<f32> {
+    item.as_float().or_else(|| item.as_integer().map(|i| i as f64)).map(|f| f as f32)
+}
+
+/// Actions that can be rebound, and the key each one is bound to by default.
+const ACTIONS: &[(&str, Keycode)] = &[
+    ("forward", Keycode::W),
+    ("back", Keycode::S),
+    ("left", Keycode::A),
+    ("right", Keycode::D),
+    ("up", Keycode::Space),
+    ("down", Keycode::LCtrl),
+    ("fast", Keycode::LShift),
+    ("slow", Keycode::LAlt),
+    ("adjust_fov", Keycode::V),
+    ("next_viewport", Keycode::Tab),
+    ("next_bsp", Keycode::PageUp),
+    ("previous_bsp", Keycode::PageDown),
+    ("lighting_mode", Keycode::Q),
+    ("fog", Keycode::F),
+    ("respawn", Keycode::R),
+    ("next_starting_location", Keycode::P),
+    ("walk", Keycode::N),
+    ("object_markers", Keycode::O),
+    ("debug_text", Keycode::F3),
+    ("overlay", Keycode::F4),
+    ("debug_render_mode", Keycode::F5),
+    ("collision_wireframe", Keycode::F8),
+    ("cluster_bounds", Keycode::F9),
+    ("fullscreen", Keycode::F11),
+    ("screenshot", Keycode::F12),
+    ("console", Keycode::Backquote),
+    ("quit", Keycode::Escape)
+];
+
+/// Keys that were bound to different actions than the default.
+///
+/// Input is handled in terms of the default keys, so pressed keys are translated to the default key of the action
+/// they are bound to.
+#[derive(Default)]
+pub struct Keybinds {
+    /// Maps a key to the default key of the action it is bound to.
+    bound: HashMap<Keycode, Keycode>,
+
+    /// Default keys whose actions were bound to something else.
+    unbound: HashSet<Keycode>
+}
+
+impl Keybinds {
+    /// Load keybinds from a table of action names to key names (e.g. `forward = "Up"`).
+    ///
+    /// Key names are as SDL names them (e.g. "Left Shift", "Page Up", "F5").
+    fn load(item: &Item) -> Result<Keybinds, String> {
+        let table = item.as_table_like().ok_or_else(|| "keybinds is not a table".to_owned())?;
+        let mut keybinds = Keybinds::default();
+
+        for (action, key) in table.iter() {
+            let Some((_, default)) = ACTIONS.iter().find(|(a, _)| *a == action) else {
+                return Err(format!("Unknown action {action}"))
+            };
+            let key_name = key.as_str().ok_or_else(|| format!("Key for {action} is not a string"))?;
+            let key = Keycode::from_name(key_name).ok_or_else(|| format!("Unknown key {key_name} for {action}"))?;
+            if keybinds.bound.insert(key, *default).is_some() {
+                return Err(format!("{key_name} is bound to more than one action"))
+            }
+            keybinds.unbound.insert(*default);
+        }
+
+        Ok(keybinds)
+    }
+
+    /// Get the default key of the action that `keycode` is bound to, if any.
+    pub fn translate(&self, keycode: Keycode) -> Option<Keycode> {
+        if let Some(default) = self.bound.get(&keycode) {
+            Some(*default)
+        }
+        else if self.unbound.contains(&keycode) {
+            None
+        }
+        else {
+            Some(keycode)
+        }
+    }
+}
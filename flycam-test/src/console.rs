@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use magellanicus::renderer::Renderer;
+
+/// Maximum number of lines of output kept in the console.
+const MAX_LINES: usize = 256;
+
+/// Number of lines of output shown while the console is open.
+const VISIBLE_LINES: usize = 16;
+
+/// Drop-down console for typing commands.
+///
+/// Input is handled on the main thread, and the console is drawn on the render thread.
+#[derive(Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    lines: VecDeque<String>,
+
+    /// Commands that were entered, oldest first.
+    history: Vec<String>,
+
+    /// Index into `history` of the command being recalled, if any.
+    history_index: Option<usize>
+}
+
+impl Console {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+    }
+
+    /// Append typed text to the input.
+    ///
+    /// The key that opens the console also types a character, so that character is ignored.
+    pub fn insert_text(&mut self, text: &str) {
+        self.input.extend(text.chars().filter(|c| *c != '`' && *c != '~' && !c.is_control()));
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Replace the input with the previous command in the history.
+    pub fn history_previous(&mut self) {
+        let index = match self.history_index {
+            Some(i) => i.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1
+        };
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// Replace the input with the next command in the history, or clear it if there is none.
+    pub fn history_next(&mut self) {
+        let Some(index) = self.history_index else {
+            return
+        };
+        if index + 1 < self.history.len() {
+            self.history_index = Some(index + 1);
+            self.input = self.history[index + 1].clone();
+        }
+        else {
+            self.history_index = None;
+            self.input.clear();
+        }
+    }
+
+    /// Take the input as a command, echoing it to the output, or `None` if the input is blank.
+    pub fn submit(&mut self) -> Option<String> {
+        let command = std::mem::take(&mut self.input).trim().to_owned();
+        self.history_index = None;
+        if command.is_empty() {
+            return None
+        }
+        self.print(&format!("> {command}"));
+        if self.history.last() != Some(&command) {
+            self.history.push(command.clone());
+        }
+        Some(command)
+    }
+
+    /// Print text to the output, one line at a time.
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            if self.lines.len() == MAX_LINES {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(line.to_owned());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Draw the most recent output and the input at the top-left corner for the next frame, if the console is open.
+    pub fn draw(&self, renderer: &mut Renderer) {
+        const SCALE: f32 = 2.0;
+
+        if !self.open {
+            return
+        }
+
+        let mut text = String::new();
+        for line in self.lines.iter().skip(self.lines.len().saturating_sub(VISIBLE_LINES)) {
+            text += line;
+            text += "\n";
+        }
+        renderer.debug_draw_text_2d([8.0, 8.0], &text, SCALE, [0.8, 0.8, 0.8, 1.0], Duration::ZERO);
+
+        // The built-in font is 8 pixels tall, and lines are spaced by its height
+        let lines_drawn = self.lines.len().min(VISIBLE_LINES) as f32;
+        renderer.debug_draw_text_2d(
+            [8.0, 8.0 + lines_drawn * 8.0 * SCALE],
+            &format!("] {}_", self.input),
+            SCALE,
+            [1.0, 0.85, 0.2, 1.0],
+            Duration::ZERO
+        );
+    }
+}
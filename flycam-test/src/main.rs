@@ -1,18 +1,34 @@
 #![allow(dead_code)]
 
-use magellanicus::renderer::{get_default_vertical_fov, AddBSPParameter, AddBSPParameterLightmapMaterial, AddBSPParameterLightmapSet, AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, AddFontParameter, AddFontParameterCharacter, AddShaderBasicShaderData, AddShaderData, AddShaderEnvironmentShaderData, AddShaderParameter, AddShaderTransparentChicagoShaderData, AddShaderTransparentChicagoShaderMap, AddSkyParameter, BSP3DNode, BSP3DNodeChild, BSP3DPlane, BSPCluster, BSPData, BSPLeaf, BSPPortal, BSPSubcluster, BitmapFormat, BitmapSprite, BitmapType, FogData, Renderer, RendererParameters, Resolution, ShaderType, MSAA};
-use std::collections::HashMap;
+mod benchmark;
+mod camera_path;
+mod config;
+mod console;
+mod resource_map;
+mod walk;
+
+use magellanicus::renderer::{get_default_vertical_fov, AddAssetsParameter, AddBSPParameter, AddBSPParameterLightmapMaterial, AddBSPParameterLightmapSet, AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, AddFontParameter, AddFontParameterCharacter, AddShaderBasicShaderData, AddShaderData, AddShaderEnvironmentShaderData, AddShaderParameter, AddShaderTransparentChicagoShaderData, AddShaderTransparentChicagoShaderMap, AddSkyParameter, BSP3DNode, BSP3DNodeChild, BSP3DPlane, BSPCluster, BSPData, BSPFogPlane, BSPLeaf, BSPPortal, BSPSubcluster, BitmapFormat, BitmapSprite, BitmapType, CapturedFrame, DebugRenderMode, DepthOfField, FallbackAsset, FogData, FovScaling, FrameStatus, LightingMode, PostProcessing, QualityPreset, Renderer, RendererParameters, Resolution, ScenarioDebugMarker, ScenarioDebugMarkerCategory, ShaderLod, ShaderType, SkyAmbientLight, SplitScreenBars, SkyLensFlare, SkyLensFlareReflection, SkyLight, MSAA};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufWriter;
 use std::mem::transmute;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard, Weak};
 use std::sync::mpsc::{channel, Receiver};
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use clap::Parser;
-use glam::Vec3;
+use benchmark::{Benchmark, DEFAULT_BENCHMARK_OUTPUT_FILE_NAME};
+use camera_path::{CameraPath, CameraPathState};
+use config::{Config, DEFAULT_CONFIG_FILE_NAME};
+use console::Console;
+use resource_map::{has_external_bitmap_data, resolve_external_bitmap_data, BitmapsResourceMap};
+use walk::{WalkGeometry, WalkState};
+use glam::{EulerRot, Mat3, Vec2, Vec3};
+use magellanicus::error::{DeviceError, Error};
 use magellanicus::vertex::{LightmapVertex, ModelTriangle, ModelVertex};
-use ringhopper::definitions::{Bitmap, BitmapDataFormat, BitmapDataType, Font, Globals, Scenario, ScenarioStructureBSP, ShaderEnvironment, ShaderModel, ShaderTransparentChicago, ShaderTransparentChicagoExtended, ShaderTransparentChicagoMap, ShaderTransparentGeneric, ShaderTransparentGlass, ShaderTransparentMeter, Sky, UnicodeStringList};
+use ringhopper::definitions::{Bitmap, BitmapDataFormat, BitmapDataType, Fog, Font, Globals, LensFlare, Scenario, ScenarioStructureBSP, ShaderEnvironment, ShaderModel, ShaderTransparentChicago, ShaderTransparentChicagoExtended, ShaderTransparentChicagoMap, ShaderTransparentGeneric, ShaderTransparentGlass, ShaderTransparentMeter, Sky, UnicodeStringList};
 use ringhopper::primitives::dynamic::DynamicTagDataArray;
 use ringhopper::primitives::engine::Engine;
 use ringhopper::primitives::primitive::{TagGroup, TagPath};
@@ -21,15 +37,19 @@ use ringhopper::tag::bitmap::MipmapTextureIterator;
 use ringhopper::tag::dependency::recursively_get_dependencies_for_map;
 use ringhopper::tag::scenario_structure_bsp::get_uncompressed_vertices_for_bsp_material;
 use ringhopper::tag::tree::{CachingTagTree, CachingTagTreeWriteStrategy, TagTree, VirtualTagsDirectory};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::video::FullscreenType;
 
 #[derive(Parser)]
 struct Arguments {
     /// Tags directory(s) to use, or a single cache file.
     ///
     /// For directories, you can use --tags multiple times to specify multiple directories in order of precedent.
-    #[arg(long = "tags", short = 't', default_value = "tags")]
+    ///
+    /// Defaults to "tags".
+    #[arg(long = "tags", short = 't')]
     pub tags: Vec<String>,
 
     /// Path to the scenario to use relative to the tags directory(s).
@@ -42,11 +62,34 @@ struct Arguments {
     /// Ignored/not needed when loading cache files, as this is derived from the map.
     pub engine: Option<String>,
 
+    /// Directory with the resource maps (bitmaps.map) that cache files take external bitmap data from.
+    ///
+    /// Defaults to the directory the cache file is in. Ignored when loading tags directories.
+    #[arg(long = "resource-maps")]
+    pub resource_maps: Option<String>,
+
     /// Sensitivity of the mouse.
     ///
-    /// Must be between 1 and 4.
-    #[arg(long = "mouse-sensitivity", short = 'm', default_value = "0.0015")]
-    pub mouse_sensitivity: f32,
+    /// Defaults to 0.0015.
+    #[arg(long = "mouse-sensitivity", short = 'm')]
+    pub mouse_sensitivity: Option<f32>,
+
+    /// How fast the right stick of a gamepad turns the camera, in radians per second.
+    ///
+    /// Defaults to 2.5.
+    #[arg(long = "stick-sensitivity")]
+    pub stick_sensitivity: Option<f32>,
+
+    /// Vertical field of view in degrees.
+    #[arg(long = "fov")]
+    pub fov: Option<f32>,
+
+    /// Path to a TOML config file.
+    ///
+    /// Defaults to flycam.toml next to the executable, if it exists. Command line arguments take precedence over
+    /// the config file.
+    #[arg(long = "config", short = 'c')]
+    pub config: Option<String>,
 
     /// Number of viewports to use.
     ///
@@ -54,6 +97,10 @@ struct Arguments {
     #[arg(long = "viewports", short = 'v', default_value = "1")]
     pub viewports: usize,
 
+    /// Draw the last viewport in a second window. Requires at least 2 viewports.
+    #[arg(long = "second-window")]
+    pub second_window: bool,
+
     /// MSAA setting to use.
     ///
     /// Note that your GPU may not support all options. If so, you will get an error.
@@ -73,7 +120,7 @@ struct Arguments {
     ///
     /// Most GPUs support up to 16x anisotropic filtering. This setting generally improves quality
     /// without significantly affecting performance, especially on discrete GPUs.
-    #[arg(long = "af", short = 'A')]
+    #[arg(long = "anisotropic-filtering", visible_alias = "af", short = 'A')]
     pub anisotropic_filtering: Option<f32>,
 
     /// Set the resolution of the renderer.
@@ -89,12 +136,81 @@ struct Arguments {
 
     /// Set the render scaling.
     #[arg(long = "render-scale", short = 'S', default_value = "1.0")]
-    pub render_scale: f32
+    pub render_scale: f32,
+
+    /// Enable screen-space ambient occlusion.
+    #[arg(long = "ssao")]
+    pub ssao: bool,
+
+    /// Reflect the scene in large water surfaces. Water is not drawn otherwise.
+    #[arg(long = "water-reflections")]
+    pub water_reflections: bool,
+
+    /// Allow blurring what is out of focus with the dof console command.
+    #[arg(long = "depth-of-field")]
+    pub depth_of_field: bool,
+
+    /// Allow blurring the scene as the camera moves with the motion_blur console command.
+    #[arg(long = "motion-blur")]
+    pub motion_blur: bool,
+
+    /// Smooth jagged edges by blending each frame with the previous ones (temporal anti-aliasing). This is much
+    /// cheaper than MSAA.
+    #[arg(long = "taa")]
+    pub taa: bool,
+
+    /// Allow changing the exposure, bloom, color overlay, and letterbox of each camera with the exposure, bloom,
+    /// overlay, and letterbox console commands.
+    #[arg(long = "post-processing")]
+    pub post_processing: bool,
+
+    /// Leave out this many of the largest mipmaps of each bitmap, halving texture resolution for each one. This helps
+    /// load large maps on GPUs with little video memory.
+    #[arg(long = "skip-mip-levels", default_value = "0")]
+    pub skip_mip_levels: u32,
+
+    /// Fly through the player starting locations for this many seconds with input disabled, then write frame time
+    /// statistics to a CSV file and exit.
+    #[arg(long = "benchmark")]
+    pub benchmark: Option<f32>,
+
+    /// CSV file to append benchmark results to.
+    ///
+    /// Defaults to benchmark.csv next to the executable.
+    #[arg(long = "benchmark-output")]
+    pub benchmark_output: Option<String>,
+
+    /// Camera path to fly along during the benchmark instead of the player starting locations.
+    ///
+    /// Paths can be recorded with the record console command.
+    #[arg(long = "benchmark-path")]
+    pub benchmark_path: Option<String>,
+
+    /// Render the map offscreen with the camera at "x,y,z,yaw,pitch", save a screenshot, and exit without opening a
+    /// window.
+    ///
+    /// Angles are in degrees, like the pos console command prints.
+    #[arg(long = "screenshot-at")]
+    pub screenshot_at: Option<String>,
+
+    /// Number of frames to draw before taking the --screenshot-at screenshot, so everything has finished loading.
+    #[arg(long = "screenshot-warmup-frames", default_value = "8")]
+    pub screenshot_warmup_frames: u32,
+
+    /// PNG file to write the --screenshot-at screenshot to.
+    ///
+    /// Defaults to a timestamped file next to the executable.
+    #[arg(long = "screenshot-output")]
+    pub screenshot_output: Option<String>
 
 }
 
 struct ScenarioData {
     tags: HashMap<TagPath, Box<dyn PrimaryTagStructDyn>>,
+
+    /// Tags directories the tags were loaded from, or `None` if they were loaded from a cache file.
+    tags_directories: Option<Vec<String>>,
+
     scenario_path: TagPath,
     scenario_tag: Scenario,
     engine: &'static Engine,
@@ -106,39 +222,84 @@ fn main() -> Result<(), String> {
         tags,
         scenario,
         engine,
+        resource_maps,
         mut viewports,
+        second_window,
         mouse_sensitivity,
+        stick_sensitivity,
+        fov,
+        config,
         msaa,
         vsync,
         resolution,
         fullscreen,
-        render_scale
+        render_scale,
+        ssao,
+        water_reflections,
+        depth_of_field,
+        motion_blur,
+        taa,
+        post_processing,
+        skip_mip_levels,
+        benchmark,
+        benchmark_output,
+        benchmark_path,
+        screenshot_at,
+        screenshot_warmup_frames,
+        screenshot_output
     } = Arguments::parse();
 
+    let config = match config {
+        Some(path) => Config::load(path.as_ref())?,
+        None => {
+            let default_path = std::env::current_exe()
+                .ok()
+                .and_then(|e| Some(e.parent()?.join(DEFAULT_CONFIG_FILE_NAME)))
+                .filter(|p| p.is_file());
+            match default_path {
+                Some(path) => Config::load(&path)?,
+                None => Config::default()
+            }
+        }
+    };
+
+    let tags = if !tags.is_empty() {
+        tags
+    }
+    else if !config.tags.is_empty() {
+        config.tags.clone()
+    }
+    else {
+        vec!["tags".to_owned()]
+    };
+    let engine = engine.or_else(|| config.engine.clone());
+    let resolution = resolution.or_else(|| config.resolution.clone());
+    let vsync = vsync || config.vsync.unwrap_or(false);
+    let mouse_sensitivity = mouse_sensitivity.or(config.mouse_sensitivity).unwrap_or(0.0015);
+    let stick_sensitivity = stick_sensitivity.or(config.stick_sensitivity).unwrap_or(2.5);
+    let fov = fov.or(config.fov);
+
+    if let Some(fov) = fov {
+        if !(1.0..179.0).contains(&fov) {
+            return Err(format!("invalid FoV {fov}; must be between 1 and 179 degrees"));
+        }
+    }
+
     if render_scale <= 0.0 || render_scale > 65535.0 {
         return Err(format!("invalid render scale {render_scale}"));
     }
 
-    let sdl = sdl2::init()?;
-    let mut events = sdl.event_pump()?;
-    let video = sdl.video()?;
-    let mouse = sdl.mouse();
+    let screenshot_camera = match screenshot_at {
+        Some(at) => Some(parse_camera_location(&at)?),
+        None => None
+    };
 
-    let resolution = match resolution {
-        Some(resolution) => parse_resolution(resolution)?,
-        None => {
-            let res = video.current_display_mode(0)
-                .map_err(|e| format!("Can't determine resolution: {e:?}"))?;
-            if fullscreen {
-                Resolution { width: res.w as u32, height: res.h as u32 }
-            }
-            else {
-                Resolution {
-                    width: ((res.w as u32) * 3 / 4).clamp(4, 1280),
-                    height: ((res.h as u32) * 3 / 4).clamp(3, 960)
-                }
-            }
-        }
+    let benchmark_duration = match benchmark {
+        Some(seconds) => Some(Duration::try_from_secs_f32(seconds)
+            .ok()
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| format!("invalid benchmark length {seconds}"))?),
+        None => None
     };
 
     if !(1..=4).contains(&viewports) {
@@ -168,50 +329,83 @@ fn main() -> Result<(), String> {
         }
     };
 
-    let first_tags_dir: &Path = tags.get(0).unwrap().as_ref();
+    let resource_maps = resource_maps.map(PathBuf::from);
+    let scenario_data = load_scenario_data(&tags, scenario, engine, resource_maps.as_deref())?;
 
-    let (scenario_path, engine, dependencies) = if tags.len() == 1 && first_tags_dir.is_file() {
-        if engine.is_some() {
-            eprintln!("--engine is ignored when loading cache files");
-        }
-        if scenario.is_some() {
-            eprintln!("scenario path is ignored when loading cache files");
-        }
-        load_tags_from_cache(first_tags_dir)?
+    if let Some(camera) = screenshot_camera {
+        let renderer_parameters = RendererParameters::builder()
+            .resolution(match resolution {
+                Some(resolution) => parse_resolution(resolution)?,
+                None => DEFAULT_HEADLESS_RESOLUTION
+            })
+            .number_of_viewports(1)
+            .anisotropic_filtering(anisotropic_filtering)
+            .msaa(msaa)
+            .render_scale(render_scale)
+            .ssao(ssao)
+            .water_reflections(water_reflections)
+            .depth_of_field(depth_of_field)
+            .motion_blur(motion_blur)
+            .taa(taa)
+            .post_processing(post_processing)
+            .skipped_mip_levels(skip_mip_levels)
+            .clamp_to_device(true)
+            .build()
+            .map_err(|e| format!("Invalid renderer parameters: {e}"))?;
+        let output = screenshot_output.map(PathBuf::from);
+        let path = take_headless_screenshot(scenario_data, resource_maps, renderer_parameters, camera, fov, screenshot_warmup_frames, output)?;
+        println!("Saved screenshot to {}", path.display());
+        return Ok(())
     }
-    else {
-        let Some(scenario) = scenario else {
-            eprintln!("No tag path specified when --tags does not point to a cache file.");
-            return Err("no tag path specified".to_owned())
-        };
-        let scenario_path = TagPath::from_path(&scenario)
-            .map_err(|e| format!("Invalid tag path {scenario}: {e}"))?;
-
-        let (engine, dependencies) = load_tags_from_dir(&tags, &scenario_path, engine)?;
-        (scenario_path, engine, dependencies)
-    };
-    let window_title = format!("Magellanicus Flycam Test {scenario_path}");
 
-    let scenario_tag = dependencies
-        .get(&scenario_path)
-        .unwrap()
-        .get_ref::<Scenario>()
-        .expect("scenario wasn't scenario???")
-        .to_owned();
+    let sdl = sdl2::init()?;
+    let mut events = sdl.event_pump()?;
+    let video = sdl.video()?;
+    let mouse = sdl.mouse();
+    let game_controller = sdl.game_controller()?;
 
-    let current_bsp_count = scenario_tag.structure_bsps.items.len();
-    if current_bsp_count == 0 {
-        return Err("No BSPs in the scenario.".to_owned());
-    }
+    let resolution = match resolution {
+        Some(resolution) => parse_resolution(resolution)?,
+        None => {
+            let res = video.current_display_mode(0)
+                .map_err(|e| format!("Can't determine resolution: {e:?}"))?;
+            if fullscreen {
+                Resolution { width: res.w as u32, height: res.h as u32 }
+            }
+            else {
+                Resolution {
+                    width: ((res.w as u32) * 3 / 4).clamp(4, 1280),
+                    height: ((res.h as u32) * 3 / 4).clamp(3, 960)
+                }
+            }
+        }
+    };
 
-    let scenario_data = ScenarioData {
-        tags: dependencies,
-        scenario_path,
-        scenario_tag,
-        engine,
+    let mut window_title = format!("Magellanicus Flycam Test {}", scenario_data.scenario_path);
+    let mut current_bsp_count = scenario_data.scenario_tag.structure_bsps.items.len();
+    let first_bsp_path = scenario_data.scenario_tag.structure_bsps.items[0].structure_bsp.path().map(|t| t.to_string());
+
+    let benchmark = match benchmark_duration {
+        Some(duration) => {
+            let output = match benchmark_output {
+                Some(output) => PathBuf::from(output),
+                None => std::env::current_exe()
+                    .map_err(|e| e.to_string())?
+                    .parent()
+                    .ok_or_else(|| "the executable has no parent directory".to_owned())?
+                    .join(DEFAULT_BENCHMARK_OUTPUT_FILE_NAME)
+            };
+            let path = match benchmark_path {
+                Some(path) => CameraPath::load(path.as_ref())?,
+                None => CameraPath::through_starting_locations(&scenario_data.scenario_tag, duration)
+            };
+            Some(Benchmark::new(path, duration, output, scenario_data.scenario_path.to_string(), sdl.event()?.event_sender()))
+        },
+        None => None
     };
+    let benchmarking = benchmark.is_some();
 
-    let mut window_builder = video.window(&window_title, resolution.width, resolution.height);
+    let mut window_builder = video.window(&bsp_window_title(&window_title, first_bsp_path.as_deref()), resolution.width, resolution.height);
 
     window_builder
         .vulkan()
@@ -221,42 +415,100 @@ fn main() -> Result<(), String> {
     if fullscreen {
         window_builder.fullscreen();
     }
+    else {
+        window_builder.resizable();
+    }
 
     let mut window = window_builder
         .build()
         .unwrap();
 
-    let renderer =
+    let renderer_parameters = RendererParameters::builder()
+        .resolution(resolution)
+        .number_of_viewports(viewports)
+        .vsync(vsync)
+        .anisotropic_filtering(anisotropic_filtering)
+        .msaa(msaa)
+        .render_scale(render_scale)
+        .ssao(ssao)
+        .water_reflections(water_reflections)
+        .depth_of_field(depth_of_field)
+        .motion_blur(motion_blur)
+        .taa(taa)
+        .post_processing(post_processing)
+        .skipped_mip_levels(skip_mip_levels)
+        .clamp_to_device(true)
+        .build()
+        .map_err(|e| format!("Invalid renderer parameters: {e}"))?;
+
+    let mut renderer =
         unsafe {
-            Renderer::new(&window, RendererParameters {
-                resolution,
-                number_of_viewports: viewports,
-                vsync,
-                anisotropic_filtering,
-                msaa,
-                render_scale
-            })
+            Renderer::new(&window, renderer_parameters)
         }.unwrap();
 
+    // The second window is kept along with its index in the renderer
+    let mut second_window = match second_window {
+        true if viewports < 2 => return Err("--second-window requires at least 2 viewports".to_owned()),
+        true => {
+            let second_window = video
+                .window(&format!("{window_title} (viewport {viewports})"), resolution.width, resolution.height)
+                .vulkan()
+                .metal_view()
+                .resizable()
+                .build()
+                .map_err(|e| format!("Can't create the second window: {e}"))?;
+            let (width, height) = second_window.vulkan_drawable_size();
+            let index = unsafe {
+                renderer.add_window(&second_window, Resolution { width, height })
+            }.map_err(|e| format!("Can't draw to the second window: {e}"))?;
+            renderer.set_viewport_window(viewports - 1, index).map_err(|e| e.to_string())?;
+            Some((second_window, index))
+        },
+        false => None
+    };
+
+    let effective_parameters = renderer.get_parameters();
+    if effective_parameters.msaa != msaa {
+        eprintln!("{}x MSAA is unsupported by your device; using {}x instead", msaa as u32, effective_parameters.msaa as u32);
+    }
+    if effective_parameters.anisotropic_filtering != anisotropic_filtering {
+        eprintln!("{:?}x AF is unsupported by your device; using {:?}x instead", anisotropic_filtering, effective_parameters.anisotropic_filtering);
+    }
+
     let mut handler = FlycamTestHandler {
         renderer: Some(Arc::new(Mutex::new(renderer))),
         scenario_data,
+        resource_maps,
         viewports,
-        camera_velocity: Arc::new([
-            [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)],
-            [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)],
-            [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)],
-            [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)],
-        ]),
+        camera_velocity: Arc::new(std::array::from_fn(|_| std::array::from_fn(|_| AtomicU32::new(0)))),
         pause_rendering_flag: Arc::new(AtomicBool::new(false)),
+        show_overlay: Arc::new(AtomicBool::new(false)),
+        console: Arc::new(Mutex::new(Console::default())),
+        debug_geometry: Arc::new(Mutex::new(PersistentDebugGeometry::default())),
+        camera_path: Arc::new(Mutex::new(CameraPathState::default())),
+        walk: Arc::new(Mutex::new(WalkState::default())),
     };
 
     let (camera_send, camera_receive) = channel::<(f32, f32, usize)>();
-    handler.initialize_and_start(camera_receive)?;
+    handler.initialize_and_start(camera_receive, benchmark)?;
+
+    if let Some(fov) = fov {
+        let mut lock = handler.lock_renderer();
+        for v in 0..lock.renderer.get_viewport_count() {
+            let mut camera = lock.renderer.get_camera_for_viewport(v);
+            camera.fov = fov.to_radians();
+            lock.renderer.set_camera_for_viewport(v, camera);
+        }
+    }
 
     window.show();
-    mouse.capture(true);
-    mouse.set_relative_mouse_mode(true);
+
+    // Size to go back to when leaving fullscreen
+    let mut windowed_size = window.size();
+    if !benchmarking {
+        mouse.capture(true);
+        mouse.set_relative_mouse_mode(true);
+    }
 
     let mut f3 = false;
     let mut w = false;
@@ -267,8 +519,16 @@ fn main() -> Result<(), String> {
     let mut space = false;
     let mut ctrl = false;
     let mut shift = false;
+    let mut alt = false;
     let mut viewport_mod = 0;
     let mut current_bsp_index = 0usize;
+    let mut current_starting_location_index = 0usize;
+    let mut collision_wireframe = false;
+    let mut cluster_bounds = false;
+
+    // Gamepads are closed when dropped, so keep them around while they're connected
+    let mut gamepads: Vec<GameController> = Vec::new();
+    let mut gamepad = GamepadState::default();
 
     fn make_thing(w: bool, a: bool, s: bool, d: bool, ctrl: bool, space: bool) -> [f32; 3] {
         let mut forward = 1.0 * (w as u32 as f32) - 1.0 * (s as u32 as f32);
@@ -284,20 +544,193 @@ fn main() -> Result<(), String> {
     }
 
     let shift_speedup = 4.0;
+    let alt_slowdown = 4.0;
 
     loop {
         let Some(event) = events.wait_event_timeout(1000) else {
             continue;
         };
+
+        // The console takes all keyboard input while it's open, before keys are translated by the keybinds
+        if handler.console.lock().unwrap().is_open() {
+            match event {
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    let mut console = handler.console.lock().unwrap();
+                    if keycode == Keycode::Escape || config.keybinds.translate(keycode) == Some(Keycode::Backquote) {
+                        console.set_open(false);
+                        video.text_input().stop();
+                    }
+                    else if keycode == Keycode::Return || keycode == Keycode::KpEnter {
+                        let Some(command) = console.submit() else {
+                            continue
+                        };
+                        if command == "clear" {
+                            console.clear();
+                            continue
+                        }
+
+                        // Don't hold the console while waiting for the renderer, since the render thread locks both
+                        drop(console);
+
+                        if command == "map" || command.starts_with("map ") {
+                            let path = command[3..].trim();
+                            let result = if path.is_empty() {
+                                Err("usage: map <scenario tag path|cache file>".to_owned())
+                            }
+                            else if Path::new(path).is_file() {
+                                handler.load_map(vec![path.to_owned()], None)
+                            }
+                            else {
+                                match handler.scenario_data.tags_directories.clone() {
+                                    Some(tags) => handler.load_map(tags, Some(path.to_owned())),
+                                    None => Err(format!("{path} is not a cache file, and there are no tags directories to find it in")),
+                                }
+                            };
+
+                            let mut console = handler.console.lock().unwrap();
+                            match result {
+                                Ok(()) => {
+                                    current_bsp_index = 0;
+                                    current_bsp_count = handler.scenario_data.scenario_tag.structure_bsps.items.len();
+                                    current_starting_location_index = 0;
+                                    handler.update_debug_geometry(current_bsp_index, collision_wireframe, cluster_bounds);
+
+                                    // Resetting the renderer unloaded the debug font
+                                    f3 = false;
+
+                                    window_title = format!("Magellanicus Flycam Test {}", handler.scenario_data.scenario_path);
+                                    if let Err(e) = window.set_title(&bsp_window_title(&window_title, handler.bsp_path(0).as_deref())) {
+                                        eprintln!("Failed to set the window title: {e}");
+                                    }
+                                    console.print(&format!("Loaded {}", handler.scenario_data.scenario_path));
+                                },
+                                Err(e) => console.print(&format!("Error: {e}"))
+                            }
+                            continue;
+                        }
+                        let previous_bsp_index = current_bsp_index;
+                        let output = handler.run_console_command(&command, viewport_mod, &mut current_bsp_index);
+                        handler.console.lock().unwrap().print(&output.unwrap_or_else(|e| format!("Error: {e}")));
+
+                        if current_bsp_index != previous_bsp_index {
+                            handler.update_debug_geometry(current_bsp_index, collision_wireframe, cluster_bounds);
+                            handler.update_walk_geometry(current_bsp_index);
+                            let path = handler.bsp_path(current_bsp_index);
+                            if let Err(e) = window.set_title(&bsp_window_title(&window_title, path.as_deref())) {
+                                eprintln!("Failed to set the window title: {e}");
+                            }
+                        }
+                    }
+                    else if keycode == Keycode::Backspace {
+                        console.backspace();
+                    }
+                    else if keycode == Keycode::Up {
+                        console.history_previous();
+                    }
+                    else if keycode == Keycode::Down {
+                        console.history_next();
+                    }
+                    continue;
+                },
+                Event::KeyUp { .. } => continue,
+                Event::TextInput { ref text, .. } => {
+                    handler.console.lock().unwrap().insert_text(text);
+                    continue;
+                },
+                _ => ()
+            }
+        }
+
+        // Gamepad buttons do the same thing as their keyboard counterparts, and rebound keys do the same thing as the
+        // keys they replace
+        let event = match event {
+            Event::KeyDown { timestamp, window_id, keycode, scancode, keymod, repeat } => {
+                let keycode = keycode.and_then(|k| config.keybinds.translate(k));
+                Event::KeyDown { timestamp, window_id, keycode, scancode, keymod, repeat }
+            },
+            Event::KeyUp { timestamp, window_id, keycode, scancode, keymod, repeat } => {
+                let keycode = keycode.and_then(|k| config.keybinds.translate(k));
+                Event::KeyUp { timestamp, window_id, keycode, scancode, keymod, repeat }
+            },
+            Event::ControllerButtonDown { timestamp, button, .. } => {
+                let Some(keycode) = gamepad_button_keycode(button) else {
+                    continue
+                };
+                Event::KeyDown { timestamp, window_id: 0, keycode: Some(keycode), scancode: None, keymod: Mod::NOMOD, repeat: false }
+            },
+            event => event
+        };
+
+        // Only allow quitting early so input doesn't affect benchmark results
+        if benchmarking {
+            match event {
+                Event::Quit { .. } | Event::Window { .. } => (),
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => (),
+                _ => continue
+            }
+        }
+
         match event {
             Event::Quit { .. } => {
                 println!("EXITING!");
                 break;
             }
+            // Moving the window to a display with a different scale factor changes the drawable size without
+            // necessarily changing the window size
+            Event::Window { window_id, win_event: WindowEvent::SizeChanged(..) | WindowEvent::Resized(..) | WindowEvent::DisplayChanged(..), .. } => {
+                let mut lock = handler.lock_renderer();
+                let result = match second_window.as_ref().filter(|(w, _)| w.id() == window_id) {
+                    Some((second_window, index)) => {
+                        let (width, height) = second_window.vulkan_drawable_size();
+                        lock.renderer.set_window_resolution(*index, Resolution { width, height })
+                    },
+                    None => {
+                        let (width, height) = window.vulkan_drawable_size();
+                        lock.renderer.set_resolution(Resolution { width, height })
+                    }
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to resize: {e}");
+                }
+            }
+            // SDL only quits once every window is closed
+            Event::Window { window_id, win_event: WindowEvent::Close, .. } if second_window.is_some() => {
+                // The viewport goes back to the main window, which has to be done before the second window is gone
+                match second_window.take_if(|(w, _)| w.id() == window_id) {
+                    Some((second_window, index)) => {
+                        if let Err(e) = handler.lock_renderer().renderer.remove_window(index) {
+                            eprintln!("Failed to remove the second window: {e}");
+                        }
+                        drop(second_window);
+                    },
+                    None => break
+                }
+            }
+            Event::ControllerDeviceAdded { which, .. } => {
+                match game_controller.open(which) {
+                    Ok(g) => {
+                        println!("Connected gamepad {}", g.name());
+                        gamepads.push(g);
+                    },
+                    Err(e) => eprintln!("Failed to open gamepad #{which}: {e}")
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                gamepads.retain(|g| g.instance_id() != which);
+                gamepad = GamepadState::default();
+                set_move_velocity(&handler.camera_velocity[viewport_mod], make_thing(w,a,s,d,ctrl,space), &gamepad, shift_speedup);
+            }
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                gamepad.set_axis(axis, value);
+                set_move_velocity(&handler.camera_velocity[viewport_mod], make_thing(w,a,s,d,ctrl,space), &gamepad, shift_speedup);
+                let [yaw, pitch] = gamepad.right_stick;
+                handler.camera_velocity[viewport_mod][4].swap((yaw * stick_sensitivity).to_bits(), Ordering::Relaxed);
+                handler.camera_velocity[viewport_mod][5].swap((pitch * stick_sensitivity).to_bits(), Ordering::Relaxed);
+            }
             Event::MouseMotion { xrel, yrel, .. } => {
                 let _ = camera_send.send((xrel as f32 * mouse_sensitivity, yrel as f32 * mouse_sensitivity, viewport_mod));
             }
-            Event::KeyDown { keycode, repeat, .. } => {
+            Event::KeyDown { keycode, keymod, repeat, .. } => {
                 if repeat == true {
                     continue
                 }
@@ -306,6 +739,48 @@ fn main() -> Result<(), String> {
                     break;
                 }
 
+                if keycode == Some(Keycode::F11) || (keycode == Some(Keycode::Return) && keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)) {
+                    let result = if window.fullscreen_state() == FullscreenType::Off {
+                        windowed_size = window.size();
+                        window.set_fullscreen(FullscreenType::Desktop)
+                    }
+                    else {
+                        window.set_fullscreen(FullscreenType::Off).and_then(|_| {
+                            let (width, height) = windowed_size;
+                            window.set_size(width, height).map_err(|e| e.to_string())
+                        })
+                    };
+                    if let Err(e) = result {
+                        eprintln!("Failed to toggle fullscreen: {e}");
+                        continue;
+                    }
+
+                    let (width, height) = window.vulkan_drawable_size();
+                    let mut lock = handler.lock_renderer();
+                    if let Err(e) = lock.renderer.set_resolution(Resolution { width, height }) {
+                        eprintln!("Failed to resize: {e}");
+                    }
+                    continue;
+                }
+
+                if keycode == Some(Keycode::Backquote) {
+                    // Keys released while the console is open are never seen, so let go of everything now
+                    if shift {
+                        let reduced = f32::from_bits(handler.camera_velocity[viewport_mod][3].load(Ordering::Relaxed)) - shift_speedup;
+                        handler.camera_velocity[viewport_mod][3].swap(reduced.to_bits(), Ordering::Relaxed);
+                    }
+                    if alt {
+                        let increased = f32::from_bits(handler.camera_velocity[viewport_mod][3].load(Ordering::Relaxed)) + alt_slowdown;
+                        handler.camera_velocity[viewport_mod][3].swap(increased.to_bits(), Ordering::Relaxed);
+                    }
+                    (w, a, s, d, v, ctrl, space, shift, alt) = (false, false, false, false, false, false, false, false, false);
+                    set_move_velocity(&handler.camera_velocity[viewport_mod], make_thing(w,a,s,d,ctrl,space), &gamepad, shift_speedup);
+
+                    handler.console.lock().unwrap().set_open(true);
+                    video.text_input().start();
+                    continue;
+                }
+
                 if keycode == Some(Keycode::Tab) {
                     viewport_mod = (viewport_mod + 1) % viewports;
                     continue;
@@ -322,6 +797,22 @@ fn main() -> Result<(), String> {
                     locked.renderer.set_debug_font(f3.then_some(font_terminal.as_str())).expect("F3 fail");
                 }
 
+                if keycode == Some(Keycode::F12) {
+                    // Capture every viewport at once, since split-screen viewports are all in the same frame
+                    let frame = handler.lock_renderer().renderer.capture_frame();
+                    match frame.map_err(|e| e.to_string()).and_then(save_screenshot) {
+                        Ok(path) => println!("Saved screenshot to {}", path.display()),
+                        Err(e) => eprintln!("Failed to take a screenshot: {e}")
+                    }
+                    continue;
+                }
+
+                if keycode == Some(Keycode::F4) {
+                    let shown = !handler.show_overlay.load(Ordering::Relaxed);
+                    handler.show_overlay.swap(shown, Ordering::Relaxed);
+                    continue;
+                }
+
                 if keycode == Some(Keycode::PageUp) || keycode == Some(Keycode::PageDown) {
                     if current_bsp_count == 1 {
                         println!("Can't switch BSPs because there is only one.");
@@ -335,25 +826,75 @@ fn main() -> Result<(), String> {
                         current_bsp_index = current_bsp_index.checked_sub(1).unwrap_or(current_bsp_count - 1);
                     }
 
-                    let path = handler
-                        .scenario_data
-                        .scenario_tag
-                        .structure_bsps
-                        .items[current_bsp_index]
-                        .structure_bsp
-                        .path()
-                        .map(|t| t.to_string());
-
+                    let path = handler.bsp_path(current_bsp_index);
                     let path = path.as_ref().map(|p| p.as_str());
                     handler.lock_renderer().renderer.set_current_bsp(path).unwrap();
+                    handler.update_debug_geometry(current_bsp_index, collision_wireframe, cluster_bounds);
+                    handler.update_walk_geometry(current_bsp_index);
+                    if let Err(e) = window.set_title(&bsp_window_title(&window_title, path)) {
+                        eprintln!("Failed to set the window title: {e}");
+                    }
                     continue;
                 }
 
                 if keycode == Some(Keycode::Q) {
                     let mut renderer = handler.lock_renderer();
                     let mut camera = renderer.renderer.get_camera_for_viewport(viewport_mod);
-                    camera.lightmaps = !camera.lightmaps;
+                    camera.lighting_mode = match camera.lighting_mode {
+                        LightingMode::TexturedLightmapped => LightingMode::Fullbright,
+                        LightingMode::Fullbright => LightingMode::LightmapOnly,
+                        LightingMode::LightmapOnly => LightingMode::Unlit,
+                        LightingMode::Unlit => LightingMode::TexturedLightmapped
+                    };
                     renderer.renderer.set_camera_for_viewport(viewport_mod, camera);
+
+                    let parameters = renderer.renderer.get_parameters();
+                    let bottom = parameters.resolution.height as f32 * parameters.render_scale - 24.0;
+                    renderer.renderer.debug_draw_text_2d(
+                        [8.0, bottom],
+                        &format!("Viewport #{viewport_mod} lighting: {:?}", camera.lighting_mode),
+                        2.0,
+                        [1.0, 1.0, 1.0, 1.0],
+                        Duration::from_secs(2)
+                    );
+                    continue;
+                }
+
+                if keycode == Some(Keycode::F5) {
+                    let mut renderer = handler.lock_renderer();
+                    let mode = match renderer.renderer.get_debug_render_mode(viewport_mod) {
+                        DebugRenderMode::None => DebugRenderMode::WorldSpaceNormals,
+                        DebugRenderMode::WorldSpaceNormals => DebugRenderMode::TangentSpaceNormals,
+                        DebugRenderMode::TangentSpaceNormals => DebugRenderMode::Tangents,
+                        DebugRenderMode::Tangents => DebugRenderMode::Binormals,
+                        DebugRenderMode::Binormals => DebugRenderMode::UVCheckerboard,
+                        DebugRenderMode::UVCheckerboard => DebugRenderMode::TexelDensity,
+                        DebugRenderMode::TexelDensity => DebugRenderMode::Overdraw,
+                        DebugRenderMode::Overdraw => DebugRenderMode::None
+                    };
+                    renderer.renderer.set_debug_render_mode(viewport_mod, mode);
+
+                    let parameters = renderer.renderer.get_parameters();
+                    let bottom = parameters.resolution.height as f32 * parameters.render_scale - 24.0;
+                    renderer.renderer.debug_draw_text_2d(
+                        [8.0, bottom],
+                        &format!("Viewport #{viewport_mod} debug mode: {mode:?}"),
+                        2.0,
+                        [1.0, 1.0, 1.0, 1.0],
+                        Duration::from_secs(2)
+                    );
+                    continue;
+                }
+
+                if keycode == Some(Keycode::F8) {
+                    collision_wireframe = !collision_wireframe;
+                    handler.update_debug_geometry(current_bsp_index, collision_wireframe, cluster_bounds);
+                    continue;
+                }
+
+                if keycode == Some(Keycode::F9) {
+                    cluster_bounds = !cluster_bounds;
+                    handler.update_debug_geometry(current_bsp_index, collision_wireframe, cluster_bounds);
                     continue;
                 }
 
@@ -365,6 +906,49 @@ fn main() -> Result<(), String> {
                     continue;
                 }
 
+                if keycode == Some(Keycode::N) {
+                    let walking = {
+                        let mut walk = handler.walk.lock().unwrap();
+                        let walking = !walk.is_walking(viewport_mod);
+                        walk.set_walking(viewport_mod, walking);
+                        walking
+                    };
+                    handler.update_walk_geometry(current_bsp_index);
+
+                    let mut renderer = handler.lock_renderer();
+                    let parameters = renderer.renderer.get_parameters();
+                    let bottom = parameters.resolution.height as f32 * parameters.render_scale - 24.0;
+                    renderer.renderer.debug_draw_text_2d(
+                        [8.0, bottom],
+                        &format!("Viewport #{viewport_mod}: {}", if walking { "walking" } else { "flying" }),
+                        2.0,
+                        [1.0, 1.0, 1.0, 1.0],
+                        Duration::from_secs(2)
+                    );
+                    continue;
+                }
+
+                if keycode == Some(Keycode::O) {
+                    let mut renderer = handler.lock_renderer();
+                    let visible = !renderer.renderer.is_scenario_debug_marker_category_visible(ScenarioDebugMarkerCategory::Object);
+                    renderer.renderer.set_scenario_debug_marker_category_visible(ScenarioDebugMarkerCategory::Object, visible);
+                    continue;
+                }
+
+                if keycode == Some(Keycode::P) {
+                    let count = handler.scenario_data.scenario_tag.player_starting_locations.items.len();
+                    if count == 0 {
+                        println!("Can't go to a player starting location because there are none.");
+                        continue;
+                    }
+
+                    current_starting_location_index = (current_starting_location_index + 1) % count;
+                    let mut renderer = handler.lock_renderer();
+                    handler.move_camera_to_starting_location(&mut renderer.renderer, viewport_mod, current_starting_location_index);
+                    println!("Teleported to player starting location #{current_starting_location_index}.");
+                    continue;
+                }
+
                 if keycode == Some(Keycode::R) {
                     let Some(current_bsp) = handler
                         .scenario_data
@@ -389,7 +973,7 @@ fn main() -> Result<(), String> {
 
                     let mut renderer = handler.lock_renderer();
                     let mut camera = renderer.renderer.get_camera_for_viewport(viewport_mod);
-                    camera.position = [x as f32, y as f32, z as f32];
+                    camera.position = Vec3::new(x as f32, y as f32, z as f32);
                     renderer.renderer.set_camera_for_viewport(viewport_mod, camera);
                     println!("Teleported to the center of the BSP.");
                     continue;
@@ -403,17 +987,19 @@ fn main() -> Result<(), String> {
                 ctrl |= keycode == Some(Keycode::LCtrl);
                 space |= keycode == Some(Keycode::Space);
                 shift |= keycode == Some(Keycode::LShift);
+                alt |= keycode == Some(Keycode::LAlt);
 
-                let result = make_thing(w,a,s,d,ctrl,space);
-                handler.camera_velocity[viewport_mod][0].swap(result[0].to_bits(), Ordering::Relaxed);
-                handler.camera_velocity[viewport_mod][1].swap(result[1].to_bits(), Ordering::Relaxed);
-                handler.camera_velocity[viewport_mod][2].swap(result[2].to_bits(), Ordering::Relaxed);
+                set_move_velocity(&handler.camera_velocity[viewport_mod], make_thing(w,a,s,d,ctrl,space), &gamepad, shift_speedup);
 
                 if keycode == Some(Keycode::LShift) {
-                    let increased = f32::from_bits(handler.camera_velocity[0][3].load(Ordering::Relaxed)) + shift_speedup;
-                    println!("Camera #{viewport_mod} speed: {}/s", camera_multiplier(increased));
+                    let increased = f32::from_bits(handler.camera_velocity[viewport_mod][3].load(Ordering::Relaxed)) + shift_speedup;
                     handler.camera_velocity[viewport_mod][3].swap(increased.to_bits(), Ordering::Relaxed);
                 }
+
+                if keycode == Some(Keycode::LAlt) {
+                    let reduced = f32::from_bits(handler.camera_velocity[viewport_mod][3].load(Ordering::Relaxed)) - alt_slowdown;
+                    handler.camera_velocity[viewport_mod][3].swap(reduced.to_bits(), Ordering::Relaxed);
+                }
             }
             Event::KeyUp { keycode, repeat, .. } => {
                 if repeat == true {
@@ -428,17 +1014,19 @@ fn main() -> Result<(), String> {
                 ctrl &= keycode != Some(Keycode::LCtrl);
                 space &= keycode != Some(Keycode::Space);
                 shift &= keycode != Some(Keycode::LShift);
+                alt &= keycode != Some(Keycode::LAlt);
 
-                let result = make_thing(w,a,s,d,ctrl,space);
-                handler.camera_velocity[viewport_mod][0].swap(result[0].to_bits(), Ordering::Relaxed);
-                handler.camera_velocity[viewport_mod][1].swap(result[1].to_bits(), Ordering::Relaxed);
-                handler.camera_velocity[viewport_mod][2].swap(result[2].to_bits(), Ordering::Relaxed);
+                set_move_velocity(&handler.camera_velocity[viewport_mod], make_thing(w,a,s,d,ctrl,space), &gamepad, shift_speedup);
 
                 if keycode == Some(Keycode::LShift) {
                     let reduced = f32::from_bits(handler.camera_velocity[viewport_mod][3].load(Ordering::Relaxed)) - shift_speedup;
-                    println!("Camera #{viewport_mod} speed: {}/s", camera_multiplier(reduced));
                     handler.camera_velocity[viewport_mod][3].swap(reduced.to_bits(), Ordering::Relaxed);
                 }
+
+                if keycode == Some(Keycode::LAlt) {
+                    let increased = f32::from_bits(handler.camera_velocity[viewport_mod][3].load(Ordering::Relaxed)) + alt_slowdown;
+                    handler.camera_velocity[viewport_mod][3].swap(increased.to_bits(), Ordering::Relaxed);
+                }
             }
             Event::MouseWheel { x, y, .. } => {
                 let mut incrementor = if x.abs() > y.abs() {
@@ -491,9 +1079,12 @@ fn main() -> Result<(), String> {
                     max += shift_speedup;
                 }
 
-                multiplier = multiplier.clamp(min, max);
+                if alt {
+                    min -= alt_slowdown;
+                    max -= alt_slowdown;
+                }
 
-                println!("Camera #{viewport_mod} speed: {}/s", camera_multiplier(multiplier));
+                multiplier = multiplier.clamp(min, max);
 
                 handler.camera_velocity[viewport_mod][3].swap(multiplier.to_bits(), Ordering::Relaxed);
             }
@@ -508,6 +1099,54 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// Load a scenario and all of its dependencies from a cache file (if `tags` is a single file) or tags directories.
+///
+/// `scenario` and `engine` are only used for tags directories, and `resource_maps` is only used for cache files.
+fn load_scenario_data(tags: &Vec<String>, scenario: Option<String>, engine: Option<String>, resource_maps: Option<&Path>) -> Result<ScenarioData, String> {
+    let first_tags_dir: &Path = tags.get(0).unwrap().as_ref();
+
+    let loading_cache = tags.len() == 1 && first_tags_dir.is_file();
+    let (scenario_path, engine, dependencies) = if loading_cache {
+        if engine.is_some() {
+            eprintln!("--engine is ignored when loading cache files");
+        }
+        if scenario.is_some() {
+            eprintln!("scenario path is ignored when loading cache files");
+        }
+        load_tags_from_cache(first_tags_dir, resource_maps)?
+    }
+    else {
+        let Some(scenario) = scenario else {
+            eprintln!("No tag path specified when --tags does not point to a cache file.");
+            return Err("no tag path specified".to_owned())
+        };
+        let scenario_path = TagPath::from_path(&scenario)
+            .map_err(|e| format!("Invalid tag path {scenario}: {e}"))?;
+
+        let (engine, dependencies) = load_tags_from_dir(tags, &scenario_path, engine)?;
+        (scenario_path, engine, dependencies)
+    };
+
+    let scenario_tag = dependencies
+        .get(&scenario_path)
+        .unwrap()
+        .get_ref::<Scenario>()
+        .expect("scenario wasn't scenario???")
+        .to_owned();
+
+    if scenario_tag.structure_bsps.items.is_empty() {
+        return Err("No BSPs in the scenario.".to_owned());
+    }
+
+    Ok(ScenarioData {
+        tags: dependencies,
+        tags_directories: (!loading_cache).then(|| tags.clone()),
+        scenario_path,
+        scenario_tag,
+        engine,
+    })
+}
+
 fn load_tags_from_dir(tags: &Vec<String>, scenario_path: &TagPath, engine: Option<String>) -> Result<(&'static Engine, HashMap<TagPath, Box<dyn PrimaryTagStructDyn>>), String> {
     let Some(engine) = engine else {
         eprintln!("You need to specify an engine if you are not loading a tags directory.");
@@ -554,14 +1193,32 @@ fn load_tags_from_dir(tags: &Vec<String>, scenario_path: &TagPath, engine: Optio
     Ok((engine, dependencies))
 }
 
-fn load_tags_from_cache(cache: &Path) -> Result<(TagPath, &'static Engine, HashMap<TagPath, Box<dyn PrimaryTagStructDyn>>), String> {
+/// Load every tag from a cache file.
+///
+/// Bitmap data stored in bitmaps.map is read from `resource_maps`, or the cache file's directory if `None`.
+fn load_tags_from_cache(cache: &Path, resource_maps: Option<&Path>) -> Result<(TagPath, &'static Engine, HashMap<TagPath, Box<dyn PrimaryTagStructDyn>>), String> {
     let map = ringhopper::map::load_map_from_filesystem(cache, ParseStrictness::Relaxed)
         .map_err(|e| format!("Failed to read {}: {e}", e.to_string()))?;
 
     let mut dependencies: HashMap<TagPath, Box<dyn PrimaryTagStructDyn>> = HashMap::new();
 
+    // Only opened if a bitmap needs it, since not every map uses external bitmap data
+    let mut bitmaps_map: Option<BitmapsResourceMap> = None;
+
     for i in map.get_all_tags() {
-        let tag = map.open_tag_copy(&i).map_err(|e| format!("Failed to read {i}: {e}"))?;
+        let mut tag = map.open_tag_copy(&i).map_err(|e| format!("Failed to read {i}: {e}"))?;
+        if let Some(bitmap) = tag.get_mut::<Bitmap>().filter(|b| has_external_bitmap_data(b)) {
+            let bitmaps_map = match bitmaps_map.as_mut() {
+                Some(b) => b,
+                None => {
+                    let directory = resource_maps.or_else(|| cache.parent()).unwrap_or(Path::new("."));
+                    let opened = BitmapsResourceMap::open(directory)
+                        .map_err(|e| format!("{i} has external bitmap data, but bitmaps.map couldn't be opened (try --resource-maps): {e}"))?;
+                    bitmaps_map.insert(opened)
+                }
+            };
+            resolve_external_bitmap_data(bitmap, bitmaps_map).map_err(|e| format!("Failed to read external bitmap data for {i}: {e}"))?;
+        }
         dependencies.insert(i, tag);
     }
 
@@ -571,10 +1228,18 @@ fn load_tags_from_cache(cache: &Path) -> Result<(TagPath, &'static Engine, HashM
 pub struct FlycamTestHandler {
     renderer: Option<Arc<Mutex<Renderer>>>,
     scenario_data: ScenarioData,
+    resource_maps: Option<PathBuf>,
     viewports: usize,
     pause_rendering_flag: Arc<AtomicBool>,
-
-    camera_velocity: Arc<[[AtomicU32; 4]; 4]>,
+    show_overlay: Arc<AtomicBool>,
+    console: Arc<Mutex<Console>>,
+    debug_geometry: Arc<Mutex<PersistentDebugGeometry>>,
+    camera_path: Arc<Mutex<CameraPathState>>,
+    walk: Arc<Mutex<WalkState>>,
+
+    /// Per viewport: forward, side, and up velocity; speed (see camera_multiplier); yaw and pitch rates in radians per
+    /// second; and speed added by the gamepad triggers.
+    camera_velocity: Arc<[[AtomicU32; 7]; 4]>,
 }
 
 impl FlycamTestHandler {
@@ -591,52 +1256,107 @@ impl FlycamTestHandler {
         }
     }
 
-    fn initialize_and_start(&mut self, camera_rotation_channel: Receiver<(f32, f32, usize)>) -> Result<(), String> {
-        if let Err(e) = self.load_bitmaps() {
+    fn initialize_and_start(&mut self, camera_rotation_channel: Receiver<(f32, f32, usize)>, benchmark: Option<Benchmark>) -> Result<(), String> {
+        self.load_assets()?;
+
+        let render_ref = Arc::downgrade(self.renderer.as_ref().unwrap());
+        let pause_rendering_ref = self.pause_rendering_flag.clone();
+        let show_overlay = self.show_overlay.clone();
+        let console = self.console.clone();
+        let debug_geometry = self.debug_geometry.clone();
+        let camera_path = self.camera_path.clone();
+        let walk = self.walk.clone();
+        let velocity = self.camera_velocity.clone();
+        std::thread::spawn(move || {
+            run_renderer_thread(render_ref, pause_rendering_ref, show_overlay, console, debug_geometry, camera_path, walk, velocity, camera_rotation_channel, benchmark);
+        });
+
+        Ok(())
+    }
+
+    /// Switch to a different scenario, clearing everything that was loaded into the renderer.
+    ///
+    /// `tags` and `scenario` work like the command line arguments. Tags directories use the current engine. If the
+    /// new scenario can't be read, the current one is kept.
+    fn load_map(&mut self, tags: Vec<String>, scenario: Option<String>) -> Result<(), String> {
+        let engine = Some(self.scenario_data.engine.name.to_owned());
+        let scenario_data = load_scenario_data(&tags, scenario, engine, self.resource_maps.as_deref())?;
+        self.lock_renderer().renderer.reset();
+        self.scenario_data = scenario_data;
+
+        // The old geometry is for a different map, and the cameras are about to be moved to the start anyway
+        *self.walk.lock().unwrap() = WalkState::default();
+
+        self.load_assets()
+    }
+
+    /// Convert the current scenario's tags and load them into the renderer, then move the cameras to the start.
+    fn load_assets(&self) -> Result<(), String> {
+        let mut assets = AddAssetsParameter {
+            worker_threads: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            ..Default::default()
+        };
+
+        if let Err(e) = self.load_bitmaps(&mut assets) {
             return Err(format!("ERROR LOADING BITMAPS: {e}"))
         }
 
-        if let Err(e) = self.load_shaders() {
+        if let Err(e) = self.load_shaders(&mut assets) {
             return Err(format!("ERROR LOADING shaders: {e}"))
         }
 
-        if let Err(e) = self.load_skies() {
+        if let Err(e) = self.load_skies(&mut assets) {
             return Err(format!("ERROR LOADING skies: {e}"))
         }
 
-        if let Err(e) = self.load_fonts() {
+        if let Err(e) = self.load_fonts(&mut assets) {
             return Err(format!("ERROR LOADING fonts: {e}"))
         }
 
-        if let Err(e) = self.load_bsps() {
+        if let Err(e) = self.load_bsps(&mut assets) {
             return Err(format!("ERROR: {e}"))
         }
 
+        let mut lock = self.lock_renderer();
+        let renderer = &mut *lock.renderer;
+
+        // The renderer can't draw while it's adding assets, so add them in batches and draw the progress in between
+        let total = asset_count(&assets);
+        let mut completed = 0;
+        let mut last_path = String::new();
+        for batch in split_assets(assets) {
+            draw_loading_screen(renderer, &self.scenario_data.scenario_path.to_string(), completed, total, &last_path);
+
+            let batch_size = asset_count(&batch);
+            let result = renderer.add_assets(batch, |progress| {
+                if (completed + progress.completed) % 100 == 0 {
+                    println!("Loading assets... {}/{}", completed + progress.completed, total);
+                }
+                last_path = progress.path.to_owned();
+            });
+            if let Err(e) = result {
+                return Err(format!("ERROR: Failed to load {last_path}: {e}"))
+            }
+            completed += batch_size;
+        }
+
         if let Some(n) = self.scenario_data.scenario_tag.structure_bsps.items.first().and_then(|b| b.structure_bsp.path()) {
-            if let Err(e) = self.renderer.as_mut().unwrap().lock().unwrap().set_current_bsp(Some(&n.to_string())) {
+            if let Err(e) = renderer.set_current_bsp(Some(&n.to_string())) {
                 return Err(format!("ERROR: {e}"))
             }
         }
 
-        let mut renderer = self.renderer.as_ref().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
+        // Objects can't be drawn yet, so mark where they are placed instead
+        let object_markers = object_placement_markers(&self.scenario_data.scenario_tag);
+        println!("Placed {} object(s) as markers.", object_markers.len());
+        renderer.set_scenario_debug_markers(object_markers);
 
-        for (vi, location) in self.scenario_data
-            .scenario_tag
-            .player_starting_locations
-            .items
-            .iter()
-            .enumerate()
-            .take(renderer.get_viewport_count()) {
-            renderer.set_camera_for_viewport(vi, magellanicus::renderer::Camera {
-                position: [location.position.x as f32, location.position.y as f32, location.position.z as f32 + 0.7],
-                rotation: {
-                    let x = location.facing.angle.cos();
-                    let y = location.facing.angle.sin();
-                    [x, y, 0.0]
-                },
-                ..Default::default()
-            });
+        // If there are more viewports than starting locations, start over from the first one
+        let starting_location_count = self.scenario_data.scenario_tag.player_starting_locations.items.len();
+        if starting_location_count > 0 {
+            for vi in 0..renderer.get_viewport_count() {
+                self.move_camera_to_starting_location(renderer, vi, vi % starting_location_count);
+            }
         }
 
         println!("--------------------------------------------------------------------------------");
@@ -645,33 +1365,411 @@ impl FlycamTestHandler {
         println!("  Type: {}", self.scenario_data.scenario_tag._type);
         println!("--------------------------------------------------------------------------------");
 
-        let render_ref = Arc::downgrade(self.renderer.as_ref().unwrap());
-        let pause_rendering_ref = self.pause_rendering_flag.clone();
-        let velocity = self.camera_velocity.clone();
-        std::thread::spawn(move || {
-            run_renderer_thread(render_ref, pause_rendering_ref, velocity, camera_rotation_channel);
-        });
-
         Ok(())
     }
 
-    fn load_bitmaps(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
+    /// Get the path of the BSP at `index` in the scenario, if it has one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    fn bsp_path(&self, index: usize) -> Option<String> {
+        self.scenario_data.scenario_tag.structure_bsps.items[index].structure_bsp.path().map(|t| t.to_string())
+    }
+
+    /// Replace the debug geometry drawn in every frame with the collision edges and/or cluster bounds of the BSP at
+    /// `bsp_index`.
+    fn update_debug_geometry(&self, bsp_index: usize, collision: bool, clusters: bool) {
+        let mut geometry = PersistentDebugGeometry::default();
+        let bsp = self.scenario_data.scenario_tag.structure_bsps.items[bsp_index]
+            .structure_bsp
+            .path()
+            .and_then(|p| self.scenario_data.tags.get(p))
+            .and_then(|t| t.get_ref::<ScenarioStructureBSP>());
+
+        if let Some(bsp) = bsp {
+            if let Some(collision_bsp) = collision.then(|| bsp.collision_bsp.items.first()).flatten() {
+                let vertices = &collision_bsp.vertices.items;
+                let point = |index: usize| vertices.get(index).map(|v| Vec3::new(v.point.x as f32, v.point.y as f32, v.point.z as f32));
+                for edge in collision_bsp.edges.items.iter() {
+                    if let (Some(from), Some(to)) = (point(edge.start_vertex as usize), point(edge.end_vertex as usize)) {
+                        geometry.lines.push((from, to, [0.1, 1.0, 1.0, 1.0]));
+                    }
+                }
+            }
+
+            if clusters {
+                for (cluster_index, cluster) in bsp.clusters.items.iter().enumerate() {
+                    let color = CLUSTER_COLORS[cluster_index % CLUSTER_COLORS.len()];
+                    for subcluster in cluster.subclusters.items.iter() {
+                        let min = Vec3::new(subcluster.world_bounds_x.lower as f32, subcluster.world_bounds_y.lower as f32, subcluster.world_bounds_z.lower as f32);
+                        let max = Vec3::new(subcluster.world_bounds_x.upper as f32, subcluster.world_bounds_y.upper as f32, subcluster.world_bounds_z.upper as f32);
+                        geometry.boxes.push((min, max, color));
+                    }
+                }
+            }
+        }
+
+        *self.debug_geometry.lock().unwrap() = geometry;
+    }
+
+    /// Make walking cameras collide with the BSP at `bsp_index`.
+    ///
+    /// The BSP's triangles are only gathered if a camera is walking, since it takes a moment.
+    fn update_walk_geometry(&self, bsp_index: usize) {
+        if !self.walk.lock().unwrap().needs_geometry(bsp_index) {
+            return
+        }
+
+        // Don't hold the walk state while reading the BSP, since the render thread locks it every frame
+        let geometry = self.scenario_data.scenario_tag.structure_bsps.items[bsp_index]
+            .structure_bsp
+            .path()
+            .and_then(|p| self.scenario_data.tags.get(p))
+            .and_then(|t| t.get_ref::<ScenarioStructureBSP>())
+            .map(WalkGeometry::from_bsp)
+            .unwrap_or_default();
+        self.walk.lock().unwrap().set_geometry(bsp_index, geometry);
+    }
+
+    /// Run a console command for `viewport`, returning what to print.
+    ///
+    /// `current_bsp_index` is updated if the BSP is switched.
+    fn run_console_command(&self, command: &str, viewport: usize, current_bsp_index: &mut usize) -> Result<String, String> {
+        let mut arguments = command.split_whitespace();
+        let name = arguments.next().unwrap_or_default();
+        let arguments: Vec<&str> = arguments.collect();
+        let numbers = || arguments
+            .iter()
+            .map(|a| a.parse::<f32>().ok().filter(|n| n.is_finite()).ok_or_else(|| format!("{a} is not a number")))
+            .collect::<Result<Vec<f32>, String>>();
+
+        match name {
+            "help" => Ok(CONSOLE_HELP.to_owned()),
+            "pos" => {
+                let camera = self.lock_renderer().renderer.get_camera_for_viewport(viewport);
+                let yaw = camera.rotation.y.atan2(camera.rotation.x).to_degrees();
+                let pitch = camera.rotation.normalize().z.asin().to_degrees();
+                let Vec3 { x, y, z } = camera.position;
+                Ok(format!("{x:.03} {y:.03} {z:.03} {yaw:.01} {pitch:.01}"))
+            },
+            "tp" => {
+                let numbers = numbers()?;
+                if numbers.len() != 3 && numbers.len() != 5 {
+                    return Err("usage: tp <x> <y> <z> [<yaw> <pitch>]".to_owned())
+                }
+                let mut renderer = self.lock_renderer();
+                let mut camera = renderer.renderer.get_camera_for_viewport(viewport);
+                camera.position = Vec3::new(numbers[0], numbers[1], numbers[2]);
+                if numbers.len() == 5 {
+                    camera.rotation = facing(numbers[3], numbers[4]);
+                }
+                renderer.renderer.set_camera_for_viewport(viewport, camera);
+                Ok(format!("Teleported camera #{viewport}."))
+            },
+            "bsp" => {
+                let count = self.scenario_data.scenario_tag.structure_bsps.items.len();
+                let Some(index) = arguments.first() else {
+                    return Ok(format!("Current BSP: #{current_bsp_index} of {count}"))
+                };
+                let index = index.parse::<usize>().ok().filter(|i| *i < count).ok_or_else(|| format!("No BSP #{index}"))?;
+                let path = self.bsp_path(index);
+                self.lock_renderer().renderer.set_current_bsp(path.as_deref()).map_err(|e| e.to_string())?;
+                *current_bsp_index = index;
+                Ok(format!("Switched to BSP #{index} ({})", path.as_deref().unwrap_or("none")))
+            },
+            "lighting" => {
+                let lighting_mode = match arguments.first().copied() {
+                    Some("textured") => LightingMode::TexturedLightmapped,
+                    Some("fullbright") => LightingMode::Fullbright,
+                    Some("lightmap") => LightingMode::LightmapOnly,
+                    Some("unlit") => LightingMode::Unlit,
+                    _ => return Err("usage: lighting <textured|fullbright|lightmap|unlit>".to_owned())
+                };
+                let mut renderer = self.lock_renderer();
+                let mut camera = renderer.renderer.get_camera_for_viewport(viewport);
+                camera.lighting_mode = lighting_mode;
+                renderer.renderer.set_camera_for_viewport(viewport, camera);
+                Ok(format!("Camera #{viewport} lighting: {lighting_mode:?}"))
+            },
+            "debug" => {
+                let mode = match arguments.first().copied() {
+                    Some("none") => DebugRenderMode::None,
+                    Some("normals") => DebugRenderMode::WorldSpaceNormals,
+                    Some("tangent_normals") => DebugRenderMode::TangentSpaceNormals,
+                    Some("tangents") => DebugRenderMode::Tangents,
+                    Some("binormals") => DebugRenderMode::Binormals,
+                    Some("uv") => DebugRenderMode::UVCheckerboard,
+                    Some("texel_density") => DebugRenderMode::TexelDensity,
+                    Some("overdraw") => DebugRenderMode::Overdraw,
+                    _ => return Err("usage: debug <none|normals|tangent_normals|tangents|binormals|uv|texel_density|overdraw>".to_owned())
+                };
+                self.lock_renderer().renderer.set_debug_render_mode(viewport, mode);
+                Ok(format!("Camera #{viewport} debug mode: {mode:?}"))
+            },
+            "fog" => {
+                let fog = match arguments.first().copied() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => return Err("usage: fog <on|off>".to_owned())
+                };
+                let mut renderer = self.lock_renderer();
+                let mut camera = renderer.renderer.get_camera_for_viewport(viewport);
+                camera.fog = fog;
+                renderer.renderer.set_camera_for_viewport(viewport, camera);
+                Ok(format!("Camera #{viewport} fog: {}", if fog { "on" } else { "off" }))
+            },
+            "fov_scaling" => {
+                let fov_scaling = match arguments.first().copied() {
+                    Some("fixed") => FovScaling::Fixed,
+                    Some("hor+") => FovScaling::HorPlus,
+                    Some("vert-") => FovScaling::VertMinus,
+                    _ => return Err("usage: fov_scaling <fixed|hor+|vert->".to_owned())
+                };
+                self.lock_renderer().renderer.set_fov_scaling(viewport, fov_scaling);
+                Ok(format!("Camera #{viewport} FoV scaling: {fov_scaling:?}"))
+            },
+            "bars" => {
+                let bars = if arguments.first() == Some(&"off") {
+                    None
+                }
+                else {
+                    let numbers = numbers()?;
+                    match numbers[..] {
+                        [thickness] => Some(SplitScreenBars { thickness, ..Default::default() }),
+                        [thickness, r, g, b, a] => Some(SplitScreenBars { thickness, color: [r, g, b, a] }),
+                        _ => return Err("usage: bars <off|thickness [r g b a]>".to_owned())
+                    }
+                };
+                self.lock_renderer().renderer.set_split_screen_bars(bars).map_err(|e| e.to_string())?;
+                Ok(format!("Split screen bars: {}", bars.map(|b| format!("{b:?}")).unwrap_or_else(|| "off".to_owned())))
+            },
+            "overbright" => {
+                let overbright = match arguments.first().copied() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => return Err("usage: overbright <on|off>".to_owned())
+                };
+                self.lock_renderer().renderer.set_lightmap_overbright(overbright);
+                Ok(format!("Overbright lightmaps: {}", if overbright { "on" } else { "off" }))
+            },
+            "quality" => {
+                let preset = match arguments.first().copied() {
+                    Some("low") => QualityPreset::Low,
+                    Some("medium") => QualityPreset::Medium,
+                    Some("high") => QualityPreset::High,
+                    Some("ultra") => QualityPreset::Ultra,
+                    None => return Ok(format!("Quality: {:?}", self.lock_renderer().renderer.get_quality_preset())),
+                    _ => return Err("usage: quality [low|medium|high|ultra]".to_owned())
+                };
+                let mut lock = self.lock_renderer();
+                lock.renderer.set_quality_preset(preset).map_err(|e| e.to_string())?;
+                let parameters = lock.renderer.get_parameters();
+                Ok(format!("Quality: {preset:?} ({}x MSAA, {}% render scale)", parameters.msaa as u32, parameters.render_scale * 100.0))
+            },
+            "fog_override" => {
+                let fog = if arguments.first() == Some(&"off") {
+                    None
+                }
+                else {
+                    let numbers = numbers()?;
+                    let [red, green, blue, distance_from, distance_to, min_opacity, max_opacity] = numbers[..] else {
+                        return Err("usage: fog_override <off|<r> <g> <b> <from> <to> <min opacity> <max opacity>>".to_owned())
+                    };
+                    Some(FogData { color: [red, green, blue], distance_from, distance_to, min_opacity, max_opacity })
+                };
+                self.lock_renderer().renderer.set_fog_override(viewport, fog).map_err(|e| e.to_string())?;
+                Ok(format!("Camera #{viewport} fog override: {}", if fog.is_some() { "on" } else { "off" }))
+            },
+            "probe" => {
+                let resolution = match arguments.first() {
+                    Some(r) => r.parse::<u32>().ok().filter(|r| *r > 0).ok_or_else(|| "usage: probe [<resolution>]".to_owned())?,
+                    None => DEFAULT_REFLECTION_PROBE_RESOLUTION
+                };
+                let mut renderer = self.lock_renderer();
+                let position = renderer.renderer.get_camera_for_viewport(viewport).position;
+                renderer.renderer
+                    .capture_reflection_probe(viewport, REFLECTION_PROBE_PATH, position, resolution)
+                    .map_err(|e| e.to_string())?;
+                renderer.renderer
+                    .set_fallback_asset(FallbackAsset::Cubemap, Some(REFLECTION_PROBE_PATH))
+                    .map_err(|e| e.to_string())?;
+                Ok(format!("Captured a {resolution}x{resolution} reflection probe; shaders without cubemaps use it once reloaded."))
+            },
+            "dof" => {
+                let depth_of_field = if arguments.first() == Some(&"off") {
+                    None
+                }
+                else {
+                    let numbers = numbers()?;
+                    let [focal_distance, aperture] = numbers[..] else {
+                        return Err("usage: dof <off|<focal distance> <aperture>>".to_owned())
+                    };
+                    Some(DepthOfField { focal_distance, aperture })
+                };
+                self.lock_renderer().renderer.set_depth_of_field(viewport, depth_of_field).map_err(|e| e.to_string())?;
+                Ok(format!("Camera #{viewport} depth of field: {}", if depth_of_field.is_some() { "on" } else { "off" }))
+            },
+            "lod" => {
+                let shader_lod = if arguments.first() == Some(&"off") {
+                    ShaderLod::default()
+                }
+                else {
+                    let numbers = numbers()?;
+                    let [bump_distance, reflection_distance, detail_distance] = numbers[..] else {
+                        return Err("usage: lod <off|<bump> <reflection> <detail>>".to_owned())
+                    };
+                    ShaderLod { bump_distance, reflection_distance, detail_distance }
+                };
+                self.lock_renderer().renderer.set_shader_lod(viewport, shader_lod).map_err(|e| e.to_string())?;
+                Ok(format!("Camera #{viewport} shader LOD: {shader_lod:?}"))
+            },
+            "motion_blur" => {
+                let numbers = numbers()?;
+                let [intensity] = numbers[..] else {
+                    return Err("usage: motion_blur <intensity>".to_owned())
+                };
+                self.lock_renderer().renderer.set_motion_blur(viewport, intensity).map_err(|e| e.to_string())?;
+                Ok(format!("Camera #{viewport} motion blur: {intensity}"))
+            },
+            "exposure" | "bloom" | "letterbox" => {
+                let numbers = numbers()?;
+                let [value] = numbers[..] else {
+                    return Err(format!("usage: {name} <value>"))
+                };
+                let mut renderer = self.lock_renderer();
+                let mut post_processing = renderer.renderer.get_post_processing(viewport);
+                match name {
+                    "exposure" => post_processing.exposure = value,
+                    "bloom" => post_processing.bloom = value,
+                    _ => post_processing.letterbox = value
+                }
+                renderer.renderer.set_post_processing(viewport, post_processing).map_err(|e| e.to_string())?;
+                Ok(format!("Camera #{viewport} {name}: {value}"))
+            },
+            "overlay" => {
+                let color_overlay = if arguments.first() == Some(&"off") {
+                    PostProcessing::default().color_overlay
+                }
+                else {
+                    let numbers = numbers()?;
+                    let [r, g, b, a] = numbers[..] else {
+                        return Err("usage: overlay <off|r g b a>".to_owned())
+                    };
+                    [r, g, b, a]
+                };
+                let mut renderer = self.lock_renderer();
+                let post_processing = PostProcessing { color_overlay, ..renderer.renderer.get_post_processing(viewport) };
+                renderer.renderer.set_post_processing(viewport, post_processing).map_err(|e| e.to_string())?;
+                Ok(format!("Camera #{viewport} color overlay: {color_overlay:?}"))
+            },
+            "reload_shaders" => {
+                let count = self.reload_shaders()?;
+                Ok(format!("Reloaded {count} shader(s)."))
+            },
+            "record" => {
+                let [output] = arguments[..] else {
+                    return Err("usage: record <file>".to_owned())
+                };
+                let mut camera_path = self.camera_path.lock().unwrap();
+                if !matches!(*camera_path, CameraPathState::Idle) {
+                    return Err("already recording or playing; type stop first".to_owned())
+                }
+                *camera_path = CameraPathState::Recording {
+                    viewport,
+                    output: PathBuf::from(output),
+                    started: Instant::now(),
+                    keyframes: Vec::new()
+                };
+                Ok(format!("Recording camera #{viewport} to {output}; type stop to save."))
+            },
+            "play" => {
+                let [input] = arguments[..] else {
+                    return Err("usage: play <file>".to_owned())
+                };
+                let path = CameraPath::load(input.as_ref())?;
+                let mut camera_path = self.camera_path.lock().unwrap();
+                if !matches!(*camera_path, CameraPathState::Idle) {
+                    return Err("already recording or playing; type stop first".to_owned())
+                }
+                *camera_path = CameraPathState::Playing { viewport, started: Instant::now(), path };
+                Ok(format!("Playing {input} on camera #{viewport}; type stop to stop."))
+            },
+            "stop" => self.camera_path.lock().unwrap().stop(),
+            _ => Err(format!("Unknown command {name}; type help for a list of commands"))
+        }
+    }
+
+    /// Read every shader from the tags directories again and replace the loaded shaders with them.
+    ///
+    /// Returns the number of shaders reloaded. Bitmaps are not reloaded, so shaders that reference bitmaps that were
+    /// not already loaded will fail to reload.
+    fn reload_shaders(&self) -> Result<usize, String> {
+        let Some(tags) = self.scenario_data.tags_directories.as_ref() else {
+            return Err("shaders can't be reloaded from a cache file".to_owned())
+        };
+
+        // Make a new tag tree so nothing is cached from the last time tags were read
+        let directories = VirtualTagsDirectory::new(tags, None)
+            .map_err(|e| format!("Error reading tags directory {tags:?}: {e}"))
+            .map(|t| CachingTagTree::new(t, CachingTagTreeWriteStrategy::Instant))?;
+
+        let mirror_shaders = self.mirror_shaders();
+        let mut shaders = Vec::new();
+        for path in self.scenario_data.tags.keys().filter(|p| p.group().subgroup() == Some(TagGroup::Shader)) {
+            let tag = directories.open_tag_shared(path)
+                .map_err(|e| format!("Failed to read {path}: {e}"))?;
+            let mut tag = tag
+                .lock()
+                .unwrap();
+            tag.set_defaults();
+            shaders.push((path.to_string(), Self::load_shader(&*tag, mirror_shaders.contains(path))));
+        }
+
+        let mut renderer = self.lock_renderer();
+        let count = shaders.len();
+        for (path, shader) in shaders {
+            renderer.renderer.replace_shader(&path, shader).map_err(|e| format!("Failed to reload {path}: {e}"))?;
+        }
+        Ok(count)
+    }
+
+    /// Move the camera of `viewport` to the player starting location at `index`, returning `false` if there is none.
+    fn move_camera_to_starting_location(&self, renderer: &mut Renderer, viewport: usize, index: usize) -> bool {
+        let Some(location) = self.scenario_data.scenario_tag.player_starting_locations.items.get(index) else {
+            return false
+        };
+
+        let mut camera = renderer.get_camera_for_viewport(viewport);
+        camera.position = Vec3::new(location.position.x as f32, location.position.y as f32, location.position.z as f32 + 0.7);
+        camera.rotation = {
+            let x = location.facing.angle.cos();
+            let y = location.facing.angle.sin();
+            Vec3::new(x, y, 0.0)
+        };
+        renderer.set_camera_for_viewport(viewport, camera);
+        true
+    }
+
+    fn load_bitmaps(&self, assets: &mut AddAssetsParameter) -> Result<(), String> {
         let all_bitmaps = self.scenario_data
             .tags
             .iter()
             .filter(|f| f.0.group() == TagGroup::Bitmap)
-            .map(|f| (f.0, f.1.get_ref::<Bitmap>().unwrap()));
-        
-        for (path, bitmap) in all_bitmaps {
-            Self::load_bitmap(renderer, &path, bitmap).map_err(|e| format!("Failed to load bitmap {path}: {e}"))?;
+            .map(|f| (f.0, f.1.get_ref::<Bitmap>().unwrap()))
+            .collect::<Vec<_>>();
+
+        let parameters = map_in_parallel(&all_bitmaps, |(path, bitmap)| {
+            Self::load_bitmap(path, bitmap).map_err(|e| format!("Failed to load bitmap {path}: {e}"))
+        });
+        for ((path, _), parameter) in all_bitmaps.iter().zip(parameters) {
+            assets.bitmaps.push((path.to_string(), parameter?));
         }
 
         Ok(())
     }
 
-    fn load_bitmap(renderer: &mut Renderer, path: &&TagPath, bitmap: &Bitmap) -> Result<(), String> {
+    fn load_bitmap(path: &&TagPath, bitmap: &Bitmap) -> Result<AddBitmapParameter, String> {
         let parameter = AddBitmapParameter {
             bitmaps: {
                 let mut bitmaps = Vec::with_capacity(bitmap.bitmap_data.items.len());
@@ -762,27 +1860,39 @@ impl FlycamTestHandler {
             }
         };
 
-        renderer.add_bitmap(&path.to_string(), parameter).map_err(|e| e.to_string())
+        Ok(parameter)
     }
 
-    fn load_shaders(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
-
+    fn load_shaders(&self, assets: &mut AddAssetsParameter) -> Result<(), String> {
         let all_shaders = self.scenario_data
             .tags
             .iter()
             .filter(|f| f.0.group().subgroup() == Some(TagGroup::Shader));
 
+        let mirror_shaders = self.mirror_shaders();
         for (path, tag) in all_shaders {
-            Self::load_shader(renderer, &path, tag).map_err(|e| format!("Failed to load shader {path}: {e}"))?;
+            assets.shaders.push((path.to_string(), Self::load_shader(tag, mirror_shaders.contains(path))));
         }
 
         Ok(())
     }
 
-    fn load_shader(renderer: &mut Renderer, path: &&TagPath, tag: &Box<dyn PrimaryTagStructDyn>) -> Result<(), String> {
-        let new_shader = match tag.group() {
+    /// Get the shaders that BSPs use for mirrors.
+    ///
+    /// Mirrors are listed in each cluster of a BSP rather than being flagged in their shaders.
+    fn mirror_shaders(&self) -> HashSet<TagPath> {
+        self.scenario_data
+            .tags
+            .values()
+            .filter_map(|t| t.get_ref::<ScenarioStructureBSP>())
+            .flat_map(|bsp| bsp.clusters.items.iter())
+            .flat_map(|cluster| cluster.mirrors.items.iter())
+            .filter_map(|mirror| mirror.shader.path().cloned())
+            .collect()
+    }
+
+    fn load_shader(tag: &Box<dyn PrimaryTagStructDyn>, mirror: bool) -> AddShaderParameter {
+        match tag.group() {
             TagGroup::ShaderEnvironment => {
                 let tag = tag.get_ref::<ShaderEnvironment>().unwrap();
                 AddShaderParameter {
@@ -811,13 +1921,19 @@ impl FlycamTestHandler {
                         ],
                         parallel_brightness: tag.reflection.parallel_brightness as f32,
                         perpendicular_brightness: tag.reflection.perpendicular_brightness as f32,
+                        specular_brightness: tag.specular.brightness as f32,
+                        specular_overbright: tag.specular.flags.overbright,
+                        specular_extra_shiny: tag.specular.flags.extra_shiny,
+                        specular_lightmap_is_specular: tag.specular.flags.lightmap_is_specular,
+                        mirror,
 
                         // SAFETY: 🔥🐶🔥 This is fine 🔥🐶🔥
                         shader_environment_type: unsafe { transmute(tag.properties.shader_environment_type as u32) },
                         detail_map_function: unsafe { transmute(tag.diffuse.detail_map_function as u32) },
                         micro_detail_map_function: unsafe { transmute(tag.diffuse.micro_detail_map_function as u32) },
                         reflection_type: unsafe { transmute(tag.reflection._type as u32) },
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             TagGroup::ShaderModel => {
@@ -827,7 +1943,8 @@ impl FlycamTestHandler {
                         bitmap: tag.maps.base_map.path().map(|q| q.to_string()),
                         shader_type: ShaderType::Model,
                         alpha_tested: !tag.properties.flags.not_alpha_tested
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             TagGroup::ShaderTransparentChicago => {
@@ -838,7 +1955,8 @@ impl FlycamTestHandler {
                         first_map_type: unsafe { transmute(tag.properties.first_map_type as u32) },
                         framebuffer_method: unsafe { transmute(tag.properties.framebuffer_blend_function as u32) },
                         maps: tag.maps.items.iter().map(chicago_map_to_magellanicus_chicago_map).collect()
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             TagGroup::ShaderTransparentChicagoExtended => {
@@ -849,7 +1967,8 @@ impl FlycamTestHandler {
                         first_map_type: unsafe { transmute(tag.properties.first_map_type as u32) },
                         framebuffer_method: unsafe { transmute(tag.properties.framebuffer_blend_function as u32) },
                         maps: tag._4_stage_maps.items.iter().map(chicago_map_to_magellanicus_chicago_map).collect()
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             TagGroup::ShaderTransparentGeneric => {
@@ -864,7 +1983,8 @@ impl FlycamTestHandler {
                             .map(|b| b.to_string()),
                         shader_type: ShaderType::TransparentGeneric,
                         alpha_tested: true
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             TagGroup::ShaderTransparentGlass => {
@@ -878,7 +1998,8 @@ impl FlycamTestHandler {
                             .map(|b| b.to_string()),
                         shader_type: ShaderType::TransparentGlass,
                         alpha_tested: true
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             TagGroup::ShaderTransparentMeter => {
@@ -892,7 +2013,8 @@ impl FlycamTestHandler {
                             .map(|b| b.to_string()),
                         shader_type: ShaderType::TransparentMeter,
                         alpha_tested: true
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             TagGroup::ShaderTransparentPlasma => {
@@ -902,7 +2024,8 @@ impl FlycamTestHandler {
                         bitmap: None,
                         shader_type: ShaderType::TransparentPlasma,
                         alpha_tested: true
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             TagGroup::ShaderTransparentWater => {
@@ -912,49 +2035,44 @@ impl FlycamTestHandler {
                         bitmap: None,
                         shader_type: ShaderType::TransparentWater,
                         alpha_tested: true
-                    })
+                    }),
+                    sort_layer: 0
                 }
             },
             n => unreachable!("{n}")
-        };
-        renderer.add_shader(&path.to_string(), new_shader).map_err(|e| e.to_string())
+        }
     }
 
-    fn load_skies(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
-
+    fn load_skies(&self, assets: &mut AddAssetsParameter) -> Result<(), String> {
         let all_skies = self.scenario_data
             .tags
             .iter()
             .filter(|f| f.0.group() == TagGroup::Sky);
 
         for (path, tag) in all_skies {
-            Self::load_sky(renderer, path, tag.get_ref().unwrap()).map_err(|e| format!("Failed to load sky {path}: {e}"))?;
+            assets.skies.push((path.to_string(), Self::load_sky(tag.get_ref().unwrap(), &self.scenario_data.tags)));
         }
 
         Ok(())
     }
 
-    fn load_fonts(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
-
+    fn load_fonts(&self, assets: &mut AddAssetsParameter) -> Result<(), String> {
         let all_fonts = self.scenario_data
             .tags
             .iter()
             .filter(|f| f.0.group() == TagGroup::Font);
 
         for (path, tag) in all_fonts {
-            Self::load_font(renderer, path, tag.get_ref().unwrap()).map_err(|e| format!("Failed to load font {path}: {e}"))?;
+            assets.fonts.push((path.to_string(), Self::load_font(tag.get_ref().unwrap())));
         }
 
         Ok(())
     }
 
-    fn load_font(renderer: &mut Renderer, path: &TagPath, font: &Font) -> Result<(), String> {
-        renderer.add_font(&path.to_string(), AddFontParameter {
+    fn load_font(font: &Font) -> AddFontParameter {
+        AddFontParameter {
             line_height: (font.ascending_height + font.descending_height) as u32,
+            ascending_height: font.ascending_height as u32,
             characters: font.characters.items.iter().filter_map(|c| {
                 let width = c.bitmap_width as usize;
                 let height = c.bitmap_height as usize;
@@ -969,16 +2087,25 @@ impl FlycamTestHandler {
                     data: data.to_vec(),
                     width,
                     height,
-                    advance_x: c.character_width as i32
+                    advance_x: c.character_width as i32,
+                    origin_x: c.bitmap_origin_x as i32,
+                    origin_y: c.bitmap_origin_y as i32
                 };
 
                 Some(character)
             }).collect()
-        }).map_err(|e| e.to_string())
+        }
     }
 
-    fn load_sky(renderer: &mut Renderer, path: &TagPath, sky: &Sky) -> Result<(), String> {
-        renderer.add_sky(&path.to_string(), AddSkyParameter {
+    fn load_sky(sky: &Sky, tags: &HashMap<TagPath, Box<dyn PrimaryTagStructDyn>>) -> AddSkyParameter {
+        // The tag stores the direction the light comes from.
+        let light_direction = |light: &ringhopper::definitions::SkyLight| {
+            let yaw = light.direction.yaw.angle as f32;
+            let pitch = light.direction.pitch.angle as f32;
+            -Vec3::new(yaw.cos() * pitch.cos(), yaw.sin() * pitch.cos(), pitch.sin())
+        };
+
+        AddSkyParameter {
             geometry: None,
             outdoor_fog: FogData {
                 color: [sky.outdoor_fog.color.red as f32, sky.outdoor_fog.color.green as f32, sky.outdoor_fog.color.blue as f32],
@@ -994,20 +2121,123 @@ impl FlycamTestHandler {
                 distance_to: sky.indoor_fog.opaque_distance as f32,
                 min_opacity: 0.0
             },
-        }).map_err(|e| e.to_string())
+            outdoor_ambient: SkyAmbientLight {
+                color: [sky.outdoor_ambient_radiosity_color.red as f32, sky.outdoor_ambient_radiosity_color.green as f32, sky.outdoor_ambient_radiosity_color.blue as f32],
+                power: sky.outdoor_ambient_radiosity_power as f32
+            },
+            indoor_ambient: SkyAmbientLight {
+                color: [sky.indoor_ambient_radiosity_color.red as f32, sky.indoor_ambient_radiosity_color.green as f32, sky.indoor_ambient_radiosity_color.blue as f32],
+                power: sky.indoor_ambient_radiosity_power as f32
+            },
+            lights: sky.lights.items.iter().map(|light| {
+                SkyLight {
+                    color: [light.color.red as f32, light.color.green as f32, light.color.blue as f32],
+                    power: light.power as f32,
+                    direction: light_direction(light)
+                }
+            }).collect(),
+            lens_flares: sky.lights.items.iter().filter_map(|light| {
+                let lens_flare = light.lens_flare
+                    .path()
+                    .and_then(|p| tags.get(p))
+                    .and_then(|t| t.get_ref::<LensFlare>())?;
+                let bitmap = lens_flare.bitmap.path()?.to_string();
+
+                // Reflection radii are in world units at the light's test distance, which the sun is drawn at.
+                let test_distance = light.test_distance as f32;
+                if test_distance <= 0.0 {
+                    return None
+                }
+
+                Some(SkyLensFlare {
+                    direction: light_direction(light),
+                    bitmap,
+                    occlusion_radius: light.diameter.angle as f32 / 2.0,
+                    reflections: lens_flare.reflections.items.iter().filter_map(|r| {
+                        let color = [r.tint_color.red, r.tint_color.green, r.tint_color.blue, r.tint_color.alpha * r.brightness.upper];
+                        Some(SkyLensFlareReflection {
+                            bitmap_index: r.bitmap_index? as usize,
+                            position: r.position as f32,
+                            radius: (r.radius.upper as f32 / test_distance).max(0.0).atan(),
+                            color: color.map(|c| (c as f32).clamp(0.0, 1.0))
+                        })
+                    }).collect()
+                })
+            }).collect()
+        }
     }
 
-    fn load_bsps(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
+    /// Get the fog planes of a BSP whose fog tags can be drawn, and the index of the fog plane each cluster is in.
+    ///
+    /// Clusters refer to fog palette entries, and fog planes refer to fog regions which refer to fog palette entries,
+    /// so each cluster gets the first fog plane with the same fog.
+    fn load_bsp_fog_planes(&self, bsp: &ScenarioStructureBSP) -> (Vec<BSPFogPlane>, Vec<Option<usize>>) {
+        let mut fog_planes = Vec::new();
+        let mut palette_fog_planes: HashMap<usize, usize> = HashMap::new();
+
+        for fog_plane in bsp.fog_planes.items.iter() {
+            let Some(palette_index) = fog_plane.front_region
+                .and_then(|r| bsp.fog_regions.items.get(r as usize))
+                .and_then(|r| r.fog_palette)
+                .map(|p| p as usize) else {
+                continue
+            };
+            if palette_fog_planes.contains_key(&palette_index) {
+                continue
+            }
+
+            let fog = bsp.fog_palette.items
+                .get(palette_index)
+                .and_then(|p| p.fog.path())
+                .and_then(|p| self.scenario_data.tags.get(p))
+                .and_then(|t| t.get_ref::<Fog>());
+            let Some(fog) = fog else {
+                continue
+            };
+
+            // Fog without a depth or distance would be opaque as soon as anything is in it, so it isn't drawn.
+            let opaque_distance = fog.opaque_distance as f32;
+            let opaque_depth = fog.opaque_depth as f32;
+            if opaque_distance <= 0.0 || opaque_depth <= 0.0 {
+                continue
+            }
+
+            palette_fog_planes.insert(palette_index, fog_planes.len());
+            fog_planes.push(BSPFogPlane {
+                plane: BSP3DPlane {
+                    angle: [fog_plane.plane.vector.x as f32, fog_plane.plane.vector.y as f32, fog_plane.plane.vector.z as f32],
+                    offset: fog_plane.plane.d as f32
+                },
+                color: [fog.color.red as f32, fog.color.green as f32, fog.color.blue as f32].map(|c| c.clamp(0.0, 1.0)),
+                max_density: (fog.maximum_density as f32).clamp(0.0, 1.0),
+                opaque_distance,
+                opaque_depth
+            });
+        }
+
+        let cluster_fog_planes = bsp.clusters.items
+            .iter()
+            .map(|c| c.fog.and_then(|f| palette_fog_planes.get(&(f as usize)).copied()))
+            .collect();
+
+        (fog_planes, cluster_fog_planes)
+    }
 
+    fn load_bsps(&self, assets: &mut AddAssetsParameter) -> Result<(), String> {
         let all_bsps = self.scenario_data
             .tags
             .iter()
             .filter(|f| f.0.group() == TagGroup::ScenarioStructureBSP)
-            .map(|f| (f.0, f.1.get_ref::<ScenarioStructureBSP>().unwrap()));
+            .map(|f| {
+                let bsp = f.1.get_ref::<ScenarioStructureBSP>().unwrap();
+                (f.0, bsp, self.load_bsp_fog_planes(bsp))
+            })
+            .collect::<Vec<_>>();
 
-        for (path, bsp) in all_bsps {
+        // Only the scenario is needed from self, so borrow just that for the worker threads
+        let scenario_tag = &self.scenario_data.scenario_tag;
+
+        let converted = map_in_parallel(&all_bsps, |(path, bsp, (fog_planes, cluster_fog_planes))| -> Result<(String, AddBSPParameter), String> {
             let mut add_bsp = AddBSPParameter {
                 lightmap_bitmap: bsp.lightmaps_bitmap.path().map(|p| p.to_native_path()),
                 lightmap_sets: Vec::with_capacity(bsp.lightmaps.items.len()),
@@ -1024,10 +2254,9 @@ impl FlycamTestHandler {
                     leaves: bsp.leaves.items.iter().map(|i| BSPLeaf {
                         cluster: i.cluster.unwrap() as usize
                     }).collect(),
-                    clusters: bsp.clusters.items.iter().map(|i| BSPCluster {
+                    clusters: bsp.clusters.items.iter().zip(cluster_fog_planes).map(|(i, fog_plane)| BSPCluster {
                         sky: if let Some(sky) = i.sky {
-                            self.scenario_data
-                                .scenario_tag
+                            scenario_tag
                                 .skies
                                 .items.get(sky as usize)
                                 .ok_or_else(|| format!("BSP {path} references sky {sky} which isn't valid on the scenario"))
@@ -1044,12 +2273,14 @@ impl FlycamTestHandler {
                             world_bounds_from: [s.world_bounds_x.lower as f32, s.world_bounds_y.lower as f32, s.world_bounds_z.lower as f32],
                             world_bounds_to: [s.world_bounds_x.upper as f32, s.world_bounds_y.upper as f32, s.world_bounds_z.upper as f32],
                         }).collect(),
-                        cluster_portals: i.portals.items.iter().map(|s| s.portal.unwrap_or(0xFFFF) as usize).collect()
+                        cluster_portals: i.portals.items.iter().map(|s| s.portal.unwrap_or(0xFFFF) as usize).collect(),
+                        fog_plane: *fog_plane
                     }).collect(),
                     portals: bsp.cluster_portals.items.iter().map(|p| BSPPortal {
                         front_cluster: p.front_cluster.unwrap_or(0xFFFF) as usize,
                         back_cluster: p.back_cluster.unwrap_or(0xFFFF) as usize,
-                    }).collect()
+                    }).collect(),
+                    fog_planes: fog_planes.clone()
                 },
             };
 
@@ -1084,14 +2315,14 @@ impl FlycamTestHandler {
                             let a = s.vertex0_index?;
                             let b = s.vertex1_index?;
                             let c = s.vertex2_index?;
-                            Some(ModelTriangle { indices: [a,b,c] })
+                            Some(ModelTriangle { indices: [a as u32, b as u32, c as u32] })
                     }).collect();
 
                     let (material, lightmap) = get_uncompressed_vertices_for_bsp_material(material).map_err(|e| {
                         format!("Material #{material_index} of Lightmap #{lightmap_index} of BSP {path} has broken vertices: {e:?}")
                     })?;
 
-                    let shader_vertices = material
+                    let shader_vertices: Vec<ModelVertex> = material
                         .map(|f| ModelVertex {
                             position: [f.position.x as f32, f.position.y as f32, f.position.z as f32],
                             normal: [f.normal.x as f32, f.normal.y as f32, f.normal.z as f32],
@@ -1108,8 +2339,8 @@ impl FlycamTestHandler {
                         .collect();
 
                     add_lightmap.materials.push(AddBSPParameterLightmapMaterial {
-                        shader_vertices,
-                        lightmap_vertices: (!lightmap.is_empty()).then_some(lightmap),
+                        shader_vertices: shader_vertices.into(),
+                        lightmap_vertices: (!lightmap.is_empty()).then(|| lightmap.into()),
                         surfaces: indices,
                         shader: shader_path.to_native_path(),
                         centroid
@@ -1118,16 +2349,26 @@ impl FlycamTestHandler {
                 add_bsp.lightmap_sets.push(add_lightmap);
             }
 
-            renderer.add_bsp(&path.to_native_path(), add_bsp).map_err(|e| format!("Failed to load BSP {path}: {e}"))?;
+            Ok((path.to_native_path(), add_bsp))
+        });
+        for bsp in converted {
+            assets.bsps.push(bsp?);
         }
 
         Ok(())
     }
 }
 
-fn run_renderer_thread(renderer: Weak<Mutex<Renderer>>, pause_rendering: Arc<AtomicBool>, velocity: Arc<[[AtomicU32; 4]; 4]>, camera_channel: Receiver<(f32, f32, usize)>) {
+fn run_renderer_thread(renderer: Weak<Mutex<Renderer>>, pause_rendering: Arc<AtomicBool>, show_overlay: Arc<AtomicBool>, console: Arc<Mutex<Console>>, debug_geometry: Arc<Mutex<PersistentDebugGeometry>>, camera_path: Arc<Mutex<CameraPathState>>, walk: Arc<Mutex<WalkState>>, velocity: Arc<[[AtomicU32; 7]; 4]>, camera_channel: Receiver<(f32, f32, usize)>, mut benchmark: Option<Benchmark>) {
     let time_start = Instant::now();
     let mut last_loop = 0.0;
+
+    // Speed of each camera and when it last changed, so it can be shown for a bit after it changes
+    let mut speed_readouts = [(1.0f32, f64::NEG_INFINITY); 4];
+
+    // Smoothed so the overlay is readable
+    let mut average_frame_time = 0.0f32;
+
     while let Some(renderer) = renderer.upgrade() {
         if pause_rendering.load(Ordering::Relaxed) {
             continue;
@@ -1135,7 +2376,7 @@ fn run_renderer_thread(renderer: Weak<Mutex<Renderer>>, pause_rendering: Arc<Ato
 
         let mut renderer = renderer.lock().unwrap();
 
-        let ms_since_start = (Instant::now() - time_start).as_millis() as f64 / 1000.0;
+        let ms_since_start = (Instant::now() - time_start).as_secs_f64();
         let mut rotate_deltas = [[0.0f32; 2]; 4];
 
         while let Ok(n) = camera_channel.try_recv() {
@@ -1143,42 +2384,100 @@ fn run_renderer_thread(renderer: Weak<Mutex<Renderer>>, pause_rendering: Arc<Ato
             rotate_deltas[n.2][1] += n.1;
         }
 
+        let mut walk = walk.lock().unwrap();
         for v in 0..renderer.get_viewport_count().min(velocity.len()) {
             let vel = &velocity[v];
             let rot = &rotate_deltas[v];
 
-            let multiplier = camera_multiplier(f32::from_bits(vel[3].load(Ordering::Relaxed)));
+            let multiplier = camera_multiplier(f32::from_bits(vel[3].load(Ordering::Relaxed)) + f32::from_bits(vel[6].load(Ordering::Relaxed)));
+
+            let seconds = (ms_since_start - last_loop) as f32;
+            let speed = 2.0 * multiplier;
+            let delta = seconds * speed;
+
+            let (last_speed, changed) = &mut speed_readouts[v];
+            if *last_speed != speed {
+                *last_speed = speed;
+                *changed = ms_since_start;
+            }
+            if ms_since_start - *changed < 2.0 {
+                let parameters = renderer.get_parameters();
+                let bottom = parameters.resolution.height as f32 * parameters.render_scale - 48.0 - 20.0 * v as f32;
+                renderer.debug_draw_text_2d(
+                    [8.0, bottom],
+                    &format!("Camera #{v} speed: {speed:.02}/s"),
+                    2.0,
+                    [1.0, 1.0, 1.0, 1.0],
+                    Duration::ZERO
+                );
+            }
 
-            let delta = (ms_since_start - last_loop) as f32 * 2.0 * multiplier;
             let forward = f32::from_bits(vel[0].load(Ordering::Relaxed)) * delta;
             let side = f32::from_bits(vel[1].load(Ordering::Relaxed)) * delta;
             let up = f32::from_bits(vel[2].load(Ordering::Relaxed)) * delta;
 
             let mut camera = renderer.get_camera_for_viewport(v);
-            let mut position = Vec3::from(camera.position);
-            camera.rotation = rotate(camera.rotation, rot[0], rot[1]);
-
-            let rotation = Vec3::from(camera.rotation);
-            position += Vec3::new(rotation.x * forward, rotation.y * forward, rotation.z * forward);
+            let mut position = camera.position;
+            let yaw = rot[0] + f32::from_bits(vel[4].load(Ordering::Relaxed)) * seconds;
+            let pitch = rot[1] + f32::from_bits(vel[5].load(Ordering::Relaxed)) * seconds;
+            camera.rotation = rotate(camera.rotation, yaw, pitch);
 
+            let rotation = camera.rotation;
             let q = Vec3::new(rotation.x, rotation.y, 0.0).normalize();
-            position -= Vec3::new(q.y * side, -q.x * side, 0.0);
-            position += Vec3::new(0.0, 0.0, up);
+            if walk.is_walking(v) {
+                // Walk along the ground regardless of pitch, and jump instead of flying up
+                let movement = Vec2::new(q.x * forward - q.y * side, q.y * forward + q.x * side);
+                position = walk.step(v, position, movement, up > 0.0, seconds);
+            }
+            else {
+                position += Vec3::new(rotation.x * forward, rotation.y * forward, rotation.z * forward);
+                position -= Vec3::new(q.y * side, -q.x * side, 0.0);
+                position += Vec3::new(0.0, 0.0, up);
+            }
 
-            camera.position = position.to_array();
+            camera.position = position;
             renderer.set_camera_for_viewport(v, camera);
         }
+        drop(walk);
+
+        camera_path.lock().unwrap().update(&mut renderer);
+        if let Some(benchmark) = benchmark.as_ref() {
+            benchmark.move_cameras(&mut renderer);
+        }
 
+        average_frame_time += ((ms_since_start - last_loop) as f32 - average_frame_time) * 0.05;
         last_loop = ms_since_start;
 
+        if show_overlay.load(Ordering::Relaxed) {
+            draw_overlay(&mut renderer, average_frame_time);
+        }
+        console.lock().unwrap().draw(&mut renderer);
+        debug_geometry.lock().unwrap().draw(&mut renderer);
+
         let frame_result = renderer.draw_frame();
 
         match frame_result {
-            Ok(n) => {
-                if !n {
-                    continue;
+            // The main thread resizes the renderer when the window size changes
+            Ok(FrameStatus::ResizeRequired) => continue,
+            Ok(FrameStatus::Minimized) => {
+                // Nothing to draw, so don't spin
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            },
+            Ok(FrameStatus::Drawn) => {
+                if benchmark.as_mut().is_some_and(|b| b.frame_drawn()) {
+                    match benchmark.take().unwrap().finish(&renderer) {
+                        Ok(path) => println!("Saved benchmark results to {}", path.display()),
+                        Err(e) => eprintln!("Failed to save benchmark results: {e}")
+                    }
+                    return;
                 }
             },
+            Err(Error::Device(DeviceError::DeviceLost)) => {
+                // Reloading everything isn't worth it for a test program
+                eprintln!("Render fail: the device was lost; stopping rendering");
+                return;
+            },
             Err(e) => {
                 eprintln!("Render fail: {e}");
                 continue;
@@ -1189,6 +2488,78 @@ fn run_renderer_thread(renderer: Weak<Mutex<Renderer>>, pause_rendering: Arc<Ato
     }
 }
 
+/// Draw frame timing, draw statistics, and where each camera is in the top-right corner for the next frame.
+fn draw_overlay(renderer: &mut Renderer, frame_time: f32) {
+    const SCALE: f32 = 2.0;
+
+    let statistics = renderer.last_frame_stats();
+    let mut text = format!(
+        "FPS: {:.01} ({:.02} ms)\nDraw calls: {}\nTriangles: {}\nPipeline binds: {}\nDescriptor sets: {}\nGeometries: {} drawn, {} culled\n",
+        1.0 / frame_time,
+        frame_time * 1000.0,
+        statistics.draw_calls,
+        statistics.triangles,
+        statistics.pipeline_binds,
+        statistics.descriptor_sets,
+        statistics.geometries_drawn,
+        statistics.geometries_culled
+    );
+
+    for v in 0..renderer.get_viewport_count() {
+        let camera = renderer.get_camera_for_viewport(v);
+        let rotation = camera.rotation.normalize();
+        let yaw = rotation.y.atan2(rotation.x).to_degrees();
+        let pitch = rotation.z.asin().to_degrees();
+        let cluster = renderer
+            .get_viewport_cluster(v)
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "none".to_owned());
+        let Vec3 { x, y, z } = camera.position;
+        text += &format!("\nCamera #{v}\n  Position: {x:.03} {y:.03} {z:.03}\n  Yaw: {yaw:.01} Pitch: {pitch:.01}\n  Cluster: {cluster}\n");
+    }
+
+    // The built-in font is 8 pixels wide
+    let width = renderer.get_parameters().resolution.width as f32;
+    let longest_line = text.lines().map(|l| l.len()).max().unwrap_or(0) as f32;
+    renderer.debug_draw_text_2d(
+        [width - longest_line * 8.0 * SCALE - 8.0, 8.0],
+        &text,
+        SCALE,
+        [1.0, 1.0, 1.0, 1.0],
+        Duration::ZERO
+    );
+}
+
+/// Colors that clusters are drawn in, cycled through by cluster index.
+const CLUSTER_COLORS: [[f32; 4]; 6] = [
+    [1.0, 0.3, 0.3, 1.0],
+    [0.3, 1.0, 0.3, 1.0],
+    [0.3, 0.5, 1.0, 1.0],
+    [1.0, 1.0, 0.3, 1.0],
+    [1.0, 0.3, 1.0, 1.0],
+    [0.3, 1.0, 1.0, 1.0]
+];
+
+/// World-space debug geometry that is drawn in every frame until it is replaced.
+#[derive(Default)]
+struct PersistentDebugGeometry {
+    lines: Vec<(Vec3, Vec3, [f32; 4])>,
+
+    /// Axis-aligned boxes from a minimum to a maximum corner.
+    boxes: Vec<(Vec3, Vec3, [f32; 4])>
+}
+
+impl PersistentDebugGeometry {
+    fn draw(&self, renderer: &mut Renderer) {
+        for (from, to, color) in self.lines.iter() {
+            renderer.debug_draw_line(*from, *to, *color, Duration::ZERO);
+        }
+        for (min, max, color) in self.boxes.iter() {
+            renderer.debug_draw_box(*min, *max, *color, Duration::ZERO);
+        }
+    }
+}
+
 struct PriorityLock<'a> {
     renderer: MutexGuard<'a, Renderer>,
     pause_rendering_flag: Arc<AtomicBool>
@@ -1201,12 +2572,77 @@ impl Drop for PriorityLock<'_> {
 }
 
 
-fn rotate(rotation: [f32; 3], yaw_delta: f32, pitch_delta: f32) -> [f32; 3] {
+/// Stick and trigger positions of the connected gamepads, from -1.0 to 1.0 (0.0 to 1.0 for triggers).
+#[derive(Default)]
+struct GamepadState {
+    left_stick: [f32; 2],
+    right_stick: [f32; 2],
+    left_trigger: f32,
+    right_trigger: f32
+}
+
+impl GamepadState {
+    fn set_axis(&mut self, axis: Axis, value: i16) {
+        let value = (value as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+        match axis {
+            Axis::LeftX => self.left_stick[0] = value,
+            Axis::LeftY => self.left_stick[1] = value,
+            Axis::RightX => self.right_stick[0] = value,
+            Axis::RightY => self.right_stick[1] = value,
+            Axis::TriggerLeft => self.left_trigger = value,
+            Axis::TriggerRight => self.right_trigger = value
+        }
+        self.left_stick = apply_deadzone(self.left_stick);
+        self.right_stick = apply_deadzone(self.right_stick);
+    }
+}
+
+/// Ignore small stick movements, since sticks rarely rest at exactly 0.
+fn apply_deadzone(stick: [f32; 2]) -> [f32; 2] {
+    const DEADZONE: f32 = 0.2;
+    let stick = Vec2::from(stick);
+    let length = stick.length();
+    if length <= DEADZONE {
+        return [0.0, 0.0]
+    }
+    (stick / length * ((length - DEADZONE) / (1.0 - DEADZONE)).min(1.0)).to_array()
+}
+
+/// Get the key that a gamepad button stands in for.
+fn gamepad_button_keycode(button: Button) -> Option<Keycode> {
+    match button {
+        Button::Start => Some(Keycode::Tab),
+        Button::Back => Some(Keycode::F3),
+        Button::Y => Some(Keycode::Q),
+        Button::X => Some(Keycode::F),
+        Button::B => Some(Keycode::R),
+        Button::DPadRight => Some(Keycode::P),
+        Button::LeftShoulder => Some(Keycode::PageDown),
+        Button::RightShoulder => Some(Keycode::PageUp),
+        _ => None
+    }
+}
+
+/// Set the velocity of a camera from the keyboard (see make_thing) and the left stick, and set the speed added by the
+/// triggers.
+fn set_move_velocity(velocity: &[AtomicU32; 7], keys: [f32; 3], gamepad: &GamepadState, shift_speedup: f32) {
+    let [forward, side, up] = keys;
+    let [stick_x, stick_y] = gamepad.left_stick;
+    let horizontal = Vec2::new(forward - stick_y, side - stick_x).clamp_length_max(1.0);
+    let trigger_speed = (gamepad.right_trigger - gamepad.left_trigger) * shift_speedup;
+
+    velocity[0].swap(horizontal.x.to_bits(), Ordering::Relaxed);
+    velocity[1].swap(horizontal.y.to_bits(), Ordering::Relaxed);
+    velocity[2].swap(up.to_bits(), Ordering::Relaxed);
+    velocity[6].swap(trigger_speed.to_bits(), Ordering::Relaxed);
+}
+
+fn rotate(rotation: Vec3, yaw_delta: f32, pitch_delta: f32) -> Vec3 {
     let mut yaw;
     let mut pitch;
 
     if rotation[0] != 0.0 || rotation[1] != 0.0 {
-        let full_xyz = Vec3::from([rotation[0], rotation[1], rotation[2]]).normalize();
+        let full_xyz = rotation.normalize();
         let full_xy = Vec3::from([full_xyz.x, full_xyz.y, 0.0]);
         let normalized_xy = Vec3::from(full_xy).normalize();
         let x_angle = normalized_xy[0].acos();
@@ -1230,7 +2666,289 @@ fn rotate(rotation: [f32; 3], yaw_delta: f32, pitch_delta: f32) -> [f32; 3] {
     pitch = pitch.clamp(-1.5, 1.5);
     let pitch_sine = pitch.sin();
     let pitch_cosine = pitch.cos();
-    [yaw.cos() * pitch_cosine, yaw.sin() * pitch_cosine, pitch_sine]
+    Vec3::new(yaw.cos() * pitch_cosine, yaw.sin() * pitch_cosine, pitch_sine)
+}
+
+/// Write `frame` as a PNG next to the executable, named after the current time.
+fn save_screenshot(frame: CapturedFrame) -> Result<PathBuf, String> {
+    let directory = std::env::current_exe()
+        .map_err(|e| e.to_string())?
+        .parent()
+        .ok_or_else(|| "the executable has no parent directory".to_owned())?
+        .to_owned();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis();
+    let path = directory.join(format!("screenshot-{timestamp}.png"));
+    write_png(frame, &path)?;
+    Ok(path)
+}
+
+/// Write `frame` as a PNG to `path`.
+fn write_png(mut frame: CapturedFrame, path: &Path) -> Result<(), String> {
+    // Whatever is in the alpha channel isn't meant to be seen
+    for pixel in frame.data.chunks_exact_mut(4) {
+        pixel[3] = 0xFF;
+    }
+
+    let file = File::create(path).map_err(|e| format!("Can't create {}: {e}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), frame.resolution.width, frame.resolution.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&frame.data).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
+
+/// Render one viewport offscreen with the camera at a position and rotation, and save it as a PNG.
+///
+/// `warmup_frames` frames are drawn first. Returns where the PNG was written, which is `output` if given.
+fn take_headless_screenshot(
+    scenario_data: ScenarioData,
+    resource_maps: Option<PathBuf>,
+    parameters: RendererParameters,
+    (position, rotation): (Vec3, Vec3),
+    fov: Option<f32>,
+    warmup_frames: u32,
+    output: Option<PathBuf>
+) -> Result<PathBuf, String> {
+    let renderer = Renderer::new_headless(parameters).map_err(|e| format!("Can't create a headless renderer: {e}"))?;
+    let handler = FlycamTestHandler {
+        renderer: Some(Arc::new(Mutex::new(renderer))),
+        scenario_data,
+        resource_maps,
+        viewports: 1,
+        camera_velocity: Arc::new(std::array::from_fn(|_| std::array::from_fn(|_| AtomicU32::new(0)))),
+        pause_rendering_flag: Arc::new(AtomicBool::new(false)),
+        show_overlay: Arc::new(AtomicBool::new(false)),
+        console: Arc::new(Mutex::new(Console::default())),
+        debug_geometry: Arc::new(Mutex::new(PersistentDebugGeometry::default())),
+        camera_path: Arc::new(Mutex::new(CameraPathState::default())),
+        walk: Arc::new(Mutex::new(WalkState::default())),
+    };
+    handler.load_assets()?;
+
+    // Nothing else is drawing, so the renderer can be used directly
+    let mut lock = handler.lock_renderer();
+    let renderer = &mut lock.renderer;
+    let mut camera = renderer.get_camera_for_viewport(0);
+    camera.position = position;
+    camera.rotation = rotation;
+    if let Some(fov) = fov {
+        camera.fov = fov.to_radians();
+    }
+    renderer.set_camera_for_viewport(0, camera);
+
+    for _ in 0..=warmup_frames {
+        renderer.draw_frame().map_err(|e| format!("Failed to draw a frame: {e}"))?;
+    }
+    let frame = renderer.capture_frame().map_err(|e| format!("Failed to capture the frame: {e}"))?;
+
+    match output {
+        Some(path) => write_png(frame, &path).map(|_| path),
+        None => save_screenshot(frame)
+    }
+}
+
+/// Parse a camera location as "x,y,z,yaw,pitch" with angles in degrees, returning its position and rotation.
+fn parse_camera_location(location: &str) -> Result<(Vec3, Vec3), String> {
+    let numbers = location
+        .split(',')
+        .map(|n| n.trim().parse::<f32>().ok().filter(|n| n.is_finite()))
+        .collect::<Option<Vec<f32>>>();
+    let Some([x, y, z, yaw, pitch]) = numbers.as_deref().and_then(|n| <[f32; 5]>::try_from(n).ok()) else {
+        return Err(format!("Invalid camera location {location}; must be x,y,z,yaw,pitch"))
+    };
+    Ok((Vec3::new(x, y, z), facing(yaw, pitch)))
+}
+
+/// Get the direction a camera faces from its yaw and pitch in degrees.
+fn facing(yaw: f32, pitch: f32) -> Vec3 {
+    let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians().clamp(-1.55, 1.55));
+    Vec3::new(yaw.cos() * pitch.cos(), yaw.sin() * pitch.cos(), pitch.sin())
+}
+
+/// Get a marker for each scenery, vehicle, machine, and control placed in the scenario.
+///
+/// Each marker is labeled with the name of the object's tag.
+fn object_placement_markers(scenario: &Scenario) -> Vec<ScenarioDebugMarker> {
+    let mut markers = Vec::new();
+
+    macro_rules! add_placements {
+        ($placements:expr, $palette:expr) => {
+            for placement in $placements.items.iter() {
+                let label = placement._type
+                    .and_then(|t| $palette.items.get(t as usize))
+                    .and_then(|p| p.name.path())
+                    .map(|p| {
+                        let path = p.to_string();
+                        let name = path.rsplit(['\\', '/']).next().unwrap_or(&path);
+                        name.split('.').next().unwrap_or(name).to_owned()
+                    });
+                let position = Vec3::new(placement.position.x as f32, placement.position.y as f32, placement.position.z as f32);
+                let rotation = Mat3::from_euler(
+                    EulerRot::ZYX,
+                    placement.rotation.yaw.angle,
+                    -placement.rotation.pitch.angle,
+                    placement.rotation.roll.angle
+                );
+                markers.push(ScenarioDebugMarker {
+                    category: ScenarioDebugMarkerCategory::Object,
+                    position,
+                    rotation,
+                    extents: Vec3::ZERO,
+                    label
+                });
+            }
+        };
+    }
+
+    add_placements!(scenario.scenery, scenario.scenery_palette);
+    add_placements!(scenario.vehicles, scenario.vehicle_palette);
+    add_placements!(scenario.machines, scenario.machine_palette);
+    add_placements!(scenario.controls, scenario.control_palette);
+
+    markers
+}
+
+/// Path of the reflection probe captured by the console, which can't collide with a tag path.
+const REFLECTION_PROBE_PATH: &str = "~reflection_probe";
+
+const DEFAULT_REFLECTION_PROBE_RESOLUTION: u32 = 256;
+
+const CONSOLE_HELP: &str = "\
+Commands apply to the selected viewport.
+  tp <x> <y> <z> [<yaw> <pitch>]  teleport (angles in degrees)
+  pos                             print the camera position and angles
+  bsp [<index>]                   print or switch the current BSP
+  lighting <mode>                 textured, fullbright, lightmap, or unlit
+  debug <mode>                    none, normals, tangent_normals, tangents,
+                                  binormals, uv, texel_density, or overdraw
+  fog <on|off>                    enable or disable fog
+  bars <off|thickness [r g b a]>  change the bars between split screen cameras
+  overbright <on|off>             double lightmaps like the original engine
+  quality [low|medium|high|ultra] print or switch the quality preset
+  fov_scaling <mode>              fixed, hor+, or vert- (how the FoV follows
+                                  the window's aspect ratio)
+  fog_override <off|r g b from to min max>
+                                  force fog regardless of the cluster
+  dof <off|focal aperture>        blur what is out of focus (aperture is a
+                                  fraction of the screen, up to 0.05;
+                                  needs --depth-of-field)
+  lod <off|bump reflection detail>
+                                  skip shader stages past these distances
+  motion_blur <intensity>         blur as the camera moves (0 to 1; 0 is off;
+                                  needs --motion-blur)
+  exposure <multiplier>           brighten or darken the scene (1 is normal;
+                                  needs --post-processing)
+  bloom <intensity>               make bright parts of the scene glow (0 is
+                                  off; needs --post-processing)
+  overlay <off|r g b a>           blend a color over the scene (0 to 1 each;
+                                  needs --post-processing)
+  letterbox <fraction>            cover the top and bottom of the screen (0 to
+                                  0.5 each; needs --post-processing)
+  probe [<resolution>]            capture a reflection probe at the camera for
+                                  shaders without cubemaps
+  reload_shaders                  read shaders from the tags directories again
+  record <file>                   record the camera's movement to a file
+  play <file>                     fly the camera along a recorded path (loops)
+  stop                            stop playing, or stop recording and save
+  map <path>                      load a cache file or a scenario from the
+                                  tags directories instead
+  clear                           clear the console";
+
+/// Run `f` on each item across all available cores, returning the results in the same order.
+fn map_in_parallel<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let worker_threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    if worker_threads <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect()
+    }
+
+    let chunk_size = items.len().div_ceil(worker_threads);
+    let f = &f;
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        threads.into_iter().flat_map(|t| t.join().unwrap()).collect()
+    })
+}
+
+fn asset_count(assets: &AddAssetsParameter) -> usize {
+    assets.bitmaps.len() + assets.fonts.len() + assets.shaders.len() + assets.skies.len() + assets.bsps.len()
+}
+
+/// Split assets into batches that can be added one after another without breaking dependencies.
+///
+/// Bitmaps and shaders are quick to add, so they are batched. Everything else is added one at a time.
+fn split_assets(assets: AddAssetsParameter) -> Vec<AddAssetsParameter> {
+    const BATCH_SIZE: usize = 32;
+
+    fn chunks<T>(mut items: Vec<T>, size: usize) -> Vec<Vec<T>> {
+        let mut chunks = Vec::new();
+        while !items.is_empty() {
+            let rest = items.split_off(size.min(items.len()));
+            chunks.push(items);
+            items = rest;
+        }
+        chunks
+    }
+
+    let AddAssetsParameter { bitmaps, fonts, shaders, skies, bsps, worker_threads } = assets;
+    let batch = || AddAssetsParameter { worker_threads, ..Default::default() };
+
+    let mut batches = Vec::new();
+    batches.extend(chunks(bitmaps, BATCH_SIZE).into_iter().map(|bitmaps| AddAssetsParameter { bitmaps, ..batch() }));
+    batches.extend(chunks(fonts, 1).into_iter().map(|fonts| AddAssetsParameter { fonts, ..batch() }));
+    batches.extend(chunks(shaders, BATCH_SIZE).into_iter().map(|shaders| AddAssetsParameter { shaders, ..batch() }));
+    batches.extend(chunks(skies, 1).into_iter().map(|skies| AddAssetsParameter { skies, ..batch() }));
+    batches.extend(chunks(bsps, 1).into_iter().map(|bsps| AddAssetsParameter { bsps, ..batch() }));
+    batches
+}
+
+/// Draw a frame with a progress bar for loading assets.
+///
+/// `path` is the asset that was last added, if any.
+fn draw_loading_screen(renderer: &mut Renderer, scenario: &str, completed: usize, total: usize, path: &str) {
+    const SCALE: f32 = 2.0;
+    const BAR_WIDTH: usize = 40;
+
+    let fraction = if total == 0 { 1.0 } else { completed as f32 / total as f32 };
+    let filled = ((fraction * BAR_WIDTH as f32) as usize).min(BAR_WIDTH);
+    let text = format!(
+        "Loading {scenario}\n\n[{}{}] {:.0}% ({completed}/{total})\n\n{path}",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        fraction * 100.0
+    );
+
+    // The built-in font is 8 pixels wide and tall
+    let resolution = renderer.get_parameters().resolution;
+    let longest_line = text.lines().map(|l| l.len()).max().unwrap_or(0) as f32;
+    let line_count = text.lines().count() as f32;
+    let position = [
+        ((resolution.width as f32 - longest_line * 8.0 * SCALE) / 2.0).max(8.0),
+        (resolution.height as f32 - line_count * 8.0 * SCALE) / 2.0
+    ];
+    renderer.debug_draw_text_2d(position, &text, SCALE, [1.0, 1.0, 1.0, 1.0], Duration::ZERO);
+
+    // Nothing else is drawn until loading is done, so it doesn't matter if this frame is skipped
+    if let Err(e) = renderer.draw_frame() {
+        eprintln!("Failed to draw the loading screen: {e}");
+    }
+}
+
+/// Resolution of --screenshot-at screenshots if --resolution is not given.
+const DEFAULT_HEADLESS_RESOLUTION: Resolution = Resolution { width: 1920, height: 1080 };
+
+/// Get the title of the window when `bsp` is the current BSP.
+fn bsp_window_title(window_title: &str, bsp: Option<&str>) -> String {
+    match bsp {
+        Some(bsp) => format!("{window_title} - {bsp}"),
+        None => window_title.to_owned()
+    }
 }
 
 fn parse_resolution(resolution_string: String) -> Result<Resolution, String> {
@@ -0,0 +1,68 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use ringhopper::definitions::Bitmap;
+
+/// Name of the resource map that holds external bitmap data.
+pub const BITMAPS_MAP_FILE_NAME: &str = "bitmaps.map";
+
+/// Type in the header of a bitmaps resource map (sounds are 2, and localization is 3).
+const BITMAPS_RESOURCE_MAP_TYPE: u32 = 1;
+
+/// A bitmaps.map file that cache files can reference pixel data in instead of storing it themselves.
+pub struct BitmapsResourceMap {
+    path: PathBuf,
+    file: File,
+    length: u64
+}
+
+impl BitmapsResourceMap {
+    /// Open the bitmaps.map in `directory`.
+    pub fn open(directory: &Path) -> Result<BitmapsResourceMap, String> {
+        let path = directory.join(BITMAPS_MAP_FILE_NAME);
+        let mut file = File::open(&path).map_err(|e| format!("Can't open {}: {e}", path.display()))?;
+        let length = file.metadata().map_err(|e| format!("Can't read {}: {e}", path.display()))?.len();
+
+        let mut resource_type = [0u8; 4];
+        file.read_exact(&mut resource_type).map_err(|e| format!("Can't read {}: {e}", path.display()))?;
+        let resource_type = u32::from_le_bytes(resource_type);
+        if resource_type != BITMAPS_RESOURCE_MAP_TYPE {
+            return Err(format!("{} is not a bitmaps resource map (type {resource_type})", path.display()))
+        }
+
+        Ok(BitmapsResourceMap { path, file, length })
+    }
+
+    /// Read `size` bytes at `offset`.
+    fn read(&mut self, offset: u64, size: usize) -> Result<Vec<u8>, String> {
+        if !offset.checked_add(size as u64).is_some_and(|end| end <= self.length) {
+            return Err(format!("Can't read {size} bytes from {offset} in {} ({} bytes)", self.path.display(), self.length))
+        }
+
+        let mut data = vec![0u8; size];
+        self.file.seek(SeekFrom::Start(offset)).map_err(|e| format!("Can't seek in {}: {e}", self.path.display()))?;
+        self.file.read_exact(&mut data).map_err(|e| format!("Can't read {}: {e}", self.path.display()))?;
+        Ok(data)
+    }
+}
+
+/// Check if any of the bitmap's data is stored in bitmaps.map.
+pub fn has_external_bitmap_data(bitmap: &Bitmap) -> bool {
+    bitmap.bitmap_data.items.iter().any(|b| b.flags.external)
+}
+
+/// Copy the bitmap's external data out of bitmaps.map and onto the end of its pixel data, so it can be read as if it
+/// had been in the cache file.
+pub fn resolve_external_bitmap_data(bitmap: &mut Bitmap, resource_map: &mut BitmapsResourceMap) -> Result<(), String> {
+    for b in bitmap.bitmap_data.items.iter_mut().filter(|b| b.flags.external) {
+        let data = resource_map.read(b.pixel_data_offset as u64, b.pixel_data_size as usize)?;
+        let bytes = &mut bitmap.processed_pixel_data.bytes;
+        b.pixel_data_offset = bytes
+            .len()
+            .try_into()
+            .map_err(|_| "pixel data is too large after resolving external data".to_owned())?;
+        bytes.extend_from_slice(&data);
+        b.flags.external = false;
+    }
+    Ok(())
+}
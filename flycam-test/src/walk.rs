@@ -0,0 +1,213 @@
+use glam::{Vec2, Vec3};
+use ringhopper::definitions::ScenarioStructureBSP;
+use ringhopper::tag::scenario_structure_bsp::get_uncompressed_vertices_for_bsp_material;
+
+/// Height of the camera above the ground while walking.
+const EYE_HEIGHT: f32 = 0.62;
+
+/// Radius of the sphere around the camera's body that is kept out of walls.
+const BODY_RADIUS: f32 = 0.2;
+
+/// Height of ledges that can be walked onto without jumping.
+const STEP_HEIGHT: f32 = 0.25;
+
+/// Downward acceleration in world units per second squared (a world unit is 10 feet, so this is about 9.8 m/s²).
+const GRAVITY: f32 = 3.2;
+
+/// Upward speed at the start of a jump, in world units per second.
+const JUMP_SPEED: f32 = 1.5;
+
+/// Fastest a camera can fall, in world units per second.
+const TERMINAL_SPEED: f32 = 10.0;
+
+/// Render geometry of a BSP that walking cameras collide with.
+#[derive(Default)]
+pub struct WalkGeometry {
+    triangles: Vec<[Vec3; 3]>
+}
+
+impl WalkGeometry {
+    /// Get every triangle of the BSP's render geometry.
+    ///
+    /// Materials with broken vertices or surface indices are skipped, since the renderer refuses to load those anyway.
+    pub fn from_bsp(bsp: &ScenarioStructureBSP) -> WalkGeometry {
+        let mut triangles = Vec::new();
+        for lightmap in bsp.lightmaps.items.iter() {
+            for material in lightmap.materials.items.iter() {
+                let Ok((vertices, _)) = get_uncompressed_vertices_for_bsp_material(material) else {
+                    continue
+                };
+                let vertices: Vec<Vec3> = vertices
+                    .map(|v| Vec3::new(v.position.x as f32, v.position.y as f32, v.position.z as f32))
+                    .collect();
+
+                let first = material.surfaces as usize;
+                let surfaces = first
+                    .checked_add(material.surface_count as usize)
+                    .and_then(|end| bsp.surfaces.items.get(first..end));
+                let Some(surfaces) = surfaces else {
+                    continue
+                };
+
+                for surface in surfaces {
+                    let corners = [surface.vertex0_index, surface.vertex1_index, surface.vertex2_index]
+                        .map(|index| index.and_then(|i| vertices.get(i as usize).copied()));
+                    if let [Some(a), Some(b), Some(c)] = corners {
+                        triangles.push([a, b, c]);
+                    }
+                }
+            }
+        }
+        WalkGeometry { triangles }
+    }
+
+    /// Get the height of the highest triangle directly below `position`, if any.
+    fn ground_below(&self, position: Vec3) -> Option<f32> {
+        self.triangles
+            .iter()
+            .filter_map(|t| height_at(t, position.truncate()))
+            .filter(|z| *z <= position.z)
+            .max_by(f32::total_cmp)
+    }
+
+    /// Move a sphere out of every triangle it overlaps.
+    fn push_out(&self, mut center: Vec3, radius: f32) -> Vec3 {
+        for triangle in self.triangles.iter() {
+            let offset = center - closest_point_on_triangle(triangle, center);
+            let distance = offset.length();
+            if distance > f32::EPSILON && distance < radius {
+                center += offset * ((radius - distance) / distance);
+            }
+        }
+        center
+    }
+}
+
+/// Walk mode for each viewport, and the geometry walking cameras collide with.
+#[derive(Default)]
+pub struct WalkState {
+    geometry: WalkGeometry,
+
+    /// Index of the BSP that `geometry` was made from.
+    bsp_index: Option<usize>,
+
+    /// Vertical speed of each viewport's camera, or `None` if it is flying.
+    vertical_speeds: [Option<f32>; 4]
+}
+
+impl WalkState {
+    pub fn is_walking(&self, viewport: usize) -> bool {
+        self.vertical_speeds[viewport].is_some()
+    }
+
+    pub fn set_walking(&mut self, viewport: usize, walking: bool) {
+        self.vertical_speeds[viewport] = walking.then_some(0.0);
+    }
+
+    /// Check if geometry needs to be made for the BSP at `bsp_index` (i.e. a camera is walking and the geometry is
+    /// from a different BSP).
+    pub fn needs_geometry(&self, bsp_index: usize) -> bool {
+        self.vertical_speeds.iter().any(Option::is_some) && self.bsp_index != Some(bsp_index)
+    }
+
+    pub fn set_geometry(&mut self, bsp_index: usize, geometry: WalkGeometry) {
+        self.geometry = geometry;
+        self.bsp_index = Some(bsp_index);
+    }
+
+    /// Move a walking camera `movement` along the ground and apply gravity, returning its new position.
+    ///
+    /// If `jump` is set and the camera is on the ground, it starts a jump. Cameras that are flying are not moved.
+    pub fn step(&mut self, viewport: usize, position: Vec3, movement: Vec2, jump: bool, seconds: f32) -> Vec3 {
+        let Some(vertical_speed) = self.vertical_speeds[viewport].as_mut() else {
+            return position
+        };
+
+        // Keep the body out of walls, while keeping it high enough to not catch on anything low enough to step onto
+        let feet = position - Vec3::Z * EYE_HEIGHT;
+        let body = feet + movement.extend(STEP_HEIGHT + BODY_RADIUS);
+        let body = self.geometry.push_out(body, BODY_RADIUS);
+        let mut feet = Vec3::new(body.x, body.y, feet.z);
+
+        let was_on_ground = *vertical_speed == 0.0;
+        *vertical_speed = (*vertical_speed - GRAVITY * seconds).max(-TERMINAL_SPEED);
+        feet.z += *vertical_speed * seconds;
+
+        // Land on the ground, stepping up onto ledges and staying on the ground when walking down slopes and steps
+        let ground = self.geometry.ground_below(Vec3::new(feet.x, feet.y, position.z - EYE_HEIGHT + STEP_HEIGHT));
+        if let Some(ground) = ground.filter(|g| feet.z <= *g || (was_on_ground && feet.z - *g <= STEP_HEIGHT)) {
+            feet.z = ground;
+            *vertical_speed = if jump { JUMP_SPEED } else { 0.0 };
+        }
+
+        feet + Vec3::Z * EYE_HEIGHT
+    }
+}
+
+/// Get the height of the triangle at `point` on the XY plane, if it is within the triangle.
+fn height_at(triangle: &[Vec3; 3], point: Vec2) -> Option<f32> {
+    let [a, b, c] = *triangle;
+    let normal = (b - a).cross(c - a);
+    if normal.z.abs() <= f32::EPSILON {
+        return None
+    }
+
+    // The point is inside if it is on the same side of every edge
+    let side = |from: Vec3, to: Vec3| (to.truncate() - from.truncate()).perp_dot(point - from.truncate());
+    let sides = [side(a, b), side(b, c), side(c, a)];
+    if !(sides.iter().all(|s| *s >= 0.0) || sides.iter().all(|s| *s <= 0.0)) {
+        return None
+    }
+
+    Some(a.z - (normal.x * (point.x - a.x) + normal.y * (point.y - a.y)) / normal.z)
+}
+
+/// Get the point on a triangle that is closest to `point`.
+fn closest_point_on_triangle(triangle: &[Vec3; 3], point: Vec3) -> Vec3 {
+    let [a, b, c] = *triangle;
+    let ab = b - a;
+    let ac = c - a;
+
+    // Check each vertex and edge region, then the face (see Ericson, Real-Time Collision Detection, 5.1.5)
+    let ap = point - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a
+    }
+
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3))
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6))
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)))
+    }
+
+    let denominator = va + vb + vc;
+    if denominator.abs() <= f32::EPSILON {
+        return a
+    }
+    a + ab * (vb / denominator) + ac * (vc / denominator)
+}
@@ -0,0 +1,235 @@
+#ifndef MAGELLANICUS_H
+#define MAGELLANICUS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MagellanicusRenderer MagellanicusRenderer;
+
+typedef enum MagellanicusResult {
+    MAGELLANICUS_RESULT_OK = 0,
+    MAGELLANICUS_RESULT_DATA_ERROR = 1,
+    MAGELLANICUS_RESULT_GRAPHICS_API_ERROR = 2,
+    MAGELLANICUS_RESULT_INVALID_ARGUMENT = 3,
+
+    /* The device was lost; call magellanicus_renderer_recover_from_device_lost and re-add all data */
+    MAGELLANICUS_RESULT_DEVICE_LOST = 4
+} MagellanicusResult;
+
+typedef struct MagellanicusRendererParameters {
+    uint32_t width;
+    uint32_t height;
+    uint32_t number_of_viewports;
+    bool vsync;
+
+    /* Samples per pixel (1, 2, 4, 8, 16, 32, or 64) */
+    uint32_t msaa;
+
+    /* Anisotropic filtering level; 0 disables it */
+    float anisotropic_filtering;
+    float render_scale;
+
+    /* Lower MSAA and anisotropic filtering to what the device supports instead of failing */
+    bool clamp_to_device;
+
+    /* Enable screen-space ambient occlusion */
+    bool ssao;
+
+    /* Reflect the scene in large water surfaces; water is not drawn otherwise */
+    bool water_reflections;
+
+    /* Allow viewports to blur what is out of focus */
+    bool depth_of_field;
+
+    /* Allow viewports to blur the scene as the camera moves */
+    bool motion_blur;
+
+    /* Smooth jagged edges by blending each frame with the previous ones (temporal anti-aliasing) */
+    bool taa;
+
+    /* Allow viewports to have exposure, bloom, color overlay, and letterbox */
+    bool post_processing;
+
+    /* Number of the largest mipmaps to leave out of bitmaps added afterward, to save video memory */
+    uint32_t skipped_mip_levels;
+} MagellanicusRendererParameters;
+
+typedef enum MagellanicusPlatform {
+    /* window is the HWND and display is the HINSTANCE */
+    MAGELLANICUS_PLATFORM_WIN32 = 0,
+
+    /* window_id is the Window and display is the Display * */
+    MAGELLANICUS_PLATFORM_XLIB = 1,
+
+    /* window is the wl_surface * and display is the wl_display * */
+    MAGELLANICUS_PLATFORM_WAYLAND = 2,
+
+    /* window is the NSView * */
+    MAGELLANICUS_PLATFORM_APPKIT = 3
+} MagellanicusPlatform;
+
+typedef struct MagellanicusWindow {
+    MagellanicusPlatform platform;
+    void *window;
+    uint64_t window_id;
+    void *display;
+    int screen;
+} MagellanicusWindow;
+
+typedef enum MagellanicusQualityPreset {
+    MAGELLANICUS_QUALITY_PRESET_LOW = 0,
+    MAGELLANICUS_QUALITY_PRESET_MEDIUM = 1,
+    MAGELLANICUS_QUALITY_PRESET_HIGH = 2,
+    MAGELLANICUS_QUALITY_PRESET_ULTRA = 3,
+
+    /* Anything else; applying it leaves the parameters as they are */
+    MAGELLANICUS_QUALITY_PRESET_CUSTOM = 4
+} MagellanicusQualityPreset;
+
+typedef enum MagellanicusLightingMode {
+    /* Draw materials normally, lit by lightmaps */
+    MAGELLANICUS_LIGHTING_MODE_TEXTURED_LIGHTMAPPED = 0,
+
+    /* Draw materials as if their lightmaps were white */
+    MAGELLANICUS_LIGHTING_MODE_FULLBRIGHT = 1,
+
+    /* Draw only lightmaps; materials that aren't lightmapped are not drawn */
+    MAGELLANICUS_LIGHTING_MODE_LIGHTMAP_ONLY = 2,
+
+    /* Draw only the base map of each material */
+    MAGELLANICUS_LIGHTING_MODE_UNLIT = 3
+} MagellanicusLightingMode;
+
+typedef struct MagellanicusCamera {
+    float fov;
+    float position[3];
+    float rotation[3];
+    MagellanicusLightingMode lighting_mode;
+    bool fog;
+} MagellanicusCamera;
+
+typedef enum MagellanicusBitmapType {
+    MAGELLANICUS_BITMAP_TYPE_2D = 0,
+    MAGELLANICUS_BITMAP_TYPE_3D = 1,
+    MAGELLANICUS_BITMAP_TYPE_CUBEMAP = 2
+} MagellanicusBitmapType;
+
+typedef enum MagellanicusBitmapFormat {
+    MAGELLANICUS_BITMAP_FORMAT_DXT1 = 0,
+    MAGELLANICUS_BITMAP_FORMAT_DXT3 = 1,
+    MAGELLANICUS_BITMAP_FORMAT_DXT5 = 2,
+    MAGELLANICUS_BITMAP_FORMAT_BC7 = 3,
+    MAGELLANICUS_BITMAP_FORMAT_A8R8G8B8 = 4,
+    MAGELLANICUS_BITMAP_FORMAT_X8R8G8B8 = 5,
+    MAGELLANICUS_BITMAP_FORMAT_R5G6B5 = 6,
+    MAGELLANICUS_BITMAP_FORMAT_A1R5G5B5 = 7,
+    MAGELLANICUS_BITMAP_FORMAT_A4R4G4B4 = 8,
+    MAGELLANICUS_BITMAP_FORMAT_A8 = 9,
+    MAGELLANICUS_BITMAP_FORMAT_Y8 = 10,
+    MAGELLANICUS_BITMAP_FORMAT_AY8 = 11,
+    MAGELLANICUS_BITMAP_FORMAT_A8Y8 = 12,
+    MAGELLANICUS_BITMAP_FORMAT_P8 = 13,
+    MAGELLANICUS_BITMAP_FORMAT_R32G32B32A32_SFLOAT = 14,
+    MAGELLANICUS_BITMAP_FORMAT_B4G4R4A4 = 15,
+    MAGELLANICUS_BITMAP_FORMAT_A8B8G8R8 = 16
+} MagellanicusBitmapFormat;
+
+typedef struct MagellanicusAddBitmapBitmapParameter {
+    MagellanicusBitmapFormat format;
+    MagellanicusBitmapType bitmap_type;
+
+    /* Only used for 3D textures */
+    uint32_t depth;
+    uint32_t width;
+    uint32_t height;
+    uint32_t mipmap_count;
+    const uint8_t *data;
+    size_t data_length;
+} MagellanicusAddBitmapBitmapParameter;
+
+typedef struct MagellanicusBitmapSprite {
+    size_t bitmap;
+    float top;
+    float left;
+    float bottom;
+    float right;
+} MagellanicusBitmapSprite;
+
+typedef struct MagellanicusBitmapSequence {
+    /* If sprites is NULL, this is a sequence of count bitmaps starting at first; otherwise, it is a sequence of
+     * sprite_count sprites. */
+    size_t first;
+    size_t count;
+    const MagellanicusBitmapSprite *sprites;
+    size_t sprite_count;
+} MagellanicusBitmapSequence;
+
+typedef struct MagellanicusAddBitmapParameter {
+    const MagellanicusAddBitmapBitmapParameter *bitmaps;
+    size_t bitmap_count;
+    const MagellanicusBitmapSequence *sequences;
+    size_t sequence_count;
+} MagellanicusAddBitmapParameter;
+
+typedef struct MagellanicusCapturedFrame {
+    uint32_t width;
+    uint32_t height;
+
+    /* RGBA8 pixel data; free with magellanicus_captured_frame_free */
+    uint8_t *data;
+    size_t data_length;
+} MagellanicusCapturedFrame;
+
+typedef struct MagellanicusFrameStatistics {
+    size_t draw_calls;
+    size_t triangles;
+    size_t pipeline_binds;
+    size_t descriptor_sets;
+    size_t geometries_drawn;
+    size_t geometries_culled;
+} MagellanicusFrameStatistics;
+
+/* Get the last error that occurred on this thread, or NULL. Valid until the next call on this thread. */
+const char *magellanicus_last_error(void);
+
+MagellanicusRendererParameters magellanicus_renderer_parameters_default(void);
+
+/* Sets MSAA, anisotropic filtering, render scale, water reflections, and SSAO; anisotropic filtering can only be set
+ * when the renderer is made */
+MagellanicusResult magellanicus_renderer_parameters_apply_quality_preset(MagellanicusRendererParameters *parameters, MagellanicusQualityPreset preset);
+
+MagellanicusResult magellanicus_renderer_new(const MagellanicusWindow *window, const MagellanicusRendererParameters *parameters, MagellanicusRenderer **renderer);
+MagellanicusResult magellanicus_renderer_new_headless(const MagellanicusRendererParameters *parameters, MagellanicusRenderer **renderer);
+void magellanicus_renderer_free(MagellanicusRenderer *renderer);
+MagellanicusResult magellanicus_renderer_reset(MagellanicusRenderer *renderer);
+MagellanicusResult magellanicus_renderer_get_parameters(MagellanicusRenderer *renderer, MagellanicusRendererParameters *parameters);
+
+MagellanicusResult magellanicus_renderer_add_bitmap(MagellanicusRenderer *renderer, const char *path, const MagellanicusAddBitmapParameter *bitmap);
+MagellanicusResult magellanicus_renderer_replace_bitmap(MagellanicusRenderer *renderer, const char *path, const MagellanicusAddBitmapParameter *bitmap);
+MagellanicusResult magellanicus_renderer_set_current_bsp(MagellanicusRenderer *renderer, const char *path);
+
+MagellanicusResult magellanicus_renderer_set_camera(MagellanicusRenderer *renderer, size_t viewport, const MagellanicusCamera *camera);
+MagellanicusResult magellanicus_renderer_get_camera(MagellanicusRenderer *renderer, size_t viewport, MagellanicusCamera *camera);
+
+/* swapchain_ok is set to false if the swapchain needs to be rebuilt; it stays true if the window is minimized */
+MagellanicusResult magellanicus_renderer_draw_frame(MagellanicusRenderer *renderer, bool *swapchain_ok);
+MagellanicusResult magellanicus_renderer_get_frame_statistics(MagellanicusRenderer *renderer, MagellanicusFrameStatistics *statistics);
+MagellanicusResult magellanicus_renderer_set_resolution(MagellanicusRenderer *renderer, uint32_t width, uint32_t height);
+MagellanicusResult magellanicus_renderer_set_quality_preset(MagellanicusRenderer *renderer, MagellanicusQualityPreset preset);
+MagellanicusResult magellanicus_renderer_get_quality_preset(MagellanicusRenderer *renderer, MagellanicusQualityPreset *preset);
+MagellanicusResult magellanicus_renderer_rebuild_swapchain(MagellanicusRenderer *renderer, const MagellanicusRendererParameters *parameters);
+MagellanicusResult magellanicus_renderer_recover_from_device_lost(MagellanicusRenderer *renderer, const MagellanicusRendererParameters *parameters);
+
+MagellanicusResult magellanicus_renderer_capture_frame(MagellanicusRenderer *renderer, MagellanicusCapturedFrame *frame);
+void magellanicus_captured_frame_free(MagellanicusCapturedFrame *frame);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
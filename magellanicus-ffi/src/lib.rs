@@ -0,0 +1,893 @@
+//! C bindings for magellanicus.
+//!
+//! See `include/magellanicus.h` for the C declarations of everything exported here.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_ulong, c_void, CStr, CString};
+use std::ptr::null;
+use std::slice;
+use magellanicus::error::{DeviceError, Error, MResult};
+use magellanicus::glam::Vec3;
+use magellanicus::renderer::{AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, BitmapFormat, BitmapSprite, BitmapType, Camera, FrameStatistics, FrameStatus, LightingMode, QualityPreset, Renderer, RendererParameters, Resolution, MSAA};
+use raw_window_handle::{AppKitDisplayHandle, AppKitWindowHandle, HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle, Win32WindowHandle, WindowsDisplayHandle, XlibDisplayHandle, XlibWindowHandle};
+
+/// Opaque renderer handle.
+pub struct MagellanicusRenderer {
+    renderer: Renderer
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MagellanicusResult {
+    Ok = 0,
+    DataError = 1,
+    GraphicsAPIError = 2,
+    InvalidArgument = 3,
+
+    /// The device was lost; call `magellanicus_renderer_recover_from_device_lost` and re-add all data.
+    DeviceLost = 4
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MagellanicusRendererParameters {
+    pub width: u32,
+    pub height: u32,
+    pub number_of_viewports: u32,
+    pub vsync: bool,
+
+    /// Samples per pixel (1, 2, 4, 8, 16, 32, or 64)
+    pub msaa: u32,
+
+    /// Anisotropic filtering level; 0 disables it
+    pub anisotropic_filtering: f32,
+    pub render_scale: f32,
+
+    /// Lower MSAA and anisotropic filtering to what the device supports instead of failing
+    pub clamp_to_device: bool,
+
+    /// Enable screen-space ambient occlusion
+    pub ssao: bool,
+
+    /// Reflect the scene in large water surfaces; water is not drawn otherwise
+    pub water_reflections: bool,
+
+    /// Allow viewports to blur what is out of focus
+    pub depth_of_field: bool,
+
+    /// Allow viewports to blur the scene as the camera moves
+    pub motion_blur: bool,
+
+    /// Smooth jagged edges by blending each frame with the previous ones (temporal anti-aliasing)
+    pub taa: bool,
+
+    /// Allow viewports to have exposure, bloom, color overlay, and letterbox
+    pub post_processing: bool,
+
+    /// Number of the largest mipmaps to leave out of bitmaps added afterward, to save video memory
+    pub skipped_mip_levels: u32
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum MagellanicusPlatform {
+    /// `window` is the HWND and `display` is the HINSTANCE
+    Win32 = 0,
+
+    /// `window_id` is the Window and `display` is the Display *
+    Xlib = 1,
+
+    /// `window` is the wl_surface * and `display` is the wl_display *
+    Wayland = 2,
+
+    /// `window` is the NSView *
+    AppKit = 3
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MagellanicusWindow {
+    pub platform: MagellanicusPlatform,
+    pub window: *mut c_void,
+    pub window_id: u64,
+    pub display: *mut c_void,
+    pub screen: c_int
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum MagellanicusQualityPreset {
+    Low = 0,
+    Medium = 1,
+    High = 2,
+    Ultra = 3,
+
+    /// Anything else; applying it leaves the parameters as they are
+    Custom = 4
+}
+
+impl MagellanicusQualityPreset {
+    fn to_quality_preset(self) -> QualityPreset {
+        match self {
+            MagellanicusQualityPreset::Low => QualityPreset::Low,
+            MagellanicusQualityPreset::Medium => QualityPreset::Medium,
+            MagellanicusQualityPreset::High => QualityPreset::High,
+            MagellanicusQualityPreset::Ultra => QualityPreset::Ultra,
+            MagellanicusQualityPreset::Custom => QualityPreset::Custom
+        }
+    }
+
+    fn from_quality_preset(preset: QualityPreset) -> Self {
+        match preset {
+            QualityPreset::Low => MagellanicusQualityPreset::Low,
+            QualityPreset::Medium => MagellanicusQualityPreset::Medium,
+            QualityPreset::High => MagellanicusQualityPreset::High,
+            QualityPreset::Ultra => MagellanicusQualityPreset::Ultra,
+            QualityPreset::Custom => MagellanicusQualityPreset::Custom
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum MagellanicusLightingMode {
+    TexturedLightmapped = 0,
+    Fullbright = 1,
+    LightmapOnly = 2,
+    Unlit = 3
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MagellanicusCamera {
+    pub fov: f32,
+    pub position: [f32; 3],
+    pub rotation: [f32; 3],
+    pub lighting_mode: MagellanicusLightingMode,
+    pub fog: bool
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum MagellanicusBitmapType {
+    Dim2D = 0,
+    Dim3D = 1,
+    Cubemap = 2
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum MagellanicusBitmapFormat {
+    DXT1 = 0,
+    DXT3 = 1,
+    DXT5 = 2,
+    BC7 = 3,
+    A8R8G8B8 = 4,
+    X8R8G8B8 = 5,
+    R5G6B5 = 6,
+    A1R5G5B5 = 7,
+    A4R4G4B4 = 8,
+    A8 = 9,
+    Y8 = 10,
+    AY8 = 11,
+    A8Y8 = 12,
+    P8 = 13,
+    R32G32B32A32SFloat = 14,
+    B4G4R4A4 = 15,
+    A8B8G8R8 = 16
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MagellanicusAddBitmapBitmapParameter {
+    pub format: MagellanicusBitmapFormat,
+    pub bitmap_type: MagellanicusBitmapType,
+
+    /// Only used for 3D textures
+    pub depth: u32,
+    pub width: u32,
+    pub height: u32,
+    pub mipmap_count: u32,
+    pub data: *const u8,
+    pub data_length: usize
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MagellanicusBitmapSprite {
+    pub bitmap: usize,
+    pub top: f32,
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MagellanicusBitmapSequence {
+    /// If `sprites` is null, this is a sequence of `count` bitmaps starting at `first`; otherwise, it is a sequence of
+    /// `sprite_count` sprites.
+    pub first: usize,
+    pub count: usize,
+    pub sprites: *const MagellanicusBitmapSprite,
+    pub sprite_count: usize
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MagellanicusAddBitmapParameter {
+    pub bitmaps: *const MagellanicusAddBitmapBitmapParameter,
+    pub bitmap_count: usize,
+    pub sequences: *const MagellanicusBitmapSequence,
+    pub sequence_count: usize
+}
+
+#[repr(C)]
+pub struct MagellanicusCapturedFrame {
+    pub width: u32,
+    pub height: u32,
+
+    /// RGBA8 pixel data; free with `magellanicus_captured_frame_free`
+    pub data: *mut u8,
+    pub data_length: usize
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MagellanicusFrameStatistics {
+    pub draw_calls: usize,
+    pub triangles: usize,
+    pub pipeline_binds: usize,
+    pub descriptor_sets: usize,
+    pub geometries_drawn: usize,
+    pub geometries_culled: usize
+}
+
+impl MagellanicusFrameStatistics {
+    fn from_frame_statistics(statistics: &FrameStatistics) -> Self {
+        Self {
+            draw_calls: statistics.draw_calls,
+            triangles: statistics.triangles,
+            pipeline_binds: statistics.pipeline_binds,
+            descriptor_sets: statistics.descriptor_sets,
+            geometries_drawn: statistics.geometries_drawn,
+            geometries_culled: statistics.geometries_culled
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: String) {
+    let error = CString::new(error.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(error));
+}
+
+fn handle_result<T>(result: MResult<T>, on_success: impl FnOnce(T)) -> MagellanicusResult {
+    match result {
+        Ok(n) => {
+            on_success(n);
+            MagellanicusResult::Ok
+        },
+        Err(e) => {
+            set_last_error(e.to_string());
+            match e {
+                Error::Device(DeviceError::DeviceLost) => MagellanicusResult::DeviceLost,
+                Error::Device(_) | Error::Swapchain(_) => MagellanicusResult::GraphicsAPIError,
+                Error::Bitmap(_) | Error::Shader(_) | Error::BSP(_) | Error::Data(_) => MagellanicusResult::DataError
+            }
+        }
+    }
+}
+
+fn invalid_argument(error: &str) -> MagellanicusResult {
+    set_last_error(error.to_owned());
+    MagellanicusResult::InvalidArgument
+}
+
+unsafe fn read_string<'a>(string: *const c_char) -> Result<&'a str, MagellanicusResult> {
+    if string.is_null() {
+        return Err(invalid_argument("string is null"))
+    }
+    CStr::from_ptr(string).to_str().map_err(|_| invalid_argument("string is not valid UTF-8"))
+}
+
+unsafe fn read_slice<'a, T>(data: *const T, length: usize) -> Result<&'a [T], MagellanicusResult> {
+    if length == 0 {
+        return Ok(&[])
+    }
+    if data.is_null() {
+        return Err(invalid_argument("array is null but has a non-zero length"))
+    }
+    Ok(slice::from_raw_parts(data, length))
+}
+
+impl MagellanicusRendererParameters {
+    fn to_renderer_parameters(&self) -> Result<RendererParameters, MagellanicusResult> {
+        let msaa = match self.msaa {
+            0 | 1 => MSAA::NoMSAA,
+            2 => MSAA::MSAA2x,
+            4 => MSAA::MSAA4x,
+            8 => MSAA::MSAA8x,
+            16 => MSAA::MSAA16x,
+            32 => MSAA::MSAA32x,
+            64 => MSAA::MSAA64x,
+            _ => return Err(invalid_argument("msaa must be 1, 2, 4, 8, 16, 32, or 64"))
+        };
+
+        Ok(RendererParameters {
+            resolution: Resolution { width: self.width, height: self.height },
+            number_of_viewports: self.number_of_viewports as usize,
+            vsync: self.vsync,
+            msaa,
+            anisotropic_filtering: if self.anisotropic_filtering > 0.0 { Some(self.anisotropic_filtering) } else { None },
+            render_scale: self.render_scale,
+            clamp_to_device: self.clamp_to_device,
+            ssao: self.ssao,
+            water_reflections: self.water_reflections,
+            depth_of_field: self.depth_of_field,
+            motion_blur: self.motion_blur,
+            taa: self.taa,
+            post_processing: self.post_processing,
+            skipped_mip_levels: self.skipped_mip_levels
+        })
+    }
+
+    fn from_renderer_parameters(parameters: &RendererParameters) -> Self {
+        Self {
+            width: parameters.resolution.width,
+            height: parameters.resolution.height,
+            number_of_viewports: parameters.number_of_viewports as u32,
+            vsync: parameters.vsync,
+            msaa: parameters.msaa as u32,
+            anisotropic_filtering: parameters.anisotropic_filtering.unwrap_or(0.0),
+            render_scale: parameters.render_scale,
+            clamp_to_device: parameters.clamp_to_device,
+            ssao: parameters.ssao,
+            water_reflections: parameters.water_reflections,
+            depth_of_field: parameters.depth_of_field,
+            motion_blur: parameters.motion_blur,
+            taa: parameters.taa,
+            post_processing: parameters.post_processing,
+            skipped_mip_levels: parameters.skipped_mip_levels
+        }
+    }
+}
+
+impl From<MagellanicusBitmapFormat> for BitmapFormat {
+    fn from(value: MagellanicusBitmapFormat) -> Self {
+        match value {
+            MagellanicusBitmapFormat::DXT1 => BitmapFormat::DXT1,
+            MagellanicusBitmapFormat::DXT3 => BitmapFormat::DXT3,
+            MagellanicusBitmapFormat::DXT5 => BitmapFormat::DXT5,
+            MagellanicusBitmapFormat::BC7 => BitmapFormat::BC7,
+            MagellanicusBitmapFormat::A8R8G8B8 => BitmapFormat::A8R8G8B8,
+            MagellanicusBitmapFormat::X8R8G8B8 => BitmapFormat::X8R8G8B8,
+            MagellanicusBitmapFormat::R5G6B5 => BitmapFormat::R5G6B5,
+            MagellanicusBitmapFormat::A1R5G5B5 => BitmapFormat::A1R5G5B5,
+            MagellanicusBitmapFormat::A4R4G4B4 => BitmapFormat::A4R4G4B4,
+            MagellanicusBitmapFormat::A8 => BitmapFormat::A8,
+            MagellanicusBitmapFormat::Y8 => BitmapFormat::Y8,
+            MagellanicusBitmapFormat::AY8 => BitmapFormat::AY8,
+            MagellanicusBitmapFormat::A8Y8 => BitmapFormat::A8Y8,
+            MagellanicusBitmapFormat::P8 => BitmapFormat::P8,
+            MagellanicusBitmapFormat::R32G32B32A32SFloat => BitmapFormat::R32G32B32A32SFloat,
+            MagellanicusBitmapFormat::B4G4R4A4 => BitmapFormat::B4G4R4A4,
+            MagellanicusBitmapFormat::A8B8G8R8 => BitmapFormat::A8B8G8R8
+        }
+    }
+}
+
+impl MagellanicusAddBitmapParameter {
+    unsafe fn to_add_bitmap_parameter(&self) -> Result<AddBitmapParameter, MagellanicusResult> {
+        let bitmaps = read_slice(self.bitmaps, self.bitmap_count)?
+            .iter()
+            .map(|b| -> Result<AddBitmapBitmapParameter, MagellanicusResult> {
+                Ok(AddBitmapBitmapParameter {
+                    format: b.format.into(),
+                    bitmap_type: match b.bitmap_type {
+                        MagellanicusBitmapType::Dim2D => BitmapType::Dim2D,
+                        MagellanicusBitmapType::Dim3D => BitmapType::Dim3D { depth: b.depth },
+                        MagellanicusBitmapType::Cubemap => BitmapType::Cubemap
+                    },
+                    resolution: Resolution { width: b.width, height: b.height },
+                    mipmap_count: b.mipmap_count,
+                    data: read_slice(b.data, b.data_length)?.to_vec()
+                })
+            })
+            .collect::<Result<Vec<_>, MagellanicusResult>>()?;
+
+        let sequences = read_slice(self.sequences, self.sequence_count)?
+            .iter()
+            .map(|s| -> Result<AddBitmapSequenceParameter, MagellanicusResult> {
+                if s.sprites.is_null() {
+                    return Ok(AddBitmapSequenceParameter::Bitmap { first: s.first, count: s.count })
+                }
+                let sprites = read_slice(s.sprites, s.sprite_count)?
+                    .iter()
+                    .map(|s| BitmapSprite { bitmap: s.bitmap, top: s.top, left: s.left, bottom: s.bottom, right: s.right })
+                    .collect();
+                Ok(AddBitmapSequenceParameter::Sprites { sprites })
+            })
+            .collect::<Result<Vec<_>, MagellanicusResult>>()?;
+
+        Ok(AddBitmapParameter { bitmaps, sequences })
+    }
+}
+
+struct FFIWindow {
+    window: RawWindowHandle,
+    display: RawDisplayHandle
+}
+
+unsafe impl HasRawWindowHandle for FFIWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.window
+    }
+}
+
+unsafe impl HasRawDisplayHandle for FFIWindow {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        self.display
+    }
+}
+
+impl From<&MagellanicusWindow> for FFIWindow {
+    fn from(value: &MagellanicusWindow) -> Self {
+        match value.platform {
+            MagellanicusPlatform::Win32 => {
+                let mut window = Win32WindowHandle::empty();
+                window.hwnd = value.window;
+                window.hinstance = value.display;
+                Self { window: RawWindowHandle::Win32(window), display: RawDisplayHandle::Windows(WindowsDisplayHandle::empty()) }
+            },
+            MagellanicusPlatform::Xlib => {
+                let mut window = XlibWindowHandle::empty();
+                window.window = value.window_id as c_ulong;
+                let mut display = XlibDisplayHandle::empty();
+                display.display = value.display;
+                display.screen = value.screen;
+                Self { window: RawWindowHandle::Xlib(window), display: RawDisplayHandle::Xlib(display) }
+            },
+            MagellanicusPlatform::Wayland => {
+                let mut window = WaylandWindowHandle::empty();
+                window.surface = value.window;
+                let mut display = WaylandDisplayHandle::empty();
+                display.display = value.display;
+                Self { window: RawWindowHandle::Wayland(window), display: RawDisplayHandle::Wayland(display) }
+            },
+            MagellanicusPlatform::AppKit => {
+                let mut window = AppKitWindowHandle::empty();
+                window.ns_view = value.window;
+                Self { window: RawWindowHandle::AppKit(window), display: RawDisplayHandle::AppKit(AppKitDisplayHandle::empty()) }
+            }
+        }
+    }
+}
+
+macro_rules! try_ffi {
+    ($e:expr) => {
+        match $e {
+            Ok(n) => n,
+            Err(e) => return e
+        }
+    };
+}
+
+macro_rules! renderer_ref {
+    ($renderer:expr) => {
+        match $renderer.as_mut() {
+            Some(n) => &mut n.renderer,
+            None => return invalid_argument("renderer is null")
+        }
+    };
+}
+
+/// Get the last error that occurred on this thread, or null if no error occurred.
+///
+/// The returned string is valid until the next magellanicus call on this thread.
+#[no_mangle]
+pub extern "C" fn magellanicus_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map(|e| e.as_ptr()).unwrap_or(null()))
+}
+
+/// Get the default renderer parameters.
+#[no_mangle]
+pub extern "C" fn magellanicus_renderer_parameters_default() -> MagellanicusRendererParameters {
+    MagellanicusRendererParameters::from_renderer_parameters(&RendererParameters::default())
+}
+
+/// Set the parameters covered by a quality preset (MSAA, anisotropic filtering, render scale, water reflections, and
+/// SSAO), leaving the rest as they are.
+///
+/// # Safety
+///
+/// `parameters` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_parameters_apply_quality_preset(
+    parameters: *mut MagellanicusRendererParameters,
+    preset: MagellanicusQualityPreset
+) -> MagellanicusResult {
+    let Some(parameters) = parameters.as_mut() else {
+        return invalid_argument("parameters must not be null")
+    };
+    let applied = preset.to_quality_preset().apply(try_ffi!(parameters.to_renderer_parameters()));
+    *parameters = MagellanicusRendererParameters::from_renderer_parameters(&applied);
+    MagellanicusResult::Ok
+}
+
+/// Get the parameters currently in effect (e.g. after clamping to the device).
+///
+/// # Safety
+///
+/// `renderer` and `parameters` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_get_parameters(
+    renderer: *mut MagellanicusRenderer,
+    parameters: *mut MagellanicusRendererParameters
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let Some(parameters) = parameters.as_mut() else {
+        return invalid_argument("parameters must not be null")
+    };
+    *parameters = MagellanicusRendererParameters::from_renderer_parameters(&renderer.get_parameters());
+    MagellanicusResult::Ok
+}
+
+/// Create a renderer that presents to the given window.
+///
+/// # Safety
+///
+/// `window` must describe a valid window that outlives the renderer, and `parameters` and `renderer` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_new(
+    window: *const MagellanicusWindow,
+    parameters: *const MagellanicusRendererParameters,
+    renderer: *mut *mut MagellanicusRenderer
+) -> MagellanicusResult {
+    let (Some(window), Some(parameters)) = (window.as_ref(), parameters.as_ref()) else {
+        return invalid_argument("window and parameters must not be null")
+    };
+    if renderer.is_null() {
+        return invalid_argument("renderer must not be null")
+    }
+
+    let parameters = try_ffi!(parameters.to_renderer_parameters());
+    let window = FFIWindow::from(window);
+    handle_result(Renderer::new(&window, parameters), |r| {
+        *renderer = Box::into_raw(Box::new(MagellanicusRenderer { renderer: r }))
+    })
+}
+
+/// Create a renderer that draws into an internal image instead of a window.
+///
+/// # Safety
+///
+/// `parameters` and `renderer` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_new_headless(
+    parameters: *const MagellanicusRendererParameters,
+    renderer: *mut *mut MagellanicusRenderer
+) -> MagellanicusResult {
+    let Some(parameters) = parameters.as_ref() else {
+        return invalid_argument("parameters must not be null")
+    };
+    if renderer.is_null() {
+        return invalid_argument("renderer must not be null")
+    }
+
+    let parameters = try_ffi!(parameters.to_renderer_parameters());
+    handle_result(Renderer::new_headless(parameters), |r| {
+        *renderer = Box::into_raw(Box::new(MagellanicusRenderer { renderer: r }))
+    })
+}
+
+/// Destroy a renderer.
+///
+/// # Safety
+///
+/// `renderer` must have been returned by a `magellanicus_renderer_new` function and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_free(renderer: *mut MagellanicusRenderer) {
+    if !renderer.is_null() {
+        drop(Box::from_raw(renderer));
+    }
+}
+
+/// Clear all loaded data.
+///
+/// # Safety
+///
+/// `renderer` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_reset(renderer: *mut MagellanicusRenderer) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    renderer.reset();
+    MagellanicusResult::Ok
+}
+
+/// Add a bitmap.
+///
+/// # Safety
+///
+/// All pointers must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_add_bitmap(
+    renderer: *mut MagellanicusRenderer,
+    path: *const c_char,
+    bitmap: *const MagellanicusAddBitmapParameter
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let path = try_ffi!(read_string(path));
+    let Some(bitmap) = bitmap.as_ref() else {
+        return invalid_argument("bitmap must not be null")
+    };
+    let bitmap = try_ffi!(bitmap.to_add_bitmap_parameter());
+    handle_result(renderer.add_bitmap(path, bitmap), |_| ())
+}
+
+/// Add a bitmap, replacing the bitmap at `path` if one is loaded.
+///
+/// # Safety
+///
+/// All pointers must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_replace_bitmap(
+    renderer: *mut MagellanicusRenderer,
+    path: *const c_char,
+    bitmap: *const MagellanicusAddBitmapParameter
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let path = try_ffi!(read_string(path));
+    let Some(bitmap) = bitmap.as_ref() else {
+        return invalid_argument("bitmap must not be null")
+    };
+    let bitmap = try_ffi!(bitmap.to_add_bitmap_parameter());
+    handle_result(renderer.replace_bitmap(path, bitmap), |_| ())
+}
+
+/// Set the current BSP, or unload it if `path` is null.
+///
+/// # Safety
+///
+/// `renderer` must be valid, and `path` must be null or a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_set_current_bsp(renderer: *mut MagellanicusRenderer, path: *const c_char) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let path = if path.is_null() { None } else { Some(try_ffi!(read_string(path))) };
+    handle_result(renderer.set_current_bsp(path), |_| ())
+}
+
+/// Set the camera for a viewport.
+///
+/// # Safety
+///
+/// `renderer` and `camera` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_set_camera(
+    renderer: *mut MagellanicusRenderer,
+    viewport: usize,
+    camera: *const MagellanicusCamera
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let Some(camera) = camera.as_ref() else {
+        return invalid_argument("camera must not be null")
+    };
+    if viewport >= renderer.get_viewport_count() {
+        return invalid_argument("viewport out of bounds")
+    }
+    if !(camera.fov > 0.0 && camera.fov < core::f32::consts::PI) {
+        return invalid_argument("fov must be between 0 and pi (exclusive)")
+    }
+    renderer.set_camera_for_viewport(viewport, Camera {
+        fov: camera.fov,
+        position: Vec3::from_array(camera.position),
+        rotation: Vec3::from_array(camera.rotation),
+        lighting_mode: match camera.lighting_mode {
+            MagellanicusLightingMode::TexturedLightmapped => LightingMode::TexturedLightmapped,
+            MagellanicusLightingMode::Fullbright => LightingMode::Fullbright,
+            MagellanicusLightingMode::LightmapOnly => LightingMode::LightmapOnly,
+            MagellanicusLightingMode::Unlit => LightingMode::Unlit
+        },
+        fog: camera.fog
+    });
+    MagellanicusResult::Ok
+}
+
+/// Get the camera for a viewport.
+///
+/// # Safety
+///
+/// `renderer` and `camera` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_get_camera(
+    renderer: *mut MagellanicusRenderer,
+    viewport: usize,
+    camera: *mut MagellanicusCamera
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let Some(camera) = camera.as_mut() else {
+        return invalid_argument("camera must not be null")
+    };
+    if viewport >= renderer.get_viewport_count() {
+        return invalid_argument("viewport out of bounds")
+    }
+    let c = renderer.get_camera_for_viewport(viewport);
+    *camera = MagellanicusCamera {
+        fov: c.fov,
+        position: c.position.to_array(),
+        rotation: c.rotation.to_array(),
+        lighting_mode: match c.lighting_mode {
+            LightingMode::TexturedLightmapped => MagellanicusLightingMode::TexturedLightmapped,
+            LightingMode::Fullbright => MagellanicusLightingMode::Fullbright,
+            LightingMode::LightmapOnly => MagellanicusLightingMode::LightmapOnly,
+            LightingMode::Unlit => MagellanicusLightingMode::Unlit
+        },
+        fog: c.fog
+    };
+    MagellanicusResult::Ok
+}
+
+/// Draw a frame.
+///
+/// `swapchain_ok` is set to false if the window was resized and `magellanicus_renderer_set_resolution` needs to be
+/// called. It is left true if the window is minimized, in which case nothing is drawn until it is restored.
+///
+/// # Safety
+///
+/// `renderer` must be valid, and `swapchain_ok` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_draw_frame(renderer: *mut MagellanicusRenderer, swapchain_ok: *mut bool) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    handle_result(renderer.draw_frame(), |status| {
+        if let Some(n) = swapchain_ok.as_mut() {
+            *n = status != FrameStatus::ResizeRequired
+        }
+    })
+}
+
+/// Get statistics of the last frame drawn.
+///
+/// # Safety
+///
+/// `renderer` and `statistics` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_get_frame_statistics(
+    renderer: *mut MagellanicusRenderer,
+    statistics: *mut MagellanicusFrameStatistics
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let Some(statistics) = statistics.as_mut() else {
+        return invalid_argument("statistics must not be null")
+    };
+    *statistics = MagellanicusFrameStatistics::from_frame_statistics(&renderer.last_frame_stats());
+    MagellanicusResult::Ok
+}
+
+/// Resize the renderer, keeping all other parameters.
+///
+/// # Safety
+///
+/// `renderer` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_set_resolution(renderer: *mut MagellanicusRenderer, width: u32, height: u32) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    handle_result(renderer.set_resolution(Resolution { width, height }), |_| ())
+}
+
+/// Switch to a quality preset, rebuilding the swapchain.
+///
+/// Anisotropic filtering cannot be changed once the renderer is made, so use
+/// `magellanicus_renderer_parameters_apply_quality_preset` to make the renderer with it.
+///
+/// # Safety
+///
+/// `renderer` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_set_quality_preset(
+    renderer: *mut MagellanicusRenderer,
+    preset: MagellanicusQualityPreset
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    handle_result(renderer.set_quality_preset(preset.to_quality_preset()), |_| ())
+}
+
+/// Get the quality preset that the parameters in effect match.
+///
+/// # Safety
+///
+/// `renderer` and `preset` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_get_quality_preset(
+    renderer: *mut MagellanicusRenderer,
+    preset: *mut MagellanicusQualityPreset
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let Some(preset) = preset.as_mut() else {
+        return invalid_argument("preset must not be null")
+    };
+    *preset = MagellanicusQualityPreset::from_quality_preset(renderer.get_quality_preset());
+    MagellanicusResult::Ok
+}
+
+/// Rebuild the swapchain with new parameters (e.g. after resizing the window).
+///
+/// # Safety
+///
+/// `renderer` and `parameters` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_rebuild_swapchain(
+    renderer: *mut MagellanicusRenderer,
+    parameters: *const MagellanicusRendererParameters
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let Some(parameters) = parameters.as_ref() else {
+        return invalid_argument("parameters must not be null")
+    };
+    let parameters = try_ffi!(parameters.to_renderer_parameters());
+    handle_result(renderer.rebuild_swapchain(parameters), |_| ())
+}
+
+/// Recreate the device after `MAGELLANICUS_RESULT_DEVICE_LOST` was returned.
+///
+/// All data is cleared and must be added again.
+///
+/// # Safety
+///
+/// `renderer` and `parameters` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_recover_from_device_lost(
+    renderer: *mut MagellanicusRenderer,
+    parameters: *const MagellanicusRendererParameters
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let Some(parameters) = parameters.as_ref() else {
+        return invalid_argument("parameters must not be null")
+    };
+    let parameters = try_ffi!(parameters.to_renderer_parameters());
+    handle_result(renderer.recover_from_device_lost(parameters), |_| ())
+}
+
+/// Read back the last drawn frame.
+///
+/// # Safety
+///
+/// `renderer` and `frame` must be valid. On success, `frame` must be freed with `magellanicus_captured_frame_free`.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_renderer_capture_frame(
+    renderer: *mut MagellanicusRenderer,
+    frame: *mut MagellanicusCapturedFrame
+) -> MagellanicusResult {
+    let renderer = renderer_ref!(renderer);
+    let Some(frame) = frame.as_mut() else {
+        return invalid_argument("frame must not be null")
+    };
+    handle_result(renderer.capture_frame(), |f| {
+        let data = Box::into_raw(f.data.into_boxed_slice());
+        *frame = MagellanicusCapturedFrame {
+            width: f.resolution.width,
+            height: f.resolution.height,
+            data: data as *mut u8,
+            data_length: (*data).len()
+        };
+    })
+}
+
+/// Free the pixel data of a captured frame.
+///
+/// # Safety
+///
+/// `frame` must have been filled by `magellanicus_renderer_capture_frame` and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn magellanicus_captured_frame_free(frame: *mut MagellanicusCapturedFrame) {
+    let Some(frame) = frame.as_mut() else {
+        return
+    };
+    if !frame.data.is_null() {
+        drop(Box::from_raw(slice::from_raw_parts_mut(frame.data, frame.data_length) as *mut [u8]));
+        frame.data = std::ptr::null_mut();
+        frame.data_length = 0;
+    }
+}
@@ -1,26 +1,310 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
+use alloc::string::String;
 
 /// General Result type
 pub type MResult<T> = Result<T, Error>;
 
 /// General Error type
+///
+/// Each variant is a category of failure; match on the inner error for details.
 #[derive(Clone, Debug)]
 pub enum Error {
-    GraphicsAPIError { backend: &'static str, error: String },
-    DataError { error: String }
+    /// A bitmap could not be added or used.
+    Bitmap(BitmapError),
+
+    /// A shader could not be added or used.
+    Shader(ShaderError),
+
+    /// A BSP could not be added or used.
+    BSP(BSPError),
+
+    /// The swapchain (or the surface it presents to) could not be created or used.
+    Swapchain(SwapchainError),
+
+    /// The graphics device failed or does not support what was requested.
+    Device(DeviceError),
+
+    /// Any other invalid data or parameters (fonts, skies, renderer parameters, etc.).
+    Data(DataError)
+}
+
+/// An error reported by the graphics API.
+#[derive(Clone, Debug)]
+pub struct GraphicsAPIError {
+    pub backend: &'static str,
+    pub error: String
+}
+
+#[derive(Clone, Debug)]
+pub enum BitmapError {
+    /// A bitmap with this path is already loaded.
+    AlreadyLoaded { path: String },
+
+    /// No bitmap with this path is loaded.
+    NotLoaded { path: String },
+
+    /// The bitmap data is invalid.
+    Invalid { reason: String },
+
+    /// The bitmap could not be uploaded to the device.
+    Upload(GraphicsAPIError)
+}
+
+#[derive(Clone, Debug)]
+pub enum ShaderError {
+    /// A shader with this path is already loaded.
+    AlreadyLoaded { path: String },
+
+    /// No shader with this path is loaded.
+    NotLoaded { path: String },
+
+    /// The shader references a bitmap that is not loaded.
+    BitmapNotLoaded { path: String },
+
+    /// The shader data is invalid.
+    Invalid { reason: String }
+}
+
+#[derive(Clone, Debug)]
+pub enum BSPError {
+    /// A BSP with this path is already loaded.
+    AlreadyLoaded { path: String },
+
+    /// No BSP with this path is loaded.
+    NotLoaded { path: String },
+
+    /// The BSP references a bitmap that is not loaded.
+    BitmapNotLoaded { path: String },
+
+    /// The BSP references a shader that is not loaded.
+    ShaderNotLoaded { path: String },
+
+    /// The BSP references a sky that is not loaded.
+    SkyNotLoaded { path: String },
+
+    /// The BSP data is invalid.
+    Invalid { reason: String }
+}
+
+#[derive(Clone, Debug)]
+pub enum SwapchainError {
+    /// The renderer was created without a surface.
+    Headless,
+
+    /// The surface cannot be presented to with the current device.
+    UnsupportedSurface,
+
+    /// The graphics API failed to create or present the swapchain.
+    GraphicsAPI(GraphicsAPIError)
+}
+
+#[derive(Clone, Debug)]
+pub enum DeviceError {
+    /// No device meets the renderer's requirements.
+    NoSuitableDevice,
+
+    /// The device does not support the requested features or parameters.
+    Unsupported { reason: String },
+
+    /// The graphics API rejected a call as invalid usage.
+    ///
+    /// This indicates a bug in the renderer (or in the host's use of a device it passed in) rather than bad data, so
+    /// the host may want to treat it as fatal.
+    Validation { backend: &'static str, error: String },
+
+    /// The device was lost (e.g. the driver crashed or was updated, or the GPU was removed or reset).
+    ///
+    /// Nothing can be drawn until the device is recreated; see `Renderer::recover_from_device_lost`.
+    DeviceLost,
+
+    /// The graphics API returned an error.
+    GraphicsAPI(GraphicsAPIError)
+}
+
+#[derive(Clone, Debug)]
+pub enum DataError {
+    /// An asset with this path is already loaded.
+    AlreadyLoaded { path: String },
+
+    /// No asset with this path is loaded.
+    NotLoaded { path: String },
+
+    /// The data is invalid.
+    Invalid { reason: String }
 }
 
 impl Error {
-    pub(crate) fn from_data_error_string(error: String) -> Self {
-        Error::DataError { error }
+    pub(crate) fn from_data_error_string(reason: String) -> Self {
+        Error::Data(DataError::Invalid { reason })
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
-            Self::GraphicsAPIError { backend, error } => write!(f, "{backend} API error: {error}"),
-            Self::DataError { error } => write!(f, "Data error: {error}")
+            Self::Bitmap(e) => write!(f, "Bitmap error: {e}"),
+            Self::Shader(e) => write!(f, "Shader error: {e}"),
+            Self::BSP(e) => write!(f, "BSP error: {e}"),
+            Self::Swapchain(e) => write!(f, "Swapchain error: {e}"),
+            Self::Device(e) => write!(f, "Device error: {e}"),
+            Self::Data(e) => write!(f, "Data error: {e}")
+        }
+    }
+}
+
+impl Display for GraphicsAPIError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} API error: {}", self.backend, self.error)
+    }
+}
+
+impl Display for BitmapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::AlreadyLoaded { path } => write!(f, "{path} already exists"),
+            Self::NotLoaded { path } => write!(f, "bitmap {path} is not loaded"),
+            Self::Invalid { reason } => f.write_str(reason),
+            Self::Upload(e) => write!(f, "failed to upload bitmap: {e}")
+        }
+    }
+}
+
+impl Display for ShaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::AlreadyLoaded { path } => write!(f, "{path} already exists"),
+            Self::NotLoaded { path } => write!(f, "shader {path} is not loaded"),
+            Self::BitmapNotLoaded { path } => write!(f, "referenced bitmap {path} is not loaded"),
+            Self::Invalid { reason } => f.write_str(reason)
         }
     }
 }
+
+impl Display for BSPError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::AlreadyLoaded { path } => write!(f, "{path} already exists"),
+            Self::NotLoaded { path } => write!(f, "BSP {path} is not loaded"),
+            Self::BitmapNotLoaded { path } => write!(f, "referenced bitmap {path} is not loaded"),
+            Self::ShaderNotLoaded { path } => write!(f, "referenced shader {path} is not loaded"),
+            Self::SkyNotLoaded { path } => write!(f, "referenced sky {path} is not loaded"),
+            Self::Invalid { reason } => f.write_str(reason)
+        }
+    }
+}
+
+impl Display for SwapchainError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Headless => f.write_str("headless renderers cannot present to a surface"),
+            Self::UnsupportedSurface => f.write_str("the surface is not supported by the current device"),
+            Self::GraphicsAPI(e) => write!(f, "{e}")
+        }
+    }
+}
+
+impl Display for DeviceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NoSuitableDevice => f.write_str("no suitable Vulkan-compatible GPUs found"),
+            Self::Unsupported { reason } => f.write_str(reason),
+            Self::Validation { backend, error } => write!(f, "{backend} validation error: {error}"),
+            Self::DeviceLost => f.write_str("the device was lost"),
+            Self::GraphicsAPI(e) => write!(f, "{e}")
+        }
+    }
+}
+
+impl Display for DataError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::AlreadyLoaded { path } => write!(f, "{path} already exists"),
+            Self::NotLoaded { path } => write!(f, "{path} is not loaded"),
+            Self::Invalid { reason } => f.write_str(reason)
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Bitmap(e) => Some(e),
+            Self::Shader(e) => Some(e),
+            Self::BSP(e) => Some(e),
+            Self::Swapchain(e) => Some(e),
+            Self::Device(e) => Some(e),
+            Self::Data(e) => Some(e)
+        }
+    }
+}
+
+impl core::error::Error for GraphicsAPIError {}
+
+impl core::error::Error for BitmapError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Upload(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl core::error::Error for ShaderError {}
+
+impl core::error::Error for BSPError {}
+
+impl core::error::Error for SwapchainError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::GraphicsAPI(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl core::error::Error for DeviceError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::GraphicsAPI(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl core::error::Error for DataError {}
+
+impl From<BitmapError> for Error {
+    fn from(value: BitmapError) -> Self {
+        Self::Bitmap(value)
+    }
+}
+
+impl From<ShaderError> for Error {
+    fn from(value: ShaderError) -> Self {
+        Self::Shader(value)
+    }
+}
+
+impl From<BSPError> for Error {
+    fn from(value: BSPError) -> Self {
+        Self::BSP(value)
+    }
+}
+
+impl From<SwapchainError> for Error {
+    fn from(value: SwapchainError) -> Self {
+        Self::Swapchain(value)
+    }
+}
+
+impl From<DeviceError> for Error {
+    fn from(value: DeviceError) -> Self {
+        Self::Device(value)
+    }
+}
+
+impl From<DataError> for Error {
+    fn from(value: DataError) -> Self {
+        Self::Data(value)
+    }
+}
@@ -1,6 +1,27 @@
+//! Without the `vulkan` feature (enabled by default), only the parameter and validation layer of [`renderer`] is
+//! built. It does not need `std` either, only `core` and `alloc`, in which case float math is done with libm.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(dead_code)]
 
+extern crate alloc;
+
 pub mod vertex;
 pub mod error;
+#[cfg(feature = "vulkan")]
+pub mod renderer;
+#[cfg(not(feature = "vulkan"))]
+#[path = "renderer/without_vulkan.rs"]
 pub mod renderer;
 pub mod types;
+mod math;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+/// Re-exported so that hosts passing their own Vulkan objects to the renderer use the same version of vulkano.
+#[cfg(feature = "vulkan")]
+pub use vulkano;
+
+/// Re-exported so that hosts use the same version of glam as the math types in the public API (e.g. cameras).
+pub use glam;
@@ -0,0 +1,35 @@
+//! Float math that works with and without std.
+//!
+//! Without std, `f32` has no methods that need a math library (e.g. `cos`), so libm is used instead.
+
+#[cfg(feature = "std")]
+pub(crate) fn cos(x: f32) -> f32 {
+    x.cos()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn cos(x: f32) -> f32 {
+    libm::cosf(x)
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn floor(x: f32) -> f32 {
+    x.floor()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn floor(x: f32) -> f32 {
+    libm::floorf(x)
+}
+
+/// Get the fractional part of `x`, the same as [`f32::fract`].
+#[cfg(feature = "std")]
+pub(crate) fn fract(x: f32) -> f32 {
+    x.fract()
+}
+
+/// Get the fractional part of `x`, the same as [`f32::fract`].
+#[cfg(not(feature = "std"))]
+pub(crate) fn fract(x: f32) -> f32 {
+    x - libm::truncf(x)
+}
@@ -1,47 +1,109 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use data::*;
 
 pub use parameters::*;
-use crate::renderer::vulkan::VulkanRenderer;
+use crate::renderer::vulkan::{VulkanBitmapData, VulkanBSPData, VulkanRenderer};
 use player_viewport::*;
-use crate::error::{Error, MResult};
+use scene::*;
+use asset_store::*;
+use crate::error::{BitmapError, BSPError, DataError, DeviceError, Error, MResult, ShaderError};
 
 pub use player_viewport::Camera;
+pub use player_viewport::DebugRenderMode;
+pub use player_viewport::{DepthOfField, MAX_DEPTH_OF_FIELD_APERTURE};
+pub use player_viewport::LightingMode;
+pub use player_viewport::{PostProcessing, MAX_LETTERBOX};
+pub use player_viewport::SplitScreenBars;
+pub use player_viewport::ShaderLod;
 pub use player_viewport::get_default_vertical_fov;
 pub use player_viewport::horizontal_to_vertical_fov;
-
-use glam::{FloatExt, Vec3};
+pub use player_viewport::vertical_to_horizontal_fov;
+pub use player_viewport::{FovScaling, FOV_REFERENCE_ASPECT_RATIO};
+pub use asset_store::{BitmapHandle, BSPHandle, ShaderHandle};
+pub use info::*;
+pub use scene_state::SceneState;
+pub use fallback::FallbackAsset;
+pub use command_queue::{RendererCommand, RendererCommandSender};
+pub use checker::{AssetChecker, AssetCheckFailure};
+pub use hud::{HudAnchor, HudBlendMode, HudCrosshairParameter, HudDrawParameter, HudInterfaceElement, HudInterfaceElementType, HudInterfaceParameter, HudNavPointParameter, HudTextAlignment, HudTextParameter, HudUnits};
+use fallback::FallbackAssets;
+use debug_primitives::{DebugPrimitives, DebugTextAnchor};
+use hud::{HudDraw, HudImage, HudMeter, HudNavPoint};
+pub use scenario_markers::{ScenarioDebugMarker, ScenarioDebugMarkerCategory};
+use scenario_markers::ScenarioDebugMarkers;
+pub use light::{LightHandle, MAX_DYNAMIC_LIGHTS};
+use light::LightHandleAllocator;
+use lens_flare::LensFlareVisibility;
+pub use ui_widget::{UIWidgetBackground, UIWidgetChild, UIWidgetParameter, UIWidgetText, UI_CANVAS_SIZE};
+
+use glam::{FloatExt, Mat3, Mat4, Vec3, Vec4};
 use crate::types::FloatColor;
+use crate::vertex::VertexOffsets;
 
 mod parameters;
 mod vulkan;
 mod data;
 mod player_viewport;
+mod scene;
+mod asset_store;
+mod info;
+mod scene_state;
+mod fallback;
+mod command_queue;
+mod checker;
+mod debug_primitives;
+mod hud;
+mod ui_widget;
+mod scenario_markers;
+mod light;
+mod lens_flare;
 
 pub struct Renderer {
     vulkan: VulkanRenderer,
     player_viewports: Vec<PlayerViewport>,
 
-    bitmaps: HashMap<Arc<String>, Bitmap>,
-    shaders: HashMap<Arc<String>, Shader>,
+    bitmaps: AssetStore<Bitmap, BitmapHandle>,
+    shaders: AssetStore<Shader, ShaderHandle>,
+    checkerboard_shaders: HashSet<ShaderHandle>,
     geometries: HashMap<Arc<String>, Geometry>,
     skies: HashMap<Arc<String>, Sky>,
-    bsps: HashMap<Arc<String>, Arc<BSP>>,
+    bsps: AssetStore<Arc<BSP>, BSPHandle>,
     fonts: HashMap<Arc<String>, Font>,
 
     default_bitmaps: DefaultBitmaps,
-    current_bsp: Option<Arc<String>>,
+    fallback_assets: FallbackAssets,
+    scenes: Vec<Scene>,
+    stereo: bool,
+    split_screen_bars: Option<SplitScreenBars>,
 
     fps_counter_value: f64,
     fps_counter_time: Instant,
     fps_counter_count: u32,
+    frame_statistics: FrameStatistics,
 
     debug_text: VecDeque<Bitmap>,
     debug_text_stale: bool,
     debug_font: Option<Arc<String>>,
+    debug_primitives: DebugPrimitives,
+    scenario_debug_markers: ScenarioDebugMarkers,
+    light_handles: LightHandleAllocator,
+    lightmap_exposure: f32,
+    lightmap_overbright: bool,
+    hud_draws: Vec<HudDraw>,
+    hud_nav_points: Vec<HudNavPoint>,
+    lens_flares: LensFlareVisibility,
+
+    overlay_hook: Option<Box<dyn FnMut(&mut OverlayContext) + Send>>,
+    swapchain_rebuilt_hook: Option<Box<dyn FnMut(Resolution) + Send>>,
+    deterministic: bool,
+
+    retained_assets: Option<AddAssetsParameter>,
+
+    command_sender: std::sync::mpsc::Sender<RendererCommand>,
+    command_receiver: std::sync::mpsc::Receiver<RendererCommand>,
 }
 
 impl Renderer {
@@ -51,87 +113,96 @@ impl Renderer {
     /// - `parameters` is invalid
     /// - the renderer backend could not be initialized for some reason
     pub unsafe fn new(surface: &(impl HasRawWindowHandle + HasRawDisplayHandle), parameters: RendererParameters) -> MResult<Self> {
-        if parameters.resolution.height == 0 || parameters.resolution.width == 0 {
-            return Err(Error::DataError { error: "resolution has 0 on one or more dimensions".to_owned() })
-        }
+        let player_viewports = Self::make_player_viewports(&parameters)?;
+        Self::new_with_backend(VulkanRenderer::new(&parameters, surface)?, player_viewports)
+    }
+
+    /// Initialize a new renderer without a window.
+    ///
+    /// Frames are drawn into an internal image at `parameters.resolution` which can be read back with
+    /// [`Renderer::capture_frame`]. This is useful for tools that need to render in batch jobs (e.g. generating BSP
+    /// thumbnails) as well as automated testing; any Vulkan device can be used, including software implementations
+    /// such as lavapipe, so a GPU is not required.
+    ///
+    /// `vsync` is ignored, as nothing is presented.
+    ///
+    /// Errors if:
+    /// - `parameters` is invalid
+    /// - the renderer backend could not be initialized for some reason
+    pub fn new_headless(parameters: RendererParameters) -> MResult<Self> {
+        let player_viewports = Self::make_player_viewports(&parameters)?;
+        Self::new_with_backend(VulkanRenderer::new_headless(&parameters)?, player_viewports)
+    }
+
+    /// Initialize a new renderer using a device and queue created by the host application.
+    ///
+    /// This is useful for applications that already have a Vulkan context. If `surface` is `None`, the renderer is
+    /// headless and the host can use [`Renderer::get_last_frame_image`] to composite the result into its own frame.
+    ///
+    /// The device must support Vulkan 1.2 (with `extended_dynamic_state` enabled) or newer, and it must have
+    /// `sampler_anisotropy` enabled if anisotropic filtering is requested. The queue must support graphics (and
+    /// presenting to `surface`, if given).
+    ///
+    /// Errors if:
+    /// - `parameters` is invalid
+    /// - `device`, `queue`, or `surface` do not meet the above requirements
+    pub fn new_from_vulkan_device(
+        device: Arc<vulkano::device::Device>,
+        queue: Arc<vulkano::device::Queue>,
+        surface: Option<Arc<vulkano::swapchain::Surface>>,
+        parameters: RendererParameters
+    ) -> MResult<Self> {
+        let player_viewports = Self::make_player_viewports(&parameters)?;
+        Self::new_with_backend(VulkanRenderer::new_from_existing_device(&parameters, device, queue, surface)?, player_viewports)
+    }
+
+    fn make_player_viewports(parameters: &RendererParameters) -> MResult<Vec<PlayerViewport>> {
+        parameters.validate()?;
 
         let mut player_viewports = vec![PlayerViewport::default(); parameters.number_of_viewports];
+        lay_out_split_screen(player_viewports.iter_mut());
 
-        match parameters.number_of_viewports {
-            1 => {
-                player_viewports[0].rel_x = 0.0;
-                player_viewports[0].rel_y = 0.0;
-                player_viewports[0].rel_width = 1.0;
-                player_viewports[0].rel_height = 1.0;
-            }
-            2 => {
-                player_viewports[0].rel_x = 0.0;
-                player_viewports[0].rel_y = 0.0;
-                player_viewports[0].rel_width = 1.0;
-                player_viewports[0].rel_height = 0.5;
-
-                player_viewports[1].rel_x = 0.0;
-                player_viewports[1].rel_y = 0.5;
-                player_viewports[1].rel_width = 1.0;
-                player_viewports[1].rel_height = 0.5;
-            }
-            3 => {
-                player_viewports[0].rel_x = 0.0;
-                player_viewports[0].rel_y = 0.0;
-                player_viewports[0].rel_width = 1.0;
-                player_viewports[0].rel_height = 0.5;
-
-                player_viewports[1].rel_x = 0.0;
-                player_viewports[1].rel_y = 0.5;
-                player_viewports[1].rel_width = 0.5;
-                player_viewports[1].rel_height = 0.5;
-
-                player_viewports[2].rel_x = 0.5;
-                player_viewports[2].rel_y = 0.5;
-                player_viewports[2].rel_width = 0.5;
-                player_viewports[2].rel_height = 0.5;
-            }
-            4 => {
-                player_viewports[0].rel_x = 0.0;
-                player_viewports[0].rel_y = 0.0;
-                player_viewports[0].rel_width = 0.5;
-                player_viewports[0].rel_height = 0.5;
-
-                player_viewports[1].rel_x = 0.5;
-                player_viewports[1].rel_y = 0.0;
-                player_viewports[1].rel_width = 0.5;
-                player_viewports[1].rel_height = 0.5;
-
-                player_viewports[2].rel_x = 0.0;
-                player_viewports[2].rel_y = 0.5;
-                player_viewports[2].rel_width = 0.5;
-                player_viewports[2].rel_height = 0.5;
-
-                player_viewports[3].rel_x = 0.5;
-                player_viewports[3].rel_y = 0.5;
-                player_viewports[3].rel_width = 0.5;
-                player_viewports[3].rel_height = 0.5;
-            }
-            n => return Err(Error::DataError { error: format!("number of viewports was set to {n}, but only 1-4 are supported") })
-        }
+        Ok(player_viewports)
+    }
 
+    fn new_with_backend(vulkan: VulkanRenderer, player_viewports: Vec<PlayerViewport>) -> MResult<Self> {
+        let (command_sender, command_receiver) = command_queue::make_command_queue();
         let mut result = Self {
-            vulkan: VulkanRenderer::new(&parameters, surface)?,
+            vulkan,
             player_viewports,
-            bitmaps: HashMap::new(),
-            shaders: HashMap::new(),
+            bitmaps: AssetStore::new(),
+            shaders: AssetStore::new(),
+            checkerboard_shaders: HashSet::new(),
             geometries: HashMap::new(),
             skies: HashMap::new(),
-            bsps: HashMap::new(),
+            bsps: AssetStore::new(),
             fonts: HashMap::new(),
-            current_bsp: None,
+            scenes: vec![Scene::default()],
+            stereo: false,
+            split_screen_bars: Some(SplitScreenBars::default()),
             default_bitmaps: DefaultBitmaps::default(),
+            fallback_assets: FallbackAssets::default(),
             fps_counter_value: 0.0,
             fps_counter_count: 0,
+            frame_statistics: FrameStatistics::default(),
             fps_counter_time: Instant::now(),
             debug_text: VecDeque::with_capacity(64),
             debug_text_stale: true,
             debug_font: None,
+            debug_primitives: DebugPrimitives::default(),
+            scenario_debug_markers: ScenarioDebugMarkers::default(),
+            light_handles: LightHandleAllocator::default(),
+            lightmap_exposure: 1.0,
+            lightmap_overbright: true,
+            hud_draws: Vec::new(),
+            hud_nav_points: Vec::new(),
+            lens_flares: LensFlareVisibility::default(),
+            overlay_hook: None,
+            swapchain_rebuilt_hook: None,
+            deterministic: false,
+            retained_assets: None,
+            command_sender,
+            command_receiver,
         };
 
         populate_default_bitmaps(&mut result)?;
@@ -141,19 +212,36 @@ impl Renderer {
 
     /// Clear all data without resetting the renderer.
     ///
-    /// All objects added with `add_` methods will be cleared.
+    /// All objects added with `add_` methods will be cleared, all handles will be invalidated, and all fallback
+    /// assets will be unregistered.
+    ///
+    /// If asset parameters are being retained, they are cleared as well.
     pub fn reset(&mut self) {
+        // Default bitmaps are recreated when restoring, so don't retain them.
+        let retained_assets = self.retained_assets.take().map(|_| AddAssetsParameter::default());
+
         self.bitmaps.clear();
         self.shaders.clear();
+        self.checkerboard_shaders.clear();
         self.geometries.clear();
         self.skies.clear();
         self.bsps.clear();
         self.fonts.clear();
-        self.current_bsp = None;
+        self.scenes = vec![Scene::default()];
+        for viewport in &mut self.player_viewports {
+            viewport.scene = 0;
+        }
         self.debug_font = None;
+        self.debug_primitives.clear();
+        self.scenario_debug_markers.markers.clear();
+        self.hud_draws.clear();
+        self.hud_nav_points.clear();
+        self.lens_flares.clear();
         self.default_bitmaps = DefaultBitmaps::default();
+        self.fallback_assets = FallbackAssets::default();
 
         populate_default_bitmaps(self).unwrap();
+        self.retained_assets = retained_assets;
         self.invalidate_debug_text();
     }
 
@@ -166,214 +254,2054 @@ impl Renderer {
     pub fn add_font(&mut self, path: &str, font: AddFontParameter) -> MResult<()> {
         let font_path = Arc::new(path.to_owned());
         if self.fonts.contains_key(&font_path) {
-            return Err(Error::from_data_error_string(format!("{path} already exists (replacing fonts is not yet supported)")))
+            return Err(DataError::AlreadyLoaded { path: path.to_owned() }.into())
         }
 
         font.validate()?;
+        let retained = self.retained_assets.as_ref().map(|_| font.clone());
         let font = Font::load_from_parameters(self, font)?;
         self.fonts.insert(font_path, font);
+        if let Some(parameter) = retained {
+            retain_asset(&mut self.retained_assets.as_mut().unwrap().fonts, path, parameter);
+        }
+        Ok(())
+    }
+
+    /// Add a bitmap with the given parameters.
+    ///
+    /// To replace a bitmap that is already loaded, use [`Renderer::replace_bitmap`].
+    ///
+    /// Returns a handle that can be used to refer to the bitmap without looking up its path.
+    ///
+    /// This will error if:
+    /// - `bitmap` is invalid
+    /// - a bitmap is already loaded at `path`
+    pub fn add_bitmap(&mut self, path: &str, bitmap: AddBitmapParameter) -> MResult<BitmapHandle> {
+        if self.bitmaps.contains_path(path) {
+            return Err(BitmapError::AlreadyLoaded { path: path.to_owned() }.into())
+        }
+
+        bitmap.validate()?;
+        let retained = self.retained_assets.as_ref().map(|_| bitmap.clone());
+        let bitmap = Bitmap::load_from_parameters(self, bitmap)?;
+        if let Some(parameter) = retained {
+            retain_asset(&mut self.retained_assets.as_mut().unwrap().bitmaps, path, parameter);
+        }
+        Ok(self.bitmaps.insert(Arc::new(path.to_owned()), bitmap))
+    }
+
+    /// Add a shader.
+    ///
+    /// To replace a shader that is already loaded, use [`Renderer::replace_shader`].
+    ///
+    /// Returns a handle that can be used to refer to the shader without looking up its path.
+    ///
+    /// This will error if:
+    /// - `shader` is invalid
+    /// - `shader` contains invalid dependencies
+    /// - a shader is already loaded at `path`
+    pub fn add_shader(&mut self, path: &str, shader: AddShaderParameter) -> MResult<ShaderHandle> {
+        if self.shaders.contains_path(path) {
+            return Err(ShaderError::AlreadyLoaded { path: path.to_owned() }.into())
+        }
+
+        shader.validate(self)?;
+        let shader = Shader::load_from_parameters(self, shader)?;
+        if let Some(retained_assets) = self.retained_assets.as_mut() {
+            retain_asset(&mut retained_assets.shaders, path, shader.parameter.clone());
+        }
+        Ok(self.shaders.insert(Arc::new(path.to_owned()), shader))
+    }
+
+    /// Add a geometry.
+    ///
+    /// Note that replacing geometries is not yet supported.
+    ///
+    /// This will error if:
+    /// - `geometry` is invalid
+    /// - `geometry` contains invalid dependencies
+    /// - replacing a geometry would break any dependencies
+    #[allow(unused_variables)]
+    pub fn add_geometry(&mut self, path: &str, geometry: AddGeometryParameter) -> MResult<()> {
+        todo!()
+    }
+
+    /// Add a sky.
+    ///
+    /// This will error if:
+    /// - `sky` is invalid
+    /// - `sky` contains invalid dependencies
+    pub fn add_sky(&mut self, path: &str, sky: AddSkyParameter) -> MResult<()> {
+        sky.validate(self)?;
+        if let Some(retained_assets) = self.retained_assets.as_mut() {
+            retain_asset(&mut retained_assets.skies, path, sky.clone());
+        }
+
+        // tool.exe defaults 0.0 max density to 1.0, so fog should be disabled if both the start and
+        // max distance are 0.0.
+
+        let mut outdoor_fog = sky.outdoor_fog;
+        let mut indoor_fog = sky.indoor_fog;
+
+        if outdoor_fog.distance_to == 0.0 {
+            outdoor_fog = FogData::default();
+        }
+
+        if indoor_fog.distance_to == 0.0 {
+            indoor_fog = FogData::default();
+        }
+
+        self.skies.insert(Arc::new(path.to_owned()), Sky {
+            geometry: sky.geometry.map(|s| self.geometries.get_key_value(&s).unwrap().0.clone()),
+            outdoor_fog,
+            indoor_fog,
+            outdoor_ambient: sky.outdoor_ambient,
+            indoor_ambient: sky.indoor_ambient,
+            lights: sky.lights.into_iter().map(|l| SkyLight { direction: l.direction.normalize(), ..l }).collect(),
+            lens_flares: sky.lens_flares.into_iter().map(|l| SkyLensFlare { direction: l.direction.normalize(), ..l }).collect()
+        });
+
+        Ok(())
+    }
+
+    /// Add a BSP.
+    ///
+    /// To replace a BSP that is already loaded, use [`Renderer::replace_bsp`].
+    ///
+    /// Returns a handle that can be used to refer to the BSP without looking up its path.
+    ///
+    /// This will error if:
+    /// - `bsp` is invalid
+    /// - `bsp` contains invalid dependencies
+    /// - a BSP is already loaded at `path`
+    pub fn add_bsp(&mut self, path: &str, bsp: AddBSPParameter) -> MResult<BSPHandle> {
+        if self.bsps.contains_path(path) {
+            return Err(BSPError::AlreadyLoaded { path: path.to_owned() }.into())
+        }
+
+        bsp.validate(self)?;
+        let retained = self.retained_assets.as_ref().map(|_| bsp.clone());
+        let bsp = BSP::load_from_parameters(self, bsp)?;
+        if let Some(parameter) = retained {
+            retain_asset(&mut self.retained_assets.as_mut().unwrap().bsps, path, parameter);
+        }
+        Ok(self.bsps.insert(Arc::new(path.to_owned()), Arc::new(bsp)))
+    }
+
+    /// Add many assets at once.
+    ///
+    /// Assets are added in dependency order (bitmaps and fonts, then shaders, then skies, then BSPs), so they can be
+    /// given in any order. `progress` is called before each asset is added.
+    ///
+    /// If an asset fails to be added, its error is returned immediately, and it is the asset that was last passed to
+    /// `progress`. Assets added before it remain loaded.
+    ///
+    /// This will error if any asset would fail to be added with its respective `add_` method.
+    pub fn add_assets(&mut self, assets: AddAssetsParameter, mut progress: impl FnMut(AddAssetsProgress)) -> MResult<()> {
+        let AddAssetsParameter { bitmaps, fonts, shaders, skies, bsps, worker_threads } = assets;
+
+        let total = bitmaps.len() + fonts.len() + shaders.len() + skies.len() + bsps.len();
+        let mut completed = 0;
+        let mut report = |asset_type: AssetType, path: &str| {
+            progress(AddAssetsProgress { asset_type, path, completed, total });
+            completed += 1;
+        };
+
+        let bitmap_validation = validate_bitmaps(&bitmaps, worker_threads);
+        for ((path, bitmap), validation) in bitmaps.into_iter().zip(bitmap_validation) {
+            report(AssetType::Bitmap, &path);
+            if self.bitmaps.contains_path(&path) {
+                return Err(BitmapError::AlreadyLoaded { path }.into())
+            }
+            validation?;
+            let retained = self.retained_assets.as_ref().map(|_| bitmap.clone());
+            let bitmap = Bitmap::load_from_parameters(self, bitmap)?;
+            if let Some(parameter) = retained {
+                retain_asset(&mut self.retained_assets.as_mut().unwrap().bitmaps, &path, parameter);
+            }
+            self.bitmaps.insert(Arc::new(path), bitmap);
+        }
+
+        for (path, font) in fonts {
+            report(AssetType::Font, &path);
+            self.add_font(&path, font)?;
+        }
+
+        for (path, shader) in shaders {
+            report(AssetType::Shader, &path);
+            self.add_shader(&path, shader)?;
+        }
+
+        for (path, sky) in skies {
+            report(AssetType::Sky, &path);
+            self.add_sky(&path, sky)?;
+        }
+
+        for (path, bsp) in bsps {
+            report(AssetType::BSP, &path);
+            self.add_bsp(&path, bsp)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add a bitmap, replacing the bitmap at `path` if one is loaded.
+    ///
+    /// A replaced bitmap keeps its handle, and shaders and BSPs that use it are rebuilt to use the new bitmap. If
+    /// this errors, nothing is changed.
+    ///
+    /// This will error if:
+    /// - `bitmap` is invalid
+    /// - replacing the bitmap would break any dependencies (e.g. a shader expects a cubemap, or a BSP uses a
+    ///   lightmap the new bitmap doesn't have)
+    pub fn replace_bitmap(&mut self, path: &str, bitmap: AddBitmapParameter) -> MResult<BitmapHandle> {
+        bitmap.validate()?;
+        let retained = self.retained_assets.as_ref().map(|_| bitmap.clone());
+        let bitmap = Bitmap::load_from_parameters(self, bitmap)?;
+
+        let handle = match self.bitmaps.get_handle(path) {
+            Some(handle) => {
+                let old_bitmap = core::mem::replace(self.bitmaps.get_mut(handle).unwrap(), bitmap);
+                if let Err(e) = self.rebuild_bitmap_dependents(path) {
+                    *self.bitmaps.get_mut(handle).unwrap() = old_bitmap;
+                    return Err(e)
+                }
+                handle
+            },
+            None => self.bitmaps.insert(Arc::new(path.to_owned()), bitmap)
+        };
+
+        if let Some(parameter) = retained {
+            retain_asset(&mut self.retained_assets.as_mut().unwrap().bitmaps, path, parameter);
+        }
+
+        Ok(handle)
+    }
+
+    /// Add a shader, replacing the shader at `path` if one is loaded.
+    ///
+    /// A replaced shader keeps its handle, so BSPs that use it will draw with the new shader.
+    ///
+    /// This will error if:
+    /// - `shader` is invalid
+    /// - `shader` contains invalid dependencies
+    /// - replacing the shader would break any dependencies (e.g. a BSP uses it and it is a model shader)
+    pub fn replace_shader(&mut self, path: &str, shader: AddShaderParameter) -> MResult<ShaderHandle> {
+        shader.validate(self)?;
+        let shader = Shader::load_from_parameters(self, shader)?;
+
+        let Some(handle) = self.shaders.get_handle(path) else {
+            if let Some(retained_assets) = self.retained_assets.as_mut() {
+                retain_asset(&mut retained_assets.shaders, path, shader.parameter.clone());
+            }
+            return Ok(self.shaders.insert(Arc::new(path.to_owned()), shader))
+        };
+
+        let dependents: Vec<BSPHandle> = self.bsps
+            .iter()
+            .filter(|(_, _, bsp)| bsp.uses_shader(handle))
+            .map(|(bsp_handle, _, _)| bsp_handle)
+            .collect();
+
+        if shader.shader_type == ShaderType::Model {
+            if let Some(bsp) = dependents.first() {
+                return Err(ShaderError::Invalid { reason: format!("{path} is used by BSP {}, and {:?} shaders aren't allowed for BSPs", self.bsps.get_path(*bsp).unwrap(), shader.shader_type) }.into())
+            }
+        }
+
+        if let Some(retained_assets) = self.retained_assets.as_mut() {
+            retain_asset(&mut retained_assets.shaders, path, shader.parameter.clone());
+        }
+        *self.shaders.get_mut(handle).unwrap() = shader;
+
+        // Transparency may have changed, so geometries need to be re-sorted.
+        for bsp_handle in dependents {
+            let (transparent_geometries, opaque_geometries) = VulkanBSPData::sort_geometries(self, &self.bsps[bsp_handle].geometries);
+            let bsp = Arc::get_mut(self.bsps.get_mut(bsp_handle).unwrap()).expect("BSP is still in use");
+            bsp.vulkan.transparent_geometries = transparent_geometries;
+            bsp.vulkan.opaque_geometries = opaque_geometries;
+        }
+
+        Ok(handle)
+    }
+
+    /// Add a BSP, replacing the BSP at `path` if one is loaded.
+    ///
+    /// A replaced BSP keeps its handle, so scenes that use it will draw the new BSP.
+    ///
+    /// This will error if:
+    /// - `bsp` is invalid
+    /// - `bsp` contains invalid dependencies
+    pub fn replace_bsp(&mut self, path: &str, bsp: AddBSPParameter) -> MResult<BSPHandle> {
+        bsp.validate(self)?;
+        let retained = self.retained_assets.as_ref().map(|_| bsp.clone());
+        let bsp = BSP::load_from_parameters(self, bsp)?;
+        let handle = self.bsps.insert(Arc::new(path.to_owned()), Arc::new(bsp));
+        if let Some(parameter) = retained {
+            retain_asset(&mut self.retained_assets.as_mut().unwrap().bsps, path, parameter);
+        }
+
+        // Fog and the sun come from the old BSP's clusters.
+        for viewport in &mut self.player_viewports {
+            if self.scenes[viewport.scene].bsp == Some(handle) {
+                viewport.viewport_fog = None;
+                viewport.sun = None;
+                viewport.ambient = None;
+            }
+        }
+
+        self.invalidate_debug_text();
+        Ok(handle)
+    }
+
+    /// Replace a single page of the lightmap bitmap of the BSP at `bsp` without reloading the BSP.
+    ///
+    /// This is useful for showing progressive results while baking lightmaps. The page may have a different
+    /// resolution or format than before. Since the page is part of the lightmap bitmap, other BSPs and shaders that
+    /// use the bitmap are updated as well. If this errors, nothing is changed.
+    ///
+    /// This will error if:
+    /// - `bsp` is not loaded or has no lightmap bitmap
+    /// - `page_index` is not a bitmap in the lightmap bitmap
+    /// - `data` is invalid or is not a 2D texture
+    pub fn update_lightmap_page(&mut self, bsp: &str, page_index: usize, data: AddBitmapBitmapParameter) -> MResult<()> {
+        let Some(bsp_data) = self.bsps.get_by_path(bsp) else {
+            return Err(BSPError::NotLoaded { path: bsp.to_owned() }.into())
+        };
+        let Some(path) = bsp_data.lightmap_bitmap.clone() else {
+            return Err(BSPError::Invalid { reason: format!("{bsp} has no lightmap bitmap") }.into())
+        };
+        let Some(handle) = self.bitmaps.get_handle(&path) else {
+            return Err(BSPError::BitmapNotLoaded { path }.into())
+        };
+
+        let page_count = self.bitmaps[handle].bitmaps.len();
+        if page_index >= page_count {
+            return Err(BitmapError::Invalid { reason: format!("Lightmap page #{page_index} does not exist; {path} has only {page_count} bitmap(s)") }.into())
+        }
+
+        data.validate(page_index)?;
+        if data.bitmap_type != BitmapType::Dim2D {
+            return Err(BitmapError::Invalid { reason: format!("Lightmap page #{page_index} must be a 2D texture, got {:?}", data.bitmap_type) }.into())
+        }
+
+        let retained = self.retained_assets.as_ref().map(|_| data.clone());
+        let page = BitmapBitmap {
+            resolution: data.resolution,
+            bitmap_type: data.bitmap_type,
+            vulkan: VulkanBitmapData::new(&mut self.vulkan, &data)?
+        };
+
+        let old_page = core::mem::replace(&mut self.bitmaps.get_mut(handle).unwrap().bitmaps[page_index], page);
+        if let Err(e) = self.rebuild_bitmap_dependents(&path) {
+            self.bitmaps.get_mut(handle).unwrap().bitmaps[page_index] = old_page;
+            return Err(e)
+        }
+
+        if let Some(parameter) = retained {
+            let retained_bitmap = self.retained_assets
+                .as_mut()
+                .unwrap()
+                .bitmaps
+                .iter_mut()
+                .find(|(p, _)| *p == path);
+            if let Some((_, bitmap)) = retained_bitmap {
+                bitmap.bitmaps[page_index] = parameter;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rebuild_bitmap_dependents(&mut self, path: &str) -> MResult<()> {
+        let shader_parameters: Vec<(ShaderHandle, AddShaderParameter)> = self.shaders
+            .iter()
+            .filter(|(_, _, shader)| shader.parameter.references_bitmap(path))
+            .map(|(handle, _, shader)| (handle, shader.parameter.clone()))
+            .collect();
+
+        let mut shaders = Vec::with_capacity(shader_parameters.len());
+        for (handle, parameter) in shader_parameters {
+            parameter.validate(self)?;
+            shaders.push((handle, Shader::load_from_parameters(self, parameter)?));
+        }
+
+        let mut lightmaps = Vec::new();
+        for (handle, _, bsp) in self.bsps.iter() {
+            if bsp.lightmap_bitmap.as_deref() != Some(path) {
+                continue
+            }
+            let lightmap_indices: Vec<usize> = bsp.vulkan.lightmap_images.keys().copied().collect();
+            lightmaps.push((handle, VulkanBSPData::make_lightmap_sets(self, Some(path), &lightmap_indices)?));
+        }
+
+        for (handle, shader) in shaders {
+            *self.shaders.get_mut(handle).unwrap() = shader;
+        }
+
+        for (handle, lightmap_images) in lightmaps {
+            let bsp = Arc::get_mut(self.bsps.get_mut(handle).unwrap()).expect("BSP is still in use");
+            bsp.vulkan.lightmap_images = lightmap_images;
+        }
+
+        Ok(())
+    }
+
+    /// Get the handle of the bitmap at `path`, if it is loaded.
+    pub fn get_bitmap_handle(&self, path: &str) -> Option<BitmapHandle> {
+        self.bitmaps.get_handle(path)
+    }
+
+    /// Get the handle of the shader at `path`, if it is loaded.
+    pub fn get_shader_handle(&self, path: &str) -> Option<ShaderHandle> {
+        self.shaders.get_handle(path)
+    }
+
+    /// Get the handle of the BSP at `path`, if it is loaded.
+    pub fn get_bsp_handle(&self, path: &str) -> Option<BSPHandle> {
+        self.bsps.get_handle(path)
+    }
+
+    /// Return `true` if a bitmap is loaded at `path`.
+    pub fn is_bitmap_loaded(&self, path: &str) -> bool {
+        self.bitmaps.contains_path(path)
+    }
+
+    /// Return `true` if a shader is loaded at `path`.
+    pub fn is_shader_loaded(&self, path: &str) -> bool {
+        self.shaders.contains_path(path)
+    }
+
+    /// Return `true` if a BSP is loaded at `path`.
+    pub fn is_bsp_loaded(&self, path: &str) -> bool {
+        self.bsps.contains_path(path)
+    }
+
+    /// Return `true` if a sky is loaded at `path`.
+    pub fn is_sky_loaded(&self, path: &str) -> bool {
+        self.skies.contains_key(&path.to_owned())
+    }
+
+    /// Get info about all loaded bitmaps.
+    ///
+    /// Default bitmaps created by the renderer are not included.
+    pub fn get_bitmaps(&self) -> Vec<BitmapInfo> {
+        self.bitmaps
+            .iter()
+            .filter(|(handle, _, _)| !self.default_bitmaps.contains(*handle))
+            .map(|(handle, path, bitmap)| Self::make_bitmap_info(handle, path, bitmap))
+            .collect()
+    }
+
+    /// Get info about the bitmap referred to by `handle`, if it is still valid.
+    pub fn get_bitmap_info(&self, handle: BitmapHandle) -> Option<BitmapInfo> {
+        self.bitmaps.get_with_path(handle).map(|(path, bitmap)| Self::make_bitmap_info(handle, path, bitmap))
+    }
+
+    /// Get info about all loaded shaders.
+    pub fn get_shaders(&self) -> Vec<ShaderInfo> {
+        self.shaders
+            .iter()
+            .map(|(handle, path, shader)| ShaderInfo { handle, path: path.clone(), shader_type: shader.shader_type })
+            .collect()
+    }
+
+    /// Get info about the shader referred to by `handle`, if it is still valid.
+    pub fn get_shader_info(&self, handle: ShaderHandle) -> Option<ShaderInfo> {
+        self.shaders
+            .get_with_path(handle)
+            .map(|(path, shader)| ShaderInfo { handle, path: path.clone(), shader_type: shader.shader_type })
+    }
+
+    /// Get info about all loaded BSPs.
+    pub fn get_bsps(&self) -> Vec<BSPInfo> {
+        self.bsps
+            .iter()
+            .map(|(handle, path, bsp)| Self::make_bsp_info(handle, path, bsp))
+            .collect()
+    }
+
+    /// Get info about the BSP referred to by `handle`, if it is still valid.
+    pub fn get_bsp_info(&self, handle: BSPHandle) -> Option<BSPInfo> {
+        self.bsps.get_with_path(handle).map(|(path, bsp)| Self::make_bsp_info(handle, path, bsp))
+    }
+
+    /// Get info about all loaded skies, sorted by path.
+    pub fn get_skies(&self) -> Vec<SkyInfo> {
+        let mut skies: Vec<SkyInfo> = self.skies
+            .iter()
+            .map(|(path, sky)| SkyInfo {
+                path: path.clone(),
+                geometry: sky.geometry.clone(),
+                outdoor_fog: sky.outdoor_fog,
+                indoor_fog: sky.indoor_fog,
+                outdoor_ambient: sky.outdoor_ambient,
+                indoor_ambient: sky.indoor_ambient,
+                lights: sky.lights.clone(),
+                lens_flares: sky.lens_flares.clone()
+            })
+            .collect();
+        skies.sort_by(|a, b| a.path.cmp(&b.path));
+        skies
+    }
+
+    fn make_bitmap_info(handle: BitmapHandle, path: &Arc<String>, bitmap: &Bitmap) -> BitmapInfo {
+        BitmapInfo {
+            handle,
+            path: path.clone(),
+            bitmaps: bitmap.bitmaps.iter().map(|b| BitmapInfoBitmap { resolution: b.resolution, bitmap_type: b.bitmap_type }).collect(),
+            sequence_count: bitmap.sequences.len()
+        }
+    }
+
+    fn make_bsp_info(handle: BSPHandle, path: &Arc<String>, bsp: &BSP) -> BSPInfo {
+        BSPInfo {
+            handle,
+            path: path.clone(),
+            geometry_count: bsp.geometries.len(),
+            cluster_count: bsp.bsp_data.clusters.len(),
+            draw_distance: bsp.draw_distance
+        }
+    }
+
+    /// Set the current BSP of the default scene (scene #0).
+    ///
+    /// If `path` is `None`, the BSP will be unloaded.
+    ///
+    /// Returns `Err` if `path` refers to a BSP that isn't loaded.
+    pub fn set_current_bsp(&mut self, path: Option<&str>) -> MResult<()> {
+        self.set_scene_bsp(0, path)
+    }
+
+    /// Add a new, empty scene, returning its index.
+    ///
+    /// Scenes let different viewports render different BSPs (e.g. to preview two maps side by side).
+    pub fn add_scene(&mut self) -> usize {
+        self.scenes.push(Scene::default());
+        self.invalidate_debug_text();
+        self.scenes.len() - 1
+    }
+
+    /// Get the number of scenes, including the default scene.
+    pub fn get_scene_count(&self) -> usize {
+        self.scenes.len()
+    }
+
+    /// Set which scene the given viewport renders.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.get_viewport_count()` or `scene >= self.get_scene_count()`
+    pub fn set_viewport_scene(&mut self, viewport: usize, scene: usize) {
+        assert!(scene < self.scenes.len(), "scene {scene} does not exist");
+
+        let viewport = &mut self.player_viewports[viewport];
+        if viewport.scene != scene {
+            viewport.scene = scene;
+            viewport.viewport_fog = None;
+            viewport.sun = None;
+            viewport.ambient = None;
+            self.invalidate_debug_text();
+        }
+    }
+
+    /// Set the current BSP of the given scene.
+    ///
+    /// If `path` is `None`, the BSP will be unloaded.
+    ///
+    /// Returns `Err` if `scene` does not exist or if `path` refers to a BSP that isn't loaded.
+    pub fn set_scene_bsp(&mut self, scene: usize, path: Option<&str>) -> MResult<()> {
+        let bsp = match path {
+            Some(p) => match self.bsps.get_handle(p) {
+                Some(handle) => Some(handle),
+                None => return Err(BSPError::NotLoaded { path: p.to_owned() }.into())
+            },
+            None => None
+        };
+        self.set_scene_bsp_handle(scene, bsp)
+    }
+
+    /// Set the current BSP of the given scene by its handle.
+    ///
+    /// If `bsp` is `None`, the BSP will be unloaded.
+    ///
+    /// Returns `Err` if `scene` does not exist or if `bsp` is no longer valid.
+    pub fn set_scene_bsp_handle(&mut self, scene: usize, bsp: Option<BSPHandle>) -> MResult<()> {
+        if scene >= self.scenes.len() {
+            return Err(Error::from_data_error_string(format!("Can't set the BSP of scene #{scene}: that scene does not exist")))
+        }
+
+        if let Some(handle) = bsp {
+            if self.bsps.get(handle).is_none() {
+                return Err(Error::from_data_error_string(format!("Can't set the BSP of scene #{scene}: {handle:?} is no longer valid")))
+            }
+        }
+
+        self.scenes[scene].bsp = bsp;
+        self.invalidate_debug_text();
+        Ok(())
+    }
+
+    /// Register a placeholder asset, replacing any previously registered one.
+    ///
+    /// The asset at `path` must already be loaded. Once registered, references to missing assets of that kind are
+    /// drawn with the placeholder instead of failing to load. Only assets added afterward are affected. Pass `None`
+    /// to unregister it.
+    ///
+    /// This will error if:
+    /// - `path` is not loaded
+    /// - the asset at `path` can't be used for `asset` (see [`FallbackAsset`])
+    pub fn set_fallback_asset(&mut self, asset: FallbackAsset, path: Option<&str>) -> MResult<()> {
+        let Some(path) = path else {
+            match asset {
+                FallbackAsset::Bitmap2D => self.fallback_assets.bitmap_2d = None,
+                FallbackAsset::Cubemap => self.fallback_assets.cubemap = None,
+                FallbackAsset::Lightmap => self.fallback_assets.lightmap = None,
+                FallbackAsset::Shader => self.fallback_assets.shader = None
+            }
+            return Ok(())
+        };
+
+        if asset == FallbackAsset::Shader {
+            let Some(handle) = self.shaders.get_handle(path) else {
+                return Err(ShaderError::NotLoaded { path: path.to_owned() }.into())
+            };
+            let shader_type = self.shaders[handle].shader_type;
+            if shader_type == ShaderType::Model {
+                return Err(ShaderError::Invalid { reason: format!("{path} is a {shader_type:?} shader, which can't be used as a fallback for BSPs") }.into())
+            }
+            self.fallback_assets.shader = Some(handle);
+            return Ok(())
+        }
+
+        let Some(handle) = self.bitmaps.get_handle(path) else {
+            return Err(BitmapError::NotLoaded { path: path.to_owned() }.into())
+        };
+        let expected_type = if asset == FallbackAsset::Cubemap { BitmapType::Cubemap } else { BitmapType::Dim2D };
+        match self.bitmaps[handle].bitmaps.first() {
+            Some(b) if b.bitmap_type == expected_type => (),
+            Some(b) => return Err(BitmapError::Invalid { reason: format!("{path} is {:?}, but a {asset:?} fallback must be {expected_type:?}", b.bitmap_type) }.into()),
+            None => return Err(BitmapError::Invalid { reason: format!("{path} has no bitmaps") }.into())
+        }
+
+        match asset {
+            FallbackAsset::Bitmap2D => self.fallback_assets.bitmap_2d = Some(handle),
+            FallbackAsset::Cubemap => self.fallback_assets.cubemap = Some(handle),
+            FallbackAsset::Lightmap => self.fallback_assets.lightmap = Some(handle),
+            FallbackAsset::Shader => unreachable!()
+        }
+        Ok(())
+    }
+
+    /// Get the path of the registered placeholder asset, if any.
+    pub fn get_fallback_asset(&self, asset: FallbackAsset) -> Option<Arc<String>> {
+        let path = match asset {
+            FallbackAsset::Bitmap2D => self.fallback_assets.bitmap_2d.and_then(|h| self.bitmaps.get_path(h)),
+            FallbackAsset::Cubemap => self.fallback_assets.cubemap.and_then(|h| self.bitmaps.get_path(h)),
+            FallbackAsset::Lightmap => self.fallback_assets.lightmap.and_then(|h| self.bitmaps.get_path(h)),
+            FallbackAsset::Shader => self.fallback_assets.shader.and_then(|h| self.shaders.get_path(h))
+        };
+        path.cloned()
+    }
+
+    /// Get a sender for queueing commands from other threads.
+    ///
+    /// Queued commands are run by [`Renderer::process_commands`].
+    pub fn get_command_sender(&self) -> RendererCommandSender {
+        RendererCommandSender { sender: self.command_sender.clone() }
+    }
+
+    /// Run all commands queued with a [`RendererCommandSender`], in the order they were sent.
+    ///
+    /// This should be called on the renderer's thread, typically before each [`Renderer::draw_frame`]. Returns the
+    /// number of commands run.
+    ///
+    /// If a command errors, its error is returned immediately, and any commands after it stay queued for the next
+    /// call.
+    pub fn process_commands(&mut self) -> MResult<usize> {
+        let mut count = 0;
+        while let Ok(command) = self.command_receiver.try_recv() {
+            count += 1;
+            command(self)?;
+        }
+        Ok(count)
+    }
+
+    /// Set whether to keep a copy of the parameters of every asset added from now on.
+    ///
+    /// This is required for [`Renderer::save_scene_state`], but it roughly doubles the memory used by assets, so it
+    /// is disabled by default. Disabling it discards all retained parameters.
+    pub fn set_retain_asset_parameters(&mut self, retain: bool) {
+        if !retain {
+            self.retained_assets = None;
+        }
+        else if self.retained_assets.is_none() {
+            self.retained_assets = Some(AddAssetsParameter::default());
+        }
+    }
+
+    /// Take a snapshot of all loaded assets, scenes, and cameras.
+    ///
+    /// Returns `Err` if asset parameters are not being retained (see [`Renderer::set_retain_asset_parameters`]).
+    pub fn save_scene_state(&self) -> MResult<SceneState> {
+        let Some(assets) = self.retained_assets.clone() else {
+            return Err(Error::from_data_error_string("Can't save the scene state: asset parameters are not being retained".to_owned()))
+        };
+
+        Ok(SceneState {
+            assets,
+            scene_bsps: self.scenes
+                .iter()
+                .map(|s| s.bsp.map(|b| self.bsps.get_path(b).unwrap().to_string()))
+                .collect(),
+            viewport_scenes: self.player_viewports.iter().map(|v| v.scene).collect(),
+            cameras: self.player_viewports.iter().map(|v| v.camera).collect()
+        })
+    }
+
+    /// Replace everything with a snapshot made with [`Renderer::save_scene_state`].
+    ///
+    /// The renderer is reset first. Only as many viewports as the renderer has are restored. Asset parameters
+    /// continue to be retained if they were before.
+    ///
+    /// This will error if any asset fails to be added or the scene state is otherwise invalid, in which case the
+    /// renderer is left partially restored.
+    pub fn restore_scene_state(&mut self, state: SceneState) -> MResult<()> {
+        let SceneState { assets, scene_bsps, viewport_scenes, cameras } = state;
+
+        if scene_bsps.is_empty() {
+            return Err(Error::from_data_error_string("Can't restore the scene state: it has no scenes".to_owned()))
+        }
+        if let Some(scene) = viewport_scenes.iter().find(|s| **s >= scene_bsps.len()) {
+            return Err(Error::from_data_error_string(format!("Can't restore the scene state: scene #{scene} does not exist")))
+        }
+        if let Some(camera) = cameras.iter().find(|c| !(c.fov > 0.0 && c.fov < core::f32::consts::PI)) {
+            return Err(Error::from_data_error_string(format!("Can't restore the scene state: camera FoV {} is invalid", camera.fov)))
+        }
+
+        self.reset();
+        self.add_assets(assets, |_| ())?;
+
+        for (scene, bsp) in scene_bsps.iter().enumerate() {
+            if scene > 0 {
+                self.add_scene();
+            }
+            self.set_scene_bsp(scene, bsp.as_deref())?;
+        }
+        for (viewport, scene) in viewport_scenes.into_iter().enumerate().take(self.player_viewports.len()) {
+            self.set_viewport_scene(viewport, scene);
+        }
+        for (viewport, camera) in cameras.into_iter().enumerate().take(self.player_viewports.len()) {
+            self.set_camera_for_viewport(viewport, camera);
+        }
+
+        Ok(())
+    }
+
+    /// Rebuild the swapchain.
+    ///
+    /// You must use this when the window is resized or if the swapchain is invalidated. If only the size changed,
+    /// [`Renderer::set_resolution`] can be used instead.
+    ///
+    /// For headless renderers, this recreates the internal image at the new resolution, so the same renderer can be
+    /// reused to draw frames of different sizes (e.g. thumbnails).
+    ///
+    /// If the renderer is not headless and `parameters.resolution` has 0 on one or more dimensions (e.g. the window
+    /// was minimized), nothing is rebuilt, and [`Renderer::draw_frame`] returns [`FrameStatus::Minimized`] until the
+    /// swapchain is rebuilt at a non-zero resolution or the window is restored.
+    ///
+    /// Anisotropic filtering cannot be changed once the renderer is made, so `parameters.anisotropic_filtering` is
+    /// ignored.
+    pub fn rebuild_swapchain(&mut self, parameters: RendererParameters) -> MResult<()> {
+        if !self.is_headless() && (parameters.resolution.width == 0 || parameters.resolution.height == 0) {
+            self.vulkan.set_minimized();
+            return Ok(())
+        }
+        parameters.validate()?;
+        if self.deterministic {
+            self.check_deterministic_support(parameters.msaa)?;
+        }
+        self.vulkan.rebuild_swapchain(
+            &parameters
+        )?;
+        self.invalidate_debug_text();
+        Ok(())
+    }
+
+    /// Resize the renderer, keeping all other parameters.
+    ///
+    /// This rebuilds the swapchain (or the internal image if headless) and all render targets. Viewports are laid
+    /// out relative to the resolution, so they are resized as well.
+    ///
+    /// Returns `Err` if the renderer is headless and `resolution` has 0 on one or more dimensions. See
+    /// [`Renderer::rebuild_swapchain`] for what happens if the renderer is not headless.
+    pub fn set_resolution(&mut self, resolution: Resolution) -> MResult<()> {
+        self.rebuild_swapchain(RendererParameters { resolution, ..self.get_parameters() })
+    }
+
+    /// Replace the surface the renderer presents to with a new window, keeping all loaded data.
+    ///
+    /// This is needed when the host destroys and recreates its window (e.g. Android suspend/resume). `parameters`
+    /// describes the new window; the swapchain is rebuilt as if [`Renderer::rebuild_swapchain`] was called.
+    ///
+    /// # Safety
+    ///
+    /// `surface` must outlive the renderer (or until this is called again).
+    ///
+    /// Errors if:
+    /// - the renderer is headless
+    /// - the new window cannot be presented to with the current device
+    /// - `parameters` is invalid
+    pub unsafe fn replace_surface(&mut self, surface: &(impl HasRawWindowHandle + HasRawDisplayHandle), parameters: RendererParameters) -> MResult<()> {
+        parameters.validate()?;
+        self.vulkan.replace_surface(&parameters, surface)
+    }
+
+    /// Attach another window to the renderer, returning its index.
+    ///
+    /// The main window is window 0. Each window has its own swapchain, drawn with the renderer's parameters at
+    /// `resolution`, and [`Renderer::draw_frame`] draws all of them. Nothing is drawn to the new window until
+    /// viewports are moved to it with [`Renderer::set_viewport_window`]. Debug text and the overlay hook are only
+    /// drawn in the main window.
+    ///
+    /// Windows are rebuilt automatically when their size changes if it can be determined. Otherwise (e.g. on
+    /// Wayland), call [`Renderer::set_window_resolution`] when the window is resized.
+    ///
+    /// # Safety
+    ///
+    /// `surface` must outlive the renderer (or until it is removed with [`Renderer::remove_window`]).
+    ///
+    /// Errors if:
+    /// - the renderer is headless
+    /// - the window cannot be presented to with the current device
+    /// - `resolution` has 0 on one or more dimensions
+    pub unsafe fn add_window(&mut self, surface: &(impl HasRawWindowHandle + HasRawDisplayHandle), resolution: Resolution) -> MResult<usize> {
+        if resolution.width == 0 || resolution.height == 0 {
+            return Err(Error::from_data_error_string("Window resolution must be non-zero".to_owned()))
+        }
+        self.vulkan.add_window(surface, resolution)
+    }
+
+    /// Detach a window added with [`Renderer::add_window`].
+    ///
+    /// Its viewports are moved back to the main window. This waits for the GPU to finish with the window, after which
+    /// the host can destroy it. The indices of other windows don't change.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is 0 or is not attached
+    pub fn remove_window(&mut self, window: usize) -> MResult<()> {
+        self.vulkan.remove_window(window)?;
+        for viewport in 0..self.player_viewports.len() {
+            if self.player_viewports[viewport].window == window {
+                self.move_viewport_to_window(viewport, 0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return `true` if `window` is the main window or an attached window.
+    pub fn has_window(&self, window: usize) -> bool {
+        self.vulkan.has_window(window)
+    }
+
+    /// Resize a window.
+    ///
+    /// For the main window (window 0), this is the same as [`Renderer::set_resolution`]. Attached windows with 0 on
+    /// one or more dimensions are skipped until they are resized again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is not attached
+    pub fn set_window_resolution(&mut self, window: usize, resolution: Resolution) -> MResult<()> {
+        if window == 0 {
+            return self.set_resolution(resolution)
+        }
+        self.vulkan.set_window_resolution(window, resolution)
+    }
+
+    /// Set which window the given viewport is drawn to.
+    ///
+    /// The viewports of each window are laid out as split screen within it, in order.
+    ///
+    /// Returns `Err` if stereo rendering is enabled, since both eyes must be drawn to the same window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()` or if `window` is not attached
+    pub fn set_viewport_window(&mut self, viewport: usize, window: usize) -> MResult<()> {
+        assert!(self.has_window(window), "window {window} is not attached");
+        if self.player_viewports[viewport].window == window {
+            return Ok(())
+        }
+        if self.stereo {
+            return Err(Error::from_data_error_string("Viewports cannot be moved between windows while rendering in stereo".to_owned()))
+        }
+
+        self.move_viewport_to_window(viewport, window);
+        Ok(())
+    }
+
+    /// Get the window the given viewport is drawn to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_viewport_window(&self, viewport: usize) -> usize {
+        self.player_viewports[viewport].window
+    }
+
+    fn move_viewport_to_window(&mut self, viewport: usize, window: usize) {
+        let old_window = self.player_viewports[viewport].window;
+
+        // Motion blur and TAA would otherwise reproject from where the viewport was in the other window.
+        self.player_viewports[viewport].window = window;
+        self.player_viewports[viewport].previous_view_projection = None;
+
+        for window in [old_window, window] {
+            lay_out_split_screen(self.player_viewports.iter_mut().filter(|v| v.window == window));
+        }
+    }
+
+    /// Recreate the device after [`Renderer::draw_frame`] returned [`DeviceError::DeviceLost`](crate::error::DeviceError::DeviceLost).
+    ///
+    /// Everything on the old device is gone. If asset parameters are being retained (see
+    /// [`Renderer::set_retain_asset_parameters`]), the retained assets are added again and each scene's BSP is
+    /// restored. Otherwise, all data is cleared as if [`Renderer::reset`] was called, and the host must add it again
+    /// before setting the BSP. Cameras, viewports, windows, and the overlay hook are kept either way, but fallback
+    /// assets are always unregistered.
+    ///
+    /// Errors if:
+    /// - the renderer was created with [`Renderer::new_from_vulkan_device`] (the host must recreate the device and the
+    ///   renderer instead)
+    /// - `parameters` is invalid
+    /// - a new device could not be created
+    /// - a retained asset could not be added again, in which case the renderer is left partially restored
+    pub fn recover_from_device_lost(&mut self, parameters: RendererParameters) -> MResult<()> {
+        parameters.validate()?;
+
+        // Only fails if nothing is retained, in which case there is nothing to restore.
+        let state = self.save_scene_state().ok();
+
+        self.vulkan.recreate_device(&parameters)?;
+        self.debug_text.clear();
+
+        match state {
+            Some(state) => self.restore_scene_state(state),
+            None => {
+                self.reset();
+                Ok(())
+            }
+        }
+    }
+
+    /// Set the position, rotation, and FoV of the camera for the given viewport.
+    ///
+    /// `fov` must be in radians, and `position` must be a vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()` or if `!(camera.fov > 0.0 && camera.fov < PI)`
+    pub fn set_camera_for_viewport(&mut self, viewport: usize, camera: Camera) {
+        assert!(camera.fov > 0.0 && camera.fov < core::f32::consts::PI, "camera.fov is not between 0 (exclusive) and pi (exclusive)");
+
+        let viewport = &mut self.player_viewports[viewport];
+        if camera == viewport.camera {
+            return;
+        }
+
+        // FIXME: determine how fast it is supposed to be transitioned here?
+        let fog_transition_amount = camera.position.distance(viewport.camera.position).min(10.0) / 10.0;
+        if let Some(n) = viewport.viewport_fog.as_mut() {
+            n.transition_amount = (n.transition_amount + fog_transition_amount).min(1.0);
+        }
+
+        viewport.camera = Camera {
+            position: camera.position,
+            rotation: camera.rotation.try_normalize().unwrap_or(Vec3::Y),
+            fov: camera.fov,
+            lighting_mode: camera.lighting_mode,
+            fog: camera.fog
+        };
+
+        self.invalidate_debug_text();
+    }
+
+    /// Set how the FoV of the given viewport's camera changes with the viewport's aspect ratio.
+    ///
+    /// This has no effect while the viewport's view and projection are overridden with
+    /// [`Renderer::set_viewport_view_projection`].
+    ///
+    /// Default = [`FovScaling::Fixed`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_fov_scaling(&mut self, viewport: usize, fov_scaling: FovScaling) {
+        self.player_viewports[viewport].fov_scaling = fov_scaling;
+    }
+
+    /// Get how the FoV of the given viewport's camera changes with the viewport's aspect ratio.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_fov_scaling(&self, viewport: usize) -> FovScaling {
+        self.player_viewports[viewport].fov_scaling
+    }
+
+    /// Set view and projection matrices for the given viewport, overriding the ones derived from its camera.
+    ///
+    /// This is intended for VR, where the runtime (e.g. OpenXR) supplies the pose and FoV of each eye. The projection
+    /// must use reversed depth, mapping the near plane to 1 and the far plane to 0 (e.g.
+    /// `Mat4::perspective_infinite_reverse_lh`). The camera position is still used for fog, sorting
+    /// transparent geometry, and determining the current cluster, so it should be kept up to date as well.
+    ///
+    /// Pass `None` to go back to using the camera.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_viewport_view_projection(&mut self, viewport: usize, view_projection: Option<(Mat4, Mat4)>) {
+        self.player_viewports[viewport].view_projection = view_projection;
+    }
+
+    /// Force the given viewport to use `fog` regardless of which cluster its camera is in.
+    ///
+    /// This is useful for debugging fog or for viewing a map in a neutral environment. Pass `None` to go back to the
+    /// fog of the current cluster's sky. Fog can still be disabled entirely with [`Camera::fog`].
+    ///
+    /// Returns `Err` if `fog` is invalid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_fog_override(&mut self, viewport: usize, fog: Option<FogData>) -> MResult<()> {
+        if let Some(fog) = fog.as_ref() {
+            fog.validate()?;
+        }
+        self.player_viewports[viewport].fog_override = fog;
+        Ok(())
+    }
+
+    /// Get the fog override of the given viewport, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_fog_override(&self, viewport: usize) -> Option<FogData> {
+        self.player_viewports[viewport].fog_override
+    }
+
+    /// Blur what is out of focus in the given viewport, or pass `None` to keep everything sharp.
+    ///
+    /// Only the 3D scene is blurred, not debug primitives or the HUD. This has no effect unless
+    /// [`RendererParameters::depth_of_field`] is enabled.
+    ///
+    /// Returns `Err` if `depth_of_field` is invalid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_depth_of_field(&mut self, viewport: usize, depth_of_field: Option<DepthOfField>) -> MResult<()> {
+        if let Some(depth_of_field) = depth_of_field.as_ref() {
+            depth_of_field.validate()?;
+        }
+        self.player_viewports[viewport].depth_of_field = depth_of_field;
+        Ok(())
+    }
+
+    /// Get the depth of field of the given viewport, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_depth_of_field(&self, viewport: usize) -> Option<DepthOfField> {
+        self.player_viewports[viewport].depth_of_field
+    }
+
+    /// Blur the given viewport as its camera moves, or pass 0.0 to turn motion blur off.
+    ///
+    /// `intensity` is the fraction of the camera's motion since the last frame to blur across, from 0.0 to 1.0. Only
+    /// camera motion is blurred, and only the 3D scene, not debug primitives or the HUD. This has no effect unless
+    /// [`RendererParameters::motion_blur`] is enabled.
+    ///
+    /// Default = 0.0
+    ///
+    /// Returns `Err` if `intensity` is out of range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_motion_blur(&mut self, viewport: usize, intensity: f32) -> MResult<()> {
+        if !(0.0..=1.0).contains(&intensity) {
+            return Err(Error::from_data_error_string(format!("Invalid motion blur intensity {intensity}")))
+        }
+        self.player_viewports[viewport].motion_blur = intensity;
+        Ok(())
+    }
+
+    /// Get the motion blur intensity of the given viewport.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_motion_blur(&self, viewport: usize) -> f32 {
+        self.player_viewports[viewport].motion_blur
+    }
+
+    /// Set the exposure, bloom, color overlay, and letterbox of the given viewport.
+    ///
+    /// Each viewport has its own, so split screen players can have different screen effects at once. Only the 3D
+    /// scene is affected, not debug primitives or the HUD. This has no effect unless
+    /// [`RendererParameters::post_processing`] is enabled.
+    ///
+    /// Default = [`PostProcessing::default`], which leaves the scene unchanged
+    ///
+    /// Returns `Err` if `post_processing` is invalid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_post_processing(&mut self, viewport: usize, post_processing: PostProcessing) -> MResult<()> {
+        post_processing.validate()?;
+        self.player_viewports[viewport].post_processing = post_processing;
+        Ok(())
+    }
+
+    /// Get the exposure, bloom, color overlay, and letterbox of the given viewport.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_post_processing(&self, viewport: usize) -> PostProcessing {
+        self.player_viewports[viewport].post_processing
+    }
+
+    /// Set the distances past which stages of environment shaders are skipped in the given viewport.
+    ///
+    /// Each viewport has its own, so a zoomed in viewport can keep more detail than the others.
+    ///
+    /// Default = [`ShaderLod::default`], which never skips anything
+    ///
+    /// Returns `Err` if any distance is negative or NaN.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_shader_lod(&mut self, viewport: usize, shader_lod: ShaderLod) -> MResult<()> {
+        shader_lod.validate()?;
+        self.player_viewports[viewport].shader_lod = shader_lod;
+        Ok(())
+    }
+
+    /// Get the distances past which stages of environment shaders are skipped in the given viewport.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_shader_lod(&self, viewport: usize) -> ShaderLod {
+        self.player_viewports[viewport].shader_lod
+    }
+
+    /// Set the multiplier applied to lightmaps in all viewports.
+    ///
+    /// This is useful for compensating for dark lightmaps or for previewing a map at different exposure levels. It
+    /// is multiplied with the exposure of each viewport set with [`Renderer::set_viewport_lightmap_exposure`].
+    ///
+    /// Default = 1.0
+    ///
+    /// Returns `Err` if `exposure` is negative or not finite.
+    pub fn set_lightmap_exposure(&mut self, exposure: f32) -> MResult<()> {
+        validate_lightmap_exposure(exposure)?;
+        self.lightmap_exposure = exposure;
+        Ok(())
+    }
+
+    /// Get the multiplier applied to lightmaps in all viewports.
+    pub fn get_lightmap_exposure(&self) -> f32 {
+        self.lightmap_exposure
+    }
+
+    /// Set whether lightmaps are doubled when combined with textures, like the original engine does (modulate 2x).
+    ///
+    /// Without this, lightmapped surfaces look darker and muddier than in the original engine. This only affects
+    /// lightmap textures, not surfaces lit by the sky because they have no lightmap, and it is applied on top of the
+    /// lightmap exposure.
+    ///
+    /// Default = true
+    pub fn set_lightmap_overbright(&mut self, overbright: bool) {
+        self.lightmap_overbright = overbright;
+    }
+
+    /// Return `true` if lightmaps are doubled when combined with textures.
+    pub fn is_lightmap_overbright(&self) -> bool {
+        self.lightmap_overbright
+    }
+
+    /// Set the multiplier applied to lightmaps in the given viewport.
+    ///
+    /// This is multiplied with the exposure set with [`Renderer::set_lightmap_exposure`].
+    ///
+    /// Default = 1.0
+    ///
+    /// Returns `Err` if `exposure` is negative or not finite.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_viewport_lightmap_exposure(&mut self, viewport: usize, exposure: f32) -> MResult<()> {
+        validate_lightmap_exposure(exposure)?;
+        self.player_viewports[viewport].lightmap_exposure = exposure;
+        Ok(())
+    }
+
+    /// Get the multiplier applied to lightmaps in the given viewport, not including the global exposure.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_viewport_lightmap_exposure(&self, viewport: usize) -> f32 {
+        self.player_viewports[viewport].lightmap_exposure
+    }
+
+    /// Draw the given viewport's geometry with a debug visualization instead of its shaders.
+    ///
+    /// This is useful for debugging imported vertex data. Pass [`DebugRenderMode::None`] to draw normally.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn set_debug_render_mode(&mut self, viewport: usize, mode: DebugRenderMode) {
+        self.player_viewports[viewport].debug_render_mode = mode;
+    }
+
+    /// Get the debug visualization of the given viewport.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_debug_render_mode(&self, viewport: usize) -> DebugRenderMode {
+        self.player_viewports[viewport].debug_render_mode
+    }
+
+    /// Set whether BSP surfaces using the shader at `path` are drawn with a UV checkerboard instead of the shader.
+    ///
+    /// This is the same as [`DebugRenderMode::UVCheckerboard`], but only for the given shader and in all viewports.
+    /// This persists if the shader is replaced, but it is cleared when the renderer is reset.
+    ///
+    /// This will error if:
+    /// - `path` is not loaded
+    pub fn set_shader_checkerboard(&mut self, path: &str, checkerboard: bool) -> MResult<()> {
+        let Some(handle) = self.shaders.get_handle(path) else {
+            return Err(ShaderError::NotLoaded { path: path.to_owned() }.into())
+        };
+        if checkerboard {
+            self.checkerboard_shaders.insert(handle);
+        }
+        else {
+            self.checkerboard_shaders.remove(&handle);
+        }
+        Ok(())
+    }
+
+    /// Return `true` if BSP surfaces using the shader at `path` are drawn with a UV checkerboard.
+    ///
+    /// Returns `false` if `path` is not loaded.
+    pub fn is_shader_checkerboard(&self, path: &str) -> bool {
+        self.shaders.get_handle(path).is_some_and(|h| self.checkerboard_shaders.contains(&h))
+    }
+
+    /// Add a dynamic light to the given scene, returning a handle to it.
+    ///
+    /// Dynamic lights are added on top of lightmaps in the scene until they are removed or the renderer is reset. Only
+    /// the [`MAX_DYNAMIC_LIGHTS`] lights nearest to a viewport's camera are used when drawing that viewport.
+    ///
+    /// This will error if:
+    /// - `scene` does not exist
+    /// - `light` is invalid
+    /// - `light` references a gel that is not loaded or is not a cube map
+    pub fn add_light(&mut self, scene: usize, light: AddLightParameter) -> MResult<LightHandle> {
+        if scene >= self.scenes.len() {
+            return Err(Error::from_data_error_string(format!("Can't add a light to scene #{scene}: that scene does not exist")))
+        }
+        light.validate(self)?;
+        let handle = self.light_handles.next();
+        self.scenes[scene].lights.add(handle, light);
+        Ok(handle)
+    }
+
+    /// Replace the parameters of a dynamic light in the given scene, such as to move it.
+    ///
+    /// The light's color animation continues from where it was rather than restarting.
+    ///
+    /// This will error if:
+    /// - `scene` does not exist
+    /// - `handle` is no longer valid or belongs to a different scene
+    /// - `light` is invalid
+    pub fn update_light(&mut self, scene: usize, handle: LightHandle, light: AddLightParameter) -> MResult<()> {
+        if scene >= self.scenes.len() {
+            return Err(Error::from_data_error_string(format!("Can't update light: scene #{scene} does not exist")))
+        }
+        light.validate(self)?;
+        let Some(existing) = self.scenes[scene].lights.get_mut(handle) else {
+            return Err(Error::from_data_error_string(format!("Can't update light: {handle:?} is no longer valid in scene #{scene}")))
+        };
+        *existing = light;
+        Ok(())
+    }
+
+    /// Remove a dynamic light from the given scene, invalidating its handle.
+    ///
+    /// This will error if:
+    /// - `scene` does not exist
+    /// - `handle` is no longer valid or belongs to a different scene
+    pub fn remove_light(&mut self, scene: usize, handle: LightHandle) -> MResult<()> {
+        let Some(scene_data) = self.scenes.get_mut(scene) else {
+            return Err(Error::from_data_error_string(format!("Can't remove light: scene #{scene} does not exist")))
+        };
+        match scene_data.lights.remove(handle) {
+            Some(_) => Ok(()),
+            None => Err(Error::from_data_error_string(format!("Can't remove light: {handle:?} is no longer valid in scene #{scene}")))
+        }
+    }
+
+    /// Draw a line from `from` to `to` in world space.
+    ///
+    /// Debug primitives are drawn on top of the scene in every viewport and are occluded by world geometry. They are
+    /// drawn in every frame until `lifetime` has passed, but always in at least one frame, so a lifetime of
+    /// [`Duration::ZERO`] draws it in the next frame only.
+    pub fn debug_draw_line(&mut self, from: Vec3, to: Vec3, color: FloatColor, lifetime: Duration) {
+        self.debug_primitives.add_line(from, to, color, lifetime);
+    }
+
+    /// Draw the edges of an axis-aligned box spanning `min` to `max` in world space.
+    ///
+    /// See [`Renderer::debug_draw_line`] for how debug primitives are drawn.
+    pub fn debug_draw_box(&mut self, min: Vec3, max: Vec3, color: FloatColor, lifetime: Duration) {
+        self.debug_primitives.add_box(min, max, color, lifetime);
+    }
+
+    /// Draw a wireframe sphere in world space as three circles, one around each axis.
+    ///
+    /// See [`Renderer::debug_draw_line`] for how debug primitives are drawn.
+    pub fn debug_draw_sphere(&mut self, center: Vec3, radius: f32, color: FloatColor, lifetime: Duration) {
+        self.debug_primitives.add_sphere(center, radius, color, lifetime);
+    }
+
+    /// Draw the axes of `rotation` at `origin` in world space, each `length` units long.
+    ///
+    /// The X, Y, and Z axes are drawn in red, green, and blue, respectively.
+    ///
+    /// See [`Renderer::debug_draw_line`] for how debug primitives are drawn.
+    pub fn debug_draw_axis(&mut self, origin: Vec3, rotation: Mat3, length: f32, lifetime: Duration) {
+        self.debug_primitives.add_axis(origin, rotation, length, lifetime);
+    }
+
+    /// Set the scenario debug markers to draw, replacing any that were previously set.
+    ///
+    /// Markers are drawn in every viewport, like debug primitives, until they are replaced or the renderer is reset.
+    /// Categories can be hidden with [`Renderer::set_scenario_debug_marker_category_visible`].
+    pub fn set_scenario_debug_markers(&mut self, markers: Vec<ScenarioDebugMarker>) {
+        self.scenario_debug_markers.markers = markers;
+    }
+
+    /// Set whether scenario debug markers of the given category are drawn.
+    ///
+    /// All categories are drawn by default.
+    pub fn set_scenario_debug_marker_category_visible(&mut self, category: ScenarioDebugMarkerCategory, visible: bool) {
+        if visible {
+            self.scenario_debug_markers.hidden_categories.remove(&category);
+        }
+        else {
+            self.scenario_debug_markers.hidden_categories.insert(category);
+        }
+    }
+
+    /// Return `true` if scenario debug markers of the given category are drawn.
+    pub fn is_scenario_debug_marker_category_visible(&self, category: ScenarioDebugMarkerCategory) -> bool {
+        !self.scenario_debug_markers.hidden_categories.contains(&category)
+    }
+
+    /// Draw `text` with its top-left corner at `position`, in pixels from the top-left corner of the frame.
+    ///
+    /// Text is drawn with a built-in 8x8 pixel font, where each pixel of the font is drawn `scale` pixels wide. Only
+    /// printable ASCII characters are supported, and `\n` starts a new line. Screen-space text is drawn once over the
+    /// whole frame rather than in each viewport.
+    ///
+    /// See [`Renderer::debug_draw_line`] for how long debug primitives are drawn.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `!(scale > 0.0)`
+    pub fn debug_draw_text_2d(&mut self, position: [f32; 2], text: &str, scale: f32, color: FloatColor, lifetime: Duration) {
+        assert!(scale > 0.0, "scale is not greater than 0");
+        self.debug_primitives.add_text(text, DebugTextAnchor::Screen(position), scale, color, lifetime);
+    }
+
+    /// Draw `text` centered on `position` in world space.
+    ///
+    /// The text always faces the camera and stays the same size on screen regardless of distance, and it is drawn on
+    /// top of world geometry. Otherwise, it is drawn like [`Renderer::debug_draw_text_2d`], but in every viewport.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `!(scale > 0.0)`
+    pub fn debug_draw_text_3d(&mut self, position: Vec3, text: &str, scale: f32, color: FloatColor, lifetime: Duration) {
+        assert!(scale > 0.0, "scale is not greater than 0");
+        self.debug_primitives.add_text(text, DebugTextAnchor::World(position), scale, color, lifetime);
+    }
+
+    /// Remove all debug primitives, including ones that have not yet expired.
+    pub fn clear_debug_primitives(&mut self) {
+        self.debug_primitives.clear();
+    }
+
+    /// Set whether to render deterministically.
+    ///
+    /// In deterministic mode, the same assets, parameters, and sequence of calls always produce identical frames on
+    /// the same device and driver, which is useful for regression testing. Time-dependent output (such as the frame
+    /// rate in the debug text) is replaced with fixed values.
+    ///
+    /// Returns `Err` if enabling deterministic mode while MSAA is enabled and the device does not use the standard
+    /// sample locations.
+    pub fn set_deterministic(&mut self, deterministic: bool) -> MResult<()> {
+        if deterministic {
+            self.check_deterministic_support(self.vulkan.get_parameters().msaa)?;
+        }
+        if self.deterministic != deterministic {
+            self.deterministic = deterministic;
+            self.invalidate_debug_text();
+        }
+        Ok(())
+    }
+
+    /// Return `true` if rendering deterministically.
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    fn check_deterministic_support(&self, msaa: MSAA) -> MResult<()> {
+        if msaa != MSAA::NoMSAA && !self.vulkan.has_standard_sample_locations() {
+            return Err(DeviceError::Unsupported { reason: format!("Deterministic rendering with {msaa:?} requires standard sample locations, which the device does not use") }.into())
+        }
+        Ok(())
+    }
+
+    /// Set whether to render in stereo.
+    ///
+    /// In stereo mode, viewport #0 (left eye) and viewport #1 (right eye) are placed side-by-side with no split
+    /// screen bars between them. Each eye occupies one half of the frame, which can be obtained with
+    /// [`Renderer::get_last_frame_image`] and submitted to the VR runtime.
+    ///
+    /// Returns `Err` if enabling stereo and the renderer does not have exactly two viewports, both in the same window.
+    pub fn set_stereo(&mut self, stereo: bool) -> MResult<()> {
+        if stereo && self.player_viewports.len() != 2 {
+            return Err(Error::from_data_error_string(format!("Stereo rendering requires 2 viewports, but the renderer has {}", self.player_viewports.len())))
+        }
+        if stereo && self.player_viewports[0].window != self.player_viewports[1].window {
+            return Err(Error::from_data_error_string("Stereo rendering requires both viewports to be in the same window".to_owned()))
+        }
+
+        if stereo {
+            for (viewport, [x, y, width, height]) in self.player_viewports.iter_mut().zip([[0.0, 0.0, 0.5, 1.0], [0.5, 0.0, 0.5, 1.0]]) {
+                viewport.rel_x = x;
+                viewport.rel_y = y;
+                viewport.rel_width = width;
+                viewport.rel_height = height;
+            }
+        }
+        else if self.player_viewports.len() == 2 {
+            let window = self.player_viewports[0].window;
+            lay_out_split_screen(self.player_viewports.iter_mut().filter(|v| v.window == window));
+        }
+
+        self.stereo = stereo;
+        Ok(())
+    }
+
+    /// Set the thickness and color of the bars drawn between split screen viewports, or pass `None` to not draw them.
+    ///
+    /// Bars are never drawn in stereo mode or in windows with a single viewport.
+    ///
+    /// Default = [`SplitScreenBars::default`], which draws black bars 2 pixels thick at 640x480
+    ///
+    /// Returns `Err` if `bars` is invalid.
+    pub fn set_split_screen_bars(&mut self, bars: Option<SplitScreenBars>) -> MResult<()> {
+        if let Some(bars) = bars.as_ref() {
+            bars.validate()?;
+        }
+        self.split_screen_bars = bars;
+        Ok(())
+    }
+
+    /// Get the bars drawn between split screen viewports, if they are drawn.
+    pub fn get_split_screen_bars(&self) -> Option<SplitScreenBars> {
+        self.split_screen_bars
+    }
+
+    /// Get the camera data for the given viewport.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_camera_for_viewport(&self, viewport: usize) -> Camera {
+        self.player_viewports[viewport].camera
+    }
+
+    /// Get the index of the BSP cluster that the camera of the given viewport is in, if any.
+    ///
+    /// Returns `None` if no BSP is loaded in the viewport's scene or if the camera is outside of the BSP.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn get_viewport_cluster(&self, viewport: usize) -> Option<usize> {
+        let viewport = &self.player_viewports[viewport];
+        let bsp = self.scenes[viewport.scene].bsp.and_then(|b| self.bsps.get(b))?;
+        bsp.bsp_data.find_cluster(viewport.camera.position)
+    }
+
+    /// Get statistics of the last frame drawn.
+    pub fn last_frame_stats(&self) -> FrameStatistics {
+        self.frame_statistics
+    }
+
+    /// Draw a frame of a bitmap sequence on the HUD layer of the given viewport in the next frame.
+    ///
+    /// The HUD layer is drawn over the viewport after the 3D scene, in the order bitmaps were queued. It is cleared
+    /// after every call to [`Renderer::draw_frame`], so HUD bitmaps need to be queued every frame.
+    ///
+    /// For sequences of bitmaps, `frame` is the index of the bitmap in the sequence. For sequences of sprites, `frame`
+    /// is the index of the sprite.
+    ///
+    /// This will error if:
+    /// - `bitmap` is not loaded
+    /// - `sequence` or `frame` is out of bounds
+    /// - the bitmap is not a 2D texture
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn draw_hud_bitmap(&mut self, viewport: usize, bitmap: &str, sequence: usize, frame: usize, parameter: HudDrawParameter) -> MResult<()> {
+        assert!(viewport < self.player_viewports.len(), "viewport is out of bounds");
+
+        let draw = self.make_hud_bitmap_draw(viewport, bitmap, sequence, frame, parameter)?;
+        self.hud_draws.push(draw);
+        Ok(())
+    }
+
+    /// Draw a crosshair centered on the given viewport in the next frame.
+    ///
+    /// `frame` selects the bitmap or sprite of `sequence`, e.g. to change the crosshair while zoomed in. Like bitmaps
+    /// drawn with [`Renderer::draw_hud_bitmap`], crosshairs are cleared after every call to [`Renderer::draw_frame`].
+    ///
+    /// This will error if:
+    /// - `bitmap` is not loaded
+    /// - `sequence` or `frame` is out of bounds
+    /// - the bitmap is not a 2D texture
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn draw_crosshair(&mut self, viewport: usize, bitmap: &str, sequence: usize, frame: usize, parameter: HudCrosshairParameter) -> MResult<()> {
+        assert!(viewport < self.player_viewports.len(), "viewport is out of bounds");
+
+        let mut draw = self.make_hud_bitmap_draw(viewport, bitmap, sequence, frame, HudDrawParameter {
+            position: HudAnchor::Center.position(),
+            units: HudUnits::Normalized,
+            tint: parameter.color,
+            blend_mode: parameter.blend_mode,
+            ..Default::default()
+        })?;
+        draw.natural_size = draw.natural_size.map(|s| s * parameter.scale);
+        draw.offset = HudAnchor::Center.element_offset(parameter.offset, draw.natural_size);
+        self.hud_draws.push(draw);
+        Ok(())
+    }
+
+    /// Draw a nav point marking a world-space position on the HUD layer of every viewport in the next frame.
+    ///
+    /// The icon is centered on the position as seen by each viewport's camera. If the position is off-screen or
+    /// behind the camera, the icon is instead kept at the edge of the viewport in the direction of the position. Like
+    /// bitmaps drawn with [`Renderer::draw_hud_bitmap`], nav points are cleared after every call to
+    /// [`Renderer::draw_frame`].
+    ///
+    /// This will error if:
+    /// - `bitmap` is not loaded
+    /// - `sequence` or `frame` is out of bounds
+    /// - the bitmap is not a 2D texture
+    /// - `parameter.distance_font` is not loaded
+    pub fn draw_nav_point(&mut self, position: Vec3, bitmap: &str, sequence: usize, frame: usize, parameter: HudNavPointParameter) -> MResult<()> {
+        if let Some(font) = parameter.distance_font.as_ref() {
+            if !self.fonts.contains_key(font) {
+                return Err(DataError::NotLoaded { path: font.to_owned() }.into())
+            }
+        }
+
+        let mut icon = self.make_hud_bitmap_draw(0, bitmap, sequence, frame, HudDrawParameter {
+            tint: parameter.color,
+            ..Default::default()
+        })?;
+        icon.natural_size = icon.natural_size.map(|s| s * parameter.scale);
+        self.hud_nav_points.push(HudNavPoint { position, icon, parameter });
+        Ok(())
+    }
+
+    /// Draw a HUD interface on the HUD layer of the given viewport in the next frame.
+    ///
+    /// `values` are what meters and numbers display, such as the fraction of a unit's health remaining or the number
+    /// of rounds in a weapon. Like bitmaps drawn with [`Renderer::draw_hud_bitmap`], the interface is cleared after
+    /// every call to [`Renderer::draw_frame`].
+    ///
+    /// This will error if:
+    /// - any bitmap is not loaded
+    /// - any sequence or frame is out of bounds
+    /// - any bitmap is not a 2D texture
+    /// - any value is out of bounds of `values`
+    ///
+    /// If this errors, nothing is drawn.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn draw_hud_interface(&mut self, viewport: usize, interface: &HudInterfaceParameter, values: &[f32]) -> MResult<()> {
+        assert!(viewport < self.player_viewports.len(), "viewport is out of bounds");
+
+        let get_value = |index: usize| values.get(index).copied().ok_or_else(|| {
+            Error::from_data_error_string(format!("Value {index} is out of bounds; only {} value(s)", values.len()))
+        });
+
+        let mut draws = Vec::new();
+        for element in &interface.elements {
+            let scale = element.scale * interface.scale;
+            let offset = element.offset.map(|o| o * interface.scale);
+            let mut parameter = HudDrawParameter {
+                position: element.anchor.position(),
+                units: HudUnits::Normalized,
+                ..Default::default()
+            };
+
+            match &element.element_type {
+                HudInterfaceElementType::Static { bitmap, sequence, frame, color, blend_mode } => {
+                    parameter.tint = *color;
+                    parameter.blend_mode = *blend_mode;
+
+                    let mut draw = self.make_hud_bitmap_draw(viewport, bitmap, *sequence, *frame, parameter)?;
+                    draw.natural_size = draw.natural_size.map(|s| s * scale);
+                    draw.offset = element.anchor.element_offset(offset, draw.natural_size);
+                    draws.push(draw);
+                },
+                HudInterfaceElementType::Meter { bitmap, sequence, frame, value, min_color, max_color, empty_color } => {
+                    let value = get_value(*value)?.clamp(0.0, 1.0);
+                    parameter.tint = Vec4::from(*min_color).lerp(Vec4::from(*max_color), value).to_array();
+
+                    let mut draw = self.make_hud_bitmap_draw(viewport, bitmap, *sequence, *frame, parameter)?;
+                    draw.natural_size = draw.natural_size.map(|s| s * scale);
+                    draw.offset = element.anchor.element_offset(offset, draw.natural_size);
+                    draw.meter = Some(HudMeter { value, empty_color: *empty_color });
+                    draws.push(draw);
+                },
+                HudInterfaceElementType::Number { bitmap, sequence, value, digits, show_leading_zeros, color } => {
+                    let value = get_value(*value)?;
+                    parameter.tint = *color;
+
+                    let max_number = 10i64.saturating_pow(*digits as u32) - 1;
+                    let number = (value.round() as i64).clamp(-max_number, max_number);
+                    let magnitude = if *show_leading_zeros {
+                        format!("{:0width$}", number.unsigned_abs(), width = *digits)
+                    }
+                    else {
+                        number.unsigned_abs().to_string()
+                    };
+
+                    let mut frames: Vec<usize> = magnitude.bytes().map(|b| (b - b'0') as usize).collect();
+                    if number < 0 {
+                        frames.insert(0, 10);
+                    }
+
+                    let zero = self.make_hud_bitmap_draw(viewport, bitmap, *sequence, 0, parameter)?;
+                    let size = [zero.natural_size[0] * scale * *digits as f32, zero.natural_size[1] * scale];
+                    let [left, top] = element.anchor.element_offset(offset, size);
+
+                    // Right-align by placing digits from the right edge of the field.
+                    let mut x = left + size[0];
+                    for frame in frames.into_iter().rev() {
+                        let mut draw = self.make_hud_bitmap_draw(viewport, bitmap, *sequence, frame, parameter)?;
+                        draw.natural_size = draw.natural_size.map(|s| s * scale);
+                        x -= draw.natural_size[0];
+                        draw.offset = [x, top];
+                        draws.push(draw);
+                    }
+                }
+            }
+        }
+
+        self.hud_draws.extend(draws);
         Ok(())
     }
 
-    /// Add a bitmap with the given parameters.
-    ///
-    /// Note that replacing bitmaps is not yet supported.
-    ///
-    /// This will error if:
-    /// - `bitmap` is invalid
-    /// - replacing a bitmap would break any dependencies (HUDs, shaders, etc.)
-    pub fn add_bitmap(&mut self, path: &str, bitmap: AddBitmapParameter) -> MResult<()> {
-        let bitmap_path = Arc::new(path.to_owned());
-        if self.bitmaps.contains_key(&bitmap_path) {
-            return Err(Error::from_data_error_string(format!("{path} already exists (replacing bitmaps is not yet supported)")))
+    fn make_hud_bitmap_draw(&self, viewport: usize, bitmap: &str, sequence: usize, frame: usize, parameter: HudDrawParameter) -> MResult<HudDraw> {
+        let Some(handle) = self.bitmaps.get_handle(bitmap) else {
+            return Err(BitmapError::NotLoaded { path: bitmap.to_owned() }.into())
+        };
+        let data = &self.bitmaps[handle];
+
+        let Some(sequence_data) = data.sequences.get(sequence) else {
+            return Err(Error::from_data_error_string(format!("Sequence {sequence} of {bitmap} is out of bounds; only {} sequence(s)", data.sequences.len())))
+        };
+
+        let (bitmap_index, texture_coords) = match sequence_data {
+            AddBitmapSequenceParameter::Bitmap { first, count } => {
+                if frame >= *count {
+                    return Err(Error::from_data_error_string(format!("Frame {frame} of sequence {sequence} of {bitmap} is out of bounds; only {count} bitmap(s)")))
+                }
+                (first + frame, [0.0, 0.0, 1.0, 1.0])
+            },
+            AddBitmapSequenceParameter::Sprites { sprites } => {
+                let Some(sprite) = sprites.get(frame) else {
+                    return Err(Error::from_data_error_string(format!("Frame {frame} of sequence {sequence} of {bitmap} is out of bounds; only {} sprite(s)", sprites.len())))
+                };
+                (sprite.bitmap, [sprite.left, sprite.top, sprite.right, sprite.bottom])
+            }
+        };
+
+        let bitmap_data = &data.bitmaps[bitmap_index];
+        if bitmap_data.bitmap_type != BitmapType::Dim2D {
+            return Err(Error::from_data_error_string(format!("Bitmap #{bitmap_index} of {bitmap} is not a 2D texture")))
         }
 
-        bitmap.validate()?;
-        let bitmap = Bitmap::load_from_parameters(self, bitmap)?;
-        self.bitmaps.insert(bitmap_path, bitmap);
-        Ok(())
+        let Resolution { width, height } = bitmap_data.resolution;
+        let [left, top, right, bottom] = texture_coords;
+        let natural_size = [(right - left) * width as f32, (bottom - top) * height as f32];
+
+        Ok(HudDraw {
+            viewport,
+            image: HudImage::Bitmap { handle, index: bitmap_index },
+            texture_coords,
+            natural_size,
+            offset: [0.0, 0.0],
+            meter: None,
+            parameter
+        })
     }
 
-    /// Add a shader.
+    /// Draw text with a font on the HUD layer of the given viewport in the next frame.
     ///
-    /// Note that replacing shaders is not yet supported.
+    /// Like bitmaps drawn with [`Renderer::draw_hud_bitmap`], text is cleared after every call to
+    /// [`Renderer::draw_frame`]. Color (`^`) and alignment (`|`) codes are handled, and lines are broken at `\n` and
+    /// `|n`. Characters that aren't in the font are skipped.
     ///
     /// This will error if:
-    /// - `pipeline` is invalid
-    /// - `pipeline` contains invalid dependencies
-    /// - replacing a pipeline would break any dependencies
-    pub fn add_shader(&mut self, path: &str, shader: AddShaderParameter) -> MResult<()> {
-        let shader_path = Arc::new(path.to_owned());
-        if self.shaders.contains_key(&shader_path) {
-            return Err(Error::from_data_error_string(format!("{path} already exists (replacing shaders is not yet supported)")))
-        }
-
-        shader.validate(self)?;
-        let shader = Shader::load_from_parameters(self, shader)?;
-        self.shaders.insert(shader_path, shader);
+    /// - `font` is not loaded
+    ///
+    /// # Panics
+    ///
+    /// Panics if:
+    /// - `viewport >= self.viewport_count()`
+    /// - `parameter.scale` is not positive
+    pub fn draw_hud_text(&mut self, viewport: usize, font: &str, text: &str, parameter: HudTextParameter) -> MResult<()> {
+        assert!(viewport < self.player_viewports.len(), "viewport is out of bounds");
+        assert!(parameter.scale > 0.0, "scale must be positive");
+
+        let mut draws = Vec::new();
+        self.make_hud_text_draws(viewport, font, text, parameter, &mut draws)?;
+        self.hud_draws.extend(draws);
         Ok(())
     }
 
-    /// Add a geometry.
+    /// Draw a UI widget and its children on the HUD layer of the given viewport in the next frame.
     ///
-    /// Note that replacing geometries is not yet supported.
+    /// Widgets are placed on a canvas of [`UI_CANVAS_SIZE`] that is stretched to fill the viewport, with `widget`'s
+    /// origin at the top-left corner. Like other HUD draws, widgets are cleared after every call to
+    /// [`Renderer::draw_frame`].
     ///
-    /// This will error if:
-    /// - `geometry` is invalid
-    /// - `geometry` contains invalid dependencies
-    /// - replacing a geometry would break any dependencies
-    #[allow(unused_variables)]
-    pub fn add_geometry(&mut self, path: &str, geometry: AddGeometryParameter) -> MResult<()> {
-        todo!()
-    }
-
-    /// Add a sky.
+    /// `is_focused` is called with the path of each widget (the indices of the children leading to it, so `widget`'s
+    /// path is empty), and it returns whether the widget is focused and should be drawn highlighted.
     ///
     /// This will error if:
-    /// - `sky` is invalid
-    /// - `sky` contains invalid dependencies
-    pub fn add_sky(&mut self, path: &str, sky: AddSkyParameter) -> MResult<()> {
-        sky.validate(self)?;
-
-        // tool.exe defaults 0.0 max density to 1.0, so fog should be disabled if both the start and
-        // max distance are 0.0.
+    /// - any bitmap or font is not loaded
+    /// - any sequence or frame is out of bounds
+    /// - any bitmap is not a 2D texture
+    ///
+    /// If this errors, nothing is drawn.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn draw_ui_widget(&mut self, viewport: usize, widget: &UIWidgetParameter, is_focused: &mut dyn FnMut(&[usize]) -> bool) -> MResult<()> {
+        assert!(viewport < self.player_viewports.len(), "viewport is out of bounds");
 
-        let mut outdoor_fog = sky.outdoor_fog;
-        let mut indoor_fog = sky.indoor_fog;
+        let mut draws = Vec::new();
+        self.make_ui_widget_draws(viewport, widget, [0.0, 0.0], &mut Vec::new(), is_focused, &mut draws)?;
+        self.hud_draws.extend(draws);
+        Ok(())
+    }
 
-        if outdoor_fog.distance_to == 0.0 {
-            outdoor_fog = FogData::default();
+    fn make_ui_widget_draws(
+        &self,
+        viewport: usize,
+        widget: &UIWidgetParameter,
+        origin: [f32; 2],
+        path: &mut Vec<usize>,
+        is_focused: &mut dyn FnMut(&[usize]) -> bool,
+        draws: &mut Vec<HudDraw>
+    ) -> MResult<()> {
+        let focused = is_focused(path.as_slice());
+        let units = HudUnits::Canvas { width: UI_CANVAS_SIZE[0], height: UI_CANVAS_SIZE[1] };
+        let [left, top, right, bottom] = widget.bounds;
+
+        if let Some(background) = &widget.background {
+            let (frame, tint) = if focused {
+                (background.focused_frame.unwrap_or(background.frame), background.focused_color)
+            }
+            else {
+                (background.frame, background.color)
+            };
+            draws.push(self.make_hud_bitmap_draw(viewport, &background.bitmap, background.sequence, frame, HudDrawParameter {
+                position: [origin[0] + left, origin[1] + top],
+                size: Some([right - left, bottom - top]),
+                units,
+                tint,
+                blend_mode: HudBlendMode::AlphaBlend
+            })?);
         }
 
-        if indoor_fog.distance_to == 0.0 {
-            indoor_fog = FogData::default();
+        if let Some(text) = &widget.text {
+            let x = match text.alignment {
+                HudTextAlignment::Left => left,
+                HudTextAlignment::Center => (left + right) / 2.0,
+                HudTextAlignment::Right => right
+            };
+            self.make_hud_text_draws(viewport, &text.font, &text.text, HudTextParameter {
+                position: [origin[0] + x, origin[1] + top],
+                units,
+                alignment: text.alignment,
+                color: if focused { text.focused_color } else { text.color },
+                max_width: (right > left).then_some(right - left),
+                drop_shadow: text.drop_shadow,
+                ..Default::default()
+            }, draws)?;
         }
 
-        self.skies.insert(Arc::new(path.to_owned()), Sky {
-            geometry: sky.geometry.map(|s| self.geometries.get_key_value(&s).unwrap().0.clone()),
-            outdoor_fog,
-            indoor_fog
-        });
+        for (index, child) in widget.children.iter().enumerate() {
+            let child_origin = [origin[0] + child.offset[0], origin[1] + child.offset[1]];
+            path.push(index);
+            let result = self.make_ui_widget_draws(viewport, &child.widget, child_origin, path, is_focused, draws);
+            path.pop();
+            result?;
+        }
 
         Ok(())
     }
 
-    /// Add a BSP.
-    ///
-    /// Note that replacing BSPs is not yet supported.
-    ///
-    /// This will error if:
-    /// - `bsp` is invalid
-    /// - `bsp` contains invalid dependencies
-    pub fn add_bsp(&mut self, path: &str, bsp: AddBSPParameter) -> MResult<()> {
-        let bsp_path = Arc::new(path.to_owned());
-        if self.bsps.contains_key(&bsp_path) {
-            return Err(Error::from_data_error_string(format!("{path} already exists (replacing BSPs is not yet supported)")))
+    fn make_hud_text_draws(&self, viewport: usize, font: &str, text: &str, parameter: HudTextParameter, draws: &mut Vec<HudDraw>) -> MResult<()> {
+        let Some((font_path, font_data)) = self.fonts.get_key_value(&font.to_owned()) else {
+            return Err(DataError::NotLoaded { path: font.to_owned() }.into())
+        };
+
+        let alignment = match parameter.alignment {
+            HudTextAlignment::Left => TextAlignment::Left,
+            HudTextAlignment::Center => TextAlignment::Center,
+            HudTextAlignment::Right => TextAlignment::Right
+        };
+        let max_width = parameter.max_width.map(|w| (w / parameter.scale) as i32);
+
+        let mut characters = Vec::new();
+        font_data.layout_string(text, parameter.color, alignment, max_width, &mut characters);
+
+        let Resolution { width: atlas_width, height: atlas_height } = font_data.atlas.bitmaps[0].resolution;
+        let scale = parameter.scale;
+
+        // Draw all shadows first so that they don't cover adjacent characters.
+        let passes: &[bool] = if parameter.drop_shadow { &[true, false] } else { &[false] };
+        for shadow in passes {
+            for character in &characters {
+                let glyph = &font_data.characters[&character.character];
+                if glyph.width == 0 || glyph.height == 0 {
+                    continue
+                }
+
+                let shadow_offset = if *shadow { scale } else { 0.0 };
+                let x = (character.x - glyph.origin_x) as f32 * scale + shadow_offset;
+                let y = (character.y + font_data.ascending_height as i32 - glyph.origin_y) as f32 * scale + shadow_offset;
+
+                let [left, top] = glyph.atlas_position;
+                let texture_coords = [
+                    left as f32 / atlas_width as f32,
+                    top as f32 / atlas_height as f32,
+                    (left as f32 + glyph.width as f32) / atlas_width as f32,
+                    (top as f32 + glyph.height as f32) / atlas_height as f32
+                ];
+
+                let tint = if *shadow { [0.0, 0.0, 0.0, character.color[3]] } else { character.color };
+
+                draws.push(HudDraw {
+                    viewport,
+                    image: HudImage::Font(font_path.clone()),
+                    texture_coords,
+                    natural_size: [glyph.width as f32 * scale, glyph.height as f32 * scale],
+                    offset: [x, y],
+                    meter: None,
+                    parameter: HudDrawParameter {
+                        position: parameter.position,
+                        size: None,
+                        units: parameter.units,
+                        tint,
+                        blend_mode: parameter.blend_mode
+                    }
+                });
+            }
         }
 
-        bsp.validate(self)?;
-        let bsp = BSP::load_from_parameters(self, bsp)?;
-        self.bsps.insert(bsp_path, Arc::new(bsp));
         Ok(())
     }
 
-    /// Set the current BSP.
+    /// Get the number of viewports.
+    pub fn get_viewport_count(&self) -> usize {
+        self.player_viewports.len()
+    }
+
+    /// Draw a frame.
     ///
-    /// If `path` is `None`, the BSP will be unloaded.
+    /// If the window size no longer matches the swapchain (e.g. the window was resized), the swapchain is rebuilt at
+    /// the new size of the window and, if the frame could not be presented, it is drawn again. The host is notified
+    /// via the hook set with [`Renderer::set_swapchain_rebuilt_hook`], if any.
     ///
-    /// Returns `Err` if `path` refers to a BSP that isn't loaded.
-    pub fn set_current_bsp(&mut self, path: Option<&str>) -> MResult<()> {
-        if let Some(p) = path {
-            let key = self
-                .bsps
-                .keys()
-                .find(|f| f.as_str() == p)
-                .map(|b| b.clone());
-
-            if key.is_none() {
-                return Err(Error::from_data_error_string(format!("Can't set current BSP to {path:?}: that BSP is not loaded")))
-            }
-
-            self.current_bsp = key;
+    /// If [`FrameStatus::ResizeRequired`] is returned, the swapchain could not be rebuilt automatically because the
+    /// size of the window is unknown (e.g. on Wayland), and [`Renderer::set_resolution`] should be called with the new
+    /// size.
+    ///
+    /// If [`FrameStatus::Minimized`] is returned, the window has no area, so nothing was drawn. Drawing resumes at the
+    /// new size of the window once it is restored.
+    ///
+    /// Windows added with [`Renderer::add_window`] are drawn after the main window, even if it is minimized. The
+    /// returned status is only of the main window.
+    pub fn draw_frame(&mut self) -> MResult<FrameStatus> {
+        let result = self.draw_all_windows();
+
+        // HUD bitmaps are queued every frame, so don't carry them over even if the frame wasn't drawn.
+        self.hud_draws.clear();
+        self.hud_nav_points.clear();
+        let result = result?;
+        if result == FrameStatus::Minimized {
+            return Ok(result)
         }
-        else {
-            self.current_bsp = None;
+
+        if result == FrameStatus::Drawn {
+            self.debug_primitives.remove_expired(Instant::now());
         }
+        self.update_frame_rate_counter();
 
-        Ok(())
+        Ok(result)
     }
 
-    /// Rebuild the swapchain.
+    /// Read back the most recently drawn frame.
     ///
-    /// You must use this when the window is resized or if the swapchain is invalidated.
-    pub fn rebuild_swapchain(&mut self, parameters: RendererParameters) -> MResult<()> {
-        if parameters.resolution.height == 0 || parameters.resolution.width == 0 {
-            return Err(Error::DataError { error: "resolution has 0 on one or more dimensions".to_owned() })
-        }
-        self.vulkan.rebuild_swapchain(
-            &parameters
-        )
+    /// The frame is returned at the render resolution (i.e. with `render_scale` applied) and before being presented.
+    /// This works for both windowed and headless renderers, but it waits for the GPU to finish all work, so it should
+    /// not be called every frame.
+    ///
+    /// Returns `Err` if no frame has been drawn yet.
+    pub fn capture_frame(&mut self) -> MResult<CapturedFrame> {
+        self.vulkan.capture_frame()
     }
 
-    /// Set the position, rotation, and FoV of the camera for the given viewport.
+    /// Capture a cubemap of the given viewport's BSP as seen from `position`, adding it as a bitmap at `path`.
     ///
-    /// `fov` must be in radians, and `position` must be a vector.
+    /// Each face is `resolution` x `resolution` and is drawn with the viewport's fog and lighting. Only opaque BSP
+    /// geometry is drawn. The bitmap can be used as a shader's reflection cube map, or it can be set as the fallback for
+    /// shaders whose tags lack cubemaps with [`Renderer::set_fallback_asset`] (which only affects shaders added after).
+    ///
+    /// As with [`Renderer::replace_bitmap`], an existing bitmap at `path` is replaced. This waits for the GPU to finish
+    /// all work, so it should not be called every frame.
+    ///
+    /// This will error if:
+    /// - no BSP is loaded in the viewport's scene
+    /// - `resolution` is 0 or too large for the device
     ///
     /// # Panics
     ///
-    /// Panics if `viewport >= self.viewport_count()` or if `!(camera.fov > 0.0 && camera.fov < PI)`
-    pub fn set_camera_for_viewport(&mut self, viewport: usize, camera: Camera) {
-        assert!(camera.fov > 0.0 && camera.fov < core::f32::consts::PI, "camera.fov is not between 0 (exclusive) and pi (exclusive)");
-
-        let viewport = &mut self.player_viewports[viewport];
-        if camera == viewport.camera {
-            return;
+    /// Panics if `viewport >= self.viewport_count()`
+    pub fn capture_reflection_probe(&mut self, viewport: usize, path: &str, position: Vec3, resolution: u32) -> MResult<BitmapHandle> {
+        let scene = self.player_viewports[viewport].scene;
+        let Some(bsp) = self.scenes[scene].bsp.and_then(|b| self.bsps.get(b)).cloned() else {
+            return Err(Error::from_data_error_string(format!("Viewport {viewport} has no BSP to capture a reflection probe in")))
+        };
+        if resolution == 0 {
+            return Err(Error::from_data_error_string("Reflection probe resolution must be non-zero".to_owned()))
         }
 
-        // FIXME: determine how fast it is supposed to be transitioned here?
-        let fog_transition_amount = Vec3::from(camera.position).distance(Vec3::from(viewport.camera.position)).min(10.0) / 10.0;
-        if let Some(n) = viewport.viewport_fog.as_mut() {
-            n.transition_amount = (n.transition_amount + fog_transition_amount).min(1.0);
-        }
+        let data = VulkanRenderer::capture_reflection_probe(self, viewport, &bsp, position, resolution)?;
+        self.replace_bitmap(path, AddBitmapParameter {
+            bitmaps: vec![AddBitmapBitmapParameter {
+                format: BitmapFormat::A8B8G8R8,
+                bitmap_type: BitmapType::Cubemap,
+                resolution: Resolution { width: resolution, height: resolution },
+                mipmap_count: 0,
+                data
+            }],
+            sequences: vec![AddBitmapSequenceParameter::Bitmap { first: 0, count: 1 }]
+        })
+    }
 
-        viewport.camera = Camera {
-            position: camera.position,
-            rotation: Vec3::from(camera.rotation).try_normalize().unwrap_or(Vec3::new(0.0, 1.0, 0.0)).into(),
-            fov: camera.fov,
-            lightmaps: camera.lightmaps,
-            fog: camera.fog
-        };
+    /// Get the image that the most recently drawn frame was rendered into (before being presented).
+    ///
+    /// The image is in `R8G8B8A8_UNORM` format at the render resolution. For headless renderers, the frame is
+    /// finished by the time [`Renderer::draw_frame`] returns, so it can be sampled or copied by the host immediately.
+    ///
+    /// Returns `None` if no frame has been drawn yet.
+    pub fn get_last_frame_image(&self) -> Option<Arc<vulkano::image::Image>> {
+        self.vulkan.get_last_frame_image()
+    }
 
-        self.invalidate_debug_text();
+    /// Get the parameters currently in effect.
+    ///
+    /// These may differ from the parameters the renderer was created with if `clamp_to_device` was set, and they
+    /// reflect any changes made with [`Renderer::rebuild_swapchain`].
+    pub fn get_parameters(&self) -> RendererParameters {
+        RendererParameters {
+            number_of_viewports: self.player_viewports.len(),
+            ..*self.vulkan.get_parameters()
+        }
     }
 
-    /// Get the camera data for the given viewport.
+    /// Switch to a quality preset, rebuilding the swapchain with the parameters it covers.
     ///
-    /// # Panics
+    /// See [`QualityPreset::apply`] for which parameters are covered. Anisotropic filtering cannot be changed once the
+    /// renderer is made, so use [`RendererParametersBuilder::quality_preset`] to make the renderer with it.
     ///
-    /// Panics if `viewport >= self.viewport_count()`
-    pub fn get_camera_for_viewport(&self, viewport: usize) -> Camera {
-        self.player_viewports[viewport].camera
+    /// Errors if the device does not support the preset's MSAA and `clamp_to_device` was not set.
+    pub fn set_quality_preset(&mut self, preset: QualityPreset) -> MResult<()> {
+        self.rebuild_swapchain(preset.apply(self.get_parameters()))
     }
 
-    /// Get the number of viewports.
-    pub fn get_viewport_count(&self) -> usize {
-        self.player_viewports.len()
+    /// Get the quality preset that the parameters in effect match, or [`QualityPreset::Custom`] if none do.
+    pub fn get_quality_preset(&self) -> QualityPreset {
+        QualityPreset::from_parameters(&self.get_parameters())
     }
 
-    /// Draw a frame.
-    ///
-    /// If `true`, the swapchain needs rebuilt.
-    pub fn draw_frame(&mut self) -> MResult<bool> {
-        if self.debug_text_stale {
-            self.draw_debug_text()?;
-        }
-        self.fixup_fog_and_render_distances();
-        let result = VulkanRenderer::draw_frame(self)?;
+    /// Return `true` if the renderer is not presenting to a surface.
+    pub fn is_headless(&self) -> bool {
+        self.vulkan.is_headless()
+    }
 
-        self.update_frame_rate_counter();
+    /// Set a function to be called after the scene is drawn but before the frame is presented.
+    ///
+    /// This allows UI libraries (egui, imgui, etc.) to record their draw calls into the same frame. See
+    /// [`OverlayContext`] for what is available.
+    ///
+    /// Pass `None` to remove the hook.
+    pub fn set_overlay_hook(&mut self, hook: Option<Box<dyn FnMut(&mut OverlayContext) + Send>>) {
+        self.overlay_hook = hook;
+    }
 
-        Ok(result)
+    /// Set a function to be called with the new resolution whenever [`Renderer::draw_frame`] rebuilds the swapchain
+    /// on its own (e.g. because the window was resized or restored).
+    ///
+    /// Pass `None` to remove the hook.
+    pub fn set_swapchain_rebuilt_hook(&mut self, hook: Option<Box<dyn FnMut(Resolution) + Send>>) {
+        self.swapchain_rebuilt_hook = hook;
     }
 
     /// Set whether debug info is displayed.
@@ -383,7 +2311,7 @@ impl Renderer {
         match font {
             Some(font) => {
                 let Some(font) = self.fonts.get_key_value(&font.to_owned()) else {
-                    return Err(Error::from_data_error_string(format!("Font {font} is not loaded")))
+                    return Err(DataError::NotLoaded { path: font.to_owned() }.into())
                 };
                 self.debug_font = Some(font.0.clone())
             }
@@ -400,17 +2328,133 @@ impl Renderer {
         self.debug_text_stale = true;
     }
 
-    fn fixup_fog_and_render_distances(&mut self) {
-        let Some(bsp) = self.current_bsp.as_ref().and_then(|b| self.bsps.get(b)) else { return };
+    /// Draw the main window, then any other windows, returning the status of the main window.
+    fn draw_all_windows(&mut self) -> MResult<FrameStatus> {
+        let status = if self.update_minimized()? {
+            self.update_viewports();
+            FrameStatus::Minimized
+        }
+        else {
+            self.draw_frame_and_recover_swapchain()?
+        };
+        VulkanRenderer::draw_windows(self)?;
+        Ok(status)
+    }
+
+    /// Update the fog, draw distances, and lens flares of each viewport for the next frame.
+    fn update_viewports(&mut self) {
+        self.fixup_fog_and_render_distances();
+        self.update_lens_flares();
+    }
+
+    fn draw_frame_and_recover_swapchain(&mut self) -> MResult<FrameStatus> {
+        if self.debug_text_stale {
+            self.draw_debug_text()?;
+        }
+        self.update_viewports();
+        match VulkanRenderer::draw_frame(self)? {
+            // The frame wasn't presented, so try again with a new swapchain
+            FrameStatus::ResizeRequired => {
+                if self.rebuild_swapchain_for_surface()? {
+                    if self.debug_text_stale {
+                        self.draw_debug_text()?;
+                    }
+                    VulkanRenderer::draw_frame(self)
+                }
+                else if self.vulkan.is_minimized() {
+                    Ok(FrameStatus::Minimized)
+                }
+                else {
+                    Ok(FrameStatus::ResizeRequired)
+                }
+            },
+
+            // The frame was presented, but the next one should use a new swapchain
+            FrameStatus::Drawn if self.vulkan.is_suboptimal() => {
+                if self.rebuild_swapchain_for_surface()? || self.vulkan.is_minimized() {
+                    Ok(FrameStatus::Drawn)
+                }
+                else {
+                    Ok(FrameStatus::ResizeRequired)
+                }
+            },
+            status => Ok(status)
+        }
+    }
+
+    /// Return `true` if the window has no area, rebuilding the swapchain if it was restored since the last frame.
+    fn update_minimized(&mut self) -> MResult<bool> {
+        let Some(resolution) = self.vulkan.get_surface_resolution()? else {
+            return Ok(self.vulkan.is_minimized())
+        };
+        if resolution.width == 0 || resolution.height == 0 {
+            self.vulkan.set_minimized();
+            return Ok(true)
+        }
+        if self.vulkan.is_minimized() {
+            self.rebuild_swapchain_for_surface()?;
+        }
+        Ok(false)
+    }
+
+    /// Rebuild the swapchain at the current size of the surface, notifying the host if it was rebuilt.
+    ///
+    /// Returns `false` if the size of the surface is unknown or if it has no area.
+    fn rebuild_swapchain_for_surface(&mut self) -> MResult<bool> {
+        let Some(resolution) = self.vulkan.get_surface_resolution()? else {
+            return Ok(false)
+        };
+        self.set_resolution(resolution)?;
+        if self.vulkan.is_minimized() {
+            return Ok(false)
+        }
+        if let Some(hook) = self.swapchain_rebuilt_hook.as_mut() {
+            hook(resolution);
+        }
+        Ok(true)
+    }
+
+    fn update_lens_flares(&mut self) {
+        let fade = self.lens_flares.begin_update(self.player_viewports.len(), self.deterministic);
+        for (index, viewport) in self.player_viewports.iter().enumerate() {
+            let Some(bsp) = self.scenes[viewport.scene].bsp.and_then(|b| self.bsps.get(b)) else {
+                self.lens_flares.update_viewport(index, None, viewport.camera.position, &Occluders::default(), fade);
+                continue
+            };
+
+            let sky = bsp.bsp_data
+                .find_cluster(viewport.camera.position)
+                .and_then(|c| bsp.bsp_data.clusters[c].sky.as_ref())
+                .and_then(|s| self.skies.get_key_value(s))
+                .filter(|(_, s)| !s.lens_flares.is_empty())
+                .map(|(path, s)| (path, s.lens_flares.as_slice()));
+            self.lens_flares.update_viewport(index, sky, viewport.camera.position, &bsp.occluders, fade);
+        }
+    }
 
+    fn fixup_fog_and_render_distances(&mut self) {
         // First pass: get fog
         for viewport in &mut self.player_viewports {
+            let Some(bsp) = self.scenes[viewport.scene].bsp.and_then(|b| self.bsps.get(b)) else {
+                continue
+            };
+
             let Some(cluster) = bsp.bsp_data.find_cluster(viewport.camera.position) else {
                 continue
             };
 
             let cluster = &bsp.bsp_data.clusters[cluster];
+            viewport.planar_fog = cluster.fog_plane.map(|f| bsp.bsp_data.fog_planes[f]);
+
             let sky = cluster.sky.as_ref().and_then(|s| self.skies.get(s));
+            viewport.sun = sky.and_then(|s| s.sun());
+            viewport.ambient = match sky {
+                Some(sky) => Some(sky.outdoor_ambient),
+                None => bsp.bsp_data.clusters
+                    .iter()
+                    .find_map(|c| c.sky.as_ref().and_then(|s| self.skies.get(s)))
+                    .map(|s| s.indoor_ambient)
+            };
 
             let Some(viewport_fog) = viewport.viewport_fog.as_mut() else {
                 let Some(sky) = sky else {
@@ -440,8 +2484,18 @@ impl Renderer {
 
         // Second pass: render distances and transitions
         for viewport in &mut self.player_viewports {
+            let Some(bsp) = self.scenes[viewport.scene].bsp.and_then(|b| self.bsps.get(b)) else {
+                continue
+            };
+
             viewport.draw_distance[0] = DRAW_DISTANCE_MINIMUM;
-            if let Some(f) = viewport.viewport_fog.as_mut() {
+            if let Some(f) = viewport.fog_override {
+                if f.max_opacity == 1.0 {
+                    viewport.draw_distance[1] = bsp.draw_distance.min(f.distance_to);
+                    continue;
+                }
+            }
+            else if let Some(f) = viewport.viewport_fog.as_mut() {
                 if f.transition_amount > 0.0 {
                     f.current_fog_data.distance_from = f.current_fog_data.distance_from.lerp(f.target_fog_data.distance_from, f.transition_amount);
                     f.current_fog_data.distance_to = f.current_fog_data.distance_to.lerp(f.target_fog_data.distance_to, f.transition_amount);
@@ -467,8 +2521,8 @@ impl Renderer {
 
         let font = self.fonts.get(f).expect("selected debug font no longer loaded?");
 
-        let fps = self.fps_counter_value;
-        let fps_ms = (1000.0 / fps) as f32;
+        let fps = (!self.deterministic).then_some(self.fps_counter_value);
+        let fps_ms = fps.map(|fps| (1000.0 / fps) as f32).unwrap_or(0.0);
 
         let max = 12.0;
         let min = 1.0;
@@ -503,8 +2557,12 @@ impl Renderer {
 
         let mut text = String::with_capacity(1024);
 
-        std::fmt::write(&mut text, format_args!("FPS: {fps:-7.03} ({fps_ms} ms / frame)\n^7BSP: {bsp}\n\n",
-                                                bsp=self.current_bsp.as_ref().map(|b| {
+        match fps {
+            Some(fps) => std::fmt::write(&mut text, format_args!("FPS: {fps:-7.03} ({fps_ms} ms / frame)\n")).unwrap(),
+            None => text.push_str("FPS: (deterministic)\n")
+        }
+        std::fmt::write(&mut text, format_args!("^7BSP: {bsp}\n\n",
+                                                bsp=self.scenes[0].bsp.and_then(|b| self.bsps.get_path(b)).map(|b| {
                                                     let bsp = b.as_str();
                                                     match bsp.rfind(".scenario_structure_bsp") {
                                                         Some(b) => &bsp[..b],
@@ -513,7 +2571,12 @@ impl Renderer {
                                                 }).unwrap_or("No BSP loaded!"))).unwrap();
 
         for (index, viewport) in self.player_viewports.iter().enumerate() {
-            std::fmt::write(&mut text, format_args!("Viewport #{index}\n")).unwrap();
+            if self.scenes.len() > 1 {
+                std::fmt::write(&mut text, format_args!("Viewport #{index} (scene #{})\n", viewport.scene)).unwrap();
+            }
+            else {
+                std::fmt::write(&mut text, format_args!("Viewport #{index}\n")).unwrap();
+            }
             std::fmt::write(&mut text, format_args!("  X:{:13.06}\n", viewport.camera.position[0])).unwrap();
             std::fmt::write(&mut text, format_args!("  Y:{:13.06}\n", viewport.camera.position[1])).unwrap();
             std::fmt::write(&mut text, format_args!("  Z:{:13.06}\n", viewport.camera.position[2])).unwrap();
@@ -534,14 +2597,23 @@ impl Renderer {
     }
 
     fn get_default_2d(&self, default_type: DefaultType) -> &BitmapBitmap {
-        &self.bitmaps[&self.default_bitmaps.default_2d].bitmaps[default_type as usize]
+        &self.bitmaps[self.default_bitmaps.default_2d].bitmaps[default_type as usize]
     }
     fn get_default_cubemap(&self, default_type: DefaultType) -> &BitmapBitmap {
-        &self.bitmaps[&self.default_bitmaps.default_cubemap].bitmaps[default_type as usize]
+        &self.bitmaps[self.default_bitmaps.default_cubemap].bitmaps[default_type as usize]
+    }
+    fn get_bitmap_or_fallback(&self, path: &str, bitmap_index: usize, bitmap_type: BitmapType) -> &BitmapBitmap {
+        match self.bitmaps.get_by_path(path) {
+            Some(n) => &n.bitmaps[bitmap_index],
+            None => {
+                let fallback = self.fallback_assets.get_bitmap(bitmap_type).expect("bitmap no longer loaded?");
+                &self.bitmaps[fallback].bitmaps[0]
+            }
+        }
     }
     fn get_or_default_2d(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap {
         let bitmap = match bitmap.as_ref() {
-            Some(n) => &self.bitmaps[n].bitmaps[bitmap_index],
+            Some(n) => self.get_bitmap_or_fallback(n, bitmap_index, BitmapType::Dim2D),
             None => &self.get_default_2d(default_type)
         };
         debug_assert_eq!(BitmapType::Dim2D, bitmap.bitmap_type);
@@ -549,21 +2621,25 @@ impl Renderer {
     }
     fn get_or_default_3d(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap {
         let bitmap = match bitmap.as_ref() {
-            Some(n) => &self.bitmaps[n].bitmaps[bitmap_index],
-            None => &self.bitmaps[&self.default_bitmaps.default_3d].bitmaps[default_type as usize]
+            Some(n) => self.get_bitmap_or_fallback(n, bitmap_index, BitmapType::Dim3D { depth: 1 }),
+            None => &self.bitmaps[self.default_bitmaps.default_3d].bitmaps[default_type as usize]
         };
         debug_assert!(matches!(bitmap.bitmap_type, BitmapType::Dim3D { .. }));
         bitmap
     }
     fn get_or_default_cubemap(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap {
         let bitmap = match bitmap.as_ref() {
-            Some(n) => &self.bitmaps[n].bitmaps[bitmap_index],
-            None => &self.bitmaps[&self.default_bitmaps.default_cubemap].bitmaps[default_type as usize]
+            Some(n) => self.get_bitmap_or_fallback(n, bitmap_index, BitmapType::Cubemap),
+            None => &self.bitmaps[self.default_bitmaps.default_cubemap].bitmaps[default_type as usize]
         };
         debug_assert_eq!(BitmapType::Cubemap, bitmap.bitmap_type);
         bitmap
     }
     fn update_frame_rate_counter(&mut self) {
+        if self.deterministic {
+            return
+        }
+
         self.fps_counter_count = self.fps_counter_count.saturating_add(1);
 
         let now = Instant::now();
@@ -576,6 +2652,121 @@ impl Renderer {
     }
 }
 
+fn validate_bitmaps(bitmaps: &[(String, AddBitmapParameter)], worker_threads: usize) -> Vec<MResult<()>> {
+    if worker_threads <= 1 || bitmaps.len() <= 1 {
+        return bitmaps.iter().map(|(_, bitmap)| bitmap.validate()).collect()
+    }
+
+    let chunk_size = bitmaps.len().div_ceil(worker_threads);
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = bitmaps
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(|(_, bitmap)| bitmap.validate()).collect::<Vec<_>>()))
+            .collect();
+        threads.into_iter().flat_map(|t| t.join().unwrap()).collect()
+    })
+}
+
+fn validate_lightmap_exposure(exposure: f32) -> MResult<()> {
+    if exposure < 0.0 || !exposure.is_finite() {
+        return Err(Error::from_data_error_string(format!("Invalid lightmap exposure {exposure}")))
+    }
+    Ok(())
+}
+
+fn retain_asset<T>(assets: &mut Vec<(String, T)>, path: &str, parameter: T) {
+    match assets.iter_mut().find(|(p, _)| p == path) {
+        Some((_, p)) => *p = parameter,
+        None => assets.push((path.to_owned(), parameter))
+    }
+}
+
+/// Passed to the overlay hook (see [`Renderer::set_overlay_hook`]) once per frame.
+pub struct OverlayContext<'a> {
+    /// Command buffer of the current frame.
+    ///
+    /// Nothing is being rendered when the hook is called, so the hook must begin and end rendering to
+    /// `color_attachment` itself (using `AttachmentLoadOp::Load` to keep the scene).
+    pub command_builder: &'a mut vulkano::command_buffer::AutoCommandBufferBuilder<vulkano::command_buffer::PrimaryAutoCommandBuffer>,
+
+    /// Single-sampled color image containing the drawn scene.
+    pub color_attachment: Arc<vulkano::image::view::ImageView>,
+
+    /// Format of `color_attachment`.
+    pub color_format: vulkano::format::Format,
+
+    /// Dimensions of `color_attachment` in pixels (i.e. the render resolution).
+    pub resolution: Resolution
+}
+
+/// Result of drawing a frame with [`Renderer::draw_frame`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FrameStatus {
+    /// The frame was drawn and presented (if not headless).
+    Drawn,
+
+    /// The swapchain no longer matches the surface (e.g. the window was resized), so the frame may not have been
+    /// presented, and the size of the surface is unknown, so it could not be rebuilt automatically.
+    ///
+    /// Call [`Renderer::set_resolution`] with the new size before drawing again.
+    ResizeRequired,
+
+    /// The window has no area (e.g. it is minimized), so nothing was drawn.
+    ///
+    /// Nothing needs to be done; drawing resumes when the window is restored.
+    Minimized
+}
+
+/// Statistics of a frame, summed across all viewports and windows.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct FrameStatistics {
+    /// Number of draw calls made for BSP geometry, including reflections.
+    pub draw_calls: usize,
+
+    /// Number of BSP triangles drawn, including reflections.
+    pub triangles: usize,
+
+    /// Number of times a pipeline was bound for any pass.
+    pub pipeline_binds: usize,
+
+    /// Number of descriptor sets allocated while drawing.
+    pub descriptor_sets: usize,
+
+    /// Number of BSP geometries drawn in each viewport, not counting reflections.
+    pub geometries_drawn: usize,
+
+    /// Number of BSP geometries skipped in each viewport (e.g. water when reflections are disabled).
+    pub geometries_culled: usize
+}
+
+impl FrameStatistics {
+    fn add_draw(&mut self, vertices: &VertexOffsets) {
+        self.draw_calls += 1;
+        self.triangles += vertices.index_count as usize / 3;
+    }
+}
+
+impl std::ops::AddAssign for FrameStatistics {
+    fn add_assign(&mut self, rhs: Self) {
+        self.draw_calls += rhs.draw_calls;
+        self.triangles += rhs.triangles;
+        self.pipeline_binds += rhs.pipeline_binds;
+        self.descriptor_sets += rhs.descriptor_sets;
+        self.geometries_drawn += rhs.geometries_drawn;
+        self.geometries_culled += rhs.geometries_culled;
+    }
+}
+
+/// Pixel data read back from a drawn frame.
+#[derive(Clone, Debug)]
+pub struct CapturedFrame {
+    /// Width and height of the frame in pixels.
+    pub resolution: Resolution,
+
+    /// Pixel data, stored as 8-bit RGBA with rows going from top to bottom.
+    pub data: Vec<u8>
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 #[repr(usize)]
 enum DefaultType {
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::Index;
+use std::sync::Arc;
+
+/// Identifies a slot in an [`AssetStore`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub(crate) struct SlotKey {
+    index: u32,
+    generation: u32
+}
+
+impl SlotKey {
+    /// Key that never resolves to anything.
+    pub const NULL: SlotKey = SlotKey { index: u32::MAX, generation: u32::MAX };
+}
+
+/// Implemented by the typed handles returned by the renderer.
+pub(crate) trait AssetHandle: Copy {
+    fn from_key(key: SlotKey) -> Self;
+    fn key(self) -> SlotKey;
+}
+
+macro_rules! asset_handle {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+        pub struct $name(SlotKey);
+
+        impl AssetHandle for $name {
+            fn from_key(key: SlotKey) -> Self {
+                Self(key)
+            }
+            fn key(self) -> SlotKey {
+                self.0
+            }
+        }
+    };
+}
+
+asset_handle!(
+    /// Refers to a bitmap added with [`Renderer::add_bitmap`](crate::renderer::Renderer::add_bitmap).
+    ///
+    /// Handles are invalidated when the renderer is reset.
+    BitmapHandle
+);
+
+asset_handle!(
+    /// Refers to a shader added with [`Renderer::add_shader`](crate::renderer::Renderer::add_shader).
+    ///
+    /// Handles are invalidated when the renderer is reset.
+    ShaderHandle
+);
+
+asset_handle!(
+    /// Refers to a BSP added with [`Renderer::add_bsp`](crate::renderer::Renderer::add_bsp).
+    ///
+    /// Handles are invalidated when the renderer is reset.
+    BSPHandle
+);
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<(Arc<String>, T)>
+}
+
+/// Slot map of assets, indexed by path.
+///
+/// Looking up an asset by its handle is O(1) with no hashing. Handles stay valid until the store is cleared, after
+/// which they no longer resolve to anything, even once their slots are reused.
+pub(crate) struct AssetStore<T, H: AssetHandle> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    paths: HashMap<String, SlotKey>,
+    _handle: PhantomData<H>
+}
+
+impl<T, H: AssetHandle> AssetStore<T, H> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            paths: HashMap::new(),
+            _handle: PhantomData
+        }
+    }
+
+    /// Insert an asset.
+    ///
+    /// If an asset with the same path exists, it is replaced and keeps its handle.
+    pub fn insert(&mut self, path: Arc<String>, value: T) -> H {
+        if let Some(key) = self.paths.get(path.as_str()) {
+            self.slots[key.index as usize].value = Some((path, value));
+            return H::from_key(*key)
+        }
+
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot { generation: 0, value: None });
+                (self.slots.len() - 1) as u32
+            }
+        };
+
+        let slot = &mut self.slots[index as usize];
+        let key = SlotKey { index, generation: slot.generation };
+        self.paths.insert(path.to_string(), key);
+        slot.value = Some((path, value));
+        H::from_key(key)
+    }
+
+    pub fn get(&self, handle: H) -> Option<&T> {
+        self.get_with_path(handle).map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, handle: H) -> Option<&mut T> {
+        let key = handle.key();
+        let slot = self.slots.get_mut(key.index as usize)?;
+        if slot.generation != key.generation {
+            return None
+        }
+        slot.value.as_mut().map(|(_, value)| value)
+    }
+
+    pub fn get_path(&self, handle: H) -> Option<&Arc<String>> {
+        self.get_with_path(handle).map(|(path, _)| path)
+    }
+
+    pub fn get_with_path(&self, handle: H) -> Option<(&Arc<String>, &T)> {
+        let key = handle.key();
+        let slot = self.slots.get(key.index as usize)?;
+        if slot.generation != key.generation {
+            return None
+        }
+        slot.value.as_ref().map(|(path, value)| (path, value))
+    }
+
+    pub fn get_handle(&self, path: &str) -> Option<H> {
+        self.paths.get(path).map(|key| H::from_key(*key))
+    }
+
+    pub fn get_by_path(&self, path: &str) -> Option<&T> {
+        self.get(self.get_handle(path)?)
+    }
+
+    pub fn contains_path(&self, path: &str) -> bool {
+        self.paths.contains_key(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (H, &Arc<String>, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let (path, value) = slot.value.as_ref()?;
+            Some((H::from_key(SlotKey { index: index as u32, generation: slot.generation }), path, value))
+        })
+    }
+
+    /// Remove all assets, invalidating all handles.
+    pub fn clear(&mut self) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.value.take().is_some() {
+                self.free.push(index as u32);
+            }
+            slot.generation = slot.generation.wrapping_add(1);
+        }
+        self.paths.clear();
+    }
+}
+
+impl<T, H: AssetHandle> Index<H> for AssetStore<T, H> {
+    type Output = T;
+
+    fn index(&self, handle: H) -> &Self::Output {
+        self.get(handle).expect("stale or invalid asset handle")
+    }
+}
@@ -0,0 +1,173 @@
+use alloc::borrow::ToOwned;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::error::{BitmapError, BSPError, DataError, Error, MResult, ShaderError};
+use crate::renderer::{AddAssetsParameter, AddBitmapParameter, AddBSPParameter, AddFontParameter, AddShaderParameter, AddSkyParameter, AssetType, BitmapType, ShaderType};
+#[cfg(feature = "vulkan")]
+use crate::renderer::Renderer;
+
+/// Assets that parameters are validated against.
+pub(crate) trait AssetLookup {
+    /// Get the type of each bitmap of the bitmap at `path`, or `None` if it isn't loaded.
+    fn bitmap_types(&self, path: &str) -> Option<Vec<BitmapType>>;
+
+    /// Get the type of the shader at `path`, or `None` if it isn't loaded.
+    fn shader_type(&self, path: &str) -> Option<ShaderType>;
+
+    fn has_geometry(&self, path: &str) -> bool;
+    fn has_sky(&self, path: &str) -> bool;
+    fn has_fallback_bitmap(&self, bitmap_type: BitmapType) -> bool;
+    fn has_fallback_shader(&self) -> bool;
+}
+
+#[cfg(feature = "vulkan")]
+impl AssetLookup for Renderer {
+    fn bitmap_types(&self, path: &str) -> Option<Vec<BitmapType>> {
+        self.bitmaps.get_by_path(path).map(|b| b.bitmaps.iter().map(|b| b.bitmap_type).collect())
+    }
+    fn shader_type(&self, path: &str) -> Option<ShaderType> {
+        self.shaders.get_by_path(path).map(|s| s.shader_type)
+    }
+    fn has_geometry(&self, path: &str) -> bool {
+        self.geometries.contains_key(&path.to_owned())
+    }
+    fn has_sky(&self, path: &str) -> bool {
+        self.skies.contains_key(&path.to_owned())
+    }
+    fn has_fallback_bitmap(&self, bitmap_type: BitmapType) -> bool {
+        self.fallback_assets.get_bitmap(bitmap_type).is_some()
+    }
+    fn has_fallback_shader(&self) -> bool {
+        self.fallback_assets.shader.is_some()
+    }
+}
+
+/// Checks whether assets would be added successfully without creating a renderer.
+///
+/// This runs the same validation as the renderer's `add_` methods, but nothing is uploaded, so no device is needed.
+/// Each asset that passes is remembered, so assets that depend on it can be checked afterward, just as if they were
+/// being added to a renderer.
+///
+/// Fallback assets are not supported, so any reference to a missing asset fails.
+#[derive(Default)]
+pub struct AssetChecker {
+    bitmaps: BTreeMap<String, Vec<BitmapType>>,
+    shaders: BTreeMap<String, ShaderType>,
+    fonts: BTreeSet<String>,
+    skies: BTreeSet<String>,
+    bsps: BTreeSet<String>
+}
+
+/// An asset that failed to be checked by [`AssetChecker::check_assets`].
+#[derive(Clone, Debug)]
+pub struct AssetCheckFailure {
+    pub asset_type: AssetType,
+    pub path: String,
+    pub error: Error
+}
+
+impl AssetChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check a bitmap as if it were added with [`Renderer::add_bitmap`].
+    pub fn check_bitmap(&mut self, path: &str, bitmap: &AddBitmapParameter) -> MResult<()> {
+        if self.bitmaps.contains_key(path) {
+            return Err(BitmapError::AlreadyLoaded { path: path.to_owned() }.into())
+        }
+        bitmap.validate()?;
+        self.bitmaps.insert(path.to_owned(), bitmap.bitmaps.iter().map(|b| b.bitmap_type).collect());
+        Ok(())
+    }
+
+    /// Check a font as if it were added with [`Renderer::add_font`].
+    pub fn check_font(&mut self, path: &str, font: &AddFontParameter) -> MResult<()> {
+        if self.fonts.contains(path) {
+            return Err(DataError::AlreadyLoaded { path: path.to_owned() }.into())
+        }
+        font.validate()?;
+        self.fonts.insert(path.to_owned());
+        Ok(())
+    }
+
+    /// Check a shader as if it were added with [`Renderer::add_shader`].
+    pub fn check_shader(&mut self, path: &str, shader: &AddShaderParameter) -> MResult<()> {
+        if self.shaders.contains_key(path) {
+            return Err(ShaderError::AlreadyLoaded { path: path.to_owned() }.into())
+        }
+        shader.validate(self)?;
+        self.shaders.insert(path.to_owned(), shader.shader_type());
+        Ok(())
+    }
+
+    /// Check a sky as if it were added with [`Renderer::add_sky`].
+    pub fn check_sky(&mut self, path: &str, sky: &AddSkyParameter) -> MResult<()> {
+        sky.validate(self)?;
+        self.skies.insert(path.to_owned());
+        Ok(())
+    }
+
+    /// Check a BSP as if it were added with [`Renderer::add_bsp`].
+    pub fn check_bsp(&mut self, path: &str, bsp: &AddBSPParameter) -> MResult<()> {
+        if self.bsps.contains(path) {
+            return Err(BSPError::AlreadyLoaded { path: path.to_owned() }.into())
+        }
+        bsp.validate(self)?;
+        self.bsps.insert(path.to_owned());
+        Ok(())
+    }
+
+    /// Check many assets as if they were added with [`Renderer::add_assets`].
+    ///
+    /// Unlike [`Renderer::add_assets`], checking continues after an asset fails, and every failure is returned.
+    pub fn check_assets(&mut self, assets: &AddAssetsParameter) -> Vec<AssetCheckFailure> {
+        let mut failures = Vec::new();
+        let mut report = |asset_type: AssetType, path: &str, result: MResult<()>| {
+            if let Err(error) = result {
+                failures.push(AssetCheckFailure { asset_type, path: path.to_owned(), error });
+            }
+        };
+
+        for (path, bitmap) in &assets.bitmaps {
+            report(AssetType::Bitmap, path, self.check_bitmap(path, bitmap));
+        }
+        for (path, font) in &assets.fonts {
+            report(AssetType::Font, path, self.check_font(path, font));
+        }
+        for (path, shader) in &assets.shaders {
+            report(AssetType::Shader, path, self.check_shader(path, shader));
+        }
+        for (path, sky) in &assets.skies {
+            report(AssetType::Sky, path, self.check_sky(path, sky));
+        }
+        for (path, bsp) in &assets.bsps {
+            report(AssetType::BSP, path, self.check_bsp(path, bsp));
+        }
+
+        failures
+    }
+}
+
+impl AssetLookup for AssetChecker {
+    fn bitmap_types(&self, path: &str) -> Option<Vec<BitmapType>> {
+        self.bitmaps.get(path).cloned()
+    }
+    fn shader_type(&self, path: &str) -> Option<ShaderType> {
+        self.shaders.get(path).copied()
+    }
+    fn has_geometry(&self, _path: &str) -> bool {
+        // Geometries can't be added yet.
+        false
+    }
+    fn has_sky(&self, path: &str) -> bool {
+        self.skies.contains(path)
+    }
+    fn has_fallback_bitmap(&self, _bitmap_type: BitmapType) -> bool {
+        false
+    }
+    fn has_fallback_shader(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::mpsc::{Receiver, Sender};
+use crate::error::{Error, MResult};
+use crate::renderer::{AddAssetsParameter, AddBitmapParameter, AddBSPParameter, AddFontParameter, AddShaderParameter, AddSkyParameter, Camera, Renderer};
+
+/// A command that is run on the renderer's thread by [`Renderer::process_commands`].
+pub type RendererCommand = Box<dyn FnOnce(&mut Renderer) -> MResult<()> + Send>;
+
+pub(crate) fn make_command_queue() -> (Sender<RendererCommand>, Receiver<RendererCommand>) {
+    std::sync::mpsc::channel()
+}
+
+/// Queues commands from any thread to be run on the thread that owns the renderer.
+///
+/// Obtained with [`Renderer::get_command_sender`]. Commands are run in the order they were sent when
+/// [`Renderer::process_commands`] is called, so the owning thread can keep drawing frames while assets are streamed in
+/// from another thread.
+#[derive(Clone)]
+pub struct RendererCommandSender {
+    pub(crate) sender: Sender<RendererCommand>
+}
+
+impl RendererCommandSender {
+    /// Queue an arbitrary command.
+    ///
+    /// Returns `Err` if the renderer no longer exists.
+    pub fn send(&self, command: impl FnOnce(&mut Renderer) -> MResult<()> + Send + 'static) -> MResult<()> {
+        self.sender
+            .send(Box::new(command))
+            .map_err(|_| Error::from_data_error_string("Can't send a command: the renderer no longer exists".to_owned()))
+    }
+
+    /// Queue [`Renderer::add_bitmap`].
+    pub fn add_bitmap(&self, path: &str, bitmap: AddBitmapParameter) -> MResult<()> {
+        let path = path.to_owned();
+        self.send(move |r| r.add_bitmap(&path, bitmap).map(|_| ()))
+    }
+
+    /// Queue [`Renderer::add_font`].
+    pub fn add_font(&self, path: &str, font: AddFontParameter) -> MResult<()> {
+        let path = path.to_owned();
+        self.send(move |r| r.add_font(&path, font))
+    }
+
+    /// Queue [`Renderer::add_shader`].
+    pub fn add_shader(&self, path: &str, shader: AddShaderParameter) -> MResult<()> {
+        let path = path.to_owned();
+        self.send(move |r| r.add_shader(&path, shader).map(|_| ()))
+    }
+
+    /// Queue [`Renderer::add_sky`].
+    pub fn add_sky(&self, path: &str, sky: AddSkyParameter) -> MResult<()> {
+        let path = path.to_owned();
+        self.send(move |r| r.add_sky(&path, sky))
+    }
+
+    /// Queue [`Renderer::add_bsp`].
+    pub fn add_bsp(&self, path: &str, bsp: AddBSPParameter) -> MResult<()> {
+        let path = path.to_owned();
+        self.send(move |r| r.add_bsp(&path, bsp).map(|_| ()))
+    }
+
+    /// Queue [`Renderer::add_assets`].
+    pub fn add_assets(&self, assets: AddAssetsParameter) -> MResult<()> {
+        self.send(move |r| r.add_assets(assets, |_| ()))
+    }
+
+    /// Queue [`Renderer::set_scene_bsp`].
+    pub fn set_scene_bsp(&self, scene: usize, path: Option<&str>) -> MResult<()> {
+        let path = path.map(|p| p.to_owned());
+        self.send(move |r| r.set_scene_bsp(scene, path.as_deref()))
+    }
+
+    /// Queue [`Renderer::set_camera_for_viewport`].
+    ///
+    /// Unlike calling it directly, an invalid viewport or camera results in an error when the command is run rather
+    /// than a panic.
+    pub fn set_camera_for_viewport(&self, viewport: usize, camera: Camera) -> MResult<()> {
+        self.send(move |r| {
+            if viewport >= r.get_viewport_count() {
+                return Err(Error::from_data_error_string(format!("Can't set the camera of viewport #{viewport}: that viewport does not exist")))
+            }
+            if !(camera.fov > 0.0 && camera.fov < core::f32::consts::PI) {
+                return Err(Error::from_data_error_string(format!("Can't set the camera of viewport #{viewport}: FoV {} is invalid", camera.fov)))
+            }
+            r.set_camera_for_viewport(viewport, camera);
+            Ok(())
+        })
+    }
+}
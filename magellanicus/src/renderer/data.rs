@@ -2,12 +2,20 @@ mod bitmap;
 mod geometry;
 mod shader;
 mod bsp;
+mod occluders;
+mod water;
+mod mirror;
 mod sky;
 mod font;
+mod debug_text_font;
 
 pub use bitmap::*;
 pub use geometry::*;
 pub use shader::*;
 pub use bsp::*;
+pub use occluders::*;
+pub use water::*;
+pub use mirror::*;
 pub use sky::*;
 pub use font::*;
+pub use debug_text_font::*;
@@ -1,20 +1,40 @@
 use core::iter;
-use std::sync::Arc;
 use crate::error::MResult;
 use crate::types::to_rgbaf32;
-use crate::renderer::{AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, BitmapFormat, Renderer, Resolution};
+use crate::renderer::data::make_debug_text_font_bitmap;
+use crate::renderer::{AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, BitmapFormat, BitmapHandle, BitmapType, Renderer, Resolution};
 use crate::renderer::vulkan::VulkanBitmapData;
+use crate::renderer::asset_store::{AssetHandle, SlotKey};
 
-#[derive(Default)]
 pub struct DefaultBitmaps {
-    pub default_2d: Arc<String>,
-    pub default_3d: Arc<String>,
-    pub default_cubemap: Arc<String>,
+    pub default_2d: BitmapHandle,
+    pub default_3d: BitmapHandle,
+    pub default_cubemap: BitmapHandle,
+    pub debug_text_font: BitmapHandle,
+}
+
+impl Default for DefaultBitmaps {
+    fn default() -> Self {
+        let null = BitmapHandle::from_key(SlotKey::NULL);
+        Self {
+            default_2d: null,
+            default_3d: null,
+            default_cubemap: null,
+            debug_text_font: null
+        }
+    }
+}
+
+impl DefaultBitmaps {
+    /// Return `true` if `handle` refers to one of the default bitmaps.
+    pub fn contains(&self, handle: BitmapHandle) -> bool {
+        handle == self.default_2d || handle == self.default_3d || handle == self.default_cubemap || handle == self.debug_text_font
+    }
 }
 
 pub struct Bitmap {
     pub bitmaps: Vec<BitmapBitmap>,
-    pub sequences: Vec<BitmapSequence>
+    pub sequences: Vec<AddBitmapSequenceParameter>
 }
 
 impl Bitmap {
@@ -38,36 +58,14 @@ impl Bitmap {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum BitmapType {
-    Dim2D,
-    Dim3D { depth: u32 },
-    Cubemap
-}
-
 pub struct BitmapBitmap {
     pub vulkan: VulkanBitmapData,
     pub resolution: Resolution,
     pub bitmap_type: BitmapType
 }
 
-#[derive(Clone)]
-pub enum BitmapSequence {
-    Bitmap { first: usize, count: usize },
-    Sprites { sprites: Vec<BitmapSprite> }
-}
-
-#[derive(Clone)]
-pub struct BitmapSprite {
-    pub bitmap: usize,
-    pub top: f32,
-    pub left: f32,
-    pub bottom: f32,
-    pub right: f32
-}
-
 pub fn populate_default_bitmaps(renderer: &mut Renderer) -> MResult<()> {
-    fn make_add_bitmap_parameter(renderer: &mut Renderer, path: &str, bitmap_type: BitmapType) -> MResult<Arc<String>> {
+    fn make_add_bitmap_parameter(renderer: &mut Renderer, path: &str, bitmap_type: BitmapType) -> MResult<BitmapHandle> {
         // note: black is fully transparent in source data, but all release builds are fully opaque
         // due to a bug with tool.exe
         let null: [u8; 16] = to_rgbaf32([0.0, 0.0, 0.0, 0.0]);
@@ -144,18 +142,19 @@ pub fn populate_default_bitmaps(renderer: &mut Renderer) -> MResult<()> {
             ],
         };
 
-        renderer.add_bitmap(path, add_data)?;
-        Ok(renderer.bitmaps.get_key_value(&path.to_string()).unwrap().0.clone())
+        renderer.add_bitmap(path, add_data)
     }
 
     let default_2d = make_add_bitmap_parameter(renderer, "~default_2d", BitmapType::Dim2D)?;
     let default_3d = make_add_bitmap_parameter(renderer, "~default_3d", BitmapType::Dim3D { depth: 1 })?;
     let default_cubemap = make_add_bitmap_parameter(renderer, "~default_cubemap", BitmapType::Cubemap)?;
+    let debug_text_font = renderer.add_bitmap("~debug_text_font", make_debug_text_font_bitmap())?;
 
     renderer.default_bitmaps = DefaultBitmaps {
         default_2d,
         default_3d,
-        default_cubemap
+        default_cubemap,
+        debug_text_font
     };
 
     Ok(())
@@ -1,7 +1,8 @@
-use std::sync::Arc;
+use glam::Vec3;
 use crate::error::MResult;
+use crate::renderer::data::{find_mirror, find_water_surfaces, Mirror, Occluders, WaterSurface};
 use crate::renderer::vulkan::VulkanBSPData;
-use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, BSPData, Renderer};
+use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, AddShaderData, BSPData, Renderer, ShaderHandle, ShaderType};
 use crate::vertex::VertexOffsets;
 
 pub const MIN_DRAW_DISTANCE_LIMIT: f32 = 100.0;
@@ -15,8 +16,20 @@ pub struct BSP {
     pub cluster_surfaces: Vec<Vec<usize>>,
     pub geometry_indices_sorted_by_material: Vec<usize>,
 
+    /// Bitmap the lightmaps are in, used for refreshing lightmaps if it is replaced.
+    pub lightmap_bitmap: Option<String>,
+
     /// Calculated based on the size of the BSP, clamped between [`MIN_DRAW_DISTANCE_LIMIT`] and [`MAX_DRAW_DISTANCE_LIMIT`].
-    pub draw_distance: f32
+    pub draw_distance: f32,
+
+    /// Triangles of environment geometry, which lens flares are hidden behind.
+    pub occluders: Occluders,
+
+    /// Flat water, largest first, which can reflect the scene.
+    pub water_surfaces: Vec<WaterSurface>,
+
+    /// Flat geometries with mirror shaders.
+    pub mirrors: Vec<Mirror>
 }
 
 impl BSP {
@@ -61,9 +74,12 @@ impl BSP {
 
         let mut vertex_offset = 0i32;
         let mut index_offset = 0u32;
+        let mut occluder_triangles = Vec::new();
+        let mut water_triangles = Vec::new();
+        let mut mirrors = Vec::new();
 
         for data in add_bsp_iterator {
-            for p in &data.material_data.shader_vertices {
+            for p in data.material_data.shader_vertices.iter() {
                 min_x = min_x.min(p.position[0]);
                 min_y = min_y.min(p.position[1]);
                 min_z = min_z.min(p.position[2]);
@@ -72,13 +88,43 @@ impl BSP {
                 max_z = max_z.max(p.position[2]);
             }
 
+            let shader = renderer.shaders.get_handle(&data.material_data.shader).or(renderer.fallback_assets.shader).unwrap();
+            let shader_type = renderer.shaders.get(shader).map(|s| s.shader_type);
+            let is_mirror = renderer.shaders.get(shader).is_some_and(|s| {
+                matches!(&s.parameter.data, AddShaderData::ShaderEnvironment(data) if data.mirror)
+            });
+            if shader_type == Some(ShaderType::Environment) || shader_type == Some(ShaderType::TransparentWater) {
+                let positions: Vec<Vec3> = data.material_data.shader_vertices.iter().map(|v| Vec3::from(v.position)).collect();
+                let triangles = data.material_data.surfaces
+                    .iter()
+                    .map(|s| s.indices.map(|i| positions.get(i as usize).copied()))
+                    .filter_map(|[a, b, c]| Some([a?, b?, c?]));
+                if shader_type == Some(ShaderType::Environment) {
+                    if is_mirror {
+                        let triangles: Vec<_> = triangles.clone().collect();
+                        mirrors.extend(find_mirror(geometries.len(), &triangles));
+                    }
+                    occluder_triangles.extend(triangles);
+                }
+                else {
+                    water_triangles.push((geometries.len(), triangles.collect::<Vec<_>>()));
+                }
+            }
+
+            let centroid = Vec3::from(data.material_data.centroid);
+            let radius = data.material_data.shader_vertices
+                .iter()
+                .map(|v| centroid.distance(Vec3::from(v.position)))
+                .fold(0.0, f32::max);
+
             let index_count = (data.material_data.surfaces.len() * 3) as u32;
             geometries.push(BSPGeometry {
-                shader: renderer.shaders.get_key_value(&data.material_data.shader).unwrap().0.clone(),
+                shader,
                 lightmap_index: data.material_data.lightmap_vertices.as_ref().and(data.lightmap_bitmap_index),
                 material_reflexive_index: data.material_reflexive_index,
                 lightmap_reflexive_index: data.lightmap_reflexive_index,
                 centroid: data.material_data.centroid,
+                radius,
                 offset: VertexOffsets {
                     index_offset,
                     vertex_offset,
@@ -110,16 +156,37 @@ impl BSP {
 
         let vulkan = VulkanBSPData::new(renderer, &add_bsp_parameter, &geometries)?;
 
-        Ok(Self { vulkan, geometries, bsp_data: add_bsp_parameter.bsp_data, cluster_surfaces, draw_distance, geometry_indices_sorted_by_material })
+        Ok(Self {
+            vulkan,
+            geometries,
+            bsp_data: add_bsp_parameter.bsp_data,
+            cluster_surfaces,
+            draw_distance,
+            geometry_indices_sorted_by_material,
+            lightmap_bitmap: add_bsp_parameter.lightmap_bitmap,
+            occluders: Occluders::new(occluder_triangles),
+            water_surfaces: find_water_surfaces(water_triangles),
+            mirrors
+        })
+    }
+}
+
+impl BSP {
+    /// Return `true` if any geometry uses `shader`.
+    pub fn uses_shader(&self, shader: ShaderHandle) -> bool {
+        self.geometries.iter().any(|g| g.shader == shader)
     }
 }
 
 pub struct BSPGeometry {
     pub offset: VertexOffsets,
-    pub shader: Arc<String>,
+    pub shader: ShaderHandle,
     pub lightmap_index: Option<usize>,
     pub centroid: [f32; 3],
 
+    /// Distance from `centroid` to the farthest vertex.
+    pub radius: f32,
+
     pub material_reflexive_index: usize,
     pub lightmap_reflexive_index: usize
 }
@@ -8,19 +8,30 @@ use glam::Vec4;
 use crate::error::MResult;
 use crate::types::FloatColor;
 use crate::renderer::{AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, AddFontParameter, BitmapFormat, BitmapType, Renderer, Resolution};
+use crate::renderer::data::Bitmap;
 use crate::renderer::data::font::colors::{ControlCode, ColorCodes};
 
+/// Width of the glyph atlas, unless a character is wider than this.
+const ATLAS_WIDTH: usize = 512;
+
+/// Transparent pixels between characters in the glyph atlas so that they don't bleed into each other when filtered.
+const ATLAS_PADDING: usize = 1;
+
 pub struct Font {
     pub line_height: u32,
+    pub ascending_height: u32,
     pub characters: HashMap<char, FontCharacter>,
-    pub colors: ColorCodes
+    pub colors: ColorCodes,
+
+    /// All characters packed into one bitmap, with white color and the character's coverage as alpha.
+    pub atlas: Bitmap
 }
 
 impl Font {
-    pub fn load_from_parameters(_: &Renderer, parameter: AddFontParameter) -> MResult<Font> {
+    pub fn load_from_parameters(renderer: &mut Renderer, parameter: AddFontParameter) -> MResult<Font> {
         // TODO: Add bold/italic/underline variants
 
-        let characters = parameter
+        let mut characters: HashMap<char, FontCharacter> = parameter
             .characters
             .into_iter()
             .map(|c| {
@@ -29,26 +40,92 @@ impl Font {
                     data: c.data,
                     width: c.width,
                     height: c.height,
-                    advance_x: c.advance_x
+                    advance_x: c.advance_x,
+                    origin_x: c.origin_x,
+                    origin_y: c.origin_y,
+                    atlas_position: [0, 0]
                 };
                 (c.character, character)
             })
             .collect();
 
+        let atlas = Bitmap::load_from_parameters(renderer, make_atlas(&mut characters))?;
+
         Ok(Font {
             line_height: parameter.line_height,
+            ascending_height: parameter.ascending_height,
             characters,
-            colors: ColorCodes::default()
+            colors: ColorCodes::default(),
+            atlas
         })
     }
 }
 
+/// Pack all characters into rows of a single bitmap, setting each character's atlas position.
+fn make_atlas(characters: &mut HashMap<char, FontCharacter>) -> AddBitmapParameter {
+    // Place taller characters first so rows are packed more tightly.
+    let mut order: Vec<char> = characters.keys().copied().collect();
+    order.sort_by_key(|c| (std::cmp::Reverse(characters[c].height), *c));
+
+    let width = characters
+        .values()
+        .map(|c| c.width + ATLAS_PADDING * 2)
+        .max()
+        .unwrap_or_default()
+        .max(ATLAS_WIDTH);
+
+    let mut x = 0;
+    let mut y = 0;
+    let mut row_height = 0;
+    for c in &order {
+        let character = characters.get_mut(c).unwrap();
+        if x + character.width + ATLAS_PADDING * 2 > width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        character.atlas_position = [(x + ATLAS_PADDING) as u32, (y + ATLAS_PADDING) as u32];
+        x += character.width + ATLAS_PADDING * 2;
+        row_height = row_height.max(character.height + ATLAS_PADDING * 2);
+    }
+    let height = (y + row_height).max(1);
+
+    let mut data = vec![0u8; width * height * 4];
+    for character in characters.values() {
+        let [left, top] = character.atlas_position.map(|p| p as usize);
+        for (row, alphas) in character.data.chunks_exact(character.width.max(1)).enumerate() {
+            for (column, alpha) in alphas.iter().enumerate() {
+                let offset = ((top + row) * width + left + column) * 4;
+                data[offset..offset + 4].copy_from_slice(&[255, 255, 255, *alpha]);
+            }
+        }
+    }
+
+    let bitmap = AddBitmapBitmapParameter {
+        format: BitmapFormat::A8B8G8R8,
+        bitmap_type: BitmapType::Dim2D,
+        resolution: Resolution { width: width as u32, height: height as u32 },
+        mipmap_count: 0,
+        data
+    };
+
+    AddBitmapParameter {
+        bitmaps: vec![bitmap],
+        sequences: vec![AddBitmapSequenceParameter::Bitmap { first: 0, count: 1 }]
+    }
+}
+
 pub struct FontCharacter {
     pub character: char,
     pub data: Vec<u8>,
     pub width: usize,
     pub height: usize,
-    pub advance_x: i32
+    pub advance_x: i32,
+    pub origin_x: i32,
+    pub origin_y: i32,
+
+    /// Position of the top-left pixel of the character in the font's atlas.
+    pub atlas_position: [u32; 2]
 }
 
 #[derive(Default, Copy, Clone, PartialEq)]
@@ -82,6 +159,84 @@ impl Font {
         }
     }
 
+    /// Lay out a string to be drawn relative to an anchor point rather than within a bitmap.
+    ///
+    /// Left-aligned text starts at the anchor, right-aligned text ends at it, and centered text is centered on it.
+    /// Table alignment is treated as left alignment.
+    ///
+    /// If `max_width` is set, lines longer than it are broken after the last space that fits. A single word that is
+    /// longer than `max_width` is not broken.
+    pub fn layout_string(&self, string: &str, color: FloatColor, alignment: TextAlignment, max_width: Option<i32>, characters: &mut Vec<DrawableCharacter>) {
+        characters.clear();
+        characters.reserve(string.len());
+        characters.extend(self.iterate_characters(string, color, TextState {
+            alignment,
+            ..Default::default()
+        }));
+
+        let advance = |c: &DrawableCharacter| self.characters[&c.character].advance_x;
+
+        if let Some(max_width) = max_width {
+            let mut original_y = None;
+            let mut added_y = 0;
+            let mut line_start = 0;
+            let mut line_width = 0;
+            let mut last_space = None;
+
+            for i in 0..characters.len() {
+                if original_y != Some(characters[i].state.y) {
+                    original_y = Some(characters[i].state.y);
+                    line_start = i;
+                    line_width = 0;
+                    last_space = None;
+                }
+                characters[i].state.y += added_y;
+
+                let width = advance(&characters[i]);
+                if characters[i].character == ' ' {
+                    last_space = Some(i);
+                }
+                else if line_width + width > max_width && i > line_start {
+                    if let Some(space) = last_space {
+                        // Move everything after the space to a new line.
+                        added_y += self.line_height;
+                        for c in &mut characters[space + 1..=i] {
+                            c.state.y += self.line_height;
+                        }
+                        characters[space + 1].alignment_changed = true;
+                        line_start = space + 1;
+                        line_width = characters[line_start..i].iter().map(advance).sum();
+                        last_space = None;
+                    }
+                }
+                line_width += width;
+            }
+        }
+
+        let mut start = 0;
+        while start < characters.len() {
+            let end = (start + 1..characters.len())
+                .find(|i| characters[*i].alignment_changed)
+                .unwrap_or(characters.len());
+            let segment = &mut characters[start..end];
+
+            // Trailing spaces (such as the one a line was broken at) shouldn't affect alignment.
+            let width: i32 = segment.iter().rev().skip_while(|c| c.character == ' ').map(advance).sum();
+            let mut x = match segment[0].state.alignment {
+                TextAlignment::Left | TextAlignment::Table(_) => 0,
+                TextAlignment::Center => -width / 2,
+                TextAlignment::Right => -width
+            };
+            for character in segment {
+                character.x = x;
+                character.y = character.state.y as i32;
+                x += self.characters[&character.character].advance_x;
+            }
+
+            start = end;
+        }
+    }
+
     pub fn draw_string_buffer_to_bitmap(&self, characters: &[DrawableCharacter], request: FontDrawRequest) -> AddBitmapParameter {
         let Some(pixel_count) = request.resolution.width.checked_mul(request.resolution.height) else {
             panic!("width * height overflows")
@@ -141,6 +296,10 @@ impl Font {
     ) {
         let character_data = &self.characters[&character.character];
 
+        // Place the character's bitmap relative to the baseline.
+        let x_offset = x_offset - character_data.origin_x;
+        let y_offset = y_offset + self.ascending_height as i32 - character_data.origin_y;
+
         for x in 0..character_data.width {
             let x_offset = x_offset + x as i32;
             if x_offset < 0 {
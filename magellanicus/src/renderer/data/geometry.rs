@@ -2,37 +2,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use crate::renderer::vulkan::VulkanMaterialData;
 use crate::vertex::{ModelVertex, VertexOffsets};
-
-#[derive(Copy, Clone, Debug)]
-pub struct GeometryDetailData<T: Sized + 'static> {
-    pub super_low: T,
-    pub low: T,
-    pub medium: T,
-    pub high: T,
-    pub super_high: T,
-}
-
-impl<T: Sized + 'static> GeometryDetailData<T> {
-    /// Returns an array of the data sorted from lowest to highest.
-    pub fn as_arr(&self) -> [&T; 5] {
-        [&self.super_low, &self.low, &self.medium, &self.high, &self.super_high]
-    }
-
-    /// Returns an array of mutable references to the data sorted from lowest to highest.
-    pub fn as_arr_mut(&mut self) -> [&mut T; 5] {
-        [&mut self.super_low, &mut self.low, &mut self.medium, &mut self.high, &mut self.super_high]
-    }
-
-    /// Return an iterator over the items, sorted from lowest to highest.
-    pub fn iter(&self) -> impl Iterator<Item = &T> {
-        self.as_arr().into_iter()
-    }
-
-    /// Return an iterator over mutable references to the items, sorted from lowest to highest.
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
-        self.as_arr_mut().into_iter()
-    }
-}
+use crate::renderer::GeometryDetailData;
 
 pub struct Geometry {
     pub nodes: HashMap<Arc<String>, GeometryNode>,
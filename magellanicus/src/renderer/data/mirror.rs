@@ -0,0 +1,59 @@
+use glam::{Mat3, Mat4, Vec3};
+
+/// Most a vertex of a mirror can be off of its plane, in world units.
+const PLANE_TOLERANCE: f32 = 0.01;
+
+/// A flat geometry of a BSP whose shader is a mirror.
+pub struct Mirror {
+    /// Index of the geometry.
+    pub geometry: usize,
+
+    /// Normal of the mirror's plane, which may face either side of the mirror.
+    pub normal: Vec3,
+
+    /// Distance of the mirror's plane from the origin along `normal`.
+    pub offset: f32,
+
+    /// Average of the mirror's vertices, used for finding the nearest mirrors.
+    pub center: Vec3
+}
+
+impl Mirror {
+    /// Get how far `position` is from the mirror's plane.
+    pub fn distance(&self, position: Vec3) -> f32 {
+        (self.normal.dot(position) - self.offset).abs()
+    }
+
+    /// Get the matrix that reflects points across the mirror's plane.
+    pub fn reflection(&self) -> Mat4 {
+        let n = self.normal;
+        let linear = Mat3::IDENTITY - Mat3::from_cols(n * n.x, n * n.y, n * n.z) * 2.0;
+        Mat4::from_translation(n * (self.offset * 2.0)) * Mat4::from_mat3(linear)
+    }
+
+    /// Get the mirror's plane as `[normal, offset]`, facing `position`, for clipping everything behind the mirror.
+    pub fn clip_plane(&self, position: Vec3) -> [f32; 4] {
+        if self.normal.dot(position) >= self.offset {
+            self.normal.extend(self.offset).to_array()
+        }
+        else {
+            (-self.normal).extend(-self.offset).to_array()
+        }
+    }
+}
+
+/// Make a mirror out of the triangles of a geometry, or return `None` if they aren't all on one plane.
+pub fn find_mirror(geometry: usize, triangles: &[[Vec3; 3]]) -> Option<Mirror> {
+    // Weighting by area keeps slivers from throwing the normal off.
+    let weighted_normal: Vec3 = triangles.iter().map(|[a, b, c]| (b - a).cross(c - a)).sum();
+    let normal = weighted_normal.try_normalize()?;
+
+    let vertices = triangles.iter().flatten();
+    let center = vertices.clone().sum::<Vec3>() / (triangles.len() * 3) as f32;
+    let offset = normal.dot(center);
+    if vertices.clone().any(|v| (normal.dot(*v) - offset).abs() > PLANE_TOLERANCE) {
+        return None
+    }
+
+    Some(Mirror { geometry, normal, offset, center })
+}
@@ -0,0 +1,136 @@
+use glam::Vec3;
+
+/// Most triangles kept in a leaf of the hierarchy.
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+/// Opaque triangles of a BSP, used for checking if lens flares are blocked.
+///
+/// Triangles are kept in a bounding volume hierarchy so rays can skip most of the BSP.
+#[derive(Default)]
+pub struct Occluders {
+    nodes: Vec<OccluderNode>,
+    triangles: Vec<[Vec3; 3]>
+}
+
+struct OccluderNode {
+    min: Vec3,
+    max: Vec3,
+    contents: OccluderNodeContents
+}
+
+enum OccluderNodeContents {
+    /// Indices of the child nodes.
+    Branch(usize, usize),
+
+    /// Range of `triangles` in the node.
+    Leaf { first: usize, count: usize }
+}
+
+impl Occluders {
+    pub fn new(mut triangles: Vec<[Vec3; 3]>) -> Self {
+        let mut nodes = Vec::new();
+        if !triangles.is_empty() {
+            let count = triangles.len();
+            build_node(&mut nodes, &mut triangles, 0, count);
+        }
+        Self { nodes, triangles }
+    }
+
+    /// Return `true` if a ray from `origin` going in `direction` hits any triangle.
+    pub fn is_ray_blocked(&self, origin: Vec3, direction: Vec3) -> bool {
+        if self.nodes.is_empty() {
+            return false
+        }
+
+        let inverse_direction = direction.recip();
+        let mut stack = vec![0usize];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !ray_hits_box(origin, inverse_direction, node.min, node.max) {
+                continue
+            }
+            match node.contents {
+                OccluderNodeContents::Branch(left, right) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                OccluderNodeContents::Leaf { first, count } => {
+                    if self.triangles[first..first + count].iter().any(|t| ray_hits_triangle(origin, direction, t)) {
+                        return true
+                    }
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// Add a node for `triangles[first..first + count]`, splitting it in half along its longest axis until the halves are
+/// small enough to be leaves, and return its index.
+fn build_node(nodes: &mut Vec<OccluderNode>, triangles: &mut [[Vec3; 3]], first: usize, count: usize) -> usize {
+    let range = &mut triangles[first..first + count];
+    let (min, max) = range.iter().flatten().fold(
+        (Vec3::INFINITY, Vec3::NEG_INFINITY),
+        |(min, max), v| (min.min(*v), max.max(*v))
+    );
+
+    let index = nodes.len();
+    nodes.push(OccluderNode { min, max, contents: OccluderNodeContents::Leaf { first, count } });
+    if count <= MAX_LEAF_TRIANGLES {
+        return index
+    }
+
+    let size = max - min;
+    let axis = if size.x >= size.y && size.x >= size.z { 0 } else if size.y >= size.z { 1 } else { 2 };
+    let half = count / 2;
+    range.select_nth_unstable_by(half, |a, b| centroid(a)[axis].total_cmp(&centroid(b)[axis]));
+
+    let left = build_node(nodes, triangles, first, half);
+    let right = build_node(nodes, triangles, first + half, count - half);
+    nodes[index].contents = OccluderNodeContents::Branch(left, right);
+    index
+}
+
+fn centroid(triangle: &[Vec3; 3]) -> Vec3 {
+    (triangle[0] + triangle[1] + triangle[2]) / 3.0
+}
+
+/// Slab test against an axis-aligned box, with `inverse_direction` being the reciprocal of the ray's direction.
+fn ray_hits_box(origin: Vec3, inverse_direction: Vec3, min: Vec3, max: Vec3) -> bool {
+    let a = (min - origin) * inverse_direction;
+    let b = (max - origin) * inverse_direction;
+    let near = a.min(b).max_element();
+    let far = a.max(b).min_element();
+
+    // NaN (from a zero direction component on a slab edge) compares false, which is treated as a miss.
+    near <= far && far >= 0.0
+}
+
+/// Möller–Trumbore intersection, hitting both sides of the triangle.
+fn ray_hits_triangle(origin: Vec3, direction: Vec3, triangle: &[Vec3; 3]) -> bool {
+    let [a, b, c] = *triangle;
+    let ab = b - a;
+    let ac = c - a;
+    let p = direction.cross(ac);
+    let determinant = ab.dot(p);
+    if determinant.abs() <= f32::EPSILON {
+        return false
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+    let ao = origin - a;
+    let u = ao.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return false
+    }
+
+    let q = ao.cross(ab);
+    let v = direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return false
+    }
+
+    // Ignore hits right at the origin so that a camera touching a wall isn't blocked by it.
+    ac.dot(q) * inverse_determinant > 0.001
+}
@@ -1,37 +1,24 @@
 use crate::error::MResult;
 use crate::renderer::vulkan::VulkanMaterialShaderData;
-use crate::renderer::{AddShaderData, AddShaderParameter, Renderer};
+use crate::renderer::{AddShaderParameter, Renderer, ShaderType};
 
 pub struct Shader {
     pub vulkan: VulkanMaterialShaderData,
-    pub shader_type: ShaderType
+    pub shader_type: ShaderType,
+
+    /// Parameters the shader was created with, used for rebuilding it if a bitmap it uses is replaced.
+    pub parameter: AddShaderParameter
 }
 
 impl Shader {
     pub fn load_from_parameters(renderer: &mut Renderer, add_shader_parameter: AddShaderParameter) -> MResult<Self> {
-        let shader_type = match &add_shader_parameter.data {
-            AddShaderData::BasicShader(s) => s.shader_type,
-            AddShaderData::ShaderEnvironment(_) => ShaderType::Environment,
-            AddShaderData::ShaderTransparentChicago(_) => ShaderType::TransparentChicago
-        };
+        let shader_type = add_shader_parameter.shader_type();
 
         let vulkan = VulkanMaterialShaderData::new_from_parameters(
             renderer,
-            add_shader_parameter
+            add_shader_parameter.clone()
         )?;
 
-        Ok(Self { vulkan, shader_type })
+        Ok(Self { vulkan, shader_type, parameter: add_shader_parameter })
     }
 }
-
-#[derive(PartialEq, Debug, Copy, Clone)]
-pub enum ShaderType {
-    Environment,
-    Model,
-    TransparentGeneric,
-    TransparentChicago,
-    TransparentGlass,
-    TransparentMeter,
-    TransparentPlasma,
-    TransparentWater
-}
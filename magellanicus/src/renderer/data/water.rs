@@ -0,0 +1,60 @@
+use glam::Vec3;
+
+/// Most a geometry's height can differ from the first geometry of a water surface for it to be part of the surface.
+const SURFACE_HEIGHT_TOLERANCE: f32 = 0.01;
+
+/// How much of a geometry's area must face straight up or down for it to be considered flat.
+const FLAT_FRACTION: f32 = 0.95;
+
+/// Smallest area, in square world units, a water surface can have to reflect anything.
+///
+/// Reflections need the whole scene to be drawn again, so puddles aren't worth it.
+pub const MIN_REFLECTIVE_WATER_AREA: f32 = 4.0;
+
+/// Flat water geometries of a BSP that are at the same height.
+pub struct WaterSurface {
+    pub height: f32,
+
+    /// Total area of the surface's triangles, in square world units.
+    pub area: f32,
+
+    /// Indices of the geometries in the surface.
+    pub geometries: Vec<usize>
+}
+
+/// Group flat water geometries into surfaces, largest first.
+///
+/// `geometries` is the index and triangles of each water geometry. Geometries that aren't flat are left out.
+pub fn find_water_surfaces(geometries: impl IntoIterator<Item = (usize, Vec<[Vec3; 3]>)>) -> Vec<WaterSurface> {
+    let mut flat_geometries: Vec<(usize, f32, f32)> = geometries
+        .into_iter()
+        .filter_map(|(index, triangles)| {
+            let mut area = 0.0;
+            let mut flat_area = 0.0;
+            let mut weighted_height = 0.0;
+            for [a, b, c] in triangles {
+                let normal = (b - a).cross(c - a);
+                let triangle_area = normal.length() / 2.0;
+                area += triangle_area;
+                flat_area += normal.z.abs() / 2.0;
+                weighted_height += (a.z + b.z + c.z) / 3.0 * triangle_area;
+            }
+            (area > 0.0 && flat_area >= area * FLAT_FRACTION).then(|| (index, weighted_height / area, area))
+        })
+        .collect();
+    flat_geometries.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut surfaces: Vec<WaterSurface> = Vec::new();
+    for (index, height, area) in flat_geometries {
+        match surfaces.last_mut() {
+            Some(surface) if height - surface.height <= SURFACE_HEIGHT_TOLERANCE => {
+                surface.area += area;
+                surface.geometries.push(index);
+            }
+            _ => surfaces.push(WaterSurface { height, area, geometries: vec![index] })
+        }
+    }
+
+    surfaces.sort_by(|a, b| b.area.total_cmp(&a.area));
+    surfaces
+}
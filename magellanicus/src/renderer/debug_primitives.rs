@@ -0,0 +1,111 @@
+use std::f32::consts::TAU;
+use std::time::{Duration, Instant};
+use glam::{Mat3, Vec3};
+use crate::types::FloatColor;
+
+/// Number of segments used to approximate each circle of a debug sphere.
+const SPHERE_SEGMENTS: usize = 32;
+
+/// A world-space line segment drawn on top of the scene.
+#[derive(Copy, Clone, Debug)]
+pub struct DebugLine {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub color: FloatColor,
+
+    /// Time after which the line is removed once it has been drawn.
+    pub expires: Instant
+}
+
+/// Where a [`DebugText`] is drawn.
+#[derive(Copy, Clone, Debug)]
+pub enum DebugTextAnchor {
+    /// Top-left corner of the text in pixels from the top-left corner of the frame.
+    Screen([f32; 2]),
+
+    /// Center of the text at a world-space position in each viewport.
+    World(Vec3)
+}
+
+/// Text drawn with the built-in debug text font.
+#[derive(Clone, Debug)]
+pub struct DebugText {
+    pub text: String,
+    pub anchor: DebugTextAnchor,
+
+    /// Size of each pixel of the font, in pixels.
+    pub scale: f32,
+    pub color: FloatColor,
+
+    /// Time after which the text is removed once it has been drawn.
+    pub expires: Instant
+}
+
+/// Debug primitives queued to be drawn.
+///
+/// Every shape is broken down into lines so that they can all be drawn at once.
+#[derive(Clone, Debug, Default)]
+pub struct DebugPrimitives {
+    pub lines: Vec<DebugLine>,
+    pub texts: Vec<DebugText>
+}
+
+impl DebugPrimitives {
+    pub fn add_line(&mut self, from: Vec3, to: Vec3, color: FloatColor, lifetime: Duration) {
+        let expires = Instant::now() + lifetime;
+        self.lines.push(DebugLine { from, to, color, expires });
+    }
+
+    pub fn add_box(&mut self, min: Vec3, max: Vec3, color: FloatColor, lifetime: Duration) {
+        let corner = |x: bool, y: bool, z: bool| Vec3::new(
+            if x { max.x } else { min.x },
+            if y { max.y } else { min.y },
+            if z { max.z } else { min.z }
+        );
+
+        for a in [false, true] {
+            for b in [false, true] {
+                self.add_line(corner(false, a, b), corner(true, a, b), color, lifetime);
+                self.add_line(corner(a, false, b), corner(a, true, b), color, lifetime);
+                self.add_line(corner(a, b, false), corner(a, b, true), color, lifetime);
+            }
+        }
+    }
+
+    pub fn add_sphere(&mut self, center: Vec3, radius: f32, color: FloatColor, lifetime: Duration) {
+        let circles = [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)];
+        for (u, v) in circles {
+            let point = |i: usize| {
+                let (sin, cos) = (i as f32 / SPHERE_SEGMENTS as f32 * TAU).sin_cos();
+                center + (u * cos + v * sin) * radius
+            };
+            for i in 0..SPHERE_SEGMENTS {
+                self.add_line(point(i), point(i + 1), color, lifetime);
+            }
+        }
+    }
+
+    pub fn add_axis(&mut self, origin: Vec3, rotation: Mat3, length: f32, lifetime: Duration) {
+        self.add_line(origin, origin + rotation.x_axis * length, [1.0, 0.0, 0.0, 1.0], lifetime);
+        self.add_line(origin, origin + rotation.y_axis * length, [0.0, 1.0, 0.0, 1.0], lifetime);
+        self.add_line(origin, origin + rotation.z_axis * length, [0.0, 0.0, 1.0, 1.0], lifetime);
+    }
+
+    pub fn add_text(&mut self, text: &str, anchor: DebugTextAnchor, scale: f32, color: FloatColor, lifetime: Duration) {
+        let expires = Instant::now() + lifetime;
+        self.texts.push(DebugText { text: text.to_owned(), anchor, scale, color, expires });
+    }
+
+    /// Remove all primitives that have expired as of `now`.
+    ///
+    /// This should be called after drawing a frame so that every primitive is drawn at least once.
+    pub fn remove_expired(&mut self, now: Instant) {
+        self.lines.retain(|l| l.expires > now);
+        self.texts.retain(|t| t.expires > now);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.texts.clear();
+    }
+}
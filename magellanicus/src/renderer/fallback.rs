@@ -0,0 +1,47 @@
+use crate::renderer::{BitmapHandle, BitmapType, ShaderHandle};
+
+/// Placeholder assets that can be registered with
+/// [`Renderer::set_fallback_asset`](crate::renderer::Renderer::set_fallback_asset).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FallbackAsset {
+    /// Bitmap used in place of 2D bitmaps that shaders reference but aren't loaded.
+    ///
+    /// Its first bitmap must be 2D.
+    Bitmap2D,
+
+    /// Bitmap used in place of cubemaps that shaders reference but aren't loaded.
+    ///
+    /// Its first bitmap must be a cubemap.
+    Cubemap,
+
+    /// Bitmap used as the lightmap of BSP materials that have no lightmap, instead of lighting them with the sky's
+    /// ambient light and sun.
+    ///
+    /// Its first bitmap must be 2D.
+    Lightmap,
+
+    /// Shader used in place of shaders that BSPs reference but aren't loaded.
+    ///
+    /// It must not be a model shader.
+    Shader
+}
+
+/// Handles of registered fallback assets, if any.
+#[derive(Copy, Clone, Default, Debug)]
+pub(crate) struct FallbackAssets {
+    pub bitmap_2d: Option<BitmapHandle>,
+    pub cubemap: Option<BitmapHandle>,
+    pub lightmap: Option<BitmapHandle>,
+    pub shader: Option<ShaderHandle>
+}
+
+impl FallbackAssets {
+    /// Get the bitmap to use in place of a missing bitmap of the given type.
+    pub fn get_bitmap(&self, bitmap_type: BitmapType) -> Option<BitmapHandle> {
+        match bitmap_type {
+            BitmapType::Dim2D => self.bitmap_2d,
+            BitmapType::Cubemap => self.cubemap,
+            BitmapType::Dim3D { .. } => None
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use std::sync::Arc;
+use glam::Vec3;
+use crate::renderer::BitmapHandle;
+use crate::types::FloatColor;
+
+/// Units of the position and size of a [`HudDrawParameter`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum HudUnits {
+    /// Pixels of the rendered frame (i.e. with `render_scale` applied).
+    #[default]
+    Pixels,
+
+    /// Fractions of the viewport's width and height, where (0, 0) is the top-left corner and (1, 1) is the
+    /// bottom-right corner.
+    Normalized,
+
+    /// Pixels of a canvas of the given size that is stretched to fill the viewport, such as Halo's 640x480 UI.
+    ///
+    /// Sizes that are otherwise in pixels, such as the resolution of bitmaps and fonts, are in canvas pixels, too.
+    Canvas { width: u32, height: u32 }
+}
+
+impl HudUnits {
+    /// Get the number of pixels per canvas pixel, or 1 if these aren't canvas units.
+    pub(crate) fn canvas_scale(self, viewport_size: [f32; 2]) -> [f32; 2] {
+        match self {
+            HudUnits::Canvas { width, height } => [viewport_size[0] / width as f32, viewport_size[1] / height as f32],
+            _ => [1.0, 1.0]
+        }
+    }
+}
+
+/// Determines how a HUD bitmap is combined with what is already drawn.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum HudBlendMode {
+    /// Blend over what is already drawn using alpha.
+    #[default]
+    AlphaBlend,
+
+    /// Add the color, multiplied by alpha, to what is already drawn.
+    Add,
+
+    /// Multiply what is already drawn by the color. Alpha is ignored.
+    Multiply
+}
+
+/// Describes how to draw a bitmap with [`Renderer::draw_hud_bitmap`](crate::renderer::Renderer::draw_hud_bitmap).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HudDrawParameter {
+    /// Position of the top-left corner of the bitmap relative to the top-left corner of the viewport.
+    ///
+    /// Default = `[0.0, 0.0]`
+    pub position: [f32; 2],
+
+    /// Width and height to draw the bitmap at.
+    ///
+    /// If `None`, the bitmap (or sprite) is drawn at its resolution in pixels (or canvas pixels if `units` is
+    /// [`HudUnits::Canvas`]).
+    ///
+    /// Default = `None`
+    pub size: Option<[f32; 2]>,
+
+    /// Units of `position` and `size`.
+    ///
+    /// Default = [`HudUnits::Pixels`]
+    pub units: HudUnits,
+
+    /// Color to multiply the bitmap by.
+    ///
+    /// Default = `[1.0, 1.0, 1.0, 1.0]`
+    pub tint: FloatColor,
+
+    /// Determines how the bitmap is combined with what is already drawn.
+    ///
+    /// Default = [`HudBlendMode::AlphaBlend`]
+    pub blend_mode: HudBlendMode
+}
+
+impl Default for HudDrawParameter {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            size: None,
+            units: HudUnits::Pixels,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            blend_mode: HudBlendMode::AlphaBlend
+        }
+    }
+}
+
+/// Describes how to draw a crosshair with [`Renderer::draw_crosshair`](crate::renderer::Renderer::draw_crosshair).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HudCrosshairParameter {
+    /// Offset in pixels of the center of the crosshair from the center of the viewport.
+    ///
+    /// Default = `[0.0, 0.0]`
+    pub offset: [f32; 2],
+
+    /// Size of each pixel of the crosshair's bitmap, in pixels.
+    ///
+    /// Default = `1.0`
+    pub scale: f32,
+
+    /// Color to multiply the crosshair by.
+    ///
+    /// Default = `[1.0, 1.0, 1.0, 1.0]`
+    pub color: FloatColor,
+
+    /// Determines how the crosshair is combined with what is already drawn.
+    ///
+    /// Default = [`HudBlendMode::AlphaBlend`]
+    pub blend_mode: HudBlendMode
+}
+
+impl Default for HudCrosshairParameter {
+    fn default() -> Self {
+        Self {
+            offset: [0.0, 0.0],
+            scale: 1.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            blend_mode: HudBlendMode::AlphaBlend
+        }
+    }
+}
+
+/// Describes how to draw a nav point with [`Renderer::draw_nav_point`](crate::renderer::Renderer::draw_nav_point).
+#[derive(Clone, PartialEq, Debug)]
+pub struct HudNavPointParameter {
+    /// Size of each pixel of the icon's bitmap, in pixels.
+    ///
+    /// Default = `1.0`
+    pub scale: f32,
+
+    /// Color to multiply the icon and distance text by.
+    ///
+    /// Default = `[1.0, 1.0, 1.0, 1.0]`
+    pub color: FloatColor,
+
+    /// Font to draw the distance from the camera in world units with, centered below the icon.
+    ///
+    /// If `None`, the distance is not drawn.
+    ///
+    /// Default = `None`
+    pub distance_font: Option<String>,
+
+    /// Distance in pixels from the edges of the viewport that the icon is kept within when the nav point is
+    /// off-screen.
+    ///
+    /// Default = `16.0`
+    pub edge_margin: f32
+}
+
+impl Default for HudNavPointParameter {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            distance_font: None,
+            edge_margin: 16.0
+        }
+    }
+}
+
+/// Horizontal alignment of text drawn with [`Renderer::draw_hud_text`](crate::renderer::Renderer::draw_hud_text).
+///
+/// Alignment codes in the text (e.g. `|r`) override this until the end of the line.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum HudTextAlignment {
+    /// Lines start at the position.
+    #[default]
+    Left,
+
+    /// Lines are centered on the position.
+    Center,
+
+    /// Lines end at the position.
+    Right
+}
+
+/// Describes how to draw text with [`Renderer::draw_hud_text`](crate::renderer::Renderer::draw_hud_text).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HudTextParameter {
+    /// Position of the top of the first line relative to the top-left corner of the viewport.
+    ///
+    /// Default = `[0.0, 0.0]`
+    pub position: [f32; 2],
+
+    /// Units of `position`.
+    ///
+    /// Default = [`HudUnits::Pixels`]
+    pub units: HudUnits,
+
+    /// Determines which part of each line is placed at `position`.
+    ///
+    /// Default = [`HudTextAlignment::Left`]
+    pub alignment: HudTextAlignment,
+
+    /// Size of each pixel of the font, in pixels.
+    ///
+    /// Default = `1.0`
+    pub scale: f32,
+
+    /// Color of the text. Color codes in the text (e.g. `^1`) replace the red, green, and blue channels.
+    ///
+    /// Default = `[1.0, 1.0, 1.0, 1.0]`
+    pub color: FloatColor,
+
+    /// If set, lines longer than this many pixels are broken between words.
+    ///
+    /// Default = `None`
+    pub max_width: Option<f32>,
+
+    /// Draw a black shadow one font pixel below and to the right of the text.
+    ///
+    /// Default = `true`
+    pub drop_shadow: bool,
+
+    /// Determines how the text is combined with what is already drawn.
+    ///
+    /// Default = [`HudBlendMode::AlphaBlend`]
+    pub blend_mode: HudBlendMode
+}
+
+impl Default for HudTextParameter {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            units: HudUnits::Pixels,
+            alignment: HudTextAlignment::Left,
+            scale: 1.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            max_width: None,
+            drop_shadow: true,
+            blend_mode: HudBlendMode::AlphaBlend
+        }
+    }
+}
+
+/// Point of a viewport that a [`HudInterfaceElement`] is placed relative to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum HudAnchor {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center
+}
+
+impl HudAnchor {
+    /// Get the anchor point in normalized viewport coordinates.
+    pub(crate) fn position(self) -> [f32; 2] {
+        match self {
+            HudAnchor::TopLeft => [0.0, 0.0],
+            HudAnchor::TopRight => [1.0, 0.0],
+            HudAnchor::BottomLeft => [0.0, 1.0],
+            HudAnchor::BottomRight => [1.0, 1.0],
+            HudAnchor::Center => [0.5, 0.5]
+        }
+    }
+
+    /// Get the offset in pixels from the anchor point to the top-left corner of an element `size` pixels large that
+    /// is placed `offset` pixels from the anchor.
+    pub(crate) fn element_offset(self, offset: [f32; 2], size: [f32; 2]) -> [f32; 2] {
+        let [x, y] = offset;
+        let [width, height] = size;
+        match self {
+            HudAnchor::TopLeft => [x, y],
+            HudAnchor::TopRight => [-x - width, y],
+            HudAnchor::BottomLeft => [x, -y - height],
+            HudAnchor::BottomRight => [-x - width, -y - height],
+            HudAnchor::Center => [x - width / 2.0, y - height / 2.0]
+        }
+    }
+}
+
+/// A set of HUD elements drawn with [`Renderer::draw_hud_interface`](crate::renderer::Renderer::draw_hud_interface),
+/// such as those of a weapon_hud_interface or unit_hud_interface tag.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HudInterfaceParameter {
+    pub elements: Vec<HudInterfaceElement>,
+
+    /// Scale applied to the offset and size of every element, e.g. to scale a HUD made for 640x480 to the viewport.
+    ///
+    /// Default = `1.0`
+    pub scale: f32
+}
+
+impl Default for HudInterfaceParameter {
+    fn default() -> Self {
+        Self {
+            elements: Vec::new(),
+            scale: 1.0
+        }
+    }
+}
+
+/// An element of a [`HudInterfaceParameter`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct HudInterfaceElement {
+    /// Point of the viewport the element is placed relative to.
+    ///
+    /// The corner of the element nearest to the anchor is placed at the anchor, or the center of the element if the
+    /// anchor is [`HudAnchor::Center`].
+    pub anchor: HudAnchor,
+
+    /// Offset in pixels from the anchor.
+    ///
+    /// Positive values move the element toward the center of the viewport, except for [`HudAnchor::Center`] where
+    /// they move it right and down.
+    pub offset: [f32; 2],
+
+    /// Size of each pixel of the element's bitmap, in pixels.
+    pub scale: f32,
+
+    pub element_type: HudInterfaceElementType
+}
+
+/// Describes what a [`HudInterfaceElement`] draws.
+///
+/// Bitmaps are referred to by path, sequence index, and frame like with
+/// [`Renderer::draw_hud_bitmap`](crate::renderer::Renderer::draw_hud_bitmap). Values are indices into the values
+/// passed to [`Renderer::draw_hud_interface`](crate::renderer::Renderer::draw_hud_interface).
+#[derive(Clone, PartialEq, Debug)]
+pub enum HudInterfaceElementType {
+    /// A bitmap drawn as-is.
+    Static {
+        bitmap: String,
+        sequence: usize,
+        frame: usize,
+        color: FloatColor,
+        blend_mode: HudBlendMode
+    },
+
+    /// A bitmap whose alpha channel is a gradient, where pixels with alpha less than or equal to the value (clamped
+    /// between 0 and 1) are filled.
+    ///
+    /// Filled pixels are drawn with `min_color` interpolated to `max_color` by the value, and the rest are drawn with
+    /// `empty_color`. Pixels with an alpha of 0 are not drawn.
+    Meter {
+        bitmap: String,
+        sequence: usize,
+        frame: usize,
+        value: usize,
+        min_color: FloatColor,
+        max_color: FloatColor,
+        empty_color: FloatColor
+    },
+
+    /// A number drawn with digits from a sequence of a bitmap, where frames 0-9 are the digits 0-9, and frame 10 (if
+    /// present) is a minus sign.
+    ///
+    /// The value is rounded to the nearest integer and clamped to fit in `digits` digits. Digits are right-aligned
+    /// within a field that is `digits` times the width of the 0 digit.
+    Number {
+        bitmap: String,
+        sequence: usize,
+        value: usize,
+        digits: usize,
+        show_leading_zeros: bool,
+        color: FloatColor
+    }
+}
+
+/// Fill of a meter drawn by a [`HudDraw`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HudMeter {
+    pub value: f32,
+    pub empty_color: FloatColor
+}
+
+/// A nav point queued to be drawn in every viewport.
+#[derive(Clone, Debug)]
+pub struct HudNavPoint {
+    pub position: Vec3,
+
+    /// Icon to draw, which is placed in each viewport when drawn.
+    pub icon: HudDraw,
+
+    pub parameter: HudNavPointParameter
+}
+
+/// Image a [`HudDraw`] samples from.
+#[derive(Clone, PartialEq, Debug)]
+pub enum HudImage {
+    /// A bitmap of a bitmap tag.
+    Bitmap { handle: BitmapHandle, index: usize },
+
+    /// The glyph atlas of a font.
+    Font(Arc<String>)
+}
+
+/// A bitmap queued to be drawn on a viewport's HUD layer.
+#[derive(Clone, Debug)]
+pub struct HudDraw {
+    pub viewport: usize,
+    pub image: HudImage,
+
+    /// Left, top, right, and bottom texture coordinates.
+    pub texture_coords: [f32; 4],
+
+    /// Width and height of the bitmap (or sprite) in pixels.
+    pub natural_size: [f32; 2],
+
+    /// Offset in pixels from the position, used to place each character of text and anchored elements.
+    pub offset: [f32; 2],
+
+    /// If set, the bitmap is drawn as a meter, and `parameter.tint` is the color of its filled pixels.
+    pub meter: Option<HudMeter>,
+
+    pub parameter: HudDrawParameter
+}
+
+impl HudDraw {
+    /// Get the position and size in pixels relative to a viewport `viewport_size` pixels large.
+    pub fn pixel_rect(&self, viewport_size: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let canvas_scale = self.parameter.units.canvas_scale(viewport_size);
+        let to_pixels = |v: [f32; 2]| match self.parameter.units {
+            HudUnits::Pixels => v,
+            HudUnits::Normalized => [v[0] * viewport_size[0], v[1] * viewport_size[1]],
+            HudUnits::Canvas { .. } => [v[0] * canvas_scale[0], v[1] * canvas_scale[1]]
+        };
+        let [x, y] = to_pixels(self.parameter.position);
+        let [offset_x, offset_y] = [self.offset[0] * canvas_scale[0], self.offset[1] * canvas_scale[1]];
+        let position = [x + offset_x, y + offset_y];
+        let size = match self.parameter.size {
+            Some(size) => to_pixels(size),
+            None => [self.natural_size[0] * canvas_scale[0], self.natural_size[1] * canvas_scale[1]]
+        };
+        (position, size)
+    }
+}
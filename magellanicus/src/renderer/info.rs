@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use crate::renderer::{BitmapHandle, BitmapType, BSPHandle, FogData, Resolution, ShaderHandle, ShaderType, SkyAmbientLight, SkyLensFlare, SkyLight};
+
+/// Describes a loaded bitmap.
+#[derive(Clone, Debug)]
+pub struct BitmapInfo {
+    pub handle: BitmapHandle,
+    pub path: Arc<String>,
+
+    /// Resolution and type of each bitmap, in order.
+    pub bitmaps: Vec<BitmapInfoBitmap>,
+
+    /// Number of sequences.
+    pub sequence_count: usize
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BitmapInfoBitmap {
+    pub resolution: Resolution,
+    pub bitmap_type: BitmapType
+}
+
+/// Describes a loaded shader.
+#[derive(Clone, Debug)]
+pub struct ShaderInfo {
+    pub handle: ShaderHandle,
+    pub path: Arc<String>,
+    pub shader_type: ShaderType
+}
+
+/// Describes a loaded BSP.
+#[derive(Clone, Debug)]
+pub struct BSPInfo {
+    pub handle: BSPHandle,
+    pub path: Arc<String>,
+
+    /// Number of material geometries.
+    pub geometry_count: usize,
+
+    /// Number of clusters.
+    pub cluster_count: usize,
+
+    /// Maximum draw distance, derived from the BSP's bounds.
+    pub draw_distance: f32
+}
+
+/// Describes a loaded sky.
+#[derive(Clone, Debug)]
+pub struct SkyInfo {
+    pub path: Arc<String>,
+
+    /// Path to the skybox geometry, if any.
+    pub geometry: Option<Arc<String>>,
+
+    pub outdoor_fog: FogData,
+    pub indoor_fog: FogData,
+    pub outdoor_ambient: SkyAmbientLight,
+    pub indoor_ambient: SkyAmbientLight,
+
+    /// Directional lights, with normalized directions.
+    pub lights: Vec<SkyLight>,
+
+    /// Lens flares, with normalized directions.
+    pub lens_flares: Vec<SkyLensFlare>
+}
@@ -0,0 +1,107 @@
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+use glam::Vec3;
+use crate::renderer::data::Occluders;
+use crate::renderer::SkyLensFlare;
+
+/// Seconds a lens flare takes to fade fully in or out when it becomes visible or blocked.
+const FADE_TIME: f32 = 0.2;
+
+/// Number of rays checked around the edge of a lens flare's occlusion radius, besides the one toward its center.
+const OCCLUSION_EDGE_RAYS: usize = 8;
+
+/// How visible the lens flares of each viewport's sky are.
+///
+/// Visibility fades toward how much of each light is unblocked so flares don't pop in and out as the camera moves.
+#[derive(Default)]
+pub(crate) struct LensFlareVisibility {
+    viewports: Vec<ViewportLensFlares>,
+    last_update: Option<Instant>
+}
+
+#[derive(Clone, Default)]
+struct ViewportLensFlares {
+    /// Sky of the cluster the camera is in, if it has any lens flares.
+    sky: Option<Arc<String>>,
+
+    /// Visibility of each of the sky's lens flares, from 0.0 (hidden) to 1.0.
+    visibility: Vec<f32>
+}
+
+impl LensFlareVisibility {
+    pub fn clear(&mut self) {
+        self.viewports.clear();
+        self.last_update = None;
+    }
+
+    /// Get the sky whose lens flares are drawn in a viewport, and how visible each of them is.
+    pub fn get(&self, viewport: usize) -> Option<(&Arc<String>, &[f32])> {
+        let viewport = self.viewports.get(viewport)?;
+        Some((viewport.sky.as_ref()?, viewport.visibility.as_slice()))
+    }
+
+    pub fn is_any_visible(&self) -> bool {
+        self.viewports.iter().any(|v| v.visibility.iter().any(|v| *v > 0.0))
+    }
+
+    /// Start updating for a new frame, returning how far visibility can fade this frame.
+    ///
+    /// If `snap` is set (e.g. when rendering deterministically), visibility is set to its target immediately.
+    pub fn begin_update(&mut self, viewport_count: usize, snap: bool) -> f32 {
+        self.viewports.resize_with(viewport_count, Default::default);
+
+        let now = Instant::now();
+        let last_update = self.last_update.replace(now);
+        match last_update {
+            Some(last_update) if !snap => (now - last_update).as_secs_f32() / FADE_TIME,
+            _ => 1.0
+        }
+    }
+
+    /// Fade the visibility of a viewport's lens flares toward how much of each light is unblocked from `position`.
+    pub fn update_viewport(
+        &mut self,
+        viewport: usize,
+        sky: Option<(&Arc<String>, &[SkyLensFlare])>,
+        position: Vec3,
+        occluders: &Occluders,
+        fade: f32
+    ) {
+        let viewport = &mut self.viewports[viewport];
+        let Some((sky, lens_flares)) = sky else {
+            *viewport = ViewportLensFlares::default();
+            return
+        };
+
+        // Flares of a different sky fade in from nothing.
+        if viewport.sky.as_ref() != Some(sky) {
+            viewport.sky = Some(sky.clone());
+            viewport.visibility.clear();
+        }
+        viewport.visibility.resize(lens_flares.len(), 0.0);
+
+        for (visibility, lens_flare) in viewport.visibility.iter_mut().zip(lens_flares) {
+            let target = unblocked_fraction(lens_flare, position, occluders);
+            *visibility += (target - *visibility).clamp(-fade, fade);
+        }
+    }
+}
+
+/// Get the fraction of rays toward a lens flare's light, spread across its occlusion radius, that aren't blocked.
+fn unblocked_fraction(lens_flare: &SkyLensFlare, position: Vec3, occluders: &Occluders) -> f32 {
+    let toward_light = -lens_flare.direction;
+    let mut unblocked = !occluders.is_ray_blocked(position, toward_light) as usize;
+    if lens_flare.occlusion_radius == 0.0 {
+        return unblocked as f32
+    }
+
+    let (sin_radius, cos_radius) = lens_flare.occlusion_radius.sin_cos();
+    let (u, v) = toward_light.any_orthonormal_pair();
+    for i in 0..OCCLUSION_EDGE_RAYS {
+        let (sin, cos) = (TAU * i as f32 / OCCLUSION_EDGE_RAYS as f32).sin_cos();
+        let direction = toward_light * cos_radius + (u * cos + v * sin) * sin_radius;
+        unblocked += !occluders.is_ray_blocked(position, direction) as usize;
+    }
+    unblocked as f32 / (OCCLUSION_EDGE_RAYS + 1) as f32
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+use glam::Vec3;
+use crate::renderer::AddLightParameter;
+
+/// Maximum number of dynamic lights that can light a single viewport.
+///
+/// If more lights than this are added, only the ones nearest to the camera are used.
+pub const MAX_DYNAMIC_LIGHTS: usize = 16;
+
+/// Refers to a light added with [`Renderer::add_light`](crate::renderer::Renderer::add_light).
+///
+/// Handles only refer to lights in the scene they were added to, and they are invalidated when the light is removed or
+/// when the renderer is reset.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct LightHandle(u64);
+
+/// Hands out light handles for every scene.
+#[derive(Clone, Debug, Default)]
+pub struct LightHandleAllocator {
+    /// Never reset, so that handles to removed lights never resolve to newer lights, even in another scene.
+    next_handle: u64
+}
+
+impl LightHandleAllocator {
+    pub fn next(&mut self) -> LightHandle {
+        let handle = LightHandle(self.next_handle);
+        self.next_handle += 1;
+        handle
+    }
+}
+
+/// A dynamic light that has been added.
+#[derive(Clone, Debug)]
+pub struct DynamicLight {
+    pub parameter: AddLightParameter,
+
+    /// Time the light was added, which its color animation starts from.
+    pub added: Instant
+}
+
+impl DynamicLight {
+    /// Get the color of the light at `now`, or at the start of its color animation if `now` is `None`.
+    pub fn color_at(&self, now: Option<Instant>) -> [f32; 3] {
+        let Some(animation) = self.parameter.color_animation.as_ref() else {
+            return self.parameter.color
+        };
+        let seconds = now.map(|n| n.saturating_duration_since(self.added).as_secs_f32()).unwrap_or(0.0);
+        animation.color_at(self.parameter.color, seconds)
+    }
+}
+
+/// Dynamic lights that have been added to a scene.
+#[derive(Clone, Debug, Default)]
+pub struct DynamicLights {
+    lights: BTreeMap<LightHandle, DynamicLight>
+}
+
+impl DynamicLights {
+    pub fn add(&mut self, handle: LightHandle, light: AddLightParameter) {
+        self.lights.insert(handle, DynamicLight { parameter: light, added: Instant::now() });
+    }
+
+    /// Get a mutable reference to the parameters of a light.
+    ///
+    /// Changing them does not restart the light's color animation.
+    pub fn get_mut(&mut self, handle: LightHandle) -> Option<&mut AddLightParameter> {
+        self.lights.get_mut(&handle).map(|l| &mut l.parameter)
+    }
+
+    pub fn remove(&mut self, handle: LightHandle) -> Option<DynamicLight> {
+        self.lights.remove(&handle)
+    }
+
+    /// Get up to [`MAX_DYNAMIC_LIGHTS`] lights whose reach is nearest to `position`, nearest first.
+    ///
+    /// Lights are ranked by the distance from `position` to the edge of their radius, so large lights that contain
+    /// `position` come before small lights that are closer.
+    pub fn nearest(&self, position: Vec3) -> Vec<&DynamicLight> {
+        let mut lights: Vec<(f32, &DynamicLight)> = self
+            .lights
+            .values()
+            .map(|l| (l.parameter.position.distance(position) - l.parameter.radius, l))
+            .collect();
+        lights.sort_by(|a, b| a.0.total_cmp(&b.0));
+        lights.into_iter().take(MAX_DYNAMIC_LIGHTS).map(|(_, l)| l).collect()
+    }
+}
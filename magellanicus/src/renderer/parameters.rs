@@ -4,6 +4,8 @@ mod shader;
 mod bsp;
 mod sky;
 mod font;
+mod assets;
+mod light;
 
 pub use bitmap::*;
 pub use geometry::*;
@@ -11,10 +13,19 @@ pub use shader::*;
 pub use bsp::*;
 pub use sky::*;
 pub use font::*;
+pub use assets::*;
+pub use light::*;
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use crate::error::{Error, MResult};
 
 /// Used for initializing a renderer.
 ///
 /// These fields can be changed later with their respective set_* methods.
+///
+/// Use [`RendererParameters::builder`] to validate the parameters before passing them to the renderer.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct RendererParameters {
     /// Resolution of the renderer in (width, height)
     ///
@@ -40,9 +51,64 @@ pub struct RendererParameters {
 
     /// Render scaling
     pub render_scale: f32,
+
+    /// Lower `msaa` and `anisotropic_filtering` to what the device supports instead of erroring.
+    ///
+    /// Use [`Renderer::get_parameters`](crate::renderer::Renderer::get_parameters) to get the values that were
+    /// actually used.
+    ///
+    /// Default = false
+    pub clamp_to_device: bool,
+
+    /// Darken creases and corners of opaque BSP geometry with screen-space ambient occlusion.
+    ///
+    /// Default = false
+    pub ssao: bool,
+
+    /// Reflect opaque BSP geometry in large water surfaces, at a lower resolution.
+    ///
+    /// Water is not drawn at all if this is off.
+    ///
+    /// Default = false
+    pub water_reflections: bool,
+
+    /// Allow viewports to blur what is out of focus, which is set per viewport with
+    /// [`Renderer::set_depth_of_field`](crate::renderer::Renderer::set_depth_of_field).
+    ///
+    /// Default = false
+    pub depth_of_field: bool,
+
+    /// Allow viewports to blur the scene as the camera moves, which is set per viewport with
+    /// [`Renderer::set_motion_blur`](crate::renderer::Renderer::set_motion_blur).
+    ///
+    /// Default = false
+    pub motion_blur: bool,
+
+    /// Smooth jagged edges by jittering the camera slightly each frame and blending each frame with the previous ones
+    /// (temporal anti-aliasing).
+    ///
+    /// This costs far less than MSAA, but fast motion may leave faint trails or look softer.
+    ///
+    /// Default = false
+    pub taa: bool,
+
+    /// Allow viewports to have exposure, bloom, color overlay, and letterbox, which are set per viewport with
+    /// [`Renderer::set_post_processing`](crate::renderer::Renderer::set_post_processing).
+    ///
+    /// Default = false
+    pub post_processing: bool,
+
+    /// Number of the largest mipmaps to leave out when uploading bitmaps, lowering texture quality to save video
+    /// memory.
+    ///
+    /// Each level halves the width and height of bitmaps. Bitmaps keep at least their smallest mipmap, so bitmaps
+    /// without mipmaps are always uploaded at full resolution. This only affects bitmaps added after it is set.
+    ///
+    /// Default = 0
+    pub skipped_mip_levels: u32
 }
 
-#[derive(Copy, Clone, PartialEq, Default)]
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
 pub enum MSAA {
     #[default]
     NoMSAA = 1,
@@ -54,6 +120,55 @@ pub enum MSAA {
     MSAA64x = 64,
 }
 
+/// Sets of parameters that trade quality for speed, so frontends don't need to know every setting.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum QualityPreset {
+    /// No MSAA, anisotropic filtering, water reflections, or SSAO, drawing at 75% of the resolution.
+    Low,
+
+    /// 4x anisotropic filtering and water reflections.
+    Medium,
+
+    /// 4x MSAA, 8x anisotropic filtering, water reflections, and SSAO.
+    High,
+
+    /// 8x MSAA, 16x anisotropic filtering, water reflections, and SSAO.
+    Ultra,
+
+    /// Anything else; the parameters are left as they are.
+    Custom
+}
+
+impl QualityPreset {
+    /// All presets besides [`QualityPreset::Custom`], from lowest to highest quality.
+    pub const ALL: [QualityPreset; 4] = [QualityPreset::Low, QualityPreset::Medium, QualityPreset::High, QualityPreset::Ultra];
+
+    /// Set the parameters covered by the preset, leaving the rest (e.g. resolution) as they are.
+    ///
+    /// Covered parameters are `msaa`, `anisotropic_filtering`, `render_scale`, `water_reflections`, and `ssao`. Set
+    /// `clamp_to_device` if the device may not support the preset's MSAA or anisotropic filtering.
+    pub fn apply(self, parameters: RendererParameters) -> RendererParameters {
+        let (msaa, anisotropic_filtering, render_scale, water_reflections, ssao) = match self {
+            QualityPreset::Low => (MSAA::NoMSAA, None, 0.75, false, false),
+            QualityPreset::Medium => (MSAA::NoMSAA, Some(4.0), 1.0, true, false),
+            QualityPreset::High => (MSAA::MSAA4x, Some(8.0), 1.0, true, true),
+            QualityPreset::Ultra => (MSAA::MSAA8x, Some(16.0), 1.0, true, true),
+            QualityPreset::Custom => return parameters
+        };
+        RendererParameters { msaa, anisotropic_filtering, render_scale, water_reflections, ssao, ..parameters }
+    }
+
+    /// Get the preset that `parameters` match, or [`QualityPreset::Custom`] if none do.
+    ///
+    /// Anisotropic filtering is not compared, since it cannot be changed once a renderer is made.
+    pub fn from_parameters(parameters: &RendererParameters) -> QualityPreset {
+        QualityPreset::ALL
+            .into_iter()
+            .find(|p| RendererParameters { anisotropic_filtering: parameters.anisotropic_filtering, ..p.apply(*parameters) } == *parameters)
+            .unwrap_or(QualityPreset::Custom)
+    }
+}
+
 impl Default for RendererParameters {
     fn default() -> Self {
         Self {
@@ -62,8 +177,154 @@ impl Default for RendererParameters {
             vsync: false,
             msaa: Default::default(),
             anisotropic_filtering: None,
-            render_scale: 1.0
+            render_scale: 1.0,
+            clamp_to_device: false,
+            ssao: false,
+            water_reflections: false,
+            depth_of_field: false,
+            motion_blur: false,
+            taa: false,
+            post_processing: false,
+            skipped_mip_levels: 0
+        }
+    }
+}
+
+impl RendererParameters {
+    /// Start building parameters from the defaults.
+    pub fn builder() -> RendererParametersBuilder {
+        RendererParametersBuilder { parameters: Self::default() }
+    }
+
+    /// Errors if any field is out of range.
+    ///
+    /// This does not check anything that depends on the device, such as MSAA support.
+    pub(crate) fn validate(&self) -> MResult<()> {
+        if self.resolution.height == 0 || self.resolution.width == 0 {
+            return Err(Error::from_data_error_string("resolution has 0 on one or more dimensions".to_owned()))
+        }
+        if !(1..=4).contains(&self.number_of_viewports) {
+            return Err(Error::from_data_error_string(format!("number of viewports was set to {}, but only 1-4 are supported", self.number_of_viewports)))
+        }
+        if let Some(af) = self.anisotropic_filtering {
+            if !(af >= 1.0 && af.is_finite()) {
+                return Err(Error::from_data_error_string(format!("anisotropic filtering was set to {af}, but it must be at least 1")))
+            }
+        }
+        if !(self.render_scale > 0.0 && self.render_scale.is_finite()) {
+            return Err(Error::from_data_error_string(format!("render scale was set to {}, but it must be greater than 0", self.render_scale)))
         }
+        Ok(())
+    }
+
+    /// Anisotropic filtering the device must support, or `None` if it can be clamped.
+    pub(crate) fn required_anisotropic_filtering(&self) -> Option<f32> {
+        if self.clamp_to_device {
+            None
+        }
+        else {
+            self.anisotropic_filtering
+        }
+    }
+}
+
+/// Builds [`RendererParameters`].
+///
+/// Unset fields are left at their defaults.
+#[derive(Copy, Clone, Debug)]
+pub struct RendererParametersBuilder {
+    parameters: RendererParameters
+}
+
+impl RendererParametersBuilder {
+    pub fn resolution(mut self, resolution: Resolution) -> Self {
+        self.parameters.resolution = resolution;
+        self
+    }
+
+    pub fn number_of_viewports(mut self, number_of_viewports: usize) -> Self {
+        self.parameters.number_of_viewports = number_of_viewports;
+        self
+    }
+
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.parameters.vsync = vsync;
+        self
+    }
+
+    pub fn msaa(mut self, msaa: MSAA) -> Self {
+        self.parameters.msaa = msaa;
+        self
+    }
+
+    pub fn anisotropic_filtering(mut self, anisotropic_filtering: Option<f32>) -> Self {
+        self.parameters.anisotropic_filtering = anisotropic_filtering;
+        self
+    }
+
+    pub fn render_scale(mut self, render_scale: f32) -> Self {
+        self.parameters.render_scale = render_scale;
+        self
+    }
+
+    pub fn clamp_to_device(mut self, clamp_to_device: bool) -> Self {
+        self.parameters.clamp_to_device = clamp_to_device;
+        self
+    }
+
+    pub fn ssao(mut self, ssao: bool) -> Self {
+        self.parameters.ssao = ssao;
+        self
+    }
+
+    pub fn water_reflections(mut self, water_reflections: bool) -> Self {
+        self.parameters.water_reflections = water_reflections;
+        self
+    }
+
+    pub fn depth_of_field(mut self, depth_of_field: bool) -> Self {
+        self.parameters.depth_of_field = depth_of_field;
+        self
+    }
+
+    pub fn motion_blur(mut self, motion_blur: bool) -> Self {
+        self.parameters.motion_blur = motion_blur;
+        self
+    }
+
+    pub fn taa(mut self, taa: bool) -> Self {
+        self.parameters.taa = taa;
+        self
+    }
+
+    pub fn post_processing(mut self, post_processing: bool) -> Self {
+        self.parameters.post_processing = post_processing;
+        self
+    }
+
+    pub fn skipped_mip_levels(mut self, skipped_mip_levels: u32) -> Self {
+        self.parameters.skipped_mip_levels = skipped_mip_levels;
+        self
+    }
+
+    /// Set the parameters covered by `quality_preset`.
+    ///
+    /// See [`QualityPreset::apply`] for which parameters are set.
+    pub fn quality_preset(mut self, quality_preset: QualityPreset) -> Self {
+        self.parameters = quality_preset.apply(self.parameters);
+        self
+    }
+
+    /// Finish building the parameters.
+    ///
+    /// Errors if:
+    /// - `resolution` has 0 on one or more dimensions
+    /// - `number_of_viewports` is not 1-4
+    /// - `anisotropic_filtering` is less than 1
+    /// - `render_scale` is not greater than 0
+    pub fn build(self) -> MResult<RendererParameters> {
+        self.parameters.validate()?;
+        Ok(self.parameters)
     }
 }
 
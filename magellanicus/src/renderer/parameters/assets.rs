@@ -0,0 +1,43 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::renderer::{AddBitmapParameter, AddBSPParameter, AddFontParameter, AddShaderParameter, AddSkyParameter};
+
+/// Assets to add at once with [`Renderer::add_assets`](crate::renderer::Renderer::add_assets).
+///
+/// Assets can be given in any order, as they are added such that dependencies are loaded first.
+#[derive(Default, Clone)]
+pub struct AddAssetsParameter {
+    pub bitmaps: Vec<(String, AddBitmapParameter)>,
+    pub fonts: Vec<(String, AddFontParameter)>,
+    pub shaders: Vec<(String, AddShaderParameter)>,
+    pub skies: Vec<(String, AddSkyParameter)>,
+    pub bsps: Vec<(String, AddBSPParameter)>,
+
+    /// Number of threads to validate bitmaps on before they are uploaded.
+    ///
+    /// Uploading always happens on the calling thread. If 0 or 1, everything is done on the calling thread.
+    pub worker_threads: usize
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AssetType {
+    Bitmap,
+    Font,
+    Shader,
+    Sky,
+    BSP
+}
+
+/// Passed to the progress callback of [`Renderer::add_assets`](crate::renderer::Renderer::add_assets) before each
+/// asset is added.
+#[derive(Copy, Clone, Debug)]
+pub struct AddAssetsProgress<'a> {
+    pub asset_type: AssetType,
+    pub path: &'a str,
+
+    /// Number of assets added so far.
+    pub completed: usize,
+
+    /// Number of assets in the batch.
+    pub total: usize
+}
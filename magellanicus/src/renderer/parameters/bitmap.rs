@@ -1,6 +1,10 @@
 pub(crate) mod mipmap_iterator;
 use core::num::NonZeroUsize;
-use crate::error::{Error, MResult};
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::error::{BitmapError, MResult};
 use crate::renderer::parameters::bitmap::mipmap_iterator::{MipmapFaceIterator, MipmapType};
 use crate::renderer::Resolution;
 
@@ -13,10 +17,10 @@ pub struct AddBitmapParameter {
 impl AddBitmapParameter {
     pub(crate) fn validate(&self) -> MResult<()> {
         if self.sequences.is_empty() {
-            return Err(Error::from_data_error_string("Bitmap has no sequences!".to_owned()))
+            return Err(BitmapError::Invalid { reason: "Bitmap has no sequences!".to_owned() }.into())
         }
         if self.bitmaps.is_empty() {
-            return Err(Error::from_data_error_string("Bitmap has no bitmaps!".to_owned()))
+            return Err(BitmapError::Invalid { reason: "Bitmap has no bitmaps!".to_owned() }.into())
         }
 
         let invalid_sequence_error = self.sequences
@@ -49,81 +53,43 @@ impl AddBitmapParameter {
             });
 
         if let Some(error) = invalid_sequence_error {
-            return Err(Error::DataError { error })
+            return Err(BitmapError::Invalid { reason: error }.into())
         }
 
         let invalid_bitmap_error = self.bitmaps
             .iter()
             .enumerate()
-            .find_map(|(bitmap_index, bitmap)| {
-                let Resolution { width, height } = bitmap.resolution;
-                let reported_mipmap_count = bitmap.mipmap_count;
-
-                let (Some(width_nz), Some(height_nz)) = (NonZeroUsize::new(width as usize), NonZeroUsize::new(height as usize)) else {
-                    return Some(format!("Bitmap #{bitmap_index} has 0 on one or more dimensions ({width}x{height})"))
-                };
-                if bitmap.data.is_empty() {
-                    return Some(format!("Bitmap #{bitmap_index} has no pixel data"))
-                }
-
-                // Block length
-                let block_length = NonZeroUsize::new(bitmap.format.block_pixel_length()).unwrap();
-
-                // Get mipmap type
-                let mipmap_type = match bitmap.bitmap_type {
-                    BitmapType::Dim2D => MipmapType::TwoDimensional,
-                    BitmapType::Dim3D { depth } => match NonZeroUsize::new(depth as usize) {
-                        Some(n) => MipmapType::ThreeDimensional(n),
-                        None => return Some(format!("Bitmap #{bitmap_index} has a depth of 0"))
-                    },
-                    BitmapType::Cubemap => MipmapType::Cubemap
-                };
-
-                let highest_dimension = width.max(height).max(match bitmap.bitmap_type { BitmapType::Dim3D { depth } => depth, _ => 1 });
-                let log_of_highest_dim = highest_dimension.ilog2();
-                let highest_possible_mipmap_count = if highest_dimension == (1 << log_of_highest_dim) {
-                    log_of_highest_dim - 0
-                }
-                else {
-                    log_of_highest_dim + 1
-                };
-
-                if reported_mipmap_count > highest_possible_mipmap_count {
-                    return Some(format!("Bitmap #{bitmap_index} ({width}x{height}) reports a mipmap count of {reported_mipmap_count}, but the highest mipmap count possible is {highest_possible_mipmap_count}"))
-                }
-
-                let mipmaps = MipmapFaceIterator::new(
-                    width_nz, height_nz, mipmap_type, block_length, Some(reported_mipmap_count as usize)
-                );
-
-                let bytes_per_block = bitmap.format.block_byte_size();
-                let Some((block_count, bytes_count)) = mipmaps
-                    .map(|b| (b.block_count as u64, b.block_count as u64 * bytes_per_block as u64))
-                    .reduce(|a, b| (a.0 + b.0, a.1 + b.1))
-                    .and_then(|(a,b)| Some((usize::try_from(a).ok()?, usize::try_from(b).ok()?)))
-                else {
-                    return Some(format!("Bitmap #{bitmap_index} can't get block count"))
-                };
-
-                let actual_length = bitmap.data.len();
-                if bytes_count != actual_length {
-                    return Some(format!("Bitmap #{bitmap_index} ({width}x{height}) has an incorrect number of bytes (expected {bytes_count} ({block_count} * {bytes_per_block}), got {actual_length})"))
-                }
-
-                None
-            });
+            .find_map(|(bitmap_index, bitmap)| bitmap.find_error(bitmap_index));
 
         if let Some(error) = invalid_bitmap_error {
-            return Err(Error::DataError { error })
+            return Err(BitmapError::Invalid { reason: error }.into())
         }
 
         Ok(())
     }
 }
 
-pub use super::super::data::BitmapSequence as AddBitmapSequenceParameter;
-pub use super::super::data::BitmapSprite;
-pub use super::super::data::BitmapType;
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BitmapType {
+    Dim2D,
+    Dim3D { depth: u32 },
+    Cubemap
+}
+
+#[derive(Clone)]
+pub enum AddBitmapSequenceParameter {
+    Bitmap { first: usize, count: usize },
+    Sprites { sprites: Vec<BitmapSprite> }
+}
+
+#[derive(Clone)]
+pub struct BitmapSprite {
+    pub bitmap: usize,
+    pub top: f32,
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32
+}
 
 #[derive(Clone)]
 pub struct AddBitmapBitmapParameter {
@@ -134,6 +100,90 @@ pub struct AddBitmapBitmapParameter {
     pub data: Vec<u8>,
 }
 
+impl AddBitmapBitmapParameter {
+    /// Errors if the bitmap is invalid, describing it as bitmap #`bitmap_index`.
+    pub(crate) fn validate(&self, bitmap_index: usize) -> MResult<()> {
+        match self.find_error(bitmap_index) {
+            Some(error) => Err(BitmapError::Invalid { reason: error }.into()),
+            None => Ok(())
+        }
+    }
+
+    fn find_error(&self, bitmap_index: usize) -> Option<String> {
+        let Resolution { width, height } = self.resolution;
+        let reported_mipmap_count = self.mipmap_count;
+
+        let (Some(width_nz), Some(height_nz)) = (NonZeroUsize::new(width as usize), NonZeroUsize::new(height as usize)) else {
+            return Some(format!("Bitmap #{bitmap_index} has 0 on one or more dimensions ({width}x{height})"))
+        };
+        if self.data.is_empty() {
+            return Some(format!("Bitmap #{bitmap_index} has no pixel data"))
+        }
+
+        // Block length
+        let block_length = NonZeroUsize::new(self.format.block_pixel_length()).unwrap();
+
+        // Get mipmap type
+        let mipmap_type = match self.bitmap_type {
+            BitmapType::Dim2D => MipmapType::TwoDimensional,
+            BitmapType::Dim3D { depth } => match NonZeroUsize::new(depth as usize) {
+                Some(n) => MipmapType::ThreeDimensional(n),
+                None => return Some(format!("Bitmap #{bitmap_index} has a depth of 0"))
+            },
+            BitmapType::Cubemap => MipmapType::Cubemap
+        };
+
+        if self.bitmap_type == BitmapType::Cubemap && width != height {
+            return Some(format!("Bitmap #{bitmap_index} is a cubemap, but its faces are not square ({width}x{height})"))
+        }
+
+        // Each mipmap halves the dimensions (rounding down) until all of them are 1, so the largest dimension
+        // determines how many mipmaps there can be.
+        let highest_dimension = width.max(height).max(match self.bitmap_type { BitmapType::Dim3D { depth } => depth, _ => 1 });
+        let highest_possible_mipmap_count = highest_dimension.ilog2();
+
+        if reported_mipmap_count > highest_possible_mipmap_count {
+            return Some(format!("Bitmap #{bitmap_index} ({width}x{height}) reports a mipmap count of {reported_mipmap_count}, but the highest mipmap count possible is {highest_possible_mipmap_count}"))
+        }
+
+        let mipmaps = MipmapFaceIterator::new(
+            width_nz, height_nz, mipmap_type, block_length, Some(reported_mipmap_count as usize)
+        );
+
+        let format = self.format;
+        let bytes_per_block = format.block_byte_size() as u64;
+        let actual_length = self.data.len() as u64;
+
+        let mut bytes_count = 0u64;
+        let mut first_incomplete_face = None;
+        for face in mipmaps {
+            bytes_count += face.block_count as u64 * bytes_per_block;
+            if first_incomplete_face.is_none() && bytes_count > actual_length {
+                first_incomplete_face = Some((face.mipmap_index, face.face_index));
+            }
+        }
+
+        let description = match self.bitmap_type {
+            BitmapType::Dim2D => format!("Bitmap #{bitmap_index} ({width}x{height} {format:?}, {reported_mipmap_count} mipmap(s))"),
+            BitmapType::Dim3D { depth } => format!("Bitmap #{bitmap_index} ({width}x{height}x{depth} {format:?}, {reported_mipmap_count} mipmap(s))"),
+            BitmapType::Cubemap => format!("Bitmap #{bitmap_index} ({width}x{height} {format:?} cubemap with 6 faces, {reported_mipmap_count} mipmap(s))")
+        };
+        if let Some((mipmap_index, face_index)) = first_incomplete_face {
+            let location = match self.bitmap_type {
+                BitmapType::Dim2D => format!("mipmap #{mipmap_index}"),
+                BitmapType::Dim3D { .. } => format!("depth slice #{face_index} of mipmap #{mipmap_index}"),
+                BitmapType::Cubemap => format!("face #{face_index} of mipmap #{mipmap_index}")
+            };
+            return Some(format!("{description} needs {bytes_count} bytes, but only {actual_length} were given; data ends partway through {location}"))
+        }
+        if bytes_count != actual_length {
+            return Some(format!("{description} needs {bytes_count} bytes, but {actual_length} were given ({} extra)", actual_length - bytes_count))
+        }
+
+        None
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum BitmapFormat {
     DXT1,
@@ -1,9 +1,21 @@
+mod sanitize;
+mod tangents;
+
+pub use sanitize::MeshSanitizationReport;
+pub use tangents::TangentFrameRecomputation;
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::vec;
+use alloc::string::String;
+use alloc::vec::Vec;
 use glam::Vec3;
-use crate::error::{Error, MResult};
-use crate::renderer::data::{Bitmap, Shader, ShaderType};
-use crate::renderer::Renderer;
-use crate::vertex::{LightmapVertex, ModelTriangle, ModelVertex};
+use crate::error::{BSPError, MResult};
+use crate::renderer::ShaderType;
+use crate::renderer::checker::AssetLookup;
+use crate::vertex::{CompressedLightmapVertex, CompressedModelVertex, LightmapVertex, ModelTriangle, ModelVertex};
 
+#[derive(Clone)]
 pub struct AddBSPParameter {
     /// Path to the bitmap.
     ///
@@ -17,6 +29,7 @@ pub struct AddBSPParameter {
     pub bsp_data: BSPData
 }
 
+#[derive(Clone)]
 pub struct AddBSPParameterLightmapSet {
     /// The bitmap index of the lightmap.
     ///
@@ -29,14 +42,15 @@ pub struct AddBSPParameterLightmapSet {
     pub materials: Vec<AddBSPParameterLightmapMaterial>
 }
 
+#[derive(Clone)]
 pub struct AddBSPParameterLightmapMaterial {
     /// Describes pipeline vertices.
-    pub shader_vertices: Vec<ModelVertex>,
+    pub shader_vertices: AddBSPParameterVertices,
 
     /// Describes lightmap vertices.
     ///
     /// Must be None or have the same length as `vertices`
-    pub lightmap_vertices: Option<Vec<LightmapVertex>>,
+    pub lightmap_vertices: Option<AddBSPParameterLightmapVertices>,
 
     /// Describes each triangle.
     pub surfaces: Vec<ModelTriangle>,
@@ -48,13 +62,99 @@ pub struct AddBSPParameterLightmapMaterial {
     pub centroid: [f32; 3]
 }
 
+/// Pipeline vertices of a BSP material, either uncompressed or as stored in compressed BSPs.
+///
+/// Compressed vertices are decompressed when the BSP is uploaded.
+#[derive(Clone)]
+pub enum AddBSPParameterVertices {
+    Uncompressed(Vec<ModelVertex>),
+    Compressed(Vec<CompressedModelVertex>)
+}
+
+impl AddBSPParameterVertices {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Uncompressed(v) => v.len(),
+            Self::Compressed(v) => v.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate through all vertices, decompressing them if needed.
+    pub fn iter(&self) -> impl Iterator<Item = ModelVertex> + '_ {
+        let (uncompressed, compressed) = match self {
+            Self::Uncompressed(v) => (Some(v.iter().copied()), None),
+            Self::Compressed(v) => (None, Some(v.iter().map(|v| ModelVertex::from(*v))))
+        };
+        uncompressed.into_iter().flatten().chain(compressed.into_iter().flatten())
+    }
+}
+
+impl From<Vec<ModelVertex>> for AddBSPParameterVertices {
+    fn from(value: Vec<ModelVertex>) -> Self {
+        Self::Uncompressed(value)
+    }
+}
+
+impl From<Vec<CompressedModelVertex>> for AddBSPParameterVertices {
+    fn from(value: Vec<CompressedModelVertex>) -> Self {
+        Self::Compressed(value)
+    }
+}
+
+/// Lightmap vertices of a BSP material, either uncompressed or as stored in compressed BSPs.
+///
+/// Compressed vertices are decompressed when the BSP is uploaded.
+#[derive(Clone)]
+pub enum AddBSPParameterLightmapVertices {
+    Uncompressed(Vec<LightmapVertex>),
+    Compressed(Vec<CompressedLightmapVertex>)
+}
+
+impl AddBSPParameterLightmapVertices {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Uncompressed(v) => v.len(),
+            Self::Compressed(v) => v.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate through all vertices, decompressing them if needed.
+    pub fn iter(&self) -> impl Iterator<Item = LightmapVertex> + '_ {
+        let (uncompressed, compressed) = match self {
+            Self::Uncompressed(v) => (Some(v.iter().copied()), None),
+            Self::Compressed(v) => (None, Some(v.iter().map(|v| LightmapVertex::from(*v))))
+        };
+        uncompressed.into_iter().flatten().chain(compressed.into_iter().flatten())
+    }
+}
+
+impl From<Vec<LightmapVertex>> for AddBSPParameterLightmapVertices {
+    fn from(value: Vec<LightmapVertex>) -> Self {
+        Self::Uncompressed(value)
+    }
+}
+
+impl From<Vec<CompressedLightmapVertex>> for AddBSPParameterLightmapVertices {
+    fn from(value: Vec<CompressedLightmapVertex>) -> Self {
+        Self::Compressed(value)
+    }
+}
+
 impl AddBSPParameter {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
-        let lightmap_bitmap: Option<(&Bitmap, &str)> = if let Some(path) = self.lightmap_bitmap.as_ref() {
-            let Some(bitmap) = renderer.bitmaps.get(path) else {
-                return Err(Error::from_data_error_string(format!("BSP refers to lightmap bitmap {path} which is not loaded in the renderer")))
+    pub(crate) fn validate(&self, assets: &impl AssetLookup) -> MResult<()> {
+        let lightmap_bitmap: Option<(usize, &str)> = if let Some(path) = self.lightmap_bitmap.as_ref() {
+            let Some(bitmap_types) = assets.bitmap_types(path) else {
+                return Err(BSPError::BitmapNotLoaded { path: path.clone() }.into())
             };
-            Some((bitmap, path))
+            Some((bitmap_types.len(), path))
         }
         else {
             None
@@ -62,40 +162,46 @@ impl AddBSPParameter {
 
         for (lightmap_index, lightmap) in self.lightmap_sets.iter().enumerate() {
             if let Some(bitmap_index) = lightmap.lightmap_index {
-                let Some((bitmap, path)) = lightmap_bitmap else {
-                    return Err(Error::from_data_error_string(format!("BSP lightmap #{lightmap_index} has a bitmap index, but no lightmap bitmap is set")))
+                let Some((bitmap_count, path)) = lightmap_bitmap else {
+                    return Err(BSPError::Invalid { reason: format!("BSP lightmap #{lightmap_index} has a bitmap index, but no lightmap bitmap is set") }.into())
                 };
-                let bitmap_count = bitmap.bitmaps.len();
                 if bitmap_index >= bitmap_count {
-                    return Err(Error::from_data_error_string(format!("BSP lightmap #{lightmap_index} refers to bitmap #{bitmap_index}, but the referenced bitmap {path} has only {bitmap_count} bitmap(s)")))
+                    return Err(BSPError::Invalid { reason: format!("BSP lightmap #{lightmap_index} refers to bitmap #{bitmap_index}, but the referenced bitmap {path} has only {bitmap_count} bitmap(s)") }.into())
                 }
             }
 
             for (material_index, material) in lightmap.materials.iter().enumerate() {
                 let vertex_count = material.shader_vertices.len();
+                if let Some((triangle_index, triangle)) = material.surfaces.iter().enumerate().find(|(_, t)| t.indices.iter().any(|i| *i as usize >= vertex_count)) {
+                    let [a, b, c] = triangle.indices;
+                    return Err(BSPError::Invalid { reason: format!("BSP material #{material_index} of lightmap #{lightmap_index} has triangle #{triangle_index} ({a},{b},{c}) referring to a vertex that does not exist (there are {vertex_count} vertices)") }.into())
+                }
                 if let Some(lightmap_vertex_count) = material.lightmap_vertices.as_ref().map(|v| v.len()) {
                     if lightmap_vertex_count != vertex_count {
-                        return Err(Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} has a pipeline vertex count of {vertex_count}, but a lightmap vertex count of {lightmap_vertex_count}")))
+                        return Err(BSPError::Invalid { reason: format!("BSP material #{material_index} of lightmap #{lightmap_index} has a pipeline vertex count of {vertex_count}, but a lightmap vertex count of {lightmap_vertex_count}") }.into())
                     }
                     if lightmap_bitmap.is_none() {
-                        return Err(Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} has lightmap vertices when no lightmap bitmap is set")))
+                        return Err(BSPError::Invalid { reason: format!("BSP material #{material_index} of lightmap #{lightmap_index} has lightmap vertices when no lightmap bitmap is set") }.into())
                     }
                 }
 
                 let shader_path = &material.shader;
-                let Some(Shader { shader_type, .. }) = renderer.shaders.get(shader_path) else {
-                    return Err(Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} references pipeline {shader_path} which is not loaded")))
+                let Some(shader_type) = assets.shader_type(shader_path) else {
+                    if assets.has_fallback_shader() {
+                        continue
+                    }
+                    return Err(BSPError::ShaderNotLoaded { path: shader_path.clone() }.into())
                 };
 
                 // No reason we can't actually render this on a BSP, but these tags are intended to
                 // only be rendered on objects.
-                if *shader_type == ShaderType::Model {
-                    return Err(Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} references pipeline {shader_path}, a {shader_type:?} type which isn't allowed for BSPs")))
+                if shader_type == ShaderType::Model {
+                    return Err(BSPError::Invalid { reason: format!("BSP material #{material_index} of lightmap #{lightmap_index} references pipeline {shader_path}, a {shader_type:?} type which isn't allowed for BSPs") }.into())
                 }
             }
         }
 
-        self.bsp_data.validate(renderer, self)?;
+        self.bsp_data.validate(assets, self)?;
 
         Ok(())
     }
@@ -107,7 +213,8 @@ pub struct BSPData {
     pub planes: Vec<BSP3DPlane>,
     pub leaves: Vec<BSPLeaf>,
     pub clusters: Vec<BSPCluster>,
-    pub portals: Vec<BSPPortal>
+    pub portals: Vec<BSPPortal>,
+    pub fog_planes: Vec<BSPFogPlane>
 }
 
 impl Default for BSPData {
@@ -117,7 +224,8 @@ impl Default for BSPData {
             planes: vec![BSP3DPlane { angle: [0.0, 1.0, 0.0], offset: 0.0 }],
             leaves: Vec::new(),
             clusters: Vec::new(),
-            portals: Vec::new()
+            portals: Vec::new(),
+            fog_planes: Vec::new()
         }
     }
 }
@@ -138,7 +246,10 @@ pub struct BSPLeaf {
 pub struct BSPCluster {
     pub sky: Option<String>,
     pub subclusters: Vec<BSPSubcluster>,
-    pub cluster_portals: Vec<usize>
+    pub cluster_portals: Vec<usize>,
+
+    /// Index of the fog plane of the fog that the cluster is in, if any.
+    pub fog_plane: Option<usize>
 }
 
 #[derive(Clone, Debug)]
@@ -155,6 +266,63 @@ pub struct BSPPortal {
 }
 
 
+/// Fog that fills everything behind a plane, such as ground fog in a valley.
+#[derive(Copy, Clone, Debug)]
+pub struct BSPFogPlane {
+    /// Surface of the fog, where everything behind the plane (i.e. opposite of the way its normal faces) is fogged.
+    ///
+    /// The normal does not need to be normalized, but it must not be zero.
+    pub plane: BSP3DPlane,
+
+    /// Color in RGB.
+    pub color: [f32; 3],
+
+    /// Maximum opacity of the fog (from 0.0 - 1.0).
+    pub max_density: f32,
+
+    /// Distance a ray has to travel through fully dense fog for the fog to reach `max_density`.
+    pub opaque_distance: f32,
+
+    /// Depth behind the plane at which the fog is fully dense.
+    ///
+    /// The fog thins out linearly toward the plane, so rays that skim the surface are barely fogged.
+    pub opaque_depth: f32
+}
+
+impl BSPFogPlane {
+    /// Get how far `position` is behind the plane, which is negative if it is in front of it.
+    pub fn depth_of(&self, position: Vec3) -> f32 {
+        let normal = Vec3::from(self.plane.angle);
+        (self.plane.offset - position.dot(normal)) / normal.length()
+    }
+
+    /// Get how dense the fog is at `position`, from 0.0 (outside of the fog) to `max_density` (at or below
+    /// `opaque_depth`).
+    pub fn density_at(&self, position: Vec3) -> f32 {
+        (self.depth_of(position) / self.opaque_depth).clamp(0.0, 1.0) * self.max_density
+    }
+
+    fn validate(&self) -> MResult<()> {
+        let normal = Vec3::from(self.plane.angle);
+        if !normal.is_finite() || normal.length_squared() == 0.0 || !self.plane.offset.is_finite() {
+            return Err(BSPError::Invalid { reason: format!("Invalid fog plane {normal} {}", self.plane.offset) }.into())
+        }
+        if let Some(c) = self.color.iter().find(|c| **c < 0.0 || **c > 1.0 || !(**c).is_finite()) {
+            return Err(BSPError::Invalid { reason: format!("Invalid fog plane color channel value {c}") }.into())
+        }
+        if self.max_density < 0.0 || self.max_density > 1.0 || !self.max_density.is_finite() {
+            return Err(BSPError::Invalid { reason: format!("Invalid fog plane max density {}", self.max_density) }.into())
+        }
+        if self.opaque_distance <= 0.0 || !self.opaque_distance.is_finite() {
+            return Err(BSPError::Invalid { reason: format!("Invalid fog plane opaque distance {}", self.opaque_distance) }.into())
+        }
+        if self.opaque_depth <= 0.0 || !self.opaque_depth.is_finite() {
+            return Err(BSPError::Invalid { reason: format!("Invalid fog plane opaque depth {}", self.opaque_depth) }.into())
+        }
+        Ok(())
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum BSP3DNodeChild {
     Node(usize),
@@ -182,12 +350,11 @@ pub struct BSP3DPlane {
 }
 
 impl BSPData {
-    pub fn find_cluster(&self, position: [f32; 3]) -> Option<usize> {
+    pub fn find_cluster(&self, position: Vec3) -> Option<usize> {
         self.find_leaf(position).map(|l| self.leaves[l].cluster)
     }
 
-    pub fn find_leaf(&self, position: [f32; 3]) -> Option<usize> {
-        let position = Vec3::from(position);
+    pub fn find_leaf(&self, position: Vec3) -> Option<usize> {
         let mut node = self.nodes[0];
         loop {
             let plane = self.planes[node.plane];
@@ -208,9 +375,9 @@ impl BSPData {
         }
     }
 
-    fn validate(&self, renderer: &Renderer, full_parameter: &AddBSPParameter) -> MResult<()> {
+    fn validate(&self, assets: &impl AssetLookup, full_parameter: &AddBSPParameter) -> MResult<()> {
         if self.nodes.is_empty() {
-            return Err(Error::from_data_error_string("No nodes present".to_owned()))
+            return Err(BSPError::Invalid { reason: "No nodes present".to_owned() }.into())
         }
 
         let mut tested_nodes = vec![false; self.nodes.len()];
@@ -219,7 +386,7 @@ impl BSPData {
         }
         for (index, leaf) in self.leaves.iter().enumerate() {
             if leaf.cluster >= self.clusters.len() {
-                return Err(Error::from_data_error_string(format!("Leaf #{index} points to cluster #{} which does not exist", leaf.cluster)))
+                return Err(BSPError::Invalid { reason: format!("Leaf #{index} points to cluster #{} which does not exist", leaf.cluster) }.into())
             }
         }
 
@@ -233,25 +400,34 @@ impl BSPData {
 
         for (index, cluster) in self.clusters.iter().enumerate() {
             if let Some(sky) = cluster.sky.as_ref() {
-                if !renderer.skies.contains_key(sky) {
-                    return Err(Error::from_data_error_string(format!("Cluster #{index} points to sky {sky} which has not been loaded")))
+                if !assets.has_sky(sky) {
+                    return Err(BSPError::SkyNotLoaded { path: sky.clone() }.into())
                 }
             }
             for (sc_index, subcluster) in cluster.subclusters.iter().enumerate() {
                 if subcluster.surface_indices.iter().any(|i| *i >= total_surface_count) {
-                    return Err(Error::from_data_error_string(format!("Subcluster {sc_index} of cluster #{index} points to an out-of-bounds surface (there are {total_surface_count} surfaces)")))
+                    return Err(BSPError::Invalid { reason: format!("Subcluster {sc_index} of cluster #{index} points to an out-of-bounds surface (there are {total_surface_count} surfaces)") }.into())
+                }
+            }
+            if let Some(fog_plane) = cluster.fog_plane {
+                if fog_plane >= self.fog_planes.len() {
+                    return Err(BSPError::Invalid { reason: format!("Cluster #{index} points to fog plane #{fog_plane} which does not exist (there are {} fog planes)", self.fog_planes.len()) }.into())
                 }
             }
             for (p_index, _portal) in cluster.cluster_portals.iter().enumerate() {
                 if p_index >= self.portals.len() {
-                    return Err(Error::from_data_error_string(format!("Portal {p_index} of cluster #{index} points to an out-of-bounds portal (there are {} surfaces)", self.portals.len())))
+                    return Err(BSPError::Invalid { reason: format!("Portal {p_index} of cluster #{index} points to an out-of-bounds portal (there are {} surfaces)", self.portals.len()) }.into())
                 }
             }
         }
 
+        for fog_plane in &self.fog_planes {
+            fog_plane.validate()?;
+        }
+
         for (p_index, portal) in self.portals.iter().enumerate() {
             if portal.front_cluster >= self.clusters.len() || portal.back_cluster >= self.clusters.len() {
-                return Err(Error::from_data_error_string(format!("Portal {p_index} points to an out-of-bounds cluster (there are {} surfaces)", self.clusters.len())))
+                return Err(BSPError::Invalid { reason: format!("Portal {p_index} points to an out-of-bounds cluster (there are {} surfaces)", self.clusters.len()) }.into())
             }
         }
 
@@ -264,7 +440,7 @@ impl BSPData {
             return Ok(())
         }
         if remaining_tests == 0 {
-            return Err(Error::from_data_error_string("infinite loop detected when traversing nodes".to_owned()))
+            return Err(BSPError::Invalid { reason: "infinite loop detected when traversing nodes".to_owned() }.into())
         }
         remaining_tests -= 1;
 
@@ -289,13 +465,13 @@ impl BSPData {
         match child {
             BSP3DNodeChild::Node(n) => {
                 if n >= self.nodes.len() {
-                    return Err(Error::from_data_error_string(format!("broken BSP: node #{n}, referenced by node #{node}, does not exist")))
+                    return Err(BSPError::Invalid { reason: format!("broken BSP: node #{n}, referenced by node #{node}, does not exist") }.into())
                 }
                 self.validate_3d_node(n, remaining_tests, nodes_tested)?;
             }
             BSP3DNodeChild::Leaf(n) => {
                 if n >= self.leaves.len() {
-                    return Err(Error::from_data_error_string(format!("broken BSP: leaf #{n}, referenced by node #{node}, does not exist")))
+                    return Err(BSPError::Invalid { reason: format!("broken BSP: leaf #{n}, referenced by node #{node}, does not exist") }.into())
                 }
             }
         }
@@ -0,0 +1,331 @@
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use glam::Vec3;
+use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, AddBSPParameterLightmapVertices, AddBSPParameterVertices};
+use crate::vertex::{LightmapVertex, ModelTriangle, ModelVertex};
+
+/// Statistics of what was removed by [`AddBSPParameter::sanitize`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshSanitizationReport {
+    /// Triangles removed for referring to the same vertex more than once or for having no area.
+    pub degenerate_triangles_removed: usize,
+
+    /// Vertices removed for being identical to another vertex of the same material.
+    pub duplicate_vertices_removed: usize,
+
+    /// Vertices removed for not being used by any remaining triangle.
+    pub unused_vertices_removed: usize
+}
+
+impl AddBSPParameter {
+    /// Clean up the geometry of each material before adding the BSP.
+    ///
+    /// This merges identical vertices, removes degenerate triangles, and removes vertices that are no longer used.
+    /// Subclusters are updated to refer to the remaining surfaces. This is optional, but it avoids rendering artifacts
+    /// and wasted work from malformed tags.
+    ///
+    /// Materials with invalid geometry (e.g. out-of-bounds indices) are left as-is so that they are still reported
+    /// when the BSP is added.
+    pub fn sanitize(&mut self) -> MeshSanitizationReport {
+        let mut report = MeshSanitizationReport::default();
+
+        // Surface indices are global across all materials, so track where each one ends up.
+        let mut surface_remap: Vec<Option<usize>> = Vec::new();
+        let mut new_surface_count = 0;
+        for material in self.lightmap_sets.iter_mut().flat_map(|l| l.materials.iter_mut()) {
+            for kept in sanitize_material(material, &mut report) {
+                surface_remap.push(kept.then(|| {
+                    new_surface_count += 1;
+                    new_surface_count - 1
+                }));
+            }
+        }
+
+        let old_surface_count = surface_remap.len();
+        for subcluster in self.bsp_data.clusters.iter_mut().flat_map(|c| c.subclusters.iter_mut()) {
+            subcluster.surface_indices = subcluster
+                .surface_indices
+                .iter()
+                .filter_map(|i| match surface_remap.get(*i) {
+                    Some(new_index) => *new_index,
+
+                    // Keep out-of-bounds indices out-of-bounds so validation still catches them.
+                    None => Some(*i - old_surface_count + new_surface_count)
+                })
+                .collect();
+        }
+
+        report
+    }
+}
+
+/// Sanitize a material, returning whether each of its original triangles was kept.
+fn sanitize_material(material: &mut AddBSPParameterLightmapMaterial, report: &mut MeshSanitizationReport) -> Vec<bool> {
+    let vertex_count = material.shader_vertices.len();
+    let triangle_count = material.surfaces.len();
+
+    let out_of_bounds = material.surfaces.iter().any(|t| t.indices.iter().any(|i| *i as usize >= vertex_count));
+    let lightmap_mismatch = material.lightmap_vertices.as_ref().is_some_and(|l| l.len() != vertex_count);
+    if out_of_bounds || lightmap_mismatch {
+        return vec![true; triangle_count]
+    }
+
+    // Map each vertex to the first vertex identical to it.
+    let mut keys: Vec<Vec<u32>> = material.shader_vertices.iter().map(model_vertex_key).collect();
+    if let Some(lightmap_vertices) = material.lightmap_vertices.as_ref() {
+        for (key, vertex) in keys.iter_mut().zip(lightmap_vertices.iter()) {
+            key.extend(lightmap_vertex_key(vertex));
+        }
+    }
+    let mut first_occurrence: BTreeMap<Vec<u32>, usize> = BTreeMap::new();
+    let canonical: Vec<usize> = keys
+        .into_iter()
+        .enumerate()
+        .map(|(index, key)| *first_occurrence.entry(key).or_insert(index))
+        .collect();
+    let unique_count = first_occurrence.len();
+
+    let positions: Vec<Vec3> = material.shader_vertices.iter().map(|v| Vec3::from(v.position)).collect();
+    let kept_triangles: Vec<bool> = material
+        .surfaces
+        .iter()
+        .map(|t| {
+            let [a, b, c] = t.indices.map(|i| canonical[i as usize]);
+            if a == b || b == c || a == c {
+                return false
+            }
+            let (pa, pb, pc) = (positions[a], positions[b], positions[c]);
+            (pb - pa).cross(pc - pa).length_squared() > 0.0
+        })
+        .collect();
+
+    // Assign new indices to the vertices that are still used, preserving their order.
+    let mut used = vec![false; vertex_count];
+    for (triangle, _) in material.surfaces.iter().zip(&kept_triangles).filter(|(_, kept)| **kept) {
+        for i in triangle.indices {
+            used[canonical[i as usize]] = true;
+        }
+    }
+    let mut new_index = vec![0u32; vertex_count];
+    let mut kept_vertices = Vec::with_capacity(unique_count);
+    for (old_index, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        new_index[old_index] = kept_vertices.len() as u32;
+        kept_vertices.push(old_index);
+    }
+
+    material.surfaces = material
+        .surfaces
+        .iter()
+        .zip(&kept_triangles)
+        .filter(|(_, kept)| **kept)
+        .map(|(t, _)| ModelTriangle { indices: t.indices.map(|i| new_index[canonical[i as usize]]) })
+        .collect();
+
+    material.shader_vertices = match &material.shader_vertices {
+        AddBSPParameterVertices::Uncompressed(v) => AddBSPParameterVertices::Uncompressed(select(v, &kept_vertices)),
+        AddBSPParameterVertices::Compressed(v) => AddBSPParameterVertices::Compressed(select(v, &kept_vertices))
+    };
+    material.lightmap_vertices = material.lightmap_vertices.as_ref().map(|l| match l {
+        AddBSPParameterLightmapVertices::Uncompressed(v) => AddBSPParameterLightmapVertices::Uncompressed(select(v, &kept_vertices)),
+        AddBSPParameterLightmapVertices::Compressed(v) => AddBSPParameterLightmapVertices::Compressed(select(v, &kept_vertices))
+    });
+
+    report.degenerate_triangles_removed += triangle_count - material.surfaces.len();
+    report.duplicate_vertices_removed += vertex_count - unique_count;
+    report.unused_vertices_removed += unique_count - kept_vertices.len();
+
+    kept_triangles
+}
+
+fn select<T: Copy>(items: &[T], indices: &[usize]) -> Vec<T> {
+    indices.iter().map(|i| items[*i]).collect()
+}
+
+/// Bit patterns of all of a vertex's fields, so that identical vertices (and only identical vertices) are equal.
+fn model_vertex_key(vertex: ModelVertex) -> Vec<u32> {
+    [vertex.position, vertex.normal, vertex.binormal, vertex.tangent]
+        .iter()
+        .flatten()
+        .chain(vertex.texture_coords.iter())
+        .map(|f| f.to_bits())
+        .collect()
+}
+
+fn lightmap_vertex_key(vertex: LightmapVertex) -> impl Iterator<Item = u32> {
+    vertex.lightmap_texture_coords.into_iter().map(|f| f.to_bits())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::ToOwned;
+    use crate::renderer::{AddBSPParameterLightmapSet, BSPCluster, BSPData, BSPSubcluster};
+
+    fn vertex(x: f32, y: f32) -> ModelVertex {
+        ModelVertex {
+            position: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            binormal: [0.0, 1.0, 0.0],
+            tangent: [1.0, 0.0, 0.0],
+            texture_coords: [x, y]
+        }
+    }
+
+    fn material(vertices: Vec<ModelVertex>, triangles: &[[u32; 3]]) -> AddBSPParameterLightmapMaterial {
+        AddBSPParameterLightmapMaterial {
+            shader_vertices: AddBSPParameterVertices::Uncompressed(vertices),
+            lightmap_vertices: None,
+            surfaces: triangles.iter().map(|indices| ModelTriangle { indices: *indices }).collect(),
+            shader: "environment.shader".to_owned(),
+            centroid: [0.0; 3]
+        }
+    }
+
+    fn bsp(materials: Vec<AddBSPParameterLightmapMaterial>, surface_indices: Vec<usize>) -> AddBSPParameter {
+        AddBSPParameter {
+            lightmap_bitmap: None,
+            lightmap_sets: vec![AddBSPParameterLightmapSet { lightmap_index: None, materials }],
+            bsp_data: BSPData {
+                nodes: Vec::new(),
+                planes: Vec::new(),
+                leaves: Vec::new(),
+                clusters: vec![BSPCluster {
+                    sky: None,
+                    subclusters: vec![BSPSubcluster { surface_indices, world_bounds_from: [-1.0; 3], world_bounds_to: [1.0; 3] }],
+                    cluster_portals: Vec::new(),
+                    fog_plane: None
+                }],
+                portals: Vec::new(),
+                fog_planes: Vec::new()
+            }
+        }
+    }
+
+    fn sanitize(material: AddBSPParameterLightmapMaterial) -> (AddBSPParameterLightmapMaterial, MeshSanitizationReport) {
+        let mut bsp = bsp(vec![material], Vec::new());
+        let report = bsp.sanitize();
+        (bsp.lightmap_sets.remove(0).materials.remove(0), report)
+    }
+
+    fn positions(material: &AddBSPParameterLightmapMaterial) -> Vec<[f32; 3]> {
+        material.shader_vertices.iter().map(|v| v.position).collect()
+    }
+
+    fn triangles(material: &AddBSPParameterLightmapMaterial) -> Vec<[u32; 3]> {
+        material.surfaces.iter().map(|t| t.indices).collect()
+    }
+
+    #[test]
+    fn clean_geometry_is_unchanged() {
+        let (material, report) = sanitize(material(vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)], &[[0, 1, 2]]));
+        assert_eq!(report, MeshSanitizationReport::default());
+        assert_eq!(positions(&material), [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(triangles(&material), [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn duplicate_vertices_are_merged() {
+        let vertices = vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0), vertex(1.0, 0.0), vertex(1.0, 1.0)];
+        let (material, report) = sanitize(material(vertices, &[[0, 1, 2], [3, 4, 2]]));
+        assert_eq!(report, MeshSanitizationReport { duplicate_vertices_removed: 1, ..Default::default() });
+        assert_eq!(positions(&material), [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]]);
+        assert_eq!(triangles(&material), [[0, 1, 2], [1, 3, 2]]);
+    }
+
+    #[test]
+    fn vertices_with_different_lightmap_coords_are_not_merged() {
+        let mut material = material(vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0), vertex(0.0, 1.0)], &[[0, 1, 2], [0, 1, 3]]);
+        material.lightmap_vertices = Some(AddBSPParameterLightmapVertices::Uncompressed(vec![
+            LightmapVertex { lightmap_texture_coords: [0.0, 0.0] },
+            LightmapVertex { lightmap_texture_coords: [1.0, 0.0] },
+            LightmapVertex { lightmap_texture_coords: [0.0, 1.0] },
+            LightmapVertex { lightmap_texture_coords: [0.0, 0.5] }
+        ]));
+
+        let (material, report) = sanitize(material);
+        assert_eq!(report, MeshSanitizationReport::default());
+        assert_eq!(positions(&material).len(), 4);
+        assert_eq!(triangles(&material), [[0, 1, 2], [0, 1, 3]]);
+    }
+
+    #[test]
+    fn triangles_repeating_a_vertex_are_removed() {
+        let (material, report) = sanitize(material(vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)], &[[0, 1, 2], [0, 1, 1]]));
+        assert_eq!(report, MeshSanitizationReport { degenerate_triangles_removed: 1, ..Default::default() });
+        assert_eq!(triangles(&material), [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn triangles_repeating_a_duplicate_vertex_are_removed() {
+        let vertices = vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0), vertex(1.0, 0.0)];
+        let (material, report) = sanitize(material(vertices, &[[0, 1, 2], [0, 1, 3]]));
+        assert_eq!(report, MeshSanitizationReport { degenerate_triangles_removed: 1, duplicate_vertices_removed: 1, ..Default::default() });
+        assert_eq!(triangles(&material), [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn triangles_without_area_are_removed() {
+        let vertices = vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0), vertex(2.0, 0.0)];
+        let (material, report) = sanitize(material(vertices, &[[0, 1, 3], [0, 1, 2]]));
+        assert_eq!(report, MeshSanitizationReport { degenerate_triangles_removed: 1, unused_vertices_removed: 1, ..Default::default() });
+        assert_eq!(positions(&material), [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(triangles(&material), [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn unused_vertices_are_removed_in_order() {
+        let vertices = vec![vertex(5.0, 5.0), vertex(0.0, 0.0), vertex(6.0, 6.0), vertex(1.0, 0.0), vertex(0.0, 1.0)];
+        let mut material = material(vertices, &[[1, 3, 4]]);
+        material.lightmap_vertices = Some(AddBSPParameterLightmapVertices::Uncompressed(
+            (0..5).map(|i| LightmapVertex { lightmap_texture_coords: [i as f32, 0.0] }).collect()
+        ));
+
+        let (material, report) = sanitize(material);
+        assert_eq!(report, MeshSanitizationReport { unused_vertices_removed: 2, ..Default::default() });
+        assert_eq!(positions(&material), [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(triangles(&material), [[0, 1, 2]]);
+
+        // Lightmap vertices are removed alongside their shader vertices.
+        let lightmap_coords: Vec<[f32; 2]> = material.lightmap_vertices.unwrap().iter().map(|v| v.lightmap_texture_coords).collect();
+        assert_eq!(lightmap_coords, [[1.0, 0.0], [3.0, 0.0], [4.0, 0.0]]);
+    }
+
+    #[test]
+    fn invalid_materials_are_left_as_is() {
+        let vertices = vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0), vertex(0.0, 1.0)];
+
+        let (out_of_bounds, report) = sanitize(material(vertices.clone(), &[[0, 1, 2], [0, 0, 4]]));
+        assert_eq!(report, MeshSanitizationReport::default());
+        assert_eq!(positions(&out_of_bounds).len(), 4);
+        assert_eq!(triangles(&out_of_bounds), [[0, 1, 2], [0, 0, 4]]);
+
+        let mut lightmap_mismatch = material(vertices, &[[0, 1, 2], [0, 0, 1]]);
+        lightmap_mismatch.lightmap_vertices = Some(AddBSPParameterLightmapVertices::Uncompressed(vec![LightmapVertex { lightmap_texture_coords: [0.0, 0.0] }]));
+        let (lightmap_mismatch, report) = sanitize(lightmap_mismatch);
+        assert_eq!(report, MeshSanitizationReport::default());
+        assert_eq!(positions(&lightmap_mismatch).len(), 4);
+        assert_eq!(triangles(&lightmap_mismatch), [[0, 1, 2], [0, 0, 1]]);
+    }
+
+    #[test]
+    fn subclusters_are_remapped_across_materials() {
+        let quad = || vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0), vertex(1.0, 1.0)];
+        let mut bsp = bsp(
+            vec![
+                // Surfaces 0 and 1; 0 is removed.
+                material(quad(), &[[0, 0, 1], [0, 1, 2]]),
+
+                // Surfaces 2 and 3; 3 is removed.
+                material(quad(), &[[1, 3, 2], [3, 3, 3]])
+            ],
+            vec![0, 1, 2, 3, 4, 10]
+        );
+
+        let report = bsp.sanitize();
+        assert_eq!(report.degenerate_triangles_removed, 2);
+
+        // Out-of-bounds indices stay out-of-bounds.
+        assert_eq!(bsp.bsp_data.clusters[0].subclusters[0].surface_indices, [0, 1, 2, 8]);
+    }
+}
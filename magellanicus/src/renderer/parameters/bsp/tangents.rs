@@ -0,0 +1,200 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use glam::{Vec2, Vec3};
+use crate::renderer::{AddBSPParameter, AddBSPParameterVertices};
+use crate::vertex::{ModelTriangle, ModelVertex};
+
+/// Which vertices [`AddBSPParameter::recompute_tangent_frames`] recomputes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum TangentFrameRecomputation {
+    /// Only recompute vertices whose tangent or binormal is zero, not finite, or parallel to the normal.
+    #[default]
+    BrokenOnly,
+
+    /// Recompute every vertex.
+    All
+}
+
+impl AddBSPParameter {
+    /// Recompute the tangents and binormals of vertices from their positions, normals, and texture coordinates.
+    ///
+    /// Some tags have zeroed or otherwise broken tangent frames, which breaks bump mapping. Recomputed vectors are
+    /// perpendicular to the normal, and the binormal keeps the handedness implied by the texture coordinates.
+    ///
+    /// Materials with compressed vertices that are changed are converted to uncompressed vertices. Materials with
+    /// out-of-bounds indices are skipped so that they are still reported when the BSP is added.
+    ///
+    /// Returns the number of vertices that were recomputed.
+    pub fn recompute_tangent_frames(&mut self, which: TangentFrameRecomputation) -> usize {
+        let mut recomputed = 0;
+        for material in self.lightmap_sets.iter_mut().flat_map(|l| l.materials.iter_mut()) {
+            let mut vertices: Vec<ModelVertex> = material.shader_vertices.iter().collect();
+            let vertex_count = vertices.len();
+            if material.surfaces.iter().any(|t| t.indices.iter().any(|i| *i as usize >= vertex_count)) {
+                continue
+            }
+
+            let count = recompute_tangent_frames(&mut vertices, &material.surfaces, which);
+            if count > 0 {
+                material.shader_vertices = AddBSPParameterVertices::Uncompressed(vertices);
+                recomputed += count;
+            }
+        }
+        recomputed
+    }
+}
+
+fn recompute_tangent_frames(vertices: &mut [ModelVertex], triangles: &[ModelTriangle], which: TangentFrameRecomputation) -> usize {
+    let needs_recomputation: Vec<bool> = vertices
+        .iter()
+        .map(|v| which == TangentFrameRecomputation::All || is_tangent_frame_broken(v))
+        .collect();
+    if !needs_recomputation.contains(&true) {
+        return 0
+    }
+
+    // Accumulate the direction of increasing U (tangent) and V (binormal) of each triangle onto its vertices. Larger
+    // triangles contribute more since the cross product isn't normalized.
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut binormals = vec![Vec3::ZERO; vertices.len()];
+    for triangle in triangles {
+        let [a, b, c] = triangle.indices.map(|i| i as usize);
+        let (va, vb, vc) = (&vertices[a], &vertices[b], &vertices[c]);
+
+        let edge1 = Vec3::from(vb.position) - Vec3::from(va.position);
+        let edge2 = Vec3::from(vc.position) - Vec3::from(va.position);
+        let uv1 = Vec2::from(vb.texture_coords) - Vec2::from(va.texture_coords);
+        let uv2 = Vec2::from(vc.texture_coords) - Vec2::from(va.texture_coords);
+
+        let determinant = uv1.x * uv2.y - uv2.x * uv1.y;
+        if determinant == 0.0 || !determinant.is_finite() {
+            continue
+        }
+        let r = 1.0 / determinant;
+        let tangent = (edge1 * uv2.y - edge2 * uv1.y) * r;
+        let binormal = (edge2 * uv1.x - edge1 * uv2.x) * r;
+
+        for i in [a, b, c] {
+            tangents[i] += tangent;
+            binormals[i] += binormal;
+        }
+    }
+
+    let mut recomputed = 0;
+    for (index, vertex) in vertices.iter_mut().enumerate() {
+        if !needs_recomputation[index] {
+            continue
+        }
+
+        let normal = Vec3::from(vertex.normal).try_normalize().unwrap_or(Vec3::Z);
+
+        // Gram-Schmidt orthogonalize against the normal, falling back to any perpendicular vector if the texture
+        // coordinates don't give a usable direction.
+        let tangent = (tangents[index] - normal * normal.dot(tangents[index]))
+            .try_normalize()
+            .unwrap_or_else(|| normal.any_orthonormal_vector());
+        let mut binormal = normal.cross(tangent);
+        if binormal.dot(binormals[index]) < 0.0 {
+            binormal = -binormal;
+        }
+
+        vertex.normal = normal.to_array();
+        vertex.tangent = tangent.to_array();
+        vertex.binormal = binormal.to_array();
+        recomputed += 1;
+    }
+    recomputed
+}
+
+fn is_tangent_frame_broken(vertex: &ModelVertex) -> bool {
+    let normal = Vec3::from(vertex.normal);
+    let tangent = Vec3::from(vertex.tangent);
+    let binormal = Vec3::from(vertex.binormal);
+
+    let is_unusable = |v: Vec3| -> bool {
+        !v.is_finite() || v.length_squared() < 1e-8 || normal.cross(v).length_squared() < 1e-8 * v.length_squared()
+    };
+    is_unusable(tangent) || is_unusable(binormal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit quad on the XY plane facing +Z, with texture coordinates `uv(x, y)` and the given tangent frame.
+    fn quad(uv: impl Fn(f32, f32) -> [f32; 2], tangent: [f32; 3], binormal: [f32; 3]) -> (Vec<ModelVertex>, Vec<ModelTriangle>) {
+        let vertices = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+            .map(|(x, y)| ModelVertex {
+                position: [x, y, 0.0],
+                normal: [0.0, 0.0, 1.0],
+                binormal,
+                tangent,
+                texture_coords: uv(x, y)
+            })
+            .to_vec();
+        let triangles = vec![ModelTriangle { indices: [0, 1, 2] }, ModelTriangle { indices: [1, 3, 2] }];
+        (vertices, triangles)
+    }
+
+    fn assert_orthonormal(vertex: &ModelVertex) {
+        let (normal, tangent, binormal) = (Vec3::from(vertex.normal), Vec3::from(vertex.tangent), Vec3::from(vertex.binormal));
+        for v in [normal, tangent, binormal] {
+            assert!((v.length() - 1.0).abs() < 1e-5, "{v} is not normalized");
+        }
+        assert!(normal.dot(tangent).abs() < 1e-5);
+        assert!(normal.dot(binormal).abs() < 1e-5);
+        assert!(tangent.dot(binormal).abs() < 1e-5);
+    }
+
+    #[test]
+    fn tangents_follow_texture_coords() {
+        let (mut vertices, triangles) = quad(|x, y| [x, y], [0.0; 3], [0.0; 3]);
+        assert_eq!(recompute_tangent_frames(&mut vertices, &triangles, TangentFrameRecomputation::All), 4);
+        for vertex in &vertices {
+            assert_orthonormal(vertex);
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+            assert_eq!(vertex.tangent, [1.0, 0.0, 0.0]);
+            assert_eq!(vertex.binormal, [0.0, 1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn mirrored_texture_coords_keep_their_handedness() {
+        let (mut vertices, triangles) = quad(|x, y| [-x, y], [0.0; 3], [0.0; 3]);
+        recompute_tangent_frames(&mut vertices, &triangles, TangentFrameRecomputation::All);
+        for vertex in &vertices {
+            assert_orthonormal(vertex);
+            assert_eq!(vertex.tangent, [-1.0, 0.0, 0.0]);
+            assert_eq!(vertex.binormal, [0.0, 1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn only_broken_frames_are_recomputed_by_default() {
+        // Rotated 90 degrees from what the texture coordinates imply, but not broken.
+        let (mut vertices, triangles) = quad(|x, y| [x, y], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]);
+        vertices[1].tangent = [0.0; 3];
+        vertices[2].binormal = [0.0, 0.0, 1.0];
+        vertices[3].tangent = [f32::NAN, 0.0, 0.0];
+
+        assert_eq!(recompute_tangent_frames(&mut vertices, &triangles, TangentFrameRecomputation::BrokenOnly), 3);
+        assert_eq!(vertices[0].tangent, [0.0, 1.0, 0.0]);
+        assert_eq!(vertices[0].binormal, [-1.0, 0.0, 0.0]);
+        for vertex in &vertices[1..] {
+            assert_orthonormal(vertex);
+            assert_eq!(vertex.tangent, [1.0, 0.0, 0.0]);
+            assert_eq!(vertex.binormal, [0.0, 1.0, 0.0]);
+        }
+
+        assert_eq!(recompute_tangent_frames(&mut vertices, &triangles, TangentFrameRecomputation::BrokenOnly), 0);
+    }
+
+    #[test]
+    fn unusable_texture_coords_still_give_an_orthonormal_frame() {
+        let (mut vertices, triangles) = quad(|_, _| [0.5, 0.5], [0.0; 3], [0.0; 3]);
+        assert_eq!(recompute_tangent_frames(&mut vertices, &triangles, TangentFrameRecomputation::All), 4);
+        for vertex in &vertices {
+            assert_orthonormal(vertex);
+        }
+    }
+}
@@ -1,8 +1,14 @@
+use alloc::format;
+use alloc::vec::Vec;
 use crate::error::{Error, MResult};
 
+#[derive(Clone)]
 pub struct AddFontParameter {
     pub characters: Vec<AddFontParameterCharacter>,
     pub line_height: u32,
+
+    /// Distance from the top of a line to its baseline.
+    pub ascending_height: u32,
 }
 
 impl AddFontParameter {
@@ -14,18 +20,23 @@ impl AddFontParameter {
     }
 }
 
+#[derive(Clone)]
 pub struct AddFontParameterCharacter {
     pub character: char,
     pub data: Vec<u8>,
     pub width: usize,
     pub height: usize,
-    pub advance_x: i32
+    pub advance_x: i32,
+
+    /// Position of the baseline origin within the character's bitmap, where (0, 0) is its top-left pixel.
+    pub origin_x: i32,
+    pub origin_y: i32
 }
 
 impl AddFontParameterCharacter {
     pub(crate) fn validate(&self) -> MResult<()> {
         if Some(self.data.len()) != self.width.checked_mul(self.height) {
-            return Err(Error::DataError { error: format!("width ({}) x height ({}) != data.len() ({})", self.width, self.height, self.data.len()) });
+            return Err(Error::from_data_error_string(format!("width ({}) x height ({}) != data.len() ({})", self.width, self.height, self.data.len())));
         }
         Ok(())
     }
@@ -1,6 +1,37 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use crate::vertex::ModelVertex;
 
-pub use crate::renderer::data::GeometryDetailData;
+#[derive(Copy, Clone, Debug)]
+pub struct GeometryDetailData<T: Sized + 'static> {
+    pub super_low: T,
+    pub low: T,
+    pub medium: T,
+    pub high: T,
+    pub super_high: T,
+}
+
+impl<T: Sized + 'static> GeometryDetailData<T> {
+    /// Returns an array of the data sorted from lowest to highest.
+    pub fn as_arr(&self) -> [&T; 5] {
+        [&self.super_low, &self.low, &self.medium, &self.high, &self.super_high]
+    }
+
+    /// Returns an array of mutable references to the data sorted from lowest to highest.
+    pub fn as_arr_mut(&mut self) -> [&mut T; 5] {
+        [&mut self.super_low, &mut self.low, &mut self.medium, &mut self.high, &mut self.super_high]
+    }
+
+    /// Return an iterator over the items, sorted from lowest to highest.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.as_arr().into_iter()
+    }
+
+    /// Return an iterator over mutable references to the items, sorted from lowest to highest.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.as_arr_mut().into_iter()
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct AddGeometryParameter {
@@ -22,7 +53,7 @@ pub struct AddGeometryParameterVertex {
 pub struct AddGeometryParameterPart {
     pub shader: String,
     pub vertices: Vec<AddGeometryParameterVertex>,
-    pub indices: Vec<u16>,
+    pub indices: Vec<u32>,
     pub centroid: [f32; 3],
     pub previous_filthy_part_index: Option<usize>,
     pub next_filthy_part_index: Option<usize>
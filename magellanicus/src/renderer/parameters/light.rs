@@ -0,0 +1,193 @@
+use core::f32::consts::{PI, TAU};
+use alloc::format;
+use alloc::string::String;
+use glam::{Mat3, Vec3};
+use crate::error::{BitmapError, Error, MResult};
+use crate::renderer::BitmapType;
+use crate::renderer::checker::AssetLookup;
+use crate::renderer::parameters::validate_light_color_and_power;
+use crate::math;
+
+/// Describes how a dynamic light emits light.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightType {
+    /// Emits light in every direction.
+    Point,
+
+    /// Emits light in a cone.
+    Spot {
+        /// Direction the light travels in.
+        ///
+        /// This does not need to be normalized, but it must not be zero.
+        direction: Vec3,
+
+        /// Angle from `direction` in radians, within which surfaces are fully lit.
+        inner_angle: f32,
+
+        /// Angle from `direction` in radians, past which surfaces are not lit at all.
+        ///
+        /// This must be at least `inner_angle` and less than PI.
+        outer_angle: f32
+    }
+}
+
+/// A light that can be added, moved, and removed at any time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddLightParameter {
+    pub light_type: LightType,
+
+    /// Position of the light in world space.
+    pub position: Vec3,
+
+    /// Color in RGB.
+    ///
+    /// If `color_animation` is set, this is the upper bound of the color.
+    pub color: [f32; 3],
+
+    /// Brightness of the light (0.0 or higher).
+    pub power: f32,
+
+    /// Distance from the light past which surfaces are not lit (greater than 0.0).
+    pub radius: f32,
+
+    /// Distance from the light within which surfaces are fully lit (0.0 to `radius`).
+    pub falloff_radius: f32,
+
+    /// Exponent of the attenuation curve `(1 - fade) ^ falloff`, where `fade` goes from 0.0 at `falloff_radius` to 1.0
+    /// at `radius` (0.0 or higher).
+    ///
+    /// At 0.0, surfaces are lit evenly up to `radius`.
+    pub falloff: f32,
+
+    /// Path to a cube map that is projected from the light, tinting it in each direction, if any.
+    ///
+    /// Only the first bitmap is used.
+    pub gel: Option<String>,
+
+    /// Orientation of `gel` in world space.
+    pub gel_rotation: Mat3,
+
+    /// Animates the color of the light, if set.
+    pub color_animation: Option<LightColorAnimation>
+}
+
+impl AddLightParameter {
+    pub(crate) fn validate(&self, assets: &impl AssetLookup) -> MResult<()> {
+        validate_light_color_and_power(self.color, self.power)?;
+        if !self.position.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid light position {}", self.position)))
+        }
+        if self.radius <= 0.0 || !self.radius.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid light radius {}", self.radius)))
+        }
+        if self.falloff_radius < 0.0 || self.falloff_radius > self.radius || !self.falloff_radius.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid light falloff radius {}", self.falloff_radius)))
+        }
+        if self.falloff < 0.0 || !self.falloff.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid light falloff {}", self.falloff)))
+        }
+        if let LightType::Spot { direction, inner_angle, outer_angle } = self.light_type {
+            if !direction.is_finite() || direction.length_squared() == 0.0 {
+                return Err(Error::from_data_error_string(format!("Invalid light direction {direction}")))
+            }
+            if inner_angle < 0.0 || !inner_angle.is_finite() {
+                return Err(Error::from_data_error_string(format!("Invalid light inner angle {inner_angle}")))
+            }
+            if outer_angle < inner_angle || outer_angle >= PI || !outer_angle.is_finite() {
+                return Err(Error::from_data_error_string(format!("Invalid light outer angle {outer_angle}")))
+            }
+        }
+        if let Some(gel) = self.gel.as_ref() {
+            let Some(bitmap_types) = assets.bitmap_types(gel) else {
+                return Err(BitmapError::NotLoaded { path: gel.clone() }.into())
+            };
+            if bitmap_types.first() != Some(&BitmapType::Cubemap) {
+                return Err(Error::from_data_error_string(format!("Light gel {gel} is not a cube map")))
+            }
+        }
+        if !self.gel_rotation.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid light gel rotation {}", self.gel_rotation)))
+        }
+        if let Some(animation) = self.color_animation.as_ref() {
+            animation.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Animates the color of a light between a lower bound and its color.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LightColorAnimation {
+    pub function: LightAnimationFunction,
+
+    /// Color in RGB when `function` is 0.0.
+    pub lower_color: [f32; 3],
+
+    /// Length of one cycle of `function` in seconds (greater than 0.0).
+    pub period: f32
+}
+
+impl LightColorAnimation {
+    fn validate(&self) -> MResult<()> {
+        validate_light_color_and_power(self.lower_color, 1.0)?;
+        if self.period <= 0.0 || !self.period.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid light color animation period {}", self.period)))
+        }
+        Ok(())
+    }
+
+    /// Get the color at `seconds` after the animation started, interpolated from `lower_color` to `upper_color`.
+    pub fn color_at(&self, upper_color: [f32; 3], seconds: f32) -> [f32; 3] {
+        let cycles = seconds / self.period;
+        let value = self.function.evaluate(math::fract(cycles), math::floor(cycles) as u32);
+        core::array::from_fn(|i| self.lower_color[i] + (upper_color[i] - self.lower_color[i]) * value)
+    }
+}
+
+/// Periodic function used to animate a light.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum LightAnimationFunction {
+    /// Always 1.0.
+    #[default]
+    One,
+
+    /// Always 0.0.
+    Zero,
+
+    /// Starts at 1.0, smoothly goes to 0.0 at the middle of the cycle, and then back to 1.0.
+    Cosine,
+
+    /// Goes linearly from 0.0 to 1.0 at the middle of the cycle, and then back to 0.0.
+    DiagonalWave,
+
+    /// Goes linearly from 0.0 to 1.0 over the cycle, and then jumps back to 0.0.
+    Slide,
+
+    /// Holds a pseudo-random value from 0.0 to 1.0 for each cycle.
+    Jitter,
+
+    /// 1.0 for the first tenth of the cycle and 0.0 otherwise.
+    Spark
+}
+
+impl LightAnimationFunction {
+    /// Evaluate the function at `phase` (0.0 to 1.0) into cycle number `cycle`.
+    pub fn evaluate(self, phase: f32, cycle: u32) -> f32 {
+        match self {
+            LightAnimationFunction::One => 1.0,
+            LightAnimationFunction::Zero => 0.0,
+            LightAnimationFunction::Cosine => 0.5 + 0.5 * math::cos(phase * TAU),
+            LightAnimationFunction::DiagonalWave => 1.0 - (phase * 2.0 - 1.0).abs(),
+            LightAnimationFunction::Slide => phase,
+            LightAnimationFunction::Jitter => {
+                // Hash the cycle so the same cycle always gets the same value.
+                let mut hash = cycle.wrapping_mul(0x9E3779B9);
+                hash ^= hash >> 16;
+                hash = hash.wrapping_mul(0x85EBCA6B);
+                hash ^= hash >> 13;
+                (hash & 0xFFFF) as f32 / 65535.0
+            },
+            LightAnimationFunction::Spark => if phase < 0.1 { 1.0 } else { 0.0 }
+        }
+    }
+}
@@ -1,35 +1,88 @@
-use crate::error::{Error, MResult};
-pub use crate::renderer::data::ShaderType;
-use crate::renderer::{BitmapType, Renderer};
-use crate::renderer::data::Bitmap;
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::error::{MResult, ShaderError};
+use crate::renderer::BitmapType;
+use crate::renderer::checker::AssetLookup;
 
 pub const MAX_SHADER_TRANSPARENT_CHICAGO_MAPS: usize = 4;
 
+#[derive(Clone)]
 pub struct AddShaderParameter {
-    pub data: AddShaderData
+    pub data: AddShaderData,
+
+    /// Layer that transparent geometry using this shader is sorted into (0 is the default).
+    ///
+    /// Transparent geometry in lower layers is drawn before (and thus behind) geometry in higher layers regardless of
+    /// distance, and geometry in the same layer is drawn from farthest to nearest. This has no effect on opaque
+    /// shaders.
+    pub sort_layer: i32
 }
 
 impl AddShaderParameter {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
+    pub(crate) fn validate(&self, assets: &impl AssetLookup) -> MResult<()> {
         match &self.data {
             AddShaderData::BasicShader(AddShaderBasicShaderData { bitmap, .. }) => {
                 if let Some(bitmap) = bitmap {
-                    if !renderer.bitmaps.contains_key(bitmap) {
-                        return Err(Error::DataError { error: format!("Referenced bitmap {bitmap} is not loaded.") })
+                    if assets.bitmap_types(bitmap).is_none() && !assets.has_fallback_bitmap(BitmapType::Dim2D) {
+                        return Err(ShaderError::BitmapNotLoaded { path: bitmap.clone() }.into())
                     }
                 }
             },
             AddShaderData::ShaderEnvironment(shader_data) => {
-                shader_data.validate(renderer)?;
+                shader_data.validate(assets)?;
             },
             AddShaderData::ShaderTransparentChicago(shader_data) => {
-                shader_data.validate(renderer)?;
+                shader_data.validate(assets)?;
             }
         }
         Ok(())
     }
+
+    /// Get the type of shader this parameter creates.
+    pub(crate) fn shader_type(&self) -> ShaderType {
+        match &self.data {
+            AddShaderData::BasicShader(s) => s.shader_type,
+            AddShaderData::ShaderEnvironment(_) => ShaderType::Environment,
+            AddShaderData::ShaderTransparentChicago(_) => ShaderType::TransparentChicago
+        }
+    }
+
+    /// Return `true` if the shader uses the bitmap at `path`.
+    pub(crate) fn references_bitmap(&self, path: &str) -> bool {
+        let path = Some(path);
+        match &self.data {
+            AddShaderData::BasicShader(shader_data) => shader_data.bitmap.as_deref() == path,
+            AddShaderData::ShaderEnvironment(shader_data) => [
+                &shader_data.base_map,
+                &shader_data.primary_detail_map,
+                &shader_data.secondary_detail_map,
+                &shader_data.micro_detail_map,
+                &shader_data.bump_map,
+                &shader_data.reflection_cube_map
+            ].into_iter().any(|b| b.as_deref() == path),
+            AddShaderData::ShaderTransparentChicago(shader_data) => shader_data.maps.iter().any(|m| m.bitmap.as_deref() == path)
+        }
+    }
+
+    /// Get the path of the shader's 2D base map, if any, along with how many times it repeats per texture coordinate.
+    pub(crate) fn base_map(&self) -> Option<(&str, [f32; 2])> {
+        match &self.data {
+            AddShaderData::BasicShader(shader_data) => Some((shader_data.bitmap.as_deref()?, [1.0, 1.0])),
+            AddShaderData::ShaderEnvironment(shader_data) => Some((shader_data.base_map.as_deref()?, [1.0, 1.0])),
+            AddShaderData::ShaderTransparentChicago(shader_data) => {
+                if shader_data.first_map_type != ShaderTransparentChicagoFirstMapType::Dim2D {
+                    return None
+                }
+                let map = shader_data.maps.first()?;
+                Some((map.bitmap.as_deref()?, map.uv_scale))
+            }
+        }
+    }
 }
 
+#[derive(Clone)]
 pub enum AddShaderData {
     /// Basic pipeline that just renders a single texture. This does not map to an actual tag group
     /// and is to be removed once all shaders are implemented
@@ -42,6 +95,7 @@ pub enum AddShaderData {
     ShaderTransparentChicago(AddShaderTransparentChicagoShaderData)
 }
 
+#[derive(Clone)]
 pub struct AddShaderBasicShaderData {
     pub bitmap: Option<String>,
     pub shader_type: ShaderType,
@@ -100,19 +154,40 @@ pub struct AddShaderEnvironmentShaderData {
     pub perpendicular_brightness: f32,
     pub parallel_color: [f32; 3],
     pub parallel_brightness: f32,
+
+    /// Brightness of the highlight of the sky's sun (0.0 or higher).
+    pub specular_brightness: f32,
+
+    /// Let the sun's highlight go past full brightness.
+    pub specular_overbright: bool,
+
+    /// Make the sun's highlight smaller and sharper.
+    pub specular_extra_shiny: bool,
+
+    /// Scale the sun's highlight by the lightmap so it does not show up in shadows.
+    pub specular_lightmap_is_specular: bool,
+
+    /// Reflect the scene in flat surfaces using this shader.
+    ///
+    /// Only the opaque BSP is reflected, and reflections are not reflected again.
+    pub mirror: bool,
 }
 impl AddShaderEnvironmentShaderData {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
-        check_bitmap(renderer, &self.base_map, BitmapType::Dim2D, "base map")?;
-        check_bitmap(renderer, &self.primary_detail_map, BitmapType::Dim2D, "primary detail map")?;
-        check_bitmap(renderer, &self.secondary_detail_map, BitmapType::Dim2D, "secondary detail map")?;
-        check_bitmap(renderer, &self.micro_detail_map, BitmapType::Dim2D, "micro detail map")?;
-        check_bitmap(renderer, &self.bump_map, BitmapType::Dim2D, "bump map")?;
-        check_bitmap(renderer, &self.reflection_cube_map, BitmapType::Cubemap, "reflection cube map")?;
+    pub(crate) fn validate(&self, assets: &impl AssetLookup) -> MResult<()> {
+        check_bitmap(assets, &self.base_map, BitmapType::Dim2D, "base map")?;
+        check_bitmap(assets, &self.primary_detail_map, BitmapType::Dim2D, "primary detail map")?;
+        check_bitmap(assets, &self.secondary_detail_map, BitmapType::Dim2D, "secondary detail map")?;
+        check_bitmap(assets, &self.micro_detail_map, BitmapType::Dim2D, "micro detail map")?;
+        check_bitmap(assets, &self.bump_map, BitmapType::Dim2D, "bump map")?;
+        check_bitmap(assets, &self.reflection_cube_map, BitmapType::Cubemap, "reflection cube map")?;
+        if self.specular_brightness < 0.0 || !self.specular_brightness.is_finite() {
+            return Err(ShaderError::Invalid { reason: format!("Invalid specular brightness {}", self.specular_brightness) }.into())
+        }
         Ok(())
     }
 }
 
+#[derive(Clone)]
 pub struct AddShaderTransparentChicagoShaderData {
     pub two_sided: bool,
     pub first_map_type: ShaderTransparentChicagoFirstMapType,
@@ -121,13 +196,13 @@ pub struct AddShaderTransparentChicagoShaderData {
 }
 
 impl AddShaderTransparentChicagoShaderData {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
+    pub(crate) fn validate(&self, assets: &impl AssetLookup) -> MResult<()> {
         if self.maps.len() > MAX_SHADER_TRANSPARENT_CHICAGO_MAPS {
-            return Err(Error::from_data_error_string(format!("Maximum number of maps ({MAX_SHADER_TRANSPARENT_CHICAGO_MAPS}) exceeded")))
+            return Err(ShaderError::Invalid { reason: format!("Maximum number of maps ({MAX_SHADER_TRANSPARENT_CHICAGO_MAPS}) exceeded") }.into())
         }
 
         if self.maps.is_empty() {
-            return Err(Error::from_data_error_string("No maps given...".to_owned()))
+            return Err(ShaderError::Invalid { reason: "No maps given...".to_owned() }.into())
         }
 
         for (index, map) in self.maps.iter().enumerate() {
@@ -138,7 +213,7 @@ impl AddShaderTransparentChicagoShaderData {
                 BitmapType::Cubemap
             };
 
-            check_bitmap(renderer, &map.bitmap, expected_type, &format!("map {index}"))?;
+            check_bitmap(assets, &map.bitmap, expected_type, &format!("map {index}"))?;
         }
 
         Ok(())
@@ -155,7 +230,7 @@ pub struct AddShaderTransparentChicagoShaderMap {
     pub alpha_replicate: bool
 }
 
-#[derive(PartialEq)]
+#[derive(Copy, Clone, PartialEq)]
 #[repr(u32)]
 pub enum ShaderTransparentChicagoFirstMapType {
     Dim2D,
@@ -164,6 +239,7 @@ pub enum ShaderTransparentChicagoFirstMapType {
     ViewerCenteredCubemap,
 }
 
+#[derive(Copy, Clone)]
 #[repr(u32)]
 pub enum ShaderTransparentChicagoFramebufferFunction {
     /// framebuffer.rgb = mix(framebuffer.rgb, pixel.rgb, pixel.a)
@@ -210,25 +286,40 @@ pub enum ShaderColorFunction {
     BlendNextMapAlphaInverse
 }
 
-fn check_bitmap(renderer: &Renderer, reference: &Option<String>, bitmap_type: BitmapType, name: &str) -> MResult<()> {
+fn check_bitmap(assets: &impl AssetLookup, reference: &Option<String>, bitmap_type: BitmapType, name: &str) -> MResult<()> {
     let Some(bitmap_path) = reference.as_ref() else {
         return Ok(())
     };
 
-    let Some(bitmap) = renderer.bitmaps.get(bitmap_path) else {
-        return Err(Error::from_data_error_string(format!("{name} {bitmap_path} is not loaded")))
+    let Some(bitmap_types) = assets.bitmap_types(bitmap_path) else {
+        if assets.has_fallback_bitmap(bitmap_type) {
+            return Ok(())
+        }
+        return Err(ShaderError::BitmapNotLoaded { path: bitmap_path.clone() }.into())
     };
 
-    expect_bitmap_or_else(bitmap, bitmap_type, name)
+    expect_bitmap_or_else(&bitmap_types, bitmap_type, name)
 }
 
-fn expect_bitmap_or_else(bitmap: &Bitmap, bitmap_type: BitmapType, name: &str) -> MResult<()> {
-    let Some((bad_index, bad_bitmap)) = bitmap.bitmaps
+fn expect_bitmap_or_else(bitmap_types: &[BitmapType], bitmap_type: BitmapType, name: &str) -> MResult<()> {
+    let Some((bad_index, bad_bitmap_type)) = bitmap_types
         .iter()
         .enumerate()
-        .find(|a| a.1.bitmap_type != bitmap_type) else {
+        .find(|a| *a.1 != bitmap_type) else {
         return Ok(())
     };
 
-    Err(Error::from_data_error_string(format!("Bitmap #{bad_index} of {name} is {:?}, expected {bitmap_type:?}", bad_bitmap.bitmap_type)))
+    Err(ShaderError::Invalid { reason: format!("Bitmap #{bad_index} of {name} is {bad_bitmap_type:?}, expected {bitmap_type:?}") }.into())
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ShaderType {
+    Environment,
+    Model,
+    TransparentGeneric,
+    TransparentChicago,
+    TransparentGlass,
+    TransparentMeter,
+    TransparentPlasma,
+    TransparentWater
 }
@@ -1,20 +1,249 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 use crate::error::{Error, MResult};
-use crate::renderer::Renderer;
+use crate::renderer::checker::AssetLookup;
+use core::f32::consts::FRAC_PI_2;
+use glam::Vec3;
+use crate::renderer::BitmapType;
+use crate::types::FloatColor;
 
-pub use crate::renderer::data::FogData;
+/// Ambient light applied uniformly.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SkyAmbientLight {
+    /// Color in RGB.
+    pub color: [f32; 3],
 
+    /// Brightness of the light (0.0 or higher).
+    pub power: f32
+}
+
+impl SkyAmbientLight {
+    pub(crate) fn validate(&self) -> MResult<()> {
+        validate_light_color_and_power(self.color, self.power)
+    }
+}
+
+/// A directional light, such as the sun.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SkyLight {
+    /// Color in RGB.
+    pub color: [f32; 3],
+
+    /// Brightness of the light (0.0 or higher).
+    pub power: f32,
+
+    /// Direction the light travels in.
+    ///
+    /// This does not need to be normalized, but it must not be zero.
+    pub direction: Vec3
+}
+
+impl SkyLight {
+    pub(crate) fn validate(&self) -> MResult<()> {
+        validate_light_color_and_power(self.color, self.power)?;
+        if !self.direction.is_finite() || self.direction.length_squared() == 0.0 {
+            return Err(Error::from_data_error_string(format!("Invalid light direction {}", self.direction)))
+        }
+        Ok(())
+    }
+}
+
+/// Glare drawn over the screen when looking toward a light, such as the sun.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkyLensFlare {
+    /// Direction the light travels in, like [`SkyLight::direction`].
+    ///
+    /// This does not need to be normalized, but it must not be zero.
+    pub direction: Vec3,
+
+    /// Path to the bitmap that the reflections are drawn from.
+    pub bitmap: String,
+
+    /// Angle in radians around the light that is checked for occlusion.
+    ///
+    /// The flare fades out as more of this area is blocked by opaque BSP geometry. If 0.0, only the direction toward
+    /// the light is checked, so the flare pops in and out.
+    pub occlusion_radius: f32,
+
+    /// Sprites drawn along the line from the light through the center of the viewport.
+    pub reflections: Vec<SkyLensFlareReflection>
+}
+
+impl SkyLensFlare {
+    pub(crate) fn validate(&self, assets: &impl AssetLookup) -> MResult<()> {
+        if !self.direction.is_finite() || self.direction.length_squared() == 0.0 {
+            return Err(Error::from_data_error_string(format!("Invalid lens flare direction {}", self.direction)))
+        }
+        if self.occlusion_radius < 0.0 || !self.occlusion_radius.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid lens flare occlusion radius {}", self.occlusion_radius)))
+        }
+
+        let Some(bitmap_types) = assets.bitmap_types(&self.bitmap) else {
+            return Err(Error::from_data_error_string(format!("Lens flare references bitmap {} which is not loaded", self.bitmap)))
+        };
+        for (index, reflection) in self.reflections.iter().enumerate() {
+            reflection.validate()?;
+            match bitmap_types.get(reflection.bitmap_index) {
+                Some(BitmapType::Dim2D) => (),
+                Some(t) => return Err(Error::from_data_error_string(format!("Lens flare reflection #{index} uses bitmap #{} of {} which is {t:?}, not a 2D bitmap", reflection.bitmap_index, self.bitmap))),
+                None => return Err(Error::from_data_error_string(format!("Lens flare reflection #{index} uses bitmap #{} of {} which only has {} bitmap(s)", reflection.bitmap_index, self.bitmap, bitmap_types.len())))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A sprite of a [`SkyLensFlare`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SkyLensFlareReflection {
+    /// Index of the bitmap in the lens flare's bitmap to draw.
+    pub bitmap_index: usize,
+
+    /// Position along the line from the light (0.0) through the center of the viewport (1.0).
+    ///
+    /// Values above 1.0 are past the center, on the opposite side from the light.
+    pub position: f32,
+
+    /// Angular radius of the sprite in radians.
+    ///
+    /// This is how large the sprite is compared to the rest of the scene, so it is drawn larger when zoomed in.
+    pub radius: f32,
+
+    /// Color to multiply the sprite by.
+    ///
+    /// Reflections are drawn additively, so alpha scales how bright they are.
+    pub color: FloatColor
+}
+
+impl SkyLensFlareReflection {
+    pub(crate) fn validate(&self) -> MResult<()> {
+        if !self.position.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid lens flare reflection position {}", self.position)))
+        }
+        if self.radius < 0.0 || self.radius >= FRAC_PI_2 || !self.radius.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid lens flare reflection radius {}", self.radius)))
+        }
+        if let Some(c) = self.color.iter().find(|c| **c < 0.0 || **c > 1.0 || !(**c).is_finite()) {
+            return Err(Error::from_data_error_string(format!("Invalid lens flare reflection color channel value {c}")))
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn validate_light_color_and_power(color: [f32; 3], power: f32) -> MResult<()> {
+    if let Some(c) = color.iter().find(|c| **c < 0.0 || **c > 1.0 || !(**c).is_finite()) {
+        return Err(Error::from_data_error_string(format!("Invalid light color channel value {c}")))
+    }
+    if power < 0.0 || !power.is_finite() {
+        return Err(Error::from_data_error_string(format!("Invalid light power {power}")))
+    }
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FogData {
+    /// Current color in RGB.
+    pub color: [f32; 3],
+
+    /// Minimum distance that fog is applied.
+    pub distance_from: f32,
+
+    /// Maximum distance that fog is applied.
+    pub distance_to: f32,
+
+    /// Minimum opacity of fog (from 0.0 - 1.0).
+    pub min_opacity: f32,
+
+    /// Maximum opacity of fog (from 0.0 - 1.0).
+    ///
+    /// At 1.0, the render distance is set to `distance_from`.
+    pub max_opacity: f32
+}
+
+impl FogData {
+    pub(crate) fn validate(&self) -> MResult<()> {
+        if let Some(c) = self.color.iter().find(|c| **c < 0.0 || **c > 1.0 || !(**c).is_finite()) {
+            return Err(Error::from_data_error_string(format!("Invalid fog color channel value {c}")))
+        }
+
+        if self.distance_from < 0.0 || !self.distance_from.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid distance from {}", self.distance_from)))
+        }
+
+        if self.distance_to < self.distance_from || !self.distance_to.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid distance to {}", self.distance_to)))
+        }
+
+        if self.min_opacity < 0.0 || self.min_opacity > 1.0 || !self.min_opacity.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid min opacity {}", self.min_opacity)))
+        }
+
+        if self.max_opacity < self.min_opacity || self.max_opacity > 1.0 || !self.max_opacity.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid max opacity {}", self.max_opacity)))
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn normalize(&mut self) {
+        self.color[0] = self.color[0].clamp(0.0, 1.0);
+        self.color[1] = self.color[1].clamp(0.0, 1.0);
+        self.color[2] = self.color[2].clamp(0.0, 1.0);
+        self.distance_from = self.distance_from.clamp(0.0, f32::MAX);
+        self.distance_to = self.distance_to.clamp(self.distance_from, f32::MAX);
+        self.min_opacity = self.min_opacity.clamp(0.0, 1.0);
+        self.max_opacity = self.max_opacity.clamp(self.min_opacity, 1.0);
+    }
+}
+
+impl Default for FogData {
+    fn default() -> Self {
+        Self {
+            color: [0.0, 0.0, 0.0],
+            distance_from: 0.0,
+            distance_to: 1.0,
+            min_opacity: 0.0,
+            max_opacity: 0.0
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct AddSkyParameter {
+    /// Path to the skybox geometry, if any.
     pub geometry: Option<String>,
+
     pub outdoor_fog: FogData,
-    pub indoor_fog: FogData
+    pub indoor_fog: FogData,
+
+    /// Ambient light for outdoor clusters.
+    pub outdoor_ambient: SkyAmbientLight,
+
+    /// Ambient light for indoor clusters.
+    pub indoor_ambient: SkyAmbientLight,
+
+    /// Directional lights (e.g. the sun).
+    pub lights: Vec<SkyLight>,
+
+    /// Lens flares drawn when looking toward lights.
+    pub lens_flares: Vec<SkyLensFlare>
 }
 
 impl AddSkyParameter {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
+    pub(crate) fn validate(&self, assets: &impl AssetLookup) -> MResult<()> {
         self.outdoor_fog.validate()?;
         self.indoor_fog.validate()?;
+        self.outdoor_ambient.validate()?;
+        self.indoor_ambient.validate()?;
+        for light in &self.lights {
+            light.validate()?;
+        }
+        for lens_flare in &self.lens_flares {
+            lens_flare.validate(assets)?;
+        }
         if let Some(s) = self.geometry.as_ref() {
-            if !renderer.geometries.contains_key(s) {
+            if !assets.has_geometry(s) {
                 return Err(Error::from_data_error_string(format!("Fog references skybox geometry {s} which is not loaded")))
             }
         }
@@ -1,6 +1,71 @@
-use glam::Vec3;
+use glam::{Mat4, Vec3};
+use crate::error::{Error, MResult};
 use crate::renderer::data::{DRAW_DISTANCE_MINIMUM, MAX_DRAW_DISTANCE_LIMIT};
-use crate::renderer::FogData;
+use crate::renderer::{BSPFogPlane, FogData, SkyAmbientLight, SkyLight};
+
+/// Relative X, Y, width, and height of each viewport for split screen, by number of viewports.
+const SPLIT_SCREEN_LAYOUTS: [&[[f32; 4]]; 4] = [
+    &[[0.0, 0.0, 1.0, 1.0]],
+    &[[0.0, 0.0, 1.0, 0.5], [0.0, 0.5, 1.0, 0.5]],
+    &[[0.0, 0.0, 1.0, 0.5], [0.0, 0.5, 0.5, 0.5], [0.5, 0.5, 0.5, 0.5]],
+    &[[0.0, 0.0, 0.5, 0.5], [0.5, 0.0, 0.5, 0.5], [0.0, 0.5, 0.5, 0.5], [0.5, 0.5, 0.5, 0.5]]
+];
+
+/// Lay out viewports as split screen, in order from the top left.
+///
+/// # Panics
+///
+/// Panics if there are more than 4 viewports.
+pub fn lay_out_split_screen<'a>(viewports: impl IntoIterator<Item = &'a mut PlayerViewport>) {
+    let viewports: Vec<&mut PlayerViewport> = viewports.into_iter().collect();
+    let Some(layout) = viewports.len().checked_sub(1).map(|i| SPLIT_SCREEN_LAYOUTS[i]) else {
+        return
+    };
+
+    for (viewport, [x, y, width, height]) in viewports.into_iter().zip(layout) {
+        viewport.rel_x = *x;
+        viewport.rel_y = *y;
+        viewport.rel_width = *width;
+        viewport.rel_height = *height;
+    }
+}
+
+/// Bars drawn between split screen viewports.
+///
+/// Set with [`Renderer::set_split_screen_bars`](crate::renderer::Renderer::set_split_screen_bars).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SplitScreenBars {
+    /// Thickness of the bars in pixels at 640x480, which is scaled up for higher resolutions.
+    ///
+    /// Default = 2.0
+    pub thickness: f32,
+
+    /// RGBA color of the bars, blended over the viewports by alpha.
+    ///
+    /// Default = opaque black
+    pub color: [f32; 4]
+}
+
+impl SplitScreenBars {
+    pub(crate) fn validate(&self) -> MResult<()> {
+        if self.thickness <= 0.0 || !self.thickness.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid split screen bar thickness {}", self.thickness)))
+        }
+        if !self.color.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return Err(Error::from_data_error_string(format!("Invalid split screen bar color {:?} (must be between 0.0 and 1.0)", self.color)))
+        }
+        Ok(())
+    }
+}
+
+impl Default for SplitScreenBars {
+    fn default() -> Self {
+        Self {
+            thickness: 2.0,
+            color: [0.0, 0.0, 0.0, 1.0]
+        }
+    }
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct PlayerViewport {
@@ -16,20 +81,258 @@ pub struct PlayerViewport {
     /// Height of the viewport (0.0-1.0)
     pub rel_height: f32,
 
+    /// Index of the window the viewport is drawn to, where 0 is the main window
+    pub window: usize,
+
     /// Camera data
     pub camera: Camera,
 
+    /// How the camera's FoV changes with the viewport's aspect ratio
+    pub fov_scaling: FovScaling,
+
+    /// Index of the scene being rendered
+    pub scene: usize,
+
+    /// View and projection matrices to use instead of ones derived from the camera (e.g. for VR)
+    pub view_projection: Option<(Mat4, Mat4)>,
+
     /// Current viewport fog data.
     ///
     /// NOTE: This will be automatically modified to the correct values when a BSP is loaded.
     pub viewport_fog: Option<ViewportFog>,
 
+    /// Fog to use instead of the fog of the current cluster's sky
+    pub fog_override: Option<FogData>,
+
+    /// Sun of the current cluster's sky, used for specular highlights.
+    ///
+    /// NOTE: This will be automatically modified to the correct values when a BSP is loaded.
+    pub sun: Option<SkyLight>,
+
+    /// Ambient light of the current cluster's sky, used for surfaces without lightmaps.
+    ///
+    /// If the cluster has no sky, this is the indoor ambient light of the BSP's sky.
+    ///
+    /// NOTE: This will be automatically modified to the correct values when a BSP is loaded.
+    pub ambient: Option<SkyAmbientLight>,
+
+    /// Planar fog of the current cluster, which is drawn on top of the sky's fog.
+    ///
+    /// NOTE: This will be automatically modified to the correct values when a BSP is loaded.
+    pub planar_fog: Option<BSPFogPlane>,
+
+    /// Multiplier applied to lightmaps in this viewport, on top of the renderer's lightmap exposure
+    pub lightmap_exposure: f32,
+
+    /// Debug visualization to draw geometry with instead of its shaders
+    pub debug_render_mode: DebugRenderMode,
+
+    /// How to blur what is out of focus, if at all
+    pub depth_of_field: Option<DepthOfField>,
+
+    /// Fraction of the camera's motion since the last frame to blur across (0.0 - 1.0)
+    pub motion_blur: f32,
+
+    /// Exposure, bloom, color overlay, and letterbox of the viewport
+    pub post_processing: PostProcessing,
+
+    /// Distances past which stages of environment shaders are skipped.
+    pub shader_lod: ShaderLod,
+
+    /// View-projection the viewport was last drawn with, which motion blur compares against.
+    ///
+    /// NOTE: This will be automatically modified when the viewport is drawn.
+    pub previous_view_projection: Option<Mat4>,
+
     /// Current draw distance.
     ///
+    /// Only the near distance is used for the projection, which has no far plane. The far distance is where fog
+    /// becomes fully opaque, if it does.
+    ///
     /// NOTE: This will be automatically modified to the correct value when a BSP is loaded.
     pub draw_distance: [f32; 2],
 }
 
+/// Debug visualizations that geometry can be drawn with instead of its shaders.
+///
+/// Set with [`Renderer::set_debug_render_mode`](crate::renderer::Renderer::set_debug_render_mode).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum DebugRenderMode {
+    /// Draw geometry normally.
+    #[default]
+    None,
+
+    /// Color geometry by its world-space vertex normals, with each axis from -1 to 1 mapped to 0 to 1.
+    WorldSpaceNormals,
+
+    /// Color geometry by its vertex normals relative to its tangents and binormals.
+    ///
+    /// Correct tangent frames are a uniform light blue; anything else means the tangents or binormals are broken and
+    /// bump mapping will be wrong.
+    TangentSpaceNormals,
+
+    /// Color geometry by its world-space vertex tangents.
+    Tangents,
+
+    /// Color geometry by its world-space vertex binormals.
+    Binormals,
+
+    /// Draw a checkerboard from the geometry's texture coordinates, with 8x8 squares per repetition of a texture.
+    ///
+    /// This shows stretched, skewed, or mis-mapped texture coordinates. To only do this for some shaders, use
+    /// [`Renderer::set_shader_checkerboard`](crate::renderer::Renderer::set_shader_checkerboard) instead.
+    UVCheckerboard,
+
+    /// Color geometry by how many texels of its shader's base map cover each world unit.
+    ///
+    /// Green is 128 texels per world unit, going toward red for lower resolution and toward blue for higher
+    /// resolution, and saturating at 8x either way. The texel grid is shaded faintly on top. Geometry whose shader has
+    /// no 2D base map is gray.
+    TexelDensity,
+
+    /// Draw a heatmap of how many times each pixel is drawn to.
+    ///
+    /// Pixels drawn once are blue, going through cyan, green, yellow, and red to white for pixels drawn 16 or more
+    /// times. Pixels not drawn at all are black. Depth is tested as it normally is, so transparent surfaces hidden
+    /// behind opaque geometry are not counted.
+    Overdraw
+}
+
+/// Largest [`DepthOfField::aperture`] allowed, since the blur gets too sparse to look smooth past this.
+pub const MAX_DEPTH_OF_FIELD_APERTURE: f32 = 0.05;
+
+/// Depth of field of a viewport, which blurs things by how far they are from the focal distance.
+///
+/// Set with [`Renderer::set_depth_of_field`](crate::renderer::Renderer::set_depth_of_field).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DepthOfField {
+    /// Distance from the camera in world units that is in focus.
+    pub focal_distance: f32,
+
+    /// Blur radius of things infinitely far away, as a fraction of the viewport's height (0.0 - 0.05).
+    ///
+    /// Things closer than the focal distance blur more the closer they get, up to this radius.
+    pub aperture: f32
+}
+
+impl DepthOfField {
+    pub(crate) fn validate(&self) -> MResult<()> {
+        if self.focal_distance <= 0.0 || !self.focal_distance.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid focal distance {}", self.focal_distance)))
+        }
+        if !(0.0..=MAX_DEPTH_OF_FIELD_APERTURE).contains(&self.aperture) {
+            return Err(Error::from_data_error_string(format!("Invalid aperture {} (must be between 0.0 and {MAX_DEPTH_OF_FIELD_APERTURE})", self.aperture)))
+        }
+        Ok(())
+    }
+}
+
+/// Largest [`PostProcessing::letterbox`] allowed, which covers the whole viewport.
+pub const MAX_LETTERBOX: f32 = 0.5;
+
+/// Screen effects applied to a viewport after it is drawn.
+///
+/// Set with [`Renderer::set_post_processing`](crate::renderer::Renderer::set_post_processing).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PostProcessing {
+    /// Multiplier applied to the color of the scene.
+    ///
+    /// Default = 1.0
+    pub exposure: f32,
+
+    /// How much bright parts of the scene glow onto their surroundings (0.0 is off).
+    ///
+    /// Default = 0.0
+    pub bloom: f32,
+
+    /// RGBA color blended over the scene, where alpha is how much of the scene it covers.
+    ///
+    /// Default = transparent
+    pub color_overlay: [f32; 4],
+
+    /// Fraction of the viewport's height covered by black bars at each of the top and bottom (0.0 - 0.5).
+    ///
+    /// Default = 0.0
+    pub letterbox: f32
+}
+
+impl PostProcessing {
+    /// Return `true` if this leaves the scene unchanged, in which case it isn't drawn at all.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) fn validate(&self) -> MResult<()> {
+        if self.exposure < 0.0 || !self.exposure.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid exposure {}", self.exposure)))
+        }
+        if self.bloom < 0.0 || !self.bloom.is_finite() {
+            return Err(Error::from_data_error_string(format!("Invalid bloom intensity {}", self.bloom)))
+        }
+        if !self.color_overlay.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return Err(Error::from_data_error_string(format!("Invalid color overlay {:?} (must be between 0.0 and 1.0)", self.color_overlay)))
+        }
+        if !(0.0..=MAX_LETTERBOX).contains(&self.letterbox) {
+            return Err(Error::from_data_error_string(format!("Invalid letterbox {} (must be between 0.0 and {MAX_LETTERBOX})", self.letterbox)))
+        }
+        Ok(())
+    }
+}
+
+impl Default for PostProcessing {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            bloom: 0.0,
+            color_overlay: [0.0; 4],
+            letterbox: 0.0
+        }
+    }
+}
+
+/// Distances past which stages of environment shaders are skipped, cutting the cost of far away geometry on large maps.
+///
+/// Distances are in world units, from the camera to the nearest part of a geometry's bounding sphere. Set with
+/// [`Renderer::set_shader_lod`](crate::renderer::Renderer::set_shader_lod).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShaderLod {
+    /// Distance past which bump maps are not applied.
+    ///
+    /// Default = infinity (never)
+    pub bump_distance: f32,
+
+    /// Distance past which cubemap reflections and specular highlights are not drawn.
+    ///
+    /// Default = infinity (never)
+    pub reflection_distance: f32,
+
+    /// Distance past which detail and micro detail maps are not applied.
+    ///
+    /// Default = infinity (never)
+    pub detail_distance: f32
+}
+
+impl ShaderLod {
+    pub(crate) fn validate(&self) -> MResult<()> {
+        for (name, distance) in [("bump", self.bump_distance), ("reflection", self.reflection_distance), ("detail", self.detail_distance)] {
+            if distance < 0.0 || distance.is_nan() {
+                return Err(Error::from_data_error_string(format!("Invalid {name} LOD distance {distance}")))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ShaderLod {
+    fn default() -> Self {
+        Self {
+            bump_distance: f32::INFINITY,
+            reflection_distance: f32::INFINITY,
+            detail_distance: f32::INFINITY
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct ViewportFog {
     /// Current fog data (displayed)
@@ -55,8 +358,23 @@ impl Default for PlayerViewport {
             rel_y: 0.0,
             rel_width: 1.0,
             rel_height: 1.0,
+            window: 0,
             camera: Camera::default(),
+            fov_scaling: FovScaling::Fixed,
+            scene: 0,
+            view_projection: None,
             viewport_fog: None,
+            fog_override: None,
+            sun: None,
+            ambient: None,
+            planar_fog: None,
+            lightmap_exposure: 1.0,
+            debug_render_mode: DebugRenderMode::None,
+            depth_of_field: None,
+            motion_blur: 0.0,
+            post_processing: PostProcessing::default(),
+            shader_lod: ShaderLod::default(),
+            previous_view_projection: None,
             draw_distance: [DRAW_DISTANCE_MINIMUM, MAX_DRAW_DISTANCE_LIMIT],
         }
     }
@@ -68,13 +386,13 @@ pub struct Camera {
     pub fov: f32,
 
     /// Position in the map of the camera
-    pub position: [f32; 3],
+    pub position: Vec3,
 
-    /// Rotation of the camera
-    pub rotation: [f32; 3],
+    /// Direction the camera is facing
+    pub rotation: Vec3,
 
-    /// Enable lightmap.
-    pub lightmaps: bool,
+    /// How materials are lit.
+    pub lighting_mode: LightingMode,
 
     /// Enable fog.
     pub fog: bool
@@ -84,14 +402,72 @@ impl Default for Camera {
     fn default() -> Self {
         Self {
             fov: get_default_vertical_fov(),
-            position: Vec3::default().to_array(),
-            rotation: [0.0, 1.0, 0.0],
-            lightmaps: true,
+            position: Vec3::ZERO,
+            rotation: Vec3::Y,
+            lighting_mode: LightingMode::TexturedLightmapped,
             fog: true
         }
     }
 }
 
+/// How materials are lit, set per viewport with its [`Camera`].
+///
+/// All material pipelines handle every mode; materials that aren't lightmapped (e.g. transparent shaders) are only
+/// affected where noted.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(u32)]
+pub enum LightingMode {
+    /// Draw materials normally, lit by lightmaps.
+    #[default]
+    TexturedLightmapped,
+
+    /// Draw materials as if their lightmaps were white.
+    Fullbright,
+
+    /// Draw only lightmaps, as if the textures of lightmapped materials were white.
+    ///
+    /// Materials that aren't lightmapped are not drawn.
+    LightmapOnly,
+
+    /// Draw only the base map of each material, without lightmaps, detail maps, bump mapping, specular, or (for
+    /// transparent shaders) any other maps.
+    Unlit
+}
+
+/// How a viewport's FoV changes with its aspect ratio, so ultrawide and portrait viewports get sensible FoVs.
+///
+/// [`Camera::fov`] is the vertical FoV at [`FOV_REFERENCE_ASPECT_RATIO`] for the modes that scale it. Set with
+/// [`Renderer::set_fov_scaling`](crate::renderer::Renderer::set_fov_scaling).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum FovScaling {
+    /// Use [`Camera::fov`] as the vertical FoV at every aspect ratio.
+    #[default]
+    Fixed,
+
+    /// Keep the vertical FoV for viewports wider than the reference, so they see more to the sides, and keep the
+    /// horizontal FoV for narrower ones, so they see more above and below (Hor+).
+    HorPlus,
+
+    /// Keep the horizontal FoV at every aspect ratio, so wider viewports see less above and below (Vert-).
+    VertMinus
+}
+
+impl FovScaling {
+    /// Get the vertical FoV to draw a viewport with, given the camera's FoV and the viewport's aspect ratio.
+    pub fn vertical_fov(self, fov: f32, aspect_ratio: f32) -> f32 {
+        let horizontal = vertical_to_horizontal_fov(fov, FOV_REFERENCE_ASPECT_RATIO, 1.0);
+        let keep_horizontal = horizontal_to_vertical_fov(horizontal, aspect_ratio, 1.0);
+        match self {
+            FovScaling::Fixed => fov,
+            FovScaling::HorPlus => fov.max(keep_horizontal),
+            FovScaling::VertMinus => keep_horizontal
+        }
+    }
+}
+
+/// Aspect ratio that [`Camera::fov`] is given at when a viewport scales it with [`FovScaling`].
+pub const FOV_REFERENCE_ASPECT_RATIO: f32 = 4.0 / 3.0;
+
 /// Default horizontal FoV to use.
 pub const DEFAULT_HORIZONTAL_FOV: f32 = 70.0;
 
@@ -105,3 +481,9 @@ pub fn get_default_vertical_fov() -> f32 {
 pub fn horizontal_to_vertical_fov(horizontal: f32, width: f32, height: f32) -> f32 {
     2.0 * ((horizontal / 2.0).tan() * height / width).atan()
 }
+
+/// Calculate the horizontal FoV given vertical FoV and aspect ratio.
+#[inline(always)]
+pub fn vertical_to_horizontal_fov(vertical: f32, width: f32, height: f32) -> f32 {
+    2.0 * ((vertical / 2.0).tan() * width / height).atan()
+}
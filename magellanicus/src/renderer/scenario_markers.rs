@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+use glam::{Mat3, Vec3};
+use crate::types::FloatColor;
+
+/// Opacity of the faces of trigger volumes.
+const TRIGGER_VOLUME_FILL_OPACITY: f32 = 0.2;
+
+/// Category of a [`ScenarioDebugMarker`], which determines how it is drawn and whether it is shown.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ScenarioDebugMarkerCategory {
+    /// Drawn as a blue post with an arrow pointing forward.
+    PlayerSpawn,
+
+    /// Drawn as a yellow flag pointing forward.
+    NetgameFlag,
+
+    /// Drawn as a translucent green box.
+    TriggerVolume,
+
+    /// Drawn as a magenta pyramid pointing forward.
+    CutsceneCamera,
+
+    /// Drawn as an orange cube with a line pointing forward.
+    ///
+    /// This stands in for placed objects such as scenery and vehicles, since models can't be drawn yet.
+    Object
+}
+
+impl ScenarioDebugMarkerCategory {
+    /// Every category.
+    pub const ALL: [ScenarioDebugMarkerCategory; 5] = [
+        ScenarioDebugMarkerCategory::PlayerSpawn,
+        ScenarioDebugMarkerCategory::NetgameFlag,
+        ScenarioDebugMarkerCategory::TriggerVolume,
+        ScenarioDebugMarkerCategory::CutsceneCamera,
+        ScenarioDebugMarkerCategory::Object
+    ];
+
+    /// Get the color markers of this category are drawn with.
+    pub const fn color(self) -> FloatColor {
+        match self {
+            ScenarioDebugMarkerCategory::PlayerSpawn => [0.2, 0.5, 1.0, 1.0],
+            ScenarioDebugMarkerCategory::NetgameFlag => [1.0, 0.85, 0.0, 1.0],
+            ScenarioDebugMarkerCategory::TriggerVolume => [0.2, 1.0, 0.3, 1.0],
+            ScenarioDebugMarkerCategory::CutsceneCamera => [1.0, 0.3, 1.0, 1.0],
+            ScenarioDebugMarkerCategory::Object => [1.0, 0.55, 0.1, 1.0]
+        }
+    }
+}
+
+/// A marker for an object of a scenario that has no geometry of its own.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScenarioDebugMarker {
+    pub category: ScenarioDebugMarkerCategory,
+
+    /// Position of the marker in world space.
+    ///
+    /// For trigger volumes, this is the corner that the box extends from.
+    pub position: Vec3,
+
+    /// Orientation of the marker, where the X axis is forward and the Z axis is up.
+    pub rotation: Mat3,
+
+    /// Length of the box along each axis of `rotation`.
+    ///
+    /// This is only used for trigger volumes.
+    pub extents: Vec3,
+
+    /// Text drawn with the debug text font above the marker, such as the name of the object.
+    pub label: Option<String>
+}
+
+impl ScenarioDebugMarker {
+    /// Get the world-space position that the label is centered on.
+    pub fn label_position(&self) -> Vec3 {
+        let base = if self.category == ScenarioDebugMarkerCategory::TriggerVolume {
+            self.position + self.rotation * (self.extents * Vec3::new(0.5, 0.5, 1.0))
+        }
+        else {
+            self.position + Vec3::Z * 0.6
+        };
+        base + Vec3::Z * 0.1
+    }
+}
+
+/// Scenario debug markers and which categories are shown.
+#[derive(Clone, Debug, Default)]
+pub struct ScenarioDebugMarkers {
+    pub markers: Vec<ScenarioDebugMarker>,
+    pub hidden_categories: HashSet<ScenarioDebugMarkerCategory>
+}
+
+impl ScenarioDebugMarkers {
+    /// Iterate through all markers that are shown.
+    pub fn visible(&self) -> impl Iterator<Item = &ScenarioDebugMarker> {
+        self.markers.iter().filter(|m| !self.hidden_categories.contains(&m.category))
+    }
+
+    /// Append the lines of all shown markers as pairs of points.
+    pub fn append_lines(&self, lines: &mut Vec<(Vec3, FloatColor)>) {
+        for marker in self.visible() {
+            let color = marker.category.color();
+            let mut line = |from: Vec3, to: Vec3| {
+                lines.push((from, color));
+                lines.push((to, color));
+            };
+
+            let origin = marker.position;
+            let forward = marker.rotation.x_axis;
+            let left = marker.rotation.y_axis;
+            let up = marker.rotation.z_axis;
+
+            match marker.category {
+                ScenarioDebugMarkerCategory::PlayerSpawn => {
+                    let tip = origin + forward * 0.5;
+                    line(origin, origin + up * 0.6);
+                    line(origin, tip);
+                    line(tip, tip - forward * 0.15 + left * 0.1);
+                    line(tip, tip - forward * 0.15 - left * 0.1);
+                },
+                ScenarioDebugMarkerCategory::NetgameFlag => {
+                    let top = origin + up * 0.5;
+                    let middle = origin + up * 0.35;
+                    line(origin, top);
+                    line(top, middle + forward * 0.25 + up * 0.075);
+                    line(middle + forward * 0.25 + up * 0.075, middle);
+                },
+                ScenarioDebugMarkerCategory::TriggerVolume => {
+                    let corners = box_corners(marker.position, marker.rotation, marker.extents);
+                    for (a, b) in BOX_EDGES {
+                        line(corners[a], corners[b]);
+                    }
+                },
+                ScenarioDebugMarkerCategory::CutsceneCamera => {
+                    let center = origin + forward * 0.3;
+                    let corners = [
+                        center + left * 0.2 + up * 0.15,
+                        center - left * 0.2 + up * 0.15,
+                        center - left * 0.2 - up * 0.15,
+                        center + left * 0.2 - up * 0.15
+                    ];
+                    for (i, corner) in corners.iter().enumerate() {
+                        line(origin, *corner);
+                        line(*corner, corners[(i + 1) % corners.len()]);
+                    }
+
+                    // Mark which way is up.
+                    line(center + up * 0.15, center + up * 0.25);
+                },
+                ScenarioDebugMarkerCategory::Object => {
+                    let corners = box_corners(origin - marker.rotation * Vec3::splat(0.15), marker.rotation, Vec3::splat(0.3));
+                    for (a, b) in BOX_EDGES {
+                        line(corners[a], corners[b]);
+                    }
+                    line(origin, origin + forward * 0.4);
+                }
+            }
+        }
+    }
+
+    /// Append the triangles of all shown markers as triples of points.
+    pub fn append_triangles(&self, triangles: &mut Vec<(Vec3, FloatColor)>) {
+        for marker in self.visible().filter(|m| m.category == ScenarioDebugMarkerCategory::TriggerVolume) {
+            let [red, green, blue, _] = marker.category.color();
+            let color = [red, green, blue, TRIGGER_VOLUME_FILL_OPACITY];
+            let corners = box_corners(marker.position, marker.rotation, marker.extents);
+            for [a, b, c, d] in BOX_FACES {
+                for i in [a, b, c, a, c, d] {
+                    triangles.push((corners[i], color));
+                }
+            }
+        }
+    }
+}
+
+/// Pairs of indices into [`box_corners`] that form the edges of a box.
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7)
+];
+
+/// Quadruples of indices into [`box_corners`] that form the faces of a box.
+const BOX_FACES: [[usize; 4]; 6] = [
+    [0, 1, 3, 2], [4, 5, 7, 6],
+    [0, 1, 5, 4], [2, 3, 7, 6],
+    [0, 2, 6, 4], [1, 3, 7, 5]
+];
+
+/// Get the corners of a box extending from `position`, where bit 0 of the index is X, bit 1 is Y, and bit 2 is Z.
+fn box_corners(position: Vec3, rotation: Mat3, extents: Vec3) -> [Vec3; 8] {
+    std::array::from_fn(|i| {
+        let x = if i & 1 != 0 { extents.x } else { 0.0 };
+        let y = if i & 2 != 0 { extents.y } else { 0.0 };
+        let z = if i & 4 != 0 { extents.z } else { 0.0 };
+        position + rotation * Vec3::new(x, y, z)
+    })
+}
@@ -0,0 +1,22 @@
+use crate::renderer::BSPHandle;
+use crate::renderer::light::DynamicLights;
+
+/// Describes a world that one or more viewports can render.
+///
+/// Each viewport renders exactly one scene. Scene #0 always exists and is what viewports render by default.
+///
+/// A scene holds its BSP and its dynamic lights. Some state is still shared by every scene:
+/// - Skies are not set per scene because they come from the clusters of the scene's BSP, so each scene already draws
+///   the skies of its own BSP.
+/// - There are no objects; this renderer only draws BSPs, skies, and overlays.
+/// - Debug primitives and scenario debug markers are drawn in every viewport, as they are for inspecting whatever is on
+///   screen rather than part of a world.
+/// - Loaded assets are shared so that scenes using the same BSP or shaders don't load them twice.
+#[derive(Clone, Default, Debug)]
+pub struct Scene {
+    /// Currently loaded BSP, if any.
+    pub bsp: Option<BSPHandle>,
+
+    /// Dynamic lights added to the scene.
+    pub lights: DynamicLights
+}
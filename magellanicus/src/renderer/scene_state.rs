@@ -0,0 +1,828 @@
+use crate::error::{Error, MResult};
+use crate::renderer::{AddAssetsParameter, AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter,
+    AddBSPParameter, AddBSPParameterLightmapMaterial, AddBSPParameterLightmapSet, AddBSPParameterLightmapVertices,
+    AddBSPParameterVertices, AddFontParameter,
+    AddFontParameterCharacter, AddShaderBasicShaderData, AddShaderData, AddShaderEnvironmentShaderData,
+    AddShaderParameter, AddShaderTransparentChicagoShaderData, AddShaderTransparentChicagoShaderMap, AddSkyParameter,
+    BitmapFormat, BitmapSprite, BitmapType, BSP3DNode, BSP3DNodeChild, BSP3DPlane, BSPCluster, BSPData, BSPFogPlane, BSPLeaf,
+    BSPPortal, BSPSubcluster, Camera, FogData, LightingMode, Resolution, ShaderColorFunction, ShaderEnvironmentMapFunction,
+    ShaderEnvironmentType, ShaderReflectionType, ShaderTransparentChicagoFirstMapType,
+    ShaderTransparentChicagoFramebufferFunction, ShaderType, SkyAmbientLight, SkyLensFlare, SkyLensFlareReflection, SkyLight};
+use crate::vertex::{CompressedLightmapVertex, CompressedModelVertex, LightmapVertex, ModelTriangle, ModelVertex};
+use glam::Vec3;
+
+const SCENE_STATE_MAGIC: [u8; 4] = *b"MGSS";
+const SCENE_STATE_VERSION: u32 = 11;
+
+/// Snapshot of a renderer's loaded assets, scenes, and cameras.
+///
+/// Obtained with [`Renderer::save_scene_state`](crate::renderer::Renderer::save_scene_state) and restored with
+/// [`Renderer::restore_scene_state`](crate::renderer::Renderer::restore_scene_state). This can be
+/// stored with [`SceneState::to_bytes`] so a preview can be reloaded later without re-reading tags.
+#[derive(Clone, Default)]
+pub struct SceneState {
+    /// Parameters of all assets added, excluding default bitmaps.
+    ///
+    /// `worker_threads` is not stored and is always 0 when read with [`SceneState::from_bytes`].
+    pub assets: AddAssetsParameter,
+
+    /// Path of the BSP of each scene, in order, starting with the default scene.
+    pub scene_bsps: Vec<Option<String>>,
+
+    /// Scene rendered by each viewport.
+    pub viewport_scenes: Vec<usize>,
+
+    /// Camera of each viewport.
+    pub cameras: Vec<Camera>
+}
+
+impl SceneState {
+    /// Encode the scene state into a compact binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(&SCENE_STATE_MAGIC);
+        SCENE_STATE_VERSION.encode(&mut output);
+        self.assets.bitmaps.encode(&mut output);
+        self.assets.fonts.encode(&mut output);
+        self.assets.shaders.encode(&mut output);
+        self.assets.skies.encode(&mut output);
+        self.assets.bsps.encode(&mut output);
+        self.scene_bsps.encode(&mut output);
+        self.viewport_scenes.encode(&mut output);
+        self.cameras.encode(&mut output);
+        output
+    }
+
+    /// Decode a scene state that was encoded with [`SceneState::to_bytes`].
+    ///
+    /// Assets are not validated until the scene state is restored.
+    ///
+    /// Errors if:
+    /// - `data` is not a scene state, or it was made with an unsupported version
+    /// - `data` is truncated or corrupt
+    pub fn from_bytes(data: &[u8]) -> MResult<Self> {
+        let Some(mut input) = data.strip_prefix(&SCENE_STATE_MAGIC) else {
+            return Err(Error::from_data_error_string("Scene state has an invalid header".to_owned()))
+        };
+        let input = &mut input;
+
+        let version = u32::decode(input)?;
+        if version != SCENE_STATE_VERSION {
+            return Err(Error::from_data_error_string(format!("Scene state version {version} is unsupported (expected {SCENE_STATE_VERSION})")))
+        }
+
+        let result = Self {
+            assets: AddAssetsParameter {
+                bitmaps: Decode::decode(input)?,
+                fonts: Decode::decode(input)?,
+                shaders: Decode::decode(input)?,
+                skies: Decode::decode(input)?,
+                bsps: Decode::decode(input)?,
+                worker_threads: 0
+            },
+            scene_bsps: Decode::decode(input)?,
+            viewport_scenes: Decode::decode(input)?,
+            cameras: Decode::decode(input)?
+        };
+
+        if !input.is_empty() {
+            return Err(Error::from_data_error_string(format!("Scene state has {} trailing byte(s)", input.len())))
+        }
+
+        Ok(result)
+    }
+}
+
+trait Encode {
+    fn encode(&self, output: &mut Vec<u8>);
+}
+
+trait Decode: Sized {
+    fn decode(input: &mut &[u8]) -> MResult<Self>;
+}
+
+fn take_bytes<'a>(input: &mut &'a [u8], length: usize) -> MResult<&'a [u8]> {
+    if input.len() < length {
+        return Err(Error::from_data_error_string("Scene state is truncated".to_owned()))
+    }
+    let (bytes, remainder) = input.split_at(length);
+    *input = remainder;
+    Ok(bytes)
+}
+
+macro_rules! codec_primitive {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, output: &mut Vec<u8>) {
+                    output.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+            impl Decode for $t {
+                fn decode(input: &mut &[u8]) -> MResult<Self> {
+                    let bytes = take_bytes(input, size_of::<$t>())?;
+                    Ok(<$t>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+codec_primitive!(u8, u16, u32, u64, i16, i32, f32);
+
+impl Encode for usize {
+    fn encode(&self, output: &mut Vec<u8>) {
+        (*self as u64).encode(output)
+    }
+}
+impl Decode for usize {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        let value = u64::decode(input)?;
+        usize::try_from(value).map_err(|_| Error::from_data_error_string(format!("Scene state value {value} is out of range")))
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, output: &mut Vec<u8>) {
+        (*self as u8).encode(output)
+    }
+}
+impl Decode for bool {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            n => Err(Error::from_data_error_string(format!("Scene state has an invalid boolean {n}")))
+        }
+    }
+}
+
+impl Encode for char {
+    fn encode(&self, output: &mut Vec<u8>) {
+        (*self as u32).encode(output)
+    }
+}
+impl Decode for char {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        let value = u32::decode(input)?;
+        char::from_u32(value).ok_or_else(|| Error::from_data_error_string(format!("Scene state has an invalid character 0x{value:08X}")))
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, output: &mut Vec<u8>) {
+        self.len().encode(output);
+        output.extend_from_slice(self.as_bytes());
+    }
+}
+impl Decode for String {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        let length = usize::decode(input)?;
+        let bytes = take_bytes(input, length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error::from_data_error_string("Scene state has a string that is not UTF-8".to_owned()))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, output: &mut Vec<u8>) {
+        self.len().encode(output);
+        for i in self {
+            i.encode(output);
+        }
+    }
+}
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        let length = usize::decode(input)?;
+
+        // Every element takes at least one byte, so don't trust the length for preallocating beyond that.
+        let mut result = Vec::with_capacity(length.min(input.len()));
+        for _ in 0..length {
+            result.push(T::decode(input)?);
+        }
+        Ok(result)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, output: &mut Vec<u8>) {
+        self.is_some().encode(output);
+        if let Some(n) = self {
+            n.encode(output);
+        }
+    }
+}
+impl<T: Decode> Decode for Option<T> {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        if bool::decode(input)? {
+            Ok(Some(T::decode(input)?))
+        }
+        else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode(&self, output: &mut Vec<u8>) {
+        for i in self {
+            i.encode(output);
+        }
+    }
+}
+impl<T: Decode + Copy + Default, const N: usize> Decode for [T; N] {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        let mut result = [T::default(); N];
+        for i in &mut result {
+            *i = T::decode(input)?;
+        }
+        Ok(result)
+    }
+}
+
+impl Encode for Vec3 {
+    fn encode(&self, output: &mut Vec<u8>) {
+        self.to_array().encode(output)
+    }
+}
+impl Decode for Vec3 {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        Ok(Vec3::from_array(Decode::decode(input)?))
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, output: &mut Vec<u8>) {
+        self.0.encode(output);
+        self.1.encode(output);
+    }
+}
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+macro_rules! codec_struct {
+    ($t:ty { $($field:ident),* }) => {
+        impl Encode for $t {
+            fn encode(&self, output: &mut Vec<u8>) {
+                $(self.$field.encode(output);)*
+            }
+        }
+        impl Decode for $t {
+            fn decode(input: &mut &[u8]) -> MResult<Self> {
+                Ok(Self { $($field: Decode::decode(input)?),* })
+            }
+        }
+    };
+}
+
+/// Fieldless enums are stored as the index of the variant.
+macro_rules! codec_enum {
+    ($t:ty { $($variant:ident),* }) => {
+        impl Encode for $t {
+            fn encode(&self, output: &mut Vec<u8>) {
+                let index = [$(matches!(self, Self::$variant)),*].iter().position(|m| *m).unwrap();
+                (index as u32).encode(output)
+            }
+        }
+        impl Decode for $t {
+            fn decode(input: &mut &[u8]) -> MResult<Self> {
+                let index = u32::decode(input)?;
+                [$(Self::$variant),*]
+                    .into_iter()
+                    .nth(index as usize)
+                    .ok_or_else(|| Error::from_data_error_string(format!("Scene state has an invalid {} {index}", stringify!($t))))
+            }
+        }
+    };
+}
+
+fn invalid_variant<T>(name: &str, index: u32) -> MResult<T> {
+    Err(Error::from_data_error_string(format!("Scene state has an invalid {name} {index}")))
+}
+
+codec_struct!(AddBitmapParameter { bitmaps, sequences });
+codec_struct!(AddBitmapBitmapParameter { format, bitmap_type, resolution, mipmap_count, data });
+codec_struct!(Resolution { width, height });
+codec_struct!(BitmapSprite { bitmap, top, left, bottom, right });
+codec_enum!(BitmapFormat {
+    DXT1, DXT3, DXT5, BC7, A8R8G8B8, X8R8G8B8, R5G6B5, A1R5G5B5, A4R4G4B4, A8, Y8, AY8, A8Y8, P8, R32G32B32A32SFloat,
+    B4G4R4A4, A8B8G8R8
+});
+
+impl Encode for BitmapType {
+    fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Self::Dim2D => 0u32.encode(output),
+            Self::Dim3D { depth } => {
+                1u32.encode(output);
+                depth.encode(output);
+            },
+            Self::Cubemap => 2u32.encode(output)
+        }
+    }
+}
+impl Decode for BitmapType {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        match u32::decode(input)? {
+            0 => Ok(Self::Dim2D),
+            1 => Ok(Self::Dim3D { depth: Decode::decode(input)? }),
+            2 => Ok(Self::Cubemap),
+            n => invalid_variant("BitmapType", n)
+        }
+    }
+}
+
+impl Encode for AddBitmapSequenceParameter {
+    fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Self::Bitmap { first, count } => {
+                0u32.encode(output);
+                first.encode(output);
+                count.encode(output);
+            },
+            Self::Sprites { sprites } => {
+                1u32.encode(output);
+                sprites.encode(output);
+            }
+        }
+    }
+}
+impl Decode for AddBitmapSequenceParameter {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        match u32::decode(input)? {
+            0 => Ok(Self::Bitmap { first: Decode::decode(input)?, count: Decode::decode(input)? }),
+            1 => Ok(Self::Sprites { sprites: Decode::decode(input)? }),
+            n => invalid_variant("AddBitmapSequenceParameter", n)
+        }
+    }
+}
+
+codec_struct!(AddFontParameter { characters, line_height, ascending_height });
+codec_struct!(AddFontParameterCharacter { character, data, width, height, advance_x, origin_x, origin_y });
+
+codec_struct!(AddShaderParameter { data, sort_layer });
+codec_struct!(AddShaderBasicShaderData { bitmap, shader_type, alpha_tested });
+codec_struct!(AddShaderEnvironmentShaderData {
+    alpha_tested, bump_map_is_specular_mask, shader_environment_type, base_map, detail_map_function,
+    primary_detail_map, primary_detail_map_scale, secondary_detail_map, secondary_detail_map_scale, micro_detail_map,
+    micro_detail_map_scale, micro_detail_map_function, bump_map, bump_map_scale, reflection_cube_map, reflection_type,
+    perpendicular_color, perpendicular_brightness, parallel_color, parallel_brightness, specular_brightness,
+    specular_overbright, specular_extra_shiny, specular_lightmap_is_specular, mirror
+});
+codec_struct!(AddShaderTransparentChicagoShaderData { two_sided, first_map_type, framebuffer_method, maps });
+codec_struct!(AddShaderTransparentChicagoShaderMap { bitmap, color_function, alpha_function, uv_scale, uv_offset, alpha_replicate });
+codec_enum!(ShaderType {
+    Environment, Model, TransparentGeneric, TransparentChicago, TransparentGlass, TransparentMeter, TransparentPlasma,
+    TransparentWater
+});
+codec_enum!(ShaderEnvironmentType { Normal, Blended, BlendedBaseSpecular });
+codec_enum!(ShaderEnvironmentMapFunction { DoubleBiasedMultiply, Multiply, DoubleBiasedAdd });
+codec_enum!(ShaderReflectionType { BumpedCubeMap, FlatCubeMap, BumpedRadiosity });
+codec_enum!(ShaderTransparentChicagoFirstMapType { Dim2D, ReflectionCubemap, ObjectCenteredCubemap, ViewerCenteredCubemap });
+codec_enum!(ShaderTransparentChicagoFramebufferFunction {
+    AlphaBlend, Multiply, DoubleMultiply, Add, Subtract, ComponentMin, ComponentMax, AlphaMultiplyAdd
+});
+codec_enum!(ShaderColorFunction {
+    Current, NextMap, Multiply, DoubleMultiply, Add, AddSignedCurrent, AddSignedNextMap, SubtractCurrent,
+    SubtractNextMap, BlendCurrentAlpha, BlendCurrentAlphaInverse, BlendNextMapAlpha, BlendNextMapAlphaInverse
+});
+
+impl Encode for AddShaderData {
+    fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Self::BasicShader(data) => {
+                0u32.encode(output);
+                data.encode(output);
+            },
+            Self::ShaderEnvironment(data) => {
+                1u32.encode(output);
+                data.encode(output);
+            },
+            Self::ShaderTransparentChicago(data) => {
+                2u32.encode(output);
+                data.encode(output);
+            }
+        }
+    }
+}
+impl Decode for AddShaderData {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        match u32::decode(input)? {
+            0 => Ok(Self::BasicShader(Decode::decode(input)?)),
+            1 => Ok(Self::ShaderEnvironment(Decode::decode(input)?)),
+            2 => Ok(Self::ShaderTransparentChicago(Decode::decode(input)?)),
+            n => invalid_variant("AddShaderData", n)
+        }
+    }
+}
+
+codec_struct!(AddSkyParameter { geometry, outdoor_fog, indoor_fog, outdoor_ambient, indoor_ambient, lights, lens_flares });
+codec_struct!(SkyAmbientLight { color, power });
+codec_struct!(SkyLight { color, power, direction });
+codec_struct!(SkyLensFlare { direction, bitmap, occlusion_radius, reflections });
+codec_struct!(SkyLensFlareReflection { bitmap_index, position, radius, color });
+codec_struct!(FogData { color, distance_from, distance_to, min_opacity, max_opacity });
+
+codec_struct!(AddBSPParameter { lightmap_bitmap, lightmap_sets, bsp_data });
+codec_struct!(AddBSPParameterLightmapSet { lightmap_index, materials });
+codec_struct!(AddBSPParameterLightmapMaterial { shader_vertices, lightmap_vertices, surfaces, shader, centroid });
+codec_struct!(ModelVertex { position, normal, binormal, tangent, texture_coords });
+codec_struct!(LightmapVertex { lightmap_texture_coords });
+codec_struct!(CompressedModelVertex { position, normal, binormal, tangent, texture_coords });
+codec_struct!(CompressedLightmapVertex { normal, lightmap_texture_coords });
+codec_struct!(ModelTriangle { indices });
+codec_struct!(BSPData { nodes, planes, leaves, clusters, portals, fog_planes });
+codec_struct!(BSP3DNode { front_child, back_child, plane });
+codec_struct!(BSP3DPlane { angle, offset });
+codec_struct!(BSPFogPlane { plane, color, max_density, opaque_distance, opaque_depth });
+codec_struct!(BSPLeaf { cluster });
+codec_struct!(BSPCluster { sky, subclusters, cluster_portals, fog_plane });
+codec_struct!(BSPSubcluster { surface_indices, world_bounds_from, world_bounds_to });
+codec_struct!(BSPPortal { front_cluster, back_cluster });
+
+impl Encode for BSP3DNodeChild {
+    fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Self::Node(index) => {
+                0u32.encode(output);
+                index.encode(output);
+            },
+            Self::Leaf(index) => {
+                1u32.encode(output);
+                index.encode(output);
+            }
+        }
+    }
+}
+impl Decode for BSP3DNodeChild {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        match u32::decode(input)? {
+            0 => Ok(Self::Node(Decode::decode(input)?)),
+            1 => Ok(Self::Leaf(Decode::decode(input)?)),
+            n => invalid_variant("BSP3DNodeChild", n)
+        }
+    }
+}
+
+impl Encode for AddBSPParameterVertices {
+    fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Self::Uncompressed(vertices) => {
+                0u32.encode(output);
+                vertices.encode(output);
+            },
+            Self::Compressed(vertices) => {
+                1u32.encode(output);
+                vertices.encode(output);
+            }
+        }
+    }
+}
+impl Decode for AddBSPParameterVertices {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        match u32::decode(input)? {
+            0 => Ok(Self::Uncompressed(Decode::decode(input)?)),
+            1 => Ok(Self::Compressed(Decode::decode(input)?)),
+            n => invalid_variant("AddBSPParameterVertices", n)
+        }
+    }
+}
+
+impl Encode for AddBSPParameterLightmapVertices {
+    fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Self::Uncompressed(vertices) => {
+                0u32.encode(output);
+                vertices.encode(output);
+            },
+            Self::Compressed(vertices) => {
+                1u32.encode(output);
+                vertices.encode(output);
+            }
+        }
+    }
+}
+impl Decode for AddBSPParameterLightmapVertices {
+    fn decode(input: &mut &[u8]) -> MResult<Self> {
+        match u32::decode(input)? {
+            0 => Ok(Self::Uncompressed(Decode::decode(input)?)),
+            1 => Ok(Self::Compressed(Decode::decode(input)?)),
+            n => invalid_variant("AddBSPParameterLightmapVertices", n)
+        }
+    }
+}
+
+codec_struct!(Camera { fov, position, rotation, lighting_mode, fog });
+codec_enum!(LightingMode { TexturedLightmapped, Fullbright, LightmapOnly, Unlit });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DataError;
+
+    fn invalid_reason<T>(result: MResult<T>) -> String {
+        match result {
+            Err(Error::Data(DataError::Invalid { reason })) => reason,
+            Err(e) => panic!("unexpected error {e:?}"),
+            Ok(_) => panic!("decoding succeeded")
+        }
+    }
+
+    fn fog(distance_to: f32) -> FogData {
+        FogData { color: [0.25, 0.5, 0.75], distance_from: 1.0, distance_to, min_opacity: 0.0, max_opacity: 1.0 }
+    }
+
+    fn model_vertex(x: f32) -> ModelVertex {
+        ModelVertex {
+            position: [x, 1.0, 2.0],
+            normal: [0.0, 0.0, 1.0],
+            binormal: [0.0, 1.0, 0.0],
+            tangent: [1.0, 0.0, 0.0],
+            texture_coords: [x, 0.5]
+        }
+    }
+
+    fn bsp(shader_vertices: AddBSPParameterVertices, lightmap_vertices: Option<AddBSPParameterLightmapVertices>) -> AddBSPParameter {
+        let plane = BSP3DPlane { angle: [0.0, 0.0, 1.0], offset: -2.0 };
+        AddBSPParameter {
+            lightmap_bitmap: Some("lightmap.bitmap".to_owned()),
+            lightmap_sets: vec![
+                AddBSPParameterLightmapSet {
+                    lightmap_index: Some(0),
+                    materials: vec![
+                        AddBSPParameterLightmapMaterial {
+                            shader_vertices,
+                            lightmap_vertices,
+                            surfaces: vec![ModelTriangle { indices: [0, 1, 2] }],
+                            shader: "environment.shader".to_owned(),
+                            centroid: [0.5, 0.5, 0.0]
+                        }
+                    ]
+                },
+                AddBSPParameterLightmapSet { lightmap_index: None, materials: Vec::new() }
+            ],
+            bsp_data: BSPData {
+                nodes: vec![BSP3DNode { front_child: Some(BSP3DNodeChild::Leaf(0)), back_child: Some(BSP3DNodeChild::Node(0)), plane: 0 }],
+                planes: vec![plane],
+                leaves: vec![BSPLeaf { cluster: 0 }],
+                clusters: vec![BSPCluster {
+                    sky: Some("sky.sky".to_owned()),
+                    subclusters: vec![BSPSubcluster { surface_indices: vec![0], world_bounds_from: [-1.0; 3], world_bounds_to: [1.0; 3] }],
+                    cluster_portals: vec![0],
+                    fog_plane: Some(0)
+                }],
+                portals: vec![BSPPortal { front_cluster: 0, back_cluster: 0 }],
+                fog_planes: vec![BSPFogPlane { plane, color: [0.1, 0.2, 0.3], max_density: 0.5, opaque_distance: 10.0, opaque_depth: 2.0 }]
+            }
+        }
+    }
+
+    fn populated_state() -> SceneState {
+        let bitmap = AddBitmapParameter {
+            bitmaps: vec![
+                AddBitmapBitmapParameter {
+                    format: BitmapFormat::A8R8G8B8,
+                    bitmap_type: BitmapType::Dim3D { depth: 2 },
+                    resolution: Resolution { width: 1, height: 1 },
+                    mipmap_count: 0,
+                    data: vec![1, 2, 3, 4, 5, 6, 7, 8]
+                },
+                AddBitmapBitmapParameter {
+                    format: BitmapFormat::DXT5,
+                    bitmap_type: BitmapType::Cubemap,
+                    resolution: Resolution { width: 4, height: 4 },
+                    mipmap_count: 2,
+                    data: vec![0xFF; 96]
+                }
+            ],
+            sequences: vec![
+                AddBitmapSequenceParameter::Bitmap { first: 0, count: 2 },
+                AddBitmapSequenceParameter::Sprites {
+                    sprites: vec![BitmapSprite { bitmap: 1, top: 0.0, left: 0.25, bottom: 0.5, right: 1.0 }]
+                }
+            ]
+        };
+
+        let font = AddFontParameter {
+            characters: vec![AddFontParameterCharacter {
+                character: 'é',
+                data: vec![0, 128, 255, 64],
+                width: 2,
+                height: 2,
+                advance_x: 3,
+                origin_x: -1,
+                origin_y: 2
+            }],
+            line_height: 12,
+            ascending_height: 9
+        };
+
+        let basic = AddShaderParameter {
+            data: AddShaderData::BasicShader(AddShaderBasicShaderData {
+                bitmap: Some("bitmap.bitmap".to_owned()),
+                shader_type: ShaderType::TransparentWater,
+                alpha_tested: true
+            }),
+            sort_layer: -2
+        };
+        let environment = AddShaderParameter {
+            data: AddShaderData::ShaderEnvironment(AddShaderEnvironmentShaderData {
+                alpha_tested: false,
+                bump_map_is_specular_mask: true,
+                shader_environment_type: ShaderEnvironmentType::BlendedBaseSpecular,
+                base_map: Some("bitmap.bitmap".to_owned()),
+                detail_map_function: ShaderEnvironmentMapFunction::DoubleBiasedAdd,
+                primary_detail_map: None,
+                primary_detail_map_scale: 2.0,
+                secondary_detail_map: Some("bitmap.bitmap".to_owned()),
+                secondary_detail_map_scale: 4.0,
+                micro_detail_map: None,
+                micro_detail_map_scale: 8.0,
+                micro_detail_map_function: ShaderEnvironmentMapFunction::Multiply,
+                bump_map: None,
+                bump_map_scale: 1.5,
+                reflection_cube_map: Some("bitmap.bitmap".to_owned()),
+                reflection_type: ShaderReflectionType::FlatCubeMap,
+                perpendicular_color: [0.1, 0.2, 0.3],
+                perpendicular_brightness: 0.4,
+                parallel_color: [0.5, 0.6, 0.7],
+                parallel_brightness: 0.8,
+                specular_brightness: 0.9,
+                specular_overbright: true,
+                specular_extra_shiny: false,
+                specular_lightmap_is_specular: true,
+                mirror: true
+            }),
+            sort_layer: 0
+        };
+        let chicago = AddShaderParameter {
+            data: AddShaderData::ShaderTransparentChicago(AddShaderTransparentChicagoShaderData {
+                two_sided: true,
+                first_map_type: ShaderTransparentChicagoFirstMapType::ViewerCenteredCubemap,
+                framebuffer_method: ShaderTransparentChicagoFramebufferFunction::AlphaMultiplyAdd,
+                maps: vec![AddShaderTransparentChicagoShaderMap {
+                    bitmap: Some("bitmap.bitmap".to_owned()),
+                    color_function: ShaderColorFunction::BlendNextMapAlphaInverse,
+                    alpha_function: ShaderColorFunction::Add,
+                    uv_scale: [2.0, 3.0],
+                    uv_offset: [0.25, -0.25],
+                    alpha_replicate: true
+                }]
+            }),
+            sort_layer: 5
+        };
+
+        let sky = AddSkyParameter {
+            geometry: None,
+            outdoor_fog: fog(100.0),
+            indoor_fog: fog(50.0),
+            outdoor_ambient: SkyAmbientLight { color: [1.0, 0.9, 0.8], power: 0.5 },
+            indoor_ambient: SkyAmbientLight::default(),
+            lights: vec![SkyLight { color: [1.0, 1.0, 0.5], power: 2.0, direction: Vec3::new(0.0, 0.6, -0.8) }],
+            lens_flares: vec![SkyLensFlare {
+                direction: Vec3::new(0.0, 0.6, -0.8),
+                bitmap: "bitmap.bitmap".to_owned(),
+                occlusion_radius: 0.05,
+                reflections: vec![SkyLensFlareReflection { bitmap_index: 1, position: 1.5, radius: 0.1, color: [1.0, 0.5, 0.25, 0.75] }]
+            }]
+        };
+
+        let uncompressed = bsp(
+            AddBSPParameterVertices::Uncompressed(vec![model_vertex(0.0), model_vertex(1.0), model_vertex(2.0)]),
+            Some(AddBSPParameterLightmapVertices::Uncompressed(vec![LightmapVertex { lightmap_texture_coords: [0.0, 1.0] }; 3]))
+        );
+        let compressed = bsp(
+            AddBSPParameterVertices::Compressed(vec![
+                CompressedModelVertex {
+                    position: [3.0, 2.0, 1.0],
+                    normal: 0x12345678,
+                    binormal: 0x9ABCDEF0,
+                    tangent: 0x0FEDCBA9,
+                    texture_coords: [0.75, 0.25]
+                };
+                3
+            ]),
+            Some(AddBSPParameterLightmapVertices::Compressed(vec![CompressedLightmapVertex { normal: 0xDEADBEEF, lightmap_texture_coords: [-32767, 16384] }; 3]))
+        );
+
+        SceneState {
+            assets: AddAssetsParameter {
+                bitmaps: vec![("bitmap.bitmap".to_owned(), bitmap)],
+                fonts: vec![("font.font".to_owned(), font)],
+                shaders: vec![
+                    ("basic.shader".to_owned(), basic),
+                    ("environment.shader".to_owned(), environment),
+                    ("chicago.shader".to_owned(), chicago)
+                ],
+                skies: vec![("sky.sky".to_owned(), sky)],
+                bsps: vec![("uncompressed.bsp".to_owned(), uncompressed), ("compressed.bsp".to_owned(), compressed)],
+                worker_threads: 4
+            },
+            scene_bsps: vec![Some("uncompressed.bsp".to_owned()), None, Some("compressed.bsp".to_owned())],
+            viewport_scenes: vec![0, 2],
+            cameras: vec![
+                Camera::default(),
+                Camera {
+                    fov: 1.2,
+                    position: Vec3::new(1.0, -2.0, 3.0),
+                    rotation: Vec3::new(0.0, 1.0, 0.0),
+                    lighting_mode: LightingMode::LightmapOnly,
+                    fog: false
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn populated_state_round_trips() {
+        let bytes = populated_state().to_bytes();
+        let decoded = SceneState::from_bytes(&bytes).unwrap();
+
+        // None of the parameter types implement PartialEq, so compare the re-encoded bytes instead.
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.assets.worker_threads, 0);
+
+        let [(uncompressed_path, uncompressed), (compressed_path, compressed)] = decoded.assets.bsps.as_slice() else {
+            panic!("expected two BSPs")
+        };
+        assert_eq!(uncompressed_path, "uncompressed.bsp");
+        assert_eq!(compressed_path, "compressed.bsp");
+
+        let uncompressed = &uncompressed.lightmap_sets[0].materials[0];
+        assert!(matches!(&uncompressed.shader_vertices, AddBSPParameterVertices::Uncompressed(v) if v.len() == 3 && v[2].position[0] == 2.0));
+        assert!(matches!(uncompressed.lightmap_vertices, Some(AddBSPParameterLightmapVertices::Uncompressed(_))));
+
+        let compressed = &compressed.lightmap_sets[0].materials[0];
+        assert!(matches!(&compressed.shader_vertices, AddBSPParameterVertices::Compressed(v) if v.len() == 3 && v[0].tangent == 0x0FEDCBA9));
+        assert!(matches!(&compressed.lightmap_vertices, Some(AddBSPParameterLightmapVertices::Compressed(v)) if v[0].lightmap_texture_coords == [-32767, 16384]));
+
+        assert_eq!(decoded.assets.fonts[0].1.characters[0].character, 'é');
+        assert_eq!(decoded.scene_bsps, [Some("uncompressed.bsp".to_owned()), None, Some("compressed.bsp".to_owned())]);
+        assert_eq!(decoded.viewport_scenes, [0, 2]);
+        assert_eq!(decoded.cameras[1].lighting_mode, LightingMode::LightmapOnly);
+    }
+
+    #[test]
+    fn empty_state_round_trips() {
+        let bytes = SceneState::default().to_bytes();
+        assert_eq!(SceneState::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+    }
+
+    #[test]
+    fn truncated_state_fails() {
+        let bytes = populated_state().to_bytes();
+        for length in SCENE_STATE_MAGIC.len()..bytes.len() {
+            assert!(SceneState::from_bytes(&bytes[..length]).is_err(), "decoding {length} of {} byte(s) succeeded", bytes.len());
+        }
+        assert_eq!(invalid_reason(SceneState::from_bytes(&bytes[..bytes.len() - 1])), "Scene state is truncated");
+    }
+
+    #[test]
+    fn invalid_header_fails() {
+        let mut bytes = SceneState::default().to_bytes();
+        assert_eq!(invalid_reason(SceneState::from_bytes(&bytes[1..])), "Scene state has an invalid header");
+
+        bytes[SCENE_STATE_MAGIC.len()..SCENE_STATE_MAGIC.len() + 4].copy_from_slice(&(SCENE_STATE_VERSION + 1).to_le_bytes());
+        assert!(invalid_reason(SceneState::from_bytes(&bytes)).contains("is unsupported"));
+    }
+
+    #[test]
+    fn trailing_bytes_fail() {
+        let mut bytes = SceneState::default().to_bytes();
+        bytes.extend_from_slice(&[0, 0]);
+        assert_eq!(invalid_reason(SceneState::from_bytes(&bytes)), "Scene state has 2 trailing byte(s)");
+    }
+
+    #[test]
+    fn invalid_variants_fail() {
+        fn decode_variant<T: Decode>(index: u32) -> MResult<T> {
+            let mut bytes = Vec::new();
+            index.encode(&mut bytes);
+            0usize.encode(&mut bytes);
+            T::decode(&mut bytes.as_slice())
+        }
+
+        assert_eq!(invalid_reason(decode_variant::<AddBSPParameterVertices>(2)), "Scene state has an invalid AddBSPParameterVertices 2");
+        assert_eq!(invalid_reason(decode_variant::<AddBSPParameterLightmapVertices>(7)), "Scene state has an invalid AddBSPParameterLightmapVertices 7");
+        assert_eq!(invalid_reason(decode_variant::<AddShaderData>(3)), "Scene state has an invalid AddShaderData 3");
+        assert_eq!(invalid_reason(decode_variant::<BitmapType>(3)), "Scene state has an invalid BitmapType 3");
+        assert_eq!(invalid_reason(decode_variant::<LightingMode>(4)), "Scene state has an invalid LightingMode 4");
+        assert!(matches!(decode_variant::<AddBSPParameterVertices>(1), Ok(AddBSPParameterVertices::Compressed(v)) if v.is_empty()));
+    }
+
+    #[test]
+    fn corrupt_variant_in_state_fails() {
+        let mut state = SceneState::default();
+        state.cameras.push(Camera { lighting_mode: LightingMode::Unlit, fog: true, ..Camera::default() });
+        let mut bytes = state.to_bytes();
+
+        // The lighting mode is the camera's second-to-last field, followed by the one-byte fog flag.
+        let lighting_mode = bytes.len() - 5;
+        assert_eq!(&bytes[lighting_mode..lighting_mode + 4], &3u32.to_le_bytes());
+        bytes[lighting_mode] = 9;
+        assert_eq!(invalid_reason(SceneState::from_bytes(&bytes)), "Scene state has an invalid LightingMode 9");
+    }
+}
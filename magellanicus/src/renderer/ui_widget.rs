@@ -0,0 +1,67 @@
+use crate::renderer::HudTextAlignment;
+use crate::types::FloatColor;
+
+/// Width and height of the canvas that UI widgets are placed on, which is stretched to fill the viewport.
+pub const UI_CANVAS_SIZE: [u32; 2] = [640, 480];
+
+/// A widget drawn with [`Renderer::draw_ui_widget`](crate::renderer::Renderer::draw_ui_widget), such as a
+/// ui_widget_definition tag, along with its children.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct UIWidgetParameter {
+    /// Left, top, right, and bottom edges of the widget in canvas pixels, relative to the widget's origin.
+    pub bounds: [f32; 4],
+
+    /// Bitmap stretched to fill the bounds of the widget.
+    pub background: Option<UIWidgetBackground>,
+
+    /// Text drawn within the bounds of the widget.
+    pub text: Option<UIWidgetText>,
+
+    /// Widgets drawn over this widget, in order.
+    pub children: Vec<UIWidgetChild>
+}
+
+/// Background bitmap of a [`UIWidgetParameter`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct UIWidgetBackground {
+    pub bitmap: String,
+    pub sequence: usize,
+    pub frame: usize,
+
+    /// Frame to draw instead of `frame` while the widget is focused.
+    pub focused_frame: Option<usize>,
+
+    /// Color to multiply the bitmap by.
+    pub color: FloatColor,
+
+    /// Color to multiply the bitmap by while the widget is focused.
+    pub focused_color: FloatColor
+}
+
+/// Text label of a [`UIWidgetParameter`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct UIWidgetText {
+    pub text: String,
+    pub font: String,
+
+    /// Determines whether lines start at the left edge, are centered, or end at the right edge of the widget.
+    ///
+    /// Lines are broken between words to fit within the widget's width.
+    pub alignment: HudTextAlignment,
+
+    pub color: FloatColor,
+
+    /// Color of the text while the widget is focused.
+    pub focused_color: FloatColor,
+
+    pub drop_shadow: bool
+}
+
+/// A widget placed within a [`UIWidgetParameter`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct UIWidgetChild {
+    /// Offset of the child's origin from its parent's origin in canvas pixels.
+    pub offset: [f32; 2],
+
+    pub widget: UIWidgetParameter
+}
@@ -8,66 +8,268 @@ mod player_viewport;
 mod vertex;
 mod material;
 mod font;
+mod render_graph;
+mod vertex_cache;
 
 use std::collections::BTreeMap;
+use std::f32::consts::FRAC_PI_2;
 use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 pub use bitmap::*;
 pub use bsp::*;
 pub use geometry::*;
 pub use material::*;
 pub use pipeline::*;
 
-use crate::error::{Error, MResult};
-use crate::renderer::data::{BSPGeometry, BSP, MAX_DRAW_DISTANCE_LIMIT};
+use crate::error::{DeviceError, Error, GraphicsAPIError, MResult, SwapchainError};
+use crate::renderer::data::{debug_text_glyph_origin, BSPGeometry, Mirror, BSP, DEBUG_TEXT_GLYPH_SIZE};
+use crate::renderer::debug_primitives::{DebugText, DebugTextAnchor};
+use crate::renderer::hud::{HudDraw, HudDrawParameter, HudImage};
+use crate::renderer::light::DynamicLight;
 use crate::renderer::player_viewport::PlayerViewport;
+use crate::renderer::vulkan::debug_view::{DebugViewData, TEXEL_DENSITY_MODE};
 use crate::renderer::vulkan::helper::{build_swapchain, LoadedVulkan};
-use crate::renderer::vulkan::vertex::{VulkanFogData, VulkanModelData, VulkanModelVertex};
-use crate::renderer::{Camera, FogData, Renderer, RendererParameters, Resolution, MSAA};
+use crate::renderer::vulkan::ambient_occlusion::AmbientOcclusionData;
+use crate::renderer::vulkan::water::WaterData;
+use crate::renderer::vulkan::shader_environment::ReducedStages;
+use crate::renderer::vulkan::mirror::MirrorClearData;
+use crate::renderer::vulkan::depth_of_field::DepthOfFieldData;
+use crate::renderer::vulkan::motion_blur::MotionBlurData;
+use crate::renderer::vulkan::temporal_anti_aliasing::TemporalAntiAliasingData;
+use crate::renderer::vulkan::post_processing::PostProcessingData;
+use crate::renderer::vulkan::hud_meter::HudMeterData;
+use crate::renderer::vulkan::render_graph::{FrameContext, RenderGraph, FRAME_PASSES, VIEWPORT_INPUTS, VIEWPORT_PASSES};
+use crate::renderer::vulkan::vertex::{VulkanDebugTextVertex, VulkanDebugVertex, VulkanHudVertex, VulkanFogData, VulkanDynamicLight, VulkanLightingData, VulkanModelData, VulkanModelVertex};
+use crate::renderer::{BitmapType, Camera, CapturedFrame, DefaultType, DepthOfField, FogData, FrameStatistics, FrameStatus, HudBlendMode, HudTextAlignment, HudTextParameter, LightingMode, LightType, PostProcessing, Renderer, RendererParameters, Resolution, ShaderHandle, ShaderType, SplitScreenBars, MSAA, MAX_DYNAMIC_LIGHTS};
 use crate::vertex::VertexOffsets;
 use crate::types::FloatColor;
-use glam::{Mat3, Mat4, Vec3};
+use glam::{FloatExt, Mat3, Mat4, Vec2, Vec3};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
 use vulkano::command_buffer::allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, ClearDepthStencilImageInfo, CommandBufferInheritanceInfo, CommandBufferInheritanceRenderPassType, CommandBufferInheritanceRenderingInfo, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract, RenderPassBeginInfo, RenderingAttachmentInfo, RenderingInfo, ResolveImageInfo, SecondaryAutoCommandBuffer, SubpassBeginInfo, SubpassContents, SubpassEndInfo};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferExecError, CopyImageInfo, CopyImageToBufferInfo, ImageCopy, ImageResolve, CommandBufferInheritanceInfo, CommandBufferInheritanceRenderPassType, CommandBufferInheritanceRenderingInfo, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract, RenderPassBeginInfo, ResolveImageInfo, RenderingAttachmentInfo, RenderingInfo, SecondaryAutoCommandBuffer, SubpassBeginInfo, SubpassContents, SubpassEndInfo};
 use vulkano::descriptor_set::allocator::{StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo};
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::device::{Device, DeviceOwned, Queue};
-use vulkano::format::{ClearDepthStencilValue, Format};
-use vulkano::image::sampler::{Filter, Sampler, SamplerCreateInfo};
-use vulkano::image::view::ImageView;
-use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage, SampleCount};
+use vulkano::format::{ClearValue, Format, FormatFeatures};
+use vulkano::image::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::image::view::{ImageView, ImageViewCreateInfo, ImageViewType};
+use vulkano::image::{Image, ImageAspects, ImageCreateInfo, ImageSubresourceLayers, ImageSubresourceRange, ImageType, ImageUsage, SampleCount};
 use vulkano::instance::Instance;
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
 use vulkano::padded::Padded;
 use vulkano::pipeline::graphics::rasterization::CullMode;
 use vulkano::pipeline::graphics::viewport::Viewport;
-use vulkano::pipeline::{Pipeline, PipelineBindPoint};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
 use vulkano::render_pass::{AttachmentLoadOp, AttachmentStoreOp, Framebuffer, FramebufferCreateInfo};
 use vulkano::swapchain::{acquire_next_image, Surface, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo, SwapchainPresentInfo};
+use vulkano::sync::future::FenceSignalFuture;
 use vulkano::sync::GpuFuture;
 use vulkano::{single_pass_renderpass, Validated, ValidationError, VulkanError};
 
 pub(crate) static OFFLINE_PIPELINE_COLOR_FORMAT: Format = Format::R8G8B8A8_UNORM;
 
+/// Format of the overdraw image; each draw adds 1/255, so counts saturate at 255.
+pub(crate) static OVERDRAW_FORMAT: Format = Format::R8_UNORM;
+
+/// Format of the ambient occlusion image, where 1.0 is unoccluded.
+pub(crate) static AMBIENT_OCCLUSION_FORMAT: Format = Format::R8_UNORM;
+
+/// Format of the depth image if the device can use it, since mirrors need a stencil aspect to be drawn.
+const DEPTH_STENCIL_FORMAT: Format = Format::D32_SFLOAT_S8_UINT;
+
+/// Most mirrors drawn in a viewport, nearest first, since each one draws the scene again.
+const MAX_MIRRORS: usize = 2;
+
+/// Closest the camera can be to a mirror's plane for it to be drawn, since the reflection is degenerate on the plane.
+const MIN_MIRROR_DISTANCE: f32 = 0.001;
+
+/// Distance in world units that SSAO looks for occluders within.
+const AMBIENT_OCCLUSION_RADIUS: f32 = 0.5;
+
+/// How dark SSAO makes fully occluded pixels (0.0 - 1.0).
+const AMBIENT_OCCLUSION_INTENSITY: f32 = 0.75;
+
+/// Longest motion blur, as a fraction of the viewport's height, so that a camera cut doesn't smear the whole screen.
+const MAX_MOTION_BLUR_LENGTH: f32 = 0.05;
+
+/// Number of frames TAA jitters the projection across before repeating.
+const TAA_JITTER_PHASES: u32 = 8;
+
+/// How much of the history TAA blends into each frame. Higher is smoother, but takes longer to catch up to changes.
+const TAA_HISTORY_WEIGHT: f32 = 0.9;
+
+/// Multiplier applied to lightmaps when overbright lightmaps are enabled, matching the modulate 2x combine of the
+/// original engine.
+const LIGHTMAP_OVERBRIGHT: f32 = 2.0;
+
+/// Farthest bright pixels glow with bloom, as a fraction of the viewport's height.
+const BLOOM_RADIUS: f32 = 0.02;
+
+/// Longest to wait for a submission to finish before giving up, so a hung device errors instead of freezing the host.
+const FENCE_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// Fence signaled when a submission finishes.
+///
+/// It also holds whatever the submission waited on (e.g. the semaphore of an acquired swapchain image) until then.
+type SubmissionFence = FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>;
+
+const MSAA_DESCENDING: [MSAA; 7] = [MSAA::MSAA64x, MSAA::MSAA32x, MSAA::MSAA16x, MSAA::MSAA8x, MSAA::MSAA4x, MSAA::MSAA2x, MSAA::NoMSAA];
+
+fn sample_count_for_msaa(msaa: MSAA) -> SampleCount {
+    match msaa {
+        MSAA::NoMSAA => SampleCount::Sample1,
+        MSAA::MSAA2x => SampleCount::Sample2,
+        MSAA::MSAA4x => SampleCount::Sample4,
+        MSAA::MSAA8x => SampleCount::Sample8,
+        MSAA::MSAA16x => SampleCount::Sample16,
+        MSAA::MSAA32x => SampleCount::Sample32,
+        MSAA::MSAA64x => SampleCount::Sample64
+    }
+}
+
+/// What BSP geometry is being drawn for.
+#[derive(Copy, Clone, PartialEq)]
+enum GeometryPass {
+    /// Drawn as the camera sees it, skipping the given stages of environment shaders.
+    Main(ReducedStages),
+
+    /// Drawn mirrored across water into the reflection images.
+    WaterReflection,
+
+    /// Drawn mirrored across a mirror, only where the mirror is marked in the stencil image.
+    Mirror,
+
+    /// Drawn into a face of a reflection probe, whose projection is flipped horizontally to match cubemap layout.
+    ReflectionProbe
+}
+
+impl GeometryPass {
+    fn cull_mode(self) -> CullMode {
+        // Mirroring flips the winding order of every triangle, so the front faces are the ones facing away.
+        if matches!(self, GeometryPass::Main(_)) {
+            CullMode::Back
+        }
+        else {
+            CullMode::Front
+        }
+    }
+
+    fn pipeline(self, main_pipeline: VulkanPipelineType) -> VulkanPipelineType {
+        match (self, main_pipeline) {
+            (GeometryPass::Mirror, VulkanPipelineType::ShaderEnvironment) => VulkanPipelineType::ShaderEnvironmentMirrored,
+            (GeometryPass::Main(stages), VulkanPipelineType::ShaderEnvironment) if stages.is_reduced() => VulkanPipelineType::ShaderEnvironmentReduced(stages),
+            _ => main_pipeline
+        }
+    }
+}
+
 pub struct VulkanRenderer {
-    current_resolution: Resolution,
+    /// Parameters in effect (i.e. after clamping to the device).
+    parameters: RendererParameters,
     instance: Arc<Instance>,
     device: Arc<Device>,
     memory_allocator: Arc<StandardMemoryAllocator>,
     command_buffer_allocator: StandardCommandBufferAllocator,
     descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
     queue: Arc<Queue>,
-    future: Option<Box<dyn GpuFuture + Send + Sync>>,
+
+    /// Fence of the uploads submitted since the last frame.
+    ///
+    /// Each upload waits on the ones before it, and the next frame waits on all of them before drawing. Once they
+    /// finish, the next upload starts over instead of growing the chain while nothing is drawn.
+    pending_upload: Option<SubmissionFence>,
+
+    /// Fence of the last frame drawn to each swapchain image.
+    ///
+    /// Each frame starts from the acquire of its image rather than from the frame before it, and only the frame that
+    /// last used an image has to finish before it can be drawn to again, so a slow present of one image doesn't hold
+    /// up frames drawn to the others. Each fence also owns the acquire and present semaphores of its frame.
+    image_fences: Vec<Option<SubmissionFence>>,
     pipelines: BTreeMap<VulkanPipelineType, Arc<dyn VulkanPipelineData>>,
-    swapchain: Arc<Swapchain>,
-    surface: Arc<Surface>,
+    swapchain: Option<Arc<Swapchain>>,
+    surface: Option<Arc<Surface>>,
     swapchain_image_views: Vec<Arc<SwapchainImages>>,
+    last_image_index: Option<u32>,
+    render_graph: RenderGraph,
+
+    /// Passes run for each viewport by the viewports pass of `render_graph`.
+    viewport_render_graph: RenderGraph,
+    owns_device: bool,
+    device_lost: bool,
+
+    /// Set when the surface has no area (e.g. the window is minimized). Swapchains cannot be empty, so the old one is
+    /// kept and frames are skipped until the window is restored.
+    minimized: bool,
+
+    /// Set when the last image was presented but the swapchain no longer matches the surface exactly.
+    suboptimal: bool,
     default_2d_sampler: Arc<Sampler>,
     samples_per_pixel: SampleCount,
-    default_box_indices: Subbuffer<[u16]>
+    default_box_indices: Subbuffer<[u16]>,
+
+    /// When the renderer was made, which water ripples are animated from.
+    created: Instant,
+
+    /// Number of frames drawn, which picks how the projection is jittered for TAA.
+    taa_frame: u32,
+
+    /// Set once the TAA history has been drawn to since the swapchain images were made.
+    taa_history_valid: bool,
+
+    /// Pipelines bound and descriptor sets allocated in the frame being drawn.
+    frame_counters: FrameCounters,
+
+    /// Windows besides the main one, where window `n` is at index `n - 1`.
+    ///
+    /// Removed windows leave `None` behind so that the indices of the others don't change.
+    windows: Vec<Option<VulkanWindow>>
+}
+
+/// Counts of work done while drawing a frame that isn't specific to any viewport.
+///
+/// Drawing only borrows the renderer immutably, so these are atomic.
+#[derive(Default)]
+struct FrameCounters {
+    pipeline_binds: AtomicUsize,
+    descriptor_sets: AtomicUsize
+}
+
+impl FrameCounters {
+    /// Get the counts and reset them for the next frame.
+    fn take(&self) -> FrameStatistics {
+        FrameStatistics {
+            pipeline_binds: self.pipeline_binds.swap(0, Ordering::Relaxed),
+            descriptor_sets: self.descriptor_sets.swap(0, Ordering::Relaxed),
+            ..Default::default()
+        }
+    }
+}
+
+/// A window besides the main one that viewports can be drawn to, with its own swapchain.
+struct VulkanWindow {
+    surface: Arc<Surface>,
+    swapchain: Arc<Swapchain>,
+    swapchain_image_views: Vec<Arc<SwapchainImages>>,
+
+    /// Fence of the last frame drawn to each swapchain image, as with the main window.
+    image_fences: Vec<Option<SubmissionFence>>,
+
+    /// Swapchain image the last frame was drawn to.
+    last_image_index: Option<u32>,
+
+    /// Resolution the swapchain was built at, or was last set to if it has no area.
+    resolution: Resolution,
+
+    /// Set when the swapchain must be rebuilt before it is drawn to again (e.g. it no longer matches the surface or
+    /// the render targets changed).
+    rebuild_required: bool,
+
+    /// Set once the TAA history has been drawn to since the swapchain images were made.
+    taa_history_valid: bool
 }
 
 #[derive(Clone)]
@@ -75,12 +277,188 @@ pub struct SwapchainImages {
     output: Arc<ImageView>,
     color: Arc<ImageView>,
     depth: Arc<ImageView>,
+
+    /// Depth aspect of the depth image, since only one aspect of a view can be sampled.
+    depth_sampled: Arc<ImageView>,
+    resolve: Option<Arc<ImageView>>,
+    framebuffer: Option<Arc<Framebuffer>>,
+
+    /// Counts how many times each pixel was drawn to, for visualizing overdraw.
+    overdraw: Arc<ImageView>,
+    overdraw_resolve: Option<Arc<ImageView>>,
+    overdraw_framebuffer: Option<Arc<Framebuffer>>,
+
+    /// How occluded each pixel is, if SSAO is enabled.
+    ambient_occlusion: Option<Arc<ImageView>>,
+    ambient_occlusion_framebuffer: Option<Arc<Framebuffer>>,
+
+    /// Copy of the scene that post effects read from, if depth of field, motion blur, or TAA is enabled.
+    scene_copy: Option<Arc<ImageView>>,
+
+    /// Anti-aliased scene of the last frame, if TAA is enabled.
+    ///
+    /// This is shared between all swapchain images, since each frame reads what the last one drew, so each frame
+    /// waits for the last one to finish before it is submitted.
+    taa_history: Option<Arc<ImageView>>,
+
+    /// Scene mirrored across a water surface, if water reflections are enabled.
+    reflection: Option<ReflectionImages>
+}
+
+/// Images the mirrored scene is drawn to for water reflections, also used for capturing reflection probes.
+///
+/// For water reflections, these cover the same area as the color image at half the resolution. The color and depth images have the same
+/// formats and sample counts as the main ones so that the same pipelines can draw to them.
+#[derive(Clone)]
+struct ReflectionImages {
+    color: Arc<ImageView>,
+    depth: Arc<ImageView>,
     resolve: Option<Arc<ImageView>>,
     framebuffer: Option<Arc<Framebuffer>>
 }
 
+impl ReflectionImages {
+    /// Get the single-sampled color image, which water samples.
+    fn staging(&self) -> &Arc<ImageView> {
+        self.resolve.as_ref().unwrap_or(&self.color)
+    }
+
+    /// Begin rendering to the reflection images, clearing color to `clear_color` and clearing depth.
+    fn begin_rendering(&self, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, clear_color: [f32; 4]) -> MResult<()> {
+        if let Some(n) = self.framebuffer.as_ref() {
+            let begin_render_pass = RenderPassBeginInfo {
+                clear_values: vec![Some(ClearValue::Float(clear_color)), Some(depth_clear_value(&self.depth))],
+                ..RenderPassBeginInfo::framebuffer(n.clone())
+            };
+            let begin_subpass = SubpassBeginInfo {
+                contents: SubpassContents::Inline,
+                ..Default::default()
+            };
+            command_builder.begin_render_pass(begin_render_pass, begin_subpass)?;
+        }
+        else {
+            command_builder.begin_rendering(RenderingInfo {
+                color_attachments: vec![Some(RenderingAttachmentInfo {
+                    load_op: AttachmentLoadOp::Clear,
+                    store_op: AttachmentStoreOp::Store,
+                    clear_value: Some(ClearValue::Float(clear_color)),
+                    ..RenderingAttachmentInfo::image_view(self.color.clone())
+                })],
+                depth_attachment: Some(RenderingAttachmentInfo {
+                    load_op: AttachmentLoadOp::Clear,
+                    store_op: AttachmentStoreOp::DontCare,
+                    clear_value: Some(depth_clear_value(&self.depth)),
+                    ..RenderingAttachmentInfo::image_view(self.depth.clone())
+                }),
+                stencil_attachment: stencil_attachment(&self.depth, AttachmentLoadOp::Clear, AttachmentStoreOp::DontCare),
+                ..Default::default()
+            })?;
+        }
+        Ok(())
+    }
+
+    /// End rendering to the reflection images, resolving them if multisampling.
+    fn end_rendering(&self, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
+        if self.framebuffer.is_some() {
+            command_builder.end_render_pass(SubpassEndInfo::default())?;
+        }
+        else {
+            command_builder.end_rendering()?;
+        }
+
+        if let Some(resolve) = self.resolve.as_ref() {
+            command_builder.resolve_image(
+                ResolveImageInfo::images(self.color.image().clone(), resolve.image().clone())
+            )?;
+        }
+        Ok(())
+    }
+}
+
 impl SwapchainImages {
-    fn begin_rendering(&self, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+    /// Return `true` if any render targets are shared with the other swapchain images of the window.
+    fn has_shared_targets(&self) -> bool {
+        self.taa_history.is_some()
+    }
+
+    /// Return `true` if the depth image has a stencil aspect, which is needed for drawing mirrors.
+    fn has_stencil(&self) -> bool {
+        self.depth.format().aspects().intersects(ImageAspects::STENCIL)
+    }
+
+    /// Get the single-sampled color image (i.e. the resolve image if multisampling, otherwise the color image).
+    fn staging(&self) -> &Arc<ImageView> {
+        self.resolve.as_ref().unwrap_or(&self.color)
+    }
+
+    /// Get the single-sampled overdraw image (i.e. the resolve image if multisampling, otherwise the overdraw image).
+    fn overdraw_staging(&self) -> &Arc<ImageView> {
+        self.overdraw_resolve.as_ref().unwrap_or(&self.overdraw)
+    }
+
+    /// Begin rendering to the overdraw image, clearing it and the depth image.
+    fn begin_overdraw_rendering(&self, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
+        if let Some(n) = self.overdraw_framebuffer.as_ref() {
+            let begin_render_pass = RenderPassBeginInfo {
+                clear_values: vec![Some(ClearValue::Float([0.0; 4])), Some(depth_clear_value(&self.depth))],
+                ..RenderPassBeginInfo::framebuffer(n.clone())
+            };
+            let begin_subpass = SubpassBeginInfo {
+                contents: SubpassContents::Inline,
+                ..Default::default()
+            };
+            command_builder.begin_render_pass(begin_render_pass, begin_subpass)?;
+        }
+        else {
+            command_builder.begin_rendering(RenderingInfo {
+                color_attachments: vec![Some(RenderingAttachmentInfo {
+                    load_op: AttachmentLoadOp::Clear,
+                    store_op: AttachmentStoreOp::Store,
+                    clear_value: Some(ClearValue::Float([0.0; 4])),
+                    ..RenderingAttachmentInfo::image_view(self.overdraw.clone())
+                })],
+                depth_attachment: Some(RenderingAttachmentInfo {
+                    load_op: AttachmentLoadOp::Clear,
+                    store_op: AttachmentStoreOp::Store,
+                    clear_value: Some(depth_clear_value(&self.depth)),
+                    ..RenderingAttachmentInfo::image_view(self.depth.clone())
+                }),
+                stencil_attachment: stencil_attachment(&self.depth, AttachmentLoadOp::Clear, AttachmentStoreOp::Store),
+                ..Default::default()
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Begin rendering to the ambient occlusion image without a depth image, so that the depth image can be read.
+    ///
+    /// Panics if SSAO is not enabled.
+    fn begin_ambient_occlusion_rendering(&self, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
+        if let Some(n) = self.ambient_occlusion_framebuffer.as_ref() {
+            let begin_render_pass = RenderPassBeginInfo {
+                clear_values: vec![None],
+                ..RenderPassBeginInfo::framebuffer(n.clone())
+            };
+            let begin_subpass = SubpassBeginInfo {
+                contents: SubpassContents::Inline,
+                ..Default::default()
+            };
+            command_builder.begin_render_pass(begin_render_pass, begin_subpass)?;
+        }
+        else {
+            command_builder.begin_rendering(RenderingInfo {
+                color_attachments: vec![Some(RenderingAttachmentInfo {
+                    load_op: AttachmentLoadOp::DontCare,
+                    store_op: AttachmentStoreOp::Store,
+                    ..RenderingAttachmentInfo::image_view(self.ambient_occlusion.clone().expect("SSAO is not enabled"))
+                })],
+                ..Default::default()
+            })?;
+        }
+        Ok(())
+    }
+
+    fn begin_rendering(&self, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
         if let Some(n) = self.framebuffer.as_ref() {
             let begin_render_pass = RenderPassBeginInfo {
                 clear_values: vec![None, None],
@@ -90,7 +468,7 @@ impl SwapchainImages {
                 contents: SubpassContents::Inline,
                 ..Default::default()
             };
-            command_builder.begin_render_pass(begin_render_pass, begin_subpass).expect("failed to begin render pass");
+            command_builder.begin_render_pass(begin_render_pass, begin_subpass)?;
         }
         else {
             command_builder.begin_rendering(RenderingInfo {
@@ -104,17 +482,21 @@ impl SwapchainImages {
                     store_op: AttachmentStoreOp::Store,
                     ..RenderingAttachmentInfo::image_view(self.depth.clone())
                 }),
+                stencil_attachment: stencil_attachment(&self.depth, AttachmentLoadOp::Load, AttachmentStoreOp::Store),
                 ..Default::default()
-            }).expect("failed to begin rendering");
+            })?;
         }
+        Ok(())
     }
-    fn end_rendering(&self, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+    fn end_rendering(&self, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
+        // All framebuffers are made together, so this works for ambient occlusion and overdraw rendering, too.
         if self.framebuffer.is_some() {
-            command_builder.end_render_pass(SubpassEndInfo::default()).expect("failed to end render pass");
+            command_builder.end_render_pass(SubpassEndInfo::default())?;
         }
         else {
-            command_builder.end_rendering().expect("failed to end rendering");
+            command_builder.end_rendering()?;
         }
+        Ok(())
     }
 }
 
@@ -123,36 +505,47 @@ impl VulkanRenderer {
         renderer_parameters: &RendererParameters,
         surface: &(impl HasRawWindowHandle + HasRawDisplayHandle)
     ) -> MResult<Self> {
-        let LoadedVulkan { device, instance, surface, queue} = helper::load_vulkan_and_get_queue(surface, renderer_parameters.anisotropic_filtering)?;
-
-        let samples_per_pixel = match renderer_parameters.msaa {
-            MSAA::NoMSAA => SampleCount::Sample1,
-            MSAA::MSAA2x => SampleCount::Sample2,
-            MSAA::MSAA4x => SampleCount::Sample4,
-            MSAA::MSAA8x => SampleCount::Sample8,
-            MSAA::MSAA16x => SampleCount::Sample16,
-            MSAA::MSAA32x => SampleCount::Sample32,
-            MSAA::MSAA64x => SampleCount::Sample64
-        };
+        let loaded = helper::load_vulkan_and_get_queue(surface, renderer_parameters.required_anisotropic_filtering())?;
+        Self::new_from_loaded(renderer_parameters, loaded)
+    }
+
+    pub fn new_headless(renderer_parameters: &RendererParameters) -> MResult<Self> {
+        let loaded = helper::load_vulkan_headless_and_get_queue(renderer_parameters.required_anisotropic_filtering())?;
+        Self::new_from_loaded(renderer_parameters, loaded)
+    }
+
+    pub fn new_from_existing_device(
+        renderer_parameters: &RendererParameters,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        surface: Option<Arc<Surface>>
+    ) -> MResult<Self> {
+        let loaded = helper::load_from_existing_device(device, queue, surface, renderer_parameters.required_anisotropic_filtering())?;
+        let mut renderer = Self::new_from_loaded(renderer_parameters, loaded)?;
+        renderer.owns_device = false;
+        Ok(renderer)
+    }
+
+    fn new_from_loaded(renderer_parameters: &RendererParameters, loaded: LoadedVulkan) -> MResult<Self> {
+        let LoadedVulkan { device, instance, surface, queue } = loaded;
+        let mut renderer_parameters = *renderer_parameters;
 
         if let Some(n) = renderer_parameters.anisotropic_filtering {
             let max = device.physical_device().properties().max_sampler_anisotropy;
-            if max < n || n < 1.0 {
+            if renderer_parameters.clamp_to_device {
+                renderer_parameters.anisotropic_filtering = device.enabled_features().sampler_anisotropy.then_some(n.clamp(1.0, max));
+            }
+            else if max < n || n < 1.0 {
                 return Err(
-                    Error::from_vulkan_impl_error(format!("{n}x AF is unsupported by your device; supported values are 1-{max}"))
+                    Error::from_vulkan_unsupported_error(format!("{n}x AF is unsupported by your device; supported values are 1-{max}"))
                 )
             }
         }
 
-        let color = device.physical_device().properties().sampled_image_color_sample_counts;
-        let depth = device.physical_device().properties().sampled_image_depth_sample_counts;
-        let intersection = color & depth;
-        if !intersection.contains_enum(samples_per_pixel) {
-            return Err(
-                Error::from_vulkan_impl_error(format!("{}x MSAA is unsupported by your device; only these are supported:{}",
-                                                      renderer_parameters.msaa as u32,
-                                                      intersection.into_iter().map(|s| format!(" {}", s as u32)).collect::<String>())));
-        }
+        renderer_parameters.msaa = Self::supported_msaa(&device, &renderer_parameters)?;
+
+        let samples_per_pixel = sample_count_for_msaa(renderer_parameters.msaa);
+        let renderer_parameters = &renderer_parameters;
 
         let command_buffer_allocator = StandardCommandBufferAllocator::new(
             device.clone(),
@@ -172,16 +565,21 @@ impl VulkanRenderer {
         ));
 
         let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
-        let future = Some(vulkano::sync::now(device.clone()).boxed_send_sync());
 
-        let output_format = device
-            .physical_device()
-            .surface_formats(surface.as_ref(), Default::default())?[0]
-            .0;
+        let (swapchain, swapchain_images) = match surface.as_ref() {
+            Some(surface) => {
+                let output_format = device
+                    .physical_device()
+                    .surface_formats(surface.as_ref(), Default::default())?[0]
+                    .0;
 
-        let (swapchain, swapchain_images) = build_swapchain(device.clone(), surface.clone(), output_format, renderer_parameters)?;
+                let (swapchain, swapchain_images) = build_swapchain(device.clone(), surface.clone(), output_format, renderer_parameters)?;
+                (Some(swapchain), swapchain_images)
+            },
+            None => (None, Self::make_headless_output_images(memory_allocator.clone(), renderer_parameters.resolution)?)
+        };
 
-        let swapchain_image_views = Self::make_swapchain_images(swapchain_images, memory_allocator.clone(), samples_per_pixel, renderer_parameters.render_scale);
+        let swapchain_image_views = Self::make_swapchain_images(swapchain_images, memory_allocator.clone(), samples_per_pixel, renderer_parameters)?;
         let pipelines = load_all_pipelines(&swapchain_image_views[0], device.clone())?;
 
         let default_2d_sampler = Sampler::new(
@@ -203,111 +601,722 @@ impl VulkanRenderer {
         )?;
 
         Ok(Self {
-            current_resolution: renderer_parameters.resolution,
+            parameters: *renderer_parameters,
             instance,
             command_buffer_allocator,
             descriptor_set_allocator,
             device,
             queue,
-            future,
+            pending_upload: None,
+            image_fences: swapchain_image_views.iter().map(|_| None).collect(),
             pipelines,
             swapchain,
             surface,
             swapchain_image_views,
+            last_image_index: None,
+            render_graph: RenderGraph::new(FRAME_PASSES)?,
+            viewport_render_graph: RenderGraph::with_inputs(VIEWPORT_PASSES, VIEWPORT_INPUTS)?,
+            owns_device: true,
+            device_lost: false,
+            minimized: false,
+            suboptimal: false,
             memory_allocator,
             default_2d_sampler,
             samples_per_pixel,
-            default_box_indices
+            default_box_indices,
+            created: Instant::now(),
+            taa_frame: 0,
+            taa_history_valid: false,
+            frame_counters: FrameCounters::default(),
+            windows: Vec::new()
         })
     }
 
-    pub fn draw_frame(renderer: &mut Renderer) -> MResult<bool> {
-        let vulkan_renderer = &mut renderer.vulkan;
+    pub fn draw_frame(renderer: &mut Renderer) -> MResult<FrameStatus> {
+        if renderer.vulkan.device_lost {
+            return Err(DeviceError::DeviceLost.into())
+        }
+
+        let result = Self::acquire_and_draw_frame(renderer);
+        if let Err(Error::Device(DeviceError::DeviceLost)) = &result {
+            renderer.vulkan.device_lost = true;
+        }
+        result
+    }
+
+    fn acquire_and_draw_frame(renderer: &mut Renderer) -> MResult<FrameStatus> {
+        let Some(swapchain) = renderer.vulkan.swapchain.clone() else {
+            return Self::draw_frame_to_image(renderer, 0, 0, None)
+        };
 
         let (image_index, suboptimal, acquire_future) =
-            match acquire_next_image(vulkan_renderer.swapchain.clone(), None).map_err(Validated::unwrap) {
+            match acquire_next_image(swapchain, None) {
                 Ok(r) => r,
-                Err(VulkanError::OutOfDate) => return Ok(false),
-                Err(e) => panic!("failed to acquire next image: {e}"),
+                Err(Validated::Error(VulkanError::OutOfDate)) => return Ok(FrameStatus::ResizeRequired),
+                Err(e) => return Err(Error::from_vulkan_submit_error(e)),
             };
 
-        Ok(Self::draw_frame_infallible(renderer, image_index, acquire_future) && !suboptimal)
+        let status = Self::draw_frame_to_image(renderer, 0, image_index, Some(acquire_future))?;
+        renderer.vulkan.suboptimal |= suboptimal;
+        Ok(status)
     }
 
-    pub fn rebuild_swapchain(&mut self, renderer_parameters: &RendererParameters) -> MResult<()> {
-        let (swapchain, swapchain_images) = self.swapchain.recreate(
-            SwapchainCreateInfo {
-                image_extent: [renderer_parameters.resolution.width, renderer_parameters.resolution.height],
-                ..self.swapchain.create_info()
-            }
-        )?;
-
-        self.swapchain = swapchain;
-        self.swapchain_image_views = Self::make_swapchain_images(swapchain_images, self.memory_allocator.clone(), self.samples_per_pixel, renderer_parameters.render_scale);
-        self.current_resolution = renderer_parameters.resolution;
-        self.pipelines = load_all_pipelines(&self.swapchain_image_views[0], self.device.clone()).expect("failed to reload pipelines...");
+    /// Recreate the device and everything made with it after the device was lost.
+    ///
+    /// Nothing that was uploaded to the old device is carried over.
+    pub fn recreate_device(&mut self, renderer_parameters: &RendererParameters) -> MResult<()> {
+        if !self.owns_device {
+            return Err(Error::from_vulkan_unsupported_error("The device was created by the host, so the host must recreate it".to_owned()))
+        }
 
+        // The old swapchains have to be gone before new ones can be made for the same surfaces
+        self.swapchain = None;
+        self.swapchain_image_views.clear();
+        self.image_fences.clear();
+        self.pending_upload = None;
+        self.last_image_index = None;
+        let windows: Vec<Option<(Arc<Surface>, Resolution)>> = self.windows
+            .drain(..)
+            .map(|w| w.map(|w| (w.surface, w.resolution)))
+            .collect();
+
+        let loaded = helper::load_device_and_get_queue(self.instance.clone(), self.surface.clone(), renderer_parameters.required_anisotropic_filtering())?;
+        *self = Self::new_from_loaded(renderer_parameters, loaded)?;
+
+        for window in windows {
+            let window = window.map(|(surface, resolution)| self.make_window(surface, resolution)).transpose()?;
+            self.windows.push(window);
+        }
         Ok(())
     }
 
-    fn make_swapchain_images(swapchain_images: Vec<Arc<Image>>, memory_allocator: Arc<StandardMemoryAllocator>, samples_per_pixel: SampleCount, render_scale: f32) -> Vec<Arc<SwapchainImages>> {
-        assert!(render_scale > 0.0);
-
-        let device = memory_allocator.device();
+    pub fn is_headless(&self) -> bool {
+        self.swapchain.is_none()
+    }
 
-        swapchain_images.iter().map(|i| {
-            let native_width = i.extent()[0];
-            let native_height = i.extent()[1];
+    pub fn get_parameters(&self) -> &RendererParameters {
+        &self.parameters
+    }
 
-            let width;
-            let height;
-            if render_scale != 1.0 {
-                let max_width = device.physical_device().properties().max_framebuffer_width;
-                let max_height = device.physical_device().properties().max_framebuffer_height;
+    /// Return `true` if multisampled images use the standard sample locations defined by Vulkan.
+    pub fn has_standard_sample_locations(&self) -> bool {
+        self.device.physical_device().properties().standard_sample_locations
+    }
 
-                let attempted_width = ((native_width as f32) * render_scale.sqrt()) as u32;
-                let attempted_height = ((native_height as f32) * render_scale.sqrt()) as u32;
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
 
-                width = attempted_width.clamp(1, max_width);
-                height = attempted_height.clamp(1, max_height);
+    /// Return `true` if the swapchain should be rebuilt even though frames can still be presented to it.
+    pub fn is_suboptimal(&self) -> bool {
+        self.suboptimal
+    }
 
-                if width != attempted_width || height != attempted_height {
-                    eprintln!("Resolution {attempted_width}x{attempted_height} is not supported by the GPU... resizing");
-                }
-            }
-            else {
-                width = native_width;
-                height = native_height;
-            }
+    /// Skip frames until the swapchain is rebuilt at a non-zero resolution.
+    pub fn set_minimized(&mut self) {
+        self.minimized = true;
+    }
 
-            println!("Render resolution: {width}x{height} ({native_width}x{native_height}x{:.02}%)", render_scale * 100.0);
+    /// Get the current size of the surface, if any.
+    ///
+    /// Returns `None` if headless or if the size is determined by the swapchain rather than the surface (e.g. on
+    /// Wayland).
+    pub fn get_surface_resolution(&self) -> MResult<Option<Resolution>> {
+        let Some(surface) = self.surface.as_ref() else {
+            return Ok(None)
+        };
+        let capabilities = self.device
+            .physical_device()
+            .surface_capabilities(surface.as_ref(), Default::default())?;
+        Ok(capabilities.current_extent.map(|[width, height]| Resolution { width, height }))
+    }
 
-            let output = ImageView::new_default(i.clone()).unwrap();
-            let color = ImageView::new_default(Image::new(
-                memory_allocator.clone(),
-                ImageCreateInfo {
-                    extent: [width, height, 1],
-                    format: OFFLINE_PIPELINE_COLOR_FORMAT,
-                    image_type: ImageType::Dim2d,
-                    samples: samples_per_pixel,
-                    usage: ImageUsage::TRANSFER_SRC | ImageUsage::COLOR_ATTACHMENT,
-                    ..Default::default()
-                },
-                AllocationCreateInfo::default(),
-            ).unwrap()).unwrap();
+    /// Get the MSAA of `renderer_parameters` if `device` supports it, or the most it supports below that if
+    /// `clamp_to_device` is set.
+    fn supported_msaa(device: &Device, renderer_parameters: &RendererParameters) -> MResult<MSAA> {
+        let color = device.physical_device().properties().sampled_image_color_sample_counts;
+        let depth = device.physical_device().properties().sampled_image_depth_sample_counts;
+        let intersection = color & depth;
+        if intersection.contains_enum(sample_count_for_msaa(renderer_parameters.msaa)) {
+            return Ok(renderer_parameters.msaa)
+        }
+        if !renderer_parameters.clamp_to_device {
+            return Err(
+                Error::from_vulkan_unsupported_error(format!("{}x MSAA is unsupported by your device; only these are supported:{}",
+                                                      renderer_parameters.msaa as u32,
+                                                      intersection.into_iter().map(|s| format!(" {}", s as u32)).collect::<String>())));
+        }
 
-            let depth = ImageView::new_default(Image::new(
-                memory_allocator.clone(),
-                ImageCreateInfo {
-                    extent: [width, height, 1],
-                    format: Format::D32_SFLOAT,
-                    image_type: ImageType::Dim2d,
-                    samples: samples_per_pixel,
-                    usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSFER_DST,
-                    ..Default::default()
-                },
-                AllocationCreateInfo::default(),
-            ).unwrap()).unwrap();
+        // 1 sample per pixel is always supported
+        Ok(MSAA_DESCENDING
+            .into_iter()
+            .find(|m| (*m as u32) <= (renderer_parameters.msaa as u32) && intersection.contains_enum(sample_count_for_msaa(*m)))
+            .unwrap_or(MSAA::NoMSAA))
+    }
+
+    pub fn rebuild_swapchain(&mut self, renderer_parameters: &RendererParameters) -> MResult<()> {
+        let msaa = Self::supported_msaa(&self.device, renderer_parameters)?;
+        let swapchain_images = match self.swapchain.as_ref() {
+            Some(old_swapchain) => {
+                let (swapchain, swapchain_images) = old_swapchain.recreate(
+                    SwapchainCreateInfo {
+                        image_extent: [renderer_parameters.resolution.width, renderer_parameters.resolution.height],
+                        ..old_swapchain.create_info()
+                    }
+                )?;
+                self.swapchain = Some(swapchain);
+                swapchain_images
+            },
+            None => Self::make_headless_output_images(self.memory_allocator.clone(), renderer_parameters.resolution)?
+        };
+
+        self.last_image_index = None;
+        self.minimized = false;
+        self.suboptimal = false;
+        self.samples_per_pixel = sample_count_for_msaa(msaa);
+        self.swapchain_image_views = Self::make_swapchain_images(swapchain_images, self.memory_allocator.clone(), self.samples_per_pixel, renderer_parameters)?;
+        self.taa_history_valid = false;
+        self.image_fences = self.swapchain_image_views.iter().map(|_| None).collect();
+        self.parameters.resolution = renderer_parameters.resolution;
+        self.parameters.msaa = msaa;
+        self.parameters.render_scale = renderer_parameters.render_scale;
+        self.parameters.ssao = renderer_parameters.ssao;
+        self.parameters.water_reflections = renderer_parameters.water_reflections;
+        self.parameters.depth_of_field = renderer_parameters.depth_of_field;
+        self.parameters.motion_blur = renderer_parameters.motion_blur;
+        self.parameters.taa = renderer_parameters.taa;
+        self.parameters.post_processing = renderer_parameters.post_processing;
+        self.parameters.skipped_mip_levels = renderer_parameters.skipped_mip_levels;
+        self.pipelines = load_all_pipelines(&self.swapchain_image_views[0], self.device.clone())?;
+        self.invalidate_windows();
+
+        Ok(())
+    }
+
+    pub unsafe fn replace_surface(
+        &mut self,
+        renderer_parameters: &RendererParameters,
+        window: &(impl HasRawWindowHandle + HasRawDisplayHandle)
+    ) -> MResult<()> {
+        if self.surface.is_none() {
+            return Err(SwapchainError::Headless.into())
+        }
+        let msaa = Self::supported_msaa(&self.device, renderer_parameters)?;
+
+        // Make sure nothing is still using the old swapchain before tearing it down
+        self.wait_for_completion()?;
+        self.swapchain = None;
+        self.surface = None;
+        self.last_image_index = None;
+
+        let surface = Surface::from_window_ref(self.instance.clone(), window)?;
+        if !self.device.physical_device().surface_support(self.queue.queue_family_index(), surface.as_ref()).unwrap_or(false) {
+            return Err(SwapchainError::UnsupportedSurface.into())
+        }
+
+        let output_format = self.device
+            .physical_device()
+            .surface_formats(surface.as_ref(), Default::default())?[0]
+            .0;
+
+        let (swapchain, swapchain_images) = build_swapchain(self.device.clone(), surface.clone(), output_format, renderer_parameters)?;
+        self.surface = Some(surface);
+        self.swapchain = Some(swapchain);
+        self.minimized = false;
+        self.suboptimal = false;
+        self.samples_per_pixel = sample_count_for_msaa(msaa);
+        self.swapchain_image_views = Self::make_swapchain_images(swapchain_images, self.memory_allocator.clone(), self.samples_per_pixel, renderer_parameters)?;
+        self.taa_history_valid = false;
+        self.image_fences = self.swapchain_image_views.iter().map(|_| None).collect();
+        self.parameters.resolution = renderer_parameters.resolution;
+        self.parameters.msaa = msaa;
+        self.parameters.render_scale = renderer_parameters.render_scale;
+        self.parameters.ssao = renderer_parameters.ssao;
+        self.parameters.water_reflections = renderer_parameters.water_reflections;
+        self.parameters.depth_of_field = renderer_parameters.depth_of_field;
+        self.parameters.motion_blur = renderer_parameters.motion_blur;
+        self.parameters.taa = renderer_parameters.taa;
+        self.parameters.post_processing = renderer_parameters.post_processing;
+        self.parameters.skipped_mip_levels = renderer_parameters.skipped_mip_levels;
+        self.parameters.vsync = renderer_parameters.vsync;
+        self.pipelines = load_all_pipelines(&self.swapchain_image_views[0], self.device.clone())?;
+        self.invalidate_windows();
+
+        Ok(())
+    }
+
+    /// Attach another window, returning its index.
+    pub unsafe fn add_window(&mut self, window: &(impl HasRawWindowHandle + HasRawDisplayHandle), resolution: Resolution) -> MResult<usize> {
+        if self.surface.is_none() {
+            return Err(SwapchainError::Headless.into())
+        }
+
+        let surface = Surface::from_window_ref(self.instance.clone(), window)?;
+        let window = self.make_window(surface, resolution)?;
+        let index = match self.windows.iter().position(Option::is_none) {
+            Some(index) => {
+                self.windows[index] = Some(window);
+                index
+            },
+            None => {
+                self.windows.push(Some(window));
+                self.windows.len() - 1
+            }
+        };
+        Ok(index + 1)
+    }
+
+    /// Detach a window added with [`VulkanRenderer::add_window`].
+    ///
+    /// This waits for the GPU to finish with the window so that the host can destroy it afterward.
+    pub fn remove_window(&mut self, window: usize) -> MResult<()> {
+        assert!(self.has_window(window) && window != 0, "window {window} is not an attached window");
+        self.wait_for_completion()?;
+        self.windows[window - 1] = None;
+        Ok(())
+    }
+
+    /// Return `true` if `window` is the main window or an attached window.
+    pub fn has_window(&self, window: usize) -> bool {
+        window == 0 || self.windows.get(window - 1).is_some_and(Option::is_some)
+    }
+
+    /// Rebuild the swapchain of an attached window at a new resolution.
+    ///
+    /// If `resolution` has no area, the window is skipped until it is resized again.
+    pub fn set_window_resolution(&mut self, window: usize, resolution: Resolution) -> MResult<()> {
+        let vulkan_window = self.get_window_mut(window);
+        if resolution.width == 0 || resolution.height == 0 {
+            vulkan_window.resolution = resolution;
+            vulkan_window.rebuild_required = true;
+            return Ok(())
+        }
+        self.rebuild_window(window, resolution)
+    }
+
+    fn make_window(&self, surface: Arc<Surface>, resolution: Resolution) -> MResult<VulkanWindow> {
+        if !self.device.physical_device().surface_support(self.queue.queue_family_index(), surface.as_ref()).unwrap_or(false) {
+            return Err(SwapchainError::UnsupportedSurface.into())
+        }
+
+        let output_format = self.device
+            .physical_device()
+            .surface_formats(surface.as_ref(), Default::default())?[0]
+            .0;
+
+        let renderer_parameters = RendererParameters { resolution, ..self.parameters };
+        let (swapchain, swapchain_images) = build_swapchain(self.device.clone(), surface.clone(), output_format, &renderer_parameters)?;
+        let swapchain_image_views = Self::make_swapchain_images(swapchain_images, self.memory_allocator.clone(), self.samples_per_pixel, &renderer_parameters)?;
+        Ok(VulkanWindow {
+            surface,
+            swapchain,
+            image_fences: swapchain_image_views.iter().map(|_| None).collect(),
+            last_image_index: None,
+            swapchain_image_views,
+            resolution,
+            rebuild_required: false,
+            taa_history_valid: false
+        })
+    }
+
+    fn rebuild_window(&mut self, window: usize, resolution: Resolution) -> MResult<()> {
+        let renderer_parameters = RendererParameters { resolution, ..self.parameters };
+        let memory_allocator = self.memory_allocator.clone();
+        let samples_per_pixel = self.samples_per_pixel;
+
+        let vulkan_window = self.get_window_mut(window);
+        let (swapchain, swapchain_images) = vulkan_window.swapchain.recreate(
+            SwapchainCreateInfo {
+                image_extent: [resolution.width, resolution.height],
+                ..vulkan_window.swapchain.create_info()
+            }
+        )?;
+        vulkan_window.swapchain = swapchain;
+        vulkan_window.swapchain_image_views = Self::make_swapchain_images(swapchain_images, memory_allocator, samples_per_pixel, &renderer_parameters)?;
+        vulkan_window.image_fences = vulkan_window.swapchain_image_views.iter().map(|_| None).collect();
+        vulkan_window.last_image_index = None;
+        vulkan_window.resolution = resolution;
+        vulkan_window.rebuild_required = false;
+        vulkan_window.taa_history_valid = false;
+        Ok(())
+    }
+
+    /// Make the attached windows rebuild their render targets before they are drawn to again, since the renderer's
+    /// parameters (and pipelines) may have changed.
+    fn invalidate_windows(&mut self) {
+        for window in self.windows.iter_mut().flatten() {
+            window.rebuild_required = true;
+        }
+    }
+
+    fn get_window_mut(&mut self, window: usize) -> &mut VulkanWindow {
+        assert!(window != 0, "window 0 is the main window");
+        self.windows
+            .get_mut(window - 1)
+            .and_then(Option::as_mut)
+            .unwrap_or_else(|| panic!("window {window} is not attached"))
+    }
+
+    fn window_images(&self, window: usize) -> &[Arc<SwapchainImages>] {
+        match window {
+            0 => &self.swapchain_image_views,
+            n => &self.windows[n - 1].as_ref().expect("window is attached").swapchain_image_views
+        }
+    }
+
+    fn window_image_fences(&mut self, window: usize) -> &mut Vec<Option<SubmissionFence>> {
+        match window {
+            0 => &mut self.image_fences,
+            n => &mut self.get_window_mut(n).image_fences
+        }
+    }
+
+    fn window_last_image_index(&self, window: usize) -> Option<u32> {
+        match window {
+            0 => self.last_image_index,
+            n => self.windows[n - 1].as_ref().and_then(|w| w.last_image_index)
+        }
+    }
+
+    fn set_window_last_image_index(&mut self, window: usize, image_index: u32) {
+        match window {
+            0 => self.last_image_index = Some(image_index),
+            n => self.get_window_mut(n).last_image_index = Some(image_index)
+        }
+    }
+
+    fn window_swapchain(&self, window: usize) -> Option<Arc<Swapchain>> {
+        match window {
+            0 => self.swapchain.clone(),
+            n => self.windows[n - 1].as_ref().map(|w| w.swapchain.clone())
+        }
+    }
+
+    /// Return `true` if the TAA history of a window has been drawn to since its swapchain images were made.
+    fn is_taa_history_valid(&self, window: usize) -> bool {
+        match window {
+            0 => self.taa_history_valid,
+            n => self.windows[n - 1].as_ref().is_some_and(|w| w.taa_history_valid)
+        }
+    }
+
+    fn set_taa_history_valid(&mut self, window: usize, valid: bool) {
+        match window {
+            0 => self.taa_history_valid = valid,
+            n => self.get_window_mut(n).taa_history_valid = valid
+        }
+    }
+
+    /// Draw the viewports of each attached window and present them.
+    ///
+    /// Windows are rebuilt at the size of their surfaces if it changed, and windows with no area are skipped.
+    pub fn draw_windows(renderer: &mut Renderer) -> MResult<()> {
+        for window in 1..=renderer.vulkan.windows.len() {
+            if renderer.vulkan.windows[window - 1].is_some() {
+                Self::draw_window(renderer, window)?;
+            }
+        }
+
+        // Every window has been drawn with this jitter, so move on to the next one.
+        renderer.vulkan.taa_frame = renderer.vulkan.taa_frame.wrapping_add(1);
+        Ok(())
+    }
+
+    fn draw_window(renderer: &mut Renderer, window: usize) -> MResult<()> {
+        let vulkan_window = renderer.vulkan.get_window_mut(window);
+        let surface = vulkan_window.surface.clone();
+        let last_resolution = vulkan_window.resolution;
+        let rebuild_required = vulkan_window.rebuild_required;
+
+        // If the size of the surface is unknown, it stays at whatever it was last set to.
+        let resolution = renderer.vulkan.device
+            .physical_device()
+            .surface_capabilities(surface.as_ref(), Default::default())?
+            .current_extent
+            .map_or(last_resolution, |[width, height]| Resolution { width, height });
+        if resolution.width == 0 || resolution.height == 0 {
+            return Ok(())
+        }
+        if rebuild_required || resolution != last_resolution {
+            renderer.vulkan.rebuild_window(window, resolution)?;
+        }
+
+        let swapchain = renderer.vulkan.get_window_mut(window).swapchain.clone();
+        let (image_index, suboptimal, acquire_future) =
+            match acquire_next_image(swapchain, None) {
+                Ok(r) => r,
+                Err(Validated::Error(VulkanError::OutOfDate)) => {
+                    renderer.vulkan.get_window_mut(window).rebuild_required = true;
+                    return Ok(())
+                },
+                Err(e) => return Err(Error::from_vulkan_submit_error(e)),
+            };
+
+        let status = Self::draw_frame_to_image(renderer, window, image_index, Some(acquire_future))?;
+        renderer.vulkan.get_window_mut(window).rebuild_required |= suboptimal || status == FrameStatus::ResizeRequired;
+        Ok(())
+    }
+
+    fn make_headless_output_images(memory_allocator: Arc<StandardMemoryAllocator>, resolution: Resolution) -> MResult<Vec<Arc<Image>>> {
+        let image = Image::new(
+            memory_allocator,
+            ImageCreateInfo {
+                extent: [resolution.width, resolution.height, 1],
+                format: OFFLINE_PIPELINE_COLOR_FORMAT,
+                image_type: ImageType::Dim2d,
+                usage: ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+        Ok(vec![image])
+    }
+
+    /// Read back the last frame that was drawn, at the render resolution, as tightly packed RGBA8 pixels.
+    pub fn capture_frame(&mut self) -> MResult<CapturedFrame> {
+        let Some(image_index) = self.last_image_index else {
+            return Err(Error::from_data_error_string("No frame has been drawn yet".to_owned()))
+        };
+
+        let images = self.swapchain_image_views[image_index as usize].clone();
+        let image = images.staging().image().clone();
+        let [width, height, _] = image.extent();
+
+        let buffer = Buffer::new_slice::<u8>(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            (width as u64) * (height as u64) * 4
+        )?;
+
+        let mut command_builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit
+        )?;
+        command_builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))?;
+        let commands = command_builder.build()?;
+
+        self.execute_command_list(commands)?;
+        self.wait_for_completion()?;
+
+        let data = buffer
+            .read()
+            .map_err(|e| Error::from_vulkan_error(format!("Failed to read captured frame: {e}")))?
+            .to_vec();
+
+        Ok(CapturedFrame {
+            resolution: Resolution { width, height },
+            data
+        })
+    }
+
+    /// Draw the opaque geometry of `bsp` in all six directions from `position`, returning the faces of a cubemap with
+    /// `resolution` x `resolution` faces as tightly packed RGBA8 pixels, in the order cubemap bitmaps are given.
+    ///
+    /// The fog, lighting, and near plane of `viewport` are used.
+    pub fn capture_reflection_probe(renderer: &mut Renderer, viewport: usize, bsp: &BSP, position: Vec3, resolution: u32) -> MResult<Vec<u8>> {
+        let max_resolution = renderer.vulkan.device.physical_device().properties().max_image_dimension_cube;
+        if resolution > max_resolution {
+            return Err(Error::from_data_error_string(format!("Reflection probe resolution {resolution} exceeds the maximum of {max_resolution}")))
+        }
+        let Some(buffers) = bsp.vulkan.subbuffers.as_ref() else {
+            return Err(Error::from_data_error_string("The BSP has no geometry to capture".to_owned()))
+        };
+
+        let player_viewport = renderer.player_viewports[viewport];
+        let images = renderer.vulkan.swapchain_image_views[0].clone();
+        let probe = Self::make_reflection_images(renderer.vulkan.memory_allocator.clone(), &images.color, &images.depth, [resolution, resolution])?;
+
+        let face_size = (resolution as u64) * (resolution as u64) * 4;
+        let buffer = Buffer::new_slice::<u8>(
+            renderer.vulkan.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            face_size * 6
+        )?;
+
+        let mut command_builder = AutoCommandBufferBuilder::primary(
+            &renderer.vulkan.command_buffer_allocator,
+            renderer.vulkan.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit
+        )?;
+        command_builder.bind_index_buffer(buffers.index_subbuffer.clone())?;
+        command_builder.bind_vertex_buffers(0, (
+            buffers.vertex_data_subbuffer.clone(),
+            buffers.texture_coords_subbuffer.clone(),
+            buffers.lightmap_texture_coords_subbuffer.clone()
+        ))?;
+
+        let (fog_data, sky_color) = viewport_fog(&player_viewport, position);
+        let fog = make_fog_uniform(renderer, &fog_data, &player_viewport, player_viewport.camera.lighting_mode, position, [0.0; 4])?;
+
+        // Cubemap faces look right down the axes, but their images are laid out as if seen from outside the cube, so
+        // the projection is flipped horizontally.
+        let proj = Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0))
+            * Mat4::perspective_infinite_reverse_lh(FRAC_PI_2, 1.0, player_viewport.draw_distance[0]);
+        let probe_viewport = Viewport {
+            offset: [0.0, 0.0],
+            extent: [resolution as f32, resolution as f32],
+            depth_range: 0.0..=1.0,
+        };
+
+        for (face, (forward, down)) in CUBEMAP_FACE_DIRECTIONS.into_iter().enumerate() {
+            let view = Mat4::look_to_lh(position, forward, down);
+            let mvp = make_model_view_uniform(renderer, position, Vec3::default(), Mat3::IDENTITY, view, proj)?;
+
+            probe.begin_rendering(&mut command_builder, sky_color)?;
+            command_builder.set_viewport(0, [probe_viewport.clone()].into_iter().collect())?;
+
+            let mut last_shader = None;
+            for geometry in bsp.vulkan.opaque_geometries.iter().map(|i| &bsp.geometries[*i]) {
+                if renderer.checkerboard_shaders.contains(&geometry.shader) {
+                    continue;
+                }
+                let shader = &renderer.shaders[geometry.shader].vulkan.pipeline_data;
+                Self::draw_bsp_geometry(renderer, bsp, &mut command_builder, &mut last_shader, geometry, fog.clone(), mvp.clone(), shader, &geometry.offset, GeometryPass::ReflectionProbe)?;
+            }
+
+            probe.end_rendering(&mut command_builder)?;
+
+            let face_offset = face as u64 * face_size;
+            command_builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                probe.staging().image().clone(),
+                buffer.clone().slice(face_offset..face_offset + face_size)
+            ))?;
+        }
+
+        let commands = command_builder.build()?;
+        renderer.vulkan.execute_command_list(commands)?;
+        renderer.vulkan.wait_for_completion()?;
+
+        let data = buffer
+            .read()
+            .map_err(|e| Error::from_vulkan_error(format!("Failed to read reflection probe: {e}")))?
+            .to_vec();
+        Ok(data)
+    }
+
+    pub fn get_last_frame_image(&self) -> Option<Arc<Image>> {
+        let images = &self.swapchain_image_views[self.last_image_index? as usize];
+        Some(images.staging().image().clone())
+    }
+
+    fn wait_for_completion(&mut self) -> MResult<()> {
+        let window_fences = self.windows.iter_mut().flatten().flat_map(|w| w.image_fences.iter_mut());
+        for fence in self.image_fences.iter_mut().chain(window_fences).filter_map(Option::take) {
+            wait_for_fence(&fence)?;
+        }
+        if let Some(upload) = self.pending_upload.take() {
+            wait_for_fence(&upload)?;
+        }
+        Ok(())
+    }
+
+    /// Take the uploads submitted since the last frame for the next submission to wait on.
+    ///
+    /// If they already finished, this starts over from now.
+    fn take_pending_upload(&mut self) -> Box<dyn GpuFuture + Send + Sync> {
+        match self.pending_upload.take() {
+            Some(upload) if !upload.is_signaled().unwrap_or(false) => upload.boxed_send_sync(),
+            _ => vulkano::sync::now(self.device.clone()).boxed_send_sync()
+        }
+    }
+
+    fn make_swapchain_images(swapchain_images: Vec<Arc<Image>>, memory_allocator: Arc<StandardMemoryAllocator>, samples_per_pixel: SampleCount, renderer_parameters: &RendererParameters) -> MResult<Vec<Arc<SwapchainImages>>> {
+        let RendererParameters { render_scale, ssao, water_reflections, depth_of_field, motion_blur, taa, post_processing, .. } = *renderer_parameters;
+        assert!(render_scale > 0.0);
+
+        let device = memory_allocator.device();
+        // Post effects copy the scene, and they read the depth image along with SSAO.
+        let copy_scene = depth_of_field || motion_blur || taa || post_processing;
+        let sample_depth = ssao || copy_scene;
+        let depth_format = Self::depth_format(device, samples_per_pixel, sample_depth);
+        let mut shared_taa_history: Option<Arc<ImageView>> = None;
+
+        swapchain_images.iter().map(|i| {
+            let native_width = i.extent()[0];
+            let native_height = i.extent()[1];
+
+            let width;
+            let height;
+            if render_scale != 1.0 {
+                let max_width = device.physical_device().properties().max_framebuffer_width;
+                let max_height = device.physical_device().properties().max_framebuffer_height;
+
+                let attempted_width = ((native_width as f32) * render_scale.sqrt()) as u32;
+                let attempted_height = ((native_height as f32) * render_scale.sqrt()) as u32;
+
+                width = attempted_width.clamp(1, max_width);
+                height = attempted_height.clamp(1, max_height);
+
+                if width != attempted_width || height != attempted_height {
+                    eprintln!("Resolution {attempted_width}x{attempted_height} is not supported by the GPU... resizing");
+                }
+            }
+            else {
+                width = native_width;
+                height = native_height;
+            }
+
+            println!("Render resolution: {width}x{height} ({native_width}x{native_height}x{:.02}%)", render_scale * 100.0);
+
+            let output = ImageView::new_default(i.clone())?;
+            let color = ImageView::new_default(Image::new(
+                memory_allocator.clone(),
+                ImageCreateInfo {
+                    extent: [width, height, 1],
+                    format: OFFLINE_PIPELINE_COLOR_FORMAT,
+                    image_type: ImageType::Dim2d,
+                    samples: samples_per_pixel,
+                    usage: ImageUsage::TRANSFER_SRC | ImageUsage::COLOR_ATTACHMENT,
+                    ..Default::default()
+                },
+                AllocationCreateInfo::default(),
+            )?)?;
+
+            let depth = ImageView::new_default(Image::new(
+                memory_allocator.clone(),
+                ImageCreateInfo {
+                    extent: [width, height, 1],
+                    format: depth_format,
+                    image_type: ImageType::Dim2d,
+                    samples: samples_per_pixel,
+                    usage: if sample_depth {
+                        ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED
+                    }
+                    else {
+                        ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSFER_DST
+                    },
+                    ..Default::default()
+                },
+                AllocationCreateInfo::default(),
+            )?)?;
+
+            let depth_sampled = if depth_format.aspects().intersects(ImageAspects::STENCIL) {
+                ImageView::new(depth.image().clone(), ImageViewCreateInfo {
+                    subresource_range: ImageSubresourceRange {
+                        aspects: ImageAspects::DEPTH,
+                        ..depth.image().subresource_range()
+                    },
+                    ..ImageViewCreateInfo::from_image(depth.image())
+                })?
+            }
+            else {
+                depth.clone()
+            };
 
             let resolve = if samples_per_pixel != SampleCount::Sample1 {
                 Some(ImageView::new_default(Image::new(
@@ -321,7 +1330,7 @@ impl VulkanRenderer {
                         ..Default::default()
                     },
                     AllocationCreateInfo::default(),
-                ).unwrap()).unwrap())
+                )?)?)
             } else {
                 None
             };
@@ -340,133 +1349,391 @@ impl VulkanRenderer {
                             load_op: Load,
                             store_op: Store,
                         },
+                        // Stored since the depth image is read for SSAO, and mirrors are marked in the stencil
+                        // while the render pass is ended and restarted partway through a frame.
                         depth_stencil: {
                             format: depth_format,
                             samples: samples,
                             load_op: Load,
-                            store_op: DontCare,
+                            store_op: Store,
                         }
                     },
                     pass: {
                         color: [color],
                         depth_stencil: {depth_stencil},
                     },
-                ).expect("failed to make render pass");
+                )?;
+
+                let framebuffer = Framebuffer::new(render_pass, FramebufferCreateInfo {
+                    attachments: vec![
+                        color.clone(),
+                        depth.clone()
+                    ],
+                    extent: [width, height],
+                    ..Default::default()
+                })?;
+
+                Some(framebuffer)
+            }
+            else {
+                None
+            };
+
+            let overdraw = ImageView::new_default(Image::new(
+                memory_allocator.clone(),
+                ImageCreateInfo {
+                    extent: [width, height, 1],
+                    format: OVERDRAW_FORMAT,
+                    image_type: ImageType::Dim2d,
+                    samples: samples_per_pixel,
+                    usage: ImageUsage::TRANSFER_SRC | ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+                    ..Default::default()
+                },
+                AllocationCreateInfo::default(),
+            )?)?;
+
+            let overdraw_resolve = if samples_per_pixel != SampleCount::Sample1 {
+                Some(ImageView::new_default(Image::new(
+                    memory_allocator.clone(),
+                    ImageCreateInfo {
+                        extent: [width, height, 1],
+                        format: OVERDRAW_FORMAT,
+                        image_type: ImageType::Dim2d,
+                        samples: SampleCount::Sample1,
+                        usage: ImageUsage::TRANSFER_DST | ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo::default(),
+                )?)?)
+            } else {
+                None
+            };
+
+            let overdraw_framebuffer = if !device.enabled_extensions().khr_dynamic_rendering {
+                let depth_format = depth.image().format();
+                let samples = overdraw.image().samples();
+
+                let render_pass = single_pass_renderpass!(
+                    device.clone(),
+                    attachments: {
+                        overdraw: {
+                            format: OVERDRAW_FORMAT,
+                            samples: samples,
+                            load_op: Clear,
+                            store_op: Store,
+                        },
+                        depth_stencil: {
+                            format: depth_format,
+                            samples: samples,
+                            load_op: Clear,
+                            store_op: DontCare,
+                        }
+                    },
+                    pass: {
+                        color: [overdraw],
+                        depth_stencil: {depth_stencil},
+                    },
+                )?;
+
+                let framebuffer = Framebuffer::new(render_pass, FramebufferCreateInfo {
+                    attachments: vec![
+                        overdraw.clone(),
+                        depth.clone()
+                    ],
+                    extent: [width, height],
+                    ..Default::default()
+                })?;
+
+                Some(framebuffer)
+            }
+            else {
+                None
+            };
+
+            let ambient_occlusion = if ssao {
+                Some(ImageView::new_default(Image::new(
+                    memory_allocator.clone(),
+                    ImageCreateInfo {
+                        extent: [width, height, 1],
+                        format: AMBIENT_OCCLUSION_FORMAT,
+                        image_type: ImageType::Dim2d,
+                        samples: SampleCount::Sample1,
+                        usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo::default(),
+                )?)?)
+            } else {
+                None
+            };
+
+            let ambient_occlusion_framebuffer = match ambient_occlusion.as_ref() {
+                Some(ambient_occlusion) if !device.enabled_extensions().khr_dynamic_rendering => {
+                    let render_pass = single_pass_renderpass!(
+                        device.clone(),
+                        attachments: {
+                            ambient_occlusion: {
+                                format: AMBIENT_OCCLUSION_FORMAT,
+                                samples: 1,
+                                load_op: DontCare,
+                                store_op: Store,
+                            }
+                        },
+                        pass: {
+                            color: [ambient_occlusion],
+                            depth_stencil: {},
+                        },
+                    )?;
+
+                    let framebuffer = Framebuffer::new(render_pass, FramebufferCreateInfo {
+                        attachments: vec![
+                            ambient_occlusion.clone()
+                        ],
+                        extent: [width, height],
+                        ..Default::default()
+                    })?;
+
+                    Some(framebuffer)
+                },
+                _ => None
+            };
+
+            let scene_copy = if copy_scene {
+                Some(ImageView::new_default(Image::new(
+                    memory_allocator.clone(),
+                    ImageCreateInfo {
+                        extent: [width, height, 1],
+                        format: OFFLINE_PIPELINE_COLOR_FORMAT,
+                        image_type: ImageType::Dim2d,
+                        samples: SampleCount::Sample1,
+                        usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo::default(),
+                )?)?)
+            } else {
+                None
+            };
 
-                let framebuffer = Framebuffer::new(render_pass, FramebufferCreateInfo {
-                    attachments: vec![
-                        color.clone(),
-                        depth.clone()
-                    ],
-                    extent: [width, height],
-                    ..Default::default()
-                }).expect("failed to make framebuffer");
+            let taa_history = match &shared_taa_history {
+                Some(history) => Some(history.clone()),
+                None if taa => {
+                    let history = ImageView::new_default(Image::new(
+                        memory_allocator.clone(),
+                        ImageCreateInfo {
+                            extent: [width, height, 1],
+                            format: OFFLINE_PIPELINE_COLOR_FORMAT,
+                            image_type: ImageType::Dim2d,
+                            samples: SampleCount::Sample1,
+                            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+                            ..Default::default()
+                        },
+                        AllocationCreateInfo::default(),
+                    )?)?;
+                    Some(shared_taa_history.insert(history).clone())
+                },
+                None => None
+            };
 
-                Some(framebuffer)
-            }
-            else {
+            let reflection = if water_reflections {
+                Some(Self::make_reflection_images(memory_allocator.clone(), &color, &depth, [(width / 2).max(1), (height / 2).max(1)])?)
+            } else {
                 None
             };
 
-            Arc::new(SwapchainImages {
+            Ok(Arc::new(SwapchainImages {
                 output,
                 color,
                 depth,
+                depth_sampled,
                 resolve,
-                framebuffer
-            })
+                framebuffer,
+                overdraw,
+                overdraw_resolve,
+                overdraw_framebuffer,
+                ambient_occlusion,
+                ambient_occlusion_framebuffer,
+                scene_copy,
+                taa_history,
+                reflection
+            }))
         }).collect()
     }
 
-    fn draw_frame_infallible(renderer: &mut Renderer, image_index: u32, image_future: SwapchainAcquireFuture) -> bool {
-        let currently_loaded_bsp = renderer
-            .current_bsp
-            .as_ref()
-            .and_then(|f| renderer.bsps.get(f))
-            .map(|b| b.clone());
-
-        let mut command_builder = AutoCommandBufferBuilder::primary(
-            &renderer.vulkan.command_buffer_allocator,
-            renderer.vulkan.queue.queue_family_index(),
-            CommandBufferUsage::OneTimeSubmit
-        ).expect("failed to init command builder");
-
-        let images = renderer.vulkan.swapchain_image_views[image_index as usize].clone();
-        image_future.wait(Some(Duration::from_millis(5000))).expect("waited too long");
-        renderer.vulkan.future.as_mut().unwrap().cleanup_finished();
+    /// Get the format to use for depth images.
+    ///
+    /// A stencil aspect is used if the device supports it with the given settings, otherwise mirrors aren't drawn.
+    fn depth_format(device: &Arc<Device>, samples_per_pixel: SampleCount, sampled: bool) -> Format {
+        let mut required_features = FormatFeatures::DEPTH_STENCIL_ATTACHMENT | FormatFeatures::TRANSFER_DST;
+        if sampled {
+            required_features |= FormatFeatures::SAMPLED_IMAGE;
+        }
 
-        let [width, height, ..] = images.color.image().extent();
-        let (width, height) = (width as f32, height as f32);
+        let physical_device = device.physical_device();
+        let supported = physical_device
+            .format_properties(DEPTH_STENCIL_FORMAT)
+            .is_ok_and(|p| p.optimal_tiling_features.contains(required_features))
+            && physical_device.properties().framebuffer_stencil_sample_counts.contains_enum(samples_per_pixel);
 
-        command_builder.clear_depth_stencil_image(ClearDepthStencilImageInfo {
-            clear_value: ClearDepthStencilValue::from(1.0),
-            ..ClearDepthStencilImageInfo::image(images.depth.clone().image().clone())
-        }).expect("failed to clear depth image");
+        if supported {
+            DEPTH_STENCIL_FORMAT
+        }
+        else {
+            Format::D32_SFLOAT
+        }
+    }
 
-        for i in 0..renderer.player_viewports.len() {
-            let player_viewport = renderer.player_viewports[i];
+    /// Make images for drawing water reflections or reflection probes at `extent`, matching the formats and sample counts of `color` and
+    /// `depth`.
+    fn make_reflection_images(memory_allocator: Arc<StandardMemoryAllocator>, color: &Arc<ImageView>, depth: &Arc<ImageView>, extent: [u32; 2]) -> MResult<ReflectionImages> {
+        let device = memory_allocator.device();
+        let samples = color.image().samples();
 
-            let viewport = Viewport {
-                offset: [player_viewport.rel_x * width, player_viewport.rel_y * height],
-                extent: [player_viewport.rel_width * width, player_viewport.rel_height * height],
-                depth_range: 0.0..=1.0,
-            };
+        let make_image = |format: Format, samples: SampleCount, usage: ImageUsage| -> MResult<Arc<ImageView>> {
+            Ok(ImageView::new_default(Image::new(
+                memory_allocator.clone(),
+                ImageCreateInfo {
+                    extent: [extent[0], extent[1], 1],
+                    format,
+                    image_type: ImageType::Dim2d,
+                    samples,
+                    usage,
+                    ..Default::default()
+                },
+                AllocationCreateInfo::default(),
+            )?)?)
+        };
 
-            Self::draw_viewport(
-                renderer,
-                &images,
-                viewport,
-                &currently_loaded_bsp,
-                &mut command_builder,
-                &player_viewport,
-                player_viewport.camera.clone()
-            );
+        let (reflection_color, resolve) = if samples != SampleCount::Sample1 {
+            (
+                make_image(OFFLINE_PIPELINE_COLOR_FORMAT, samples, ImageUsage::TRANSFER_SRC | ImageUsage::COLOR_ATTACHMENT)?,
+                Some(make_image(OFFLINE_PIPELINE_COLOR_FORMAT, SampleCount::Sample1, ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED)?)
+            )
         }
+        else {
+            (make_image(OFFLINE_PIPELINE_COLOR_FORMAT, samples, ImageUsage::TRANSFER_SRC | ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED)?, None)
+        };
+        let reflection_depth = make_image(depth.image().format(), samples, ImageUsage::DEPTH_STENCIL_ATTACHMENT)?;
+
+        // Load and store operations don't affect render pass compatibility, so pipelines made for the main render pass
+        // can draw in this one.
+        let framebuffer = if !device.enabled_extensions().khr_dynamic_rendering {
+            let render_pass = single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        format: OFFLINE_PIPELINE_COLOR_FORMAT,
+                        samples: samples,
+                        load_op: Clear,
+                        store_op: Store,
+                    },
+                    depth_stencil: {
+                        format: depth.image().format(),
+                        samples: samples,
+                        load_op: Clear,
+                        store_op: DontCare,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {depth_stencil},
+                },
+            )?;
+
+            let framebuffer = Framebuffer::new(render_pass, FramebufferCreateInfo {
+                attachments: vec![
+                    reflection_color.clone(),
+                    reflection_depth.clone()
+                ],
+                extent,
+                ..Default::default()
+            })?;
 
-        if renderer.player_viewports.len() > 1 {
-            images.begin_rendering(&mut command_builder);
-            Self::draw_split_screen_bars(renderer, &mut command_builder, width, height);
-            images.end_rendering(&mut command_builder);
+            Some(framebuffer)
         }
+        else {
+            None
+        };
 
-        if renderer.debug_font.is_some() {
-            let debug_data = renderer.debug_text.iter().last().expect("where????");
-            images.begin_rendering(&mut command_builder);
-            draw_sprite(renderer, 0.0, 0.0, (renderer.vulkan.current_resolution.height as f32) / 480.0, &debug_data.bitmaps[0].vulkan.image, &mut command_builder).expect("could not draw debug shit");
-            images.end_rendering(&mut command_builder);
-        }
+        Ok(ReflectionImages {
+            color: reflection_color,
+            depth: reflection_depth,
+            resolve,
+            framebuffer
+        })
+    }
 
-        let staging_image = if let Some(resolved_color_view) = images.resolve.as_ref().map(|iv| iv.image()) {
-            command_builder.resolve_image(
-                ResolveImageInfo::images(images.color.image().clone(), resolved_color_view.clone())
-            ).expect("resolve fail");
-            resolved_color_view
+    /// Draw the viewports of a window to one of its swapchain images, then present it (if not headless).
+    fn draw_frame_to_image(renderer: &mut Renderer, window: usize, image_index: u32, image_future: Option<SwapchainAcquireFuture>) -> MResult<FrameStatus> {
+        let command_builder = AutoCommandBufferBuilder::primary(
+            &renderer.vulkan.command_buffer_allocator,
+            renderer.vulkan.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit
+        )?;
+
+        let images = renderer.vulkan.window_images(window)[image_index as usize].clone();
+
+        // The render targets of this image may still be in use by the last frame drawn to it, but frames drawn to
+        // other images can keep going.
+        if let Some(fence) = renderer.vulkan.window_image_fences(window).get_mut(image_index as usize).and_then(Option::take) {
+            wait_for_fence(&fence)?;
         }
-        else {
-            images.color.image()
-        };
 
-        command_builder.blit_image(BlitImageInfo {
-            filter: Filter::Linear,
-            ..BlitImageInfo::images(staging_image.clone(), images.output.image().clone())
-        }).unwrap();
+        let shares_targets = images.has_shared_targets();
+        let [width, height, ..] = images.color.image().extent();
+        let mut context = FrameContext {
+            command_builder,
+            images,
+            window,
+            width: width as f32,
+            height: height as f32,
+            viewport: None
+        };
 
-        let commands = command_builder.build().expect("failed to build command builder");
+        // Drawing the viewports replaces the statistics of the last frame, so these are added afterward.
+        renderer.vulkan.frame_counters.take();
+        let render_graph = renderer.vulkan.render_graph.clone();
+        render_graph.execute(renderer, &mut context)?;
+        renderer.frame_statistics += renderer.vulkan.frame_counters.take();
+        let command_builder = context.command_builder;
+
+        let commands = command_builder.build()?;
+
+        // Targets shared with the other images (e.g. the TAA history) are also used by the last frame, whichever image
+        // it was drawn to, so it has to finish first.
+        if shares_targets {
+            let last_image_index = renderer.vulkan.window_last_image_index(window);
+            if let Some(fence) = last_image_index.and_then(|i| renderer.vulkan.window_image_fences(window).get_mut(i as usize)?.take()) {
+                wait_for_fence(&fence)?;
+            }
+        }
 
-        let future = renderer.vulkan
-            .future
-            .take()
-            .expect("there's no future :(");
+        let (Some(image_future), Some(swapchain)) = (image_future, renderer.vulkan.window_swapchain(window)) else {
+            // Headless; nothing to present, so just wait for the frame to finish so it can be read back immediately
+            let future = renderer.vulkan
+                .take_pending_upload()
+                .then_execute(renderer.vulkan.queue.clone(), commands)?
+                .boxed_send_sync()
+                .then_signal_fence_and_flush()
+                .map_err(Error::from_vulkan_submit_error)?;
+            wait_for_fence(&future)?;
+            renderer.vulkan.last_image_index = Some(image_index);
+            return Ok(FrameStatus::Drawn)
+        };
 
-        let swapchain_present = SwapchainPresentInfo::swapchain_image_index(renderer.vulkan.swapchain.clone(), image_index);
+        let swapchain_present = SwapchainPresentInfo::swapchain_image_index(swapchain, image_index);
 
-        let future = future
+        let future = renderer.vulkan
+            .take_pending_upload()
             .join(image_future)
-            .then_execute(renderer.vulkan.queue.clone(), commands.clone())
-            .expect("can't execute commands")
+            .then_execute(renderer.vulkan.queue.clone(), commands.clone())?
             .then_swapchain_present(renderer.vulkan.queue.clone(), swapchain_present)
+            .boxed_send_sync()
             .then_signal_fence();
 
+        #[cfg(target_os = "macos")]
+        let flush_start = Instant::now();
+
         loop {
             match future.flush() {
                 Ok(()) => break,
@@ -474,139 +1741,85 @@ impl VulkanRenderer {
                 Err(Validated::ValidationError(v)) if v.problem.starts_with("access to a resource has been denied") => {
                     // Workaround for macOS.
                     //
-                    // Sometimes even though we called cleanup_finished() and waited for the swapchain image, the images
-                    // are still considered in use when they clearly shouldn't be.
+                    // Sometimes even though we waited for the last frame drawn to this image, the images are still
+                    // considered in use when they clearly shouldn't be. Retry until they aren't, but give up after as
+                    // long as waiting on a fence would.
+                    if flush_start.elapsed() >= FENCE_TIMEOUT {
+                        return Err(Error::from_vulkan_error(format!("Timed out after {} ms waiting for the images to be released", FENCE_TIMEOUT.as_millis())))
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
                     continue;
                 },
-                Err(Validated::Error(VulkanError::OutOfDate)) => {
-                    renderer.vulkan.future = Some(vulkano::sync::now(renderer.vulkan.device.clone()).boxed_send_sync());
-                    return false
-                },
-                Err(e) => {
-                    panic!("Oh, shit! Some bullshit just happened: {e:?}")
-                }
+                Err(Validated::Error(VulkanError::OutOfDate)) => return Ok(FrameStatus::ResizeRequired),
+                Err(e) => return Err(Error::from_vulkan_submit_error(e))
             }
         }
 
-        renderer.vulkan.future = Some(future.boxed_send_sync());
-        true
+        renderer.vulkan.window_image_fences(window)[image_index as usize] = Some(future);
+        renderer.vulkan.set_window_last_image_index(window, image_index);
+        Ok(FrameStatus::Drawn)
     }
 
-    fn draw_viewport(
-        renderer: &mut Renderer,
-        images: &Arc<SwapchainImages>,
-        viewport: Viewport,
-        currently_loaded_bsp: &Option<Arc<BSP>>,
+    /// Draw debug primitives, scenario debug markers, and world-space debug text in the current viewport.
+    fn draw_viewport_debug_primitives(
+        renderer: &Renderer,
+        debug_geometry: &DebugGeometry,
         command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
-        player_viewport: &PlayerViewport,
-        camera: Camera
-    ) {
-        command_builder.set_viewport(0, [viewport.clone()].into_iter().collect()).unwrap();
-        images.begin_rendering(command_builder);
-
-        let aspect_ratio = viewport.extent[0] / viewport.extent[1];
-        let [z_near, mut z_far] = player_viewport.draw_distance;
-
-        let mut fog_data = player_viewport
-            .viewport_fog
-            .map(|f| f.current_fog_data)
-            .unwrap_or_default();
-
-        if !player_viewport.camera.fog {
-            fog_data.max_opacity = 0.0;
-            fog_data.min_opacity = 0.0;
-            z_far = MAX_DRAW_DISTANCE_LIMIT;
-        }
-
-        let sky_color = [fog_data.color[0], fog_data.color[1], fog_data.color[2], 1.0];
-        draw_box(
-            renderer,
-            0.0,
-            0.0,
-            1.0,
-            1.0,
-            sky_color,
-            command_builder
-        ).unwrap();
-
-        let proj = Mat4::perspective_lh(
-            camera.fov,
-            aspect_ratio,
-            z_near,
-            z_far
-        );
-        let view = Mat4::look_to_lh(
-            camera.position.into(),
-            camera.rotation.into(),
-            Vec3::new(0.0, 0.0, -1.0)
-        );
-
-        let fog = make_fog_uniform(renderer, &fog_data);
-
-        let mut transparent_geometries: Vec<(usize, f32)> = Vec::with_capacity(256);
-
-        if let Some((bsp, buffers)) = currently_loaded_bsp.as_ref().and_then(|bsp| {
-            Some((bsp, bsp.vulkan.subbuffers.as_ref()?))
-        }) {
-            command_builder.bind_index_buffer(buffers.index_subbuffer.clone()).expect("failed to bind indices");
-            command_builder.bind_vertex_buffers(0, (
-                buffers.vertex_data_subbuffer.clone(),
-                buffers.texture_coords_subbuffer.clone(),
-                buffers.lightmap_texture_coords_subbuffer.clone()
-            )).expect("failed to bind vertex data");
-
-            let mvp = make_model_view_uniform(renderer, camera.position.into(), Vec3::default(), Mat3::IDENTITY, view, proj);
-
-            // Draw non-transparent shaders first
-            let mut last_shader = None;
-
-            let get_geometry_shader = |f: &usize| (&bsp.geometries[*f], &renderer.shaders[&bsp.geometries[*f].shader].vulkan.pipeline_data);
+        mvp: Arc<PersistentDescriptorSet>,
+        view_projection: Mat4,
+        viewport: &Viewport
+    ) -> MResult<()> {
+        draw_debug_geometry(renderer, debug_geometry, command_builder, mvp)?;
+
+        let now = Instant::now();
+        let labels: Vec<DebugText> = renderer
+            .scenario_debug_markers
+            .visible()
+            .filter_map(|m| Some(DebugText {
+                text: m.label.clone()?,
+                anchor: DebugTextAnchor::World(m.label_position()),
+                scale: 1.0,
+                color: m.category.color(),
+                expires: now
+            }))
+            .collect();
+
+        let mut vertices = Vec::new();
+        for text in renderer.debug_primitives.texts.iter().chain(labels.iter()) {
+            let DebugTextAnchor::World(position) = text.anchor else {
+                continue
+            };
 
-            for (geometry, shader) in bsp
-                .vulkan
-                .opaque_geometries
-                .iter()
-                .map(get_geometry_shader) {
-                Self::draw_bsp_geometry(renderer, bsp, command_builder, &camera, &mut last_shader, geometry, fog.clone(), mvp.clone(), shader, &geometry.offset);
+            let clip = view_projection * position.extend(1.0);
+            if clip.w <= 0.0 {
+                continue
             }
 
-            transparent_geometries.extend(bsp
-                .vulkan
-                .transparent_geometries
-                .iter()
-                .map(|i| (*i, Vec3::from(camera.position).distance_squared(Vec3::from(bsp.geometries[*i].centroid))))
-            );
-            transparent_geometries
-                .sort_by(|a,b| b.1.total_cmp(&a.1));
-
-            for (geometry, shader) in transparent_geometries
-                .iter()
-                .map(|b| &b.0)
-                .map(get_geometry_shader) {
-                if geometry.shader.ends_with("water") {
-                    // FIXME: water is not yet supported and the fallback shader is broken for it; should be fixed later
-                    continue;
-                }
-                Self::draw_bsp_geometry(renderer, bsp, command_builder, &camera, &mut last_shader, geometry, fog.clone(), mvp.clone(), shader, &geometry.offset);
-            }
+            // Round to whole pixels so each texel of the font covers the same number of pixels.
+            let [width, height] = debug_text_size(&text.text, text.scale);
+            let x = (clip.x / clip.w + 1.0) / 2.0 * viewport.extent[0] - width / 2.0;
+            let y = (clip.y / clip.w + 1.0) / 2.0 * viewport.extent[1] - height / 2.0;
+            push_debug_text_quads(&mut vertices, text, [x.round(), y.round()], viewport.extent);
         }
 
-        images.end_rendering(command_builder);
+        draw_debug_text_vertices(renderer, vertices, command_builder)
     }
 
     fn draw_bsp_geometry<'a, 'b>(
         renderer: &Renderer,
         currently_loaded_bsp: &'a BSP,
         mut command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
-        camera: &Camera,
-        last_shader: &'b mut Option<&'a Arc<String>>,
+        last_shader: &'b mut Option<(ShaderHandle, VulkanPipelineType)>,
         geometry: &'a BSPGeometry,
         fog_data: Arc<PersistentDescriptorSet>,
         mvp: Arc<PersistentDescriptorSet>,
         shader: &Arc<dyn VulkanMaterial>,
-        vertices: &VertexOffsets
-    ) {
-        let this_shader = &geometry.shader;
+        vertices: &VertexOffsets,
+        pass: GeometryPass
+    ) -> MResult<()> {
+        // The same shader may be drawn with a different pipeline if its geometry is farther away.
+        let pipeline_type = pass.pipeline(shader.get_main_pipeline());
+        let this_shader = (geometry.shader, pipeline_type);
         let repeat_shader = if *last_shader != Some(this_shader) && shader.can_reuse_descriptors() {
             false
         }
@@ -615,55 +1828,488 @@ impl VulkanRenderer {
         };
         *last_shader = Some(this_shader);
 
-        let main_pipeline = renderer.vulkan.pipelines.get(&shader.get_main_pipeline()).unwrap();
-        let mut desired_lightmap = geometry.lightmap_index;
-        if !camera.lightmaps {
-            desired_lightmap = None;
+        let main_pipeline = renderer.vulkan.pipelines.get(&pipeline_type).unwrap();
+        if !repeat_shader {
+            bind_pipeline(renderer, command_builder, main_pipeline.get_pipeline())?;
+            command_builder.set_cull_mode(pass.cull_mode())?;
         }
 
-        if !repeat_shader {
-            command_builder
-                .bind_pipeline_graphics(main_pipeline.get_pipeline())
-                .expect("tried to bind pipeline");
-            command_builder.set_cull_mode(CullMode::Back)
-                .expect("tried to set cull mode back to Back");
+        upload_main_material_uniform(&mut command_builder, main_pipeline.clone(), mvp.clone())?;
+        upload_fog_uniform(&mut command_builder, main_pipeline.clone(), fog_data.clone())?;
+        upload_lightmap_descriptor_set(geometry.lightmap_index, &currently_loaded_bsp, &mut command_builder, main_pipeline.clone())?;
+
+        shader.generate_commands(renderer, &vertices, repeat_shader, &mut command_builder)
+    }
+
+    /// Draw opaque BSP geometry mirrored across a water surface into the reflection images.
+    ///
+    /// `fog` must have a clip plane at the water so that nothing below it is drawn.
+    fn draw_water_reflection(
+        renderer: &Renderer,
+        currently_loaded_bsp: &BSP,
+        images: &SwapchainImages,
+        command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        viewport: &Viewport,
+        fog: Arc<PersistentDescriptorSet>,
+        mvp: Arc<PersistentDescriptorSet>,
+        clear_color: FloatColor,
+        statistics: &mut FrameStatistics
+    ) -> MResult<()> {
+        let reflection = images.reflection.as_ref().expect("water reflections are not enabled");
+
+        // The reflection images cover the same area as the color image at a lower resolution.
+        let color_extent = images.color.image().extent();
+        let reflection_extent = reflection.color.image().extent();
+        let scale_x = reflection_extent[0] as f32 / color_extent[0] as f32;
+        let scale_y = reflection_extent[1] as f32 / color_extent[1] as f32;
+        let reflection_viewport = Viewport {
+            offset: [viewport.offset[0] * scale_x, viewport.offset[1] * scale_y],
+            extent: [viewport.extent[0] * scale_x, viewport.extent[1] * scale_y],
+            depth_range: viewport.depth_range.clone()
+        };
+
+        reflection.begin_rendering(command_builder, clear_color)?;
+        command_builder.set_viewport(0, [reflection_viewport].into_iter().collect())?;
+
+        let mut last_shader = None;
+        for geometry in currently_loaded_bsp.vulkan.opaque_geometries.iter().map(|i| &currently_loaded_bsp.geometries[*i]) {
+            if renderer.checkerboard_shaders.contains(&geometry.shader) {
+                continue;
+            }
+            let shader = &renderer.shaders[geometry.shader].vulkan.pipeline_data;
+            Self::draw_bsp_geometry(renderer, currently_loaded_bsp, command_builder, &mut last_shader, geometry, fog.clone(), mvp.clone(), shader, &geometry.offset, GeometryPass::WaterReflection)?;
+            statistics.add_draw(&geometry.offset);
+        }
+
+        reflection.end_rendering(command_builder)
+    }
+
+    /// Draw what the nearest mirrors the camera can see reflect.
+    ///
+    /// Each mirror is marked in the stencil image and cleared, and then the opaque BSP is drawn mirrored across it where
+    /// it is marked. Mirrors are not drawn in reflections, so there is only one bounce.
+    fn draw_mirrors(
+        renderer: &Renderer,
+        currently_loaded_bsp: &BSP,
+        command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        player_viewport: &PlayerViewport,
+        fog_data: &FogData,
+        camera: &Camera,
+        view: Mat4,
+        proj: Mat4,
+        mvp: Arc<PersistentDescriptorSet>,
+        clear_color: FloatColor,
+        statistics: &mut FrameStatistics
+    ) -> MResult<()> {
+        let mut mirrors: Vec<&Mirror> = currently_loaded_bsp
+            .mirrors
+            .iter()
+            .filter(|m| m.distance(camera.position) > MIN_MIRROR_DISTANCE)
+            .collect();
+        mirrors.sort_by(|a, b| camera.position.distance_squared(a.center).total_cmp(&camera.position.distance_squared(b.center)));
+        mirrors.truncate(MAX_MIRRORS);
+
+        let mask = renderer.vulkan.pipelines[&VulkanPipelineType::MirrorMask].get_pipeline();
+        let clear = renderer.vulkan.pipelines[&VulkanPipelineType::MirrorClear].get_pipeline();
+        let restore = renderer.vulkan.pipelines[&VulkanPipelineType::MirrorRestore].get_pipeline();
+
+        for mirror in mirrors {
+            let mirror_geometry = &currently_loaded_bsp.geometries[mirror.geometry];
+
+            // Mark where the mirror is visible, then clear the color and depth there.
+            //
+            // Mirrors are culled like the rest of the BSP, so only the side that was drawn gets marked.
+            bind_pipeline(renderer, command_builder, mask.clone())?;
+            command_builder.set_cull_mode(CullMode::Back)?;
+            command_builder.bind_descriptor_sets(PipelineBindPoint::Graphics, mask.layout().clone(), 0, mvp.clone())?;
+            mirror_geometry.offset.make_vulkan_draw_command(command_builder)?;
+
+            bind_pipeline(renderer, command_builder, clear.clone())?;
+            command_builder.bind_descriptor_sets(PipelineBindPoint::Graphics, clear.layout().clone(), 0, mvp.clone())?;
+            command_builder.push_constants(clear.layout().clone(), 0, MirrorClearData { color: clear_color })?;
+            mirror_geometry.offset.make_vulkan_draw_command(command_builder)?;
+
+            let reflection = mirror.reflection();
+            let mirrored_camera = reflection.transform_point3(camera.position);
+            let clip_plane = mirror.clip_plane(camera.position);
+            let mirror_fog = make_fog_uniform(renderer, fog_data, player_viewport, camera.lighting_mode, mirrored_camera, clip_plane)?;
+            let mirror_mvp = make_model_view_uniform(renderer, mirrored_camera, Vec3::default(), Mat3::IDENTITY, view * reflection, proj)?;
+
+            let mut last_shader = None;
+            for (index, geometry) in currently_loaded_bsp.vulkan.opaque_geometries.iter().map(|i| (*i, &currently_loaded_bsp.geometries[*i])) {
+                let shader = &renderer.shaders[geometry.shader].vulkan.pipeline_data;
+                if shader.get_main_pipeline() != VulkanPipelineType::ShaderEnvironment
+                    || renderer.checkerboard_shaders.contains(&geometry.shader)
+                    || currently_loaded_bsp.mirrors.iter().any(|m| m.geometry == index) {
+                    continue;
+                }
+                Self::draw_bsp_geometry(renderer, currently_loaded_bsp, command_builder, &mut last_shader, geometry, mirror_fog.clone(), mirror_mvp.clone(), shader, &geometry.offset, GeometryPass::Mirror)?;
+                statistics.add_draw(&geometry.offset);
+            }
+
+            // Put back the mirror's depth so transparent geometry behind it stays hidden, and unmark it.
+            bind_pipeline(renderer, command_builder, restore.clone())?;
+            command_builder.set_cull_mode(CullMode::Back)?;
+            command_builder.bind_descriptor_sets(PipelineBindPoint::Graphics, restore.layout().clone(), 0, mvp.clone())?;
+            mirror_geometry.offset.make_vulkan_draw_command(command_builder)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draw water, mixing in the reflection images by `water_data.reflectivity`.
+    fn draw_water_geometry(
+        renderer: &Renderer,
+        command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        geometry: &BSPGeometry,
+        fog: Arc<PersistentDescriptorSet>,
+        mvp: Arc<PersistentDescriptorSet>,
+        shader: &Arc<dyn VulkanMaterial>,
+        reflection: Arc<PersistentDescriptorSet>,
+        water_data: WaterData
+    ) -> MResult<()> {
+        let pipeline = renderer.vulkan.pipelines[&VulkanPipelineType::Water].clone();
+        let layout = pipeline.get_pipeline().layout().clone();
+        bind_pipeline(renderer, command_builder, pipeline.get_pipeline())?;
+        command_builder.set_cull_mode(CullMode::Back)?;
+        upload_main_material_uniform(command_builder, pipeline.clone(), mvp)?;
+        upload_fog_uniform(command_builder, pipeline, fog)?;
+        command_builder.bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            layout.clone(),
+            4,
+            reflection
+        )?;
+        command_builder.push_constants(layout, 0, water_data)?;
+        shader.generate_commands(renderer, &geometry.offset, false, command_builder)
+    }
+
+    fn draw_bsp_debug_view<'a>(
+        renderer: &Renderer,
+        geometries: impl Iterator<Item = &'a BSPGeometry>,
+        command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        mode: u32,
+        mvp: Arc<PersistentDescriptorSet>
+    ) -> MResult<()> {
+        let pipeline = renderer.vulkan.pipelines[&VulkanPipelineType::DebugView].get_pipeline();
+        bind_pipeline(renderer, command_builder, pipeline.clone())?;
+        command_builder.set_cull_mode(CullMode::Back)?;
+        command_builder.bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            mvp
+        )?;
+        command_builder.push_constants(pipeline.layout().clone(), 0, DebugViewData { base_map_size: [0.0, 0.0], mode })?;
+
+        for geometry in geometries {
+            if mode == TEXEL_DENSITY_MODE {
+                let base_map_size = Self::base_map_size(renderer, geometry);
+                command_builder.push_constants(pipeline.layout().clone(), 0, DebugViewData { base_map_size, mode })?;
+            }
+            geometry.offset.make_vulkan_draw_command(command_builder)?;
+        }
+        Ok(())
+    }
+
+    /// Get the size of the base map bound for `geometry`'s shader multiplied by its UV scale, or zero if it has none.
+    fn base_map_size(renderer: &Renderer, geometry: &BSPGeometry) -> [f32; 2] {
+        let Some((path, [scale_x, scale_y])) = renderer.shaders[geometry.shader].parameter.base_map() else {
+            return [0.0, 0.0]
+        };
+        let resolution = renderer.get_bitmap_or_fallback(path, 0, BitmapType::Dim2D).resolution;
+        [resolution.width as f32 * scale_x, resolution.height as f32 * scale_y]
+    }
+
+    fn draw_bsp_overdraw(
+        renderer: &Renderer,
+        currently_loaded_bsp: &BSP,
+        images: &SwapchainImages,
+        command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        mvp: Arc<PersistentDescriptorSet>,
+        statistics: &mut FrameStatistics
+    ) -> MResult<()> {
+        images.begin_overdraw_rendering(command_builder)?;
+
+        // Depth is tested like it normally is, so only fragments that would actually be drawn are counted.
+        for (pipeline_type, geometries) in [
+            (VulkanPipelineType::OverdrawCountOpaque, &currently_loaded_bsp.vulkan.opaque_geometries),
+            (VulkanPipelineType::OverdrawCountTransparent, &currently_loaded_bsp.vulkan.transparent_geometries)
+        ] {
+            let pipeline = renderer.vulkan.pipelines[&pipeline_type].get_pipeline();
+            bind_pipeline(renderer, command_builder, pipeline.clone())?;
+            command_builder.set_cull_mode(CullMode::Back)?;
+            command_builder.bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                pipeline.layout().clone(),
+                0,
+                mvp.clone()
+            )?;
+
+            for geometry in geometries.iter().map(|i| &currently_loaded_bsp.geometries[*i]) {
+                if images.reflection.is_none() && renderer.shaders[geometry.shader].shader_type == ShaderType::TransparentWater {
+                    // Water is skipped when drawing normally, too
+                    statistics.geometries_culled += 1;
+                    continue;
+                }
+                geometry.offset.make_vulkan_draw_command(command_builder)?;
+                statistics.add_draw(&geometry.offset);
+                statistics.geometries_drawn += 1;
+            }
+        }
+
+        images.end_rendering(command_builder)?;
+
+        if let Some(overdraw_resolve) = images.overdraw_resolve.as_ref() {
+            command_builder.resolve_image(
+                ResolveImageInfo::images(images.overdraw.image().clone(), overdraw_resolve.image().clone())
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_hud(
+        renderer: &Renderer,
+        viewport_index: usize,
+        viewport: Viewport,
+        command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>
+    ) -> MResult<()> {
+        // Lens flares and nav points go under everything else since they are of things in the world.
+        let mut draws = Self::make_lens_flare_draws(renderer, viewport_index, &viewport);
+        draws.extend(Self::make_nav_point_draws(renderer, viewport_index, &viewport)?);
+        draws.extend(renderer.hud_draws.iter().filter(|d| d.viewport == viewport_index).cloned());
+        if draws.is_empty() {
+            return Ok(())
+        }
+
+        let mut vertices = Vec::with_capacity(draws.len() * 6);
+        for draw in &draws {
+            let ([x, y], [width, height]) = draw.pixel_rect(viewport.extent);
+            let [left, top, right, bottom] = draw.texture_coords;
+            let corner = |cx: f32, cy: f32| VulkanHudVertex {
+                position: [(x + cx * width) / viewport.extent[0], (y + cy * height) / viewport.extent[1]],
+                texture_coords: [left.lerp(right, cx), top.lerp(bottom, cy)],
+                color: draw.parameter.tint
+            };
+            vertices.extend_from_slice(&[
+                corner(0.0, 0.0), corner(0.0, 1.0), corner(1.0, 1.0),
+                corner(0.0, 0.0), corner(1.0, 1.0), corner(1.0, 0.0)
+            ]);
+        }
+
+        let vertices = Buffer::from_iter(
+            renderer.vulkan.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            default_allocation_create_info(),
+            vertices
+        )?;
+
+        command_builder.set_viewport(0, [viewport].into_iter().collect())?;
+        command_builder.set_cull_mode(CullMode::None)?;
+        command_builder.bind_vertex_buffers(0, vertices)?;
+
+        let mut last_pipeline_type = None;
+        let mut index = 0;
+        while index < draws.len() {
+            let draw = &draws[index];
+
+            // Consecutive draws from the same image, such as the characters of a string, can be drawn at once.
+            let count = draws[index..]
+                .iter()
+                .take_while(|d| d.image == draw.image && d.parameter.blend_mode == draw.parameter.blend_mode && d.meter == draw.meter)
+                .count();
+            let first_vertex = (index * 6) as u32;
+            index += count;
+
+            // The bitmap or font may have been replaced since the draw was queued.
+            let image = match &draw.image {
+                HudImage::Bitmap { handle, index: bitmap_index } => renderer.bitmaps.get(*handle).and_then(|b| b.bitmaps.get(*bitmap_index)),
+                HudImage::Font(path) => renderer.fonts.get(path).map(|f| &f.atlas.bitmaps[0])
+            };
+            let Some(image) = image else {
+                continue
+            };
+
+            let pipeline_type = match (draw.meter, draw.parameter.blend_mode) {
+                (Some(_), _) => VulkanPipelineType::HudMeter,
+                (None, HudBlendMode::AlphaBlend) => VulkanPipelineType::HudAlphaBlend,
+                (None, HudBlendMode::Add) => VulkanPipelineType::HudAdd,
+                (None, HudBlendMode::Multiply) => VulkanPipelineType::HudMultiply
+            };
+            let pipeline = renderer.vulkan.pipelines[&pipeline_type].get_pipeline();
+            if last_pipeline_type != Some(pipeline_type) {
+                bind_pipeline(renderer, command_builder, pipeline.clone())?;
+                last_pipeline_type = Some(pipeline_type);
+            }
+
+            let set = allocate_descriptor_set(
+                renderer,
+                pipeline.layout().set_layouts()[0].clone(),
+                [
+                    WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
+                    WriteDescriptorSet::image_view(1, ImageView::new_default(image.vulkan.image.clone())?),
+                ]
+            )?;
+            command_builder.bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                pipeline.layout().clone(),
+                0,
+                set
+            )?;
+            if let Some(meter) = draw.meter {
+                command_builder.push_constants(pipeline.layout().clone(), 0, HudMeterData {
+                    empty_color: meter.empty_color,
+                    value: meter.value
+                })?;
+            }
+            command_builder.draw((count * 6) as u32, 1, first_vertex, 0)?;
+        }
+
+        Ok(())
+    }
+
+    fn make_lens_flare_draws(renderer: &Renderer, viewport_index: usize, viewport: &Viewport) -> Vec<HudDraw> {
+        let mut draws = Vec::new();
+        let Some((sky, visibility)) = renderer.lens_flares.get(viewport_index) else {
+            return draws
+        };
+        let Some(sky) = renderer.skies.get(sky) else {
+            return draws
+        };
+
+        let player_viewport = &renderer.player_viewports[viewport_index];
+        let [width, height] = viewport.extent;
+        let z_near = player_viewport.draw_distance[0];
+        let (view, proj) = view_projection(player_viewport, &player_viewport.camera, width / height, z_near);
+        let view_projection = proj * view;
+
+        for (lens_flare, visibility) in sky.lens_flares.iter().zip(visibility) {
+            if *visibility <= 0.0 {
+                continue
+            }
+            let Some(handle) = renderer.bitmaps.get_handle(&lens_flare.bitmap) else {
+                continue
+            };
+
+            // Lights are infinitely far away, so project the direction toward the light instead of a point.
+            let clip = view_projection * (-lens_flare.direction).extend(0.0);
+            if clip.w <= 0.0 {
+                continue
+            }
+            let light = Vec2::new(clip.x, clip.y) / clip.w;
+            if light.x.abs() > 1.0 || light.y.abs() > 1.0 {
+                continue
+            }
+
+            // Reflections are placed along the line from the light through the center of the viewport.
+            let light = light * Vec2::new(width, height) / 2.0;
+            for reflection in &lens_flare.reflections {
+                // The projection's Y scale is 1 / tan(vertical FoV / 2), whether or not the FoV was scaled.
+                let size = height * reflection.radius.tan() * proj.y_axis.y;
+                let center = light * (1.0 - reflection.position);
+                let [r, g, b, a] = reflection.color;
+                draws.push(HudDraw {
+                    viewport: viewport_index,
+                    image: HudImage::Bitmap { handle, index: reflection.bitmap_index },
+                    texture_coords: [0.0, 0.0, 1.0, 1.0],
+                    natural_size: [size, size],
+                    offset: [width / 2.0 + center.x - size / 2.0, height / 2.0 + center.y - size / 2.0],
+                    meter: None,
+                    parameter: HudDrawParameter {
+                        tint: [r, g, b, a * visibility],
+                        blend_mode: HudBlendMode::Add,
+                        ..Default::default()
+                    }
+                });
+            }
+        }
+
+        draws
+    }
+
+    fn make_nav_point_draws(renderer: &Renderer, viewport_index: usize, viewport: &Viewport) -> MResult<Vec<HudDraw>> {
+        let mut draws = Vec::new();
+        if renderer.hud_nav_points.is_empty() {
+            return Ok(draws)
         }
 
-        upload_main_material_uniform(&mut command_builder, main_pipeline.clone(), mvp.clone());
-        upload_fog_uniform(&mut command_builder, main_pipeline.clone(), fog_data.clone());
-        upload_lightmap_descriptor_set(desired_lightmap, &currently_loaded_bsp, &mut command_builder, main_pipeline.clone());
+        let player_viewport = &renderer.player_viewports[viewport_index];
+        let camera = player_viewport.camera;
+        let [width, height] = viewport.extent;
+        let z_near = player_viewport.draw_distance[0];
+        let (view, proj) = view_projection(player_viewport, &camera, width / height, z_near);
+        let view_projection = proj * view;
+
+        for nav_point in &renderer.hud_nav_points {
+            let [icon_width, icon_height] = nav_point.icon.natural_size;
+            let clip = view_projection * nav_point.position.extend(1.0);
+
+            // Position relative to the center of the viewport in pixels. Dividing by |w| keeps points behind the
+            // camera on the correct side instead of mirroring them.
+            let behind = clip.w <= 0.0;
+            let mut center = Vec2::new(clip.x, clip.y) / clip.w.abs().max(f32::EPSILON) * Vec2::new(width, height) / 2.0;
+
+            let max_x = (width / 2.0 - nav_point.parameter.edge_margin - icon_width / 2.0).max(0.0);
+            let max_y = (height / 2.0 - nav_point.parameter.edge_margin - icon_height / 2.0).max(0.0);
+            if behind || center.x.abs() > max_x || center.y.abs() > max_y {
+                if center == Vec2::ZERO {
+                    center = Vec2::Y;
+                }
+
+                // Move toward the center of the viewport until within the edges.
+                let scale_x = if center.x == 0.0 { f32::INFINITY } else { max_x / center.x.abs() };
+                let scale_y = if center.y == 0.0 { f32::INFINITY } else { max_y / center.y.abs() };
+                center *= scale_x.min(scale_y);
+            }
+
+            let [x, y] = [width / 2.0 + center.x, height / 2.0 + center.y];
+            let mut icon = nav_point.icon.clone();
+            icon.viewport = viewport_index;
+            icon.offset = [x - icon_width / 2.0, y - icon_height / 2.0];
+            draws.push(icon);
+
+            if let Some(font) = nav_point.parameter.distance_font.as_ref() {
+                let distance = camera.position.distance(nav_point.position);
+                renderer.make_hud_text_draws(viewport_index, font, &format!("{distance:.0}"), HudTextParameter {
+                    position: [x, y + icon_height / 2.0],
+                    alignment: HudTextAlignment::Center,
+                    color: nav_point.parameter.color,
+                    ..Default::default()
+                }, &mut draws)?;
+            }
+        }
 
-        shader
-            .generate_commands(renderer, &vertices, repeat_shader, &mut command_builder)
-            .expect("can't generate stage commands");
+        Ok(draws)
     }
 
-    fn draw_split_screen_bars(renderer: &Renderer, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, width: f32, height: f32) {
-        if renderer.player_viewports.len() <= 1 {
-            return;
+    /// Draw the lines between `viewport_count` viewports laid out as split screen.
+    fn draw_split_screen_bars(renderer: &Renderer, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, bars: &SplitScreenBars, viewport_count: usize, width: f32, height: f32) -> MResult<()> {
+        if viewport_count <= 1 {
+            return Ok(());
         }
 
-        let color = [0.0, 0.0, 0.0, 1.0];
+        let color = bars.color;
         let viewport = Viewport {
             offset: [0.0, 0.0],
             extent: [width, height],
             depth_range: 0.0..=1.0,
         };
-        command_builder.set_viewport(0, [viewport].into_iter().collect()).unwrap();
+        command_builder.set_viewport(0, [viewport].into_iter().collect())?;
 
-        let base_thickness = 2.0;
         let scale = (width / 640.0).min(height / 480.0).max(1.0);
-        let line_thickness_horizontal = base_thickness / height * scale;
-        let line_thickness_vertical = base_thickness / width * scale;
+        let line_thickness_horizontal = bars.thickness / height * scale;
+        let line_thickness_vertical = bars.thickness / width * scale;
 
-        draw_box(renderer, 0.0, 0.5 - line_thickness_horizontal / 2.0, 1.0, line_thickness_horizontal, color, command_builder)
-            .expect("can't draw split screen vertical bar");
+        draw_box(renderer, 0.0, 0.5 - line_thickness_horizontal / 2.0, 1.0, line_thickness_horizontal, color, command_builder)?;
 
-        if renderer.player_viewports.len() > 2 {
+        if viewport_count > 2 {
             let y;
             let line_height;
 
-            if renderer.player_viewports.len() == 3 {
+            if viewport_count == 3 {
                 y = 0.5;
                 line_height = 0.5;
             } else {
@@ -671,26 +2317,26 @@ impl VulkanRenderer {
                 line_height = 1.0;
             }
 
-            draw_box(renderer, 0.5 - line_thickness_vertical / 2.0, y, line_thickness_vertical, line_height, color, command_builder)
-                .expect("can't draw split screen horizontal bar");
+            draw_box(renderer, 0.5 - line_thickness_vertical / 2.0, y, line_thickness_vertical, line_height, color, command_builder)?;
         }
-    }
 
-    fn execute_command_list(&mut self, command_buffer: Arc<impl PrimaryCommandBufferAbstract + 'static>) {
-        let execution = command_buffer.execute(self.queue.clone()).unwrap();
+        Ok(())
+    }
 
-        let future = self.future
-            .take()
-            .expect("no future?")
-            .join(execution)
+    fn execute_command_list(&mut self, command_buffer: Arc<impl PrimaryCommandBufferAbstract + 'static>) -> MResult<()> {
+        let upload = self
+            .take_pending_upload()
+            .then_execute(self.queue.clone(), command_buffer)?
+            .boxed_send_sync()
             .then_signal_fence_and_flush()
-            .expect("failed to signal/flush")
-            .boxed_send_sync();
+            .map_err(Error::from_vulkan_submit_error)?;
 
-        self.future = Some(future)
+        self.pending_upload = Some(upload);
+        Ok(())
     }
 
     fn generate_secondary_buffer_builder(&self) -> MResult<AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>> {
+        let depth_format = self.swapchain_image_views[0].depth.format();
         let result = AutoCommandBufferBuilder::secondary(
             &self.command_buffer_allocator,
             self.queue.queue_family_index(),
@@ -698,7 +2344,8 @@ impl VulkanRenderer {
             CommandBufferInheritanceInfo {
                 render_pass: Some(CommandBufferInheritanceRenderPassType::BeginRendering(CommandBufferInheritanceRenderingInfo {
                     color_attachment_formats: vec![Some(OFFLINE_PIPELINE_COLOR_FORMAT)],
-                    depth_attachment_format: Some(Format::D32_SFLOAT),
+                    depth_attachment_format: Some(depth_format),
+                    stencil_attachment_format: depth_format.aspects().intersects(ImageAspects::STENCIL).then_some(depth_format),
                     ..CommandBufferInheritanceRenderingInfo::default()
                 })),
                 ..CommandBufferInheritanceInfo::default()
@@ -712,6 +2359,69 @@ extern "C" {
     fn exit(code: i32) -> !;
 }
 
+/// Get the view and projection matrices of a viewport, using its overridden matrices if set.
+fn view_projection(player_viewport: &PlayerViewport, camera: &Camera, aspect_ratio: f32, z_near: f32) -> (Mat4, Mat4) {
+    player_viewport.view_projection.unwrap_or_else(|| {
+        // Reversed depth with an infinite far plane, which spreads the precision of floating point depth far more
+        // evenly than regular depth does.
+        let proj = Mat4::perspective_infinite_reverse_lh(
+            player_viewport.fov_scaling.vertical_fov(camera.fov, aspect_ratio),
+            aspect_ratio,
+            z_near
+        );
+        let view = Mat4::look_to_lh(
+            camera.position,
+            camera.rotation,
+            Vec3::new(0.0, 0.0, -1.0)
+        );
+        (view, proj)
+    })
+}
+
+/// Forward and down directions of the views of each face of a cubemap, in the order cubemap bitmaps are given.
+///
+/// Cubemap bitmaps go around the horizon before the top and bottom, so their second and third faces are swapped when
+/// uploaded.
+const CUBEMAP_FACE_DIRECTIONS: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::NEG_Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_X, Vec3::NEG_Y),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::NEG_Y),
+    (Vec3::NEG_Z, Vec3::NEG_Y)
+];
+
+/// Get the fog of a viewport, and the color of the background as seen from `position`.
+fn viewport_fog(player_viewport: &PlayerViewport, position: Vec3) -> (FogData, FloatColor) {
+    let mut fog_data = player_viewport
+        .fog_override
+        .or(player_viewport.viewport_fog.map(|f| f.current_fog_data))
+        .unwrap_or_default();
+
+    if !player_viewport.camera.fog {
+        fog_data.max_opacity = 0.0;
+        fog_data.min_opacity = 0.0;
+    }
+
+    // The background is infinitely far away, so it is as fogged as the fog the camera is in.
+    let mut sky_color = Vec3::from(fog_data.color);
+    if let Some(planar_fog) = player_viewport.planar_fog.filter(|_| player_viewport.camera.fog) {
+        sky_color = sky_color.lerp(Vec3::from(planar_fog.color), planar_fog.density_at(position));
+    }
+    (fog_data, sky_color.extend(1.0).to_array())
+}
+
+/// Get which stages of environment shaders to skip for a geometry as seen from `position`.
+fn reduced_stages(player_viewport: &PlayerViewport, position: Vec3, geometry: &BSPGeometry) -> ReducedStages {
+    let lod = &player_viewport.shader_lod;
+    let distance = position.distance(Vec3::from(geometry.centroid)) - geometry.radius;
+    ReducedStages {
+        bump: distance > lod.bump_distance,
+        reflection: distance > lod.reflection_distance,
+        detail: distance > lod.detail_distance
+    }
+}
+
 fn default_allocation_create_info() -> AllocationCreateInfo {
     AllocationCreateInfo {
         memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
@@ -719,6 +2429,26 @@ fn default_allocation_create_info() -> AllocationCreateInfo {
     }
 }
 
+/// Get the value to clear `depth` to (infinitely far away, with no mirrors marked in the stencil if it has one).
+fn depth_clear_value(depth: &Arc<ImageView>) -> ClearValue {
+    if depth.format().aspects().intersects(ImageAspects::STENCIL) {
+        ClearValue::DepthStencil((0.0, 0))
+    }
+    else {
+        ClearValue::Depth(0.0)
+    }
+}
+
+/// Get the stencil attachment for dynamically rendering with `depth`, if it has a stencil aspect.
+fn stencil_attachment(depth: &Arc<ImageView>, load_op: AttachmentLoadOp, store_op: AttachmentStoreOp) -> Option<RenderingAttachmentInfo> {
+    depth.format().aspects().intersects(ImageAspects::STENCIL).then(|| RenderingAttachmentInfo {
+        load_op,
+        store_op,
+        clear_value: (load_op == AttachmentLoadOp::Clear).then(|| depth_clear_value(depth)),
+        ..RenderingAttachmentInfo::image_view(depth.clone())
+    })
+}
+
 impl<T: Display> From<Validated<T>> for Error {
     fn from(value: Validated<T>) -> Self {
         match value {
@@ -730,9 +2460,13 @@ impl<T: Display> From<Validated<T>> for Error {
 
 impl From<Box<ValidationError>> for Error {
     fn from(value: Box<ValidationError>) -> Self {
-        // FIXME: figure out a more graceful way to do this
-        eprintln!("Validation error! {value:?}\n\n-----------\n\nBACKTRACE:\n\n{}\n\n-----------\n\n", std::backtrace::Backtrace::force_capture());
-        std::process::abort();
+        Self::Device(DeviceError::Validation { backend: "Vulkan", error: value.to_string() })
+    }
+}
+
+impl From<CommandBufferExecError> for Error {
+    fn from(value: CommandBufferExecError) -> Self {
+        Self::from_vulkan_error(format!("Command buffer execution error! {value}"))
     }
 }
 
@@ -744,10 +2478,27 @@ impl From<vulkano::LoadingError> for Error {
 
 impl Error {
     fn from_vulkan_error(error: String) -> Self {
-        Self::GraphicsAPIError { backend: "Vulkan", error }
+        Self::Device(DeviceError::GraphicsAPI(GraphicsAPIError { backend: "Vulkan", error }))
+    }
+    fn from_vulkan_unsupported_error(reason: String) -> Self {
+        Self::Device(DeviceError::Unsupported { reason })
+    }
+
+    /// Convert an error from submitting work to the device, which is where a lost device gets reported.
+    fn from_vulkan_submit_error(error: impl Into<Validated<VulkanError>>) -> Self {
+        match error.into() {
+            Validated::Error(VulkanError::DeviceLost) => Self::Device(DeviceError::DeviceLost),
+            e => e.into()
+        }
     }
-    fn from_vulkan_impl_error(error: String) -> Self {
-        Self::GraphicsAPIError { backend: "Vulkan-IMPL", error }
+}
+
+/// Wait for a submission to finish, erroring if it takes longer than [`FENCE_TIMEOUT`].
+fn wait_for_fence(fence: &SubmissionFence) -> MResult<()> {
+    match fence.wait(Some(FENCE_TIMEOUT)) {
+        Ok(()) => Ok(()),
+        Err(Validated::Error(VulkanError::Timeout)) => Err(Error::from_vulkan_error(format!("Timed out after {} ms waiting for the device", FENCE_TIMEOUT.as_millis()))),
+        Err(e) => Err(Error::from_vulkan_submit_error(e))
     }
 }
 
@@ -756,9 +2507,9 @@ fn upload_lightmap_descriptor_set(
     bsp: &BSP,
     builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
     pipeline: Arc<dyn VulkanPipelineData>
-) {
+) -> MResult<()> {
     if !pipeline.has_lightmaps() {
-        return;
+        return Ok(());
     }
 
     let set = lightmap_index
@@ -770,29 +2521,31 @@ fn upload_lightmap_descriptor_set(
         pipeline.get_pipeline().layout().clone(),
         1,
         set
-    ).unwrap();
+    )?;
+    Ok(())
 }
 
 fn upload_main_material_uniform(
     builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
     pipeline: Arc<dyn VulkanPipelineData>,
     set: Arc<PersistentDescriptorSet>
-) {
+) -> MResult<()> {
     builder.bind_descriptor_sets(
         PipelineBindPoint::Graphics,
         pipeline.get_pipeline().layout().clone(),
         0,
         set
-    ).unwrap();
+    )?;
+    Ok(())
 }
 
 fn upload_fog_uniform(
     builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
     pipeline: Arc<dyn VulkanPipelineData>,
     set: Arc<PersistentDescriptorSet>
-) {
+) -> MResult<()> {
     if !pipeline.has_fog() {
-        return;
+        return Ok(());
     }
 
     builder.bind_descriptor_sets(
@@ -800,41 +2553,177 @@ fn upload_fog_uniform(
         pipeline.get_pipeline().layout().clone(),
         2,
         set
-    ).unwrap();
+    )?;
+    Ok(())
 }
 
+/// Make the fog descriptor set, which also holds the lighting mode, the sky's lights, and the dynamic lights nearest to
+/// the camera since they are per-viewport, too.
 fn make_fog_uniform(
     renderer: &Renderer,
-    fog: &FogData
-) -> Arc<PersistentDescriptorSet> {
+    fog: &FogData,
+    player_viewport: &PlayerViewport,
+    lighting_mode: LightingMode,
+    camera_position: Vec3,
+    clip_plane: [f32; 4]
+) -> MResult<Arc<PersistentDescriptorSet>> {
     let pipeline = renderer
         .vulkan
         .pipelines[&VulkanPipelineType::ShaderEnvironment]
         .get_pipeline();
 
-    let fog_data = VulkanFogData {
+    // Planar fog is disabled with a max density of 0.0.
+    let mut fog_data = VulkanFogData {
         sky_fog_to: fog.distance_to,
         sky_fog_from: fog.distance_from,
         sky_fog_min_opacity: fog.min_opacity,
         sky_fog_max_opacity: fog.max_opacity,
-        sky_fog_color: [fog.color[0], fog.color[1], fog.color[2], 1.0]
+        sky_fog_color: [fog.color[0], fog.color[1], fog.color[2], 1.0],
+        planar_fog_plane: [0.0, 0.0, 1.0, 0.0],
+        planar_fog_color: [0.0, 0.0, 0.0, 1.0],
+        planar_fog_max_density: 0.0,
+        planar_fog_opaque_distance: 1.0,
+        planar_fog_opaque_depth: Padded(1.0),
+        clip_plane
     };
 
+    if let Some(planar_fog) = player_viewport.planar_fog.filter(|_| player_viewport.camera.fog) {
+        let normal = Vec3::from(planar_fog.plane.angle);
+        let length = normal.length();
+        fog_data.planar_fog_plane = (normal / length).extend(planar_fog.plane.offset / length).to_array();
+        fog_data.planar_fog_color = [planar_fog.color[0], planar_fog.color[1], planar_fog.color[2], 1.0];
+        fog_data.planar_fog_max_density = planar_fog.max_density;
+        fog_data.planar_fog_opaque_distance = planar_fog.opaque_distance;
+        fog_data.planar_fog_opaque_depth = Padded(planar_fog.opaque_depth);
+    }
+
     let fog_uniform_buffer = Buffer::from_data(
         renderer.vulkan.memory_allocator.clone(),
         BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
         default_allocation_create_info(),
         fog_data
-    ).unwrap();
+    )?;
 
-    PersistentDescriptorSet::new(
-        renderer.vulkan.descriptor_set_allocator.as_ref(),
+    // Color animations are held at their start when rendering deterministically.
+    let now = (!renderer.deterministic).then(Instant::now);
+    let lights = renderer.scenes[player_viewport.scene].lights.nearest(camera_position);
+    let light_gels = make_light_gel_views(renderer, &lights)?;
+
+    let lighting_uniform_buffer = Buffer::from_data(
+        renderer.vulkan.memory_allocator.clone(),
+        BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
+        default_allocation_create_info(),
+        make_lighting_data(
+            &lights,
+            player_viewport,
+            lighting_mode,
+            renderer.lightmap_exposure * player_viewport.lightmap_exposure,
+            if renderer.lightmap_overbright { LIGHTMAP_OVERBRIGHT } else { 1.0 },
+            now
+        )
+    )?;
+
+    let set = allocate_descriptor_set(
+        renderer,
         pipeline.layout().set_layouts()[2].clone(),
         [
             WriteDescriptorSet::buffer(0, fog_uniform_buffer),
-        ],
-        []
-    ).unwrap()
+            WriteDescriptorSet::buffer(1, lighting_uniform_buffer),
+            WriteDescriptorSet::sampler(2, renderer.vulkan.default_2d_sampler.clone()),
+            WriteDescriptorSet::image_view_array(3, 0, light_gels),
+        ]
+    )?;
+    Ok(set)
+}
+
+fn make_lighting_data(
+    lights: &[&DynamicLight],
+    player_viewport: &PlayerViewport,
+    lighting_mode: LightingMode,
+    lightmap_exposure: f32,
+    lightmap_overbright: f32,
+    now: Option<Instant>
+) -> VulkanLightingData {
+    let mut vulkan_lights = [VulkanDynamicLight::default(); MAX_DYNAMIC_LIGHTS];
+    for (vulkan_light, light) in vulkan_lights.iter_mut().zip(lights.iter()) {
+        let parameter = &light.parameter;
+        let [red, green, blue] = light.color_at(now).map(|c| c * parameter.power);
+        vulkan_light.position_radius = parameter.position.extend(parameter.radius).to_array();
+        vulkan_light.color_falloff = [red, green, blue, parameter.falloff];
+        vulkan_light.cutoff = [0.0, 0.0, parameter.falloff_radius, 0.0];
+        if let LightType::Spot { direction, inner_angle, outer_angle } = parameter.light_type {
+            let inner_cutoff = inner_angle.cos();
+            let outer_cutoff = outer_angle.cos();
+
+            // smoothstep is undefined if both edges are equal, so keep them apart.
+            vulkan_light.direction_type = direction.normalize().extend(1.0).to_array();
+            vulkan_light.cutoff[0] = inner_cutoff.max(outer_cutoff + 0.0001);
+            vulkan_light.cutoff[1] = outer_cutoff;
+        }
+
+        // The gel's rotation is orthonormal, so its transpose rotates world space into gel space.
+        let gel_rotation = parameter.gel_rotation.transpose();
+        vulkan_light.gel_rotation = [
+            gel_rotation.x_axis.extend(0.0).to_array(),
+            gel_rotation.y_axis.extend(0.0).to_array(),
+            gel_rotation.z_axis.extend(0.0).to_array()
+        ];
+    }
+
+    // Sky light directions are already normalized.
+    let (sun_direction, sun_color) = match player_viewport.sun {
+        Some(sun) => {
+            let [red, green, blue] = sun.color.map(|c| c * sun.power);
+            (sun.direction.extend(1.0).to_array(), [red, green, blue, 1.0])
+        },
+        None => ([0.0; 4], [0.0; 4])
+    };
+    let ambient_color = match player_viewport.ambient {
+        Some(ambient) => {
+            let [red, green, blue] = ambient.color.map(|c| c * ambient.power);
+            [red, green, blue, 1.0]
+        },
+        None => [0.0; 4]
+    };
+
+    VulkanLightingData {
+        lighting_mode: lighting_mode as u32,
+        light_count: lights.len() as u32,
+        lightmap_exposure,
+        lightmap_overbright,
+        sun_direction,
+        sun_color,
+        ambient_color,
+        lights: vulkan_lights
+    }
+}
+
+/// Make a cube image view of each light's gel, using a white cube map for lights without one and unused lights.
+fn make_light_gel_views(renderer: &Renderer, lights: &[&DynamicLight]) -> MResult<Vec<Arc<ImageView>>> {
+    let make_cube_view = |image: &Arc<Image>| ImageView::new(
+        image.clone(),
+        ImageViewCreateInfo {
+            view_type: ImageViewType::Cube,
+            ..ImageViewCreateInfo::from_image(image)
+        }
+    );
+
+    let white = make_cube_view(&renderer.get_default_cubemap(DefaultType::White).vulkan.image)?;
+    let mut views = Vec::with_capacity(MAX_DYNAMIC_LIGHTS);
+    for index in 0..MAX_DYNAMIC_LIGHTS {
+        // Gels are validated when lights are added, but the bitmap may have been replaced since.
+        let gel = lights
+            .get(index)
+            .and_then(|l| l.parameter.gel.as_ref())
+            .and_then(|path| renderer.bitmaps.get_by_path(path))
+            .and_then(|b| b.bitmaps.first())
+            .filter(|b| b.bitmap_type == BitmapType::Cubemap);
+        match gel {
+            Some(gel) => views.push(make_cube_view(&gel.vulkan.image)?),
+            None => views.push(white.clone())
+        }
+    }
+    Ok(views)
 }
 
 fn make_model_view_uniform(
@@ -844,7 +2733,7 @@ fn make_model_view_uniform(
     rotation: Mat3,
     view: Mat4,
     proj: Mat4,
-) -> Arc<PersistentDescriptorSet> {
+) -> MResult<Arc<PersistentDescriptorSet>> {
     let pipeline = renderer.vulkan.pipelines[&VulkanPipelineType::ShaderEnvironment].get_pipeline();
     let model = Mat4::IDENTITY;
 
@@ -866,20 +2755,38 @@ fn make_model_view_uniform(
         BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
         default_allocation_create_info(),
         model_data
-    ).unwrap();
+    )?;
 
-    PersistentDescriptorSet::new(
-        renderer.vulkan.descriptor_set_allocator.as_ref(),
+    let set = allocate_descriptor_set(
+        renderer,
         pipeline.layout().set_layouts()[0].clone(),
         [
             WriteDescriptorSet::buffer(0, model_uniform_buffer),
-        ],
-        []
-    ).unwrap()
+        ]
+    )?;
+    Ok(set)
+}
+
+/// Bind a graphics pipeline, counting it in the frame statistics.
+fn bind_pipeline(renderer: &Renderer, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, pipeline: Arc<GraphicsPipeline>) -> MResult<()> {
+    command_builder.bind_pipeline_graphics(pipeline)?;
+    renderer.vulkan.frame_counters.pipeline_binds.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Allocate a descriptor set for drawing the current frame, counting it in the frame statistics.
+fn allocate_descriptor_set(
+    renderer: &Renderer,
+    layout: Arc<DescriptorSetLayout>,
+    writes: impl IntoIterator<Item = WriteDescriptorSet>
+) -> MResult<Arc<PersistentDescriptorSet>> {
+    let set = PersistentDescriptorSet::new(renderer.vulkan.descriptor_set_allocator.as_ref(), layout, writes, [])?;
+    renderer.vulkan.frame_counters.descriptor_sets.fetch_add(1, Ordering::Relaxed);
+    Ok(set)
 }
 
 fn draw_box(renderer: &Renderer, x: f32, y: f32, width: f32, height: f32, color: FloatColor, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
-    let vertices = generate_box(renderer, x, y, width, height);
+    let vertices = generate_box(renderer, x, y, width, height)?;
 
     let pipeline = renderer
         .vulkan
@@ -891,29 +2798,28 @@ fn draw_box(renderer: &Renderer, x: f32, y: f32, width: f32, height: f32, color:
         BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
         default_allocation_create_info(),
         color
-    ).unwrap();
+    )?;
 
-    let set = PersistentDescriptorSet::new(
-        renderer.vulkan.descriptor_set_allocator.as_ref(),
+    let set = allocate_descriptor_set(
+        renderer,
         pipeline.layout().set_layouts()[1].clone(),
         [
             WriteDescriptorSet::buffer(0, uniform_buffer),
-        ],
-        []
-    ).unwrap();
+        ]
+    )?;
 
     command_builder.bind_descriptor_sets(
         PipelineBindPoint::Graphics,
         pipeline.layout().clone(),
         1,
         set
-    ).unwrap();
+    )?;
 
-    command_builder.set_cull_mode(CullMode::None).unwrap();
-    command_builder.bind_index_buffer(renderer.vulkan.default_box_indices.clone()).unwrap();
-    command_builder.bind_vertex_buffers(0, vertices).unwrap();
-    command_builder.bind_pipeline_graphics(pipeline).unwrap();
-    command_builder.draw_indexed(6, 1, 0, 0, 0).unwrap();
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_index_buffer(renderer.vulkan.default_box_indices.clone())?;
+    command_builder.bind_vertex_buffers(0, vertices)?;
+    bind_pipeline(renderer, command_builder, pipeline)?;
+    command_builder.draw_indexed(6, 1, 0, 0, 0)?;
 
     Ok(())
 }
@@ -924,38 +2830,600 @@ fn draw_sprite(renderer: &Renderer, x: f32, y: f32, scale: f32, bitmap: &Arc<Ima
         .pipelines[&VulkanPipelineType::DrawSprite]
         .get_pipeline();
 
-    let set = PersistentDescriptorSet::new(
-        renderer.vulkan.descriptor_set_allocator.as_ref(),
+    let set = allocate_descriptor_set(
+        renderer,
         pipeline.layout().set_layouts()[0].clone(),
         [
             WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
             WriteDescriptorSet::image_view(1, ImageView::new_default(bitmap.clone())?),
-        ],
-        []
-    ).unwrap();
+        ]
+    )?;
 
     let [width, height, _] = bitmap.extent();
-    let width = width as f32 * scale / (renderer.vulkan.current_resolution.width as f32);
-    let height = height as f32 * scale / (renderer.vulkan.current_resolution.height as f32);
+    let width = width as f32 * scale / (renderer.vulkan.parameters.resolution.width as f32);
+    let height = height as f32 * scale / (renderer.vulkan.parameters.resolution.height as f32);
+
+    let vertices = generate_box(renderer, x, y, width, height)?;
+
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_index_buffer(renderer.vulkan.default_box_indices.clone())?;
+    command_builder.bind_vertex_buffers(0, vertices)?;
+    command_builder.bind_descriptor_sets(
+        PipelineBindPoint::Graphics,
+        pipeline.layout().clone(),
+        0,
+        set
+    )?;
+    bind_pipeline(renderer, command_builder, pipeline)?;
+    command_builder.draw_indexed(6, 1, 0, 0, 0)?;
+    Ok(())
+}
+
+/// Vertices of debug primitives and scenario debug markers, which are uploaded once and drawn in every viewport.
+#[derive(Clone)]
+struct DebugGeometry {
+    lines: Option<Subbuffer<[VulkanDebugVertex]>>,
+    triangles: Option<Subbuffer<[VulkanDebugVertex]>>
+}
+
+/// Upload all debug lines into a single vertex buffer to be shared by all viewports.
+fn make_debug_geometry(renderer: &Renderer) -> MResult<DebugGeometry> {
+    let mut lines: Vec<(Vec3, FloatColor)> = renderer
+        .debug_primitives
+        .lines
+        .iter()
+        .flat_map(|l| [(l.from, l.color), (l.to, l.color)])
+        .collect();
+    renderer.scenario_debug_markers.append_lines(&mut lines);
+
+    let mut triangles = Vec::new();
+    renderer.scenario_debug_markers.append_triangles(&mut triangles);
+
+    let upload = |vertices: Vec<(Vec3, FloatColor)>| -> MResult<Option<Subbuffer<[VulkanDebugVertex]>>> {
+        if vertices.is_empty() {
+            return Ok(None)
+        }
+
+        let buffer = Buffer::from_iter(
+            renderer.vulkan.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            default_allocation_create_info(),
+            vertices.into_iter().map(|(position, color)| VulkanDebugVertex { position: position.to_array(), color })
+        )?;
+        Ok(Some(buffer))
+    };
+
+    Ok(DebugGeometry {
+        lines: upload(lines)?,
+        triangles: upload(triangles)?
+    })
+}
+
+fn draw_debug_geometry(
+    renderer: &Renderer,
+    geometry: &DebugGeometry,
+    command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    mvp: Arc<PersistentDescriptorSet>
+) -> MResult<()> {
+    // Draw translucent faces first so lines on their edges aren't tinted by them.
+    let buffers = [
+        (VulkanPipelineType::SolidColorTriangles, &geometry.triangles),
+        (VulkanPipelineType::SolidColor, &geometry.lines)
+    ];
+
+    for (pipeline_type, vertices) in buffers {
+        let Some(vertices) = vertices else {
+            continue
+        };
+
+        let pipeline = renderer
+            .vulkan
+            .pipelines[&pipeline_type]
+            .get_pipeline();
+
+        command_builder.set_cull_mode(CullMode::None)?;
+        bind_pipeline(renderer, command_builder, pipeline.clone())?;
+        command_builder.bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            mvp.clone()
+        )?;
+        command_builder.bind_vertex_buffers(0, vertices.clone())?;
+        command_builder.draw(vertices.len() as u32, 1, 0, 0)?;
+    }
+    Ok(())
+}
+
+/// Size of `text` drawn with the debug text font at `scale`, in pixels.
+fn debug_text_size(text: &str, scale: f32) -> [f32; 2] {
+    let glyph_size = DEBUG_TEXT_GLYPH_SIZE as f32 * scale;
+    let columns = text.split('\n').map(|l| l.chars().count()).max().unwrap_or(0);
+    let rows = text.split('\n').count();
+    [columns as f32 * glyph_size, rows as f32 * glyph_size]
+}
+
+/// Add two triangles for each glyph of `text`, with its top-left corner at `position` in a viewport `size` pixels large.
+///
+/// Glyphs get a drop shadow so the text is readable on any background.
+fn push_debug_text_quads(vertices: &mut Vec<VulkanDebugTextVertex>, text: &DebugText, position: [f32; 2], size: [f32; 2]) {
+    let glyph_texels = DEBUG_TEXT_GLYPH_SIZE as f32;
+    let glyph_size = glyph_texels * text.scale;
+    let shadow_color = [0.0, 0.0, 0.0, text.color[3]];
+
+    for (offset, color) in [(text.scale, shadow_color), (0.0, text.color)] {
+        let mut x = position[0] + offset;
+        let mut y = position[1] + offset;
+
+        for c in text.text.chars() {
+            if c == '\n' {
+                x = position[0] + offset;
+                y += glyph_size;
+                continue
+            }
+
+            if !c.is_whitespace() {
+                let [u, v] = debug_text_glyph_origin(c).map(|t| t as f32);
+                let corner = |cx: f32, cy: f32| VulkanDebugTextVertex {
+                    position: [(x + cx * glyph_size) / size[0], (y + cy * glyph_size) / size[1]],
+                    texture_coords: [u + cx * glyph_texels, v + cy * glyph_texels],
+                    color
+                };
+                vertices.extend_from_slice(&[
+                    corner(0.0, 0.0), corner(0.0, 1.0), corner(1.0, 1.0),
+                    corner(0.0, 0.0), corner(1.0, 1.0), corner(1.0, 0.0)
+                ]);
+            }
+
+            x += glyph_size;
+        }
+    }
+}
+
+fn draw_debug_text_vertices(renderer: &Renderer, vertices: Vec<VulkanDebugTextVertex>, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
+    if vertices.is_empty() {
+        return Ok(())
+    }
+
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::DebugText]
+        .get_pipeline();
+
+    let font = &renderer.bitmaps[renderer.default_bitmaps.debug_text_font].bitmaps[0];
+    let set = allocate_descriptor_set(
+        renderer,
+        pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
+            WriteDescriptorSet::image_view(1, ImageView::new_default(font.vulkan.image.clone())?),
+        ]
+    )?;
+
+    let vertex_count = vertices.len() as u32;
+    let vertices = Buffer::from_iter(
+        renderer.vulkan.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        default_allocation_create_info(),
+        vertices
+    )?;
+
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_vertex_buffers(0, vertices)?;
+    command_builder.bind_descriptor_sets(
+        PipelineBindPoint::Graphics,
+        pipeline.layout().clone(),
+        0,
+        set
+    )?;
+    bind_pipeline(renderer, command_builder, pipeline)?;
+    command_builder.draw(vertex_count, 1, 0, 0)?;
+    Ok(())
+}
+
+fn draw_overdraw_heatmap(renderer: &Renderer, images: &SwapchainImages, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::OverdrawHeatmap]
+        .get_pipeline();
+
+    let set = allocate_descriptor_set(
+        renderer,
+        pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
+            WriteDescriptorSet::image_view(1, images.overdraw_staging().clone()),
+        ]
+    )?;
+
+    // The heatmap reads the overdraw image pixel-for-pixel, so it just needs to cover the viewport.
+    let vertices = generate_box(renderer, 0.0, 0.0, 1.0, 1.0)?;
+
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_index_buffer(renderer.vulkan.default_box_indices.clone())?;
+    command_builder.bind_vertex_buffers(0, vertices)?;
+    command_builder.bind_descriptor_sets(
+        PipelineBindPoint::Graphics,
+        pipeline.layout().clone(),
+        0,
+        set
+    )?;
+    bind_pipeline(renderer, command_builder, pipeline)?;
+    command_builder.draw_indexed(6, 1, 0, 0, 0)?;
+    Ok(())
+}
+
+/// Draw how occluded each pixel of the viewport is into the ambient occlusion image.
+///
+/// This must not be called while rendering, since the depth image is read.
+fn draw_ambient_occlusion(
+    renderer: &Renderer,
+    images: &SwapchainImages,
+    command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    proj: Mat4,
+    viewport: &Viewport
+) -> MResult<()> {
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::AmbientOcclusion]
+        .get_pipeline();
+
+    let uniform_buffer = Buffer::from_data(
+        renderer.vulkan.memory_allocator.clone(),
+        BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
+        default_allocation_create_info(),
+        AmbientOcclusionData {
+            proj: proj.to_cols_array_2d(),
+            inverse_proj: proj.inverse().to_cols_array_2d(),
+            viewport: [viewport.offset[0], viewport.offset[1], viewport.extent[0], viewport.extent[1]],
+            radius: AMBIENT_OCCLUSION_RADIUS,
+            intensity: AMBIENT_OCCLUSION_INTENSITY
+        }
+    )?;
+
+    let set = allocate_descriptor_set(
+        renderer,
+        pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
+            WriteDescriptorSet::image_view(1, images.depth_sampled.clone()),
+            WriteDescriptorSet::buffer(2, uniform_buffer),
+        ]
+    )?;
+
+    let vertices = generate_box(renderer, 0.0, 0.0, 1.0, 1.0)?;
+
+    images.begin_ambient_occlusion_rendering(command_builder)?;
+    command_builder.set_viewport(0, [viewport.clone()].into_iter().collect())?;
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_index_buffer(renderer.vulkan.default_box_indices.clone())?;
+    command_builder.bind_vertex_buffers(0, vertices)?;
+    command_builder.bind_descriptor_sets(
+        PipelineBindPoint::Graphics,
+        pipeline.layout().clone(),
+        0,
+        set
+    )?;
+    bind_pipeline(renderer, command_builder, pipeline)?;
+    command_builder.draw_indexed(6, 1, 0, 0, 0)?;
+    images.end_rendering(command_builder)
+}
+
+/// Multiply the blurred ambient occlusion image with what has been drawn in the viewport.
+fn composite_ambient_occlusion(renderer: &Renderer, images: &SwapchainImages, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::AmbientOcclusionComposite]
+        .get_pipeline();
+
+    let ambient_occlusion = images.ambient_occlusion.clone().expect("SSAO is not enabled");
+    let set = allocate_descriptor_set(
+        renderer,
+        pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
+            WriteDescriptorSet::image_view(1, ambient_occlusion),
+        ]
+    )?;
+
+    // Like the occlusion pass, this reads the image pixel-for-pixel, so it just needs to cover the viewport.
+    let vertices = generate_box(renderer, 0.0, 0.0, 1.0, 1.0)?;
+
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_index_buffer(renderer.vulkan.default_box_indices.clone())?;
+    command_builder.bind_vertex_buffers(0, vertices)?;
+    command_builder.bind_descriptor_sets(
+        PipelineBindPoint::Graphics,
+        pipeline.layout().clone(),
+        0,
+        set
+    )?;
+    bind_pipeline(renderer, command_builder, pipeline)?;
+    command_builder.draw_indexed(6, 1, 0, 0, 0)?;
+    Ok(())
+}
+
+/// Copy the scene and draw it blurred by depth of field over the viewport.
+///
+/// This must be called outside of rendering, and it leaves the color image being rendered to.
+fn draw_depth_of_field(
+    renderer: &Renderer,
+    images: &SwapchainImages,
+    command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    depth_of_field: &DepthOfField,
+    proj: Mat4,
+    viewport: &Viewport
+) -> MResult<()> {
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::DepthOfField]
+        .get_pipeline();
+
+    let scene = copy_scene(images, command_builder)?;
+    let uniform_buffer = Buffer::from_data(
+        renderer.vulkan.memory_allocator.clone(),
+        BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
+        default_allocation_create_info(),
+        DepthOfFieldData {
+            inverse_proj: proj.inverse().to_cols_array_2d(),
+            viewport: [viewport.offset[0], viewport.offset[1], viewport.extent[0], viewport.extent[1]],
+            focal_distance: depth_of_field.focal_distance,
+            max_radius: depth_of_field.aperture * viewport.extent[1]
+        }
+    )?;
+
+    draw_post_effect(renderer, images, command_builder, pipeline, scene, uniform_buffer, viewport, [])
+}
+
+/// Copy the scene and draw it blurred along how far each pixel moved since `previous_view_projection` over the
+/// viewport.
+///
+/// This must be called outside of rendering, and it leaves the color image being rendered to.
+fn draw_motion_blur(
+    renderer: &Renderer,
+    images: &SwapchainImages,
+    command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    intensity: f32,
+    view_projection: Mat4,
+    previous_view_projection: Mat4,
+    viewport: &Viewport
+) -> MResult<()> {
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::MotionBlur]
+        .get_pipeline();
+
+    let scene = copy_scene(images, command_builder)?;
+    let uniform_buffer = Buffer::from_data(
+        renderer.vulkan.memory_allocator.clone(),
+        BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
+        default_allocation_create_info(),
+        MotionBlurData {
+            inverse_view_proj: view_projection.inverse().to_cols_array_2d(),
+            previous_view_proj: previous_view_projection.to_cols_array_2d(),
+            viewport: [viewport.offset[0], viewport.offset[1], viewport.extent[0], viewport.extent[1]],
+            intensity,
+            max_length: MAX_MOTION_BLUR_LENGTH * viewport.extent[1]
+        }
+    )?;
+
+    draw_post_effect(renderer, images, command_builder, pipeline, scene, uniform_buffer, viewport, [])
+}
+
+/// Copy the scene and draw it with exposure, bloom, color overlay, and letterbox over the viewport.
+///
+/// This must be called outside of rendering, and it leaves the color image being rendered to.
+fn draw_post_processing(
+    renderer: &Renderer,
+    images: &SwapchainImages,
+    command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    post_processing: &PostProcessing,
+    viewport: &Viewport
+) -> MResult<()> {
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::PostProcessing]
+        .get_pipeline();
+
+    let scene = copy_scene(images, command_builder)?;
+    let uniform_buffer = Buffer::from_data(
+        renderer.vulkan.memory_allocator.clone(),
+        BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
+        default_allocation_create_info(),
+        PostProcessingData {
+            viewport: [viewport.offset[0], viewport.offset[1], viewport.extent[0], viewport.extent[1]],
+            color_overlay: post_processing.color_overlay,
+            exposure: post_processing.exposure,
+            bloom: post_processing.bloom,
+            bloom_radius: BLOOM_RADIUS * viewport.extent[1],
+            letterbox: post_processing.letterbox
+        }
+    )?;
+
+    draw_post_effect(renderer, images, command_builder, pipeline, scene, uniform_buffer, viewport, [])
+}
+
+/// Copy the scene and draw it blended with the TAA history reprojected from `previous_view_projection` over the
+/// viewport, then copy the result into the history for the next frame.
+///
+/// If `previous_view_projection` is `None`, the history is ignored.
+///
+/// This must be called outside of rendering, and it leaves the color image being rendered to.
+fn draw_temporal_anti_aliasing(
+    renderer: &Renderer,
+    images: &SwapchainImages,
+    command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    view_projection: Mat4,
+    previous_view_projection: Option<Mat4>,
+    viewport: &Viewport
+) -> MResult<()> {
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::TemporalAntiAliasing]
+        .get_pipeline();
+
+    let history = images.taa_history.clone().expect("TAA is not enabled");
+    let scene = copy_scene(images, command_builder)?;
+    let uniform_buffer = Buffer::from_data(
+        renderer.vulkan.memory_allocator.clone(),
+        BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
+        default_allocation_create_info(),
+        TemporalAntiAliasingData {
+            inverse_view_proj: view_projection.inverse().to_cols_array_2d(),
+            previous_view_proj: previous_view_projection.unwrap_or(view_projection).to_cols_array_2d(),
+            viewport: [viewport.offset[0], viewport.offset[1], viewport.extent[0], viewport.extent[1]],
+            history_weight: if previous_view_projection.is_some() { TAA_HISTORY_WEIGHT } else { 0.0 }
+        }
+    )?;
+
+    draw_post_effect(renderer, images, command_builder, pipeline, scene, uniform_buffer, viewport, [WriteDescriptorSet::image_view(4, history.clone())])?;
+    images.end_rendering(command_builder)?;
+
+    // Only this viewport is copied, since other viewports may not have been drawn yet this frame.
+    let subresource = ImageSubresourceLayers {
+        aspects: ImageAspects::COLOR,
+        mip_level: 0,
+        array_layers: 0..1
+    };
+    let offset = [viewport.offset[0] as u32, viewport.offset[1] as u32, 0];
+    let extent = [viewport.extent[0] as u32, viewport.extent[1] as u32, 1];
+    if images.color.image().samples() != SampleCount::Sample1 {
+        command_builder.resolve_image(ResolveImageInfo {
+            regions: [ImageResolve {
+                src_subresource: subresource.clone(),
+                src_offset: offset,
+                dst_subresource: subresource,
+                dst_offset: offset,
+                extent,
+                ..Default::default()
+            }].into(),
+            ..ResolveImageInfo::images(images.color.image().clone(), history.image().clone())
+        })?;
+    }
+    else {
+        command_builder.copy_image(CopyImageInfo {
+            regions: [ImageCopy {
+                src_subresource: subresource.clone(),
+                src_offset: offset,
+                dst_subresource: subresource,
+                dst_offset: offset,
+                extent,
+                ..Default::default()
+            }].into(),
+            ..CopyImageInfo::images(images.color.image().clone(), history.image().clone())
+        })?;
+    }
+
+    images.begin_rendering(command_builder)?;
+    command_builder.set_viewport(0, [viewport.clone()].into_iter().collect())?;
+    Ok(())
+}
+
+/// Get how far to move the projection for TAA on a frame, in normalized device coordinates.
+///
+/// This cycles through a Halton sequence, which spreads the jitter evenly across the pixel.
+fn taa_jitter(frame: u32, viewport: &Viewport) -> Vec2 {
+    let index = frame % TAA_JITTER_PHASES + 1;
+    let pixel = Vec2::new(halton(index, 2), halton(index, 3)) - 0.5;
+    pixel * 2.0 / Vec2::from(viewport.extent)
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
 
-    let vertices = generate_box(renderer, x, y, width, height);
+/// Copy the color image into the scene copy, since it can't be sampled while it is drawn to.
+fn copy_scene(images: &SwapchainImages, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<Arc<ImageView>> {
+    let scene = images.scene_copy.clone().expect("post effects are not enabled");
+    if images.color.image().samples() != SampleCount::Sample1 {
+        command_builder.resolve_image(ResolveImageInfo::images(images.color.image().clone(), scene.image().clone()))?;
+    }
+    else {
+        command_builder.copy_image(CopyImageInfo::images(images.color.image().clone(), scene.image().clone()))?;
+    }
+    Ok(scene)
+}
 
-    command_builder.set_cull_mode(CullMode::None).unwrap();
-    command_builder.bind_index_buffer(renderer.vulkan.default_box_indices.clone()).unwrap();
-    command_builder.bind_vertex_buffers(0, vertices).unwrap();
+/// Draw a post effect over the viewport, reading from `scene`, the depth image (if the effect has a binding for it), and
+/// anything else in `extra_writes`.
+fn draw_post_effect<T: BufferContents>(
+    renderer: &Renderer,
+    images: &SwapchainImages,
+    command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pipeline: Arc<GraphicsPipeline>,
+    scene: Arc<ImageView>,
+    uniform_buffer: Subbuffer<T>,
+    viewport: &Viewport,
+    extra_writes: impl IntoIterator<Item = WriteDescriptorSet>
+) -> MResult<()> {
+    // Effects that don't read the depth image have no binding for it.
+    let layout = pipeline.layout().set_layouts()[0].clone();
+    let depth = layout
+        .bindings()
+        .contains_key(&2)
+        .then(|| WriteDescriptorSet::image_view(2, images.depth_sampled.clone()));
+
+    let set = allocate_descriptor_set(
+        renderer,
+        layout,
+        [
+            WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
+            WriteDescriptorSet::image_view(1, scene),
+            WriteDescriptorSet::buffer(3, uniform_buffer),
+        ].into_iter().chain(depth).chain(extra_writes)
+    )?;
+
+    let vertices = generate_box(renderer, 0.0, 0.0, 1.0, 1.0)?;
+
+    images.begin_rendering(command_builder)?;
+    command_builder.set_viewport(0, [viewport.clone()].into_iter().collect())?;
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_index_buffer(renderer.vulkan.default_box_indices.clone())?;
+    command_builder.bind_vertex_buffers(0, vertices)?;
     command_builder.bind_descriptor_sets(
         PipelineBindPoint::Graphics,
         pipeline.layout().clone(),
         0,
         set
-    ).unwrap();
-    command_builder.bind_pipeline_graphics(pipeline).unwrap();
-    command_builder.draw_indexed(6, 1, 0, 0, 0).unwrap();
+    )?;
+    bind_pipeline(renderer, command_builder, pipeline)?;
+    command_builder.draw_indexed(6, 1, 0, 0, 0)?;
     Ok(())
 }
 
-fn generate_box(renderer: &Renderer, x: f32, y: f32, width: f32, height: f32) -> Subbuffer<[VulkanModelVertex]> {
-    Buffer::from_iter(
+/// Make the descriptor set water samples the reflection images with.
+fn make_water_reflection_uniform(renderer: &Renderer, reflection: &ReflectionImages) -> MResult<Arc<PersistentDescriptorSet>> {
+    let pipeline = renderer
+        .vulkan
+        .pipelines[&VulkanPipelineType::Water]
+        .get_pipeline();
+
+    let set = allocate_descriptor_set(
+        renderer,
+        pipeline.layout().set_layouts()[4].clone(),
+        [
+            WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
+            WriteDescriptorSet::image_view(1, reflection.staging().clone()),
+        ]
+    )?;
+    Ok(set)
+}
+
+fn generate_box(renderer: &Renderer, x: f32, y: f32, width: f32, height: f32) -> MResult<Subbuffer<[VulkanModelVertex]>> {
+    let buffer = Buffer::from_iter(
         renderer.vulkan.memory_allocator.clone(),
         BufferCreateInfo {
             usage: BufferUsage::VERTEX_BUFFER,
@@ -988,5 +3456,6 @@ fn generate_box(renderer: &Renderer, x: f32, y: f32, width: f32, height: f32) ->
                 tangent: [1.0, 0.0, 0.0]
             }
         ]
-    ).expect("failed to make a simple box :(")
+    )?;
+    Ok(buffer)
 }
@@ -1,4 +1,4 @@
-use crate::error::{Error, MResult};
+use crate::error::{BitmapError, Error, GraphicsAPIError, MResult};
 use crate::renderer::mipmap_iterator::{MipmapFaceIterator, MipmapMetadata, MipmapTextureIterator, MipmapType};
 use crate::renderer::vulkan::{default_allocation_create_info, VulkanRenderer};
 use crate::renderer::{decode_p8_to_a8r8g8b8le, AddBitmapBitmapParameter, BitmapFormat, BitmapType};
@@ -114,13 +114,17 @@ impl VulkanBitmapData {
             }
         };
 
+        // Only mipmaps can be skipped, so the smallest one is always kept.
+        let skipped_mip_levels = vulkan_renderer.parameters.skipped_mip_levels.min(parameter.mipmap_count);
+        let mip_extent = |length: u32| (length >> skipped_mip_levels).max(1);
+
         let image = Image::new(
             vulkan_renderer.memory_allocator.clone(),
             ImageCreateInfo {
                 image_type,
                 format,
-                extent: [parameter.resolution.width, parameter.resolution.height, depth],
-                mip_levels: parameter.mipmap_count + 1,
+                extent: [mip_extent(parameter.resolution.width), mip_extent(parameter.resolution.height), mip_extent(depth)],
+                mip_levels: parameter.mipmap_count + 1 - skipped_mip_levels,
                 array_layers: if parameter.bitmap_type == BitmapType::Cubemap { 6 } else { 1 },
                 usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
                 flags: if parameter.bitmap_type == BitmapType::Cubemap {
@@ -138,21 +142,6 @@ impl VulkanBitmapData {
             },
         )?;
 
-        let upload_buffer = Buffer::new_slice(
-            vulkan_renderer.memory_allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::TRANSFER_SRC,
-                ..Default::default()
-            },
-            default_allocation_create_info(),
-            bytes.len() as DeviceSize,
-        )?;
-
-        upload_buffer
-            .write()
-            .map_err(|e| Error::from_vulkan_error(e.to_string()))?
-            .copy_from_slice(bytes);
-
         let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
             &vulkan_renderer.command_buffer_allocator,
             vulkan_renderer.queue.queue_family_index(),
@@ -163,6 +152,7 @@ impl VulkanBitmapData {
         if parameter.bitmap_type == BitmapType::Dim2D
             && parameter.mipmap_count == 0
             && parameter.format.block_pixel_length() == 1 {
+            let upload_buffer = make_upload_buffer(vulkan_renderer, bytes)?;
             upload_image(
                 &image,
                 &upload_buffer,
@@ -177,7 +167,7 @@ impl VulkanBitmapData {
                 1
             )?;
             let buffer = command_buffer_builder.build()?;
-            vulkan_renderer.execute_command_list(buffer);
+            vulkan_renderer.execute_command_list(buffer)?;
             return Ok(Self { image })
         }
 
@@ -214,10 +204,16 @@ impl VulkanBitmapData {
             &mut mipmap_face_iterator
         };
 
-        let mut offset = 0;
+        // Skipped mipmaps come first in the data, so they can be left out of the upload entirely.
         let block_size = bitmap_format.block_byte_size();
+        let (skipped, kept): (Vec<MipmapMetadata>, Vec<MipmapMetadata>) = iterator_to_use
+            .partition(|i| i.mipmap_index < skipped_mip_levels as usize);
+        let skipped_bytes: usize = skipped.iter().map(|i| block_size * i.block_count).sum();
+        let upload_buffer = make_upload_buffer(vulkan_renderer, &bytes[skipped_bytes..])?;
+
+        let mut offset = 0;
         let pixel_size = bitmap_format.block_pixel_length();
-        for i in iterator_to_use {
+        for i in kept {
             let size = block_size * i.block_count;
             let actual_face_index = if parameter.bitmap_type != BitmapType::Cubemap {
                 0
@@ -237,7 +233,7 @@ impl VulkanBitmapData {
 
             let mip_height_physical = (i.block_height * pixel_size) as u32;
             let mip_width_physical = (i.block_width * pixel_size) as u32;
-            let mip_level = i.mipmap_index as u32;
+            let mip_level = i.mipmap_index as u32 - skipped_mip_levels;
             let mip_width_logical = i.width as u32;
             let mip_height_logical = i.height as u32;
             let mip_depth_logical = i.depth as u32;
@@ -248,12 +244,31 @@ impl VulkanBitmapData {
         }
 
         let buffer = command_buffer_builder.build()?;
-        vulkan_renderer.execute_command_list(buffer);
+        vulkan_renderer.execute_command_list(buffer)?;
 
         Ok(Self { image })
     }
 }
 
+fn make_upload_buffer(vulkan_renderer: &VulkanRenderer, bytes: &[u8]) -> MResult<Subbuffer<[u8]>> {
+    let upload_buffer = Buffer::new_slice(
+        vulkan_renderer.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        default_allocation_create_info(),
+        bytes.len() as DeviceSize,
+    )?;
+
+    upload_buffer
+        .write()
+        .map_err(|e| BitmapError::Upload(GraphicsAPIError { backend: "Vulkan", error: e.to_string() }))?
+        .copy_from_slice(bytes);
+
+    Ok(upload_buffer)
+}
+
 fn upload_image(image: &Arc<Image>, upload_buffer: &Subbuffer<[u8]>, command_buffer_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, offset: DeviceSize, actual_face_index: u32, mip_width_physical: u32, mip_height_physical: u32, mip_level: u32, mip_width_logical: u32, mip_height_logical: u32, mip_depth_logical: u32) -> Result<(), Error> {
     command_buffer_builder.copy_buffer_to_image(CopyBufferToImageInfo {
         regions: [
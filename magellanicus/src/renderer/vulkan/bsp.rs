@@ -1,13 +1,16 @@
-use crate::error::MResult;
+use crate::error::{BSPError, DeviceError, MResult};
 use crate::renderer::{AddBSPParameter, DefaultType, Renderer};
 
 use crate::renderer::data::BSPGeometry;
+use crate::renderer::vulkan::helper::{max_draw_index, narrow_indices, NarrowedIndices};
+use crate::renderer::vulkan::pipeline::shader_environment::LightmapData;
+use crate::renderer::vulkan::vertex_cache::optimize_vertex_cache;
 use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexLightmapTextureCoords, VulkanModelVertexTextureCoords};
 use crate::renderer::vulkan::{default_allocation_create_info, VulkanPipelineType};
 use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::vec::Vec;
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, IndexBuffer, Subbuffer};
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::image::sampler::{Sampler, SamplerAddressMode, SamplerCreateInfo};
 use vulkano::image::view::{ImageView, ImageViewCreateInfo};
@@ -30,22 +33,25 @@ impl VulkanBSPData {
         geometries: &Vec<BSPGeometry>
     ) -> MResult<Self> {
         let mut vertex_data: Vec<VulkanModelVertex> = Vec::new();
-        let mut indices: Vec<u16> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
         let mut texture_coords_data: Vec<VulkanModelVertexTextureCoords> = Vec::new();
         let mut lightmap_texture_coords_data: Vec<VulkanModelVertexLightmapTextureCoords> = Vec::new();
 
         for l in &param.lightmap_sets {
             for m in &l.materials {
-                indices.extend(m.surfaces.iter().map(|m| m.indices.iter()).flatten());
-                vertex_data.extend(m.shader_vertices.iter().map(|s| VulkanModelVertex {
-                    position: s.position,
-                    normal: s.normal,
-                    binormal: s.binormal,
-                    tangent: s.tangent
-                }));
-                texture_coords_data.extend(m.shader_vertices.iter().map(|s| VulkanModelVertexTextureCoords {
-                    texture_coords: s.texture_coords
-                }));
+                let material_indices: Vec<u32> = m.surfaces.iter().flat_map(|m| m.indices).collect();
+                indices.extend(optimize_vertex_cache(&material_indices, m.shader_vertices.len()));
+                for s in m.shader_vertices.iter() {
+                    vertex_data.push(VulkanModelVertex {
+                        position: s.position,
+                        normal: s.normal,
+                        binormal: s.binormal,
+                        tangent: s.tangent
+                    });
+                    texture_coords_data.push(VulkanModelVertexTextureCoords {
+                        texture_coords: s.texture_coords
+                    });
+                }
                 if let Some(n) = m.lightmap_vertices.as_ref() {
                     lightmap_texture_coords_data.extend(n.iter().map(|s| VulkanModelVertexLightmapTextureCoords {
                         lightmap_texture_coords: s.lightmap_texture_coords
@@ -59,84 +65,27 @@ impl VulkanBSPData {
             }
         }
 
-        let shader_environment_pipeline = renderer.vulkan.pipelines[&VulkanPipelineType::ShaderEnvironment].get_pipeline();
-        let mut images = BTreeMap::new();
-        if let Some(n) = &param.lightmap_bitmap {
-            let image = renderer
-                .bitmaps
-                .get(n)
-                .unwrap();
-
-            for i in param.lightmap_sets.iter().filter_map(|b| b.lightmap_index) {
-                if images.contains_key(&i) {
-                    continue;
-                }
-
-                let image = image.bitmaps[i].vulkan.image.clone();
-
-                let lightmap = ImageView::new(
-                    image.clone(),
-                    ImageViewCreateInfo::from_image(image.as_ref())
-                )?;
-
-                let sampler = Sampler::new(
-                    renderer.vulkan.device.clone(),
-                    SamplerCreateInfo {
-                        address_mode: [
-                            SamplerAddressMode::ClampToEdge,
-                            SamplerAddressMode::ClampToEdge,
-                            SamplerAddressMode::ClampToEdge
-                        ],
-                        ..SamplerCreateInfo::simple_repeat_linear_no_mipmap()
-                    }
-                )?;
-
-                let descriptor_set = PersistentDescriptorSet::new(
-                    renderer.vulkan.descriptor_set_allocator.as_ref(),
-                    shader_environment_pipeline.layout().set_layouts()[1].clone(),
-                    [
-                        WriteDescriptorSet::sampler(0, sampler),
-                        WriteDescriptorSet::image_view(1, lightmap),
-                    ],
-                    []
-                )?;
-
-                images.insert(i, descriptor_set);
-            }
-        }
+        let lightmap_indices: Vec<usize> = param.lightmap_sets.iter().filter_map(|b| b.lightmap_index).collect();
+        let images = Self::make_lightmap_sets(renderer, param.lightmap_bitmap.as_deref(), &lightmap_indices)?;
 
+        // Without a fallback lightmap, surfaces without lightmaps are lit by the sky instead.
+        let (null_lightmap, vertex_lighting) = match renderer.fallback_assets.lightmap {
+            Some(handle) => (&renderer.bitmaps[handle].bitmaps[0], false),
+            None => (renderer.get_default_2d(DefaultType::White), true)
+        };
+        let shader_environment_pipeline = renderer.vulkan.pipelines[&VulkanPipelineType::ShaderEnvironment].get_pipeline();
         let null_set = PersistentDescriptorSet::new(
             renderer.vulkan.descriptor_set_allocator.as_ref(),
             shader_environment_pipeline.layout().set_layouts()[1].clone(),
             [
                 WriteDescriptorSet::sampler(0, renderer.vulkan.default_2d_sampler.clone()),
-                WriteDescriptorSet::image_view(1, ImageView::new_default(renderer.get_default_2d(DefaultType::White).vulkan.image.clone())?),
+                WriteDescriptorSet::image_view(1, ImageView::new_default(null_lightmap.vulkan.image.clone())?),
+                WriteDescriptorSet::buffer(2, Self::make_lightmap_data(renderer, vertex_lighting)?),
             ],
             []
         ).unwrap();
 
-        let mut transparent_geometries: Vec<usize> = geometries
-            .iter()
-            .enumerate()
-            .filter_map(|f| if renderer.shaders[&f.1.shader].vulkan.pipeline_data.is_transparent() {
-                Some(f.0)
-            }
-            else {
-                None
-            }).collect();
-
-        let mut opaque_geometries: Vec<usize> = geometries
-            .iter()
-            .enumerate()
-            .filter_map(|f| if !renderer.shaders[&f.1.shader].vulkan.pipeline_data.is_transparent() {
-                Some(f.0)
-            }
-            else {
-                None
-            }).collect();
-
-        transparent_geometries.sort_by(|a,b| geometries[*a].shader.cmp(&geometries[*b].shader));
-        opaque_geometries.sort_by(|a,b| geometries[*a].shader.cmp(&geometries[*b].shader));
+        let (transparent_geometries, opaque_geometries) = Self::sort_geometries(renderer, geometries);
 
         let subbuffers = if !indices.is_empty() {
             let vertex_data_subbuffer = Buffer::from_iter(
@@ -169,15 +118,35 @@ impl VulkanBSPData {
                 lightmap_texture_coords_data.into_iter()
             )?;
 
-            let index_subbuffer = Buffer::from_iter(
-                renderer.vulkan.memory_allocator.clone(),
-                BufferCreateInfo {
-                    usage: BufferUsage::INDEX_BUFFER,
-                    ..Default::default()
-                },
-                default_allocation_create_info(),
-                indices.into_iter()
-            )?;
+            let index_buffer_info = BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            };
+            let index_subbuffer: IndexBuffer = match narrow_indices(indices) {
+                NarrowedIndices::U16(indices) => Buffer::from_iter(
+                    renderer.vulkan.memory_allocator.clone(),
+                    index_buffer_info,
+                    default_allocation_create_info(),
+                    indices.into_iter()
+                )?.into(),
+                NarrowedIndices::U32(indices) => {
+                    let device = &renderer.vulkan.device;
+                    let max_index = indices.iter().copied().max().unwrap_or(0);
+                    let max_supported = max_draw_index(
+                        device.physical_device().properties().max_draw_indexed_index_value,
+                        device.enabled_features().full_draw_index_uint32
+                    );
+                    if max_index > max_supported {
+                        return Err(DeviceError::Unsupported { reason: format!("BSP has a vertex index of {max_index}, but the device only supports up to {max_supported}") }.into())
+                    }
+                    Buffer::from_iter(
+                        renderer.vulkan.memory_allocator.clone(),
+                        index_buffer_info,
+                        default_allocation_create_info(),
+                        indices.into_iter()
+                    )?.into()
+                }
+            };
 
             Some(VulkanBSPVertexDataBuffers {
                 vertex_data_subbuffer,
@@ -198,11 +167,94 @@ impl VulkanBSPData {
             transparent_geometries
         })
     }
+
+    fn make_lightmap_data(renderer: &Renderer, vertex_lighting: bool) -> MResult<Subbuffer<LightmapData>> {
+        let buffer = Buffer::from_data(
+            renderer.vulkan.memory_allocator.clone(),
+            BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
+            default_allocation_create_info(),
+            LightmapData { vertex_lighting: vertex_lighting as u32 }
+        )?;
+        Ok(buffer)
+    }
+
+    /// Make a lightmap descriptor set for each of `lightmap_indices` from `lightmap_bitmap`.
+    ///
+    /// Errors if `lightmap_bitmap` is not loaded or does not have one of the indices.
+    pub fn make_lightmap_sets(
+        renderer: &Renderer,
+        lightmap_bitmap: Option<&str>,
+        lightmap_indices: &[usize]
+    ) -> MResult<BTreeMap<usize, Arc<PersistentDescriptorSet>>> {
+        let mut images = BTreeMap::new();
+        let Some(path) = lightmap_bitmap else {
+            return Ok(images)
+        };
+
+        let Some(bitmap) = renderer.bitmaps.get_by_path(path) else {
+            return Err(BSPError::BitmapNotLoaded { path: path.to_owned() }.into())
+        };
+
+        let shader_environment_pipeline = renderer.vulkan.pipelines[&VulkanPipelineType::ShaderEnvironment].get_pipeline();
+        for &i in lightmap_indices {
+            if images.contains_key(&i) {
+                continue;
+            }
+
+            let Some(image) = bitmap.bitmaps.get(i) else {
+                return Err(BSPError::Invalid { reason: format!("BSP refers to lightmap bitmap #{i}, but {path} has only {} bitmap(s)", bitmap.bitmaps.len()) }.into())
+            };
+            let image = image.vulkan.image.clone();
+
+            let lightmap = ImageView::new(
+                image.clone(),
+                ImageViewCreateInfo::from_image(image.as_ref())
+            )?;
+
+            let sampler = Sampler::new(
+                renderer.vulkan.device.clone(),
+                SamplerCreateInfo {
+                    address_mode: [
+                        SamplerAddressMode::ClampToEdge,
+                        SamplerAddressMode::ClampToEdge,
+                        SamplerAddressMode::ClampToEdge
+                    ],
+                    ..SamplerCreateInfo::simple_repeat_linear_no_mipmap()
+                }
+            )?;
+
+            let descriptor_set = PersistentDescriptorSet::new(
+                renderer.vulkan.descriptor_set_allocator.as_ref(),
+                shader_environment_pipeline.layout().set_layouts()[1].clone(),
+                [
+                    WriteDescriptorSet::sampler(0, sampler),
+                    WriteDescriptorSet::image_view(1, lightmap),
+                    WriteDescriptorSet::buffer(2, Self::make_lightmap_data(renderer, false)?),
+                ],
+                []
+            )?;
+
+            images.insert(i, descriptor_set);
+        }
+
+        Ok(images)
+    }
+
+    /// Split geometries into transparent and opaque geometries (in that order), each sorted by shader.
+    pub fn sort_geometries(renderer: &Renderer, geometries: &[BSPGeometry]) -> (Vec<usize>, Vec<usize>) {
+        let (mut transparent_geometries, mut opaque_geometries): (Vec<usize>, Vec<usize>) = (0..geometries.len())
+            .partition(|i| renderer.shaders[geometries[*i].shader].vulkan.pipeline_data.is_transparent());
+
+        transparent_geometries.sort_by(|a,b| geometries[*a].shader.cmp(&geometries[*b].shader));
+        opaque_geometries.sort_by(|a,b| geometries[*a].shader.cmp(&geometries[*b].shader));
+
+        (transparent_geometries, opaque_geometries)
+    }
 }
 
 pub struct VulkanBSPVertexDataBuffers {
     pub vertex_data_subbuffer: Subbuffer<[VulkanModelVertex]>,
     pub texture_coords_subbuffer: Subbuffer<[VulkanModelVertexTextureCoords]>,
     pub lightmap_texture_coords_subbuffer: Subbuffer<[VulkanModelVertexLightmapTextureCoords]>,
-    pub index_subbuffer: Subbuffer<[u16]>,
+    pub index_subbuffer: IndexBuffer,
 }
@@ -2,6 +2,7 @@ use std::sync::Arc;
 use crate::vertex::{LightmapVertex, ModelVertex, ModelTriangle};
 use crate::error::{Error, MResult};
 use crate::renderer::vulkan::vertex::*;
+use crate::renderer::vulkan::vertex_cache::optimize_vertex_cache;
 
 pub struct VulkanMaterialData {
     pub buffers: Arc<VulkanMaterialVertexBuffers>
@@ -11,7 +12,7 @@ pub struct VulkanMaterialVertexBuffers {
     pub vertices: Vec<VulkanModelVertex>,
     pub texture_coords: Vec<VulkanModelVertexTextureCoords>,
     pub lightmap_coords: Option<Vec<VulkanModelVertexTextureCoords>>,
-    pub indices: Vec<u16>
+    pub indices: Vec<u32>
 }
 
 impl VulkanMaterialVertexBuffers {
@@ -20,7 +21,7 @@ impl VulkanMaterialVertexBuffers {
     /// Errors if:
     /// - `lightmap_vertices` is not empty but does not have the same number of vertices as `vertices`
     /// - `indices` contains vertex indices that are out-of-bounds (i.e. `index >= vertices.collect().len()`)
-    /// - `vertices` contains more than 65535 vertices
+    /// - `vertices` contains more than 4294967295 vertices
     pub fn new(
         vertices: impl IntoIterator<Item = ModelVertex>,
         lightmap_vertices: impl IntoIterator<Item = LightmapVertex>,
@@ -39,12 +40,12 @@ impl VulkanMaterialVertexBuffers {
         indices: impl Iterator<Item = ModelTriangle>
     ) -> MResult<Arc<VulkanMaterialVertexBuffers>> {
         // Prevent allocating/loading too many vertices
-        const MAX_VERTEX_LIMIT: usize = u16::MAX as usize;
+        const MAX_VERTEX_LIMIT: usize = u32::MAX as usize;
         const MAX_VERTEX_ALLOC_LIMIT: usize = MAX_VERTEX_LIMIT + 1;
 
         let size_hint = vertices.size_hint().0;
         if size_hint > MAX_VERTEX_LIMIT {
-            return Err(Error::DataError { error: std::format!("Vertex iterator will exceed the vertex limit ({size_hint} > 65535)") })
+            return Err(Error::from_data_error_string(std::format!("Vertex iterator will exceed the vertex limit ({size_hint} > {MAX_VERTEX_LIMIT})")))
         }
 
         let mut vertices_buf: Vec<VulkanModelVertex> = Vec::with_capacity(size_hint);
@@ -66,8 +67,8 @@ impl VulkanMaterialVertexBuffers {
 
         let vertex_count = vertices_buf.len();
 
-        if vertex_count > (u16::MAX as usize) {
-            return Err(Error::DataError { error: std::format!("Vertex iterator exceeded the vertex limit ({vertex_count} > 65535)") })
+        if vertex_count > MAX_VERTEX_LIMIT {
+            return Err(Error::from_data_error_string(std::format!("Vertex iterator exceeded the vertex limit ({vertex_count} > {MAX_VERTEX_LIMIT})")))
         }
 
         let mut lightmap_vertices = lightmap_vertices.peekable();
@@ -77,7 +78,7 @@ impl VulkanMaterialVertexBuffers {
                 lightmap_coords.push(VulkanModelVertexTextureCoords { texture_coords: i.lightmap_texture_coords })
             }
             if lightmap_coords.len() != vertex_count {
-                return Err(Error::DataError { error: std::format!("Lightmap vertex coordinates count ({}) != vertices count ({vertex_count})", lightmap_coords.len()) })
+                return Err(Error::from_data_error_string(std::format!("Lightmap vertex coordinates count ({}) != vertices count ({vertex_count})", lightmap_coords.len())))
             }
             lightmap_coords.shrink_to_fit();
             Some(lightmap_coords)
@@ -89,13 +90,13 @@ impl VulkanMaterialVertexBuffers {
         let mut indices_buf = Vec::with_capacity(indices.size_hint().0);
         for ModelTriangle { indices: [a, b, c] } in indices {
             if a as usize >= vertex_count || b as usize >= vertex_count || c as usize >= vertex_count {
-                return Err(Error::DataError { error: std::format!("triangle {a},{b},{c} out-of-bounds (at least one index was >= {vertex_count})") })
+                return Err(Error::from_data_error_string(std::format!("triangle {a},{b},{c} out-of-bounds (at least one index was >= {vertex_count})")))
             }
             indices_buf.push(a);
             indices_buf.push(b);
             indices_buf.push(c);
         }
-        indices_buf.shrink_to_fit();
+        let indices_buf = optimize_vertex_cache(&indices_buf, vertex_count);
 
         let buffers = VulkanMaterialVertexBuffers {
             vertices: vertices_buf,
@@ -1,4 +1,4 @@
-use crate::error::{Error, MResult};
+use crate::error::{DeviceError, Error, MResult};
 use crate::renderer::RendererParameters;
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use std::string::ToString;
@@ -6,7 +6,7 @@ use std::borrow::ToOwned;
 use std::sync::Arc;
 use std::vec::Vec;
 use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
-use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo, QueueFlags};
+use vulkano::device::{Device, DeviceCreateInfo, DeviceOwned, DeviceExtensions, Features, Queue, QueueCreateInfo, QueueFlags};
 use vulkano::format::Format;
 use vulkano::image::{Image, ImageUsage};
 use vulkano::instance::{Instance, InstanceCreateInfo};
@@ -17,7 +17,7 @@ pub struct LoadedVulkan {
     pub instance: Arc<Instance>,
     pub device: Arc<Device>,
     pub queue: Arc<Queue>,
-    pub surface: Arc<Surface>,
+    pub surface: Option<Arc<Surface>>,
 }
 
 pub unsafe fn load_vulkan_and_get_queue(
@@ -27,9 +27,78 @@ pub unsafe fn load_vulkan_and_get_queue(
     let library = VulkanLibrary::new()?;
 
     let enabled_extensions = Surface::required_extensions(surface);
+    let instance = Instance::new(library.clone(), InstanceCreateInfo {
+        enabled_extensions,
+        ..Default::default()
+    })?;
+
+    let surface = Surface::from_window_ref(instance.clone(), surface)?;
+    load_device_and_get_queue(instance, Some(surface), anisotropic_filtering)
+}
+
+/// Load Vulkan without a surface.
+///
+/// Any device with a graphics queue is accepted, including software implementations such as lavapipe.
+pub fn load_vulkan_headless_and_get_queue(anisotropic_filtering: Option<f32>) -> MResult<LoadedVulkan> {
+    let library = VulkanLibrary::new()?;
+    let instance = Instance::new(library.clone(), InstanceCreateInfo::default())?;
+    load_device_and_get_queue(instance, None, anisotropic_filtering)
+}
+
+/// Validate a host-provided device and queue and wrap them.
+pub fn load_from_existing_device(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    surface: Option<Arc<Surface>>,
+    anisotropic_filtering: Option<f32>
+) -> MResult<LoadedVulkan> {
+    if !Arc::ptr_eq(queue.device(), &device) {
+        return Err(Error::from_vulkan_unsupported_error("The given queue does not belong to the given device".to_string()))
+    }
+
+    let physical_device = device.physical_device();
+    let queue_family_index = queue.queue_family_index();
+    if !physical_device.queue_family_properties()[queue_family_index as usize].queue_flags.intersects(QueueFlags::GRAPHICS) {
+        return Err(Error::from_vulkan_unsupported_error("The given queue does not support graphics".to_string()))
+    }
+
+    if device.api_version() < Version::V1_2 {
+        return Err(Error::from_vulkan_unsupported_error("The given device does not support Vulkan 1.2".to_string()))
+    }
+
+    if device.api_version() < Version::V1_3 && !device.enabled_features().extended_dynamic_state {
+        return Err(Error::from_vulkan_unsupported_error("The given device does not have extended_dynamic_state enabled".to_string()))
+    }
+
+    if anisotropic_filtering.is_some() && !device.enabled_features().sampler_anisotropy {
+        return Err(Error::from_vulkan_unsupported_error("Anisotropic filtering was requested, but sampler_anisotropy is not enabled".to_string()))
+    }
+
+    if let Some(surface) = surface.as_ref() {
+        if !device.enabled_extensions().khr_swapchain {
+            return Err(Error::from_vulkan_unsupported_error("A surface was given, but khr_swapchain is not enabled".to_string()))
+        }
+        if !physical_device.surface_support(queue_family_index, surface.as_ref()).unwrap_or(false) {
+            return Err(Error::from_vulkan_unsupported_error("The given queue cannot present to the given surface".to_string()))
+        }
+    }
+
+    Ok(LoadedVulkan {
+        instance: device.instance().clone(),
+        device,
+        queue,
+        surface
+    })
+}
+
+pub fn load_device_and_get_queue(
+    instance: Arc<Instance>,
+    surface: Option<Arc<Surface>>,
+    anisotropic_filtering: Option<f32>
+) -> MResult<LoadedVulkan> {
     let device_extensions_all = DeviceExtensions {
-        // Non-negotiable; required to do swapchains
-        khr_swapchain: true,
+        // Non-negotiable; required to do swapchains (but there is nothing to present to if headless)
+        khr_swapchain: surface.is_some(),
         ..DeviceExtensions::empty()
     };
 
@@ -41,6 +110,8 @@ pub unsafe fn load_vulkan_and_get_queue(
 
     let required_device_features = Features {
         sampler_anisotropy: anisotropic_filtering.is_some(),
+        // Required for looking up the gel of each dynamic light in a loop
+        shader_sampled_image_array_dynamic_indexing: true,
         ..Features::empty()
     };
 
@@ -52,13 +123,6 @@ pub unsafe fn load_vulkan_and_get_queue(
         ..optional_extensions_all
     };
 
-    let instance = Instance::new(library.clone(), InstanceCreateInfo {
-        enabled_extensions,
-        ..Default::default()
-    })?;
-
-    let surface = Surface::from_window_ref(instance.clone(), surface)?;
-
     let (physical_device, queue_family_index, device_extensions) = find_best_gpu(
         instance.clone(),
         device_extensions_12,
@@ -66,8 +130,8 @@ pub unsafe fn load_vulkan_and_get_queue(
         optional_extensions_12,
         optional_extensions_all,
         required_device_features,
-        surface.clone()
-    ).ok_or_else(|| Error::from_vulkan_error("No suitable Vulkan-compatible GPUs found".to_string()))?;
+        surface.as_ref()
+    ).ok_or(DeviceError::NoSuitableDevice)?;
 
     let (device, mut queues) = create_device_and_queues(
         physical_device,
@@ -79,7 +143,38 @@ pub unsafe fn load_vulkan_and_get_queue(
     Ok(LoadedVulkan { instance, device, queue, surface })
 }
 
+/// Vertex indices stored in the smallest type that can hold all of them.
+pub enum NarrowedIndices {
+    U16(Vec<u16>),
+    U32(Vec<u32>)
+}
+
+/// Narrow `indices` to 16-bit if they all fit, since 16-bit indices take half the memory and bandwidth.
+pub fn narrow_indices(indices: Vec<u32>) -> NarrowedIndices {
+    if indices.iter().all(|i| *i <= u16::MAX as u32) {
+        NarrowedIndices::U16(indices.into_iter().map(|i| i as u16).collect())
+    }
+    else {
+        NarrowedIndices::U32(indices)
+    }
+}
+
+/// Get the largest vertex index a device can draw with.
+///
+/// Without `full_draw_index_uint32`, indices are limited to 2^24 - 1 regardless of what the device reports.
+pub fn max_draw_index(max_draw_indexed_index_value: u32, full_draw_index_uint32: bool) -> u32 {
+    if full_draw_index_uint32 {
+        max_draw_indexed_index_value
+    }
+    else {
+        max_draw_indexed_index_value.min((1 << 24) - 1)
+    }
+}
+
 fn create_device_and_queues(physical_device: Arc<PhysicalDevice>, device_extensions: DeviceExtensions, queue_family_index: u32) -> Result<(Arc<Device>, impl ExactSizeIterator<Item=Arc<Queue>> + Sized), Validated<VulkanError>> {
+    // Allow 32-bit indices beyond the guaranteed 2^24 - 1 limit where possible.
+    let full_draw_index_uint32 = physical_device.supported_features().full_draw_index_uint32;
+
     Device::new(
         physical_device,
         DeviceCreateInfo {
@@ -92,6 +187,8 @@ fn create_device_and_queues(physical_device: Arc<PhysicalDevice>, device_extensi
                 dynamic_rendering: device_extensions.khr_dynamic_rendering,
                 extended_dynamic_state: true,
                 sampler_anisotropy: true,
+                shader_sampled_image_array_dynamic_indexing: true,
+                full_draw_index_uint32,
                 ..Features::default()
             },
             ..Default::default()
@@ -102,8 +199,7 @@ fn create_device_and_queues(physical_device: Arc<PhysicalDevice>, device_extensi
 pub fn build_swapchain(device: Arc<Device>, surface: Arc<Surface>, image_format: Format, renderer_parameters: &RendererParameters) -> MResult<(Arc<Swapchain>, Vec<Arc<Image>>)> {
     let surface_capabilities = device
         .physical_device()
-        .surface_capabilities(surface.as_ref(), Default::default())
-        .unwrap();
+        .surface_capabilities(surface.as_ref(), Default::default())?;
 
     let result = Swapchain::new(
         device.clone(),
@@ -143,7 +239,7 @@ fn find_best_gpu(
     optional_extensions_12: DeviceExtensions,
     optional_extensions_13: DeviceExtensions,
     required_device_features: Features,
-    surface: Arc<Surface>
+    surface: Option<&Arc<Surface>>
 ) -> Option<(Arc<PhysicalDevice>, u32, DeviceExtensions)> {
     instance
         .enumerate_physical_devices()
@@ -176,7 +272,7 @@ fn find_best_gpu(
                 .iter()
                 .enumerate()
                 .position(|(i, q)| {
-                    q.queue_flags.intersects(QueueFlags::GRAPHICS) && (device.surface_support(i as u32, surface.as_ref()).unwrap_or(false))
+                    q.queue_flags.intersects(QueueFlags::GRAPHICS) && surface.map(|s| device.surface_support(i as u32, s.as_ref()).unwrap_or(false)).unwrap_or(true)
                 })
                 .map(|i| (device, i as u32, extensions))
         })
@@ -188,3 +284,36 @@ fn find_best_gpu(
             _ => u32::MAX,
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_that_fit_are_narrowed() {
+        let NarrowedIndices::U16(indices) = narrow_indices(vec![0, 1, 65535]) else {
+            panic!("indices were not narrowed")
+        };
+        assert_eq!(indices, [0, 1, 65535]);
+
+        let NarrowedIndices::U16(indices) = narrow_indices(Vec::new()) else {
+            panic!("empty indices were not narrowed")
+        };
+        assert!(indices.is_empty());
+    }
+
+    #[test]
+    fn indices_that_do_not_fit_stay_32_bit() {
+        let NarrowedIndices::U32(indices) = narrow_indices(vec![0, 65536, 1]) else {
+            panic!("indices were narrowed")
+        };
+        assert_eq!(indices, [0, 65536, 1]);
+    }
+
+    #[test]
+    fn max_draw_index_is_limited_without_full_draw_index_uint32() {
+        assert_eq!(max_draw_index(u32::MAX, true), u32::MAX);
+        assert_eq!(max_draw_index(u32::MAX, false), (1 << 24) - 1);
+        assert_eq!(max_draw_index(65535, false), 65535);
+    }
+}
@@ -25,8 +25,12 @@ use crate::vertex::VertexOffsets;
 /// - set 0, binding 0 is ModelData, defined as [`VulkanModelData`](crate::renderer::vulkan::vertex::VulkanModelData)
 /// - set 1, binding 0 is a sampler for lightmaps
 /// - set 1, binding 1 is an image view for lightmaps
+/// - set 2, binding 0 is fog data, defined as [`VulkanFogData`](crate::renderer::vulkan::vertex::VulkanFogData)
+/// - set 2, binding 1 is the lighting mode and dynamic lights, defined as [`VulkanLightingData`](crate::renderer::vulkan::vertex::VulkanLightingData)
+/// - set 2, binding 2 is a sampler for light gels
+/// - set 2, binding 3 is an array of cube image views for light gels, one for each dynamic light
 ///
-/// Nothing will be bound on layout 1+. Anything on set 2+ is shader-specific.
+/// Nothing will be bound on layout 1+. Anything on set 3+ is shader-specific.
 
 pub struct VulkanMaterialShaderData {
     pub pipeline_data: Arc<dyn VulkanMaterial>,
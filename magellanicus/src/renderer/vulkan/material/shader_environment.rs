@@ -64,6 +64,9 @@ impl VulkanShaderEnvironmentMaterial {
                 let mut flags = 0;
                 flags |= (add_shader_parameter.alpha_tested as u32) << 0;
                 flags |= (add_shader_parameter.bump_map_is_specular_mask as u32) << 1;
+                flags |= (add_shader_parameter.specular_overbright as u32) << 2;
+                flags |= (add_shader_parameter.specular_extra_shiny as u32) << 3;
+                flags |= (add_shader_parameter.specular_lightmap_is_specular as u32) << 4;
                 flags
             },
             shader_environment_type: add_shader_parameter.shader_environment_type as u32,
@@ -71,6 +74,7 @@ impl VulkanShaderEnvironmentMaterial {
             micro_detail_map_function: add_shader_parameter.micro_detail_map_function as u32,
             parallel_color: [add_shader_parameter.parallel_color[0], add_shader_parameter.parallel_color[1], add_shader_parameter.parallel_color[2], add_shader_parameter.parallel_brightness],
             perpendicular_color: [add_shader_parameter.perpendicular_color[0], add_shader_parameter.perpendicular_color[1], add_shader_parameter.perpendicular_color[2], add_shader_parameter.perpendicular_brightness],
+            specular_brightness: add_shader_parameter.specular_brightness,
         };
 
         let map_sampler = renderer.vulkan.default_2d_sampler.clone();
@@ -1,6 +1,6 @@
 use crate::error::MResult;
 use crate::renderer::vulkan::{VertexOffsets, VulkanMaterial, VulkanPipelineType};
-use crate::renderer::{AddShaderBasicShaderData, DefaultType, Renderer};
+use crate::renderer::{AddShaderBasicShaderData, DefaultType, Renderer, ShaderType};
 use std::eprintln;
 use std::sync::Arc;
 use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
@@ -13,13 +13,17 @@ use vulkano::pipeline::{Pipeline, PipelineBindPoint};
 pub struct VulkanSimpleShaderMaterial {
     diffuse: Arc<ImageView>,
     diffuse_sampler: Arc<Sampler>,
-    descriptor_set: Arc<PersistentDescriptorSet>
+    descriptor_set: Arc<PersistentDescriptorSet>,
+    pipeline: VulkanPipelineType
 }
 
 impl VulkanSimpleShaderMaterial {
     pub fn new(renderer: &mut Renderer, add_shader_parameter: AddShaderBasicShaderData) -> MResult<Self> {
+        // Water without a base map is dark so that only its reflection shows.
+        let is_water = add_shader_parameter.shader_type == ShaderType::TransparentWater;
+        let default = if is_water { DefaultType::Null } else { DefaultType::White };
         let diffuse = renderer
-            .get_or_default_2d(&add_shader_parameter.bitmap, 0, DefaultType::White)
+            .get_or_default_2d(&add_shader_parameter.bitmap, 0, default)
             .vulkan
             .image
             .clone();
@@ -44,7 +48,14 @@ impl VulkanSimpleShaderMaterial {
 
         let diffuse_sampler = renderer.vulkan.default_2d_sampler.clone();
 
-        let pipeline = renderer.vulkan.pipelines.get(&VulkanPipelineType::SimpleTexture).unwrap();
+        // Water has its own pipeline so it can be drawn with reflections; it has the same set 3 layout.
+        let pipeline_type = if is_water {
+            VulkanPipelineType::Water
+        }
+        else {
+            VulkanPipelineType::SimpleTexture
+        };
+        let pipeline = renderer.vulkan.pipelines.get(&pipeline_type).unwrap();
 
         let descriptor_set = PersistentDescriptorSet::new(
             renderer.vulkan.descriptor_set_allocator.as_ref(),
@@ -56,7 +67,7 @@ impl VulkanSimpleShaderMaterial {
             []
         )?;
 
-        Ok(Self { diffuse, diffuse_sampler, descriptor_set })
+        Ok(Self { diffuse, diffuse_sampler, descriptor_set, pipeline: pipeline_type })
     }
 }
 
@@ -86,7 +97,7 @@ impl VulkanMaterial for VulkanSimpleShaderMaterial {
     }
 
     fn get_main_pipeline(&self) -> VulkanPipelineType {
-        VulkanPipelineType::SimpleTexture
+        self.pipeline
     }
 
     fn can_reuse_descriptors(&self) -> bool {
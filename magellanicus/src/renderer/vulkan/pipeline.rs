@@ -2,9 +2,11 @@ use std::collections::BTreeMap;
 use std::sync::Arc;
 use vulkano::device::Device;
 use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, BlendFactor, BlendOp};
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
 use vulkano::pipeline::GraphicsPipeline;
 use crate::error::MResult;
 use crate::renderer::vulkan::SwapchainImages;
+use crate::renderer::vulkan::pipeline::pipeline_loader::DepthAccess;
 
 pub mod solid_color;
 pub mod simple_texture;
@@ -13,6 +15,18 @@ mod color_box;
 pub mod shader_environment;
 pub mod shader_transparent_chicago;
 mod draw_sprite;
+pub mod debug_view;
+mod overdraw;
+mod debug_text;
+mod hud;
+pub mod hud_meter;
+pub mod ambient_occlusion;
+pub mod water;
+pub mod mirror;
+pub mod depth_of_field;
+pub mod motion_blur;
+pub mod temporal_anti_aliasing;
+pub mod post_processing;
 
 pub trait VulkanPipelineData: Send + Sync + 'static {
     fn get_pipeline(&self) -> Arc<GraphicsPipeline>;
@@ -23,10 +37,15 @@ pub trait VulkanPipelineData: Send + Sync + 'static {
 pub fn load_all_pipelines(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<BTreeMap<VulkanPipelineType, Arc<dyn VulkanPipelineData>>> {
     let mut pipelines: BTreeMap<VulkanPipelineType, Arc<dyn VulkanPipelineData>> = BTreeMap::new();
 
-    pipelines.insert(VulkanPipelineType::SolidColor, Arc::new(solid_color::SolidColorShader::new(swapchain_images, device.clone())?));
+    pipelines.insert(VulkanPipelineType::SolidColor, Arc::new(solid_color::SolidColorShader::new(swapchain_images, device.clone(), PrimitiveTopology::LineList)?));
+    pipelines.insert(VulkanPipelineType::SolidColorTriangles, Arc::new(solid_color::SolidColorShader::new(swapchain_images, device.clone(), PrimitiveTopology::TriangleList)?));
     pipelines.insert(VulkanPipelineType::SimpleTexture, Arc::new(simple_texture::SimpleTextureShader::new(swapchain_images, device.clone())?));
     pipelines.insert(VulkanPipelineType::ColorBox, Arc::new(color_box::ColorBox::new(swapchain_images, device.clone())?));
     pipelines.insert(VulkanPipelineType::ShaderEnvironment, Arc::new(shader_environment::ShaderEnvironment::new(swapchain_images, device.clone())?));
+    for stages in shader_environment::ReducedStages::all_reduced() {
+        pipelines.insert(VulkanPipelineType::ShaderEnvironmentReduced(stages), Arc::new(shader_environment::ShaderEnvironment::new_reduced(swapchain_images, device.clone(), stages)?));
+    }
+    pipelines.insert(VulkanPipelineType::Water, Arc::new(water::Water::new(swapchain_images, device.clone())?));
 
     let add = AttachmentBlend::additive();
     let alpha_blend = AttachmentBlend::alpha();
@@ -71,6 +90,55 @@ pub fn load_all_pipelines(swapchain_images: &SwapchainImages, device: Arc<Device
     pipelines.insert(VulkanPipelineType::ShaderTransparentChicagoMultiply, Arc::new(shader_transparent_chicago::ShaderTransparentChicago::new(swapchain_images, device.clone(), Some(multiply))?));
 
     pipelines.insert(VulkanPipelineType::DrawSprite, Arc::new(draw_sprite::DrawSprite::new(swapchain_images, device.clone())?));
+    pipelines.insert(VulkanPipelineType::DebugView, Arc::new(debug_view::DebugView::new(swapchain_images, device.clone())?));
+    pipelines.insert(VulkanPipelineType::OverdrawCountOpaque, Arc::new(overdraw::OverdrawCount::new(swapchain_images, device.clone(), DepthAccess::DepthWrite)?));
+    pipelines.insert(VulkanPipelineType::OverdrawCountTransparent, Arc::new(overdraw::OverdrawCount::new(swapchain_images, device.clone(), DepthAccess::DepthReadOnlyTransparent)?));
+    pipelines.insert(VulkanPipelineType::OverdrawHeatmap, Arc::new(overdraw::OverdrawHeatmap::new(swapchain_images, device.clone())?));
+    pipelines.insert(VulkanPipelineType::DebugText, Arc::new(debug_text::DebugText::new(swapchain_images, device.clone())?));
+
+    let hud_add = AttachmentBlend {
+        src_color_blend_factor: BlendFactor::SrcAlpha,
+        dst_color_blend_factor: BlendFactor::One,
+        color_blend_op: BlendOp::Add,
+        src_alpha_blend_factor: BlendFactor::Zero,
+        dst_alpha_blend_factor: BlendFactor::One,
+        alpha_blend_op: BlendOp::Add,
+    };
+    let hud_multiply = AttachmentBlend {
+        src_color_blend_factor: BlendFactor::DstColor,
+        dst_color_blend_factor: BlendFactor::Zero,
+        color_blend_op: BlendOp::Add,
+        src_alpha_blend_factor: BlendFactor::Zero,
+        dst_alpha_blend_factor: BlendFactor::One,
+        alpha_blend_op: BlendOp::Add,
+    };
+
+    pipelines.insert(VulkanPipelineType::HudAlphaBlend, Arc::new(hud::Hud::new(swapchain_images, device.clone(), alpha_blend)?));
+    pipelines.insert(VulkanPipelineType::HudAdd, Arc::new(hud::Hud::new(swapchain_images, device.clone(), hud_add)?));
+    pipelines.insert(VulkanPipelineType::HudMultiply, Arc::new(hud::Hud::new(swapchain_images, device.clone(), hud_multiply)?));
+    pipelines.insert(VulkanPipelineType::HudMeter, Arc::new(hud_meter::HudMeter::new(swapchain_images, device.clone())?));
+
+    if swapchain_images.has_stencil() {
+        pipelines.insert(VulkanPipelineType::ShaderEnvironmentMirrored, Arc::new(shader_environment::ShaderEnvironment::new_mirrored(swapchain_images, device.clone())?));
+        pipelines.insert(VulkanPipelineType::MirrorMask, Arc::new(mirror::MirrorMask::new(swapchain_images, device.clone())?));
+        pipelines.insert(VulkanPipelineType::MirrorClear, Arc::new(mirror::MirrorClear::new(swapchain_images, device.clone())?));
+        pipelines.insert(VulkanPipelineType::MirrorRestore, Arc::new(mirror::MirrorRestore::new(swapchain_images, device.clone())?));
+    }
+
+    if swapchain_images.ambient_occlusion.is_some() {
+        pipelines.insert(VulkanPipelineType::AmbientOcclusion, Arc::new(ambient_occlusion::AmbientOcclusion::new(swapchain_images, device.clone())?));
+        pipelines.insert(VulkanPipelineType::AmbientOcclusionComposite, Arc::new(ambient_occlusion::AmbientOcclusionComposite::new(swapchain_images, device.clone(), hud_multiply)?));
+    }
+
+    if swapchain_images.scene_copy.is_some() {
+        pipelines.insert(VulkanPipelineType::DepthOfField, Arc::new(depth_of_field::DepthOfField::new(swapchain_images, device.clone())?));
+        pipelines.insert(VulkanPipelineType::MotionBlur, Arc::new(motion_blur::MotionBlur::new(swapchain_images, device.clone())?));
+        pipelines.insert(VulkanPipelineType::PostProcessing, Arc::new(post_processing::PostProcessing::new(swapchain_images, device.clone())?));
+    }
+
+    if swapchain_images.taa_history.is_some() {
+        pipelines.insert(VulkanPipelineType::TemporalAntiAliasing, Arc::new(temporal_anti_aliasing::TemporalAntiAliasing::new(swapchain_images, device.clone())?));
+    }
 
     Ok(pipelines)
 }
@@ -78,11 +146,16 @@ pub fn load_all_pipelines(swapchain_images: &SwapchainImages, device: Arc<Device
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u32)]
 pub enum VulkanPipelineType {
-    /// Writes a solid color.
+    /// Draws lines with per-vertex colors.
     ///
-    /// Useful for testing.
+    /// Used for debug primitives.
     SolidColor,
 
+    /// Draws translucent triangles with per-vertex colors.
+    ///
+    /// Used for scenario debug markers.
+    SolidColorTriangles,
+
     /// Draws a texture.
     SimpleTexture,
 
@@ -92,6 +165,29 @@ pub enum VulkanPipelineType {
     /// shader_environment
     ShaderEnvironment,
 
+    /// shader_environment, skipping some stages for far away geometry
+    ShaderEnvironmentReduced(shader_environment::ReducedStages),
+
+    /// Draws water with reflections.
+    Water,
+
+    /// shader_environment, only where a mirror was marked in the stencil image
+    ///
+    /// This is only loaded if the depth image has a stencil aspect.
+    ShaderEnvironmentMirrored,
+    /// Marks where a mirror is visible in the stencil image.
+    ///
+    /// This is only loaded if the depth image has a stencil aspect.
+    MirrorMask,
+    /// Clears color and depth where a mirror was marked.
+    ///
+    /// This is only loaded if the depth image has a stencil aspect.
+    MirrorClear,
+    /// Restores the depth of a mirror and unmarks it.
+    ///
+    /// This is only loaded if the depth image has a stencil aspect.
+    MirrorRestore,
+
     /// shader_transparent_chicago + Add
     ShaderTransparentChicagoAdd,
     /// shader_transparent_chicago + Alpha Blend
@@ -106,5 +202,54 @@ pub enum VulkanPipelineType {
     ShaderTransparentChicagoMultiply,
 
     /// Draws a sprite to the screen.
-    DrawSprite
+    DrawSprite,
+
+    /// Draws geometry with a debug visualization.
+    DebugView,
+
+    /// Counts overdraw of opaque geometry (writes depth).
+    OverdrawCountOpaque,
+    /// Counts overdraw of transparent geometry (does not write depth).
+    OverdrawCountTransparent,
+    /// Draws the overdraw image as a heatmap.
+    OverdrawHeatmap,
+
+    /// Draws text with the built-in debug text font.
+    DebugText,
+
+    /// Draws a HUD bitmap + Alpha Blend
+    HudAlphaBlend,
+    /// Draws a HUD bitmap + Add
+    HudAdd,
+    /// Draws a HUD bitmap + Multiply
+    HudMultiply,
+
+    /// Draws a HUD meter.
+    HudMeter,
+
+    /// Draws screen-space ambient occlusion from the depth image.
+    ///
+    /// This is only loaded if SSAO is enabled.
+    AmbientOcclusion,
+    /// Multiplies the blurred ambient occlusion image with the color image.
+    ///
+    /// This is only loaded if SSAO is enabled.
+    AmbientOcclusionComposite,
+
+    /// Blurs the scene by how out of focus each pixel is.
+    ///
+    /// This is only loaded if depth of field, motion blur, TAA, or post processing is enabled.
+    DepthOfField,
+    /// Blurs the scene along how far each pixel moved since the last frame.
+    ///
+    /// This is only loaded if depth of field, motion blur, TAA, or post processing is enabled.
+    MotionBlur,
+    /// Blends the scene with the previous frames reprojected onto it.
+    ///
+    /// This is only loaded if TAA is enabled.
+    TemporalAntiAliasing,
+    /// Applies a viewport's exposure, bloom, color overlay, and letterbox to the scene.
+    ///
+    /// This is only loaded if depth of field, motion blur, TAA, or post processing is enabled.
+    PostProcessing
 }
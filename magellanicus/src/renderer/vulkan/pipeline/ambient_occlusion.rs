@@ -0,0 +1,117 @@
+use crate::error::MResult;
+use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings, RenderTarget};
+use crate::renderer::vulkan::vertex::VulkanModelVertex;
+use crate::renderer::vulkan::{SwapchainImages, VulkanPipelineData, AMBIENT_OCCLUSION_FORMAT};
+use std::sync::Arc;
+use std::vec;
+use vulkano::device::Device;
+use vulkano::image::SampleCount;
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendAttachmentState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::GraphicsPipeline;
+
+mod fullscreen_vertex {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/renderer/vulkan/pipeline/ambient_occlusion/fullscreen.vert"
+    }
+}
+
+mod occlusion_fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/ambient_occlusion/occlusion.frag"
+    }
+}
+
+mod occlusion_fragment_multisampled {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/ambient_occlusion/occlusion.frag",
+        define: [("MULTISAMPLED", "1")]
+    }
+}
+
+mod composite_fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/ambient_occlusion/composite.frag"
+    }
+}
+
+pub use occlusion_fragment::AmbientOcclusionData;
+
+/// Draws how much each pixel is occluded into the ambient occlusion image, based on the depth image.
+pub struct AmbientOcclusion {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl AmbientOcclusion {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        // Multisampled depth images have to be read as such.
+        let load_fragment_shader = if swapchain_images.depth.image().samples() == SampleCount::Sample1 {
+            occlusion_fragment::load
+        }
+        else {
+            occlusion_fragment_multisampled::load
+        };
+
+        let pipeline = load_pipeline(swapchain_images, device, fullscreen_vertex::load, load_fragment_shader, &PipelineSettings {
+            depth_access: DepthAccess::NoDepth,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
+            samples: SampleCount::Sample1,
+            color_blend_attachment_state: ColorBlendAttachmentState::default(),
+            format: AMBIENT_OCCLUSION_FORMAT,
+            render_target: RenderTarget::AmbientOcclusion,
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for AmbientOcclusion {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
+
+/// Blurs the ambient occlusion image and multiplies it with the color image.
+pub struct AmbientOcclusionComposite {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl AmbientOcclusionComposite {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>, multiply: AttachmentBlend) -> MResult<Self> {
+        let pipeline = load_pipeline(swapchain_images, device, fullscreen_vertex::load, composite_fragment::load, &PipelineSettings {
+            depth_access: DepthAccess::NoDepth,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
+            samples: swapchain_images.color.image().samples(),
+            color_blend_attachment_state: ColorBlendAttachmentState {
+                blend: Some(multiply),
+                ..ColorBlendAttachmentState::default()
+            },
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for AmbientOcclusionComposite {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
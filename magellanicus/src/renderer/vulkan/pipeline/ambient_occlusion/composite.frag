@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+layout(set = 0, binding = 1) uniform texture2D ambient_occlusion;
+
+void main() {
+    // Blur over the 4x4 pattern that the occlusion pass rotates its samples in.
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    ivec2 last_pixel = textureSize(sampler2D(ambient_occlusion, s), 0) - 1;
+    float total = 0.0;
+    for(int y = -2; y < 2; y++) {
+        for(int x = -2; x < 2; x++) {
+            total += texelFetch(sampler2D(ambient_occlusion, s), clamp(pixel + ivec2(x, y), ivec2(0), last_pixel), 0).r;
+        }
+    }
+
+    // This is multiplied with what is already drawn.
+    f_color = vec4(vec3(total / 16.0), 1.0);
+}
@@ -0,0 +1,114 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+
+#ifdef MULTISAMPLED
+layout(set = 0, binding = 1) uniform texture2DMS depth_texture;
+#else
+layout(set = 0, binding = 1) uniform texture2D depth_texture;
+#endif
+
+layout(set = 0, binding = 2) uniform AmbientOcclusionData {
+    mat4 proj;
+    mat4 inverse_proj;
+
+    // xy = offset, zw = extent of the viewport in pixels
+    vec4 viewport;
+
+    // Distance in world units that samples are taken within
+    float radius;
+
+    // How dark fully occluded pixels get (0.0 - 1.0)
+    float intensity;
+} ambient_occlusion_data;
+
+#define SAMPLE_COUNT 16
+
+// Minimum difference in distance from the camera for a sample to be occluded, which prevents flat surfaces from
+// occluding themselves
+#define DEPTH_BIAS 0.025
+
+#define GOLDEN_ANGLE 2.39996323
+#define TAU 6.28318531
+
+float fetch_depth(ivec2 pixel) {
+#ifdef MULTISAMPLED
+    // Only the first sample is used, since occlusion doesn't need to be antialiased.
+    return texelFetch(sampler2DMS(depth_texture, s), pixel, 0).r;
+#else
+    return texelFetch(sampler2D(depth_texture, s), pixel, 0).r;
+#endif
+}
+
+// Get the view-space position of the surface at the given pixel of the frame.
+vec3 view_position(vec2 pixel, float depth) {
+    vec2 ndc = (pixel - ambient_occlusion_data.viewport.xy) / ambient_occlusion_data.viewport.zw * 2.0 - 1.0;
+    vec4 position = ambient_occlusion_data.inverse_proj * vec4(ndc, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+void main() {
+    vec2 pixel = gl_FragCoord.xy;
+    float depth = fetch_depth(ivec2(pixel));
+    vec3 position = view_position(pixel, depth);
+
+    // Reconstruct the normal from the depth before branching so that the derivatives are defined.
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+
+    // Nothing was drawn here (depth is reversed, so 0.0 is infinitely far away).
+    if(depth <= 0.0) {
+        f_color = vec4(1.0);
+        return;
+    }
+
+    // The camera is at the origin, so flip the normal if it faces away from it. This works regardless of the
+    // handedness of the view space.
+    if(dot(normal, position) > 0.0) {
+        normal = -normal;
+    }
+
+    // Rotate the samples in a 4x4 pattern, which the composite pass blurs away.
+    ivec2 pattern = ivec2(pixel) & 3;
+    float angle = float(pattern.x + pattern.y * 4) / 16.0 * TAU;
+    vec3 helper = abs(normal.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0);
+    vec3 tangent = normalize(cross(helper, normal));
+    vec3 binormal = cross(normal, tangent);
+    tangent = tangent * cos(angle) + binormal * sin(angle);
+    binormal = cross(normal, tangent);
+    mat3 tbn = mat3(tangent, binormal, normal);
+
+    float distance_from_camera = length(position);
+    float occlusion = 0.0;
+    for(int i = 0; i < SAMPLE_COUNT; i++) {
+        // Spiral around the hemisphere, with more samples near the surface where occlusion matters the most.
+        float t = (float(i) + 0.5) / float(SAMPLE_COUNT);
+        float phi = float(i) * GOLDEN_ANGLE;
+        float z = 1.0 - t;
+        float r = sqrt(1.0 - z * z);
+        vec3 direction = vec3(cos(phi) * r, sin(phi) * r, z);
+        vec3 sample_position = position + tbn * direction * ambient_occlusion_data.radius * mix(0.1, 1.0, t * t);
+
+        vec4 clip = ambient_occlusion_data.proj * vec4(sample_position, 1.0);
+        if(clip.w <= 0.0) {
+            continue;
+        }
+        vec2 sample_pixel = ambient_occlusion_data.viewport.xy + (clip.xy / clip.w * 0.5 + 0.5) * ambient_occlusion_data.viewport.zw;
+        vec2 viewport_end = ambient_occlusion_data.viewport.xy + ambient_occlusion_data.viewport.zw;
+        if(any(lessThan(sample_pixel, ambient_occlusion_data.viewport.xy)) || any(greaterThanEqual(sample_pixel, viewport_end))) {
+            continue;
+        }
+
+        // The surface and the sample are on the same ray from the camera, so compare their distances.
+        vec3 surface = view_position(sample_pixel, fetch_depth(ivec2(sample_pixel)));
+        float surface_distance = length(surface);
+        if(surface_distance < length(sample_position) - DEPTH_BIAS) {
+            // Fade out occluders that are far in front of the pixel, such as a wall in front of the floor.
+            occlusion += smoothstep(0.0, 1.0, ambient_occlusion_data.radius / abs(distance_from_camera - surface_distance));
+        }
+    }
+
+    float ambient = 1.0 - occlusion / float(SAMPLE_COUNT) * ambient_occlusion_data.intensity;
+    f_color = vec4(vec3(clamp(ambient, 0.0, 1.0)), 1.0);
+}
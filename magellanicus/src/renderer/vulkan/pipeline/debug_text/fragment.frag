@@ -0,0 +1,14 @@
+#version 450
+
+layout(location = 0) in vec2 texture_coords;
+layout(location = 1) in vec4 color;
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+layout(set = 0, binding = 1) uniform texture2D tex;
+
+void main() {
+    // Texture coordinates are in texels so the font stays sharp at any scale.
+    float coverage = texelFetch(sampler2D(tex, s), ivec2(floor(texture_coords)), 0).a;
+    f_color = vec4(color.rgb, color.a * coverage);
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use crate::error::MResult;
+use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
+use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexTextureCoords};
+use crate::renderer::vulkan::{SwapchainImages, VulkanPipelineData};
+use crate::renderer::DebugRenderMode;
+use std::vec;
+use vulkano::device::Device;
+use vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::GraphicsPipeline;
+
+mod vertex {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/renderer/vulkan/pipeline/debug_view/vertex.vert"
+    }
+}
+
+mod fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/debug_view/fragment.frag"
+    }
+}
+
+pub use fragment::DebugViewData;
+
+/// Draws geometry with a debug visualization instead of its shader.
+pub struct DebugView {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl DebugView {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        let pipeline = load_pipeline(swapchain_images, device, vertex::load, fragment::load, &PipelineSettings {
+            depth_access: DepthAccess::DepthWrite,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex(), VulkanModelVertexTextureCoords::per_vertex()],
+            color_blend_attachment_state: ColorBlendAttachmentState::default(),
+            samples: swapchain_images.color.image().samples(),
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for DebugView {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
+
+/// Get the mode to pass to the debug view pipeline, or `None` if `mode` is not drawn with it.
+pub fn debug_view_mode(mode: DebugRenderMode) -> Option<u32> {
+    match mode {
+        DebugRenderMode::None | DebugRenderMode::Overdraw => None,
+        DebugRenderMode::WorldSpaceNormals => Some(0),
+        DebugRenderMode::TangentSpaceNormals => Some(1),
+        DebugRenderMode::Tangents => Some(2),
+        DebugRenderMode::Binormals => Some(3),
+        DebugRenderMode::UVCheckerboard => Some(UV_CHECKERBOARD_MODE),
+        DebugRenderMode::TexelDensity => Some(TEXEL_DENSITY_MODE)
+    }
+}
+
+/// Mode for drawing a checkerboard from texture coordinates.
+pub const UV_CHECKERBOARD_MODE: u32 = 4;
+
+/// Mode for coloring by texel density, which needs the base map size of each geometry.
+pub const TEXEL_DENSITY_MODE: u32 = 5;
@@ -0,0 +1,102 @@
+#version 450
+
+// Must match debug_view_mode() in debug_view.rs
+#define DEBUG_VIEW_WORLD_SPACE_NORMALS 0
+#define DEBUG_VIEW_TANGENT_SPACE_NORMALS 1
+#define DEBUG_VIEW_TANGENTS 2
+#define DEBUG_VIEW_BINORMALS 3
+#define DEBUG_VIEW_UV_CHECKERBOARD 4
+#define DEBUG_VIEW_TEXEL_DENSITY 5
+
+// Number of checkerboard squares across one repetition of a texture
+#define CHECKERBOARD_SQUARES 8.0
+
+// Texels per world unit shown as green; each halving goes toward red and each doubling toward blue
+#define TARGET_TEXEL_DENSITY 128.0
+
+// Number of halvings/doublings from the target density until the color is fully red/blue
+#define TEXEL_DENSITY_RANGE 3.0
+
+layout(push_constant) uniform DebugViewData {
+    // Size of the base map multiplied by its UV scale, or 0 if there is no base map (only used for texel density)
+    vec2 base_map_size;
+    uint mode;
+} debug_view;
+
+layout(location = 0) in vec3 normal;
+layout(location = 1) in vec3 binormal;
+layout(location = 2) in vec3 tangent;
+layout(location = 3) in vec2 texture_coords;
+layout(location = 4) in vec3 world_position;
+
+layout(location = 0) out vec4 f_color;
+
+// Zero-length vectors (i.e. broken vertex data) come out as zero instead of NaN.
+vec3 safe_normalize(vec3 v) {
+    float len = length(v);
+    return len > 0.0 ? v / len : vec3(0.0);
+}
+
+void main() {
+    vec3 n = safe_normalize(normal);
+    vec3 b = safe_normalize(binormal);
+    vec3 t = safe_normalize(tangent);
+
+    vec3 v;
+    switch(debug_view.mode) {
+        case DEBUG_VIEW_WORLD_SPACE_NORMALS:
+            v = n;
+            break;
+        case DEBUG_VIEW_TANGENT_SPACE_NORMALS:
+            // The normal expressed in the tangent frame; this is (0,0,1) for a correct frame.
+            v = vec3(dot(n, t), dot(n, b), dot(n, safe_normalize(cross(t, b))));
+            break;
+        case DEBUG_VIEW_TANGENTS:
+            v = t;
+            break;
+        case DEBUG_VIEW_BINORMALS:
+            v = b;
+            break;
+        case DEBUG_VIEW_UV_CHECKERBOARD: {
+            // Tint by position within the texture so that orientation and seams are visible, too.
+            vec2 square = floor(texture_coords * CHECKERBOARD_SQUARES);
+            float shade = mod(square.x + square.y, 2.0) == 0.0 ? 0.9 : 0.3;
+            vec2 tint = fract(texture_coords);
+            f_color = vec4(shade * vec3(0.5 + 0.5 * tint.x, 0.5 + 0.5 * tint.y, 1.0), 1.0);
+            return;
+        }
+        case DEBUG_VIEW_TEXEL_DENSITY: {
+            if(debug_view.base_map_size.x == 0.0) {
+                f_color = vec4(0.5, 0.5, 0.5, 1.0);
+                return;
+            }
+
+            // Compare how much texture area and world area one pixel covers; texture coordinates and positions are
+            // interpolated linearly across a triangle, so this is constant for each triangle.
+            vec2 texels = texture_coords * debug_view.base_map_size;
+            vec2 texels_dx = dFdx(texels);
+            vec2 texels_dy = dFdy(texels);
+            float texel_area = abs(texels_dx.x * texels_dy.y - texels_dx.y * texels_dy.x);
+            float world_area = length(cross(dFdx(world_position), dFdy(world_position)));
+            if(world_area == 0.0) {
+                f_color = vec4(0.5, 0.5, 0.5, 1.0);
+                return;
+            }
+
+            float density = sqrt(texel_area / world_area);
+            float t = clamp(log2(density / TARGET_TEXEL_DENSITY) / TEXEL_DENSITY_RANGE, -1.0, 1.0);
+            vec3 color = t < 0.0 ? mix(vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), -t) : mix(vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0), t);
+
+            // Overlay the texel grid faintly so individual texels can be seen up close.
+            vec2 texel = floor(texels);
+            float shade = mod(texel.x + texel.y, 2.0) == 0.0 ? 1.0 : 0.85;
+            f_color = vec4(color * shade, 1.0);
+            return;
+        }
+        default:
+            v = vec3(0.0);
+            break;
+    }
+
+    f_color = vec4(v * 0.5 + 0.5, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+#define USE_TEXTURE_COORDS
+
+#include "../include/material.vert"
+
+layout(location = 4) in vec3 normal;
+layout(location = 5) in vec3 binormal;
+layout(location = 6) in vec3 tangent;
+
+layout(location = 0) out vec3 f_normal;
+layout(location = 1) out vec3 f_binormal;
+layout(location = 2) out vec3 f_tangent;
+layout(location = 3) out vec2 f_texture_coords;
+layout(location = 4) out vec3 f_world_position;
+
+void main() {
+    mat4 worldview = uniforms.view * uniforms.world;
+    vec3 vertex_position = position.xyz + uniforms.offset.xyz;
+    gl_Position = uniforms.proj * worldview * vec4(vertex_position, 1.0);
+    f_normal = uniforms.rotation * normal;
+    f_binormal = uniforms.rotation * binormal;
+    f_tangent = uniforms.rotation * tangent;
+    f_texture_coords = texture_coords.xy;
+    f_world_position = (uniforms.world * vec4(vertex_position, 1.0)).xyz;
+}
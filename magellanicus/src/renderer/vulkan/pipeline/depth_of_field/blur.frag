@@ -0,0 +1,85 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+layout(set = 0, binding = 1) uniform texture2D scene;
+
+#ifdef MULTISAMPLED
+layout(set = 0, binding = 2) uniform texture2DMS depth_texture;
+#else
+layout(set = 0, binding = 2) uniform texture2D depth_texture;
+#endif
+
+layout(set = 0, binding = 3) uniform DepthOfFieldData {
+    mat4 inverse_proj;
+
+    // xy = offset, zw = extent of the viewport in pixels
+    vec4 viewport;
+
+    // Distance from the camera in world units that is in focus
+    float focal_distance;
+
+    // Blur radius in pixels of things infinitely far away
+    float max_radius;
+} depth_of_field_data;
+
+#define SAMPLE_COUNT 48
+
+#define GOLDEN_ANGLE 2.39996323
+
+float fetch_depth(ivec2 pixel) {
+#ifdef MULTISAMPLED
+    // Only the first sample is used, since the blur covers more than a pixel anyway.
+    return texelFetch(sampler2DMS(depth_texture, s), pixel, 0).r;
+#else
+    return texelFetch(sampler2D(depth_texture, s), pixel, 0).r;
+#endif
+}
+
+// Get the radius in pixels of the circle of confusion of the surface at the given pixel.
+float blur_radius(vec2 pixel) {
+    float depth = fetch_depth(ivec2(pixel));
+
+    // Nothing was drawn here (depth is reversed, so 0.0 is infinitely far away).
+    if(depth <= 0.0) {
+        return depth_of_field_data.max_radius;
+    }
+
+    vec2 ndc = (pixel - depth_of_field_data.viewport.xy) / depth_of_field_data.viewport.zw * 2.0 - 1.0;
+    vec4 position = depth_of_field_data.inverse_proj * vec4(ndc, depth, 1.0);
+    float distance_from_camera = length(position.xyz / position.w);
+
+    // Thin lens approximation, with things closer than the focal distance blurring no more than things infinitely far.
+    float defocus = abs(1.0 - depth_of_field_data.focal_distance / distance_from_camera);
+    return depth_of_field_data.max_radius * min(defocus, 1.0);
+}
+
+void main() {
+    vec2 pixel = gl_FragCoord.xy;
+    vec2 scene_size = vec2(textureSize(sampler2D(scene, s), 0));
+    vec2 viewport_min = depth_of_field_data.viewport.xy + 0.5;
+    vec2 viewport_max = depth_of_field_data.viewport.xy + depth_of_field_data.viewport.zw - 0.5;
+
+    float radius = blur_radius(pixel);
+    vec4 total = textureLod(sampler2D(scene, s), pixel / scene_size, 0.0);
+    float total_weight = 1.0;
+
+    if(radius >= 0.5) {
+        for(int i = 0; i < SAMPLE_COUNT; i++) {
+            // Spiral outward so the samples cover the circle evenly.
+            float t = sqrt((float(i) + 0.5) / float(SAMPLE_COUNT));
+            float angle = float(i) * GOLDEN_ANGLE;
+            vec2 offset = vec2(cos(angle), sin(angle)) * t * radius;
+            vec2 sample_pixel = clamp(pixel + offset, viewport_min, viewport_max);
+
+            // Only take samples that are blurry enough to reach this pixel so sharp things don't bleed into their
+            // blurred surroundings.
+            float weight = smoothstep(-1.0, 0.0, blur_radius(sample_pixel) - length(offset));
+            total += textureLod(sampler2D(scene, s), sample_pixel / scene_size, 0.0) * weight;
+            total_weight += weight;
+        }
+    }
+
+    f_color = total / total_weight;
+}
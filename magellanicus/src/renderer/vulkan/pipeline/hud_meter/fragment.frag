@@ -0,0 +1,25 @@
+#version 450
+
+layout(push_constant) uniform HudMeterData {
+    vec4 empty_color;
+    float value;
+} meter;
+
+layout(location = 0) in vec2 texture_coords;
+layout(location = 1) in vec4 color;
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+layout(set = 0, binding = 1) uniform texture2D tex;
+
+void main() {
+    vec4 texel = texture(sampler2D(tex, s), texture_coords);
+
+    // Alpha is the gradient that the value fills, so transparent pixels aren't part of the meter.
+    if(texel.a == 0.0) {
+        discard;
+    }
+
+    vec4 meter_color = texel.a <= meter.value ? color : meter.empty_color;
+    f_color = vec4(texel.rgb * meter_color.rgb, meter_color.a);
+}
@@ -1,6 +1,11 @@
 #ifdef USE_LIGHTMAPS
 layout(set = 1, binding = 0) uniform sampler lightmap_sampler;
 layout(set = 1, binding = 1) uniform texture2D lightmap_texture;
+
+layout(set = 1, binding = 2) uniform LightmapData {
+    // 1 if the surface has no lightmap and is lit by the sky instead
+    uint vertex_lighting;
+} lightmap_data;
 #endif
 
 #ifdef USE_FOG
@@ -10,8 +15,25 @@ layout(set = 2, binding = 0) uniform FogData {
     float sky_fog_to;
     float min_opacity;
     float max_opacity;
+
+    // xyz = normal, w = offset; fog fills everything behind the plane
+    vec4 planar_fog_plane;
+    vec4 planar_fog_color;
+
+    // 0.0 if there is no planar fog
+    float planar_fog_max_density;
+    float planar_fog_opaque_distance;
+    float planar_fog_opaque_depth;
+
+    // xyz = normal, w = offset; fragments behind the plane are discarded when drawing reflections, and a zero normal
+    // clips nothing
+    vec4 clip_plane;
 } sky_fog_data;
 
+bool is_clipped(vec3 vertex_position) {
+    return dot(sky_fog_data.clip_plane.xyz, vertex_position) < sky_fog_data.clip_plane.w;
+}
+
 float calculate_fog_density(float distance_from_camera) {
     float clamped = clamp(distance_from_camera, sky_fog_data.sky_fog_from, sky_fog_data.sky_fog_to);
 
@@ -30,9 +52,138 @@ float calculate_fog_density(float distance_from_camera) {
     return interpolation * sky_fog_data.max_opacity;
 }
 
-vec3 apply_fog(float distance_from_camera, vec3 color) {
-    float fog_density = calculate_fog_density(distance_from_camera);
-    return mix(color, sky_fog_data.sky_fog_color.rgb, fog_density);
+// Get how much planar fog is between the camera and a point.
+//
+// The fog gets denser the further it is behind the plane, so this is the length of the part of the ray that is behind
+// the plane, weighted by its average density.
+float calculate_planar_fog_density(vec3 camera_position, vec3 vertex_position) {
+    if(sky_fog_data.planar_fog_max_density == 0.0) {
+        return 0.0;
+    }
+
+    float camera_depth = sky_fog_data.planar_fog_plane.w - dot(sky_fog_data.planar_fog_plane.xyz, camera_position);
+    float vertex_depth = sky_fog_data.planar_fog_plane.w - dot(sky_fog_data.planar_fog_plane.xyz, vertex_position);
+    if(camera_depth <= 0.0 && vertex_depth <= 0.0) {
+        return 0.0;
+    }
+
+    // Clip off the part of the ray in front of the plane.
+    float fogged_distance = distance(camera_position, vertex_position);
+    if(camera_depth < 0.0 || vertex_depth < 0.0) {
+        fogged_distance *= max(camera_depth, vertex_depth) / abs(vertex_depth - camera_depth);
+        camera_depth = max(camera_depth, 0.0);
+        vertex_depth = max(vertex_depth, 0.0);
+    }
+
+    float average_density = min((camera_depth + vertex_depth) / 2.0 / sky_fog_data.planar_fog_opaque_depth, 1.0);
+    float opacity = clamp(fogged_distance * average_density / sky_fog_data.planar_fog_opaque_distance, 0.0, 1.0);
+    return opacity * sky_fog_data.planar_fog_max_density;
+}
+
+vec3 apply_fog(vec3 camera_position, vec3 vertex_position, vec3 color) {
+    float fog_density = calculate_fog_density(distance(camera_position, vertex_position));
+    color = mix(color, sky_fog_data.sky_fog_color.rgb, fog_density);
+
+    float planar_fog_density = calculate_planar_fog_density(camera_position, vertex_position);
+    return mix(color, sky_fog_data.planar_fog_color.rgb, planar_fog_density);
+}
+
+// Must match LightingMode in player_viewport.rs
+#define LIGHTING_MODE_TEXTURED_LIGHTMAPPED 0
+#define LIGHTING_MODE_FULLBRIGHT 1
+#define LIGHTING_MODE_LIGHTMAP_ONLY 2
+#define LIGHTING_MODE_UNLIT 3
+
+// Must match MAX_DYNAMIC_LIGHTS in light.rs
+#define MAX_DYNAMIC_LIGHTS 16
+
+// Must match VulkanDynamicLight in vertex.rs
+struct DynamicLight {
+    vec4 position_radius;
+    vec4 color_falloff;
+    vec4 direction_type;
+    vec4 cutoff;
+    mat3x4 gel_rotation;
+};
+
+// This is per-viewport like fog data, so it is bound with it.
+layout(set = 2, binding = 1) uniform LightingData {
+    uint lighting_mode;
+    uint light_count;
+    float lightmap_exposure;
+
+    // Multiplier applied to sampled lightmaps on top of the exposure (2.0 for the original engine's modulate 2x)
+    float lightmap_overbright;
+
+    // xyz = direction the sun's light travels in, w = 1.0 if there is a sun
+    vec4 sun_direction;
+    vec4 sun_color;
+    vec4 ambient_color;
+
+    DynamicLight lights[MAX_DYNAMIC_LIGHTS];
+} lighting_data;
+
+// Cube maps projected from each dynamic light, or white if a light has no gel.
+layout(set = 2, binding = 2) uniform sampler light_gel_sampler;
+layout(set = 2, binding = 3) uniform textureCube light_gels[MAX_DYNAMIC_LIGHTS];
+
+// Sum the diffuse light of all dynamic lights, or get black if the lighting mode does not use lighting.
+vec3 calculate_dynamic_lighting(vec3 world_position, vec3 world_normal) {
+    if(lighting_data.lighting_mode == LIGHTING_MODE_FULLBRIGHT || lighting_data.lighting_mode == LIGHTING_MODE_UNLIT) {
+        return vec3(0.0);
+    }
+
+    vec3 total = vec3(0.0);
+    for(uint i = 0; i < lighting_data.light_count; i++) {
+        DynamicLight light = lighting_data.lights[i];
+        vec3 to_light = light.position_radius.xyz - world_position;
+        float distance_to_light = length(to_light);
+        float radius = light.position_radius.w;
+        if(distance_to_light >= radius) {
+            continue;
+        }
+
+        vec3 light_direction = to_light / max(distance_to_light, 0.0001);
+        float falloff_radius = light.cutoff.z;
+        float fade = clamp((distance_to_light - falloff_radius) / max(radius - falloff_radius, 0.0001), 0.0, 1.0);
+        float attenuation = pow(1.0 - fade, light.color_falloff.a);
+        if(light.direction_type.w != 0.0) {
+            float cos_angle = dot(-light_direction, light.direction_type.xyz);
+            attenuation *= smoothstep(light.cutoff.y, light.cutoff.x, cos_angle);
+        }
+
+        // This is in non-uniform control flow, so implicit derivatives can't be used.
+        vec3 gel_direction = mat3(light.gel_rotation) * -light_direction;
+        vec3 gel_color = textureLod(samplerCube(light_gels[i], light_gel_sampler), gel_direction, 0.0).rgb;
+
+        total += light.color_falloff.rgb * gel_color * attenuation * max(dot(world_normal, light_direction), 0.0);
+    }
+    return total;
+}
+#endif
+
+#if defined(USE_LIGHTMAPS) && defined(USE_FOG)
+// Light a surface with the sky's ambient light and sun, for surfaces that have no lightmap.
+vec3 calculate_vertex_lighting() {
+#ifdef USE_TANGENT
+    float sun_amount = max(dot(normalize(normal), -lighting_data.sun_direction.xyz), 0.0);
+#else
+    // Without normals, assume the surface is partly facing the sun.
+    float sun_amount = 0.5;
+#endif
+    return lighting_data.ambient_color.rgb + lighting_data.sun_color.rgb * sun_amount;
+}
+
+// Sample the lightmap scaled by the exposure, or get white if the lighting mode does not use lightmaps.
+vec4 sample_lightmap(vec2 lightmap_texture_coordinates) {
+    if(lighting_data.lighting_mode == LIGHTING_MODE_FULLBRIGHT || lighting_data.lighting_mode == LIGHTING_MODE_UNLIT) {
+        return vec4(1.0);
+    }
+    if(lightmap_data.vertex_lighting != 0) {
+        return vec4(calculate_vertex_lighting() * lighting_data.lightmap_exposure, 1.0);
+    }
+    vec4 lightmap_color = texture(sampler2D(lightmap_texture, lightmap_sampler), lightmap_texture_coordinates);
+    return vec4(lightmap_color.rgb * lighting_data.lightmap_exposure * lighting_data.lightmap_overbright, lightmap_color.a);
 }
 #endif
 
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use vulkano::device::Device;
+use std::vec;
+use vulkano::pipeline::graphics::color_blend::{ColorBlendAttachmentState, ColorComponents};
+use vulkano::pipeline::graphics::depth_stencil::{CompareOp, StencilOp, StencilOpState, StencilOps, StencilState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use crate::error::MResult;
+use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
+use crate::renderer::vulkan::vertex::VulkanModelVertex;
+use crate::renderer::vulkan::{SwapchainImages, VulkanPipelineData};
+
+mod vertex {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/renderer/vulkan/pipeline/mirror/vertex.vert"
+    }
+}
+
+mod mask_fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/mirror/mask.frag"
+    }
+}
+
+mod clear_fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/mirror/clear.frag"
+    }
+}
+
+pub use clear_fragment::MirrorClearData;
+
+/// Stencil value of pixels where the mirror being drawn is visible.
+const MIRROR_STENCIL_REFERENCE: u32 = 1;
+
+/// Get a stencil state that tests against pixels marked as being part of a mirror with `compare_op`, doing `pass_op`
+/// to pixels that pass.
+pub fn mirror_stencil(compare_op: CompareOp, pass_op: StencilOp) -> StencilState {
+    let state = StencilOpState {
+        ops: StencilOps {
+            fail_op: StencilOp::Keep,
+            pass_op,
+            depth_fail_op: StencilOp::Keep,
+            compare_op
+        },
+        reference: MIRROR_STENCIL_REFERENCE,
+        ..StencilOpState::default()
+    };
+    StencilState { front: state, back: state }
+}
+
+fn no_color() -> ColorBlendAttachmentState {
+    ColorBlendAttachmentState {
+        color_write_mask: ColorComponents::empty(),
+        ..ColorBlendAttachmentState::default()
+    }
+}
+
+/// Marks the pixels where a mirror is visible in the stencil image.
+pub struct MirrorMask {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl MirrorMask {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        let pipeline = load_pipeline(swapchain_images, device, vertex::load, mask_fragment::load, &PipelineSettings {
+            depth_access: DepthAccess::DepthReadOnlyTransparent,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
+            color_blend_attachment_state: no_color(),
+            samples: swapchain_images.color.image().samples(),
+            stencil: Some(mirror_stencil(CompareOp::Always, StencilOp::Replace)),
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+/// Clears the color and depth of the pixels marked by [`MirrorMask`] so the reflection can be drawn in them.
+pub struct MirrorClear {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl MirrorClear {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        let pipeline = load_pipeline(swapchain_images, device, vertex::load, clear_fragment::load, &PipelineSettings {
+            depth_access: DepthAccess::DepthOverwrite,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
+            color_blend_attachment_state: ColorBlendAttachmentState::default(),
+            samples: swapchain_images.color.image().samples(),
+            stencil: Some(mirror_stencil(CompareOp::Equal, StencilOp::Keep)),
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+/// Puts the mirror's depth back in the pixels marked by [`MirrorMask`] and unmarks them.
+///
+/// This way, transparent geometry behind the mirror stays hidden.
+pub struct MirrorRestore {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl MirrorRestore {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        let pipeline = load_pipeline(swapchain_images, device, vertex::load, mask_fragment::load, &PipelineSettings {
+            depth_access: DepthAccess::DepthOverwrite,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
+            color_blend_attachment_state: no_color(),
+            samples: swapchain_images.color.image().samples(),
+            stencil: Some(mirror_stencil(CompareOp::Equal, StencilOp::Zero)),
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for MirrorMask {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
+
+impl VulkanPipelineData for MirrorClear {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
+
+impl VulkanPipelineData for MirrorRestore {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
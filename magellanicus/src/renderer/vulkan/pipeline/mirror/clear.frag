@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform MirrorClearData {
+    vec4 color;
+} clear_data;
+
+void main() {
+    // Depth is reversed, so 0.0 is infinitely far away.
+    f_color = clear_data.color;
+    gl_FragDepth = 0.0;
+}
@@ -0,0 +1,5 @@
+#version 450
+
+// Only the stencil and depth are written.
+void main() {
+}
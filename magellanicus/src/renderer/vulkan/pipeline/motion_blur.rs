@@ -0,0 +1,74 @@
+use crate::error::MResult;
+use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
+use crate::renderer::vulkan::vertex::VulkanModelVertex;
+use crate::renderer::vulkan::{SwapchainImages, VulkanPipelineData};
+use std::sync::Arc;
+use std::vec;
+use vulkano::device::Device;
+use vulkano::image::SampleCount;
+use vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::GraphicsPipeline;
+
+mod fullscreen_vertex {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/renderer/vulkan/pipeline/ambient_occlusion/fullscreen.vert"
+    }
+}
+
+mod blur_fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/motion_blur/blur.frag"
+    }
+}
+
+mod blur_fragment_multisampled {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/motion_blur/blur.frag",
+        define: [("MULTISAMPLED", "1")]
+    }
+}
+
+pub use blur_fragment::MotionBlurData;
+
+/// Draws a copy of the scene over the color image, blurred along how far each pixel moved since the last frame.
+pub struct MotionBlur {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl MotionBlur {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        // Multisampled depth images have to be read as such.
+        let load_fragment_shader = if swapchain_images.depth.image().samples() == SampleCount::Sample1 {
+            blur_fragment::load
+        }
+        else {
+            blur_fragment_multisampled::load
+        };
+
+        let pipeline = load_pipeline(swapchain_images, device, fullscreen_vertex::load, load_fragment_shader, &PipelineSettings {
+            depth_access: DepthAccess::NoDepth,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
+            samples: swapchain_images.color.image().samples(),
+            color_blend_attachment_state: ColorBlendAttachmentState::default(),
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for MotionBlur {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
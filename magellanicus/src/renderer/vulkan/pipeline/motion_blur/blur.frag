@@ -0,0 +1,74 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+layout(set = 0, binding = 1) uniform texture2D scene;
+
+#ifdef MULTISAMPLED
+layout(set = 0, binding = 2) uniform texture2DMS depth_texture;
+#else
+layout(set = 0, binding = 2) uniform texture2D depth_texture;
+#endif
+
+layout(set = 0, binding = 3) uniform MotionBlurData {
+    mat4 inverse_view_proj;
+    mat4 previous_view_proj;
+
+    // xy = offset, zw = extent of the viewport in pixels
+    vec4 viewport;
+
+    // Fraction of the motion since the last frame to blur across
+    float intensity;
+
+    // Longest blur in pixels
+    float max_length;
+} motion_blur_data;
+
+#define SAMPLE_COUNT 16
+
+float fetch_depth(ivec2 pixel) {
+#ifdef MULTISAMPLED
+    // Only the first sample is used, since the blur covers more than a pixel anyway.
+    return texelFetch(sampler2DMS(depth_texture, s), pixel, 0).r;
+#else
+    return texelFetch(sampler2D(depth_texture, s), pixel, 0).r;
+#endif
+}
+
+void main() {
+    vec2 pixel = gl_FragCoord.xy;
+    vec2 scene_size = vec2(textureSize(sampler2D(scene, s), 0));
+    vec2 ndc = (pixel - motion_blur_data.viewport.xy) / motion_blur_data.viewport.zw * 2.0 - 1.0;
+
+    // Find where the surface at this pixel was on screen last frame. This stays homogeneous so that pixels where
+    // nothing was drawn (at a depth of 0.0, infinitely far away) reproject as directions.
+    vec4 world = motion_blur_data.inverse_view_proj * vec4(ndc, fetch_depth(ivec2(pixel)), 1.0);
+    vec4 previous = motion_blur_data.previous_view_proj * world;
+
+    vec2 velocity = vec2(0.0);
+    if(previous.w > 0.0) {
+        velocity = (ndc - previous.xy / previous.w) * 0.5 * motion_blur_data.viewport.zw * motion_blur_data.intensity;
+        float velocity_length = length(velocity);
+        if(velocity_length > motion_blur_data.max_length) {
+            velocity *= motion_blur_data.max_length / velocity_length;
+        }
+    }
+
+    if(dot(velocity, velocity) < 0.25) {
+        f_color = textureLod(sampler2D(scene, s), pixel / scene_size, 0.0);
+        return;
+    }
+
+    vec2 viewport_min = motion_blur_data.viewport.xy + 0.5;
+    vec2 viewport_max = motion_blur_data.viewport.xy + motion_blur_data.viewport.zw - 0.5;
+    vec4 total = vec4(0.0);
+    for(int i = 0; i < SAMPLE_COUNT; i++) {
+        // Centered on the pixel so that the blur doesn't shift the image.
+        float t = (float(i) + 0.5) / float(SAMPLE_COUNT) - 0.5;
+        vec2 sample_pixel = clamp(pixel + velocity * t, viewport_min, viewport_max);
+        total += textureLod(sampler2D(scene, s), sample_pixel / scene_size, 0.0);
+    }
+
+    f_color = total / float(SAMPLE_COUNT);
+}
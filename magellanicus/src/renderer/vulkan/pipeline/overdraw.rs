@@ -0,0 +1,105 @@
+use crate::error::MResult;
+use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings, RenderTarget};
+use crate::renderer::vulkan::vertex::VulkanModelVertex;
+use crate::renderer::vulkan::{SwapchainImages, VulkanPipelineData, OVERDRAW_FORMAT};
+use std::sync::Arc;
+use std::vec;
+use vulkano::device::Device;
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendAttachmentState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::GraphicsPipeline;
+
+mod count_vertex {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/renderer/vulkan/pipeline/overdraw/count.vert"
+    }
+}
+
+mod count_fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/overdraw/count.frag"
+    }
+}
+
+mod heatmap_vertex {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/renderer/vulkan/pipeline/overdraw/heatmap.vert"
+    }
+}
+
+mod heatmap_fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/overdraw/heatmap.frag"
+    }
+}
+
+/// Counts how many times each pixel is drawn to by adding to the overdraw image.
+pub struct OverdrawCount {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl OverdrawCount {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>, depth_access: DepthAccess) -> MResult<Self> {
+        let pipeline = load_pipeline(swapchain_images, device, count_vertex::load, count_fragment::load, &PipelineSettings {
+            depth_access,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
+            samples: swapchain_images.overdraw.image().samples(),
+            color_blend_attachment_state: ColorBlendAttachmentState {
+                blend: Some(AttachmentBlend::additive()),
+                ..ColorBlendAttachmentState::default()
+            },
+            format: OVERDRAW_FORMAT,
+            render_target: RenderTarget::Overdraw,
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for OverdrawCount {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
+
+/// Draws the (resolved) overdraw image as a heatmap.
+pub struct OverdrawHeatmap {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl OverdrawHeatmap {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        let pipeline = load_pipeline(swapchain_images, device, heatmap_vertex::load, heatmap_fragment::load, &PipelineSettings {
+            depth_access: DepthAccess::NoDepth,
+            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
+            samples: swapchain_images.color.image().samples(),
+            color_blend_attachment_state: ColorBlendAttachmentState::default(),
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for OverdrawHeatmap {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,8 @@
+#version 450
+
+layout(location = 0) out vec4 f_count;
+
+void main() {
+    // Each fragment adds 1 to the count, which is additively blended into a UNORM8 image.
+    f_count = vec4(1.0 / 255.0);
+}
@@ -0,0 +1,9 @@
+#version 450
+
+#include "../include/material.vert"
+
+void main() {
+    mat4 worldview = uniforms.view * uniforms.world;
+    vec3 vertex_position = position.xyz + uniforms.offset.xyz;
+    gl_Position = uniforms.proj * worldview * vec4(vertex_position, 1.0);
+}
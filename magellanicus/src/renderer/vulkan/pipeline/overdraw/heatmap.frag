@@ -0,0 +1,30 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+layout(set = 0, binding = 1) uniform texture2D overdraw;
+
+// Number of times a pixel has to be drawn to be shown as white
+#define HEATMAP_MAX_COUNT 16.0
+
+void main() {
+    float count = round(texelFetch(sampler2D(overdraw, s), ivec2(gl_FragCoord.xy), 0).r * 255.0);
+    if(count == 0.0) {
+        f_color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    // blue (drawn once) -> cyan -> green -> yellow -> red -> white (drawn HEATMAP_MAX_COUNT or more times)
+    const vec3 colors[6] = vec3[](
+        vec3(0.0, 0.0, 1.0),
+        vec3(0.0, 1.0, 1.0),
+        vec3(0.0, 1.0, 0.0),
+        vec3(1.0, 1.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 1.0, 1.0)
+    );
+    float position = clamp((count - 1.0) / (HEATMAP_MAX_COUNT - 1.0), 0.0, 1.0) * 5.0;
+    int index = min(int(position), 4);
+    f_color = vec4(mix(colors[index], colors[index + 1], position - float(index)), 1.0);
+}
@@ -2,12 +2,13 @@ use crate::error::MResult;
 use std::sync::Arc;
 use std::vec::Vec;
 use std::vec;
+use vulkano::descriptor_set::layout::{DescriptorSetLayoutBinding, DescriptorType};
 use vulkano::device::Device;
 use vulkano::format::Format;
-use vulkano::image::SampleCount;
+use vulkano::image::{ImageAspects, SampleCount};
 use vulkano::pipeline::graphics::color_blend::{ColorBlendAttachmentState, ColorBlendState};
-use vulkano::pipeline::graphics::depth_stencil::{CompareOp, DepthState, DepthStencilState};
-use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::depth_stencil::{CompareOp, DepthState, DepthStencilState, StencilState};
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
 use vulkano::pipeline::graphics::multisample::MultisampleState;
 use vulkano::pipeline::graphics::rasterization::{FrontFace, RasterizationState};
 use vulkano::pipeline::graphics::subpass::PipelineRenderingCreateInfo;
@@ -17,11 +18,16 @@ use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
 use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
 use vulkano::pipeline::{DynamicState, GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo};
 use vulkano::render_pass::Subpass;
+use vulkano::shader::{ShaderStages, SpecializationConstant};
 use crate::renderer::vulkan::{SwapchainImages, OFFLINE_PIPELINE_COLOR_FORMAT};
+use crate::renderer::MAX_DYNAMIC_LIGHTS;
 
+/// How a pipeline tests and writes to the depth buffer.
+///
+/// Depth is reversed, so 1.0 is the near plane and 0.0 is infinitely far away.
 #[derive(Copy, Clone, Default, PartialEq)]
 pub enum DepthAccess {
-    /// The depth as determined by the vertex shader must be less than or equal.
+    /// The depth as determined by the vertex shader must be greater than or equal.
     ///
     /// This will pass as long as nothing is in front of the vertices.
     ///
@@ -36,19 +42,39 @@ pub enum DepthAccess {
     /// This is used if one needs to overlay on top of something already written.
     DepthReadOnly,
 
-    /// The depth as determined by the vertex must be less than or equal.
+    /// The depth as determined by the vertex must be greater than or equal.
     ///
     /// This will pass as long as nothing is in front of the vertices.
     ///
     /// This is used if one needs to write to the depth buffer.
     DepthWrite,
 
+    /// The depth buffer is written to without being tested.
+    ///
+    /// This is used for replacing the depth of an area (e.g. where a mirror is).
+    DepthOverwrite,
+
     /// The depth buffer is completely ignored.
     ///
     /// Draw on top of whatever is there.
     NoDepth
 }
 
+#[derive(Copy, Clone, Default, PartialEq)]
+pub enum RenderTarget {
+    #[default]
+    /// Draw to the color image.
+    Color,
+
+    /// Draw to the overdraw image.
+    ///
+    /// This is used for counting how many times each pixel is drawn to.
+    Overdraw,
+
+    /// Draw to the ambient occlusion image, without a depth image.
+    AmbientOcclusion
+}
+
 #[derive(Clone)]
 pub struct PipelineSettings {
     /// Determines how depth is accessed.
@@ -64,7 +90,21 @@ pub struct PipelineSettings {
     pub samples: SampleCount,
 
     /// Color format to use
-    pub format: Format
+    pub format: Format,
+
+    /// Images to draw to.
+    pub render_target: RenderTarget,
+
+    /// How vertices are assembled into primitives.
+    pub topology: PrimitiveTopology,
+
+    /// How the stencil aspect of the depth image is tested and written, if at all.
+    ///
+    /// This must only be set if the depth image has a stencil aspect.
+    pub stencil: Option<StencilState>,
+
+    /// Values of the fragment shader's specialization constants, by constant ID.
+    pub fragment_specialization: Vec<(u32, SpecializationConstant)>
 }
 
 impl Default for PipelineSettings {
@@ -74,7 +114,11 @@ impl Default for PipelineSettings {
             vertex_buffer_descriptions: Default::default(),
             color_blend_attachment_state: Default::default(),
             samples: SampleCount::Sample1,
-            format: OFFLINE_PIPELINE_COLOR_FORMAT
+            format: OFFLINE_PIPELINE_COLOR_FORMAT,
+            render_target: RenderTarget::Color,
+            topology: PrimitiveTopology::TriangleList,
+            stencil: None,
+            fragment_specialization: Vec::new()
         }
     }
 }
@@ -90,6 +134,7 @@ pub fn load_pipeline(
         .entry_point("main")
         .expect("Missing main() entry point for vertex pipeline!");
     let fragment_shader = load_fragment_shader(device.clone())?
+        .specialize(settings.fragment_specialization.iter().cloned().collect())?
         .entry_point("main")
         .expect("Missing main() entry point for fragment pipeline!");
 
@@ -102,9 +147,12 @@ pub fn load_pipeline(
         PipelineShaderStageCreateInfo::new(fragment_shader),
     ];
 
+    let mut layout_create_info = PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages);
+    add_light_gel_bindings(&mut layout_create_info);
+
     let layout = PipelineLayout::new(
         device.clone(),
-        PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+        layout_create_info
             .into_pipeline_layout_create_info(device.clone())
             .unwrap(),
     )?;
@@ -114,14 +162,23 @@ pub fn load_pipeline(
         settings.color_blend_attachment_state.clone(),
     );
 
+    let framebuffer = match settings.render_target {
+        RenderTarget::Color => swapchain_images.framebuffer.as_ref(),
+        RenderTarget::Overdraw => swapchain_images.overdraw_framebuffer.as_ref(),
+        RenderTarget::AmbientOcclusion => swapchain_images.ambient_occlusion_framebuffer.as_ref()
+    };
+
     let pipeline = GraphicsPipeline::new(
         device.clone(),
         None,
         GraphicsPipelineCreateInfo {
             stages: stages.into_iter().collect(),
             vertex_input_state: Some(vertex_input_state),
-            input_assembly_state: Some(InputAssemblyState::default()),
-            viewport_state: Some(if let Some(fb) = swapchain_images.framebuffer.as_ref() {
+            input_assembly_state: Some(InputAssemblyState {
+                topology: settings.topology,
+                ..InputAssemblyState::default()
+            }),
+            viewport_state: Some(if let Some(fb) = framebuffer {
                 ViewportState {
                     viewports: [Viewport {
                         offset: [0.0, 0.0],
@@ -147,25 +204,37 @@ pub fn load_pipeline(
                 DynamicState::Viewport,
                 DynamicState::CullMode,
             ].into_iter().collect(),
-            depth_stencil_state: Some(DepthStencilState {
-                depth: Some(DepthState {
-                    write_enable: settings.depth_access == DepthAccess::DepthWrite,
-                    compare_op: match settings.depth_access {
-                        DepthAccess::DepthWrite => CompareOp::LessOrEqual,
-                        DepthAccess::DepthReadOnly => CompareOp::Equal,
-                        DepthAccess::DepthReadOnlyTransparent => CompareOp::LessOrEqual,
-                        DepthAccess::NoDepth => CompareOp::Always
-                    }
-                }),
-                ..DepthStencilState::default()
-            }),
-            subpass: Some(if let Some(fb) = swapchain_images.framebuffer.as_ref() {
+            // The ambient occlusion image is drawn without a depth image so the depth image can be read.
+            depth_stencil_state: if settings.render_target == RenderTarget::AmbientOcclusion {
+                None
+            }
+            else {
+                Some(DepthStencilState {
+                    depth: Some(DepthState {
+                        write_enable: matches!(settings.depth_access, DepthAccess::DepthWrite | DepthAccess::DepthOverwrite),
+                        compare_op: match settings.depth_access {
+                            DepthAccess::DepthWrite => CompareOp::GreaterOrEqual,
+                            DepthAccess::DepthOverwrite => CompareOp::Always,
+                            DepthAccess::DepthReadOnly => CompareOp::Equal,
+                            DepthAccess::DepthReadOnlyTransparent => CompareOp::GreaterOrEqual,
+                            DepthAccess::NoDepth => CompareOp::Always
+                        }
+                    }),
+                    stencil: settings.stencil.clone(),
+                    ..DepthStencilState::default()
+                })
+            },
+            subpass: Some(if let Some(fb) = framebuffer {
                 Subpass::from(fb.render_pass().clone(), 0).unwrap().into()
             }
             else {
+                let depth_format = swapchain_images.depth.format();
+                let has_depth = settings.render_target != RenderTarget::AmbientOcclusion;
+                let has_stencil = has_depth && depth_format.aspects().intersects(ImageAspects::STENCIL);
                 let create_info = PipelineRenderingCreateInfo {
                     color_attachment_formats: vec![Some(settings.format)],
-                    depth_attachment_format: Some(swapchain_images.depth.format()),
+                    depth_attachment_format: has_depth.then_some(depth_format),
+                    stencil_attachment_format: has_stencil.then_some(depth_format),
                     ..Default::default()
                 };
                 create_info.into()
@@ -177,3 +246,26 @@ pub fn load_pipeline(
 
     Ok(pipeline)
 }
+
+/// Add the light gel bindings to the per-viewport set (set 2) of material pipelines whose shaders don't sample them.
+///
+/// Every material pipeline is bound with the same per-viewport descriptor set, so they all need the same layout for
+/// it, but layouts are otherwise only made from the bindings each shader uses.
+fn add_light_gel_bindings(layout_create_info: &mut PipelineDescriptorSetLayoutCreateInfo) {
+    let Some(set) = layout_create_info.set_layouts.get_mut(2) else {
+        return
+    };
+    if set.bindings.is_empty() {
+        return
+    }
+
+    set.bindings.entry(2).or_insert_with(|| DescriptorSetLayoutBinding {
+        stages: ShaderStages::FRAGMENT,
+        ..DescriptorSetLayoutBinding::descriptor_type(DescriptorType::Sampler)
+    });
+    set.bindings.entry(3).or_insert_with(|| DescriptorSetLayoutBinding {
+        stages: ShaderStages::FRAGMENT,
+        descriptor_count: MAX_DYNAMIC_LIGHTS as u32,
+        ..DescriptorSetLayoutBinding::descriptor_type(DescriptorType::SampledImage)
+    });
+}
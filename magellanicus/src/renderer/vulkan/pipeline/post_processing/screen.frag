@@ -0,0 +1,66 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+layout(set = 0, binding = 1) uniform texture2D scene;
+
+layout(set = 0, binding = 3) uniform PostProcessingData {
+    // xy = offset, zw = extent of the viewport in pixels
+    vec4 viewport;
+
+    // RGB color blended over the scene by A
+    vec4 color_overlay;
+
+    // Multiplier applied to the scene's color
+    float exposure;
+
+    // How much bright pixels glow onto their surroundings
+    float bloom;
+
+    // Farthest a pixel glows, in pixels
+    float bloom_radius;
+
+    // Fraction of the viewport's height covered at each of the top and bottom
+    float letterbox;
+} post_processing_data;
+
+#define BLOOM_SAMPLE_COUNT 24
+#define BLOOM_THRESHOLD 0.7
+#define GOLDEN_ANGLE 2.39996323
+
+vec3 sample_scene(vec2 pixel, vec2 scene_size) {
+    vec2 viewport_min = post_processing_data.viewport.xy + 0.5;
+    vec2 viewport_max = post_processing_data.viewport.xy + post_processing_data.viewport.zw - 0.5;
+    return textureLod(sampler2D(scene, s), clamp(pixel, viewport_min, viewport_max) / scene_size, 0.0).rgb * post_processing_data.exposure;
+}
+
+void main() {
+    vec2 pixel = gl_FragCoord.xy;
+    float relative_y = (pixel.y - post_processing_data.viewport.y) / post_processing_data.viewport.w;
+    if(relative_y < post_processing_data.letterbox || relative_y > 1.0 - post_processing_data.letterbox) {
+        f_color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec2 scene_size = vec2(textureSize(sampler2D(scene, s), 0));
+    vec3 color = sample_scene(pixel, scene_size);
+
+    if(post_processing_data.bloom > 0.0) {
+        // Spiral outward so the samples cover the disk evenly, with farther samples glowing less.
+        vec3 glow = vec3(0.0);
+        float total_weight = 0.0;
+        for(int i = 0; i < BLOOM_SAMPLE_COUNT; i++) {
+            float radius = sqrt((float(i) + 0.5) / float(BLOOM_SAMPLE_COUNT));
+            float angle = float(i) * GOLDEN_ANGLE;
+            vec2 offset = vec2(cos(angle), sin(angle)) * radius * post_processing_data.bloom_radius;
+            float weight = 1.0 - radius;
+            glow += max(sample_scene(pixel + offset, scene_size) - BLOOM_THRESHOLD, vec3(0.0)) * weight;
+            total_weight += weight;
+        }
+        color += glow / total_weight * post_processing_data.bloom;
+    }
+
+    color = mix(color, post_processing_data.color_overlay.rgb, post_processing_data.color_overlay.a);
+    f_color = vec4(color, 1.0);
+}
@@ -1,4 +1,5 @@
 use crate::error::MResult;
+use crate::renderer::vulkan::pipeline::mirror::mirror_stencil;
 use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
 use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexLightmapTextureCoords, VulkanModelVertexTextureCoords};
 use crate::renderer::vulkan::{SwapchainImages, VulkanPipelineData};
@@ -6,8 +7,10 @@ use std::sync::Arc;
 use std::vec;
 use vulkano::device::Device;
 use vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState;
+use vulkano::pipeline::graphics::depth_stencil::{CompareOp, StencilOp, StencilState};
 use vulkano::pipeline::graphics::vertex_input::Vertex;
 use vulkano::pipeline::GraphicsPipeline;
+use vulkano::shader::SpecializationConstant;
 
 mod vertex {
     vulkano_shaders::shader! {
@@ -24,7 +27,26 @@ mod fragment {
     }
 }
 
-pub use fragment::ShaderEnvironmentData;
+pub use fragment::{LightmapData, ShaderEnvironmentData};
+
+/// Stages of shader_environment that are skipped for far away geometry.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct ReducedStages {
+    pub bump: bool,
+    pub reflection: bool,
+    pub detail: bool
+}
+
+impl ReducedStages {
+    /// Get every combination of stages that skips at least one.
+    pub fn all_reduced() -> impl Iterator<Item = ReducedStages> {
+        (1..8u32).map(|bits| ReducedStages { bump: bits & 1 != 0, reflection: bits & 2 != 0, detail: bits & 4 != 0 })
+    }
+
+    pub fn is_reduced(self) -> bool {
+        self.bump || self.reflection || self.detail
+    }
+}
 
 pub struct ShaderEnvironment {
     pub pipeline: Arc<GraphicsPipeline>
@@ -32,11 +54,31 @@ pub struct ShaderEnvironment {
 
 impl ShaderEnvironment {
     pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        Self::load(swapchain_images, device, None, ReducedStages::default())
+    }
+
+    /// Load a pipeline that only draws where a mirror was marked in the stencil image.
+    pub fn new_mirrored(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        Self::load(swapchain_images, device, Some(mirror_stencil(CompareOp::Equal, StencilOp::Keep)), ReducedStages::default())
+    }
+
+    /// Load a pipeline that skips `stages`.
+    pub fn new_reduced(swapchain_images: &SwapchainImages, device: Arc<Device>, stages: ReducedStages) -> MResult<Self> {
+        Self::load(swapchain_images, device, None, stages)
+    }
+
+    fn load(swapchain_images: &SwapchainImages, device: Arc<Device>, stencil: Option<StencilState>, stages: ReducedStages) -> MResult<Self> {
         let pipeline = load_pipeline(swapchain_images, device, vertex::load, fragment::load, &PipelineSettings {
             depth_access: DepthAccess::DepthWrite,
             vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex(), VulkanModelVertexTextureCoords::per_vertex(), VulkanModelVertexLightmapTextureCoords::per_vertex()],
             samples: swapchain_images.color.image().samples(),
             color_blend_attachment_state: ColorBlendAttachmentState::default(),
+            stencil,
+            fragment_specialization: vec![
+                (0, SpecializationConstant::Bool(stages.bump)),
+                (1, SpecializationConstant::Bool(stages.reflection)),
+                (2, SpecializationConstant::Bool(stages.detail))
+            ],
             ..Default::default()
         })?;
 
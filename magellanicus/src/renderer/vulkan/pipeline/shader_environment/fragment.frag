@@ -27,6 +27,12 @@ layout(set = 3, binding = 5) uniform texture2D micro_detail_map;
 layout(set = 3, binding = 6) uniform texture2D bump_map;
 layout(set = 3, binding = 7) uniform textureCube cubemap;
 
+// Stages skipped for far away geometry. These are set per pipeline so that skipped stages cost nothing, and the
+// textures are still referenced so that every variant has the same descriptor set layout.
+layout(constant_id = 0) const bool SKIP_BUMP = false;
+layout(constant_id = 1) const bool SKIP_REFLECTION = false;
+layout(constant_id = 2) const bool SKIP_DETAIL = false;
+
 vec3 blend_with_mix_type(vec3 color, vec3 with, uint blend_type) {
     switch(blend_type) {
         case 0:
@@ -40,19 +46,41 @@ vec3 blend_with_mix_type(vec3 color, vec3 with, uint blend_type) {
     }
 }
 
+// Get the highlight of the sun reflected towards the camera, tinted from the parallel color (grazing angles) to the
+// perpendicular color (head-on).
+vec3 calculate_sun_specular(vec3 reflection_normal, float diffuse_reflection) {
+    if(lighting_data.sun_direction.w == 0.0) {
+        return vec3(0.0);
+    }
+
+    float exponent = (shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_SPECULAR_EXTRA_SHINY) != 0 ? 64.0 : 16.0;
+    float highlight = pow(max(dot(reflection_normal, -lighting_data.sun_direction.xyz), 0.0), exponent);
+    vec3 tint = mix(shader_environment_data.parallel_color.rgb, shader_environment_data.perpendicular_color.rgb, diffuse_reflection);
+    return tint * lighting_data.sun_color.rgb * highlight * shader_environment_data.specular_brightness;
+}
+
 void main() {
+    // Only what is above the water is reflected in it.
+    if(is_clipped(vertex_position)) {
+        discard;
+    }
+
     vec3 camera_difference = camera_position - vertex_position;
-    float distance_from_camera = distance(camera_position, vertex_position);
 
     vec4 base_map_color = texture(sampler2D(base_map, map_sampler), base_map_texture_coordinates);
 
-    vec4 bump_color = texture(
-        sampler2D(bump_map, map_sampler),
-        base_map_texture_coordinates * shader_environment_data.bump_map_scale
-    );
+    // Alpha testing needs the bump map even if bump mapping is skipped.
+    bool alpha_tested = (shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_ALPHA_TEST) == 1;
+    vec4 bump_color = vec4(0.5, 0.5, 1.0, 1.0);
+    if(!SKIP_BUMP || alpha_tested) {
+        bump_color = texture(
+            sampler2D(bump_map, map_sampler),
+            base_map_texture_coordinates * shader_environment_data.bump_map_scale
+        );
+    }
 
     // Alpha testing
-    if((shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_ALPHA_TEST) == 1) {
+    if(alpha_tested) {
         // TODO: Is it just normal that discards 0-alpha pixels? The alpha is used for blending and specular on other
         // types, so it makes no sense to test alpha on those types.
         if(shader_environment_data.shader_environment_type == SHADER_ENVIRONMENT_TYPE_NORMAL && base_map_color.a == 0.0) {
@@ -65,27 +93,44 @@ void main() {
     }
     bump_color.a = 1.0;
 
-    vec3 bump_vector = bump_color.rgb * 2.0 - 1.0;
-
-    vec4 primary_detail_map_color = texture(
-        sampler2D(primary_detail_map, map_sampler),
-        base_map_texture_coordinates * shader_environment_data.primary_detail_map_scale
-    );
+    vec3 bump_vector = SKIP_BUMP ? vec3(0.0, 0.0, 1.0) : bump_color.rgb * 2.0 - 1.0;
+
+    // The detail maps' alpha is still needed for the specular mask of blended base specular shaders.
+    bool needs_detail = !SKIP_DETAIL || (!SKIP_REFLECTION && shader_environment_data.shader_environment_type == SHADER_ENVIRONMENT_TYPE_BLENDED_BASE_SPECULAR);
+    vec4 primary_detail_map_color = vec4(0.5);
+    vec4 secondary_detail_map_color = vec4(0.5);
+    vec4 micro_detail_map_color = vec4(0.5);
+    if(needs_detail) {
+        primary_detail_map_color = texture(
+            sampler2D(primary_detail_map, map_sampler),
+            base_map_texture_coordinates * shader_environment_data.primary_detail_map_scale
+        );
+
+        secondary_detail_map_color = texture(
+            sampler2D(secondary_detail_map, map_sampler),
+            base_map_texture_coordinates * shader_environment_data.secondary_detail_map_scale
+        );
+    }
+    if(!SKIP_DETAIL) {
+        micro_detail_map_color = texture(
+            sampler2D(micro_detail_map, map_sampler),
+            base_map_texture_coordinates * shader_environment_data.micro_detail_map_scale
+        );
+    }
 
-    vec4 secondary_detail_map_color = texture(
-        sampler2D(secondary_detail_map, map_sampler),
-        base_map_texture_coordinates * shader_environment_data.secondary_detail_map_scale
-    );
+    vec3 world_normal = calculate_world_normal(bump_vector);
 
-    vec4 micro_detail_map_color = texture(
-        sampler2D(micro_detail_map, map_sampler),
-        base_map_texture_coordinates * shader_environment_data.micro_detail_map_scale
-    );
+    vec4 lightmap_color = sample_lightmap(lightmap_texture_coordinates);
+    lightmap_color.rgb += calculate_dynamic_lighting(vertex_position, normalize(world_normal));
 
-    vec4 lightmap_color = texture(
-        sampler2D(lightmap_texture, lightmap_sampler),
-        lightmap_texture_coordinates
-    );
+    switch(lighting_data.lighting_mode) {
+        case LIGHTING_MODE_LIGHTMAP_ONLY:
+            f_color = vec4(apply_fog(camera_position, vertex_position, lightmap_color.rgb), 1.0);
+            return;
+        case LIGHTING_MODE_UNLIT:
+            f_color = vec4(apply_fog(camera_position, vertex_position, base_map_color.rgb), 1.0);
+            return;
+    }
 
     vec4 blended_detail;
     switch(shader_environment_data.shader_environment_type) {
@@ -101,30 +146,35 @@ void main() {
             return;
     }
 
-    // Specular (based on noclip.website - https://github.com/magcius/noclip.website)
-    vec3 camera_normal = normalize(camera_difference);
-    vec3 world_normal = calculate_world_normal(bump_vector);
-    float tangent_on_camera = dot(world_normal, camera_normal);
-    vec3 reflection_normal = normalize(2.0 * tangent_on_camera * world_normal - camera_normal);
-    vec3 reflection_color = texture(samplerCube(cubemap, map_sampler), reflection_normal).xyz;
-    vec3 specular_color = pow(reflection_color, vec3(8.0));
-    float diffuse_reflection = tangent_on_camera * tangent_on_camera;
-    float reflect_attenuation = mix(shader_environment_data.parallel_color.a, shader_environment_data.perpendicular_color.a, diffuse_reflection);
-    vec3 specular = mix(shader_environment_data.parallel_color.rgb, shader_environment_data.perpendicular_color.rgb, diffuse_reflection);
-    specular = mix(specular_color, reflection_color, specular);
-    specular *= reflect_attenuation;
-
-    float specular_mask;
-    if((shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_BUMPMAP_ALPHA_SPECULAR_MASK) != 0) {
-        specular_mask = bump_color.a;
-    }
-    else if(shader_environment_data.shader_environment_type == SHADER_ENVIRONMENT_TYPE_BLENDED_BASE_SPECULAR) {
-        specular_mask = blended_detail.a;
-    }
-    else {
-        specular_mask = base_map_color.a;
+    vec3 specular = vec3(0.0);
+    vec3 sun_specular = vec3(0.0);
+    if(!SKIP_REFLECTION) {
+        // Specular (based on noclip.website - https://github.com/magcius/noclip.website)
+        vec3 camera_normal = normalize(camera_difference);
+        float tangent_on_camera = dot(world_normal, camera_normal);
+        vec3 reflection_normal = normalize(2.0 * tangent_on_camera * world_normal - camera_normal);
+        vec3 reflection_color = texture(samplerCube(cubemap, map_sampler), reflection_normal).xyz;
+        vec3 specular_color = pow(reflection_color, vec3(8.0));
+        float diffuse_reflection = tangent_on_camera * tangent_on_camera;
+        float reflect_attenuation = mix(shader_environment_data.parallel_color.a, shader_environment_data.perpendicular_color.a, diffuse_reflection);
+        specular = mix(shader_environment_data.parallel_color.rgb, shader_environment_data.perpendicular_color.rgb, diffuse_reflection);
+        specular = mix(specular_color, reflection_color, specular);
+        specular *= reflect_attenuation;
+
+        float specular_mask;
+        if((shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_BUMPMAP_ALPHA_SPECULAR_MASK) != 0) {
+            specular_mask = bump_color.a;
+        }
+        else if(shader_environment_data.shader_environment_type == SHADER_ENVIRONMENT_TYPE_BLENDED_BASE_SPECULAR) {
+            specular_mask = blended_detail.a;
+        }
+        else {
+            specular_mask = base_map_color.a;
+        }
+        specular *= specular_mask;
+
+        sun_specular = calculate_sun_specular(reflection_normal, diffuse_reflection) * specular_mask;
     }
-    specular *= specular_mask;
 
     // Specular
     base_map_color.rgb = clamp(base_map_color.rgb + specular.rgb, vec3(0.0), vec3(1.0));
@@ -132,17 +182,28 @@ void main() {
     // Lightmap stage
     base_map_color.rgb *= lightmap_color.rgb;
 
+    // Sun specular, which is added after the lightmap so that it is not darkened by it unless requested
+    if((shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_SPECULAR_LIGHTMAP_IS_SPECULAR) != 0) {
+        sun_specular *= lightmap_color.rgb;
+    }
+    base_map_color.rgb += sun_specular;
+    if((shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_SPECULAR_OVERBRIGHT) == 0) {
+        base_map_color.rgb = min(base_map_color.rgb, vec3(1.0));
+    }
+
     // Detail
-    vec3 scratch_color = blended_detail.rgb;
-    scratch_color = blend_with_mix_type(base_map_color.rgb, scratch_color, shader_environment_data.detail_map_function);
-    scratch_color = blend_with_mix_type(micro_detail_map_color.rgb, scratch_color, shader_environment_data.micro_detail_map_function);
+    vec3 scratch_color = base_map_color.rgb;
+    if(!SKIP_DETAIL) {
+        scratch_color = blend_with_mix_type(base_map_color.rgb, blended_detail.rgb, shader_environment_data.detail_map_function);
+        scratch_color = blend_with_mix_type(micro_detail_map_color.rgb, scratch_color, shader_environment_data.micro_detail_map_function);
+    }
 
     // Bumpmap
     float base_shading = dot(bump_vector, vec3(0.0, 0.0, 1.0));
     scratch_color.rgb *= vec3(base_shading);
 
     // Fog stage
-    scratch_color.rgb = apply_fog(distance_from_camera, scratch_color.rgb);
+    scratch_color.rgb = apply_fog(camera_position, vertex_position, scratch_color.rgb);
 
     f_color = vec4(scratch_color, 1.0);
 }
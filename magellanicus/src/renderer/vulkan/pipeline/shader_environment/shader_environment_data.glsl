@@ -11,6 +11,8 @@ layout(set = 3, binding = 0) uniform ShaderEnvironmentData {
 
     vec4 parallel_color; // a = brightness
     vec4 perpendicular_color; // a = brightness
+
+    float specular_brightness;
 } shader_environment_data;
 
 #define SHADER_ENVIRONMENT_TYPE_NORMAL 0
@@ -19,3 +21,6 @@ layout(set = 3, binding = 0) uniform ShaderEnvironmentData {
 
 #define SHADER_ENVIRONMENT_FLAGS_ALPHA_TEST 1
 #define SHADER_ENVIRONMENT_FLAGS_BUMPMAP_ALPHA_SPECULAR_MASK 2
+#define SHADER_ENVIRONMENT_FLAGS_SPECULAR_OVERBRIGHT 4
+#define SHADER_ENVIRONMENT_FLAGS_SPECULAR_EXTRA_SHINY 8
+#define SHADER_ENVIRONMENT_FLAGS_SPECULAR_LIGHTMAP_IS_SPECULAR 16
@@ -84,6 +84,11 @@ vec4 calculate_colors(
 }
 
 void main() {
+    // Not lightmapped
+    if(lighting_data.lighting_mode == LIGHTING_MODE_LIGHTMAP_ONLY) {
+        discard;
+    }
+
     vec4 map0_color;
 
     if(shader_transparent_chicago_data.first_map_type == 0) {
@@ -115,33 +120,35 @@ void main() {
 
     vec4 current_color = map0_color;
 
-    current_color = calculate_colors(
-        current_color,
-        map1_color,
-        shader_transparent_chicago_data.map0_color_function,
-        shader_transparent_chicago_data.map0_alpha_function,
-        1
-    );
+    if(lighting_data.lighting_mode != LIGHTING_MODE_UNLIT) {
+        current_color = calculate_colors(
+            current_color,
+            map1_color,
+            shader_transparent_chicago_data.map0_color_function,
+            shader_transparent_chicago_data.map0_alpha_function,
+            1
+        );
 
-    current_color = calculate_colors(
-        current_color,
-        map2_color,
-        shader_transparent_chicago_data.map1_color_function,
-        shader_transparent_chicago_data.map1_alpha_function,
-        2
-    );
+        current_color = calculate_colors(
+            current_color,
+            map2_color,
+            shader_transparent_chicago_data.map1_color_function,
+            shader_transparent_chicago_data.map1_alpha_function,
+            2
+        );
 
-    current_color = calculate_colors(
-        current_color,
-        map3_color,
-        shader_transparent_chicago_data.map2_color_function,
-        shader_transparent_chicago_data.map2_alpha_function,
-        3
-    );
+        current_color = calculate_colors(
+            current_color,
+            map3_color,
+            shader_transparent_chicago_data.map2_color_function,
+            shader_transparent_chicago_data.map2_alpha_function,
+            3
+        );
+    }
 
     vec3 camera_difference = camera_position - vertex_position;
     float distance_from_camera = distance(camera_position, vertex_position);
-    float inverse_density = 1.0 - calculate_fog_density(distance_from_camera);
+    float inverse_density = (1.0 - calculate_fog_density(distance_from_camera)) * (1.0 - calculate_planar_fog_density(camera_position, vertex_position));
 
     current_color.a *= inverse_density;
 
@@ -14,8 +14,11 @@ layout(set = 3, binding = 0) uniform sampler s;
 layout(set = 3, binding = 1) uniform texture2D tex;
 
 void main() {
-    vec4 lightmap_color = texture(sampler2D(lightmap_texture, lightmap_sampler), lightmap_texcoords);
+    vec4 lightmap_color = sample_lightmap(lightmap_texcoords);
     vec4 color = texture(sampler2D(tex, s), tex_coords);
+    if(lighting_data.lighting_mode == LIGHTING_MODE_LIGHTMAP_ONLY) {
+        color.rgb = vec3(1.0);
+    }
     vec4 lightmapped_color = vec4(color.rgb * lightmap_color.rgb, 1.0);
 
     // FIXME: Messes with additive transparent stuff
@@ -1,11 +1,12 @@
 use std::sync::Arc;
 use crate::error::MResult;
 use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
-use crate::renderer::vulkan::vertex::VulkanModelVertex;
+use crate::renderer::vulkan::vertex::VulkanDebugVertex;
 use crate::renderer::vulkan::{SwapchainImages, VulkanPipelineData};
 use std::vec;
 use vulkano::device::Device;
-use vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState;
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendAttachmentState};
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
 use vulkano::pipeline::graphics::vertex_input::Vertex;
 use vulkano::pipeline::GraphicsPipeline;
 
@@ -28,12 +29,16 @@ pub struct SolidColorShader {
 }
 
 impl SolidColorShader {
-    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>, topology: PrimitiveTopology) -> MResult<Self> {
         let pipeline = load_pipeline(swapchain_images, device, vertex::load, fragment::load, &PipelineSettings {
-            depth_access: DepthAccess::DepthWrite,
-            vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
-            color_blend_attachment_state: ColorBlendAttachmentState::default(),
+            depth_access: DepthAccess::DepthReadOnlyTransparent,
+            vertex_buffer_descriptions: vec![VulkanDebugVertex::per_vertex()],
+            color_blend_attachment_state: ColorBlendAttachmentState {
+                blend: Some(AttachmentBlend::alpha()),
+                ..ColorBlendAttachmentState::default()
+            },
             samples: swapchain_images.color.image().samples(),
+            topology,
             ..Default::default()
         })?;
 
@@ -2,10 +2,11 @@
 
 #include "../include/material.vert"
 
-layout(location = 0) out vec3 color;
+layout(location = 1) in vec4 color;
+layout(location = 0) out vec4 frag_color;
 
 void main() {
     mat4 worldview = uniforms.view * uniforms.world;
     gl_Position = uniforms.proj * worldview * vec4((position.xyz + uniforms.offset.xyz), 1.0);
-    color = mod(position, 1.0);
+    frag_color = color;
 }
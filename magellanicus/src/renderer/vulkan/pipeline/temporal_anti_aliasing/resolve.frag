@@ -0,0 +1,82 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler s;
+layout(set = 0, binding = 1) uniform texture2D scene;
+
+#ifdef MULTISAMPLED
+layout(set = 0, binding = 2) uniform texture2DMS depth_texture;
+#else
+layout(set = 0, binding = 2) uniform texture2D depth_texture;
+#endif
+
+layout(set = 0, binding = 3) uniform TemporalAntiAliasingData {
+    mat4 inverse_view_proj;
+    mat4 previous_view_proj;
+
+    // xy = offset, zw = extent of the viewport in pixels
+    vec4 viewport;
+
+    // How much of the history to keep (0.0 - 1.0)
+    float history_weight;
+} taa_data;
+
+layout(set = 0, binding = 4) uniform texture2D history;
+
+float fetch_depth(ivec2 pixel) {
+#ifdef MULTISAMPLED
+    return texelFetch(sampler2DMS(depth_texture, s), pixel, 0).r;
+#else
+    return texelFetch(sampler2D(depth_texture, s), pixel, 0).r;
+#endif
+}
+
+void main() {
+    vec2 pixel = gl_FragCoord.xy;
+    vec2 scene_size = vec2(textureSize(sampler2D(scene, s), 0));
+    ivec2 viewport_min = ivec2(taa_data.viewport.xy);
+    ivec2 viewport_max = ivec2(taa_data.viewport.xy + taa_data.viewport.zw) - 1;
+
+    // Gather the range of colors around the pixel, along with the nearest depth (depth is reversed, so that is the
+    // largest) so that the edges of things in front reproject along with them.
+    vec4 current = texelFetch(sampler2D(scene, s), ivec2(pixel), 0);
+    vec4 neighborhood_min = current;
+    vec4 neighborhood_max = current;
+    float depth = 0.0;
+    for(int y = -1; y <= 1; y++) {
+        for(int x = -1; x <= 1; x++) {
+            ivec2 neighbor = clamp(ivec2(pixel) + ivec2(x, y), viewport_min, viewport_max);
+            vec4 color = texelFetch(sampler2D(scene, s), neighbor, 0);
+            neighborhood_min = min(neighborhood_min, color);
+            neighborhood_max = max(neighborhood_max, color);
+            depth = max(depth, fetch_depth(neighbor));
+        }
+    }
+
+    // Find where this pixel was last frame. This stays homogeneous so that pixels where nothing was drawn (at a depth
+    // of 0.0, infinitely far away) reproject as directions.
+    vec2 ndc = (pixel - taa_data.viewport.xy) / taa_data.viewport.zw * 2.0 - 1.0;
+    vec4 world = taa_data.inverse_view_proj * vec4(ndc, depth, 1.0);
+    vec4 previous = taa_data.previous_view_proj * world;
+
+    float history_weight = taa_data.history_weight;
+    vec2 previous_pixel = pixel;
+    if(previous.w > 0.0) {
+        previous_pixel = taa_data.viewport.xy + (previous.xy / previous.w * 0.5 + 0.5) * taa_data.viewport.zw;
+    }
+    else {
+        history_weight = 0.0;
+    }
+
+    // Whatever was outside of the viewport last frame has no history.
+    if(any(lessThan(previous_pixel, taa_data.viewport.xy)) || any(greaterThanEqual(previous_pixel, taa_data.viewport.xy + taa_data.viewport.zw))) {
+        history_weight = 0.0;
+    }
+
+    // Clamping the history to colors around the pixel keeps anything that is no longer there from ghosting.
+    vec4 previous_color = textureLod(sampler2D(history, s), previous_pixel / scene_size, 0.0);
+    previous_color = clamp(previous_color, neighborhood_min, neighborhood_max);
+
+    f_color = mix(current, previous_color, history_weight);
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+use vulkano::device::Device;
+use std::vec;
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendAttachmentState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use crate::error::MResult;
+use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
+use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexTextureCoords};
+use crate::renderer::vulkan::{SwapchainImages, VulkanPipelineData};
+
+mod vertex {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/renderer/vulkan/pipeline/water/vertex.vert"
+    }
+}
+
+mod fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/water/fragment.frag"
+    }
+}
+
+pub use fragment::WaterData;
+
+/// Draws water, mixing its base map with the reflection image.
+pub struct Water {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl Water {
+    pub fn new(swapchain_images: &SwapchainImages, device: Arc<Device>) -> MResult<Self> {
+        let pipeline = load_pipeline(swapchain_images, device, vertex::load, fragment::load, &PipelineSettings {
+            depth_access: DepthAccess::DepthReadOnlyTransparent,
+            vertex_buffer_descriptions: vec![
+                VulkanModelVertex::per_vertex(),
+                VulkanModelVertexTextureCoords::per_vertex()
+            ],
+            color_blend_attachment_state: ColorBlendAttachmentState {
+                blend: Some(AttachmentBlend::alpha()),
+                ..ColorBlendAttachmentState::default()
+            },
+            samples: swapchain_images.color.image().samples(),
+            ..Default::default()
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for Water {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+    fn has_lightmaps(&self) -> bool {
+        false
+    }
+    fn has_fog(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,60 @@
+#version 450
+
+#define USE_FOG
+#include "../include/material.frag"
+
+layout(location = 0) out vec4 f_color;
+
+layout(location = 0) in vec2 tex_coords;
+layout(location = 1) in vec3 camera_position;
+layout(location = 2) in vec3 vertex_position;
+
+layout(set = 3, binding = 0) uniform sampler s;
+layout(set = 3, binding = 1) uniform texture2D tex;
+
+// The scene mirrored across the water, covering the same area as the color image.
+layout(set = 4, binding = 0) uniform sampler reflection_sampler;
+layout(set = 4, binding = 1) uniform texture2D reflection;
+
+layout(push_constant) uniform WaterData {
+    // Size of the color image in pixels
+    vec2 resolution;
+
+    // Seconds the ripples have been moving for
+    float time;
+
+    // 0.0 if this water isn't the surface that was mirrored
+    float reflectivity;
+} water_data;
+
+// Distance in world units between ripples, and how fast they move.
+#define RIPPLE_SCALE 0.35
+#define RIPPLE_SPEED 0.6
+
+// How far ripples move the reflection and the base map, in texture coordinates.
+#define RIPPLE_STRENGTH 0.008
+
+void main() {
+    // Two sets of waves crossing each other look less regular than one.
+    vec2 position = vertex_position.xy / RIPPLE_SCALE;
+    float t = water_data.time * RIPPLE_SPEED;
+    vec2 ripple = vec2(
+        sin(position.x + t) + sin(position.y * 0.7 - t * 1.3),
+        cos(position.y + t * 0.8) + cos(position.x * 0.6 + t * 1.1)
+    ) * (RIPPLE_STRENGTH / 2.0);
+
+    vec4 base_color = texture(sampler2D(tex, s), tex_coords + ripple);
+    if(lighting_data.lighting_mode == LIGHTING_MODE_LIGHTMAP_ONLY) {
+        base_color.rgb = vec3(1.0);
+    }
+
+    vec2 reflection_coords = clamp(gl_FragCoord.xy / water_data.resolution + ripple, vec2(0.0), vec2(1.0));
+    vec3 reflected_color = texture(sampler2D(reflection, reflection_sampler), reflection_coords).rgb;
+
+    // Water reflects more when seen at a shallow angle (Schlick's approximation, with water's reflectance head-on).
+    float facing = abs(normalize(camera_position - vertex_position).z);
+    float fresnel = 0.02 + 0.98 * pow(1.0 - facing, 5.0);
+
+    vec3 color = mix(base_color.rgb, reflected_color, water_data.reflectivity * mix(0.3, 1.0, fresnel));
+    f_color = vec4(apply_fog(camera_position, vertex_position, color), mix(0.6, 0.95, fresnel));
+}
@@ -0,0 +1,17 @@
+#version 450
+
+#define USE_TEXTURE_COORDS
+
+#include "../include/material.vert"
+
+layout(location = 0) out vec2 texcoords;
+layout(location = 1) out vec3 camera_position;
+layout(location = 2) out vec3 vertex_position;
+
+void main() {
+    mat4 worldview = uniforms.view * uniforms.world;
+    vertex_position = position.xyz + uniforms.offset.xyz;
+    camera_position = uniforms.camera;
+    gl_Position = uniforms.proj * worldview * vec4(vertex_position, 1.0);
+    texcoords = texture_coords.xy;
+}
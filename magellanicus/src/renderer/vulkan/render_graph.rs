@@ -0,0 +1,890 @@
+use std::sync::Arc;
+use glam::{Mat3, Mat4, Vec3};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, ClearDepthStencilImageInfo, PrimaryAutoCommandBuffer, ResolveImageInfo};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::format::ClearDepthStencilValue;
+use vulkano::image::sampler::Filter;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use crate::error::{Error, MResult};
+use crate::renderer::{DebugRenderMode, FogData, FrameStatistics, OverlayContext, Renderer, Resolution, ShaderType};
+use crate::renderer::data::{BSP, MIN_REFLECTIVE_WATER_AREA};
+use crate::renderer::debug_primitives::DebugTextAnchor;
+use crate::renderer::player_viewport::PlayerViewport;
+use crate::renderer::vulkan::debug_view::{debug_view_mode, UV_CHECKERBOARD_MODE};
+use crate::renderer::vulkan::water::WaterData;
+use crate::renderer::vulkan::{composite_ambient_occlusion, draw_ambient_occlusion, draw_box, draw_debug_text_vertices, draw_depth_of_field, draw_motion_blur, draw_overdraw_heatmap, draw_post_processing, draw_sprite, draw_temporal_anti_aliasing, make_debug_geometry, make_fog_uniform, make_model_view_uniform, make_water_reflection_uniform, push_debug_text_quads, reduced_stages, taa_jitter, view_projection, viewport_fog, DebugGeometry, GeometryPass, SwapchainImages, VulkanRenderer};
+use crate::types::FloatColor;
+
+/// Images of a swapchain image set that a pass can read from or write to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FrameAttachment {
+    /// Multisampled color image the scene is drawn into.
+    Color,
+
+    /// Depth image.
+    Depth,
+
+    /// Single-sampled color image (the resolve image if MSAA is enabled, otherwise the color image).
+    Staging,
+
+    /// Image that gets presented.
+    Output,
+
+    /// Overdraw image, drawn to by the overdraw debug render mode.
+    Overdraw,
+
+    /// Ambient occlusion image, if SSAO is enabled.
+    AmbientOcclusion,
+
+    /// Images the scene is drawn into mirrored across water, if water reflections are enabled.
+    Reflection,
+
+    /// TAA history, if TAA is enabled.
+    TaaHistory
+}
+
+/// Everything a pass needs to record commands for the current frame.
+pub struct FrameContext {
+    pub command_builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pub images: Arc<SwapchainImages>,
+
+    /// Window being drawn to, where 0 is the main window.
+    pub window: usize,
+    pub width: f32,
+    pub height: f32,
+
+    /// Viewport being drawn by [`VIEWPORT_PASSES`], if any.
+    pub viewport: Option<ViewportContext>
+}
+
+/// Everything the passes of a viewport share while drawing it.
+pub struct ViewportContext {
+    pub player_viewport: PlayerViewport,
+
+    /// Area of the images the viewport covers.
+    pub rect: Viewport,
+
+    /// BSP of the viewport's scene, if one is loaded and uploaded.
+    pub bsp: Option<Arc<BSP>>,
+    pub debug_geometry: DebugGeometry,
+
+    pub fog_data: FogData,
+    pub sky_color: FloatColor,
+    pub fog: Arc<PersistentDescriptorSet>,
+
+    pub view: Mat4,
+    pub proj: Mat4,
+
+    /// Set if the scene is drawn with a jittered projection for TAA.
+    pub taa: bool,
+
+    /// Projection the scene is drawn with, which is `proj` jittered if TAA is enabled.
+    pub scene_proj: Mat4,
+
+    /// Model-view uniform of `view` and `scene_proj`.
+    pub mvp: Arc<PersistentDescriptorSet>,
+
+    /// Index of the BSP's water surface that is reflected, if any.
+    pub reflected_water: Option<usize>,
+
+    /// Set once a debug render mode has drawn the viewport's geometry, so the remaining scene passes and post effects
+    /// are skipped.
+    pub replaced: bool,
+
+    pub statistics: FrameStatistics
+}
+
+impl ViewportContext {
+    /// Get the BSP to draw the scene passes with, or `None` if there is none or a debug render mode replaced them.
+    fn scene_bsp(&self) -> Option<Arc<BSP>> {
+        self.bsp.clone().filter(|_| !self.replaced)
+    }
+}
+
+/// A named step of drawing a frame.
+#[derive(Copy, Clone)]
+pub struct FramePass {
+    pub name: &'static str,
+
+    /// Attachments that must have been written to by a (transitive) dependency before this pass runs.
+    pub reads: &'static [FrameAttachment],
+
+    /// Attachments this pass writes to.
+    pub writes: &'static [FrameAttachment],
+
+    /// Passes that must run before this pass.
+    pub depends_on: &'static [&'static str],
+
+    pub execute: fn(&mut Renderer, &mut FrameContext) -> MResult<()>
+}
+
+/// Passes sorted by their dependencies.
+#[derive(Clone)]
+pub struct RenderGraph {
+    passes: Arc<[FramePass]>
+}
+
+impl RenderGraph {
+    /// Sort the passes such that all dependencies run first.
+    ///
+    /// Errors if:
+    /// - a pass depends on a pass that does not exist
+    /// - there is a dependency cycle
+    /// - a pass reads an attachment that none of its dependencies write to
+    pub fn new(passes: &[FramePass]) -> MResult<Self> {
+        Self::with_inputs(passes, &[])
+    }
+
+    /// Sort the passes such that all dependencies run first, where `inputs` were written to before the graph runs.
+    ///
+    /// This is for graphs run by a pass of another graph, whose passes can read what that pass reads.
+    ///
+    /// Errors the same as [`RenderGraph::new`].
+    pub fn with_inputs(passes: &[FramePass], inputs: &[FrameAttachment]) -> MResult<Self> {
+        for pass in passes {
+            for dependency in pass.depends_on {
+                if !passes.iter().any(|p| p.name == *dependency) {
+                    return Err(Error::from_data_error_string(format!("Pass {} depends on unknown pass {dependency}", pass.name)))
+                }
+            }
+        }
+
+        let mut sorted: Vec<FramePass> = Vec::with_capacity(passes.len());
+        while sorted.len() < passes.len() {
+            let next = passes
+                .iter()
+                .find(|p| !sorted.iter().any(|s| s.name == p.name) && p.depends_on.iter().all(|d| sorted.iter().any(|s| s.name == *d)));
+
+            let Some(next) = next else {
+                return Err(Error::from_data_error_string("Render graph has a dependency cycle".to_owned()))
+            };
+
+            sorted.push(*next);
+        }
+
+        for pass in &sorted {
+            for attachment in pass.reads.iter().filter(|a| !inputs.contains(a)) {
+                let mut dependencies: Vec<&'static str> = pass.depends_on.to_vec();
+                let mut written = false;
+                while let Some(dependency) = dependencies.pop() {
+                    let dependency = sorted.iter().find(|p| p.name == dependency).unwrap();
+                    if dependency.writes.contains(attachment) {
+                        written = true;
+                        break;
+                    }
+                    dependencies.extend_from_slice(dependency.depends_on);
+                }
+                if !written {
+                    return Err(Error::from_data_error_string(format!("Pass {} reads {attachment:?}, but none of its dependencies write to it", pass.name)))
+                }
+            }
+        }
+
+        Ok(Self { passes: sorted.into() })
+    }
+
+    pub fn execute(&self, renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+        for pass in self.passes.iter() {
+            (pass.execute)(renderer, context)?;
+        }
+        Ok(())
+    }
+}
+
+/// All passes used to draw a frame.
+pub const FRAME_PASSES: &[FramePass] = &[
+    FramePass {
+        name: "clear_depth",
+        reads: &[],
+        writes: &[FrameAttachment::Depth],
+        depends_on: &[],
+        execute: clear_depth
+    },
+    FramePass {
+        name: "viewports",
+        reads: VIEWPORT_INPUTS,
+        writes: &[FrameAttachment::Color, FrameAttachment::Depth],
+        depends_on: &["clear_depth"],
+        execute: draw_viewports
+    },
+    FramePass {
+        name: "hud",
+        reads: &[FrameAttachment::Color],
+        writes: &[FrameAttachment::Color],
+        depends_on: &["viewports"],
+        execute: draw_hud
+    },
+    FramePass {
+        name: "split_screen_bars",
+        reads: &[FrameAttachment::Color],
+        writes: &[FrameAttachment::Color],
+        depends_on: &["hud"],
+        execute: draw_split_screen_bars
+    },
+    FramePass {
+        name: "debug_text",
+        reads: &[FrameAttachment::Color],
+        writes: &[FrameAttachment::Color],
+        depends_on: &["split_screen_bars"],
+        execute: draw_debug_text
+    },
+    FramePass {
+        name: "resolve",
+        reads: &[FrameAttachment::Color],
+        writes: &[FrameAttachment::Staging],
+        depends_on: &["debug_text"],
+        execute: resolve
+    },
+    FramePass {
+        name: "overlay",
+        reads: &[FrameAttachment::Staging],
+        writes: &[FrameAttachment::Staging],
+        depends_on: &["resolve"],
+        execute: draw_overlay
+    },
+    FramePass {
+        name: "output",
+        reads: &[FrameAttachment::Staging],
+        writes: &[FrameAttachment::Output],
+        depends_on: &["overlay"],
+        execute: blit_to_output
+    }
+];
+
+/// Attachments written to before the passes of each viewport run.
+pub const VIEWPORT_INPUTS: &[FrameAttachment] = &[FrameAttachment::Depth];
+
+/// Passes run for each viewport by the viewports pass, in [`FrameContext::viewport`].
+///
+/// Rendering to the color and depth images is active in between these passes, from the sky onward. The scene is
+/// drawn first, then post effects, then debug primitives on top so they stay sharp.
+pub const VIEWPORT_PASSES: &[FramePass] = &[
+    FramePass {
+        name: "sky",
+        reads: &[],
+        writes: &[FrameAttachment::Color],
+        depends_on: &[],
+        execute: draw_viewport_sky
+    },
+    FramePass {
+        name: "debug_view",
+        reads: &[FrameAttachment::Depth],
+        writes: &[FrameAttachment::Color, FrameAttachment::Depth, FrameAttachment::Overdraw],
+        depends_on: &["sky"],
+        execute: draw_viewport_debug_view
+    },
+    FramePass {
+        name: "water_reflection",
+        reads: &[],
+        writes: &[FrameAttachment::Reflection],
+        depends_on: &["debug_view"],
+        execute: draw_viewport_water_reflection
+    },
+    FramePass {
+        name: "opaque",
+        reads: &[FrameAttachment::Color, FrameAttachment::Depth],
+        writes: &[FrameAttachment::Color, FrameAttachment::Depth],
+        depends_on: &["water_reflection"],
+        execute: draw_viewport_opaque
+    },
+    FramePass {
+        name: "ambient_occlusion",
+        reads: &[FrameAttachment::Color, FrameAttachment::Depth],
+        writes: &[FrameAttachment::Color, FrameAttachment::AmbientOcclusion],
+        depends_on: &["opaque"],
+        execute: draw_viewport_ambient_occlusion
+    },
+    FramePass {
+        name: "mirrors",
+        reads: &[FrameAttachment::Color, FrameAttachment::Depth],
+        writes: &[FrameAttachment::Color, FrameAttachment::Depth],
+        depends_on: &["ambient_occlusion"],
+        execute: draw_viewport_mirrors
+    },
+    FramePass {
+        name: "transparent",
+        reads: &[FrameAttachment::Color, FrameAttachment::Depth, FrameAttachment::Reflection],
+        writes: &[FrameAttachment::Color],
+        depends_on: &["mirrors"],
+        execute: draw_viewport_transparent
+    },
+    FramePass {
+        name: "temporal_anti_aliasing",
+        reads: &[FrameAttachment::Color, FrameAttachment::Depth],
+        writes: &[FrameAttachment::Color, FrameAttachment::TaaHistory],
+        depends_on: &["transparent"],
+        execute: draw_viewport_temporal_anti_aliasing
+    },
+    FramePass {
+        name: "depth_of_field",
+        reads: &[FrameAttachment::Color, FrameAttachment::Depth],
+        writes: &[FrameAttachment::Color],
+        depends_on: &["temporal_anti_aliasing"],
+        execute: draw_viewport_depth_of_field
+    },
+    FramePass {
+        name: "motion_blur",
+        reads: &[FrameAttachment::Color, FrameAttachment::Depth],
+        writes: &[FrameAttachment::Color],
+        depends_on: &["depth_of_field"],
+        execute: draw_viewport_motion_blur
+    },
+    FramePass {
+        name: "post_processing",
+        reads: &[FrameAttachment::Color],
+        writes: &[FrameAttachment::Color],
+        depends_on: &["motion_blur"],
+        execute: draw_viewport_post_processing
+    },
+    FramePass {
+        name: "debug_primitives",
+        reads: &[FrameAttachment::Color, FrameAttachment::Depth],
+        writes: &[FrameAttachment::Color],
+        depends_on: &["post_processing"],
+        execute: draw_viewport_debug_primitives
+    }
+];
+
+fn clear_depth(_: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    // Depth is reversed, so 0.0 is the farthest. The stencil (if any) marks mirrors, and nothing is marked yet.
+    context.command_builder.clear_depth_stencil_image(ClearDepthStencilImageInfo {
+        clear_value: ClearDepthStencilValue { depth: 0.0, stencil: 0 },
+        ..ClearDepthStencilImageInfo::image(context.images.depth.image().clone())
+    })?;
+    Ok(())
+}
+
+fn draw_viewports(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let debug_geometry = make_debug_geometry(renderer)?;
+    let viewport_render_graph = renderer.vulkan.viewport_render_graph.clone();
+    let mut statistics = FrameStatistics::default();
+
+    for i in 0..renderer.player_viewports.len() {
+        if renderer.player_viewports[i].window != context.window {
+            continue
+        }
+
+        context.viewport = Some(begin_viewport(renderer, context, i, debug_geometry.clone())?);
+        viewport_render_graph.execute(renderer, context)?;
+        let viewport = context.viewport.take().unwrap();
+        statistics += viewport.statistics;
+
+        // Motion blur and TAA compare against where the camera was when the viewport was last drawn.
+        renderer.player_viewports[i].previous_view_projection = Some(viewport.proj * viewport.view);
+    }
+
+    // Every viewport of the window has drawn its part of the TAA history by now.
+    let taa = renderer.vulkan.parameters.taa;
+    renderer.vulkan.set_taa_history_valid(context.window, taa);
+
+    // The main window is drawn first, so statistics of the other windows are added onto it.
+    if context.window == 0 {
+        renderer.frame_statistics = statistics;
+    }
+    else {
+        renderer.frame_statistics += statistics;
+    }
+    Ok(())
+}
+
+/// Set up what the passes of a viewport share, such as its camera's matrices and fog.
+fn begin_viewport(renderer: &Renderer, context: &FrameContext, index: usize, debug_geometry: DebugGeometry) -> MResult<ViewportContext> {
+    let player_viewport = renderer.player_viewports[index];
+    let camera = player_viewport.camera;
+    let rect = player_viewport_rect(&player_viewport, context);
+
+    let bsp = renderer
+        .scenes[player_viewport.scene]
+        .bsp
+        .and_then(|f| renderer.bsps.get(f))
+        .filter(|b| b.vulkan.subbuffers.is_some())
+        .cloned();
+
+    let aspect_ratio = rect.extent[0] / rect.extent[1];
+    let (fog_data, sky_color) = viewport_fog(&player_viewport, camera.position);
+    let (view, proj) = view_projection(&player_viewport, &camera, aspect_ratio, player_viewport.draw_distance[0]);
+
+    // For TAA, the scene is drawn with the projection moved by a fraction of a pixel each frame, but post effects
+    // and debug primitives use the projection as is.
+    let taa = renderer.vulkan.parameters.taa && player_viewport.debug_render_mode == DebugRenderMode::None;
+    let scene_proj = if taa {
+        Mat4::from_translation(taa_jitter(renderer.vulkan.taa_frame, &rect).extend(0.0)) * proj
+    }
+    else {
+        proj
+    };
+
+    let fog = make_fog_uniform(renderer, &fog_data, &player_viewport, camera.lighting_mode, camera.position, [0.0; 4])?;
+    let mvp = make_model_view_uniform(renderer, camera.position, Vec3::default(), Mat3::IDENTITY, view, scene_proj)?;
+
+    Ok(ViewportContext {
+        player_viewport,
+        rect,
+        bsp,
+        debug_geometry,
+        fog_data,
+        sky_color,
+        fog,
+        view,
+        proj,
+        taa,
+        scene_proj,
+        mvp,
+        reflected_water: None,
+        replaced: false,
+        statistics: FrameStatistics::default()
+    })
+}
+
+/// Split the context into the command builder, the images, and the viewport being drawn.
+fn viewport_parts(context: &mut FrameContext) -> (&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, &Arc<SwapchainImages>, &mut ViewportContext) {
+    let FrameContext { command_builder, images, viewport, .. } = context;
+    (command_builder, images, viewport.as_mut().expect("no viewport is being drawn"))
+}
+
+fn bind_bsp_buffers(bsp: &BSP, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
+    let buffers = bsp.vulkan.subbuffers.as_ref().expect("BSP is not uploaded");
+    command_builder.bind_index_buffer(buffers.index_subbuffer.clone())?;
+    command_builder.bind_vertex_buffers(0, (
+        buffers.vertex_data_subbuffer.clone(),
+        buffers.texture_coords_subbuffer.clone(),
+        buffers.lightmap_texture_coords_subbuffer.clone()
+    ))?;
+    Ok(())
+}
+
+fn draw_viewport_sky(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    command_builder.set_viewport(0, [viewport.rect.clone()].into_iter().collect())?;
+    images.begin_rendering(command_builder)?;
+    draw_box(renderer, 0.0, 0.0, 1.0, 1.0, viewport.sky_color, command_builder)
+}
+
+fn draw_viewport_debug_view(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    let Some(bsp) = viewport.scene_bsp() else {
+        return Ok(())
+    };
+    let debug_render_mode = viewport.player_viewport.debug_render_mode;
+
+    if debug_render_mode == DebugRenderMode::Overdraw {
+        bind_bsp_buffers(&bsp, command_builder)?;
+        images.end_rendering(command_builder)?;
+        VulkanRenderer::draw_bsp_overdraw(renderer, &bsp, images, command_builder, viewport.mvp.clone(), &mut viewport.statistics)?;
+        images.begin_rendering(command_builder)?;
+        draw_overdraw_heatmap(renderer, images, command_builder)?;
+        viewport.replaced = true;
+    }
+    else if let Some(mode) = debug_view_mode(debug_render_mode) {
+        // Transparent geometry is drawn as if it were opaque so that it can be inspected too.
+        bind_bsp_buffers(&bsp, command_builder)?;
+        VulkanRenderer::draw_bsp_debug_view(renderer, bsp.geometries.iter(), command_builder, mode, viewport.mvp.clone())?;
+        for geometry in &bsp.geometries {
+            viewport.statistics.add_draw(&geometry.offset);
+        }
+        viewport.statistics.geometries_drawn += bsp.geometries.len();
+        viewport.replaced = true;
+    }
+
+    Ok(())
+}
+
+fn draw_viewport_water_reflection(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    let Some(bsp) = viewport.scene_bsp().filter(|_| images.reflection.is_some()) else {
+        return Ok(())
+    };
+    let camera = viewport.player_viewport.camera;
+
+    // Only the largest water surface below the camera reflects anything, since the reflection is the scene drawn
+    // again.
+    viewport.reflected_water = bsp.water_surfaces
+        .iter()
+        .position(|s| s.area >= MIN_REFLECTIVE_WATER_AREA && s.height < camera.position.z);
+    let Some(surface) = viewport.reflected_water.map(|i| &bsp.water_surfaces[i]) else {
+        return Ok(())
+    };
+
+    let mirror = Mat4::from_translation(Vec3::Z * (surface.height * 2.0)) * Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0));
+    let mirrored_camera = mirror.transform_point3(camera.position);
+    let clip_plane = [0.0, 0.0, 1.0, surface.height];
+    let reflection_fog = make_fog_uniform(renderer, &viewport.fog_data, &viewport.player_viewport, camera.lighting_mode, mirrored_camera, clip_plane)?;
+    let reflection_mvp = make_model_view_uniform(renderer, mirrored_camera, Vec3::default(), Mat3::IDENTITY, viewport.view * mirror, viewport.proj)?;
+
+    bind_bsp_buffers(&bsp, command_builder)?;
+    images.end_rendering(command_builder)?;
+    VulkanRenderer::draw_water_reflection(renderer, &bsp, images, command_builder, &viewport.rect, reflection_fog, reflection_mvp, viewport.sky_color, &mut viewport.statistics)?;
+    images.begin_rendering(command_builder)?;
+    command_builder.set_viewport(0, [viewport.rect.clone()].into_iter().collect())?;
+    Ok(())
+}
+
+fn draw_viewport_opaque(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, _, viewport) = viewport_parts(context);
+    let Some(bsp) = viewport.scene_bsp() else {
+        return Ok(())
+    };
+    let position = viewport.player_viewport.camera.position;
+
+    bind_bsp_buffers(&bsp, command_builder)?;
+    let mut last_shader = None;
+    for geometry in bsp.vulkan.opaque_geometries.iter().map(|i| &bsp.geometries[*i]) {
+        viewport.statistics.add_draw(&geometry.offset);
+        viewport.statistics.geometries_drawn += 1;
+        if renderer.checkerboard_shaders.contains(&geometry.shader) {
+            VulkanRenderer::draw_bsp_debug_view(renderer, std::iter::once(geometry), command_builder, UV_CHECKERBOARD_MODE, viewport.mvp.clone())?;
+            last_shader = None;
+            continue;
+        }
+        let shader = &renderer.shaders[geometry.shader].vulkan.pipeline_data;
+        let stages = reduced_stages(&viewport.player_viewport, position, geometry);
+        VulkanRenderer::draw_bsp_geometry(renderer, &bsp, command_builder, &mut last_shader, geometry, viewport.fog.clone(), viewport.mvp.clone(), shader, &geometry.offset, GeometryPass::Main(stages))?;
+    }
+    Ok(())
+}
+
+/// Ambient occlusion darkens opaque geometry only, so it goes in between opaque and transparent geometry.
+fn draw_viewport_ambient_occlusion(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    if !renderer.vulkan.parameters.ssao || viewport.scene_bsp().is_none() {
+        return Ok(())
+    }
+
+    images.end_rendering(command_builder)?;
+    draw_ambient_occlusion(renderer, images, command_builder, viewport.proj, &viewport.rect)?;
+    images.begin_rendering(command_builder)?;
+    composite_ambient_occlusion(renderer, images, command_builder)
+}
+
+/// Reflections go after ambient occlusion, since it is for the geometry in front of the mirror.
+fn draw_viewport_mirrors(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    let Some(bsp) = viewport.scene_bsp().filter(|b| images.has_stencil() && !b.mirrors.is_empty()) else {
+        return Ok(())
+    };
+
+    bind_bsp_buffers(&bsp, command_builder)?;
+    let camera = viewport.player_viewport.camera;
+    VulkanRenderer::draw_mirrors(renderer, &bsp, command_builder, &viewport.player_viewport, &viewport.fog_data, &camera, viewport.view, viewport.scene_proj, viewport.mvp.clone(), viewport.sky_color, &mut viewport.statistics)
+}
+
+fn draw_viewport_transparent(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    let Some(bsp) = viewport.scene_bsp() else {
+        return Ok(())
+    };
+    let position = viewport.player_viewport.camera.position;
+
+    let mut transparent_geometries: Vec<(usize, f32)> = bsp
+        .vulkan
+        .transparent_geometries
+        .iter()
+        .map(|i| (*i, position.distance_squared(Vec3::from(bsp.geometries[*i].centroid))))
+        .collect();
+    // Sort layers override distance, since some surfaces (e.g. glass over water) must always go on top.
+    let sort_layer = |index: usize| renderer.shaders[bsp.geometries[index].shader].parameter.sort_layer;
+    transparent_geometries
+        .sort_by(|a,b| sort_layer(a.0).cmp(&sort_layer(b.0)).then(b.1.total_cmp(&a.1)));
+
+    // Water samples the reflection images, so it is only drawn if they exist.
+    let water_reflection = images
+        .reflection
+        .as_ref()
+        .map(|reflection| make_water_reflection_uniform(renderer, reflection))
+        .transpose()?;
+    let water_time = if renderer.deterministic { 0.0 } else { renderer.vulkan.created.elapsed().as_secs_f32() };
+    let reflected_water = viewport.reflected_water.map(|i| &bsp.water_surfaces[i]);
+
+    bind_bsp_buffers(&bsp, command_builder)?;
+    let mut last_shader = None;
+    for index in transparent_geometries.iter().map(|b| b.0) {
+        let geometry = &bsp.geometries[index];
+        let shader = &renderer.shaders[geometry.shader].vulkan.pipeline_data;
+        let is_water = renderer.shaders[geometry.shader].shader_type == ShaderType::TransparentWater;
+        if is_water && water_reflection.is_none() {
+            viewport.statistics.geometries_culled += 1;
+            continue;
+        }
+        viewport.statistics.add_draw(&geometry.offset);
+        viewport.statistics.geometries_drawn += 1;
+        if renderer.checkerboard_shaders.contains(&geometry.shader) {
+            VulkanRenderer::draw_bsp_debug_view(renderer, std::iter::once(geometry), command_builder, UV_CHECKERBOARD_MODE, viewport.mvp.clone())?;
+            last_shader = None;
+            continue;
+        }
+        if let Some(reflection) = water_reflection.as_ref().filter(|_| is_water) {
+            let color_extent = images.color.image().extent();
+            let water_data = WaterData {
+                resolution: [color_extent[0] as f32, color_extent[1] as f32],
+                time: water_time,
+                reflectivity: if reflected_water.is_some_and(|s| s.geometries.contains(&index)) { 1.0 } else { 0.0 }
+            };
+            VulkanRenderer::draw_water_geometry(renderer, command_builder, geometry, viewport.fog.clone(), viewport.mvp.clone(), shader, reflection.clone(), water_data)?;
+            last_shader = None;
+            continue;
+        }
+        let stages = reduced_stages(&viewport.player_viewport, position, geometry);
+        VulkanRenderer::draw_bsp_geometry(renderer, &bsp, command_builder, &mut last_shader, geometry, viewport.fog.clone(), viewport.mvp.clone(), shader, &geometry.offset, GeometryPass::Main(stages))?;
+    }
+    Ok(())
+}
+
+fn draw_viewport_temporal_anti_aliasing(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let window = context.window;
+    let (command_builder, images, viewport) = viewport_parts(context);
+    if !viewport.taa || viewport.replaced {
+        return Ok(())
+    }
+
+    let previous_view_projection = viewport.player_viewport.previous_view_projection.filter(|_| renderer.vulkan.is_taa_history_valid(window));
+    images.end_rendering(command_builder)?;
+    draw_temporal_anti_aliasing(renderer, images, command_builder, viewport.proj * viewport.view, previous_view_projection, &viewport.rect)
+}
+
+fn draw_viewport_depth_of_field(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    let Some(depth_of_field) = viewport.player_viewport.depth_of_field.filter(|d| d.aperture > 0.0 && renderer.vulkan.parameters.depth_of_field && !viewport.replaced) else {
+        return Ok(())
+    };
+
+    images.end_rendering(command_builder)?;
+    draw_depth_of_field(renderer, images, command_builder, &depth_of_field, viewport.proj, &viewport.rect)
+}
+
+fn draw_viewport_motion_blur(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    let intensity = viewport.player_viewport.motion_blur;
+    if intensity <= 0.0 || !renderer.vulkan.parameters.motion_blur || viewport.replaced {
+        return Ok(())
+    }
+
+    let current_view_projection = viewport.proj * viewport.view;
+    let previous_view_projection = viewport.player_viewport.previous_view_projection.unwrap_or(current_view_projection);
+    images.end_rendering(command_builder)?;
+    draw_motion_blur(renderer, images, command_builder, intensity, current_view_projection, previous_view_projection, &viewport.rect)
+}
+
+fn draw_viewport_post_processing(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    let post_processing = viewport.player_viewport.post_processing;
+    if post_processing.is_identity() || !renderer.vulkan.parameters.post_processing || viewport.replaced {
+        return Ok(())
+    }
+
+    images.end_rendering(command_builder)?;
+    draw_post_processing(renderer, images, command_builder, &post_processing, &viewport.rect)
+}
+
+/// Debug primitives are drawn after post effects so they stay sharp, and they aren't jittered for TAA.
+fn draw_viewport_debug_primitives(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let (command_builder, images, viewport) = viewport_parts(context);
+    let mvp = if viewport.taa {
+        let position = viewport.player_viewport.camera.position;
+        make_model_view_uniform(renderer, position, Vec3::default(), Mat3::IDENTITY, viewport.view, viewport.proj)?
+    }
+    else {
+        viewport.mvp.clone()
+    };
+
+    VulkanRenderer::draw_viewport_debug_primitives(renderer, &viewport.debug_geometry, command_builder, mvp, viewport.proj * viewport.view, &viewport.rect)?;
+    images.end_rendering(command_builder)
+}
+
+fn draw_hud(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    if renderer.hud_draws.is_empty() && renderer.hud_nav_points.is_empty() && !renderer.lens_flares.is_any_visible() {
+        return Ok(())
+    }
+
+    context.images.begin_rendering(&mut context.command_builder)?;
+    for i in 0..renderer.player_viewports.len() {
+        if renderer.player_viewports[i].window != context.window {
+            continue
+        }
+        let viewport = player_viewport_rect(&renderer.player_viewports[i], context);
+        VulkanRenderer::draw_hud(renderer, i, viewport, &mut context.command_builder)?;
+    }
+    context.images.end_rendering(&mut context.command_builder)
+}
+
+fn player_viewport_rect(player_viewport: &PlayerViewport, context: &FrameContext) -> Viewport {
+    Viewport {
+        offset: [player_viewport.rel_x * context.width, player_viewport.rel_y * context.height],
+        extent: [player_viewport.rel_width * context.width, player_viewport.rel_height * context.height],
+        depth_range: 0.0..=1.0,
+    }
+}
+
+fn draw_split_screen_bars(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    let Some(bars) = renderer.split_screen_bars else {
+        return Ok(())
+    };
+    let viewport_count = renderer.player_viewports.iter().filter(|v| v.window == context.window).count();
+    if viewport_count <= 1 || renderer.stereo {
+        return Ok(())
+    }
+
+    context.images.begin_rendering(&mut context.command_builder)?;
+    VulkanRenderer::draw_split_screen_bars(renderer, &mut context.command_builder, &bars, viewport_count, context.width, context.height)?;
+    context.images.end_rendering(&mut context.command_builder)
+}
+
+fn draw_debug_text(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    // Debug text is for the renderer as a whole, so it only goes in the main window.
+    if context.window != 0 {
+        return Ok(())
+    }
+
+    let debug_data = renderer.debug_font.as_ref().and_then(|_| renderer.debug_text.iter().last());
+
+    let mut vertices = Vec::new();
+    for text in &renderer.debug_primitives.texts {
+        if let DebugTextAnchor::Screen(position) = text.anchor {
+            push_debug_text_quads(&mut vertices, text, position, [context.width, context.height]);
+        }
+    }
+
+    if debug_data.is_none() && vertices.is_empty() {
+        return Ok(())
+    }
+
+    context.images.begin_rendering(&mut context.command_builder)?;
+    context.command_builder.set_viewport(0, [Viewport {
+        offset: [0.0, 0.0],
+        extent: [context.width, context.height],
+        depth_range: 0.0..=1.0,
+    }].into_iter().collect())?;
+    if let Some(debug_data) = debug_data {
+        draw_sprite(renderer, 0.0, 0.0, (renderer.vulkan.parameters.resolution.height as f32) / 480.0, &debug_data.bitmaps[0].vulkan.image, &mut context.command_builder)?;
+    }
+    draw_debug_text_vertices(renderer, vertices, &mut context.command_builder)?;
+    context.images.end_rendering(&mut context.command_builder)
+}
+
+fn resolve(_: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    if let Some(resolved_color_view) = context.images.resolve.as_ref() {
+        context.command_builder.resolve_image(
+            ResolveImageInfo::images(context.images.color.image().clone(), resolved_color_view.image().clone())
+        )?;
+    }
+    Ok(())
+}
+
+fn draw_overlay(renderer: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    if context.window != 0 {
+        return Ok(())
+    }
+
+    let Some(mut hook) = renderer.overlay_hook.take() else {
+        return Ok(())
+    };
+
+    let color_attachment = context.images.staging().clone();
+    let [width, height, _] = color_attachment.image().extent();
+    hook(&mut OverlayContext {
+        command_builder: &mut context.command_builder,
+        color_format: color_attachment.format(),
+        color_attachment,
+        resolution: Resolution { width, height }
+    });
+    renderer.overlay_hook = Some(hook);
+    Ok(())
+}
+
+fn blit_to_output(_: &mut Renderer, context: &mut FrameContext) -> MResult<()> {
+    context.command_builder.blit_image(BlitImageInfo {
+        filter: Filter::Linear,
+        ..BlitImageInfo::images(context.images.staging().image().clone(), context.images.output.image().clone())
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DataError;
+
+    fn nothing(_: &mut Renderer, _: &mut FrameContext) -> MResult<()> {
+        Ok(())
+    }
+
+    fn pass(name: &'static str, reads: &'static [FrameAttachment], writes: &'static [FrameAttachment], depends_on: &'static [&'static str]) -> FramePass {
+        FramePass { name, reads, writes, depends_on, execute: nothing }
+    }
+
+    fn order(graph: &RenderGraph) -> Vec<&'static str> {
+        graph.passes.iter().map(|p| p.name).collect()
+    }
+
+    fn invalid_reason(result: MResult<RenderGraph>) -> String {
+        match result {
+            Err(Error::Data(DataError::Invalid { reason })) => reason,
+            Err(e) => panic!("unexpected error {e:?}"),
+            Ok(graph) => panic!("graph was built: {:?}", order(&graph))
+        }
+    }
+
+    #[test]
+    fn dependencies_run_first() {
+        let graph = RenderGraph::new(&[
+            pass("post", &[FrameAttachment::Staging], &[FrameAttachment::Output], &["resolve"]),
+            pass("resolve", &[FrameAttachment::Color], &[FrameAttachment::Staging], &["draw"]),
+            pass("draw", &[], &[FrameAttachment::Color], &[]),
+            pass("clear", &[], &[FrameAttachment::Depth], &[])
+        ]).unwrap();
+
+        // The first pass in the list that can run is picked each time.
+        assert_eq!(order(&graph), ["draw", "resolve", "post", "clear"]);
+    }
+
+    #[test]
+    fn unknown_dependency_errors() {
+        let reason = invalid_reason(RenderGraph::new(&[
+            pass("draw", &[], &[FrameAttachment::Color], &["clear"])
+        ]));
+        assert_eq!(reason, "Pass draw depends on unknown pass clear");
+    }
+
+    #[test]
+    fn cycle_errors() {
+        let reason = invalid_reason(RenderGraph::new(&[
+            pass("clear", &[], &[FrameAttachment::Depth], &[]),
+            pass("a", &[], &[FrameAttachment::Color], &["clear", "b"]),
+            pass("b", &[], &[FrameAttachment::Staging], &["a"])
+        ]));
+        assert_eq!(reason, "Render graph has a dependency cycle");
+    }
+
+    #[test]
+    fn reads_must_be_written_by_a_dependency() {
+        // Running earlier isn't enough; the writer has to be a dependency.
+        let reason = invalid_reason(RenderGraph::new(&[
+            pass("draw", &[], &[FrameAttachment::Color], &[]),
+            pass("resolve", &[FrameAttachment::Color], &[FrameAttachment::Staging], &[])
+        ]));
+        assert_eq!(reason, "Pass resolve reads Color, but none of its dependencies write to it");
+
+        // Transitive dependencies count.
+        let graph = RenderGraph::new(&[
+            pass("draw", &[], &[FrameAttachment::Color], &[]),
+            pass("resolve", &[], &[FrameAttachment::Staging], &["draw"]),
+            pass("post", &[FrameAttachment::Color], &[FrameAttachment::Output], &["resolve"])
+        ]).unwrap();
+        assert_eq!(order(&graph), ["draw", "resolve", "post"]);
+    }
+
+    #[test]
+    fn inputs_can_be_read_without_a_writer() {
+        let passes = [pass("draw", &[FrameAttachment::Depth], &[FrameAttachment::Color], &[])];
+        let reason = invalid_reason(RenderGraph::new(&passes));
+        assert_eq!(reason, "Pass draw reads Depth, but none of its dependencies write to it");
+
+        let graph = RenderGraph::with_inputs(&passes, &[FrameAttachment::Depth]).unwrap();
+        assert_eq!(order(&graph), ["draw"]);
+    }
+
+    #[test]
+    fn built_in_graphs_are_valid() {
+        RenderGraph::new(FRAME_PASSES).unwrap();
+        RenderGraph::with_inputs(VIEWPORT_PASSES, VIEWPORT_INPUTS).unwrap();
+    }
+}
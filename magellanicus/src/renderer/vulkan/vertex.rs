@@ -1,6 +1,7 @@
 use vulkano::buffer::BufferContents;
 use vulkano::pipeline::graphics::vertex_input::Vertex;
 use vulkano::padded::Padded;
+use crate::renderer::MAX_DYNAMIC_LIGHTS;
 
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
@@ -35,6 +36,45 @@ pub struct VulkanModelVertexLightmapTextureCoords {
     pub lightmap_texture_coords: [f32; 2],
 }
 
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+#[derive(BufferContents, Vertex)]
+pub struct VulkanDebugVertex {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4]
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+#[derive(BufferContents, Vertex)]
+pub struct VulkanDebugTextVertex {
+    #[format(R32G32_SFLOAT)]
+    pub position: [f32; 2],
+
+    #[format(R32G32_SFLOAT)]
+    pub texture_coords: [f32; 2],
+
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4]
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+#[derive(BufferContents, Vertex)]
+pub struct VulkanHudVertex {
+    #[format(R32G32_SFLOAT)]
+    pub position: [f32; 2],
+
+    #[format(R32G32_SFLOAT)]
+    pub texture_coords: [f32; 2],
+
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4]
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 #[derive(BufferContents)]
@@ -56,4 +96,61 @@ pub struct VulkanFogData {
     pub sky_fog_to: f32,
     pub sky_fog_min_opacity: f32,
     pub sky_fog_max_opacity: f32,
+
+    /// XYZ = normalized normal of the fog plane, W = offset, with fog being behind the plane
+    pub planar_fog_plane: [f32; 4],
+
+    /// RGB = color of the planar fog
+    pub planar_fog_color: [f32; 4],
+
+    /// 0.0 if there is no planar fog
+    pub planar_fog_max_density: f32,
+    pub planar_fog_opaque_distance: f32,
+    pub planar_fog_opaque_depth: Padded<f32, 4>,
+
+    /// XYZ = normalized normal, W = offset, with geometry behind the plane not being drawn (zero to draw everything)
+    pub clip_plane: [f32; 4]
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+#[derive(BufferContents)]
+pub struct VulkanLightingData {
+    pub lighting_mode: u32,
+    pub light_count: u32,
+    pub lightmap_exposure: f32,
+
+    /// Multiplier applied to lightmap textures (not vertex lighting) on top of the exposure
+    pub lightmap_overbright: f32,
+
+    /// XYZ = normalized direction the sun's light travels in, W = 1.0 if there is a sun or 0.0 if not
+    pub sun_direction: [f32; 4],
+
+    /// RGB = sun color multiplied by power
+    pub sun_color: [f32; 4],
+
+    /// RGB = sky ambient color multiplied by power
+    pub ambient_color: [f32; 4],
+
+    pub lights: [VulkanDynamicLight; MAX_DYNAMIC_LIGHTS]
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+#[derive(BufferContents)]
+pub struct VulkanDynamicLight {
+    /// XYZ = position, W = radius
+    pub position_radius: [f32; 4],
+
+    /// RGB = color multiplied by power, A = falloff
+    pub color_falloff: [f32; 4],
+
+    /// XYZ = normalized spot direction, W = 1.0 if a spot light or 0.0 if a point light
+    pub direction_type: [f32; 4],
+
+    /// X = cosine of the inner angle, Y = cosine of the outer angle, Z = falloff radius
+    pub cutoff: [f32; 4],
+
+    /// Columns of the matrix that transforms world-space directions into gel space
+    pub gel_rotation: [[f32; 4]; 3]
 }
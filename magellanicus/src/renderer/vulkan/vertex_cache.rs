@@ -0,0 +1,215 @@
+//! Triangle reordering for better post-transform vertex cache reuse.
+//!
+//! This is Tom Forsyth's "Linear-Speed Vertex Cache Optimisation" algorithm. It greedily emits the triangle whose
+//! vertices score highest, where vertices score higher the more recently they were used (i.e. are likely still in the
+//! GPU's vertex cache) and the fewer triangles they have left (so that they can be finished off and evicted).
+
+/// Size of the simulated vertex cache.
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: u32) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+
+        // The vertices of the last triangle get a fixed score so that the next triangle isn't biased toward any one
+        // of its edges.
+        Some(p) if p < 3 => LAST_TRIANGLE_SCORE,
+        Some(p) => {
+            let scaler = 1.0 / (CACHE_SIZE - 3) as f32;
+            (1.0 - (p - 3) as f32 * scaler).powf(CACHE_DECAY_POWER)
+        }
+    };
+
+    cache_score + VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Reorder the triangles of a triangle list for vertex cache reuse.
+///
+/// The result contains the same triangles with the same winding, just in a different order.
+///
+/// # Panics
+///
+/// Panics if any index is `>= vertex_count`.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count <= 1 {
+        return indices.to_vec()
+    }
+
+    // Build a list of triangles for each vertex.
+    let mut remaining = vec![0u32; vertex_count];
+    for i in &indices[..triangle_count * 3] {
+        remaining[*i as usize] += 1;
+    }
+    let mut adjacency_offsets = Vec::with_capacity(vertex_count + 1);
+    let mut offset = 0;
+    for count in &remaining {
+        adjacency_offsets.push(offset);
+        offset += *count as usize;
+    }
+    adjacency_offsets.push(offset);
+
+    let mut adjacency = vec![0usize; offset];
+    let mut cursor = adjacency_offsets.clone();
+    for (triangle, vertices) in indices.chunks_exact(3).enumerate() {
+        for v in vertices {
+            adjacency[cursor[*v as usize]] = triangle;
+            cursor[*v as usize] += 1;
+        }
+    }
+
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = remaining.iter().map(|r| vertex_score(None, *r)).collect();
+    let triangle_score = |vertex_scores: &[f32], triangle: usize| -> f32 {
+        indices[triangle * 3..triangle * 3 + 3].iter().map(|v| vertex_scores[*v as usize]).sum()
+    };
+
+    let mut triangle_added = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(triangle_count * 3);
+    let mut first_unadded = 0;
+
+    let mut next = (0..triangle_count).max_by(|a, b| {
+        triangle_score(&vertex_scores, *a).total_cmp(&triangle_score(&vertex_scores, *b))
+    });
+
+    while let Some(triangle) = next {
+        triangle_added[triangle] = true;
+        let vertices = &indices[triangle * 3..triangle * 3 + 3];
+        output.extend_from_slice(vertices);
+
+        // Move the triangle's vertices to the front of the cache.
+        let mut new_cache: Vec<u32> = vertices.to_vec();
+        new_cache.extend(cache.iter().filter(|v| !vertices.contains(*v)));
+        for v in vertices {
+            remaining[*v as usize] -= 1;
+        }
+        for (position, v) in new_cache.iter().enumerate() {
+            let v = *v as usize;
+            cache_position[v] = (position < CACHE_SIZE).then_some(position);
+            vertex_scores[v] = vertex_score(cache_position[v], remaining[v]);
+        }
+
+        // Only triangles using vertices whose scores changed need to be considered.
+        next = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for v in &new_cache {
+            let v = *v as usize;
+            for adjacent in &adjacency[adjacency_offsets[v]..adjacency_offsets[v + 1]] {
+                if triangle_added[*adjacent] {
+                    continue
+                }
+                let score = triangle_score(&vertex_scores, *adjacent);
+                if score > best_score {
+                    best_score = score;
+                    next = Some(*adjacent);
+                }
+            }
+        }
+
+        new_cache.truncate(CACHE_SIZE);
+        cache = new_cache;
+
+        // Nothing in the cache has triangles left, so start over somewhere else.
+        if next.is_none() {
+            while first_unadded < triangle_count && triangle_added[first_unadded] {
+                first_unadded += 1;
+            }
+            next = (first_unadded < triangle_count).then_some(first_unadded);
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+        triangles.sort();
+        triangles
+    }
+
+    /// Average cache miss ratio (vertex transforms per triangle) of a FIFO cache.
+    fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+        let mut cache: Vec<u32> = Vec::new();
+        let mut misses = 0;
+        for i in indices {
+            if !cache.contains(i) {
+                misses += 1;
+                cache.insert(0, *i);
+                cache.truncate(cache_size);
+            }
+        }
+        misses as f32 / (indices.len() / 3) as f32
+    }
+
+    /// A grid of `width` by `height` quads, split into triangles and listed row by row.
+    fn grid(width: u32, height: u32) -> Vec<u32> {
+        let mut indices = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let a = y * (width + 1) + x;
+                let b = a + 1;
+                let c = a + width + 1;
+                let d = c + 1;
+                indices.extend_from_slice(&[a, b, c, b, d, c]);
+            }
+        }
+        indices
+    }
+
+    #[test]
+    fn triangles_are_kept_with_their_winding() {
+        let indices = grid(8, 8);
+        let optimized = optimize_vertex_cache(&indices, 81);
+        assert_eq!(optimized.len(), indices.len());
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&indices));
+    }
+
+    #[test]
+    fn disconnected_triangles_are_all_kept() {
+        let indices = [0, 1, 2, 3, 4, 5, 6, 7, 8, 2, 1, 0];
+        let optimized = optimize_vertex_cache(&indices, 9);
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&indices));
+    }
+
+    #[test]
+    fn trivial_meshes_are_unchanged() {
+        assert!(optimize_vertex_cache(&[], 0).is_empty());
+        assert_eq!(optimize_vertex_cache(&[2, 0, 1], 3), [2, 0, 1]);
+    }
+
+    #[test]
+    fn strips_do_not_get_worse() {
+        let indices = grid(64, 1);
+        let optimized = optimize_vertex_cache(&indices, 130);
+        assert!(acmr(&optimized, 16) <= acmr(&indices, 16));
+    }
+
+    #[test]
+    fn scrambled_meshes_get_better() {
+        // Deterministically shuffle the triangles of a grid.
+        let mut triangles: Vec<[u32; 3]> = sorted_triangles(&grid(32, 32));
+        let mut seed = 12345u32;
+        for i in (1..triangles.len()).rev() {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            triangles.swap(i, (seed >> 8) as usize % (i + 1));
+        }
+        let indices: Vec<u32> = triangles.into_iter().flatten().collect();
+
+        let optimized = optimize_vertex_cache(&indices, 33 * 33);
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&indices));
+        assert!(acmr(&optimized, 16) < acmr(&indices, 16) / 2.0);
+    }
+}
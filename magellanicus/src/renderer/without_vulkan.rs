@@ -0,0 +1,10 @@
+//! The renderer module when built without the `vulkan` feature.
+//!
+//! Only the parameters and the asset checker are available, as these do not need Vulkan (or std). See `renderer.rs`
+//! for the renderer itself.
+
+pub use parameters::*;
+pub use checker::{AssetChecker, AssetCheckFailure};
+
+mod parameters;
+mod checker;
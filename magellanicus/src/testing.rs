@@ -0,0 +1,13 @@
+//! Facilities for testing the renderer against reference ("golden") images.
+//!
+//! A test typically builds a scene out of the synthetic assets in this module, renders it with
+//! [`render_golden_frame`], and checks the result with [`check_golden_image`]. Only available with the `testing`
+//! feature.
+//!
+//! The renderer's own golden tests are in `tests/golden.rs`, with their reference images in `tests/golden`.
+
+mod golden;
+mod synthetic;
+
+pub use golden::*;
+pub use synthetic::*;
@@ -0,0 +1,364 @@
+use std::path::{Path, PathBuf};
+use crate::error::{Error, MResult};
+use crate::renderer::{CapturedFrame, Renderer, RendererParameters, Resolution, MSAA};
+
+/// If this environment variable is set, [`check_golden_image`] writes reference images instead of comparing.
+pub const UPDATE_GOLDEN_IMAGES_VAR: &str = "MAGELLANICUS_UPDATE_GOLDEN";
+
+/// How far a frame may deviate from its reference image and still pass.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GoldenTolerance {
+    /// Largest difference allowed in any single channel before a pixel is counted as differing.
+    ///
+    /// Default = 2
+    pub channel_tolerance: u8,
+
+    /// Number of differing pixels allowed.
+    ///
+    /// Default = 0
+    pub max_differing_pixels: usize
+}
+
+impl Default for GoldenTolerance {
+    fn default() -> Self {
+        Self {
+            channel_tolerance: 2,
+            max_differing_pixels: 0
+        }
+    }
+}
+
+/// Result of comparing a frame against a reference image with [`compare_frames`].
+#[derive(Clone, Debug)]
+pub struct GoldenComparison {
+    /// Number of pixels where at least one channel differs by more than the channel tolerance.
+    pub differing_pixels: usize,
+
+    /// Largest difference found in any channel of any pixel.
+    pub max_channel_difference: u8,
+
+    /// Image highlighting differing pixels in red over a darkened copy of the expected image.
+    pub diff: CapturedFrame
+}
+
+impl GoldenComparison {
+    /// Returns `true` if the comparison is within `tolerance`.
+    pub fn passes(&self, tolerance: &GoldenTolerance) -> bool {
+        self.differing_pixels <= tolerance.max_differing_pixels
+    }
+}
+
+/// Make a headless renderer suitable for rendering golden images.
+///
+/// This uses a single viewport, no MSAA or anisotropic filtering, and deterministic mode, so that results depend
+/// only on the scene and the driver.
+pub fn new_golden_renderer(resolution: Resolution) -> MResult<Renderer> {
+    let parameters = RendererParameters {
+        resolution,
+        number_of_viewports: 1,
+        msaa: MSAA::NoMSAA,
+        anisotropic_filtering: None,
+        ..Default::default()
+    };
+    let mut renderer = Renderer::new_headless(parameters)?;
+    renderer.set_deterministic(true)?;
+    Ok(renderer)
+}
+
+/// Draw a frame and read it back.
+pub fn render_golden_frame(renderer: &mut Renderer) -> MResult<CapturedFrame> {
+    renderer.draw_frame()?;
+    renderer.capture_frame()
+}
+
+/// Compare `actual` against `expected` pixel by pixel.
+///
+/// This will error if:
+/// - the frames have different resolutions
+/// - either frame's data length does not match its resolution
+pub fn compare_frames(actual: &CapturedFrame, expected: &CapturedFrame, tolerance: &GoldenTolerance) -> MResult<GoldenComparison> {
+    if actual.resolution != expected.resolution {
+        let Resolution { width: aw, height: ah } = actual.resolution;
+        let Resolution { width: ew, height: eh } = expected.resolution;
+        return Err(Error::from_data_error_string(format!("Frame is {aw}x{ah}, but the reference image is {ew}x{eh}")))
+    }
+    validate_frame_length(actual)?;
+    validate_frame_length(expected)?;
+
+    let mut differing_pixels = 0;
+    let mut max_channel_difference = 0;
+    let mut diff = Vec::with_capacity(expected.data.len());
+
+    for (a, e) in actual.data.chunks_exact(4).zip(expected.data.chunks_exact(4)) {
+        let difference = a.iter().zip(e).map(|(a, e)| a.abs_diff(*e)).max().unwrap_or(0);
+        max_channel_difference = max_channel_difference.max(difference);
+
+        if difference > tolerance.channel_tolerance {
+            differing_pixels += 1;
+            diff.extend_from_slice(&[0xFF, 0x00, 0x00, 0xFF]);
+        }
+        else {
+            diff.extend_from_slice(&[e[0] / 4, e[1] / 4, e[2] / 4, 0xFF]);
+        }
+    }
+
+    Ok(GoldenComparison {
+        differing_pixels,
+        max_channel_difference,
+        diff: CapturedFrame { resolution: expected.resolution, data: diff }
+    })
+}
+
+/// Check `frame` against the reference image at `reference_path`.
+///
+/// If [`UPDATE_GOLDEN_IMAGES_VAR`] is set, `frame` is written as the new reference image instead. A missing reference
+/// image is an error otherwise, so that a check can't pass without having compared anything.
+///
+/// If the check fails, the frame and a diff image are written next to the reference image with `.actual.pam` and
+/// `.diff.pam` extensions so they can be inspected.
+///
+/// This will error if:
+/// - the frame does not match the reference image within `tolerance`
+/// - the reference image does not exist and [`UPDATE_GOLDEN_IMAGES_VAR`] is not set
+/// - the reference image could not be read or written
+pub fn check_golden_image(frame: &CapturedFrame, reference_path: &Path, tolerance: &GoldenTolerance) -> MResult<()> {
+    if std::env::var_os(UPDATE_GOLDEN_IMAGES_VAR).is_some() {
+        return write_reference_image(reference_path, frame)
+    }
+    if !reference_path.exists() {
+        return Err(Error::from_data_error_string(format!(
+            "Reference image {} does not exist; set {UPDATE_GOLDEN_IMAGES_VAR} to write it",
+            reference_path.display()
+        )))
+    }
+
+    let expected = read_reference_image(reference_path)?;
+    let comparison = compare_frames(frame, &expected, tolerance)?;
+    if comparison.passes(tolerance) {
+        return Ok(())
+    }
+
+    let actual_path = with_suffix(reference_path, "actual.pam");
+    let diff_path = with_suffix(reference_path, "diff.pam");
+    write_reference_image(&actual_path, frame)?;
+    write_reference_image(&diff_path, &comparison.diff)?;
+
+    Err(Error::from_data_error_string(format!(
+        "Frame does not match {}: {} pixel(s) differ (max {} allowed), largest channel difference is {}; see {} and {}",
+        reference_path.display(),
+        comparison.differing_pixels,
+        tolerance.max_differing_pixels,
+        comparison.max_channel_difference,
+        actual_path.display(),
+        diff_path.display()
+    )))
+}
+
+/// Read a reference image written by [`write_reference_image`].
+pub fn read_reference_image(path: &Path) -> MResult<CapturedFrame> {
+    let data = std::fs::read(path)
+        .map_err(|e| Error::from_data_error_string(format!("Can't read reference image {}: {e}", path.display())))?;
+    decode_pam(&data)
+        .map_err(|e| Error::from_data_error_string(format!("Can't read reference image {}: {e}", path.display())))
+}
+
+/// Write `frame` to `path` as an RGBA Netpbm PAM image.
+///
+/// PAM is used since it is trivial to encode losslessly and most image viewers and editors can open it.
+pub fn write_reference_image(path: &Path, frame: &CapturedFrame) -> MResult<()> {
+    validate_frame_length(frame)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| Error::from_data_error_string(format!("Can't create directory {}: {e}", parent.display())))?;
+    }
+    std::fs::write(path, encode_pam(frame))
+        .map_err(|e| Error::from_data_error_string(format!("Can't write reference image {}: {e}", path.display())))
+}
+
+fn encode_pam(frame: &CapturedFrame) -> Vec<u8> {
+    let Resolution { width, height } = frame.resolution;
+    let mut data = format!("P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n").into_bytes();
+    data.extend_from_slice(&frame.data);
+    data
+}
+
+fn decode_pam(data: &[u8]) -> Result<CapturedFrame, String> {
+    let mut width = None;
+    let mut height = None;
+    let mut offset = 0;
+    let mut first_line = true;
+
+    loop {
+        let Some(line_length) = data[offset..].iter().position(|b| *b == b'\n') else {
+            return Err("header is not terminated with ENDHDR".to_owned())
+        };
+        let line = std::str::from_utf8(&data[offset..offset + line_length])
+            .map_err(|_| "header is not valid UTF-8".to_owned())?
+            .trim();
+        offset += line_length + 1;
+
+        if first_line {
+            if line != "P7" {
+                return Err("not a PAM image".to_owned())
+            }
+            first_line = false;
+            continue
+        }
+
+        let mut tokens = line.split_whitespace();
+        let (Some(key), value) = (tokens.next(), tokens.next()) else {
+            continue
+        };
+        let parse = |value: Option<&str>| value
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or_else(|| format!("invalid {key} value"));
+
+        match key {
+            "ENDHDR" => break,
+            "WIDTH" => width = Some(parse(value)?),
+            "HEIGHT" => height = Some(parse(value)?),
+            "DEPTH" if parse(value)? != 4 => return Err("only RGBA images are supported".to_owned()),
+            "MAXVAL" if parse(value)? != 255 => return Err("only 8-bit images are supported".to_owned()),
+            _ => ()
+        }
+    }
+
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err("header is missing WIDTH or HEIGHT".to_owned())
+    };
+
+    let frame = CapturedFrame {
+        resolution: Resolution { width, height },
+        data: data[offset..].to_vec()
+    };
+    validate_frame_length(&frame).map_err(|e| e.to_string())?;
+    Ok(frame)
+}
+
+fn validate_frame_length(frame: &CapturedFrame) -> MResult<()> {
+    let Resolution { width, height } = frame.resolution;
+    let expected = (width as usize) * (height as usize) * 4;
+    let actual = frame.data.len();
+    if actual != expected {
+        return Err(Error::from_data_error_string(format!("{width}x{height} frame should have {expected} bytes of pixel data, got {actual}")))
+    }
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.to_owned();
+    path.set_extension(suffix);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_frame(color: [u8; 4], width: u32, height: u32) -> CapturedFrame {
+        CapturedFrame {
+            resolution: Resolution { width, height },
+            data: color.repeat((width * height) as usize)
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("magellanicus-golden-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn identical_frames_match() {
+        let frame = solid_frame([10, 20, 30, 255], 4, 3);
+        let comparison = compare_frames(&frame, &frame, &GoldenTolerance::default()).unwrap();
+        assert_eq!(comparison.differing_pixels, 0);
+        assert_eq!(comparison.max_channel_difference, 0);
+        assert!(comparison.passes(&GoldenTolerance::default()));
+    }
+
+    #[test]
+    fn differences_within_channel_tolerance_match() {
+        let expected = solid_frame([10, 20, 30, 255], 4, 3);
+        let actual = solid_frame([12, 18, 30, 255], 4, 3);
+        let comparison = compare_frames(&actual, &expected, &GoldenTolerance::default()).unwrap();
+        assert_eq!(comparison.differing_pixels, 0);
+        assert_eq!(comparison.max_channel_difference, 2);
+    }
+
+    #[test]
+    fn differing_pixels_are_counted_and_marked() {
+        let expected = solid_frame([100, 100, 100, 255], 4, 3);
+        let mut actual = expected.clone();
+        actual.data[4 * 5 + 1] = 110;
+
+        let tolerance = GoldenTolerance::default();
+        let comparison = compare_frames(&actual, &expected, &tolerance).unwrap();
+        assert_eq!(comparison.differing_pixels, 1);
+        assert_eq!(comparison.max_channel_difference, 10);
+        assert!(!comparison.passes(&tolerance));
+        assert!(comparison.passes(&GoldenTolerance { max_differing_pixels: 1, ..tolerance }));
+
+        assert_eq!(comparison.diff.resolution, expected.resolution);
+        assert_eq!(comparison.diff.data[4 * 5..4 * 6], [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(comparison.diff.data[0..4], [25, 25, 25, 0xFF]);
+    }
+
+    #[test]
+    fn comparing_different_resolutions_fails() {
+        let a = solid_frame([0, 0, 0, 255], 4, 3);
+        let b = solid_frame([0, 0, 0, 255], 3, 4);
+        assert!(compare_frames(&a, &b, &GoldenTolerance::default()).is_err());
+    }
+
+    #[test]
+    fn comparing_truncated_frames_fails() {
+        let expected = solid_frame([0, 0, 0, 255], 4, 3);
+        let mut actual = expected.clone();
+        actual.data.pop();
+        assert!(compare_frames(&actual, &expected, &GoldenTolerance::default()).is_err());
+    }
+
+    #[test]
+    fn pam_round_trip() {
+        let mut frame = solid_frame([0, 0, 0, 0], 5, 2);
+        for (i, byte) in frame.data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let decoded = decode_pam(&encode_pam(&frame)).unwrap();
+        assert_eq!(decoded.resolution, frame.resolution);
+        assert_eq!(decoded.data, frame.data);
+
+        let path = temp_path("round-trip.pam");
+        write_reference_image(&path, &frame).unwrap();
+        let read = read_reference_image(&path);
+        let _ = std::fs::remove_file(&path);
+        let read = read.unwrap();
+        assert_eq!(read.resolution, frame.resolution);
+        assert_eq!(read.data, frame.data);
+    }
+
+    #[test]
+    fn invalid_pam_is_rejected() {
+        let frame = solid_frame([1, 2, 3, 4], 2, 2);
+        let encoded = encode_pam(&frame);
+
+        assert!(decode_pam(&encoded[..encoded.len() - 1]).is_err());
+        assert!(decode_pam(b"P6\n2 2\n255\n").is_err());
+        assert!(decode_pam(b"P7\nWIDTH 2\nHEIGHT 2\nDEPTH 3\nMAXVAL 255\nENDHDR\n").is_err());
+        assert!(decode_pam(b"P7\nWIDTH 2\nDEPTH 4\nMAXVAL 255\nENDHDR\n").is_err());
+        assert!(decode_pam(b"P7\nWIDTH 2\nHEIGHT 2\n").is_err());
+    }
+
+    #[test]
+    fn missing_reference_image_fails() {
+        // Writing reference images is the expected behavior when updating them.
+        if std::env::var_os(UPDATE_GOLDEN_IMAGES_VAR).is_some() {
+            return
+        }
+
+        let path = temp_path("missing.pam");
+        let frame = solid_frame([0, 0, 0, 255], 2, 2);
+        assert!(check_golden_image(&frame, &path, &GoldenTolerance::default()).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::renderer::{AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, AddBSPParameter, AddBSPParameterLightmapMaterial, AddBSPParameterLightmapSet, AddShaderBasicShaderData, AddShaderData, AddShaderParameter, BitmapFormat, BitmapType, BSP3DNode, BSP3DNodeChild, BSP3DPlane, BSPCluster, BSPData, BSPLeaf, BSPSubcluster, Resolution, ShaderType};
+use crate::vertex::{ModelTriangle, ModelVertex};
+
+/// Make a single 2D bitmap where every pixel is `color` (RGBA).
+pub fn solid_color_bitmap(color: [u8; 4], resolution: Resolution) -> AddBitmapParameter {
+    let pixel_count = (resolution.width as usize) * (resolution.height as usize);
+    single_bitmap(BitmapType::Dim2D, resolution, color.repeat(pixel_count))
+}
+
+/// Make a single 2D bitmap with a checkerboard pattern of `square_size` pixel squares, starting with `a` (RGBA) in the
+/// top-left corner.
+///
+/// This is useful for catching texture coordinate and filtering regressions that a solid color would hide.
+///
+/// # Panics
+///
+/// Panics if `square_size` is 0.
+pub fn checkerboard_bitmap(a: [u8; 4], b: [u8; 4], square_size: u32, resolution: Resolution) -> AddBitmapParameter {
+    assert!(square_size > 0, "square_size must be greater than 0");
+
+    let mut data = Vec::with_capacity((resolution.width as usize) * (resolution.height as usize) * 4);
+    for y in 0..resolution.height {
+        for x in 0..resolution.width {
+            let color = if (x / square_size + y / square_size) % 2 == 0 { a } else { b };
+            data.extend_from_slice(&color);
+        }
+    }
+    single_bitmap(BitmapType::Dim2D, resolution, data)
+}
+
+/// Make a single cubemap bitmap where every face is `color` (RGBA).
+pub fn solid_color_cubemap(color: [u8; 4], size: u32) -> AddBitmapParameter {
+    let pixel_count = (size as usize) * (size as usize) * 6;
+    single_bitmap(BitmapType::Cubemap, Resolution { width: size, height: size }, color.repeat(pixel_count))
+}
+
+fn single_bitmap(bitmap_type: BitmapType, resolution: Resolution, data: Vec<u8>) -> AddBitmapParameter {
+    AddBitmapParameter {
+        bitmaps: vec![AddBitmapBitmapParameter {
+            format: BitmapFormat::A8B8G8R8,
+            bitmap_type,
+            resolution,
+            mipmap_count: 0,
+            data
+        }],
+        sequences: vec![AddBitmapSequenceParameter::Bitmap { first: 0, count: 1 }]
+    }
+}
+
+/// Make a basic shader that renders `bitmap` as-is, or nothing if `bitmap` is `None`.
+pub fn basic_shader(bitmap: Option<&str>, shader_type: ShaderType) -> AddShaderParameter {
+    AddShaderParameter {
+        data: AddShaderData::BasicShader(AddShaderBasicShaderData {
+            bitmap: bitmap.map(|b| b.to_owned()),
+            shader_type,
+            alpha_tested: false
+        }),
+        sort_layer: 0
+    }
+}
+
+/// Make a BSP consisting of a single square facing the default camera.
+///
+/// The square is centered on the Y axis at `distance` units from the origin and is `size` units wide, with texture
+/// coordinates going from 0 to 1 across it. It has no lightmap, and it is drawn from both sides so it is visible
+/// regardless of winding order.
+///
+/// The whole BSP is one leaf in one cluster, so the camera is always in that cluster.
+pub fn quad_bsp(shader: &str, distance: f32, size: f32) -> AddBSPParameter {
+    let half = size / 2.0;
+    let corners = [
+        ([-half, distance, half], [0.0, 0.0]),
+        ([half, distance, half], [1.0, 0.0]),
+        ([half, distance, -half], [1.0, 1.0]),
+        ([-half, distance, -half], [0.0, 1.0])
+    ];
+
+    let shader_vertices: Vec<ModelVertex> = corners
+        .iter()
+        .map(|(position, texture_coords)| ModelVertex {
+            position: *position,
+            normal: [0.0, -1.0, 0.0],
+            binormal: [0.0, 0.0, -1.0],
+            tangent: [1.0, 0.0, 0.0],
+            texture_coords: *texture_coords
+        })
+        .collect();
+
+    let surfaces = vec![
+        ModelTriangle { indices: [0, 1, 2] },
+        ModelTriangle { indices: [0, 2, 3] },
+        ModelTriangle { indices: [2, 1, 0] },
+        ModelTriangle { indices: [3, 2, 0] }
+    ];
+    let surface_count = surfaces.len();
+
+    AddBSPParameter {
+        lightmap_bitmap: None,
+        lightmap_sets: vec![AddBSPParameterLightmapSet {
+            lightmap_index: None,
+            materials: vec![AddBSPParameterLightmapMaterial {
+                shader_vertices: shader_vertices.into(),
+                lightmap_vertices: None,
+                surfaces,
+                shader: shader.to_owned(),
+                centroid: [0.0, distance, 0.0]
+            }]
+        }],
+        bsp_data: BSPData {
+            nodes: vec![BSP3DNode {
+                front_child: Some(BSP3DNodeChild::Leaf(0)),
+                back_child: Some(BSP3DNodeChild::Leaf(0)),
+                plane: 0
+            }],
+            planes: vec![BSP3DPlane { angle: [0.0, 1.0, 0.0], offset: 0.0 }],
+            leaves: vec![BSPLeaf { cluster: 0 }],
+            clusters: vec![BSPCluster {
+                sky: None,
+                subclusters: vec![BSPSubcluster {
+                    surface_indices: (0..surface_count).collect(),
+                    world_bounds_from: [-half, distance, -half],
+                    world_bounds_to: [half, distance, half]
+                }],
+                cluster_portals: Vec::new(),
+                fog_plane: None
+            }],
+            portals: Vec::new(),
+            fog_planes: Vec::new()
+        }
+    }
+}
@@ -2,9 +2,6 @@ mod string32;
 
 pub use string32::String32;
 
-use std::fmt::Display;
-use std::hash::{Hash, Hasher};
-
 /// RGBA
 pub type FloatColor = [f32; 4];
 
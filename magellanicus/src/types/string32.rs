@@ -1,6 +1,6 @@
-use std::cmp::Ordering;
-use std::fmt::{Debug, Display, Formatter};
-use std::hash::{Hash, Hasher};
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
 
 /// Describes a string that can be represented in 32 bytes or fewer.
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -11,7 +11,7 @@ pub struct String32 {
 
 impl String32 {
     pub fn as_str(&self) -> &str {
-        unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
+        unsafe { core::str::from_utf8_unchecked(self.as_bytes()) }
     }
     pub fn len(&self) -> usize {
         self.string_length
@@ -22,7 +22,7 @@ impl String32 {
 }
 
 impl Display for String32 {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.write_str(self.as_str())
     }
 }
@@ -87,7 +87,7 @@ impl TryFrom<&str> for String32 {
 }
 
 impl Debug for String32 {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         Display::fmt(self, f)
     }
 }
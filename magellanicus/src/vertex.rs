@@ -14,10 +14,68 @@ pub struct LightmapVertex {
     pub lightmap_texture_coords: [f32; 2]
 }
 
+/// [`ModelVertex`] with its normal, binormal, and tangent packed into 32-bit integers.
+///
+/// This is the format used by compressed BSPs (e.g. Xbox maps). See [`decompress_vector`] for how vectors are packed.
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct CompressedModelVertex {
+    pub position: [f32; 3],
+    pub normal: u32,
+    pub binormal: u32,
+    pub tangent: u32,
+    pub texture_coords: [f32; 2]
+}
+
+impl From<CompressedModelVertex> for ModelVertex {
+    fn from(value: CompressedModelVertex) -> Self {
+        Self {
+            position: value.position,
+            normal: decompress_vector(value.normal),
+            binormal: decompress_vector(value.binormal),
+            tangent: decompress_vector(value.tangent),
+            texture_coords: value.texture_coords
+        }
+    }
+}
+
+/// [`LightmapVertex`] with its texture coordinates packed into normalized 16-bit integers.
+///
+/// This is the format used by compressed BSPs (e.g. Xbox maps).
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct CompressedLightmapVertex {
+    /// Packed normal; unused by the renderer.
+    pub normal: u32,
+    pub lightmap_texture_coords: [i16; 2]
+}
+
+impl From<CompressedLightmapVertex> for LightmapVertex {
+    fn from(value: CompressedLightmapVertex) -> Self {
+        let [x, y] = value.lightmap_texture_coords;
+        Self {
+            lightmap_texture_coords: [x as f32 / i16::MAX as f32, y as f32 / i16::MAX as f32]
+        }
+    }
+}
+
+/// Unpack a vector packed as signed, normalized 11-bit x, 11-bit y, and 10-bit z components (from least significant
+/// to most significant bits).
+pub fn decompress_vector(vector: u32) -> [f32; 3] {
+    // Shift each component to the top of the integer, then shift back arithmetically to sign-extend it.
+    let x = ((vector << 21) as i32) >> 21;
+    let y = ((vector << 10) as i32) >> 21;
+    let z = (vector as i32) >> 22;
+    [x as f32 / 1023.0, y as f32 / 1023.0, z as f32 / 511.0]
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 pub struct ModelTriangle {
-    pub indices: [u16; 3]
+    /// Indices of the triangle's vertices.
+    ///
+    /// These are narrowed to 16-bit indices on upload if all indices of the mesh fit.
+    pub indices: [u32; 3]
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -25,4 +83,59 @@ pub struct VertexOffsets {
     pub index_count: u32,
     pub vertex_offset: i32,
     pub index_offset: u32,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompress_vector_unpacks_each_component() {
+        assert_eq!(decompress_vector(0), [0.0, 0.0, 0.0]);
+
+        // Largest positive value of each component
+        assert_eq!(decompress_vector(0x0000_03FF), [1.0, 0.0, 0.0]);
+        assert_eq!(decompress_vector(0x001F_F800), [0.0, 1.0, 0.0]);
+        assert_eq!(decompress_vector(0x7FC0_0000), [0.0, 0.0, 1.0]);
+
+        // Negative values are sign-extended
+        assert_eq!(decompress_vector(0x0000_0401), [-1.0, 0.0, 0.0]);
+        assert_eq!(decompress_vector(0x0020_0800), [0.0, -1.0, 0.0]);
+        assert_eq!(decompress_vector(0x8040_0000), [0.0, 0.0, -1.0]);
+        assert_eq!(decompress_vector(0xFFFF_FFFF), [-1.0 / 1023.0, -1.0 / 1023.0, -1.0 / 511.0]);
+
+        // The most negative value is slightly past -1
+        assert_eq!(decompress_vector(0x0000_0400), [-1024.0 / 1023.0, 0.0, 0.0]);
+        assert_eq!(decompress_vector(0x8000_0000), [0.0, 0.0, -512.0 / 511.0]);
+    }
+
+    #[test]
+    fn decompress_vector_does_not_mix_components() {
+        // x = 1023, y = -1023, z = 256
+        let packed = 0x3FF | (0x401 << 11) | (0x100 << 22);
+        assert_eq!(decompress_vector(packed), [1.0, -1.0, 256.0 / 511.0]);
+    }
+
+    #[test]
+    fn compressed_model_vertex_keeps_position_and_texture_coords() {
+        let vertex = ModelVertex::from(CompressedModelVertex {
+            position: [1.0, -2.0, 3.0],
+            normal: 0x7FC0_0000,
+            binormal: 0x001F_F800,
+            tangent: 0x0000_03FF,
+            texture_coords: [0.25, -0.5]
+        });
+        assert_eq!(vertex.position, [1.0, -2.0, 3.0]);
+        assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+        assert_eq!(vertex.binormal, [0.0, 1.0, 0.0]);
+        assert_eq!(vertex.tangent, [1.0, 0.0, 0.0]);
+        assert_eq!(vertex.texture_coords, [0.25, -0.5]);
+    }
+
+    #[test]
+    fn compressed_lightmap_vertex_normalizes_texture_coords() {
+        let decompress = |coords| LightmapVertex::from(CompressedLightmapVertex { normal: 0, lightmap_texture_coords: coords }).lightmap_texture_coords;
+        assert_eq!(decompress([0, i16::MAX]), [0.0, 1.0]);
+        assert_eq!(decompress([-i16::MAX, i16::MAX / 2]), [-1.0, (i16::MAX / 2) as f32 / i16::MAX as f32]);
+        assert_eq!(decompress([i16::MIN, 0]), [i16::MIN as f32 / i16::MAX as f32, 0.0]);
+    }
+}
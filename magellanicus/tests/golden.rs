@@ -0,0 +1,31 @@
+//! Golden image tests.
+//!
+//! These need the `testing` feature and a Vulkan device (a software implementation such as lavapipe works). Set
+//! `MAGELLANICUS_UPDATE_GOLDEN` to rewrite the reference images after an intended change.
+
+use std::path::PathBuf;
+use magellanicus::renderer::{Camera, LightingMode, Resolution, ShaderType};
+use magellanicus::testing::{basic_shader, check_golden_image, new_golden_renderer, quad_bsp, render_golden_frame, solid_color_bitmap, GoldenTolerance};
+
+fn reference_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(name)
+}
+
+#[test]
+fn fullbright_quad() {
+    let mut renderer = new_golden_renderer(Resolution { width: 64, height: 48 }).unwrap();
+    renderer.add_bitmap("quad.bitmap", solid_color_bitmap([0x40, 0x80, 0xC0, 0xFF], Resolution { width: 4, height: 4 })).unwrap();
+    renderer.add_shader("quad.shader", basic_shader(Some("quad.bitmap"), ShaderType::Environment)).unwrap();
+
+    // The quad is far bigger than the view, so it covers the whole frame in the bitmap's color.
+    renderer.add_bsp("quad.bsp", quad_bsp("quad.shader", 1.0, 100.0)).unwrap();
+    renderer.set_current_bsp(Some("quad.bsp")).unwrap();
+    renderer.set_camera_for_viewport(0, Camera {
+        lighting_mode: LightingMode::Fullbright,
+        fog: false,
+        ..Camera::default()
+    });
+
+    let frame = render_golden_frame(&mut renderer).unwrap();
+    check_golden_image(&frame, &reference_path("fullbright_quad.pam"), &GoldenTolerance::default()).unwrap();
+}